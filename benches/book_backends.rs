@@ -0,0 +1,137 @@
+// Replays the same generated order flows through each book backend and
+// prints the time taken. `dense` trades around the touch the way a futures
+// book does; `deep` builds a few very long levels and then cancels into and
+// sweeps through them.
+
+use std::time::{Duration, Instant};
+
+use trading_engine::corelib::ladder::LadderBook;
+use trading_engine::corelib::order::BuyOrSell;
+use trading_engine::corelib::orderbook::OrderBook;
+use trading_engine::corelib::replay::{BookBackend, BookCommand};
+use trading_engine::corelib::rng::SeededRng;
+
+const COMMANDS: usize = 200_000;
+const RUNS: u32 = 5;
+
+fn dense_flow(seed: u64) -> Vec<(u64, BookCommand)> {
+    let mut rng = SeededRng::new(seed);
+    let mut submitted = 0;
+    (0..COMMANDS as u64)
+        .map(|timestamp| {
+            let command = match rng.between(0, 9) {
+                0..=6 => {
+                    submitted += 1;
+                    BookCommand::Submit {
+                        side: side(&mut rng),
+                        price: 4_990.0 + rng.between(0, 80) as f64 * 0.25,
+                        quantity: rng.between(1, 50) as u32,
+                    }
+                }
+                7 if submitted > 0 => BookCommand::Cancel {
+                    order_id: rng.between(1, submitted),
+                },
+                _ => BookCommand::Match,
+            };
+            (timestamp, command)
+        })
+        .collect()
+}
+
+fn apply(book: &mut impl BookBackend, commands: &[(u64, BookCommand)]) {
+    for (timestamp, command) in commands {
+        match *command {
+            BookCommand::Submit {
+                side,
+                price,
+                quantity,
+            } => {
+                book.submit(side, price, quantity, *timestamp);
+            }
+            BookCommand::Cancel { order_id } => book.cancel(order_id, *timestamp),
+            BookCommand::Match => book.match_orders(*timestamp),
+        }
+    }
+}
+
+fn side(rng: &mut SeededRng) -> BuyOrSell {
+    if rng.between(0, 1) == 0 {
+        BuyOrSell::Buy
+    } else {
+        BuyOrSell::Sell
+    }
+}
+
+// Bids rest on 4_998.25..=4_999.0 and asks on 5_000.0..=5_000.75, so the
+// book never crosses on its own.
+fn resting(rng: &mut SeededRng) -> BookCommand {
+    let side = side(rng);
+    let ticks = rng.between(0, 3) as f64 * 0.25;
+    BookCommand::Submit {
+        side,
+        price: match side {
+            BuyOrSell::Buy => 4_999.0 - ticks,
+            BuyOrSell::Sell => 5_000.0 + ticks,
+        },
+        quantity: rng.between(1, 50) as u32,
+    }
+}
+
+fn deep_flow(seed: u64) -> Vec<(u64, BookCommand)> {
+    let mut rng = SeededRng::new(seed);
+    let mut commands: Vec<BookCommand> = (0..COMMANDS / 2).map(|_| resting(&mut rng)).collect();
+    let mut submitted = commands.len() as u64;
+    while commands.len() < COMMANDS {
+        match rng.between(0, 9) {
+            0..=3 => {
+                submitted += 1;
+                commands.push(resting(&mut rng));
+            }
+            4..=7 => commands.push(BookCommand::Cancel {
+                order_id: rng.between(1, submitted),
+            }),
+            _ => {
+                submitted += 1;
+                let side = side(&mut rng);
+                commands.push(BookCommand::Submit {
+                    side,
+                    price: match side {
+                        BuyOrSell::Buy => 5_000.75,
+                        BuyOrSell::Sell => 4_998.25,
+                    },
+                    quantity: 2_000,
+                });
+                commands.push(BookCommand::Match);
+            }
+        }
+    }
+    (0..).zip(commands).collect()
+}
+
+// Best of `RUNS` replays on fresh books.
+fn time<B: BookBackend>(name: &str, commands: &[(u64, BookCommand)], new: impl Fn() -> B) {
+    let mut best = Duration::MAX;
+    for _ in 0..RUNS {
+        let mut book = new();
+        let started = Instant::now();
+        apply(&mut book, commands);
+        std::hint::black_box(book.events().len());
+        best = best.min(started.elapsed());
+    }
+    println!(
+        "{:<10} {:>10.2?} ({:.0} ns/command)",
+        name,
+        best,
+        best.as_nanos() as f64 / commands.len() as f64
+    );
+}
+
+fn main() {
+    for (flow, commands) in [("dense", dense_flow(7)), ("deep", deep_flow(7))] {
+        println!("{} flow, {} commands", flow, commands.len());
+        time("orderbook", &commands, OrderBook::new);
+        time("ladder", &commands, || {
+            LadderBook::new(4_900.0, 0.25, 1_000).unwrap()
+        });
+    }
+}
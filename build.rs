@@ -0,0 +1,18 @@
+fn main() {
+    // Generate the C header of the `capi` feature into OUT_DIR, so builds
+    // never touch the checked-in copy under `include/`.
+    #[cfg(feature = "capi")]
+    {
+        println!("cargo:rerun-if-changed=src/capi.rs");
+        println!("cargo:rerun-if-changed=cbindgen.toml");
+        let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+        let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", crate_dir))
+            .expect("cbindgen.toml is readable");
+        cbindgen::generate_with_config(&crate_dir, config)
+            .expect("C API header generates")
+            .write_to_file(format!(
+                "{}/trading_engine.h",
+                std::env::var("OUT_DIR").unwrap()
+            ));
+    }
+}
@@ -0,0 +1,125 @@
+#ifndef TRADING_ENGINE_H
+#define TRADING_ENGINE_H
+
+/* Generated by cbindgen from src/capi.rs; do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define MAX_PRICE 1e12
+
+typedef enum TeEventKind {
+  TE_EVENT_KIND_ADDED = 0,
+  TE_EVENT_KIND_FILLED,
+  TE_EVENT_KIND_CANCELLED,
+  TE_EVENT_KIND_RESCALED,
+  TE_EVENT_KIND_PRIORITY_FEE_SET,
+} TeEventKind;
+
+typedef enum TeSide {
+  TE_SIDE_BUY = 0,
+  TE_SIDE_SELL,
+} TeSide;
+
+typedef enum TeStatus {
+  TE_STATUS_OK = 0,
+  TE_STATUS_NULL_POINTER,
+  TE_STATUS_UNKNOWN_TICKER,
+  TE_STATUS_REJECTED,
+  TE_STATUS_NOT_FOUND,
+  TE_STATUS_EMPTY,
+  TE_STATUS_GAP,
+} TeStatus;
+
+typedef struct TeEngine TeEngine;
+
+typedef struct TeBookEvent {
+  uint64_t sequence;
+  enum TeEventKind kind;
+  uint64_t order_id;
+  enum TeSide side;
+  double price;
+  uint32_t quantity;
+  uint32_t remaining;
+  uint64_t timestamp;
+  double old_price;
+} TeBookEvent;
+
+typedef struct TeTrade {
+  uint64_t trade_id;
+  uint64_t buy_order_id;
+  uint64_t sell_order_id;
+  double price;
+  uint64_t quantity;
+  uint64_t timestamp;
+} TeTrade;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Engine on the system clock. Release it with `te_engine_free`.
+ */
+struct TeEngine *te_engine_new(void);
+
+/**
+ * # Safety
+ * `engine` must come from `te_engine_new` and not be used afterwards.
+ */
+void te_engine_free(struct TeEngine *engine);
+
+/**
+ * # Safety
+ * `engine` must be a live handle and `ticker` a NUL-terminated string.
+ */
+enum TeStatus te_list_token(struct TeEngine *engine, const char *ticker);
+
+/**
+ * # Safety
+ * `engine` must be a live handle, `ticker` a NUL-terminated string and
+ * `order_id` null or writable.
+ */
+enum TeStatus te_submit_order(struct TeEngine *engine,
+                              const char *ticker,
+                              enum TeSide side,
+                              double price,
+                              uint32_t quantity,
+                              uint64_t *order_id);
+
+/**
+ * # Safety
+ * `engine` must be a live handle and `ticker` a NUL-terminated string.
+ */
+enum TeStatus te_cancel_order(struct TeEngine *engine, const char *ticker, uint64_t order_id);
+
+/**
+ * # Safety
+ * `engine` must be a live handle and `fills` null or writable.
+ */
+enum TeStatus te_match_orders(struct TeEngine *engine, uintptr_t *fills);
+
+/**
+ * Next book event of the instrument after the handle's cursor.
+ *
+ * # Safety
+ * `engine` must be a live handle, `ticker` a NUL-terminated string and
+ * `event` writable.
+ */
+enum TeStatus te_poll_event(struct TeEngine *engine, const char *ticker, struct TeBookEvent *event);
+
+/**
+ * Next trade on any instrument after the handle's cursor.
+ *
+ * # Safety
+ * `engine` must be a live handle and `trade` writable.
+ */
+enum TeStatus te_poll_trade(struct TeEngine *engine, struct TeTrade *trade);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* TRADING_ENGINE_H */
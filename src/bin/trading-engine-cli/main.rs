@@ -0,0 +1,331 @@
+// Command-line tool for poking at a local engine: load and save snapshots,
+// submit orders, print depth ladders and pool reserves, replay WAL files.
+//
+//     trading-engine-cli load book.snap \; order ETH buy 100 5 \; depth ETH
+//     trading-engine-cli < script.txt
+
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use trading_engine::corelib::clock::SystemClock;
+use trading_engine::corelib::command::{Command, CommandResult};
+use trading_engine::corelib::wal::read_log;
+use trading_engine::prelude::*;
+
+#[cfg(feature = "tui")]
+mod tui;
+
+const USAGE: &str = "\
+usage: trading-engine-cli [COMMAND [; COMMAND]...]
+Runs the commands given as arguments, or one command per line from stdin.
+
+commands:
+  load FILE                           replace the engine with a snapshot
+  save FILE                           write the books and configuration
+  replay PATH                         recover from a WAL directory, or apply one segment
+  list TICKER                         open an order book
+  order TICKER buy|sell PRICE QTY [KEY=VALUE]...
+                                      submit a good-till-cancel order with optional tags
+  cancel TICKER ORDER [SEQ]           take a resting order off the book
+  replace TICKER ORDER PRICE QTY [SEQ]
+                                      cancel an order and enter a new one
+                                      SEQ refuses the request once the book is past it
+  match                               match every book
+  depth TICKER [LEVELS]               print the depth ladder
+  deposit WALLET TICKER AMOUNT TICKER AMOUNT
+                                      add liquidity to a pair pool
+  pools                               print pool reserves
+  tui TICKER                          live depth, trades and pools (tui feature)
+  help
+  quit";
+
+fn main() {
+    let mut engine = TradeEngine::new();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        for words in args.split(|arg| arg == ";") {
+            let words: Vec<&str> = words.iter().map(String::as_str).collect();
+            match run(&mut engine, &words) {
+                Ok(Some(output)) => print!("{}", output),
+                Ok(None) => return,
+                Err(err) => {
+                    eprintln!("error: {}", err);
+                    std::process::exit(1);
+                }
+            }
+        }
+        return;
+    }
+    let stdin = io::stdin();
+    for line in stdin.lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match run(&mut engine, &words) {
+            Ok(Some(output)) => print!("{}", output),
+            Ok(None) => return,
+            Err(err) => eprintln!("error: {}", err),
+        }
+        let _ = io::stdout().flush();
+    }
+}
+
+// Apply one command and return what to print; None on quit.
+fn run(engine: &mut TradeEngine, words: &[&str]) -> Result<Option<String>, String> {
+    let Some((name, args)) = words.split_first() else {
+        return Ok(Some(String::new()));
+    };
+    let output = match (*name, args) {
+        ("load", [path]) => {
+            *engine = TradeEngine::load_snapshot(path, Box::new(SystemClock))
+                .map_err(|err| format!("{:?}", err))?;
+            format!("loaded {} books\n", engine.order_books.len())
+        }
+        ("save", [path]) => {
+            engine
+                .save_snapshot(path)
+                .map_err(|err| format!("{:?}", err))?;
+            format!("saved {} books\n", engine.order_books.len())
+        }
+        ("replay", [path]) => replay(engine, Path::new(path))?,
+        ("list", [ticker]) => {
+            engine.execute(Command::ListToken {
+                ticker: parse_ticker(ticker)?,
+            });
+            String::new()
+        }
+        ("order", [ticker, side, price, quantity, tags @ ..]) => {
+            let command = Command::SubmitOrder {
+                ticker: parse_ticker(ticker)?,
+                side: parse_side(side)?,
+                price: parse(price)?,
+                quantity: parse(quantity)?,
+                time_in_force: TimeInForce::GoodTillCancel,
+                metadata: parse_metadata(tags)?,
+            };
+            match engine.execute(command) {
+                CommandResult::OrderAccepted { order_id } => format!("order {}\n", order_id),
+                result => return Err(format!("{:?}", result)),
+            }
+        }
+        ("cancel", [ticker, order_id, sequence @ ..]) if sequence.len() <= 1 => {
+            let command = Command::CancelOrder {
+                ticker: parse_ticker(ticker)?,
+                order_id: parse(order_id)?,
+                expected_sequence: sequence.first().map(|seq| parse(seq)).transpose()?,
+            };
+            match engine.execute(command) {
+                CommandResult::OrderCancelled {
+                    order_id,
+                    remaining,
+                } => format!("cancelled {} ({} left)\n", order_id, remaining),
+                result => return Err(format!("{:?}", result)),
+            }
+        }
+        ("replace", [ticker, order_id, price, quantity, sequence @ ..]) if sequence.len() <= 1 => {
+            let command = Command::ReplaceOrder {
+                ticker: parse_ticker(ticker)?,
+                order_id: parse(order_id)?,
+                price: parse(price)?,
+                quantity: parse(quantity)?,
+                expected_sequence: sequence.first().map(|seq| parse(seq)).transpose()?,
+            };
+            match engine.execute(command) {
+                CommandResult::OrderReplaced { new_order_id, .. } => {
+                    format!("order {}\n", new_order_id)
+                }
+                result => return Err(format!("{:?}", result)),
+            }
+        }
+        ("match", []) => {
+            let CommandResult::Matched { trades } = engine.execute(Command::MatchOrders) else {
+                unreachable!();
+            };
+            trades
+                .iter()
+                .map(|(buy, sell, price, quantity)| {
+                    format!(
+                        "trade {} @ {} (buy {} sell {})\n",
+                        quantity, price, buy, sell
+                    )
+                })
+                .collect()
+        }
+        ("depth", [ticker]) => depth(engine, &parse_ticker(ticker)?, usize::MAX)?,
+        ("depth", [ticker, levels]) => depth(engine, &parse_ticker(ticker)?, parse(levels)?)?,
+        ("deposit", [wallet, ticker_a, amount_a, ticker_b, amount_b]) => {
+            let pair = Pair::new(parse_ticker(ticker_a)?, parse_ticker(ticker_b)?);
+            let now = engine.now();
+            let minted = engine.amm_pools.entry(pair.clone()).or_default().deposit(
+                &Wallet::new(wallet.to_string()),
+                &pair,
+                parse(amount_a)?,
+                parse(amount_b)?,
+                u64::MAX,
+                now,
+            );
+            if minted == 0 {
+                return Err(String::from("deposit would mint no LP tokens"));
+            }
+            format!("minted {} LP tokens\n", minted)
+        }
+        ("pools", []) => pool_lines(engine, None)
+            .into_iter()
+            .map(|line| line + "\n")
+            .collect(),
+        #[cfg(feature = "tui")]
+        ("tui", [ticker]) => {
+            tui::run(engine, parse_ticker(ticker)?).map_err(|err| err.to_string())?;
+            String::new()
+        }
+        ("help", []) => format!("{}\n", USAGE),
+        ("quit", []) | ("exit", []) => return Ok(None),
+        _ => return Err(format!("cannot parse `{}`; try `help`", words.join(" "))),
+    };
+    Ok(Some(output))
+}
+
+// A directory is recovered as a whole log; a single segment file has its
+// records applied to the current engine, one line per record.
+fn replay(engine: &mut TradeEngine, path: &Path) -> Result<String, String> {
+    if path.is_dir() {
+        *engine = TradeEngine::recover(path, Box::new(SystemClock))
+            .map_err(|err| format!("{:?}", err))?;
+        return Ok(format!("recovered {} books\n", engine.order_books.len()));
+    }
+    let records = read_log(path).map_err(|err| format!("{:?}", err))?;
+    Ok(records
+        .into_iter()
+        .map(|record| {
+            let summary = format!(
+                "#{} @{} {:?}",
+                record.sequence, record.timestamp, record.command
+            );
+            format!("{} => {:?}\n", summary, engine.execute(record.command))
+        })
+        .collect())
+}
+
+fn depth(engine: &TradeEngine, ticker: &TokenTicker, levels: usize) -> Result<String, String> {
+    let book = engine
+        .order_books
+        .get(ticker)
+        .ok_or_else(|| format!("{} is not listed", ticker))?;
+    Ok(book
+        .depth_snapshot()
+        .ladder(levels)
+        .into_iter()
+        .map(|line| line + "\n")
+        .collect())
+}
+
+// Reserves of every pool, or only of the pairs holding `ticker`.
+fn pool_lines(engine: &TradeEngine, ticker: Option<&TokenTicker>) -> Vec<String> {
+    let mut lines: Vec<String> = engine
+        .amm_pools
+        .iter()
+        .map(|(pair, pool)| (pair, None, pool))
+        .chain(
+            engine
+                .tier_pools
+                .iter()
+                .map(|((pair, fee_bps), pool)| (pair, Some(*fee_bps), pool)),
+        )
+        .filter(|(pair, _, _)| {
+            ticker.is_none_or(|ticker| pair.ticker_a == *ticker || pair.ticker_b == *ticker)
+        })
+        .map(|(pair, fee_bps, pool)| {
+            let mut line = format!("{}/{}", pair.ticker_a, pair.ticker_b);
+            if let Some(fee_bps) = fee_bps {
+                line += &format!(" ({} bps)", fee_bps);
+            }
+            for ticker in [&pair.ticker_a, &pair.ticker_b] {
+                line += &format!(" {}={}", ticker, pool.reserve(ticker).unwrap_or(0));
+            }
+            line
+        })
+        .collect();
+    lines.sort();
+    lines
+}
+
+fn parse_ticker(name: &str) -> Result<TokenTicker, String> {
+    TokenTicker::from_name(name).ok_or_else(|| format!("unknown ticker `{}`", name))
+}
+
+fn parse_side(side: &str) -> Result<BuyOrSell, String> {
+    match side {
+        "buy" => Ok(BuyOrSell::Buy),
+        "sell" => Ok(BuyOrSell::Sell),
+        side => Err(format!("side must be buy or sell, not `{}`", side)),
+    }
+}
+
+// None without tags, so untagged orders stay untagged.
+fn parse_metadata(tags: &[&str]) -> Result<Option<OrderMetadata>, String> {
+    if tags.is_empty() {
+        return Ok(None);
+    }
+    let tags = tags
+        .iter()
+        .map(|tag| match tag.split_once('=') {
+            Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+            _ => Err(format!("tags are KEY=VALUE, not `{}`", tag)),
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    Ok(Some(tags.into()))
+}
+
+fn parse<T: std::str::FromStr>(word: &str) -> Result<T, String> {
+    word.parse().map_err(|_| format!("cannot parse `{}`", word))
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    fn run_line(engine: &mut TradeEngine, line: &str) -> String {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        run(engine, &words).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_orders_depth_and_pools() {
+        let mut engine = TradeEngine::new();
+        run_line(&mut engine, "list ETH");
+        assert_eq!(run_line(&mut engine, "order eth buy 100 5"), "order 1\n");
+        run_line(&mut engine, "order ETH sell 101.5 3");
+        assert_eq!(
+            run_line(&mut engine, "depth ETH"),
+            format!(
+                "{:>10} {:>14} {:>10}\n{:>10} {:>14} {:>10}\n{:>10} {:>14} {:>10}\n",
+                "BID", "PRICE", "ASK", "", 101.5, 3, 5, 100, ""
+            )
+        );
+        assert!(run(&mut engine, &["order", "ETH", "hold", "1", "1"]).is_err());
+        assert!(run(&mut engine, &["order", "ETH", "buy", "1", "1", "desk"]).is_err());
+        assert_eq!(
+            run_line(&mut engine, "order ETH buy 99 2 desk=mm client_id=c-7"),
+            "order 3\n"
+        );
+        let (_, order) = engine.order_books[&TokenTicker::ETH].find_order(3).unwrap();
+        assert_eq!(
+            order.metadata.as_deref(),
+            Some(
+                &[
+                    (String::from("desk"), String::from("mm")),
+                    (String::from("client_id"), String::from("c-7")),
+                ][..]
+            )
+        );
+
+        run_line(&mut engine, "deposit alice ETH 1000 USDT 2000");
+        assert_eq!(
+            run_line(&mut engine, "pools"),
+            "ETH/USDT ETH=1000 USDT=2000\n"
+        );
+        assert_eq!(run(&mut engine, &["quit"]), Ok(None));
+    }
+}
@@ -0,0 +1,232 @@
+// Live view of one instrument: depth ladder, recent trades and pool
+// reserves. The ladder follows the book's delta feed and the trades pane
+// the trade feed, the way a remote consumer would; a prompt at the bottom
+// runs CLI commands against the same engine.
+
+use std::collections::VecDeque;
+use std::io;
+use std::time::Duration;
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::Stylize;
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph};
+use ratatui::Frame;
+use trading_engine::corelib::engine::TradeEngine;
+use trading_engine::corelib::market_data::{FeedSubscriber, SyncStatus};
+use trading_engine::corelib::token::TokenTicker;
+use trading_engine::corelib::trade::Trade;
+
+use super::pool_lines;
+
+const RECENT_TRADES: usize = 50;
+
+struct Viewer {
+    ticker: TokenTicker,
+    subscriber: FeedSubscriber,
+    last_trade_id: u64,
+    trades: VecDeque<Trade>,
+    input: String,
+    status: String,
+}
+
+impl Viewer {
+    fn new(ticker: TokenTicker) -> Viewer {
+        Viewer {
+            ticker,
+            subscriber: FeedSubscriber::new(),
+            last_trade_id: 0,
+            trades: VecDeque::new(),
+            input: String::new(),
+            status: String::from("type a command, `quit` or Esc to leave"),
+        }
+    }
+
+    // Catch up with both feeds. Deltas are applied from the last sequence
+    // seen; when they are no longer retained, or a hole shows up, the
+    // ladder starts over from a snapshot.
+    fn poll(&mut self, engine: &TradeEngine) {
+        let deltas = match self.subscriber.status() {
+            SyncStatus::Live { sequence } => engine.book_deltas_since(&self.ticker, sequence),
+            _ => None,
+        };
+        let mut in_sync = deltas.is_some();
+        for (sequence, event) in deltas.unwrap_or_default() {
+            if !matches!(
+                self.subscriber.on_delta(sequence, event),
+                SyncStatus::Live { .. }
+            ) {
+                in_sync = false;
+                break;
+            }
+        }
+        if !in_sync {
+            if let Some(snapshot) = engine.book_snapshot(&self.ticker) {
+                self.subscriber.on_snapshot(snapshot);
+            }
+        }
+
+        for trade in engine.trade_feed.trades_for(&self.ticker) {
+            if trade.id > self.last_trade_id {
+                self.last_trade_id = trade.id;
+                self.trades.push_front(trade.clone());
+            }
+        }
+        self.trades.truncate(RECENT_TRADES);
+    }
+
+    fn draw(&self, frame: &mut Frame, engine: &TradeEngine) {
+        let [panes, prompt] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(3)]).areas(frame.area());
+        let [depth, trades, pools] = Layout::horizontal([
+            Constraint::Length(40),
+            Constraint::Min(30),
+            Constraint::Min(30),
+        ])
+        .areas(panes);
+        let name = self.ticker.to_string();
+
+        // header and borders take three rows
+        let levels = (depth.height.saturating_sub(3) / 2) as usize;
+        let ladder_lines: Vec<Line> = match self.subscriber.depth() {
+            Some(snapshot) => snapshot
+                .ladder(levels)
+                .into_iter()
+                .enumerate()
+                .map(|(index, line)| {
+                    let asks = snapshot.asks.len().min(levels);
+                    match index {
+                        0 => Line::from(line).bold(),
+                        index if index <= asks => Line::from(line).red(),
+                        _ => Line::from(line).green(),
+                    }
+                })
+                .collect(),
+            None => vec![Line::from(format!("{} is not listed", name))],
+        };
+        frame.render_widget(
+            Paragraph::new(ladder_lines)
+                .block(Block::bordered().title(format!(" {} depth ", name))),
+            depth,
+        );
+
+        let trade_lines: Vec<Line> = self
+            .trades
+            .iter()
+            .map(|trade| {
+                Line::from(format!(
+                    "#{:<6} {:>8} @ {:<12} {:?}",
+                    trade.id, trade.quantity, trade.price, trade.kind
+                ))
+            })
+            .collect();
+        frame.render_widget(
+            Paragraph::new(trade_lines).block(Block::bordered().title(" trades ")),
+            trades,
+        );
+
+        let pool_lines: Vec<Line> = pool_lines(engine, Some(&self.ticker))
+            .into_iter()
+            .map(Line::from)
+            .collect();
+        frame.render_widget(
+            Paragraph::new(pool_lines).block(Block::bordered().title(" pools ")),
+            pools,
+        );
+
+        frame.render_widget(
+            Paragraph::new(format!("> {}", self.input))
+                .block(Block::bordered().title(format!(" {} ", self.status))),
+            prompt,
+        );
+    }
+}
+
+// Run the viewer until the user quits.
+pub fn run(engine: &mut TradeEngine, ticker: TokenTicker) -> io::Result<()> {
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, engine, ticker);
+    ratatui::restore();
+    result
+}
+
+fn event_loop(
+    terminal: &mut ratatui::DefaultTerminal,
+    engine: &mut TradeEngine,
+    ticker: TokenTicker,
+) -> io::Result<()> {
+    let mut viewer = Viewer::new(ticker);
+    loop {
+        viewer.poll(engine);
+        terminal.draw(|frame| viewer.draw(frame, engine))?;
+        if !event::poll(Duration::from_millis(250))? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Esc => return Ok(()),
+            KeyCode::Backspace => {
+                viewer.input.pop();
+            }
+            KeyCode::Char(c) => viewer.input.push(c),
+            KeyCode::Enter => {
+                let line = std::mem::take(&mut viewer.input);
+                let words: Vec<&str> = line.split_whitespace().collect();
+                viewer.status = match words.first() {
+                    Some(&"tui") => String::from("already viewing"),
+                    _ => match super::run(engine, &words) {
+                        Ok(None) => return Ok(()),
+                        Ok(Some(output)) => output.lines().last().unwrap_or("ok").to_string(),
+                        Err(err) => format!("error: {}", err),
+                    },
+                };
+                // a loaded or recovered engine restarts both feeds
+                if matches!(words.first(), Some(&"load") | Some(&"replay")) {
+                    viewer = Viewer {
+                        status: std::mem::take(&mut viewer.status),
+                        ..Viewer::new(viewer.ticker.clone())
+                    };
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use trading_engine::corelib::order::BuyOrSell;
+
+    #[test]
+    fn test_viewer_follows_feeds() {
+        let mut engine = TradeEngine::new();
+        engine.list_new_token(TokenTicker::ETH);
+        let mut viewer = Viewer::new(TokenTicker::ETH);
+        viewer.poll(&engine);
+        assert_eq!(viewer.subscriber.status(), SyncStatus::Live { sequence: 0 });
+
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Buy, 100.0, 5)
+            .unwrap();
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Sell, 100.0, 2)
+            .unwrap();
+        engine.match_orders();
+        viewer.poll(&engine);
+        assert_eq!(
+            viewer.subscriber.depth(),
+            Some(&engine.order_books[&TokenTicker::ETH].depth_snapshot())
+        );
+        assert_eq!(viewer.trades.len(), 1);
+        viewer.poll(&engine);
+        assert_eq!(viewer.trades.len(), 1);
+    }
+}
@@ -0,0 +1,435 @@
+// C API for embedding the engine. The engine sits behind an opaque handle
+// from `te_engine_new`; calls report a `TeStatus` and write results through
+// out pointers. Book events and trades are pulled with the poll functions,
+// each handle keeping its own cursors. Tickers are NUL-terminated names
+// such as "ETH". The build script generates the header into `OUT_DIR`;
+// the copy checked in under `include/` is refreshed with cbindgen by hand.
+
+use std::collections::HashMap;
+use std::ffi::{c_char, CStr};
+
+use crate::corelib::engine::{OrderError, TradeEngine};
+use crate::corelib::events::BookEvent;
+use crate::corelib::order::BuyOrSell;
+use crate::corelib::token::TokenTicker;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TeStatus {
+    Ok = 0,
+    NullPointer,
+    UnknownTicker,
+    // The order was refused by the engine.
+    Rejected,
+    NotFound,
+    // Nothing new to poll.
+    Empty,
+    // Events were dropped before they were polled; the cursor moved to the
+    // book's current sequence and the consumer should re-read the depth.
+    Gap,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TeSide {
+    Buy = 0,
+    Sell,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TeEventKind {
+    Added = 0,
+    Filled,
+    Cancelled,
+    Rescaled,
+    PriorityFeeSet,
+}
+
+// One book event. `quantity` is the added, filled or cancelled quantity,
+// the quantity before a rescale, or the resting quantity of an order given
+// a priority fee; `remaining` what is left on the order. `old_price` is the
+// price before a rescale and `price` otherwise.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TeBookEvent {
+    pub sequence: u64,
+    pub kind: TeEventKind,
+    pub order_id: u64,
+    pub side: TeSide,
+    pub price: f64,
+    pub quantity: u32,
+    pub remaining: u32,
+    pub timestamp: u64,
+    pub old_price: f64,
+}
+
+// Order ids are 0 for trades that did not cross a resting order.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TeTrade {
+    pub trade_id: u64,
+    pub buy_order_id: u64,
+    pub sell_order_id: u64,
+    pub price: f64,
+    pub quantity: u64,
+    pub timestamp: u64,
+}
+
+pub struct TeEngine {
+    engine: TradeEngine,
+    // Last book event sequence polled per instrument.
+    event_cursors: HashMap<TokenTicker, u64>,
+    last_trade_id: u64,
+}
+
+impl From<BuyOrSell> for TeSide {
+    fn from(side: BuyOrSell) -> Self {
+        match side {
+            BuyOrSell::Buy => TeSide::Buy,
+            BuyOrSell::Sell => TeSide::Sell,
+        }
+    }
+}
+
+impl From<TeSide> for BuyOrSell {
+    fn from(side: TeSide) -> Self {
+        match side {
+            TeSide::Buy => BuyOrSell::Buy,
+            TeSide::Sell => BuyOrSell::Sell,
+        }
+    }
+}
+
+impl TeBookEvent {
+    fn new(sequence: u64, event: &BookEvent) -> TeBookEvent {
+        let mut old_price = None;
+        let (kind, order_id, side, price, quantity, remaining, timestamp) = match *event {
+            BookEvent::OrderAdded {
+                order_id,
+                side,
+                price,
+                quantity,
+                timestamp,
+                ..
+            } => (
+                TeEventKind::Added,
+                order_id,
+                side,
+                price,
+                quantity,
+                quantity,
+                timestamp,
+            ),
+            BookEvent::OrderFilled {
+                order_id,
+                side,
+                price,
+                filled,
+                remaining,
+                timestamp,
+                ..
+            } => (
+                TeEventKind::Filled,
+                order_id,
+                side,
+                price,
+                filled,
+                remaining,
+                timestamp,
+            ),
+            BookEvent::OrderCancelled {
+                order_id,
+                side,
+                price,
+                remaining,
+                timestamp,
+                ..
+            } => (
+                TeEventKind::Cancelled,
+                order_id,
+                side,
+                price,
+                remaining,
+                0,
+                timestamp,
+            ),
+            BookEvent::OrderRescaled {
+                order_id,
+                side,
+                old_price: previous,
+                price,
+                old_quantity,
+                quantity,
+                timestamp,
+                ..
+            } => {
+                old_price = Some(previous);
+                (
+                    TeEventKind::Rescaled,
+                    order_id,
+                    side,
+                    price,
+                    old_quantity,
+                    quantity,
+                    timestamp,
+                )
+            }
+            BookEvent::PriorityFeeSet {
+                order_id,
+                side,
+                price,
+                quantity,
+                timestamp,
+                ..
+            } => (
+                TeEventKind::PriorityFeeSet,
+                order_id,
+                side,
+                price,
+                quantity,
+                quantity,
+                timestamp,
+            ),
+        };
+        TeBookEvent {
+            sequence,
+            kind,
+            order_id,
+            side: side.into(),
+            price,
+            quantity,
+            remaining,
+            timestamp,
+            old_price: old_price.unwrap_or(price),
+        }
+    }
+}
+
+// The handle and ticker behind raw pointers, or the status to return.
+unsafe fn resolve<'a>(
+    engine: *mut TeEngine,
+    ticker: *const c_char,
+) -> Result<(&'a mut TeEngine, TokenTicker), TeStatus> {
+    if engine.is_null() || ticker.is_null() {
+        return Err(TeStatus::NullPointer);
+    }
+    let name = CStr::from_ptr(ticker)
+        .to_str()
+        .map_err(|_| TeStatus::UnknownTicker)?;
+    let ticker = TokenTicker::from_name(name).ok_or(TeStatus::UnknownTicker)?;
+    Ok((&mut *engine, ticker))
+}
+
+macro_rules! try_status {
+    ($result:expr) => {
+        match $result {
+            Ok(value) => value,
+            Err(status) => return status,
+        }
+    };
+}
+
+/// Engine on the system clock. Release it with `te_engine_free`.
+#[no_mangle]
+pub extern "C" fn te_engine_new() -> *mut TeEngine {
+    Box::into_raw(Box::new(TeEngine {
+        engine: TradeEngine::new(),
+        event_cursors: HashMap::new(),
+        last_trade_id: 0,
+    }))
+}
+
+/// # Safety
+/// `engine` must come from `te_engine_new` and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn te_engine_free(engine: *mut TeEngine) {
+    if !engine.is_null() {
+        drop(Box::from_raw(engine));
+    }
+}
+
+/// # Safety
+/// `engine` must be a live handle and `ticker` a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn te_list_token(engine: *mut TeEngine, ticker: *const c_char) -> TeStatus {
+    let (handle, ticker) = try_status!(resolve(engine, ticker));
+    handle.engine.list_new_token(ticker);
+    TeStatus::Ok
+}
+
+/// # Safety
+/// `engine` must be a live handle, `ticker` a NUL-terminated string and
+/// `order_id` null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn te_submit_order(
+    engine: *mut TeEngine,
+    ticker: *const c_char,
+    side: TeSide,
+    price: f64,
+    quantity: u32,
+    order_id: *mut u64,
+) -> TeStatus {
+    let (handle, ticker) = try_status!(resolve(engine, ticker));
+    match handle
+        .engine
+        .submit_order(&ticker, side.into(), price, quantity)
+    {
+        Ok(id) => {
+            if !order_id.is_null() {
+                *order_id = id;
+            }
+            TeStatus::Ok
+        }
+        Err(OrderError::UnknownTicker) => TeStatus::UnknownTicker,
+        Err(_) => TeStatus::Rejected,
+    }
+}
+
+/// # Safety
+/// `engine` must be a live handle and `ticker` a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn te_cancel_order(
+    engine: *mut TeEngine,
+    ticker: *const c_char,
+    order_id: u64,
+) -> TeStatus {
+    let (handle, ticker) = try_status!(resolve(engine, ticker));
+    let now = handle.engine.now();
+    let Some(orderbook) = handle.engine.order_books.get_mut(&ticker) else {
+        return TeStatus::UnknownTicker;
+    };
+    match orderbook.cancel_order(order_id, now) {
+        Some(_) => TeStatus::Ok,
+        None => TeStatus::NotFound,
+    }
+}
+
+/// # Safety
+/// `engine` must be a live handle and `fills` null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn te_match_orders(engine: *mut TeEngine, fills: *mut usize) -> TeStatus {
+    if engine.is_null() {
+        return TeStatus::NullPointer;
+    }
+    let matched = (*engine).engine.match_orders().len();
+    if !fills.is_null() {
+        *fills = matched;
+    }
+    TeStatus::Ok
+}
+
+/// Next book event of the instrument after the handle's cursor.
+///
+/// # Safety
+/// `engine` must be a live handle, `ticker` a NUL-terminated string and
+/// `event` writable.
+#[no_mangle]
+pub unsafe extern "C" fn te_poll_event(
+    engine: *mut TeEngine,
+    ticker: *const c_char,
+    event: *mut TeBookEvent,
+) -> TeStatus {
+    if event.is_null() {
+        return TeStatus::NullPointer;
+    }
+    let (handle, ticker) = try_status!(resolve(engine, ticker));
+    let Some(orderbook) = handle.engine.order_books.get(&ticker) else {
+        return TeStatus::UnknownTicker;
+    };
+    let cursor = handle.event_cursors.entry(ticker).or_insert(0);
+    match orderbook.event_after(*cursor) {
+        None => {
+            *cursor = orderbook.sequence();
+            TeStatus::Gap
+        }
+        Some(Some((sequence, next))) => {
+            *event = TeBookEvent::new(sequence, next);
+            *cursor = sequence;
+            TeStatus::Ok
+        }
+        Some(None) => TeStatus::Empty,
+    }
+}
+
+/// Next trade on any instrument after the handle's cursor.
+///
+/// # Safety
+/// `engine` must be a live handle and `trade` writable.
+#[no_mangle]
+pub unsafe extern "C" fn te_poll_trade(engine: *mut TeEngine, trade: *mut TeTrade) -> TeStatus {
+    if engine.is_null() || trade.is_null() {
+        return TeStatus::NullPointer;
+    }
+    let handle = &mut *engine;
+    let Some(next) = handle
+        .engine
+        .trade_feed
+        .trades_after(handle.last_trade_id)
+        .first()
+    else {
+        return TeStatus::Empty;
+    };
+    handle.last_trade_id = next.id;
+    *trade = TeTrade {
+        trade_id: next.id,
+        buy_order_id: next.buy_order_id.unwrap_or(0),
+        sell_order_id: next.sell_order_id.unwrap_or(0),
+        price: next.price,
+        quantity: next.quantity,
+        timestamp: next.timestamp,
+    };
+    TeStatus::Ok
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_submit_match_and_poll() {
+        let eth = c"ETH".as_ptr();
+        unsafe {
+            let engine = te_engine_new();
+            assert_eq!(te_list_token(engine, eth), TeStatus::Ok);
+            let mut buy = 0;
+            let mut sell = 0;
+            te_submit_order(engine, eth, TeSide::Buy, 100.0, 5, &mut buy);
+            te_submit_order(engine, eth, TeSide::Sell, 100.0, 2, &mut sell);
+            assert_eq!(
+                te_submit_order(engine, c"XYZ".as_ptr(), TeSide::Buy, 1.0, 1, &mut buy),
+                TeStatus::UnknownTicker
+            );
+            let mut fills = 0;
+            te_match_orders(engine, &mut fills);
+            assert_eq!(fills, 1);
+            assert_eq!(te_cancel_order(engine, eth, buy), TeStatus::Ok);
+            assert_eq!(te_cancel_order(engine, eth, buy), TeStatus::NotFound);
+
+            let mut event = std::mem::zeroed::<TeBookEvent>();
+            let mut kinds = Vec::new();
+            while te_poll_event(engine, eth, &mut event) == TeStatus::Ok {
+                kinds.push((event.sequence, event.kind, event.order_id));
+            }
+            assert_eq!(
+                kinds,
+                vec![
+                    (1, TeEventKind::Added, buy),
+                    (2, TeEventKind::Added, sell),
+                    (3, TeEventKind::Filled, buy),
+                    (4, TeEventKind::Filled, sell),
+                    (5, TeEventKind::Cancelled, buy),
+                ]
+            );
+            assert_eq!(event.remaining, 0);
+            assert_eq!(event.quantity, 3);
+
+            let mut trade = std::mem::zeroed::<TeTrade>();
+            assert_eq!(te_poll_trade(engine, &mut trade), TeStatus::Ok);
+            assert_eq!((trade.buy_order_id, trade.quantity), (buy, 2));
+            assert_eq!(te_poll_trade(engine, &mut trade), TeStatus::Empty);
+            te_engine_free(engine);
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use super::margin::Position;
+use super::order::Wallet;
+use super::token::TokenTicker;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AdlEvent {
+    // `reduced` units of the wallet's position were closed against a
+    // liquidation at `price`.
+    Deleveraged {
+        wallet: Wallet,
+        underlying: TokenTicker,
+        reduced: i64,
+        remaining: i64,
+        price: f64,
+        realized_pnl: f64,
+        timestamp: u64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdlCandidate {
+    // Index into the ranked positions.
+    pub index: usize,
+    pub wallet: Wallet,
+    pub score: f64,
+}
+
+// Auto-deleveraging is the last resort once a liquidation could be absorbed
+// neither by the book nor by the insurance fund: profitable positions on the
+// other side are closed against it at the bankruptcy price. The most
+// profitable and most leveraged positions go first; the score is the profit
+// ratio times the effective leverage.
+pub fn adl_score(position: &Position) -> f64 {
+    let cost = position.quantity.unsigned_abs() as f64 * position.entry_price;
+    if cost == 0.0 {
+        return 0.0;
+    }
+    position.unrealized_pnl() / cost * position.effective_leverage()
+}
+
+// Positions on `underlying` opposing a liquidated position of
+// `liquidated_quantity`, in ADL order. Losing positions are never selected.
+pub fn adl_ranking(
+    positions: &[Position],
+    underlying: &TokenTicker,
+    liquidated_quantity: i64,
+) -> Vec<AdlCandidate> {
+    let mut ranking: Vec<AdlCandidate> = positions
+        .iter()
+        .enumerate()
+        .filter(|(_, position)| {
+            &position.underlying == underlying
+                && position.quantity.signum() == -liquidated_quantity.signum()
+                && position.unrealized_pnl() > 0.0
+        })
+        .map(|(index, position)| AdlCandidate {
+            index,
+            wallet: position.wallet.clone(),
+            score: adl_score(position),
+        })
+        .collect();
+    ranking.sort_by(|a, b| b.score.total_cmp(&a.score));
+    ranking
+}
+
+// Close up to `liquidated_quantity` (signed as the liquidated position) of
+// opposing positions at `bankruptcy_price`, in ranking order. Returns one
+// event per affected wallet; whatever could not be matched is left out.
+pub fn auto_deleverage(
+    positions: &mut [Position],
+    underlying: &TokenTicker,
+    liquidated_quantity: i64,
+    bankruptcy_price: f64,
+    timestamp: u64,
+) -> Vec<AdlEvent> {
+    let mut left = liquidated_quantity.unsigned_abs() as i64;
+    let mut events = Vec::new();
+    for candidate in adl_ranking(positions, underlying, liquidated_quantity) {
+        if left == 0 {
+            break;
+        }
+        let position = &mut positions[candidate.index];
+        let reduce = position.quantity.abs().min(left);
+        let reduced = reduce * position.quantity.signum();
+        position.quantity -= reduced;
+        left -= reduce;
+        events.push(AdlEvent::Deleveraged {
+            wallet: position.wallet.clone(),
+            underlying: underlying.clone(),
+            reduced,
+            remaining: position.quantity,
+            price: bankruptcy_price,
+            realized_pnl: reduced as f64 * (bankruptcy_price - position.entry_price),
+            timestamp,
+        });
+    }
+    events
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::margin::InstrumentKind;
+
+    fn short(name: &str, quantity: i64, entry_price: f64, collateral: u64) -> Position {
+        Position {
+            wallet: Wallet::new(String::from(name)),
+            underlying: TokenTicker::BTC,
+            kind: InstrumentKind::Perpetual,
+            quantity: -quantity,
+            entry_price,
+            mark_price: 40_000.0,
+            collateral,
+        }
+    }
+
+    #[test]
+    fn test_ranks_by_profit_and_leverage() {
+        let positions = vec![
+            short("adlcautious", 1, 50_000.0, 40_000),
+            short("adlleveraged", 1, 50_000.0, 1_000),
+            // underwater shorts are not deleveraged
+            short("adllosing", 1, 30_000.0, 5_000),
+        ];
+        let ranking = adl_ranking(&positions, &TokenTicker::BTC, 5);
+        let order: Vec<usize> = ranking.iter().map(|candidate| candidate.index).collect();
+        assert_eq!(order, vec![1, 0]);
+        assert!(adl_ranking(&positions, &TokenTicker::BTC, -5).is_empty());
+        assert!(adl_ranking(&positions, &TokenTicker::ETH, 5).is_empty());
+    }
+
+    #[test]
+    fn test_deleverage_reduces_in_order() {
+        let mut positions = vec![
+            short("adlfirst", 3, 50_000.0, 1_000),
+            short("adlsecond", 5, 45_000.0, 50_000),
+        ];
+        let events = auto_deleverage(&mut positions, &TokenTicker::BTC, 4, 41_000.0, 7);
+        assert_eq!(positions[0].quantity, 0);
+        assert_eq!(positions[1].quantity, -4);
+        assert_eq!(
+            events[1],
+            AdlEvent::Deleveraged {
+                wallet: Wallet::new(String::from("adlsecond")),
+                underlying: TokenTicker::BTC,
+                reduced: -1,
+                remaining: -4,
+                price: 41_000.0,
+                realized_pnl: 4_000.0,
+                timestamp: 7,
+            }
+        );
+    }
+}
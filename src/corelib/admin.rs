@@ -0,0 +1,141 @@
+use super::audit::AuditAction;
+use super::engine::TradeEngine;
+use super::fees::FeeSchedule;
+use super::token::TokenTicker;
+
+// Runtime configuration changes. They travel through the command pipeline
+// like orders, so they are sequenced, written to the WAL and audited.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdminCommand {
+    SetFeeSchedule {
+        maker_bps: u64,
+        taker_bps: u64,
+    },
+    // None removes the limit.
+    SetOpenNotionalLimit {
+        ticker: TokenTicker,
+        limit: Option<u64>,
+    },
+    // A halted instrument neither accepts orders nor matches.
+    HaltInstrument {
+        ticker: TokenTicker,
+    },
+    ResumeInstrument {
+        ticker: TokenTicker,
+    },
+    // Start a new WAL segment; only meaningful through `execute_logged`.
+    RotateLog,
+}
+
+impl TradeEngine {
+    pub fn is_halted(&self, ticker: &TokenTicker) -> bool {
+        self.halted_instruments.contains(ticker)
+    }
+
+    pub(crate) fn apply_admin(&mut self, command: AdminCommand) {
+        self.configure(&command);
+        let now = self.now();
+        self.audit_log
+            .record(now, AuditAction::AdminCommandApplied { command });
+    }
+
+    // Apply the change without auditing it, e.g. when restoring a snapshot.
+    pub(crate) fn configure(&mut self, command: &AdminCommand) {
+        match command {
+            AdminCommand::SetFeeSchedule {
+                maker_bps,
+                taker_bps,
+            } => {
+                self.fees.schedule = FeeSchedule {
+                    maker_bps: *maker_bps,
+                    taker_bps: *taker_bps,
+                };
+            }
+            AdminCommand::SetOpenNotionalLimit { ticker, limit } => match limit {
+                Some(limit) => self.set_open_notional_limit(ticker.clone(), *limit),
+                None => {
+                    self.open_notional_limits.remove(ticker);
+                }
+            },
+            AdminCommand::HaltInstrument { ticker } => {
+                self.halted_instruments.insert(ticker.clone());
+            }
+            AdminCommand::ResumeInstrument { ticker } => {
+                self.halted_instruments.remove(ticker);
+            }
+            AdminCommand::RotateLog => {}
+        }
+    }
+
+    // Commands that recreate the current configuration.
+    pub(crate) fn configuration(&self) -> Vec<AdminCommand> {
+        let mut commands = vec![AdminCommand::SetFeeSchedule {
+            maker_bps: self.fees.schedule.maker_bps,
+            taker_bps: self.fees.schedule.taker_bps,
+        }];
+        let mut limits: Vec<(&TokenTicker, &u64)> = self.open_notional_limits.iter().collect();
+        limits.sort();
+        commands.extend(limits.into_iter().map(|(ticker, limit)| {
+            AdminCommand::SetOpenNotionalLimit {
+                ticker: ticker.clone(),
+                limit: Some(*limit),
+            }
+        }));
+        let mut halted: Vec<&TokenTicker> = self.halted_instruments.iter().collect();
+        halted.sort();
+        commands.extend(
+            halted
+                .into_iter()
+                .map(|ticker| AdminCommand::HaltInstrument {
+                    ticker: ticker.clone(),
+                }),
+        );
+        commands
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::command::{Command, CommandResult};
+    use crate::corelib::engine::OrderError;
+    use crate::corelib::order::BuyOrSell;
+
+    #[test]
+    fn test_admin_commands_through_pipeline() {
+        let mut engine = TradeEngine::new();
+        engine.list_new_token(TokenTicker::ETH);
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Buy, 10.0, 1)
+            .unwrap();
+        let halt = AdminCommand::HaltInstrument {
+            ticker: TokenTicker::ETH,
+        };
+        assert_eq!(
+            engine.execute(Command::Admin(halt.clone())),
+            CommandResult::AdminApplied
+        );
+        assert_eq!(
+            engine.submit_order(&TokenTicker::ETH, BuyOrSell::Sell, 10.0, 1),
+            Err(OrderError::InstrumentHalted)
+        );
+        engine.execute(Command::Admin(AdminCommand::ResumeInstrument {
+            ticker: TokenTicker::ETH,
+        }));
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Sell, 10.0, 1)
+            .unwrap();
+
+        engine.execute(Command::Admin(AdminCommand::SetFeeSchedule {
+            maker_bps: 1,
+            taker_bps: 5,
+        }));
+        assert_eq!(engine.fees.taker_fee(10_000), 5);
+        assert_eq!(
+            engine.audit_log.records()[0].action,
+            AuditAction::AdminCommandApplied { command: halt }
+        );
+        assert_eq!(engine.audit_log.records().len(), 3);
+    }
+}
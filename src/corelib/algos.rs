@@ -0,0 +1,369 @@
+use std::collections::BTreeMap;
+
+use super::engine::{OrderError, TradeEngine};
+use super::order::{BuyOrSell, TimeInForce, Wallet};
+use super::token::TokenTicker;
+
+const BPS_DENOMINATOR: u64 = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlgoStrategy {
+    // Equal slices at regular intervals between start and end, the first
+    // at start.
+    Twap {
+        slices: u32,
+        start_millis: u64,
+        end_millis: u64,
+    },
+    // Child orders sized at `participation_bps` of the volume other
+    // participants traded since the previous child; the remainder is placed
+    // at `end_millis`.
+    Vwap {
+        participation_bps: u64,
+        end_millis: u64,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlgoStatus {
+    Working,
+    Completed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParentOrder {
+    pub wallet: Wallet,
+    pub ticker: TokenTicker,
+    pub side: BuyOrSell,
+    pub quantity: u32,
+    // Every child order is a limit order at this price.
+    pub limit_price: f64,
+    pub strategy: AlgoStrategy,
+    pub status: AlgoStatus,
+    // Child order ids with their quantities, oldest first.
+    pub children: Vec<(u64, u32)>,
+    // Trades up to this id were already counted towards VWAP volume.
+    last_trade_seen: u64,
+}
+
+impl ParentOrder {
+    pub fn placed(&self) -> u32 {
+        self.children.iter().map(|(_, quantity)| quantity).sum()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AlgoProgress {
+    pub quantity: u32,
+    pub placed: u32,
+    pub filled: u32,
+    pub status: AlgoStatus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChildOrder {
+    pub parent: u64,
+    pub order_id: u64,
+    pub quantity: u32,
+}
+
+#[derive(Default)]
+pub struct ExecutionAlgos {
+    parents: BTreeMap<u64, ParentOrder>,
+    next_id: u64,
+}
+
+impl ExecutionAlgos {
+    pub fn new() -> ExecutionAlgos {
+        ExecutionAlgos::default()
+    }
+
+    pub fn get(&self, id: u64) -> Option<&ParentOrder> {
+        self.parents.get(&id)
+    }
+}
+
+impl TradeEngine {
+    pub fn submit_algo_order(
+        &mut self,
+        wallet: &Wallet,
+        ticker: &TokenTicker,
+        side: BuyOrSell,
+        quantity: u32,
+        limit_price: f64,
+        strategy: AlgoStrategy,
+    ) -> Result<u64, OrderError> {
+        if !self.order_books.contains_key(ticker) {
+            return Err(OrderError::UnknownTicker);
+        }
+        let last_trade_seen = self.trade_feed.trades().last().map_or(0, |trade| trade.id);
+        let algos = &mut self.algos;
+        algos.next_id += 1;
+        algos.parents.insert(
+            algos.next_id,
+            ParentOrder {
+                wallet: wallet.clone(),
+                ticker: ticker.clone(),
+                side,
+                quantity,
+                limit_price,
+                strategy,
+                status: AlgoStatus::Working,
+                children: Vec::new(),
+                last_trade_seen,
+            },
+        );
+        Ok(algos.next_id)
+    }
+
+    // Quantity of the parent's child orders that traded.
+    fn algo_filled(&self, parent: &ParentOrder) -> u32 {
+        self.trade_feed
+            .trades_for(&parent.ticker)
+            .filter(|trade| {
+                let order_id = match parent.side {
+                    BuyOrSell::Buy => trade.buy_order_id,
+                    BuyOrSell::Sell => trade.sell_order_id,
+                };
+                order_id.is_some_and(|id| parent.children.iter().any(|(child, _)| *child == id))
+            })
+            .map(|trade| trade.quantity as u32)
+            .sum()
+    }
+
+    pub fn algo_progress(&self, id: u64) -> Option<AlgoProgress> {
+        let parent = self.algos.parents.get(&id)?;
+        Some(AlgoProgress {
+            quantity: parent.quantity,
+            placed: parent.placed(),
+            filled: self.algo_filled(parent),
+            status: parent.status,
+        })
+    }
+
+    // Place the child orders that are due. Children rejected by order entry
+    // are retried on the next run.
+    pub fn run_algos(&mut self) -> Vec<ChildOrder> {
+        let now = self.now();
+        let working: Vec<u64> = self
+            .algos
+            .parents
+            .iter()
+            .filter(|(_, parent)| parent.status == AlgoStatus::Working)
+            .map(|(id, _)| *id)
+            .collect();
+
+        let mut placed = Vec::new();
+        for id in working {
+            let parent = &self.algos.parents[&id];
+            if self.algo_filled(parent) >= parent.quantity {
+                self.algos.parents.get_mut(&id).unwrap().status = AlgoStatus::Completed;
+                continue;
+            }
+            let (target, last_trade_seen) = self.algo_target(parent, now);
+            let quantity = target.min(parent.quantity).saturating_sub(parent.placed());
+            let parent = self.algos.parents.get_mut(&id).unwrap();
+            parent.last_trade_seen = last_trade_seen;
+            if quantity == 0 {
+                continue;
+            }
+            let parent = parent.clone();
+            if let Ok(order_id) = self.submit_wallet_order(
+                &parent.wallet,
+                &parent.ticker,
+                parent.side,
+                parent.limit_price,
+                quantity,
+                TimeInForce::GoodTillCancel,
+            ) {
+                let parent = self.algos.parents.get_mut(&id).unwrap();
+                parent.children.push((order_id, quantity));
+                placed.push(ChildOrder {
+                    parent: id,
+                    order_id,
+                    quantity,
+                });
+            }
+        }
+        placed
+    }
+
+    // Cumulative quantity that should have been placed by `now`, and the
+    // last trade considered.
+    fn algo_target(&self, parent: &ParentOrder, now: u64) -> (u32, u64) {
+        match parent.strategy {
+            AlgoStrategy::Twap {
+                slices,
+                start_millis,
+                end_millis,
+            } => {
+                if now < start_millis {
+                    return (0, parent.last_trade_seen);
+                }
+                let slices = slices.max(1) as u64;
+                let duration = end_millis.saturating_sub(start_millis).max(1);
+                let due = ((now - start_millis) * slices / duration + 1).min(slices);
+                let target = parent.quantity as u64 * due / slices;
+                (target as u32, parent.last_trade_seen)
+            }
+            AlgoStrategy::Vwap {
+                participation_bps,
+                end_millis,
+            } => {
+                let mut last_trade_seen = parent.last_trade_seen;
+                let mut volume = 0;
+                for trade in self
+                    .trade_feed
+                    .trades_for(&parent.ticker)
+                    .filter(|trade| trade.id > parent.last_trade_seen)
+                {
+                    last_trade_seen = last_trade_seen.max(trade.id);
+                    let own = [trade.buy_order_id, trade.sell_order_id]
+                        .into_iter()
+                        .flatten()
+                        .any(|id| parent.children.iter().any(|(child, _)| *child == id));
+                    if !own {
+                        volume += trade.quantity;
+                    }
+                }
+                if now >= end_millis {
+                    return (parent.quantity, last_trade_seen);
+                }
+                let slice = volume * participation_bps / BPS_DENOMINATOR;
+                let target = (parent.placed() as u64 + slice).min(parent.quantity as u64);
+                (target as u32, last_trade_seen)
+            }
+        }
+    }
+
+    // Cancel the parent and every child still resting on the book. Returns
+    // the cancelled child ids.
+    pub fn cancel_algo(&mut self, id: u64) -> Option<Vec<u64>> {
+        let parent = self.algos.parents.get_mut(&id)?;
+        if parent.status != AlgoStatus::Working {
+            return Some(Vec::new());
+        }
+        parent.status = AlgoStatus::Cancelled;
+        let children: Vec<u64> = parent.children.iter().map(|(child, _)| *child).collect();
+        let ticker = parent.ticker.clone();
+        let now = self.now();
+        let orderbook = self.order_books.get_mut(&ticker)?;
+        Some(
+            children
+                .into_iter()
+                .filter(|child| orderbook.cancel_order(*child, now).is_some())
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::clock::SimulatedClock;
+
+    fn engine_with_book() -> (SimulatedClock, TradeEngine) {
+        let clock = SimulatedClock::new(0);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        engine.list_new_token(TokenTicker::ETH);
+        (clock, engine)
+    }
+
+    #[test]
+    fn test_twap_slices_and_cancel_cascade() {
+        let (clock, mut engine) = engine_with_book();
+        let wallet = Wallet::new(String::from("twapwallet"));
+        let id = engine
+            .submit_algo_order(
+                &wallet,
+                &TokenTicker::ETH,
+                BuyOrSell::Buy,
+                10,
+                100.0,
+                AlgoStrategy::Twap {
+                    slices: 4,
+                    start_millis: 1_000,
+                    end_millis: 5_000,
+                },
+            )
+            .unwrap();
+        assert!(engine.run_algos().is_empty());
+
+        clock.advance(1_000);
+        assert_eq!(engine.run_algos()[0].quantity, 2);
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Sell, 100.0, 1)
+            .unwrap();
+        engine.match_orders();
+
+        clock.advance(2_000);
+        assert_eq!(engine.run_algos()[0].quantity, 5);
+        assert_eq!(
+            engine.algo_progress(id),
+            Some(AlgoProgress {
+                quantity: 10,
+                placed: 7,
+                filled: 1,
+                status: AlgoStatus::Working,
+            })
+        );
+
+        let cancelled = engine.cancel_algo(id).unwrap();
+        assert_eq!(cancelled.len(), 2);
+        let orderbook = engine.get_token_order_book(&TokenTicker::ETH).unwrap();
+        assert_eq!(orderbook.wallet_orders(&wallet).count(), 0);
+        clock.advance(10_000);
+        assert!(engine.run_algos().is_empty());
+    }
+
+    #[test]
+    fn test_vwap_follows_market_volume() {
+        let (clock, mut engine) = engine_with_book();
+        let wallet = Wallet::new(String::from("vwapwallet"));
+        let id = engine
+            .submit_algo_order(
+                &wallet,
+                &TokenTicker::ETH,
+                BuyOrSell::Sell,
+                20,
+                90.0,
+                AlgoStrategy::Vwap {
+                    participation_bps: 5_000,
+                    end_millis: 10_000,
+                },
+            )
+            .unwrap();
+        assert!(engine.run_algos().is_empty());
+
+        // 8 traded by others: the algo places half of it
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Buy, 95.0, 8)
+            .unwrap();
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Sell, 95.0, 8)
+            .unwrap();
+        engine.match_orders();
+        assert_eq!(engine.run_algos()[0].quantity, 4);
+
+        // its own fills do not count as market volume
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Buy, 95.0, 4)
+            .unwrap();
+        engine.match_orders();
+        assert!(engine.run_algos().is_empty());
+
+        clock.advance(10_000);
+        assert_eq!(engine.run_algos()[0].quantity, 16);
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Buy, 95.0, 16)
+            .unwrap();
+        engine.match_orders();
+        engine.run_algos();
+        assert_eq!(
+            engine.algo_progress(id).unwrap().status,
+            AlgoStatus::Completed
+        );
+    }
+}
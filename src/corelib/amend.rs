@@ -0,0 +1,201 @@
+use super::engine::{OrderError, TradeEngine};
+use super::execution_quality::{book_mid, OrderArrival};
+use super::order::Order;
+use super::token::TokenTicker;
+use super::validation::validate_order;
+
+// Why a cancel or replace was refused. Nothing on the book changes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AmendError {
+    Rejected(OrderError),
+    OrderNotFound,
+    // The book moved past the sequence the request was based on.
+    StaleSequence { expected: u64, current: u64 },
+}
+
+impl TradeEngine {
+    // Sequence number of the book's latest mutation.
+    pub fn book_sequence(&self, ticker: &TokenTicker) -> Option<u64> {
+        self.order_books
+            .get(ticker)
+            .map(|orderbook| orderbook.sequence())
+    }
+
+    // Requests carrying the book sequence their sender last saw only apply
+    // if nothing has happened on the book since.
+    fn check_sequence(
+        &self,
+        ticker: &TokenTicker,
+        expected_sequence: Option<u64>,
+    ) -> Result<(), AmendError> {
+        let current = self
+            .book_sequence(ticker)
+            .ok_or(AmendError::OrderNotFound)?;
+        match expected_sequence {
+            Some(expected) if expected != current => {
+                Err(AmendError::StaleSequence { expected, current })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // `cancel_order` that refuses to act on a stale view of the book.
+    pub fn cancel_order_checked(
+        &mut self,
+        ticker: &TokenTicker,
+        order_id: u64,
+        expected_sequence: Option<u64>,
+    ) -> Result<Order, AmendError> {
+        self.check_sequence(ticker, expected_sequence)?;
+        self.cancel_order(ticker, order_id)
+            .ok_or(AmendError::OrderNotFound)
+    }
+
+    // Cancel a resting order and enter its replacement at a new price and
+    // quantity, subject to the same checks as a new order; the replaced
+    // order's notional does not count against the wallet's limit. The
+    // replacement loses time priority. Returns its id.
+    pub fn replace_order(
+        &mut self,
+        ticker: &TokenTicker,
+        order_id: u64,
+        price: f64,
+        quantity: u32,
+        expected_sequence: Option<u64>,
+    ) -> Result<u64, AmendError> {
+        self.update_sessions();
+        self.update_cancel_timers();
+        self.check_accepting_orders(ticker)
+            .map_err(AmendError::Rejected)?;
+        self.check_sequence(ticker, expected_sequence)?;
+        validate_order(price, quantity).map_err(AmendError::Rejected)?;
+        let (_, order) = self.order_books[ticker]
+            .find_order(order_id)
+            .ok_or(AmendError::OrderNotFound)?;
+        let wallet = order.wallet.clone();
+        let price = self
+            .apply_price_policy(wallet.as_ref(), ticker, price)
+            .map_err(AmendError::Rejected)?;
+        let orderbook = &self.order_books[ticker];
+        let (side, order) = orderbook.find_order(order_id).unwrap();
+        orderbook
+            .check_order(side, price, quantity)
+            .map_err(|rejection| AmendError::Rejected(OrderError::Book(rejection)))?;
+        if let Some(wallet) = &order.wallet {
+            if let Some(limit) = self.open_notional_limits.get(ticker).copied() {
+                let exposure = self.open_notional(wallet, ticker)
+                    - self.order_notional(order.price, order.quantity);
+                let order_notional = self.order_notional(price, quantity);
+                if exposure.saturating_add(order_notional) > limit {
+                    return Err(AmendError::Rejected(OrderError::OpenNotionalExceeded {
+                        exposure,
+                        order_notional,
+                        limit,
+                    }));
+                }
+            }
+        }
+        let timestamp = self.now();
+        let arrival = OrderArrival {
+            wallet: order.wallet.clone(),
+            side,
+            timestamp,
+            mid: book_mid(orderbook),
+        };
+        let orderbook = self.order_books.get_mut(ticker).unwrap();
+        let new_order_id = orderbook
+            .replace_order(order_id, price, quantity, timestamp)
+            .unwrap();
+        self.order_arrivals.record(ticker, new_order_id, arrival);
+        Ok(new_order_id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::order::{BuyOrSell, TimeInForce, Wallet};
+
+    #[test]
+    fn test_stale_requests_are_rejected() {
+        let mut engine = TradeEngine::new();
+        engine.list_new_token(TokenTicker::ETH);
+        let order = engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Buy, 100.0, 5)
+            .unwrap();
+        let seen = engine.book_sequence(&TokenTicker::ETH).unwrap();
+        assert_eq!(seen, 1);
+
+        // someone else's order moves the book on
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Sell, 105.0, 1)
+            .unwrap();
+        assert_eq!(
+            engine.replace_order(&TokenTicker::ETH, order, 101.0, 5, Some(seen)),
+            Err(AmendError::StaleSequence {
+                expected: 1,
+                current: 2
+            })
+        );
+        assert_eq!(
+            engine.cancel_order_checked(&TokenTicker::ETH, order, Some(seen)),
+            Err(AmendError::StaleSequence {
+                expected: 1,
+                current: 2
+            })
+        );
+
+        let seen = engine.book_sequence(&TokenTicker::ETH);
+        let replacement = engine
+            .replace_order(&TokenTicker::ETH, order, 101.0, 4, seen)
+            .unwrap();
+        let book = &engine.order_books[&TokenTicker::ETH];
+        assert!(book.find_order(order).is_none());
+        let (side, resting) = book.find_order(replacement).unwrap();
+        assert_eq!(
+            (side, resting.price, resting.quantity),
+            (BuyOrSell::Buy, 101.0, 4)
+        );
+        // a cancel and an add
+        assert_eq!(engine.book_sequence(&TokenTicker::ETH), Some(4));
+        assert_eq!(
+            engine.cancel_order_checked(&TokenTicker::ETH, order, None),
+            Err(AmendError::OrderNotFound)
+        );
+        assert!(engine
+            .cancel_order_checked(&TokenTicker::ETH, replacement, Some(4))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_replace_checks_limit_without_the_old_order() {
+        let mut engine = TradeEngine::new();
+        engine.list_new_token(TokenTicker::ETH);
+        engine.open_notional_limits.insert(TokenTicker::ETH, 1_000);
+        let wallet = Wallet::new(String::from("maker"));
+        let order = engine
+            .submit_wallet_order(
+                &wallet,
+                &TokenTicker::ETH,
+                BuyOrSell::Buy,
+                100.0,
+                8,
+                TimeInForce::GoodTillCancel,
+            )
+            .unwrap();
+        let replacement = engine
+            .replace_order(&TokenTicker::ETH, order, 100.0, 10, None)
+            .unwrap();
+        assert_eq!(
+            engine.replace_order(&TokenTicker::ETH, replacement, 100.0, 11, None),
+            Err(AmendError::Rejected(OrderError::OpenNotionalExceeded {
+                exposure: 0,
+                order_notional: 1_100,
+                limit: 1_000
+            }))
+        );
+        let book = &engine.order_books[&TokenTicker::ETH];
+        assert_eq!(book.find_order(replacement).unwrap().1.wallet, Some(wallet));
+    }
+}
@@ -1,12 +1,116 @@
 use crate::corelib::order::Wallet;
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
+use std::fmt;
 
+use super::arbitrage::{find_cycles, ArbitrageCycle, RateGraph};
+use super::ledger::rescale_amount;
+use super::rounding::{Flow, Rounding};
 use super::token::{Pair, TokenTicker};
 
+// LP tokens locked for good by a pool's first deposit, so the supply never
+// returns to zero and a dust first deposit cannot inflate the share price.
+pub const MINIMUM_LIQUIDITY: u64 = 1_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LiquidityError {
+    // The amounts are not at the target price of token_a in token_b.
+    RatioOutOfTolerance { actual: f64, target: f64 },
+}
+
 pub struct AMMPool {
     liquidity_pools: HashMap<TokenTicker, u64>,
     total_lp_per_pair: HashMap<Pair, u64>,
     account_lp_tokens: HashMap<Wallet, HashMap<Pair, u64>>,
+    // LP tokens moved into the ledger as `TokenTicker::Lp`. No wallet holds
+    // them in the pool; they come back to whoever returns them from the
+    // ledger.
+    wrapped_lp: HashMap<Pair, u64>,
+    pub fee_bps: u64,
+}
+
+impl Default for AMMPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Constant product quotes for non-empty reserves and a fee below 100%.
+// Pools always round in their own favour so the product never shrinks. The
+// fast path works in u64 and gives up, returning None, once a reserve times
+// the fee denominator or the result no longer fits; with `precise-amm` the
+// wide path takes over there.
+fn exact_in(reserve_in: u64, reserve_out: u64, fee_bps: u64, amount_in: u64) -> Option<u64> {
+    let amount_in = amount_in as u128 * (10_000 - fee_bps as u128);
+    let denominator = u64::try_from(reserve_in as u128 * 10_000 + amount_in).ok()?;
+    let amount_in = u64::try_from(amount_in).ok()?;
+    Some(Rounding::TowardHouse.ratio(reserve_out, amount_in, denominator, Flow::FromHouse))
+}
+
+fn exact_out(reserve_in: u64, reserve_out: u64, fee_bps: u64, amount_out: u64) -> Option<u64> {
+    let denominator = (reserve_out - amount_out) as u128 * (10_000 - fee_bps as u128);
+    let reserve_in = u64::try_from(reserve_in as u128 * 10_000).ok()?;
+    let denominator = u64::try_from(denominator).ok()?;
+    if reserve_in as u128 * amount_out as u128 / denominator as u128 >= u64::MAX as u128 {
+        return None;
+    }
+    Some(Rounding::TowardHouse.ratio(reserve_in, amount_out, denominator, Flow::ToHouse))
+}
+
+// The same quotes computed exactly for any reserves; None only when the
+// answer itself does not fit a u64.
+#[cfg(feature = "precise-amm")]
+fn exact_in_wide(reserve_in: u64, reserve_out: u64, fee_bps: u64, amount_in: u64) -> Option<u64> {
+    let amount_in = amount_in as u128 * (10_000 - fee_bps as u128);
+    let denominator = reserve_in as u128 * 10_000 + amount_in;
+    let amount_out = Rounding::TowardHouse.ratio_wide(
+        reserve_out as u128,
+        amount_in,
+        denominator,
+        Flow::FromHouse,
+    )?;
+    u64::try_from(amount_out).ok()
+}
+
+#[cfg(feature = "precise-amm")]
+fn exact_out_wide(reserve_in: u64, reserve_out: u64, fee_bps: u64, amount_out: u64) -> Option<u64> {
+    let denominator = (reserve_out - amount_out) as u128 * (10_000 - fee_bps as u128);
+    let amount_in = Rounding::TowardHouse.ratio_wide(
+        reserve_in as u128 * 10_000,
+        amount_out as u128,
+        denominator,
+        Flow::ToHouse,
+    )?;
+    u64::try_from(amount_in).ok()
+}
+
+// Reserves, then the spot price and value locked for each pair of tokens,
+// valued in the pair's second token.
+impl fmt::Display for AMMPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "AMM pool, fee {} bps", self.fee_bps)?;
+        let mut reserves: Vec<(&TokenTicker, u64)> = self.reserves().collect();
+        reserves.sort();
+        for (token, reserve) in reserves.iter() {
+            writeln!(f, "  {:<12} {:>14}", token.to_string(), reserve)?;
+        }
+        for (index, (base, base_reserve)) in reserves.iter().enumerate() {
+            for (quote, quote_reserve) in reserves[index + 1..].iter() {
+                let price = match base_reserve {
+                    0 => f64::NAN,
+                    _ => *quote_reserve as f64 / *base_reserve as f64,
+                };
+                writeln!(
+                    f,
+                    "  {:<12} price {} TVL {} {}",
+                    format!("{}/{}", base, quote),
+                    price,
+                    2 * *quote_reserve as u128,
+                    quote
+                )?;
+            }
+        }
+        Ok(())
+    }
 }
 
 impl AMMPool {
@@ -15,6 +119,8 @@ impl AMMPool {
             liquidity_pools: HashMap::new(),
             account_lp_tokens: HashMap::new(),
             total_lp_per_pair: HashMap::new(),
+            wrapped_lp: HashMap::new(),
+            fee_bps: 0,
         }
     }
 
@@ -22,6 +128,7 @@ impl AMMPool {
         *self.liquidity_pools.entry(token).or_insert(0) += amount;
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn add_liquidity_pair(
         &mut self,
         wallet: Wallet,
@@ -31,9 +138,10 @@ impl AMMPool {
         amount_b: u64,
         target_ratio: f64,
         tolerance: f64,
-    ) -> u64 {
-        // Calculate the ratio of the amounts being added
-        let actual_ratio = amount_a as f64 / amount_b as f64;
+    ) -> Result<u64, LiquidityError> {
+        // Calculate the ratio of the amounts being added, as the price of
+        // token_a in token_b
+        let actual_ratio = amount_b as f64 / amount_a as f64;
 
         // Check if the actual ratio matches the target ratio within the specified tolerance
         if (actual_ratio - target_ratio).abs() <= tolerance {
@@ -42,11 +150,11 @@ impl AMMPool {
             self.add_liquidity(token_b.clone(), amount_b);
 
             // Calculate LP tokens to mint based on the shares of the new pair
-            let total_liquidity_a = *self.liquidity_pools.get(&token_b).unwrap() as f64;
-            let share_a = amount_a as f64 / total_liquidity_a as f64;
+            let total_liquidity_a = *self.liquidity_pools.get(&token_a).unwrap() as f64;
+            let share_a = amount_a as f64 / total_liquidity_a;
 
             let total_liquidity_b = *self.liquidity_pools.get(&token_b).unwrap() as f64;
-            let share_b = (amount_b as f64 / total_liquidity_b as f64) as f64;
+            let share_b = amount_b as f64 / total_liquidity_b;
 
             // Mint and return LP tokens to the user based on the proportion of liquidity provided
             let lp_tokens_a = (share_a * total_liquidity_a) as u64;
@@ -56,25 +164,362 @@ impl AMMPool {
                 ticker_a: token_a,
                 ticker_b: token_b,
             };
-            let mut wallet_pairs = self
+            *self.total_lp_per_pair.entry(pair.clone()).or_insert(0) += lp_tokens_a + lp_tokens_b;
+            *self
                 .account_lp_tokens
                 .entry(wallet)
-                .or_insert_with(|| HashMap::new());
-            for p in wallet_pairs.iter_mut() {
-                if *p.0 == pair {
-                    wallet_pairs
-                        .entry(pair)
-                        .and_modify(|qt| *qt += lp_tokens_a + lp_tokens_b);
-                    break;
-                } else {
-                }
-            }
-            lp_tokens_a + lp_tokens_b
+                .or_default()
+                .entry(pair)
+                .or_insert(0) += lp_tokens_a + lp_tokens_b;
+            Ok(lp_tokens_a + lp_tokens_b)
         } else {
             // Reject the operation if the ratio doesn't match within tolerance
-            println!("Error: Actual ratio does not match the target ratio within the specified tolerance.");
-            0 // Return 0 LP tokens
+            Err(LiquidityError::RatioOutOfTolerance {
+                actual: actual_ratio,
+                target: target_ratio,
+            })
+        }
+    }
+
+    // LP tokens a deposit would mint to the depositor: the sum of the
+    // amounts for the first deposit, less `MINIMUM_LIQUIDITY`, afterwards pro
+    // rata to the smaller of the two contributions. None if the reserves or
+    // the LP supply would no longer fit a u64.
+    pub fn lp_for_deposit(&self, pair: &Pair, amount_a: u64, amount_b: u64) -> Option<u64> {
+        let total_lp = self.total_lp_tokens(pair);
+        let reserve_a = self.reserve(&pair.ticker_a).unwrap_or(0);
+        let reserve_b = self.reserve(&pair.ticker_b).unwrap_or(0);
+        reserve_a.checked_add(amount_a)?;
+        reserve_b.checked_add(amount_b)?;
+        let minted = if total_lp == 0 {
+            amount_a
+                .checked_add(amount_b)?
+                .saturating_sub(MINIMUM_LIQUIDITY)
+        } else if reserve_a == 0 || reserve_b == 0 {
+            amount_a.checked_add(amount_b)?
+        } else {
+            let lp_a = amount_a as u128 * total_lp as u128 / reserve_a as u128;
+            let lp_b = amount_b as u128 * total_lp as u128 / reserve_b as u128;
+            u64::try_from(lp_a.min(lp_b)).ok()?
+        };
+        total_lp
+            .checked_add(minted)?
+            .checked_add(MINIMUM_LIQUIDITY)?;
+        Some(minted)
+    }
+
+    // Add both sides of `pair` at the pool's current ratio and mint LP tokens
+    // to `wallet`. A deposit that would mint nothing, or overflow the pool, or
+    // arrives after `deadline`, is refused and returns 0.
+    pub fn deposit(
+        &mut self,
+        wallet: &Wallet,
+        pair: &Pair,
+        amount_a: u64,
+        amount_b: u64,
+        deadline: u64,
+        now: u64,
+    ) -> u64 {
+        if now > deadline {
+            return 0;
+        }
+        let minted = self.lp_for_deposit(pair, amount_a, amount_b).unwrap_or(0);
+        if minted == 0 {
+            return 0;
         }
+        let locked = if self.total_lp_tokens(pair) == 0 {
+            MINIMUM_LIQUIDITY
+        } else {
+            0
+        };
+        self.add_liquidity(pair.ticker_a.clone(), amount_a);
+        self.add_liquidity(pair.ticker_b.clone(), amount_b);
+        *self.total_lp_per_pair.entry(pair.clone()).or_insert(0) += minted + locked;
+        *self
+            .account_lp_tokens
+            .entry(wallet.clone())
+            .or_default()
+            .entry(pair.clone())
+            .or_insert(0) += minted;
+        minted
+    }
+
+    // Burn `lp_tokens` of `wallet` and release the matching share of both
+    // reserves. None if the wallet does not hold that many, or once `now` is
+    // past `deadline`.
+    pub fn withdraw(
+        &mut self,
+        wallet: &Wallet,
+        pair: &Pair,
+        lp_tokens: u64,
+        deadline: u64,
+        now: u64,
+    ) -> Option<(u64, u64)> {
+        if now > deadline || lp_tokens == 0 || self.lp_balance(wallet, pair) < lp_tokens {
+            return None;
+        }
+        let total_lp = self.total_lp_tokens(pair) as u128;
+        let share = |reserve: u64| (reserve as u128 * lp_tokens as u128 / total_lp) as u64;
+        let amount_a = share(self.reserve(&pair.ticker_a).unwrap_or(0));
+        let amount_b = share(self.reserve(&pair.ticker_b).unwrap_or(0));
+
+        *self.liquidity_pools.get_mut(&pair.ticker_a)? -= amount_a;
+        *self.liquidity_pools.get_mut(&pair.ticker_b)? -= amount_b;
+        *self.total_lp_per_pair.get_mut(pair)? -= lp_tokens;
+        *self.account_lp_tokens.get_mut(wallet)?.get_mut(pair)? -= lp_tokens;
+        Some((amount_a, amount_b))
+    }
+
+    // Move LP tokens between wallets. False if `from` holds fewer than
+    // `amount`, or once `now` is past `deadline`.
+    pub fn transfer_lp(
+        &mut self,
+        from: &Wallet,
+        to: &Wallet,
+        pair: &Pair,
+        amount: u64,
+        deadline: u64,
+        now: u64,
+    ) -> bool {
+        if now > deadline || self.lp_balance(from, pair) < amount {
+            return false;
+        }
+        *self
+            .account_lp_tokens
+            .get_mut(from)
+            .and_then(|pairs| pairs.get_mut(pair))
+            .unwrap() -= amount;
+        *self
+            .account_lp_tokens
+            .entry(to.clone())
+            .or_default()
+            .entry(pair.clone())
+            .or_insert(0) += amount;
+        true
+    }
+
+    // Move the wallet's LP tokens out of the pool into the wrapped supply.
+    // False if it holds fewer than `amount`.
+    pub(crate) fn wrap_lp(&mut self, wallet: &Wallet, pair: &Pair, amount: u64) -> bool {
+        if self.lp_balance(wallet, pair) < amount {
+            return false;
+        }
+        *self
+            .account_lp_tokens
+            .get_mut(wallet)
+            .and_then(|pairs| pairs.get_mut(pair))
+            .unwrap() -= amount;
+        *self.wrapped_lp.entry(pair.clone()).or_insert(0) += amount;
+        true
+    }
+
+    // Give `amount` wrapped LP tokens back to the wallet. False if fewer
+    // are wrapped.
+    pub(crate) fn unwrap_lp(&mut self, wallet: &Wallet, pair: &Pair, amount: u64) -> bool {
+        if self.wrapped_lp(pair) < amount {
+            return false;
+        }
+        *self.wrapped_lp.get_mut(pair).unwrap() -= amount;
+        *self
+            .account_lp_tokens
+            .entry(wallet.clone())
+            .or_default()
+            .entry(pair.clone())
+            .or_insert(0) += amount;
+        true
+    }
+
+    pub fn wrapped_lp(&self, pair: &Pair) -> u64 {
+        self.wrapped_lp.get(pair).copied().unwrap_or(0)
+    }
+
+    pub fn lp_balance(&self, wallet: &Wallet, pair: &Pair) -> u64 {
+        self.account_lp_tokens
+            .get(wallet)
+            .and_then(|pairs| pairs.get(pair))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn total_lp_tokens(&self, pair: &Pair) -> u64 {
+        self.total_lp_per_pair.get(pair).copied().unwrap_or(0)
+    }
+
+    // Wallets holding LP tokens of `pair`, with their balances.
+    pub fn lp_holders(&self, pair: &Pair) -> Vec<(Wallet, u64)> {
+        self.account_lp_tokens
+            .iter()
+            .filter_map(|(wallet, pairs)| {
+                pairs
+                    .get(pair)
+                    .filter(|amount| **amount > 0)
+                    .map(|amount| (wallet.clone(), *amount))
+            })
+            .collect()
+    }
+
+    // Outstanding LP tokens per pair.
+    pub(crate) fn lp_supplies(&self) -> impl Iterator<Item = (&Pair, u64)> {
+        self.total_lp_per_pair
+            .iter()
+            .map(|(pair, total)| (pair, *total))
+    }
+
+    pub(crate) fn lp_positions(&self) -> impl Iterator<Item = (&Wallet, &Pair, u64)> {
+        self.account_lp_tokens.iter().flat_map(|(wallet, pairs)| {
+            pairs
+                .iter()
+                .map(move |(pair, amount)| (wallet, pair, *amount))
+        })
+    }
+
+    pub(crate) fn wrapped_lp_supplies(&self) -> impl Iterator<Item = (&Pair, u64)> {
+        self.wrapped_lp
+            .iter()
+            .map(|(pair, wrapped)| (pair, *wrapped))
+    }
+
+    // Pool holding exactly the given state, e.g. read back from a snapshot.
+    pub(crate) fn restore(
+        fee_bps: u64,
+        reserves: Vec<(TokenTicker, u64)>,
+        lp_supplies: Vec<(Pair, u64)>,
+        lp_positions: Vec<(Wallet, Pair, u64)>,
+        wrapped_lp: Vec<(Pair, u64)>,
+    ) -> AMMPool {
+        let mut pool = AMMPool {
+            liquidity_pools: reserves.into_iter().collect(),
+            total_lp_per_pair: lp_supplies.into_iter().collect(),
+            wrapped_lp: wrapped_lp.into_iter().collect(),
+            fee_bps,
+            ..AMMPool::new()
+        };
+        for (wallet, pair, amount) in lp_positions {
+            pool.account_lp_tokens
+                .entry(wallet)
+                .or_default()
+                .insert(pair, amount);
+        }
+        pool
+    }
+
+    pub fn reserves(&self) -> impl Iterator<Item = (&TokenTicker, u64)> {
+        self.liquidity_pools
+            .iter()
+            .map(|(token, reserve)| (token, *reserve))
+    }
+
+    pub fn reserve(&self, token: &TokenTicker) -> Option<u64> {
+        self.liquidity_pools.get(token).copied()
+    }
+
+    // Multiply the reserve of `token` by `numerator / denominator`, rounding
+    // down.
+    pub(crate) fn rescale_token(&mut self, token: &TokenTicker, numerator: u64, denominator: u64) {
+        if let Some(reserve) = self.liquidity_pools.get_mut(token) {
+            *reserve = rescale_amount(*reserve, numerator, denominator)
+                .expect("rescale checked beforehand");
+        }
+    }
+
+    // Whether the reserve of `token` still fits a u64 after `rescale_token`
+    // with the same ratio.
+    pub(crate) fn can_rescale_token(
+        &self,
+        token: &TokenTicker,
+        numerator: u64,
+        denominator: u64,
+    ) -> bool {
+        self.liquidity_pools
+            .get(token)
+            .is_none_or(|reserve| rescale_amount(*reserve, numerator, denominator).is_some())
+    }
+
+    // Look for cycles through the pool's tokens that return more than they
+    // cost after paying the pool fee on every hop. Spot prices of a single
+    // pool are mutually consistent, so any result points at broken reserve
+    // accounting.
+    pub fn find_arbitrage_cycles(&self, max_hops: usize) -> Vec<ArbitrageCycle> {
+        let fee = 1.0 - self.fee_bps as f64 / 10_000.0;
+        let mut graph = RateGraph::new();
+        for (token_a, reserve_a) in self.liquidity_pools.iter() {
+            for (token_b, reserve_b) in self.liquidity_pools.iter() {
+                if token_a == token_b || *reserve_a == 0 || *reserve_b == 0 {
+                    continue;
+                }
+                graph
+                    .entry(token_a.clone())
+                    .or_default()
+                    .push((token_b.clone(), *reserve_b as f64 / *reserve_a as f64 * fee));
+            }
+        }
+        find_cycles(&graph, max_hops, 0.0)
+    }
+
+    // Input of `token_in` needed to take `amount_out` of `token_out` under
+    // the constant product, after the pool fee, rounded up in the pool's
+    // favour. None if the pool cannot pay out that much.
+    pub fn quote_exact_out(
+        &self,
+        token_in: &TokenTicker,
+        token_out: &TokenTicker,
+        amount_out: u64,
+    ) -> Option<u64> {
+        let reserve_in = self.reserve(token_in)?;
+        let reserve_out = self.reserve(token_out)?;
+        if reserve_in == 0 || amount_out >= reserve_out || self.fee_bps >= 10_000 {
+            return None;
+        }
+        let amount_in = exact_out(reserve_in, reserve_out, self.fee_bps, amount_out);
+        #[cfg(feature = "precise-amm")]
+        let amount_in =
+            amount_in.or_else(|| exact_out_wide(reserve_in, reserve_out, self.fee_bps, amount_out));
+        amount_in
+    }
+
+    // Swap for exactly `amount_out`, returning the input taken.
+    pub fn swap_exact_out(
+        &mut self,
+        token_in: &TokenTicker,
+        token_out: &TokenTicker,
+        amount_out: u64,
+    ) -> Option<u64> {
+        let amount_in = self.quote_exact_out(token_in, token_out, amount_out)?;
+        self.update_reserves(token_in.clone(), token_out.clone(), amount_in, amount_out)?;
+        Some(amount_in)
+    }
+
+    // Output of `token_out` for selling exactly `amount_in` of `token_in`
+    // under the constant product, after the pool fee, rounded down in the
+    // pool's favour.
+    pub fn quote_exact_in(
+        &self,
+        token_in: &TokenTicker,
+        token_out: &TokenTicker,
+        amount_in: u64,
+    ) -> Option<u64> {
+        let reserve_in = self.reserve(token_in)?;
+        let reserve_out = self.reserve(token_out)?;
+        if reserve_in == 0 || reserve_out == 0 || self.fee_bps >= 10_000 {
+            return None;
+        }
+        let amount_out = exact_in(reserve_in, reserve_out, self.fee_bps, amount_in);
+        #[cfg(feature = "precise-amm")]
+        let amount_out =
+            amount_out.or_else(|| exact_in_wide(reserve_in, reserve_out, self.fee_bps, amount_in));
+        amount_out
+    }
+
+    // Sell exactly `amount_in`, returning the output paid.
+    pub fn swap_exact_in(
+        &mut self,
+        token_in: &TokenTicker,
+        token_out: &TokenTicker,
+        amount_in: u64,
+    ) -> Option<u64> {
+        let amount_out = self.quote_exact_in(token_in, token_out, amount_in)?;
+        self.update_reserves(token_in.clone(), token_out.clone(), amount_in, amount_out)?;
+        Some(amount_out)
     }
 
     pub fn token_swap(
@@ -85,8 +530,9 @@ impl AMMPool {
     ) -> Option<u64> {
         // Perform the multi-token swap
         // Find the path with the highest output amount for the given token pair
+        // Trade directly unless an intermediate token pays more
         let mut max_output_amount = 0;
-        let mut optimal_path: Vec<TokenTicker> = Vec::new();
+        let mut optimal_path: Vec<TokenTicker> = vec![token_in.clone(), token_out.clone()];
 
         // Iterate over all tokens in the pool
         for (token, _) in self.liquidity_pools.iter() {
@@ -105,10 +551,9 @@ impl AMMPool {
 
         // Perform the swap using the optimal path
         let mut amount_in_remaining = amount_in;
-        let mut current_token = token_in;
-        for i in 0..optimal_path.len() - 1 {
-            let token_a = optimal_path[i].clone();
-            let token_b = optimal_path[i + 1].clone();
+        for hop in optimal_path.windows(2) {
+            let token_a = hop[0].clone();
+            let token_b = hop[1].clone();
 
             let amount_out = self.calculate_output_amount(
                 token_a.clone(),
@@ -126,9 +571,6 @@ impl AMMPool {
 
             // Update remaining input amount
             amount_in_remaining = amount_out;
-
-            // Update current token for the next iteration
-            current_token = token_b;
         }
 
         Some(amount_in_remaining)
@@ -143,14 +585,14 @@ impl AMMPool {
         let reserve_a = *self.liquidity_pools.get(&token_a)?;
         let reserve_b = *self.liquidity_pools.get(&token_b)?;
 
-        // a constant product model (e.g., Uniswap) for AMM swaps
-        let new_reserve_a = reserve_a + amount_in;
-        let new_reserve_b = reserve_b + amount_in;
-
-        let numerator = new_reserve_b * reserve_a;
-        let denominator = new_reserve_a;
-
-        Some((numerator / denominator) as u64)
+        // priced at the ratio of the two reserves; None when token_b's
+        // reserve cannot pay out that much
+        if reserve_a == 0 {
+            return None;
+        }
+        let amount_out =
+            u64::try_from(amount_in as u128 * reserve_b as u128 / reserve_a as u128).ok()?;
+        (amount_out <= reserve_b).then_some(amount_out)
     }
 
     // Update the reserves for swapping token_a for token_b
@@ -161,11 +603,13 @@ impl AMMPool {
         amount_in: u64,
         amount_out: u64,
     ) -> Option<()> {
-        let reserve_a = self.liquidity_pools.get_mut(&token_a)?;
-
-        *reserve_a += amount_in;
-        let reserve_b = self.liquidity_pools.get_mut(&token_b)?;
-        *reserve_b -= amount_out;
+        let reserve_a = self.liquidity_pools.get(&token_a)?.checked_add(amount_in)?;
+        let reserve_b = self
+            .liquidity_pools
+            .get(&token_b)?
+            .checked_sub(amount_out)?;
+        self.liquidity_pools.insert(token_a, reserve_a);
+        self.liquidity_pools.insert(token_b, reserve_b);
 
         Some(())
     }
@@ -191,6 +635,57 @@ mod test {
         assert_eq!(amm.liquidity_pools.get(&token_b), Some(&2000));
     }
 
+    #[test]
+    fn test_first_deposit_locks_minimum_liquidity() {
+        let mut amm = AMMPool::new();
+        let wallet = Wallet::new(String::from("minimumwallet"));
+        let pair = Pair::new(TokenTicker::ETH, TokenTicker::USDT);
+
+        // a dust first deposit mints nothing and changes nothing
+        assert_eq!(amm.deposit(&wallet, &pair, 400, 600, u64::MAX, 0), 0);
+        assert_eq!(amm.reserve(&TokenTicker::ETH), None);
+
+        assert_eq!(
+            amm.deposit(&wallet, &pair, 1_000, 1_000, u64::MAX, 0),
+            1_000
+        );
+        assert_eq!(amm.total_lp_tokens(&pair), 2_000);
+        // the locked tokens keep their share of the reserves
+        assert_eq!(
+            amm.withdraw(&wallet, &pair, 1_000, u64::MAX, 0),
+            Some((500, 500))
+        );
+        assert_eq!(amm.total_lp_tokens(&pair), MINIMUM_LIQUIDITY);
+
+        // a deposit overflowing the pool is refused rather than wrapping
+        assert_eq!(amm.lp_for_deposit(&pair, u64::MAX, 1), None);
+        assert_eq!(amm.deposit(&wallet, &pair, u64::MAX, 1, u64::MAX, 0), 0);
+        assert_eq!(amm.reserve(&TokenTicker::ETH), Some(500));
+        let empty = AMMPool::new();
+        assert_eq!(empty.lp_for_deposit(&pair, u64::MAX, 1), None);
+    }
+
+    #[test]
+    fn test_liquidity_operations_refused_past_deadline() {
+        let mut amm = AMMPool::new();
+        let alice = Wallet::new(String::from("deadlinealice"));
+        let bob = Wallet::new(String::from("deadlinebob"));
+        let pair = Pair::new(TokenTicker::ETH, TokenTicker::USDT);
+
+        assert_eq!(amm.deposit(&alice, &pair, 1_000, 1_000, 99, 100), 0);
+        assert_eq!(amm.reserve(&TokenTicker::ETH), None);
+        // the deadline itself is still in time
+        assert_eq!(amm.deposit(&alice, &pair, 1_000, 1_000, 100, 100), 1_000);
+
+        assert!(!amm.transfer_lp(&alice, &bob, &pair, 500, 99, 100));
+        assert_eq!(amm.lp_balance(&bob, &pair), 0);
+        assert!(amm.transfer_lp(&alice, &bob, &pair, 500, 100, 100));
+
+        assert_eq!(amm.withdraw(&bob, &pair, 500, 99, 100), None);
+        assert_eq!(amm.lp_balance(&bob, &pair), 500);
+        assert_eq!(amm.withdraw(&bob, &pair, 500, 100, 100), Some((250, 250)));
+    }
+
     #[test]
     fn test_add_liquidity_pair() {
         let mut amm = AMMPool::new();
@@ -212,7 +707,7 @@ mod test {
             tolerance,
         );
 
-        assert_eq!(lp_tokens, 3000); // Assuming LP tokens minted correctly
+        assert_eq!(lp_tokens, Ok(3000)); // Assuming LP tokens minted correctly
     }
 
     #[test]
@@ -226,6 +721,8 @@ mod test {
             liquidity_pools,
             total_lp_per_pair: HashMap::new(),
             account_lp_tokens: HashMap::new(),
+            wrapped_lp: HashMap::new(),
+            fee_bps: 0,
         };
 
         let token_in = TokenTicker::ETH;
@@ -248,6 +745,8 @@ mod test {
             liquidity_pools,
             total_lp_per_pair: HashMap::new(),
             account_lp_tokens: HashMap::new(),
+            wrapped_lp: HashMap::new(),
+            fee_bps: 0,
         };
 
         let token_in = TokenTicker::ETH;
@@ -270,6 +769,8 @@ mod test {
             liquidity_pools,
             total_lp_per_pair: HashMap::new(),
             account_lp_tokens: HashMap::new(),
+            wrapped_lp: HashMap::new(),
+            fee_bps: 0,
         };
 
         let token_in = TokenTicker::ETH;
@@ -280,4 +781,83 @@ mod test {
 
         assert_eq!(amount_out, Some(0)); // Expecting zero output amount for zero input amount
     }
+
+    #[test]
+    fn test_pool_prints_reserves_price_and_tvl() {
+        let mut amm = AMMPool::new();
+        amm.fee_bps = 30;
+        let wallet = Wallet::new(String::from("displaywallet"));
+        amm.deposit(
+            &wallet,
+            &Pair::new(TokenTicker::ETH, TokenTicker::USDT),
+            10_000,
+            25_000_000,
+            u64::MAX,
+            0,
+        );
+        assert_eq!(
+            amm.to_string(),
+            [
+                "AMM pool, fee 30 bps",
+                "  ETH                   10000",
+                "  USDT               25000000",
+                "  ETH/USDT     price 2500 TVL 50000000 USDT",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    // Any amount from 1 to u64::MAX, spread evenly over magnitudes.
+    #[cfg(feature = "precise-amm")]
+    fn amount(rng: &mut crate::corelib::rng::SeededRng) -> u64 {
+        (rng.next_u64() >> rng.between(0, 63)).max(1)
+    }
+
+    #[cfg(feature = "precise-amm")]
+    #[test]
+    fn test_wide_quotes_agree_with_fast_path() {
+        let mut rng = crate::corelib::rng::SeededRng::new(978);
+        let close =
+            |quoted: u64, expected: f64| (quoted as f64 - expected).abs() <= expected * 1e-9 + 1.0;
+        let mut beyond_fast_path = 0;
+        for _ in 0..20_000 {
+            let (reserve_in, reserve_out) = (amount(&mut rng), amount(&mut rng));
+            let fee_bps = rng.between(0, 100);
+            let after_fee = 1.0 - fee_bps as f64 / 10_000.0;
+
+            let amount_in = amount(&mut rng);
+            let wide = exact_in_wide(reserve_in, reserve_out, fee_bps, amount_in).unwrap();
+            match exact_in(reserve_in, reserve_out, fee_bps, amount_in) {
+                Some(fast) => assert_eq!(wide, fast),
+                None => {
+                    beyond_fast_path += 1;
+                    let net_in = amount_in as f64 * after_fee;
+                    let expected = reserve_out as f64 * net_in / (reserve_in as f64 + net_in);
+                    assert!(close(wide, expected), "{} vs {}", wide, expected);
+                }
+            }
+
+            if reserve_out < 2 {
+                continue;
+            }
+            let amount_out = rng.between(1, reserve_out - 1);
+            let wide = exact_out_wide(reserve_in, reserve_out, fee_bps, amount_out);
+            match (
+                exact_out(reserve_in, reserve_out, fee_bps, amount_out),
+                wide,
+            ) {
+                (Some(fast), wide) => assert_eq!(wide, Some(fast)),
+                (None, Some(wide)) => {
+                    beyond_fast_path += 1;
+                    let expected = reserve_in as f64 * amount_out as f64
+                        / ((reserve_out - amount_out) as f64 * after_fee);
+                    assert!(close(wide, expected), "{} vs {}", wide, expected);
+                }
+                // the input needed does not fit a u64 at all
+                (None, None) => {}
+            }
+        }
+        assert!(beyond_fast_path > 1_000);
+    }
 }
@@ -0,0 +1,258 @@
+use super::engine::TradeEngine;
+use super::events::BookEvent;
+use super::order::BuyOrSell;
+use super::token::TokenTicker;
+use super::trade::Trade;
+
+const DAY_MILLIS: u64 = 24 * 60 * 60 * 1_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HiddenLiquidityEstimate {
+    // Orders that reappeared at the level shortly after a resting order
+    // there was fully filled.
+    pub replenishments: usize,
+    // Average size of the replenishing orders, i.e. the displayed clip.
+    pub average_clip: f64,
+    // Quantity that only became visible through replenishment.
+    pub replenished_quantity: u64,
+    // True when enough equally sized refills were seen to suggest a single
+    // iceberg order rather than unrelated traders joining the level.
+    pub likely_iceberg: bool,
+}
+
+// Minimum number of refills before a level is flagged as an iceberg.
+const MIN_ICEBERG_REPLENISHMENTS: usize = 2;
+// Allowed relative deviation of each clip from the average clip.
+const CLIP_TOLERANCE: f64 = 0.2;
+
+// Estimate liquidity hidden behind a price level by looking for the
+// replenishment pattern in an L3 stream: a resting order at `price` is filled
+// completely and a new order on the same side and price shows up within
+// `window_millis`.
+pub fn estimate_hidden_liquidity(
+    events: &[BookEvent],
+    side: BuyOrSell,
+    price: f64,
+    window_millis: u64,
+) -> HiddenLiquidityEstimate {
+    let mut last_depletion: Option<u64> = None;
+    let mut clips: Vec<u32> = Vec::new();
+
+    for event in events {
+        match event {
+            BookEvent::OrderFilled {
+                side: fill_side,
+                price: fill_price,
+                remaining: 0,
+                timestamp,
+                ..
+            } if *fill_side == side && *fill_price == price => {
+                last_depletion = Some(*timestamp);
+            }
+            BookEvent::OrderAdded {
+                side: add_side,
+                price: add_price,
+                quantity,
+                timestamp,
+                ..
+            } if *add_side == side && *add_price == price => {
+                if let Some(depleted_at) = last_depletion.take() {
+                    if timestamp.saturating_sub(depleted_at) <= window_millis {
+                        clips.push(*quantity);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let replenished_quantity: u64 = clips.iter().map(|clip| *clip as u64).sum();
+    let average_clip = if clips.is_empty() {
+        0.0
+    } else {
+        replenished_quantity as f64 / clips.len() as f64
+    };
+    let likely_iceberg = clips.len() >= MIN_ICEBERG_REPLENISHMENTS
+        && clips
+            .iter()
+            .all(|clip| (*clip as f64 - average_clip).abs() <= average_clip * CLIP_TOLERANCE);
+
+    HiddenLiquidityEstimate {
+        replenishments: clips.len(),
+        average_clip,
+        replenished_quantity,
+        likely_iceberg,
+    }
+}
+
+// Volume-weighted average price of `trades`; None without volume.
+pub fn vwap<'a>(trades: impl IntoIterator<Item = &'a Trade>) -> Option<f64> {
+    let (notional, volume) = trades
+        .into_iter()
+        .fold((0.0, 0), |(notional, volume), trade| {
+            (
+                notional + trade.price * trade.quantity as f64,
+                volume + trade.quantity,
+            )
+        });
+    (volume > 0).then(|| notional / volume as f64)
+}
+
+impl TradeEngine {
+    // VWAP of the instrument's trades from `anchor_millis` on.
+    pub fn anchored_vwap(&self, ticker: &TokenTicker, anchor_millis: u64) -> Option<f64> {
+        vwap(
+            self.trade_feed
+                .trades_for(ticker)
+                .filter(|trade| trade.timestamp >= anchor_millis),
+        )
+    }
+
+    // VWAP since the current session's open. Instruments without a schedule
+    // trade around the clock and reset at midnight UTC.
+    pub fn session_vwap(&self, ticker: &TokenTicker) -> Option<f64> {
+        let now = self.now();
+        let anchor = match self.session_schedule(ticker) {
+            Some(schedule) => schedule.last_open_at(now)?,
+            None => now - now % DAY_MILLIS,
+        };
+        self.anchored_vwap(ticker, anchor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::clock::SimulatedClock;
+    use crate::corelib::session::SessionSchedule;
+    use crate::corelib::trade::TradeKind;
+    use chrono::NaiveTime;
+
+    fn added(
+        order_id: u64,
+        side: BuyOrSell,
+        price: f64,
+        quantity: u32,
+        timestamp: u64,
+    ) -> BookEvent {
+        BookEvent::OrderAdded {
+            order_id,
+            side,
+            price,
+            quantity,
+            timestamp,
+            metadata: None,
+        }
+    }
+
+    fn depleted(
+        order_id: u64,
+        side: BuyOrSell,
+        price: f64,
+        filled: u32,
+        timestamp: u64,
+    ) -> BookEvent {
+        BookEvent::OrderFilled {
+            order_id,
+            side,
+            price,
+            filled,
+            remaining: 0,
+            timestamp,
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_detects_refilled_level() {
+        let events = vec![
+            added(1, BuyOrSell::Sell, 101.0, 100, 0),
+            depleted(1, BuyOrSell::Sell, 101.0, 100, 10),
+            added(2, BuyOrSell::Sell, 101.0, 100, 12),
+            depleted(2, BuyOrSell::Sell, 101.0, 100, 20),
+            added(3, BuyOrSell::Sell, 101.0, 95, 21),
+            depleted(3, BuyOrSell::Sell, 101.0, 95, 30),
+            added(4, BuyOrSell::Sell, 101.0, 105, 33),
+            // unrelated activity on other levels and sides
+            added(5, BuyOrSell::Buy, 101.0, 500, 34),
+            added(6, BuyOrSell::Sell, 102.0, 500, 35),
+        ];
+
+        let estimate = estimate_hidden_liquidity(&events, BuyOrSell::Sell, 101.0, 5);
+        assert_eq!(estimate.replenishments, 3);
+        assert_eq!(estimate.replenished_quantity, 300);
+        assert_eq!(estimate.average_clip, 100.0);
+        assert!(estimate.likely_iceberg);
+    }
+
+    #[test]
+    fn test_slow_refill_is_not_counted() {
+        let events = vec![
+            added(1, BuyOrSell::Buy, 99.0, 10, 0),
+            depleted(1, BuyOrSell::Buy, 99.0, 10, 10),
+            added(2, BuyOrSell::Buy, 99.0, 10, 1_000),
+        ];
+
+        let estimate = estimate_hidden_liquidity(&events, BuyOrSell::Buy, 99.0, 50);
+        assert_eq!(estimate.replenishments, 0);
+        assert!(!estimate.likely_iceberg);
+    }
+
+    #[test]
+    fn test_anchored_and_session_vwap() {
+        // 2024-01-05 09:00 UTC, a Friday
+        let friday = 1_704_445_200_000;
+        let clock = SimulatedClock::new(friday);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        engine.list_new_token(TokenTicker::ETH);
+        engine.list_new_token(TokenTicker::BTC);
+        let hour = 60 * 60 * 1_000;
+        for (ticker, price, quantity, timestamp) in [
+            (TokenTicker::ETH, 100.0, 1, friday - 10 * hour),
+            (TokenTicker::ETH, 110.0, 3, friday - hour),
+            (TokenTicker::ETH, 120.0, 1, friday + 2 * hour),
+            (TokenTicker::BTC, 50.0, 2, friday - hour),
+        ] {
+            engine.trade_feed.publish(Trade {
+                id: 0,
+                ticker,
+                price,
+                quantity,
+                buyer: None,
+                seller: None,
+                buy_order_id: None,
+                sell_order_id: None,
+                timestamp,
+                kind: TradeKind::Lit,
+                buy_metadata: None,
+                sell_metadata: None,
+                settled_notional: None,
+            });
+        }
+        clock.advance(3 * hour);
+
+        assert_eq!(
+            engine.anchored_vwap(&TokenTicker::ETH, friday - hour),
+            Some(112.5)
+        );
+        assert_eq!(
+            engine.anchored_vwap(&TokenTicker::ETH, friday + 3 * hour),
+            None
+        );
+        // no schedule: the session starts at midnight
+        assert_eq!(engine.session_vwap(&TokenTicker::ETH), Some(112.5));
+
+        engine
+            .set_session_schedule(
+                TokenTicker::ETH,
+                SessionSchedule::new(
+                    NaiveTime::from_hms_opt(9, 30, 0).unwrap(),
+                    NaiveTime::from_hms_opt(16, 0, 0).unwrap(),
+                ),
+            )
+            .unwrap();
+        assert_eq!(engine.session_vwap(&TokenTicker::ETH), Some(120.0));
+        assert_eq!(engine.session_vwap(&TokenTicker::BTC), Some(50.0));
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+
+use super::engine::TradeEngine;
+use super::token::TokenTicker;
+
+const BPS: f64 = 10_000.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArbitrageCycle {
+    // Tokens visited, starting and ending with the same token.
+    pub route: Vec<TokenTicker>,
+    // Units of the start token returned per unit put in, at spot prices
+    // and after fees.
+    pub rate_product: f64,
+    pub expected_profit_bps: f64,
+}
+
+// Directed exchange rate from one token to another, net of fees.
+pub(crate) type RateGraph = HashMap<TokenTicker, Vec<(TokenTicker, f64)>>;
+
+// Depth-first search for simple cycles of at most `max_hops` edges whose
+// rate product beats 1 by more than `min_profit_bps`. Each cycle is reported
+// once, starting from its smallest token; best cycles first.
+pub(crate) fn find_cycles(
+    graph: &RateGraph,
+    max_hops: usize,
+    min_profit_bps: f64,
+) -> Vec<ArbitrageCycle> {
+    let mut cycles = Vec::new();
+    let mut starts: Vec<&TokenTicker> = graph.keys().collect();
+    starts.sort();
+    for start in starts {
+        let mut route = vec![start.clone()];
+        walk(
+            graph,
+            start,
+            1.0,
+            max_hops,
+            min_profit_bps,
+            &mut route,
+            &mut cycles,
+        );
+    }
+    cycles.sort_by(|a, b| b.expected_profit_bps.total_cmp(&a.expected_profit_bps));
+    cycles
+}
+
+fn walk(
+    graph: &RateGraph,
+    start: &TokenTicker,
+    rate: f64,
+    max_hops: usize,
+    min_profit_bps: f64,
+    route: &mut Vec<TokenTicker>,
+    cycles: &mut Vec<ArbitrageCycle>,
+) {
+    let current = route.last().unwrap().clone();
+    for (next, edge_rate) in graph.get(&current).into_iter().flatten() {
+        let rate = rate * edge_rate;
+        if next == start {
+            let expected_profit_bps = (rate - 1.0) * BPS;
+            // a two-token round trip needs at least two hops
+            if route.len() > 2 && expected_profit_bps > min_profit_bps {
+                let mut cycle = route.clone();
+                cycle.push(start.clone());
+                cycles.push(ArbitrageCycle {
+                    route: cycle,
+                    rate_product: rate,
+                    expected_profit_bps,
+                });
+            }
+            continue;
+        }
+        // only extend through tokens ordered after the start so every cycle
+        // is enumerated from a single rotation
+        if next < start || route.contains(next) || route.len() >= max_hops {
+            continue;
+        }
+        route.push(next.clone());
+        walk(graph, start, rate, max_hops, min_profit_bps, route, cycles);
+        route.pop();
+    }
+}
+
+impl TradeEngine {
+    // Scan the pair pools for profitable cycles across pools, e.g. when the
+    // ETH/USDT, BTC/USDT and ETH/BTC pools disagree on prices.
+    pub fn find_arbitrage_cycles(
+        &self,
+        max_hops: usize,
+        min_profit_bps: f64,
+    ) -> Vec<ArbitrageCycle> {
+        let mut graph = RateGraph::new();
+        let tiers = self.tier_pools.iter().map(|((pair, _), pool)| (pair, pool));
+        for (pair, pool) in self.amm_pools.iter().chain(tiers) {
+            let (Some(reserve_a), Some(reserve_b)) =
+                (pool.reserve(&pair.ticker_a), pool.reserve(&pair.ticker_b))
+            else {
+                continue;
+            };
+            if reserve_a == 0 || reserve_b == 0 {
+                continue;
+            }
+            let fee = 1.0 - pool.fee_bps as f64 / BPS;
+            let rate = reserve_b as f64 / reserve_a as f64;
+            graph
+                .entry(pair.ticker_a.clone())
+                .or_default()
+                .push((pair.ticker_b.clone(), rate * fee));
+            graph
+                .entry(pair.ticker_b.clone())
+                .or_default()
+                .push((pair.ticker_a.clone(), fee / rate));
+        }
+        find_cycles(&graph, max_hops, min_profit_bps)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::amm::AMMPool;
+    use crate::corelib::token::Pair;
+
+    fn pool(
+        engine: &mut TradeEngine,
+        a: TokenTicker,
+        reserve_a: u64,
+        b: TokenTicker,
+        reserve_b: u64,
+    ) {
+        let pool = engine
+            .amm_pools
+            .entry(Pair::new(a.clone(), b.clone()))
+            .or_default();
+        pool.add_liquidity(a, reserve_a);
+        pool.add_liquidity(b, reserve_b);
+        pool.fee_bps = 30;
+    }
+
+    #[test]
+    fn test_finds_mispriced_triangle() {
+        let mut engine = TradeEngine::new();
+        pool(
+            &mut engine,
+            TokenTicker::ETH,
+            1_000,
+            TokenTicker::USDT,
+            3_000_000,
+        );
+        pool(
+            &mut engine,
+            TokenTicker::BTC,
+            100,
+            TokenTicker::USDT,
+            6_000_000,
+        );
+        // implies 1 ETH = 0.06 BTC = 3_600 USDT, cheaper than the ETH/USDT pool
+        pool(&mut engine, TokenTicker::BTC, 60, TokenTicker::ETH, 1_000);
+
+        let cycles = engine.find_arbitrage_cycles(3, 0.0);
+        assert_eq!(cycles.len(), 1);
+        let cycle = &cycles[0];
+        assert_eq!(
+            cycle.route,
+            vec![
+                TokenTicker::BTC,
+                TokenTicker::USDT,
+                TokenTicker::ETH,
+                TokenTicker::BTC
+            ]
+        );
+        assert!(cycle.expected_profit_bps > 1_000.0);
+
+        // a threshold above the edge filters it out
+        assert!(engine.find_arbitrage_cycles(3, 2_000.0).is_empty());
+        // and the triangle needs three hops
+        assert!(engine.find_arbitrage_cycles(2, 0.0).is_empty());
+    }
+
+    #[test]
+    fn test_single_pool_is_consistent() {
+        let mut amm = AMMPool::new();
+        amm.add_liquidity(TokenTicker::ETH, 1_000);
+        amm.add_liquidity(TokenTicker::USDT, 3_000_000);
+        amm.add_liquidity(TokenTicker::BTC, 50);
+        amm.fee_bps = 5;
+
+        assert!(amm.find_arbitrage_cycles(3).is_empty());
+    }
+}
@@ -0,0 +1,133 @@
+use super::engine::TradeEngine;
+use super::order::BuyOrSell;
+use super::orderbook::OrderBook;
+use super::session::SessionState;
+use super::token::TokenTicker;
+
+// Price at which the book would uncross if the auction ended now.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IndicativeUncross {
+    pub price: f64,
+    pub matched_quantity: u64,
+    // Quantity left unmatched at `price` on the heavier side.
+    pub imbalance_quantity: u64,
+    pub imbalance_side: Option<BuyOrSell>,
+}
+
+impl OrderBook {
+    // Equilibrium over the resting levels: the price executing the most
+    // quantity, then leaving the smallest imbalance, then closest to
+    // `reference` (the lowest such price without one). None while the book
+    // does not cross.
+    pub fn indicative_uncross(&self, reference: Option<f64>) -> Option<IndicativeUncross> {
+        let depth = self.depth_snapshot();
+        let mut prices: Vec<f64> = depth
+            .bids
+            .iter()
+            .chain(depth.asks.iter())
+            .map(|level| level.price)
+            .collect();
+        prices.sort_by(|a, b| a.total_cmp(b));
+        prices.dedup();
+
+        let mut best: Option<IndicativeUncross> = None;
+        for price in prices {
+            let demand: u64 = depth
+                .bids
+                .iter()
+                .filter(|level| level.price >= price)
+                .map(|level| level.quantity)
+                .sum();
+            let supply: u64 = depth
+                .asks
+                .iter()
+                .filter(|level| level.price <= price)
+                .map(|level| level.quantity)
+                .sum();
+            let matched_quantity = demand.min(supply);
+            if matched_quantity == 0 {
+                continue;
+            }
+            let candidate = IndicativeUncross {
+                price,
+                matched_quantity,
+                imbalance_quantity: demand.abs_diff(supply),
+                imbalance_side: match demand.cmp(&supply) {
+                    std::cmp::Ordering::Greater => Some(BuyOrSell::Buy),
+                    std::cmp::Ordering::Less => Some(BuyOrSell::Sell),
+                    std::cmp::Ordering::Equal => None,
+                },
+            };
+            let better = match &best {
+                None => true,
+                Some(best) => {
+                    let distance =
+                        |uncross: &IndicativeUncross| reference.map(|r| (uncross.price - r).abs());
+                    (
+                        candidate.matched_quantity,
+                        std::cmp::Reverse(candidate.imbalance_quantity),
+                    ) > (
+                        best.matched_quantity,
+                        std::cmp::Reverse(best.imbalance_quantity),
+                    ) || (candidate.matched_quantity == best.matched_quantity
+                        && candidate.imbalance_quantity == best.imbalance_quantity
+                        && distance(&candidate) < distance(best))
+                }
+            };
+            if better {
+                best = Some(candidate);
+            }
+        }
+        best
+    }
+}
+
+impl TradeEngine {
+    // Indicative opening price of an instrument in pre-open, recomputed from
+    // the book on every call so it follows orders as they arrive. The last
+    // trade breaks ties.
+    pub fn indicative_open(&self, ticker: &TokenTicker) -> Option<IndicativeUncross> {
+        if self.session_state(ticker) != SessionState::PreOpen {
+            return None;
+        }
+        let reference = self.trade_feed.last_trade(ticker).map(|trade| trade.price);
+        self.order_books.get(ticker)?.indicative_uncross(reference)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_indicative_uncross() {
+        let mut orderbook = OrderBook::new();
+        orderbook.add_order(BuyOrSell::Buy, 99.0, 10, 0);
+        orderbook.add_order(BuyOrSell::Sell, 101.0, 10, 0);
+        assert_eq!(orderbook.indicative_uncross(None), None);
+
+        orderbook.add_order(BuyOrSell::Buy, 102.0, 5, 0);
+        orderbook.add_order(BuyOrSell::Buy, 101.0, 3, 0);
+        orderbook.add_order(BuyOrSell::Sell, 100.0, 4, 0);
+        assert_eq!(
+            orderbook.indicative_uncross(None),
+            Some(IndicativeUncross {
+                price: 101.0,
+                matched_quantity: 8,
+                imbalance_quantity: 6,
+                imbalance_side: Some(BuyOrSell::Sell),
+            })
+        );
+
+        // an equally good range is resolved towards the reference
+        let mut orderbook = OrderBook::new();
+        orderbook.add_order(BuyOrSell::Buy, 105.0, 5, 0);
+        orderbook.add_order(BuyOrSell::Sell, 100.0, 5, 0);
+        assert_eq!(orderbook.indicative_uncross(None).unwrap().price, 100.0);
+        assert_eq!(
+            orderbook.indicative_uncross(Some(104.0)).unwrap().price,
+            105.0
+        );
+    }
+}
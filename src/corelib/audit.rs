@@ -0,0 +1,156 @@
+use super::admin::AdminCommand;
+use super::fees::FeeRouting;
+use super::order::Wallet;
+use super::price_policy::PricePolicy;
+use super::token::{Pair, TokenTicker};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuditAction {
+    FeeRoutingChanged {
+        from: FeeRouting,
+        to: FeeRouting,
+    },
+    TreasuryLiquiditySeeded {
+        pair: Pair,
+        amount_a: u64,
+        amount_b: u64,
+        lp_minted: u64,
+    },
+    TreasuryLiquidityWithdrawn {
+        pair: Pair,
+        lp_burned: u64,
+        amount_a: u64,
+        amount_b: u64,
+    },
+    // A fee due in `quote_token` was paid in `fee_token`, converted through
+    // the AMM.
+    FeeConverted {
+        wallet: Wallet,
+        fee_token: TokenTicker,
+        fee_token_amount: u64,
+        quote_token: TokenTicker,
+        quote_amount: u64,
+    },
+    InternalTransfer {
+        from: Wallet,
+        to: Wallet,
+        token: TokenTicker,
+        amount: u64,
+        memo: Option<String>,
+    },
+    // A keeper trade moved the pool back to the reference price.
+    PoolRebalanced {
+        pair: Pair,
+        token_in: TokenTicker,
+        amount_in: u64,
+        amount_out: u64,
+    },
+    // The engine's generator was restarted from `seed`.
+    RngSeeded {
+        seed: u64,
+    },
+    // Seed for shuffling same-priced orders within each batch auction.
+    BatchTieBreakSeeded {
+        ticker: TokenTicker,
+        seed: u64,
+    },
+    AdminCommandApplied {
+        command: AdminCommand,
+    },
+    PricePolicySet {
+        ticker: TokenTicker,
+        policy: PricePolicy,
+    },
+    // Order entry raised a price to the instrument's floor.
+    PriceClamped {
+        ticker: TokenTicker,
+        wallet: Option<Wallet>,
+        price: f64,
+        entered: f64,
+    },
+    // Order entry refused a zero or negative price.
+    PriceRejected {
+        ticker: TokenTicker,
+        wallet: Option<Wallet>,
+        price: f64,
+    },
+    MintAuthorityGranted {
+        token: TokenTicker,
+        authority: Wallet,
+    },
+    MintAuthorityRevoked {
+        token: TokenTicker,
+        authority: Wallet,
+    },
+    SupplyCapSet {
+        token: TokenTicker,
+        cap: Option<u64>,
+    },
+    // Supply created or destroyed on `authority`'s word, e.g. by a bridge.
+    Minted {
+        token: TokenTicker,
+        wallet: Wallet,
+        amount: u64,
+        authority: Wallet,
+    },
+    Burned {
+        token: TokenTicker,
+        wallet: Wallet,
+        amount: u64,
+        authority: Wallet,
+    },
+    // A trade cancelled as clearly erroneous, with its ledger legs reversed.
+    TradeBusted {
+        trade_id: u64,
+        ticker: TokenTicker,
+        price: f64,
+        quantity: u64,
+        reversed_settlement: bool,
+    },
+    // Locked funds returned to free on recovery from a light snapshot,
+    // which does not keep what locked them.
+    LockReleasedOnRecovery {
+        wallet: Wallet,
+        token: TokenTicker,
+        amount: u64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditRecord {
+    pub sequence: u64,
+    pub timestamp: u64,
+    pub action: AuditAction,
+}
+
+// Append-only trail of privileged operations.
+#[derive(Default)]
+pub struct AuditLog {
+    records: Vec<AuditRecord>,
+}
+
+impl AuditLog {
+    pub fn new() -> AuditLog {
+        AuditLog::default()
+    }
+
+    pub fn record(&mut self, timestamp: u64, action: AuditAction) -> u64 {
+        let sequence = self.records.len() as u64 + 1;
+        self.records.push(AuditRecord {
+            sequence,
+            timestamp,
+            action,
+        });
+        sequence
+    }
+
+    pub fn records(&self) -> &[AuditRecord] {
+        &self.records
+    }
+
+    // Records with a sequence number above `sequence`.
+    pub fn since(&self, sequence: u64) -> &[AuditRecord] {
+        let start = (sequence as usize).min(self.records.len());
+        &self.records[start..]
+    }
+}
@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+
+use super::clock::Clock;
+use super::engine::TradeEngine;
+use super::ledger::Balance;
+use super::order::Wallet;
+use super::token::TokenTicker;
+
+type Balances = HashMap<Wallet, HashMap<TokenTicker, Balance>>;
+
+// A balance as it was right after a change.
+struct BalanceChange {
+    timestamp: u64,
+    wallet: Wallet,
+    token: TokenTicker,
+    balance: Balance,
+}
+
+// Every balance as it was after the first `changes` changes.
+struct Checkpoint {
+    changes: usize,
+    balances: Balances,
+}
+
+// Journal of a ledger's balance changes with periodic full checkpoints. A
+// query replays the changes since the last checkpoint before its time.
+// Assumes the clock does not go backwards.
+pub(crate) struct BalanceHistory {
+    clock: Box<dyn Clock>,
+    checkpoint_every: usize,
+    started_at: u64,
+    changes: Vec<BalanceChange>,
+    checkpoints: Vec<Checkpoint>,
+}
+
+impl BalanceHistory {
+    pub(crate) fn new(
+        clock: Box<dyn Clock>,
+        checkpoint_every: usize,
+        balances: &Balances,
+    ) -> BalanceHistory {
+        BalanceHistory {
+            started_at: clock.now_millis(),
+            clock,
+            checkpoint_every: checkpoint_every.max(1),
+            changes: Vec::new(),
+            checkpoints: vec![Checkpoint {
+                changes: 0,
+                balances: balances.clone(),
+            }],
+        }
+    }
+
+    // `balances` already holds the change.
+    pub(crate) fn record(
+        &mut self,
+        wallet: &Wallet,
+        token: &TokenTicker,
+        balance: Balance,
+        balances: &Balances,
+    ) {
+        self.changes.push(BalanceChange {
+            timestamp: self.clock.now_millis(),
+            wallet: wallet.clone(),
+            token: token.clone(),
+            balance,
+        });
+        if self.changes.len().is_multiple_of(self.checkpoint_every) {
+            self.checkpoints.push(Checkpoint {
+                changes: self.changes.len(),
+                balances: balances.clone(),
+            });
+        }
+    }
+
+    // None before the history started.
+    pub(crate) fn positions_at(
+        &self,
+        wallet: &Wallet,
+        timestamp: u64,
+    ) -> Option<Vec<(TokenTicker, Balance)>> {
+        if timestamp < self.started_at {
+            return None;
+        }
+        let end = self
+            .changes
+            .partition_point(|change| change.timestamp <= timestamp);
+        let checkpoint = &self.checkpoints[self
+            .checkpoints
+            .partition_point(|checkpoint| checkpoint.changes <= end)
+            - 1];
+        let mut positions = checkpoint.balances.get(wallet).cloned().unwrap_or_default();
+        for change in &self.changes[checkpoint.changes..end] {
+            if change.wallet == *wallet {
+                positions.insert(change.token.clone(), change.balance);
+            }
+        }
+        Some(positions.into_iter().collect())
+    }
+}
+
+impl TradeEngine {
+    // Keep the ledger's balance history on the engine's clock, so
+    // `Ledger::balance_at` and `Ledger::positions_at` can answer for any
+    // time from now on.
+    pub fn record_balance_history(&mut self, checkpoint_every: usize) {
+        let clock = Box::new(self.clock.clone());
+        self.ledger.record_history(clock, checkpoint_every);
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::clock::SimulatedClock;
+
+    #[test]
+    fn test_balances_replay_to_any_time_with_or_without_checkpoints() {
+        let run = |checkpoint_every| {
+            let clock = SimulatedClock::new(1_000);
+            let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+            let alice = Wallet::new(String::from("historyalice"));
+            let bob = Wallet::new(String::from("historybob"));
+            engine
+                .ledger
+                .deposit(alice.clone(), TokenTicker::USDT, 50)
+                .unwrap();
+            engine.record_balance_history(checkpoint_every);
+
+            clock.set(2_000);
+            engine
+                .ledger
+                .deposit(alice.clone(), TokenTicker::USDT, 100)
+                .unwrap();
+            engine
+                .ledger
+                .deposit(alice.clone(), TokenTicker::ETH, 3)
+                .unwrap();
+            clock.set(3_000);
+            engine
+                .transfer(&alice, &bob, &TokenTicker::USDT, 70, None)
+                .unwrap();
+            engine.ledger.lock(&alice, &TokenTicker::ETH, 2).unwrap();
+            clock.set(4_000);
+            engine
+                .ledger
+                .withdraw(&alice, &TokenTicker::USDT, 80)
+                .unwrap();
+            (engine.ledger, alice, bob)
+        };
+
+        for checkpoint_every in [1, 2, 1_000] {
+            let (ledger, alice, bob) = run(checkpoint_every);
+            let usdt = |wallet: &Wallet, timestamp| {
+                ledger
+                    .balance_at(wallet, &TokenTicker::USDT, timestamp)
+                    .map(|balance| balance.free)
+            };
+            assert_eq!(usdt(&alice, 999), None);
+            assert_eq!(usdt(&alice, 1_500), Some(50));
+            assert_eq!(usdt(&alice, 2_000), Some(150));
+            assert_eq!(usdt(&alice, 3_500), Some(80));
+            assert_eq!(usdt(&bob, 2_999), Some(0));
+            assert_eq!(usdt(&bob, 3_000), Some(70));
+            assert_eq!(usdt(&alice, 9_999), Some(0));
+
+            let mut positions = ledger.positions_at(&alice, 3_000).unwrap();
+            positions.sort_by(|left, right| left.0.cmp(&right.0));
+            let mut expected = vec![
+                (
+                    TokenTicker::USDT,
+                    Balance {
+                        free: 80,
+                        locked: 0,
+                    },
+                ),
+                (TokenTicker::ETH, Balance { free: 1, locked: 2 }),
+            ];
+            expected.sort_by(|left, right| left.0.cmp(&right.0));
+            assert_eq!(positions, expected);
+        }
+    }
+}
@@ -0,0 +1,333 @@
+use std::collections::HashMap;
+
+use super::engine::{OrderError, TradeEngine};
+use super::margin::{InstrumentKind, Position};
+use super::order::{BuyOrSell, TimeInForce, Wallet};
+use super::orderbook::OrderBookTrait;
+use super::token::TokenTicker;
+use super::trade::Trade;
+
+// Weighted combination of listed tokens: one unit of the basket is
+// `units` of each constituent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Basket {
+    pub ticker: TokenTicker,
+    pub constituents: Vec<(TokenTicker, u32)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BasketError {
+    AlreadyDefined,
+    UnknownBasket,
+    EmptyBasket,
+    UnknownConstituent(TokenTicker),
+    // The constituent has nothing resting on the side the child would take.
+    NoQuote(TokenTicker),
+    Order(OrderError),
+}
+
+impl From<OrderError> for BasketError {
+    fn from(err: OrderError) -> Self {
+        BasketError::Order(err)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChildOrder {
+    pub ticker: TokenTicker,
+    pub order_id: u64,
+    pub price: f64,
+    pub quantity: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BasketOrder {
+    pub basket: TokenTicker,
+    pub side: BuyOrSell,
+    pub quantity: u32,
+    // Sum of the child prices weighted by units, per basket unit.
+    pub price: f64,
+    pub children: Vec<ChildOrder>,
+}
+
+// A placed basket order and how much of each child's fills were busted.
+#[derive(Debug, Clone)]
+struct BookedBasket {
+    wallet: Wallet,
+    basket: TokenTicker,
+    side: BuyOrSell,
+    price: f64,
+    // (units per basket, constituent quantity busted) per child.
+    children: Vec<(u32, u64)>,
+    // Basket units already taken back out of the position.
+    reversed: u64,
+}
+
+// Basket orders by their children's (constituent, order id).
+#[derive(Debug, Clone, Default)]
+pub struct BasketOrders {
+    orders: Vec<BookedBasket>,
+    by_child: HashMap<(TokenTicker, u64), (usize, usize)>,
+}
+
+impl TradeEngine {
+    pub fn define_basket(
+        &mut self,
+        name: &str,
+        constituents: Vec<(TokenTicker, u32)>,
+    ) -> Result<TokenTicker, BasketError> {
+        let ticker = TokenTicker::Basket(String::from(name));
+        if self.baskets.contains_key(&ticker) {
+            return Err(BasketError::AlreadyDefined);
+        }
+        if constituents.iter().all(|(_, units)| *units == 0) {
+            return Err(BasketError::EmptyBasket);
+        }
+        if let Some((missing, _)) = constituents
+            .iter()
+            .find(|(constituent, _)| !self.order_books.contains_key(constituent))
+        {
+            return Err(BasketError::UnknownConstituent(missing.clone()));
+        }
+        let constituents = constituents
+            .into_iter()
+            .filter(|(_, units)| *units > 0)
+            .collect();
+        self.baskets.insert(
+            ticker.clone(),
+            Basket {
+                ticker: ticker.clone(),
+                constituents,
+            },
+        );
+        Ok(ticker)
+    }
+
+    // Decompose a basket order into one marketable child per constituent,
+    // priced at the constituent's best opposite quote. Either every child is
+    // placed or none is. The wallet's basket position moves by `quantity` at
+    // the combined price.
+    pub fn submit_basket_order(
+        &mut self,
+        wallet: &Wallet,
+        basket: &TokenTicker,
+        side: BuyOrSell,
+        quantity: u32,
+    ) -> Result<BasketOrder, BasketError> {
+        let constituents = self
+            .baskets
+            .get(basket)
+            .ok_or(BasketError::UnknownBasket)?
+            .constituents
+            .clone();
+        let mut legs = Vec::new();
+        for (ticker, units) in constituents {
+            let orderbook = &self.order_books[&ticker];
+            let quote = match side {
+                BuyOrSell::Buy => orderbook.best_sell_price(),
+                BuyOrSell::Sell => orderbook.best_buy_price(),
+            };
+            let price = quote.ok_or(BasketError::NoQuote(ticker.clone()))?;
+            legs.push((ticker, price.into_inner(), units));
+        }
+
+        let mut children: Vec<ChildOrder> = Vec::new();
+        for (ticker, price, units) in legs.iter().cloned() {
+            let child_quantity = units * quantity;
+            match self.submit_wallet_order(
+                wallet,
+                &ticker,
+                side,
+                price,
+                child_quantity,
+                TimeInForce::GoodTillCancel,
+            ) {
+                Ok(order_id) => children.push(ChildOrder {
+                    ticker,
+                    order_id,
+                    price,
+                    quantity: child_quantity,
+                }),
+                Err(err) => {
+                    let now = self.now();
+                    for child in children {
+                        if let Some(orderbook) = self.order_books.get_mut(&child.ticker) {
+                            orderbook.cancel_order(child.order_id, now);
+                        }
+                    }
+                    return Err(err.into());
+                }
+            }
+        }
+
+        let price = children
+            .iter()
+            .map(|child| child.price * child.quantity as f64)
+            .sum::<f64>()
+            / quantity as f64;
+        let signed = match side {
+            BuyOrSell::Buy => quantity as i64,
+            BuyOrSell::Sell => -(quantity as i64),
+        };
+        self.add_basket_position(wallet, basket, signed, price);
+        let index = self.basket_orders.orders.len();
+        for (child_index, child) in children.iter().enumerate() {
+            self.basket_orders
+                .by_child
+                .insert((child.ticker.clone(), child.order_id), (index, child_index));
+        }
+        self.basket_orders.orders.push(BookedBasket {
+            wallet: wallet.clone(),
+            basket: basket.clone(),
+            side,
+            price,
+            children: legs.iter().map(|(_, _, units)| (*units, 0)).collect(),
+            reversed: 0,
+        });
+        Ok(BasketOrder {
+            basket: basket.clone(),
+            side,
+            quantity,
+            price,
+            children,
+        })
+    }
+
+    // Take a busted fill of a basket child back out of the basket position.
+    // A basket unit is gone once any of its constituents is, so the order
+    // gives back as many units as its most busted child has touched.
+    pub(crate) fn unwind_basket_fill(&mut self, trade: &Trade) {
+        for order_id in [trade.buy_order_id, trade.sell_order_id]
+            .into_iter()
+            .flatten()
+        {
+            let Some((index, child)) = self
+                .basket_orders
+                .by_child
+                .get(&(trade.ticker.clone(), order_id))
+                .copied()
+            else {
+                continue;
+            };
+            let booked = &mut self.basket_orders.orders[index];
+            booked.children[child].1 += trade.quantity;
+            let broken = booked
+                .children
+                .iter()
+                .map(|(units, busted)| busted.div_ceil(*units as u64))
+                .max()
+                .unwrap_or(0);
+            let units = broken.saturating_sub(booked.reversed);
+            if units == 0 {
+                continue;
+            }
+            booked.reversed = broken;
+            let signed = match booked.side {
+                BuyOrSell::Buy => -(units as i64),
+                BuyOrSell::Sell => units as i64,
+            };
+            let (wallet, basket, price) =
+                (booked.wallet.clone(), booked.basket.clone(), booked.price);
+            self.add_basket_position(&wallet, &basket, signed, price);
+        }
+    }
+
+    pub fn basket_position(&self, wallet: &Wallet, basket: &TokenTicker) -> Option<&Position> {
+        self.positions
+            .iter()
+            .find(|position| &position.wallet == wallet && &position.underlying == basket)
+    }
+
+    // Adding to a position averages the entry price; flipping through zero
+    // starts again at `price`; a flat position is dropped.
+    fn add_basket_position(
+        &mut self,
+        wallet: &Wallet,
+        basket: &TokenTicker,
+        quantity: i64,
+        price: f64,
+    ) {
+        let existing = self
+            .positions
+            .iter()
+            .position(|position| &position.wallet == wallet && &position.underlying == basket);
+        let Some(index) = existing else {
+            self.positions.push(Position {
+                wallet: wallet.clone(),
+                underlying: basket.clone(),
+                kind: InstrumentKind::Spot,
+                quantity,
+                entry_price: price,
+                mark_price: price,
+                collateral: 0,
+            });
+            return;
+        };
+        let position = &mut self.positions[index];
+        let total = position.quantity + quantity;
+        if position.quantity.signum() == quantity.signum() {
+            position.entry_price = (position.entry_price * position.quantity as f64
+                + price * quantity as f64)
+                / total as f64;
+        } else if total.signum() != position.quantity.signum() {
+            position.entry_price = price;
+        }
+        position.quantity = total;
+        position.mark_price = price;
+        if total == 0 {
+            self.positions.remove(index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_basket_order_decomposes_into_children() {
+        let mut engine = TradeEngine::new();
+        let wallet = Wallet::new(String::from("basketwallet"));
+        engine.list_new_token(TokenTicker::ETH);
+        engine.list_new_token(TokenTicker::BTC);
+        assert_eq!(
+            engine.define_basket("DUO", vec![(TokenTicker::ETH, 2), (TokenTicker::SOL, 1)]),
+            Err(BasketError::UnknownConstituent(TokenTicker::SOL))
+        );
+        let basket = engine
+            .define_basket("DUO", vec![(TokenTicker::ETH, 2), (TokenTicker::BTC, 1)])
+            .unwrap();
+
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Sell, 10.0, 100)
+            .unwrap();
+        assert_eq!(
+            engine.submit_basket_order(&wallet, &basket, BuyOrSell::Buy, 3),
+            Err(BasketError::NoQuote(TokenTicker::BTC))
+        );
+        engine
+            .submit_order(&TokenTicker::BTC, BuyOrSell::Sell, 100.0, 100)
+            .unwrap();
+
+        let order = engine
+            .submit_basket_order(&wallet, &basket, BuyOrSell::Buy, 3)
+            .unwrap();
+        assert_eq!(order.price, 120.0);
+        let children: Vec<(TokenTicker, u32)> = order
+            .children
+            .iter()
+            .map(|child| (child.ticker.clone(), child.quantity))
+            .collect();
+        assert_eq!(children, vec![(TokenTicker::ETH, 6), (TokenTicker::BTC, 3)]);
+        assert_eq!(engine.match_orders().len(), 2);
+
+        assert_eq!(
+            engine.submit_basket_order(&wallet, &basket, BuyOrSell::Sell, 1),
+            Err(BasketError::NoQuote(TokenTicker::ETH))
+        );
+        let position = engine.basket_position(&wallet, &basket).unwrap();
+        assert_eq!(position.quantity, 3);
+        assert_eq!(position.entry_price, 120.0);
+    }
+}
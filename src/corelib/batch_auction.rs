@@ -0,0 +1,267 @@
+use ordered_float::OrderedFloat;
+
+use super::audit::AuditAction;
+use super::engine::TradeEngine;
+use super::orderbook::OrderBook;
+use super::rng::SeededRng;
+use super::token::TokenTicker;
+
+// Frequent batch auction: orders rest without matching during each interval
+// and the book is uncrossed at a single price when it ends. Intervals are
+// aligned to multiples of `interval_millis` on the engine clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchAuction {
+    pub interval_millis: u64,
+    pub next_clear_millis: u64,
+    // When set, same-priced orders that arrived during the interval are
+    // shuffled before the clear so arriving first buys nothing.
+    pub tie_break_seed: Option<u64>,
+    // Clearing price of a clear that ran out of fills in its step. It is
+    // finished at the same price in the following steps, before the next
+    // interval clears.
+    pub unfinished_clear: Option<OrderedFloat<f64>>,
+    rng: SeededRng,
+}
+
+impl BatchAuction {
+    pub fn new(interval_millis: u64, now: u64) -> BatchAuction {
+        BatchAuction {
+            interval_millis,
+            next_clear_millis: (now / interval_millis + 1) * interval_millis,
+            tie_break_seed: None,
+            unfinished_clear: None,
+            rng: SeededRng::new(0),
+        }
+    }
+
+    // Start of the interval that clears next.
+    pub fn interval_start(&self) -> u64 {
+        self.next_clear_millis - self.interval_millis
+    }
+
+    pub fn due(&self, now: u64) -> bool {
+        now >= self.next_clear_millis
+    }
+
+    // Move to the interval containing `now`; missed intervals clear once.
+    pub(crate) fn advance(&mut self, now: u64) {
+        self.next_clear_millis = (now / self.interval_millis + 1) * self.interval_millis;
+    }
+
+    // Shuffle the orders arriving since `since` at every level that trades
+    // at `price`. Orders left over from earlier batches keep their place
+    // ahead of them.
+    pub(crate) fn shuffle_arrivals(&mut self, orderbook: &mut OrderBook, price: f64, since: u64) {
+        if self.tie_break_seed.is_none() {
+            return;
+        }
+        let mut levels: Vec<&mut Vec<_>> = Vec::new();
+        let mut bids: Vec<_> = orderbook
+            .buy_orders
+            .iter_mut()
+            .filter(|(level, _)| **level >= OrderedFloat(price))
+            .collect();
+        let mut asks: Vec<_> = orderbook
+            .sell_orders
+            .iter_mut()
+            .filter(|(level, _)| **level <= OrderedFloat(price))
+            .collect();
+        // hash map order is not stable; walk levels by price for replays
+        bids.sort_by_key(|(level, _)| **level);
+        asks.sort_by_key(|(level, _)| **level);
+        levels.extend(bids.into_iter().map(|(_, orders)| orders));
+        levels.extend(asks.into_iter().map(|(_, orders)| orders));
+        for orders in levels {
+            let start = orders
+                .iter()
+                .position(|order| order.timestamp >= since)
+                .unwrap_or(orders.len());
+            self.rng.shuffle(&mut orders[start..]);
+        }
+    }
+}
+
+impl TradeEngine {
+    pub fn enable_batch_auction(&mut self, ticker: TokenTicker, interval_millis: u64) {
+        let now = self.now();
+        self.batch_auctions
+            .insert(ticker, BatchAuction::new(interval_millis, now));
+    }
+
+    // Randomize tie-breaking within each batch from `seed`. The seed goes in
+    // the audit log so a replay can reproduce the same fills.
+    pub fn set_batch_tie_break_seed(&mut self, ticker: &TokenTicker, seed: u64) -> bool {
+        let Some(batch) = self.batch_auctions.get_mut(ticker) else {
+            return false;
+        };
+        batch.tie_break_seed = Some(seed);
+        batch.rng = SeededRng::new(seed);
+        let now = self.now();
+        self.audit_log.record(
+            now,
+            AuditAction::BatchTieBreakSeeded {
+                ticker: ticker.clone(),
+                seed,
+            },
+        );
+        true
+    }
+
+    // Tie-break seed drawn from the engine's generator; None if the
+    // instrument has no batch auction.
+    pub fn enable_batch_tie_break(&mut self, ticker: &TokenTicker) -> Option<u64> {
+        if !self.batch_auctions.contains_key(ticker) {
+            return None;
+        }
+        let seed = self.draw_seed();
+        self.set_batch_tie_break_seed(ticker, seed);
+        Some(seed)
+    }
+
+    // Back to continuous matching from the next step.
+    pub fn disable_batch_auction(&mut self, ticker: &TokenTicker) -> Option<BatchAuction> {
+        self.batch_auctions.remove(ticker)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::clock::SimulatedClock;
+    use crate::corelib::order::BuyOrSell;
+    use crate::corelib::trade::TradeKind;
+
+    #[test]
+    fn test_batch_clears_at_uniform_price() {
+        let clock = SimulatedClock::new(1_050);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        engine.list_new_token(TokenTicker::ETH);
+        engine.list_new_token(TokenTicker::BTC);
+        engine.enable_batch_auction(TokenTicker::ETH, 100);
+        assert_eq!(
+            engine.batch_auctions[&TokenTicker::ETH].next_clear_millis,
+            1_100
+        );
+
+        for (side, price, quantity) in [
+            (BuyOrSell::Buy, 102.0, 5),
+            (BuyOrSell::Buy, 101.0, 3),
+            (BuyOrSell::Sell, 100.0, 4),
+            (BuyOrSell::Sell, 101.0, 10),
+        ] {
+            engine
+                .submit_order(&TokenTicker::ETH, side, price, quantity)
+                .unwrap();
+        }
+        // continuous instruments still match every step
+        engine
+            .submit_order(&TokenTicker::BTC, BuyOrSell::Buy, 50.0, 1)
+            .unwrap();
+        engine
+            .submit_order(&TokenTicker::BTC, BuyOrSell::Sell, 50.0, 1)
+            .unwrap();
+        assert_eq!(engine.match_orders().len(), 1);
+
+        clock.set(1_100);
+        let trades = engine.match_orders();
+        let matched: u32 = trades.iter().map(|trade| trade.3).sum();
+        assert_eq!(matched, 8);
+        assert!(trades.iter().all(|trade| trade.2 == 101.0));
+        let eth_trade = engine.trade_feed.last_trade(&TokenTicker::ETH).unwrap();
+        assert_eq!(eth_trade.kind, TradeKind::Auction);
+        assert_eq!(
+            engine.batch_auctions[&TokenTicker::ETH].next_clear_millis,
+            1_200
+        );
+        let remaining: u32 = engine.order_books[&TokenTicker::ETH]
+            .orders(BuyOrSell::Sell)
+            .map(|order| order.quantity)
+            .sum();
+        assert_eq!(remaining, 6);
+    }
+
+    #[test]
+    fn test_limited_step_finishes_clear_at_the_same_price() {
+        let clock = SimulatedClock::new(0);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        engine.list_new_token(TokenTicker::ETH);
+        engine.enable_batch_auction(TokenTicker::ETH, 100);
+        engine.max_fills_per_step = Some(2);
+        for _ in 0..3 {
+            engine
+                .submit_order(&TokenTicker::ETH, BuyOrSell::Buy, 11.0, 1)
+                .unwrap();
+            engine
+                .submit_order(&TokenTicker::ETH, BuyOrSell::Sell, 10.0, 1)
+                .unwrap();
+        }
+
+        clock.set(100);
+        let first = engine.match_orders();
+        assert_eq!(first.len(), 2);
+        let price = first[0].2;
+        let batch = engine.batch_auctions[&TokenTicker::ETH];
+        assert_eq!(batch.unfinished_clear, Some(OrderedFloat(price)));
+        assert_eq!(batch.next_clear_millis, 200);
+        assert!(engine.unfinished_matches.contains(&TokenTicker::ETH));
+
+        // an order arriving before the clear finishes takes part in it, in
+        // price-time priority and at the clearing price
+        let late = engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Sell, 9.0, 1)
+            .unwrap();
+        clock.set(110);
+        let rest = engine.match_orders();
+        assert_eq!(rest.len(), 1);
+        assert_eq!((rest[0].1, rest[0].2), (late, price));
+        assert_eq!(
+            engine.batch_auctions[&TokenTicker::ETH].unfinished_clear,
+            None
+        );
+        assert!(engine.unfinished_matches.is_empty());
+        assert_eq!(engine.match_orders().len(), 0);
+    }
+
+    fn batch_fills(seed: u64) -> Vec<(u64, u64, f64, u32)> {
+        let clock = SimulatedClock::new(0);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        engine.list_new_token(TokenTicker::ETH);
+        engine.enable_batch_auction(TokenTicker::ETH, 100);
+        assert!(engine.set_batch_tie_break_seed(&TokenTicker::ETH, seed));
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Sell, 10.0, 3)
+            .unwrap();
+        for _ in 0..8 {
+            clock.advance(10);
+            engine
+                .submit_order(&TokenTicker::ETH, BuyOrSell::Buy, 10.0, 1)
+                .unwrap();
+        }
+        clock.set(100);
+        engine.match_orders()
+    }
+
+    #[test]
+    fn test_tie_break_seed_is_replayable() {
+        let fills = batch_fills(42);
+        assert_eq!(fills.len(), 3);
+        assert_eq!(fills, batch_fills(42));
+        let buyers: Vec<u64> = fills.iter().map(|fill| fill.0).collect();
+        // arrival order alone would fill the first three buyers
+        assert_ne!(buyers, vec![2, 3, 4]);
+
+        let mut engine = TradeEngine::new();
+        engine.list_new_token(TokenTicker::ETH);
+        assert!(!engine.set_batch_tie_break_seed(&TokenTicker::ETH, 42));
+        engine.enable_batch_auction(TokenTicker::ETH, 100);
+        engine.set_batch_tie_break_seed(&TokenTicker::ETH, 42);
+        assert_eq!(
+            engine.audit_log.records()[0].action,
+            AuditAction::BatchTieBreakSeeded {
+                ticker: TokenTicker::ETH,
+                seed: 42
+            }
+        );
+    }
+}
@@ -0,0 +1,143 @@
+use super::engine::TradeEngine;
+use super::order::BuyOrSell;
+use super::orderbook::{BookRejection, OrderBook};
+use super::price::PriceLike;
+use super::token::TokenTicker;
+
+// Configures a book before it opens: tick and lot sizes, a cap on price
+// levels per side and orders already resting.
+pub struct OrderBookBuilder<P: PriceLike = f64> {
+    tick_size: Option<P>,
+    lot_size: Option<u32>,
+    max_levels: Option<usize>,
+    orders: Vec<(BuyOrSell, P, u32, u64)>,
+}
+
+impl<P: PriceLike> Default for OrderBookBuilder<P> {
+    fn default() -> Self {
+        OrderBookBuilder {
+            tick_size: None,
+            lot_size: None,
+            max_levels: None,
+            orders: Vec::new(),
+        }
+    }
+}
+
+impl OrderBook {
+    pub fn builder() -> OrderBookBuilder {
+        OrderBookBuilder::default()
+    }
+}
+
+impl<P: PriceLike> OrderBookBuilder<P> {
+    // Prices must be whole multiples of `tick_size`.
+    pub fn tick_size(mut self, tick_size: P) -> Self {
+        self.tick_size = Some(tick_size);
+        self
+    }
+
+    // Quantities must be whole multiples of `lot_size`.
+    pub fn lot_size(mut self, lot_size: u32) -> Self {
+        self.lot_size = Some(lot_size);
+        self
+    }
+
+    // Orders that would open a price level beyond `max_levels` on their
+    // side are refused.
+    pub fn max_levels(mut self, max_levels: usize) -> Self {
+        self.max_levels = Some(max_levels);
+        self
+    }
+
+    // An order resting when the book opens, subject to the same checks as
+    // later orders. Orders get ids in the order they are seeded.
+    pub fn seed_order(mut self, side: BuyOrSell, price: P, quantity: u32, timestamp: u64) -> Self {
+        self.orders.push((side, price, quantity, timestamp));
+        self
+    }
+
+    // The book, or the first seeded order its configuration refuses. Seeded
+    // orders are not matched, so one that would cross the book is refused.
+    pub fn build(self) -> Result<OrderBook<P>, BookRejection<P>> {
+        let mut orderbook = OrderBook::configured(self.tick_size, self.lot_size, self.max_levels);
+        for (side, price, quantity, timestamp) in self.orders {
+            orderbook.check_order(side, price, quantity)?;
+            orderbook.add_order(side, price, quantity, timestamp);
+            if orderbook.is_crossed() {
+                return Err(BookRejection::Crossed { price });
+            }
+        }
+        Ok(orderbook)
+    }
+}
+
+impl TradeEngine {
+    // List a token with a book built elsewhere, e.g. by `OrderBookBuilder`.
+    // Does nothing if the token is already listed.
+    pub fn list_new_token_with_book(&mut self, token_ticker: TokenTicker, orderbook: OrderBook) {
+        self.order_books.entry(token_ticker).or_insert(orderbook);
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::engine::OrderError;
+
+    #[test]
+    fn test_built_book_enforces_its_configuration() {
+        assert_eq!(
+            OrderBook::builder()
+                .lot_size(10)
+                .seed_order(BuyOrSell::Buy, 99.5, 15, 0)
+                .build()
+                .err(),
+            Some(BookRejection::OffLot {
+                quantity: 15,
+                lot_size: 10
+            })
+        );
+        assert_eq!(
+            OrderBook::builder()
+                .seed_order(BuyOrSell::Sell, 100.0, 10, 0)
+                .seed_order(BuyOrSell::Buy, 100.0, 10, 0)
+                .build()
+                .err(),
+            Some(BookRejection::Crossed { price: 100.0 })
+        );
+
+        let orderbook = OrderBook::builder()
+            .tick_size(0.05)
+            .lot_size(10)
+            .max_levels(2)
+            .seed_order(BuyOrSell::Buy, 99.95, 10, 0)
+            .seed_order(BuyOrSell::Buy, 99.9, 20, 0)
+            .seed_order(BuyOrSell::Sell, 100.05, 10, 0)
+            .build()
+            .unwrap();
+        assert_eq!(orderbook.depth_snapshot().bids.len(), 2);
+
+        let mut engine = TradeEngine::new();
+        engine.list_new_token_with_book(TokenTicker::ETH, orderbook);
+        let mut submit = |price, quantity| {
+            engine.submit_order(&TokenTicker::ETH, BuyOrSell::Buy, price, quantity)
+        };
+        assert_eq!(
+            submit(99.93, 10),
+            Err(OrderError::Book(BookRejection::OffTick {
+                price: 99.93,
+                tick_size: 0.05
+            }))
+        );
+        assert_eq!(
+            submit(99.85, 10),
+            Err(OrderError::Book(BookRejection::TooManyLevels {
+                max_levels: 2
+            }))
+        );
+        // joining an existing level is fine
+        assert_eq!(submit(99.9, 30), Ok(4));
+    }
+}
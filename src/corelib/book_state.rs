@@ -0,0 +1,109 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use super::order::{BuyOrSell, OrderMetadata, TimeInForce, Wallet};
+use super::orderbook::OrderBook;
+use super::price::PriceLike;
+
+// Everything about a resting order that affects how the book trades from
+// here on. Orders with equal states are interchangeable.
+#[derive(Debug, PartialEq, Hash)]
+struct RestingState<'a, K> {
+    side: BuyOrSell,
+    price: K,
+    id: u64,
+    quantity: u32,
+    timestamp: u64,
+    wallet: Option<&'a Wallet>,
+    time_in_force: TimeInForce,
+    priority_fee: u64,
+    metadata: Option<&'a OrderMetadata>,
+}
+
+impl<P: PriceLike> OrderBook<P> {
+    // Resting orders bids first, best price first, in queue order within a
+    // level. Independent of how the book's maps happen to iterate.
+    fn canonical_state(&self) -> Vec<RestingState<'_, P::Key>> {
+        let mut state = Vec::new();
+        for side in [BuyOrSell::Buy, BuyOrSell::Sell] {
+            for (_, orders) in self.levels(side) {
+                state.extend(orders.iter().map(|order| RestingState {
+                    side,
+                    price: order.price.key(),
+                    id: order.id,
+                    quantity: order.quantity,
+                    timestamp: order.timestamp,
+                    wallet: order.wallet.as_ref(),
+                    time_in_force: order.time_in_force,
+                    priority_fee: order.priority_fee,
+                    metadata: order.metadata.as_ref(),
+                }));
+            }
+        }
+        state
+    }
+
+    // Hash of the resting orders, their queue positions and the next order
+    // id. Books with the same digest trade identically from here on; the
+    // event history does not count. Stable within a build, not across
+    // Rust versions.
+    pub fn state_digest(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.next_order_id().hash(&mut hasher);
+        self.canonical_state().hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+// Panics describing the first difference if the books' resting state, as
+// covered by `state_digest`, differs.
+pub fn assert_books_equal<P: PriceLike>(left: &OrderBook<P>, right: &OrderBook<P>) {
+    assert_eq!(
+        left.next_order_id(),
+        right.next_order_id(),
+        "books would number their next orders differently"
+    );
+    let (left, right) = (left.canonical_state(), right.canonical_state());
+    if let Some(index) = (0..left.len().max(right.len())).find(|&i| left.get(i) != right.get(i)) {
+        panic!(
+            "books differ at resting order {}: {:?} != {:?}",
+            index,
+            left.get(index),
+            right.get(index)
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_equal_books_ignore_map_order_but_not_queue_order() {
+        let build = |sells: &[(f64, u32)]| {
+            let mut book = OrderBook::new();
+            for price in [99.0, 98.0, 97.0, 96.0] {
+                book.add_order(BuyOrSell::Buy, price, 1, 0);
+            }
+            for &(price, quantity) in sells {
+                book.add_order(BuyOrSell::Sell, price, quantity, 0);
+            }
+            book
+        };
+        let book = build(&[(101.0, 1), (101.0, 2), (102.0, 3)]);
+        let same = build(&[(101.0, 1), (101.0, 2), (102.0, 3)]);
+        assert_eq!(book.state_digest(), same.state_digest());
+        assert_books_equal(&book, &same);
+
+        // same levels and quantities, other queue order
+        let swapped = build(&[(101.0, 2), (101.0, 1), (102.0, 3)]);
+        assert_eq!(book.depth_snapshot(), swapped.depth_snapshot());
+        assert_ne!(book.state_digest(), swapped.state_digest());
+        let message = std::panic::catch_unwind(|| assert_books_equal(&book, &swapped))
+            .unwrap_err()
+            .downcast::<String>()
+            .unwrap();
+        assert!(message.starts_with("books differ at resting order 4:"));
+    }
+}
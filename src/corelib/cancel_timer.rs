@@ -0,0 +1,134 @@
+use super::engine::TradeEngine;
+use super::order::Wallet;
+use super::token::TokenTicker;
+
+// Cancel-on-disconnect timer: unless refreshed, the wallet's orders are
+// cancelled once the clock reaches `deadline_millis`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CancelTimer {
+    pub ttl_millis: u64,
+    pub deadline_millis: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CancelTimerFired {
+    pub wallet: Wallet,
+    pub timestamp: u64,
+    // Cancelled order ids per instrument.
+    pub cancelled: Vec<(TokenTicker, Vec<u64>)>,
+}
+
+impl TradeEngine {
+    // Arm, or re-arm with a new TTL, the wallet's dead man's switch.
+    pub fn arm_cancel_on_timeout(&mut self, wallet: &Wallet, ttl_millis: u64) {
+        let deadline_millis = self.now() + ttl_millis;
+        self.cancel_timers.insert(
+            wallet.clone(),
+            CancelTimer {
+                ttl_millis,
+                deadline_millis,
+            },
+        );
+    }
+
+    // Heartbeat: push the deadline one TTL past now. Returns false when no
+    // timer is armed, including one that already fired.
+    pub fn refresh_cancel_timer(&mut self, wallet: &Wallet) -> bool {
+        self.update_cancel_timers();
+        let now = self.now();
+        match self.cancel_timers.get_mut(wallet) {
+            Some(timer) => {
+                timer.deadline_millis = now + timer.ttl_millis;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn disarm_cancel_timer(&mut self, wallet: &Wallet) -> Option<CancelTimer> {
+        self.cancel_timers.remove(wallet)
+    }
+
+    // Fire every timer whose deadline has passed. A fired timer is disarmed.
+    // Called on order entry and matching, like session updates.
+    pub fn update_cancel_timers(&mut self) -> Vec<CancelTimerFired> {
+        let now = self.now();
+        let mut expired: Vec<Wallet> = self
+            .cancel_timers
+            .iter()
+            .filter(|(_, timer)| timer.deadline_millis <= now)
+            .map(|(wallet, _)| wallet.clone())
+            .collect();
+        expired.sort_by(|a, b| a.address.cmp(&b.address));
+
+        let mut fired = Vec::new();
+        for wallet in expired {
+            self.cancel_timers.remove(&wallet);
+            let mut cancelled: Vec<(TokenTicker, Vec<u64>)> = self
+                .order_books
+                .iter_mut()
+                .map(|(ticker, orderbook)| {
+                    (ticker.clone(), orderbook.cancel_wallet_orders(&wallet, now))
+                })
+                .filter(|(_, orders)| !orders.is_empty())
+                .collect();
+            cancelled.sort();
+            fired.push(CancelTimerFired {
+                wallet,
+                timestamp: now,
+                cancelled,
+            });
+        }
+        fired
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::clock::SimulatedClock;
+    use crate::corelib::order::{BuyOrSell, TimeInForce};
+
+    #[test]
+    fn test_orders_cancelled_unless_refreshed() {
+        let clock = SimulatedClock::new(0);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        let wallet = Wallet::new(String::from("timerwallet"));
+        let other = Wallet::new(String::from("timerother"));
+        engine.list_new_token(TokenTicker::ETH);
+        for owner in [&wallet, &other] {
+            engine
+                .submit_wallet_order(
+                    owner,
+                    &TokenTicker::ETH,
+                    BuyOrSell::Buy,
+                    100.0,
+                    1,
+                    TimeInForce::GoodTillCancel,
+                )
+                .unwrap();
+        }
+        engine.arm_cancel_on_timeout(&wallet, 5_000);
+
+        clock.advance(4_000);
+        assert!(engine.refresh_cancel_timer(&wallet));
+        clock.advance(4_000);
+        assert!(engine.update_cancel_timers().is_empty());
+
+        clock.advance(1_000);
+        let fired = engine.update_cancel_timers();
+        assert_eq!(
+            fired,
+            vec![CancelTimerFired {
+                wallet: wallet.clone(),
+                timestamp: 9_000,
+                cancelled: vec![(TokenTicker::ETH, vec![1])],
+            }]
+        );
+        assert!(!engine.refresh_cancel_timer(&wallet));
+        let orderbook = engine.get_token_order_book(&TokenTicker::ETH).unwrap();
+        assert_eq!(orderbook.wallet_orders(&wallet).count(), 0);
+        assert_eq!(orderbook.wallet_orders(&other).count(), 1);
+    }
+}
@@ -0,0 +1,73 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use chrono::Utc;
+
+// Time source for everything in the engine that expires or is scheduled.
+// Timestamps are milliseconds since the unix epoch.
+pub trait Clock: Send {
+    fn now_millis(&self) -> u64;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_millis(&self) -> u64 {
+        Utc::now().timestamp_millis().try_into().unwrap_or(0)
+    }
+}
+
+// One clock read from several places, e.g. the engine and its ledger's
+// balance history. Replacing the inner clock affects every handle.
+#[derive(Clone)]
+pub struct SharedClock {
+    inner: Arc<Mutex<Box<dyn Clock>>>,
+}
+
+impl SharedClock {
+    pub fn new(clock: Box<dyn Clock>) -> SharedClock {
+        SharedClock {
+            inner: Arc::new(Mutex::new(clock)),
+        }
+    }
+
+    pub fn replace(&self, clock: Box<dyn Clock>) {
+        *self.inner.lock().unwrap() = clock;
+    }
+}
+
+impl Clock for SharedClock {
+    fn now_millis(&self) -> u64 {
+        self.inner.lock().unwrap().now_millis()
+    }
+}
+
+// Manually driven clock for simulations and tests. Clones share the same
+// time, so a handle kept outside the engine can advance it.
+#[derive(Debug, Clone, Default)]
+pub struct SimulatedClock {
+    now: Arc<AtomicU64>,
+}
+
+impl SimulatedClock {
+    pub fn new(start_millis: u64) -> SimulatedClock {
+        SimulatedClock {
+            now: Arc::new(AtomicU64::new(start_millis)),
+        }
+    }
+
+    pub fn set(&self, millis: u64) {
+        self.now.store(millis, Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, millis: u64) {
+        self.now.fetch_add(millis, Ordering::SeqCst);
+    }
+}
+
+impl Clock for SimulatedClock {
+    fn now_millis(&self) -> u64 {
+        self.now.load(Ordering::SeqCst)
+    }
+}
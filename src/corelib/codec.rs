@@ -0,0 +1,884 @@
+use std::fmt;
+
+use super::admin::AdminCommand;
+use super::command::{Command, CommandResult};
+use super::events::BookEvent;
+use super::order::{BuyOrSell, OrderMetadata, TimeInForce};
+use super::token::{Pair, TokenTicker};
+use super::wal::WalRecord;
+
+#[cfg(feature = "cbor")]
+pub mod cbor;
+// Always built, since replay output is written as JSON lines; the feature
+// only adds `Format::Json`.
+pub mod json;
+#[cfg(feature = "msgpack")]
+pub mod msgpack;
+
+// Written into every envelope. Older versions are upgraded on read, newer
+// ones rejected.
+//
+// 1: the original model.
+// 2: order commands carry a time in force.
+pub const SCHEMA_VERSION: u64 = 2;
+
+// Deepest nesting of arrays and maps the decoders accept. Anything deeper is
+// rejected as malformed instead of recursing until the stack runs out.
+const MAX_DEPTH: usize = 128;
+
+// Format-neutral tree that events and commands encode to. Each wire format
+// only has to map this onto its own types.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    UInt(u64),
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Array(Vec<Value>),
+    // Entries keep their insertion order.
+    Map(Vec<(String, Value)>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    // Not valid in the wire format.
+    Malformed,
+    UnsupportedVersion(u64),
+    // Valid in the wire format but not the shape of the expected type.
+    Schema(String),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Malformed => write!(f, "malformed input"),
+            DecodeError::UnsupportedVersion(version) => {
+                write!(f, "unsupported schema version {}", version)
+            }
+            DecodeError::Schema(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+fn schema(message: &str) -> DecodeError {
+    DecodeError::Schema(String::from(message))
+}
+
+impl Value {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Map(entries) => entries
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn field(&self, key: &str) -> Result<&Value, DecodeError> {
+        self.get(key)
+            .ok_or_else(|| DecodeError::Schema(format!("missing field `{}`", key)))
+    }
+
+    pub fn as_u64(&self) -> Result<u64, DecodeError> {
+        match self {
+            Value::UInt(n) => Ok(*n),
+            Value::Int(n) if *n >= 0 => Ok(*n as u64),
+            _ => Err(schema("expected an unsigned integer")),
+        }
+    }
+
+    pub fn as_u32(&self) -> Result<u32, DecodeError> {
+        self.as_u64()?
+            .try_into()
+            .map_err(|_| schema("integer out of range"))
+    }
+
+    pub fn as_f64(&self) -> Result<f64, DecodeError> {
+        match self {
+            Value::Float(x) => Ok(*x),
+            Value::UInt(n) => Ok(*n as f64),
+            Value::Int(n) => Ok(*n as f64),
+            _ => Err(schema("expected a number")),
+        }
+    }
+
+    pub fn as_str(&self) -> Result<&str, DecodeError> {
+        match self {
+            Value::Str(s) => Ok(s),
+            _ => Err(schema("expected a string")),
+        }
+    }
+
+    // Name of a tagged enum variant.
+    pub fn kind(&self) -> Result<&str, DecodeError> {
+        self.field("type")?.as_str()
+    }
+}
+
+fn tagged(kind: &str, fields: Vec<(&str, Value)>) -> Value {
+    let mut entries = vec![(String::from("type"), Value::Str(String::from(kind)))];
+    entries.extend(
+        fields
+            .into_iter()
+            .map(|(name, value)| (String::from(name), value)),
+    );
+    Value::Map(entries)
+}
+
+// Absent or null when the request does not check the book sequence.
+fn expected_sequence(value: &Value) -> Result<Option<u64>, DecodeError> {
+    match value.get("expected_sequence") {
+        None | Some(Value::Null) => Ok(None),
+        Some(sequence) => Ok(Some(sequence.as_u64()?)),
+    }
+}
+
+// Events of untagged orders encode as they did before orders carried
+// metadata. Tags are `[key, value]` pairs, in order.
+fn with_metadata(mut event: Value, metadata: &Option<OrderMetadata>) -> Value {
+    if let (Value::Map(entries), Some(metadata)) = (&mut event, metadata) {
+        let tags = metadata
+            .iter()
+            .map(|(key, value)| {
+                Value::Array(vec![Value::Str(key.clone()), Value::Str(value.clone())])
+            })
+            .collect();
+        entries.push((String::from("metadata"), Value::Array(tags)));
+    }
+    event
+}
+
+fn metadata(value: &Value) -> Result<Option<OrderMetadata>, DecodeError> {
+    let tags = match value.get("metadata") {
+        None | Some(Value::Null) => return Ok(None),
+        Some(Value::Array(tags)) => tags,
+        Some(_) => return Err(schema("expected an array of tags")),
+    };
+    let tags = tags
+        .iter()
+        .map(|tag| match tag {
+            Value::Array(pair) if pair.len() == 2 => Ok((
+                String::from(pair[0].as_str()?),
+                String::from(pair[1].as_str()?),
+            )),
+            _ => Err(schema("expected a [key, value] tag")),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Some(tags.into()))
+}
+
+fn unknown(what: &str, kind: &str) -> DecodeError {
+    DecodeError::Schema(format!("unknown {} `{}`", what, kind))
+}
+
+pub trait Encode {
+    fn encode(&self) -> Value;
+}
+
+pub trait Decode: Sized {
+    fn decode(value: &Value) -> Result<Self, DecodeError>;
+}
+
+// Wire formats compiled in through the `json`, `msgpack` and `cbor` features.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    #[cfg(feature = "json")]
+    Json,
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+    #[cfg(feature = "cbor")]
+    Cbor,
+}
+
+impl Format {
+    #[cfg_attr(
+        not(any(feature = "json", feature = "msgpack", feature = "cbor")),
+        allow(unused_variables)
+    )]
+    pub fn write(self, value: &Value) -> Vec<u8> {
+        match self {
+            #[cfg(feature = "json")]
+            Format::Json => json::write(value),
+            #[cfg(feature = "msgpack")]
+            Format::MessagePack => msgpack::write(value),
+            #[cfg(feature = "cbor")]
+            Format::Cbor => cbor::write(value),
+        }
+    }
+
+    #[cfg_attr(
+        not(any(feature = "json", feature = "msgpack", feature = "cbor")),
+        allow(unused_variables)
+    )]
+    pub fn read(self, bytes: &[u8]) -> Result<Value, DecodeError> {
+        match self {
+            #[cfg(feature = "json")]
+            Format::Json => json::read(bytes),
+            #[cfg(feature = "msgpack")]
+            Format::MessagePack => msgpack::read(bytes),
+            #[cfg(feature = "cbor")]
+            Format::Cbor => cbor::read(bytes),
+        }
+    }
+}
+
+// Encode `item` in an envelope tagged with the schema version.
+pub fn to_bytes<T: Encode>(item: &T, format: Format) -> Vec<u8> {
+    format.write(&Value::Map(vec![
+        (String::from("version"), Value::UInt(SCHEMA_VERSION)),
+        (String::from("data"), item.encode()),
+    ]))
+}
+
+pub fn from_bytes<T: Decode>(bytes: &[u8], format: Format) -> Result<T, DecodeError> {
+    let envelope = format.read(bytes)?;
+    let version = envelope.field("version")?.as_u64()?;
+    if !(1..=SCHEMA_VERSION).contains(&version) {
+        return Err(DecodeError::UnsupportedVersion(version));
+    }
+    let mut data = envelope.field("data")?.clone();
+    for from in version..SCHEMA_VERSION {
+        data = upgrade(from, data);
+    }
+    T::decode(&data)
+}
+
+// Rewrite data of schema version `from` into version `from + 1`.
+fn upgrade(from: u64, data: Value) -> Value {
+    match from {
+        1 => upgrade_v1(data),
+        _ => data,
+    }
+}
+
+// Orders written before version 2 were all good till cancel.
+fn upgrade_v1(data: Value) -> Value {
+    match data {
+        Value::Map(mut entries) => {
+            let is_order = entries.iter().any(|(key, value)| {
+                key == "type" && *value == Value::Str(String::from("SubmitOrder"))
+            });
+            if is_order && !entries.iter().any(|(key, _)| key == "time_in_force") {
+                entries.push((
+                    String::from("time_in_force"),
+                    TimeInForce::GoodTillCancel.encode(),
+                ));
+            }
+            Value::Map(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, upgrade_v1(value)))
+                    .collect(),
+            )
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(upgrade_v1).collect()),
+        value => value,
+    }
+}
+
+// Listed tickers by name; LP and basket tickers as tagged maps.
+impl Encode for TokenTicker {
+    fn encode(&self) -> Value {
+        match self {
+            TokenTicker::Lp(pair) => tagged(
+                "Lp",
+                vec![
+                    ("ticker_a", pair.ticker_a.encode()),
+                    ("ticker_b", pair.ticker_b.encode()),
+                ],
+            ),
+            TokenTicker::Basket(name) => tagged("Basket", vec![("name", Value::Str(name.clone()))]),
+            ticker => Value::Str(format!("{:?}", ticker)),
+        }
+    }
+}
+
+impl Decode for TokenTicker {
+    fn decode(value: &Value) -> Result<Self, DecodeError> {
+        if let Value::Str(name) = value {
+            return TokenTicker::ALL
+                .iter()
+                .find(|ticker| format!("{:?}", ticker) == *name)
+                .cloned()
+                .ok_or_else(|| unknown("ticker", name));
+        }
+        match value.kind()? {
+            "Lp" => Ok(TokenTicker::lp(Pair::new(
+                TokenTicker::decode(value.field("ticker_a")?)?,
+                TokenTicker::decode(value.field("ticker_b")?)?,
+            ))),
+            "Basket" => Ok(TokenTicker::Basket(String::from(
+                value.field("name")?.as_str()?,
+            ))),
+            kind => Err(unknown("ticker", kind)),
+        }
+    }
+}
+
+impl Encode for BuyOrSell {
+    fn encode(&self) -> Value {
+        Value::Str(format!("{:?}", self))
+    }
+}
+
+impl Decode for BuyOrSell {
+    fn decode(value: &Value) -> Result<Self, DecodeError> {
+        match value.as_str()? {
+            "Buy" => Ok(BuyOrSell::Buy),
+            "Sell" => Ok(BuyOrSell::Sell),
+            side => Err(unknown("side", side)),
+        }
+    }
+}
+
+impl Encode for TimeInForce {
+    fn encode(&self) -> Value {
+        Value::Str(format!("{:?}", self))
+    }
+}
+
+impl Decode for TimeInForce {
+    fn decode(value: &Value) -> Result<Self, DecodeError> {
+        match value.as_str()? {
+            "GoodTillCancel" => Ok(TimeInForce::GoodTillCancel),
+            "Day" => Ok(TimeInForce::Day),
+            time_in_force => Err(unknown("time in force", time_in_force)),
+        }
+    }
+}
+
+impl Encode for BookEvent {
+    fn encode(&self) -> Value {
+        match self {
+            BookEvent::OrderAdded {
+                order_id,
+                side,
+                price,
+                quantity,
+                timestamp,
+                metadata,
+            } => with_metadata(
+                tagged(
+                    "OrderAdded",
+                    vec![
+                        ("order_id", Value::UInt(*order_id)),
+                        ("side", side.encode()),
+                        ("price", Value::Float(*price)),
+                        ("quantity", Value::UInt(*quantity as u64)),
+                        ("timestamp", Value::UInt(*timestamp)),
+                    ],
+                ),
+                metadata,
+            ),
+            BookEvent::OrderFilled {
+                order_id,
+                side,
+                price,
+                filled,
+                remaining,
+                timestamp,
+                metadata,
+            } => with_metadata(
+                tagged(
+                    "OrderFilled",
+                    vec![
+                        ("order_id", Value::UInt(*order_id)),
+                        ("side", side.encode()),
+                        ("price", Value::Float(*price)),
+                        ("filled", Value::UInt(*filled as u64)),
+                        ("remaining", Value::UInt(*remaining as u64)),
+                        ("timestamp", Value::UInt(*timestamp)),
+                    ],
+                ),
+                metadata,
+            ),
+            BookEvent::OrderCancelled {
+                order_id,
+                side,
+                price,
+                remaining,
+                timestamp,
+                metadata,
+            } => with_metadata(
+                tagged(
+                    "OrderCancelled",
+                    vec![
+                        ("order_id", Value::UInt(*order_id)),
+                        ("side", side.encode()),
+                        ("price", Value::Float(*price)),
+                        ("remaining", Value::UInt(*remaining as u64)),
+                        ("timestamp", Value::UInt(*timestamp)),
+                    ],
+                ),
+                metadata,
+            ),
+            BookEvent::OrderRescaled {
+                order_id,
+                side,
+                old_price,
+                price,
+                old_quantity,
+                quantity,
+                timestamp,
+                metadata,
+            } => with_metadata(
+                tagged(
+                    "OrderRescaled",
+                    vec![
+                        ("order_id", Value::UInt(*order_id)),
+                        ("side", side.encode()),
+                        ("old_price", Value::Float(*old_price)),
+                        ("price", Value::Float(*price)),
+                        ("old_quantity", Value::UInt(*old_quantity as u64)),
+                        ("quantity", Value::UInt(*quantity as u64)),
+                        ("timestamp", Value::UInt(*timestamp)),
+                    ],
+                ),
+                metadata,
+            ),
+            BookEvent::PriorityFeeSet {
+                order_id,
+                side,
+                price,
+                quantity,
+                priority_fee,
+                timestamp,
+                metadata,
+            } => with_metadata(
+                tagged(
+                    "PriorityFeeSet",
+                    vec![
+                        ("order_id", Value::UInt(*order_id)),
+                        ("side", side.encode()),
+                        ("price", Value::Float(*price)),
+                        ("quantity", Value::UInt(*quantity as u64)),
+                        ("priority_fee", Value::UInt(*priority_fee)),
+                        ("timestamp", Value::UInt(*timestamp)),
+                    ],
+                ),
+                metadata,
+            ),
+        }
+    }
+}
+
+impl Decode for BookEvent {
+    fn decode(value: &Value) -> Result<Self, DecodeError> {
+        let order_id = value.field("order_id")?.as_u64()?;
+        let side = BuyOrSell::decode(value.field("side")?)?;
+        let price = value.field("price")?.as_f64()?;
+        let timestamp = value.field("timestamp")?.as_u64()?;
+        let metadata = metadata(value)?;
+        match value.kind()? {
+            "OrderAdded" => Ok(BookEvent::OrderAdded {
+                order_id,
+                side,
+                price,
+                quantity: value.field("quantity")?.as_u32()?,
+                timestamp,
+                metadata,
+            }),
+            "OrderFilled" => Ok(BookEvent::OrderFilled {
+                order_id,
+                side,
+                price,
+                filled: value.field("filled")?.as_u32()?,
+                remaining: value.field("remaining")?.as_u32()?,
+                timestamp,
+                metadata,
+            }),
+            "OrderCancelled" => Ok(BookEvent::OrderCancelled {
+                order_id,
+                side,
+                price,
+                remaining: value.field("remaining")?.as_u32()?,
+                timestamp,
+                metadata,
+            }),
+            "OrderRescaled" => Ok(BookEvent::OrderRescaled {
+                order_id,
+                side,
+                old_price: value.field("old_price")?.as_f64()?,
+                price,
+                old_quantity: value.field("old_quantity")?.as_u32()?,
+                quantity: value.field("quantity")?.as_u32()?,
+                timestamp,
+                metadata,
+            }),
+            "PriorityFeeSet" => Ok(BookEvent::PriorityFeeSet {
+                order_id,
+                side,
+                price,
+                quantity: value.field("quantity")?.as_u32()?,
+                priority_fee: value.field("priority_fee")?.as_u64()?,
+                timestamp,
+                metadata,
+            }),
+            kind => Err(unknown("book event", kind)),
+        }
+    }
+}
+
+impl Encode for AdminCommand {
+    fn encode(&self) -> Value {
+        match self {
+            AdminCommand::SetFeeSchedule {
+                maker_bps,
+                taker_bps,
+            } => tagged(
+                "SetFeeSchedule",
+                vec![
+                    ("maker_bps", Value::UInt(*maker_bps)),
+                    ("taker_bps", Value::UInt(*taker_bps)),
+                ],
+            ),
+            AdminCommand::SetOpenNotionalLimit { ticker, limit } => tagged(
+                "SetOpenNotionalLimit",
+                vec![
+                    ("ticker", ticker.encode()),
+                    ("limit", limit.map_or(Value::Null, Value::UInt)),
+                ],
+            ),
+            AdminCommand::HaltInstrument { ticker } => {
+                tagged("HaltInstrument", vec![("ticker", ticker.encode())])
+            }
+            AdminCommand::ResumeInstrument { ticker } => {
+                tagged("ResumeInstrument", vec![("ticker", ticker.encode())])
+            }
+            AdminCommand::RotateLog => tagged("RotateLog", vec![]),
+        }
+    }
+}
+
+impl Decode for AdminCommand {
+    fn decode(value: &Value) -> Result<Self, DecodeError> {
+        let ticker = || TokenTicker::decode(value.field("ticker")?);
+        match value.kind()? {
+            "SetFeeSchedule" => Ok(AdminCommand::SetFeeSchedule {
+                maker_bps: value.field("maker_bps")?.as_u64()?,
+                taker_bps: value.field("taker_bps")?.as_u64()?,
+            }),
+            "SetOpenNotionalLimit" => Ok(AdminCommand::SetOpenNotionalLimit {
+                ticker: ticker()?,
+                limit: match value.field("limit")? {
+                    Value::Null => None,
+                    limit => Some(limit.as_u64()?),
+                },
+            }),
+            "HaltInstrument" => Ok(AdminCommand::HaltInstrument { ticker: ticker()? }),
+            "ResumeInstrument" => Ok(AdminCommand::ResumeInstrument { ticker: ticker()? }),
+            "RotateLog" => Ok(AdminCommand::RotateLog),
+            kind => Err(unknown("admin command", kind)),
+        }
+    }
+}
+
+impl Encode for Command {
+    fn encode(&self) -> Value {
+        match self {
+            Command::ListToken { ticker } => tagged("ListToken", vec![("ticker", ticker.encode())]),
+            Command::SubmitOrder {
+                ticker,
+                side,
+                price,
+                quantity,
+                time_in_force,
+                metadata,
+            } => with_metadata(
+                tagged(
+                    "SubmitOrder",
+                    vec![
+                        ("ticker", ticker.encode()),
+                        ("side", side.encode()),
+                        ("price", Value::Float(*price)),
+                        ("quantity", Value::UInt(*quantity as u64)),
+                        ("time_in_force", time_in_force.encode()),
+                    ],
+                ),
+                metadata,
+            ),
+            Command::CancelOrder {
+                ticker,
+                order_id,
+                expected_sequence,
+            } => tagged(
+                "CancelOrder",
+                vec![
+                    ("ticker", ticker.encode()),
+                    ("order_id", Value::UInt(*order_id)),
+                    (
+                        "expected_sequence",
+                        expected_sequence.map_or(Value::Null, Value::UInt),
+                    ),
+                ],
+            ),
+            Command::ReplaceOrder {
+                ticker,
+                order_id,
+                price,
+                quantity,
+                expected_sequence,
+            } => tagged(
+                "ReplaceOrder",
+                vec![
+                    ("ticker", ticker.encode()),
+                    ("order_id", Value::UInt(*order_id)),
+                    ("price", Value::Float(*price)),
+                    ("quantity", Value::UInt(*quantity as u64)),
+                    (
+                        "expected_sequence",
+                        expected_sequence.map_or(Value::Null, Value::UInt),
+                    ),
+                ],
+            ),
+            Command::MatchOrders => tagged("MatchOrders", vec![]),
+            Command::Admin(command) => tagged("Admin", vec![("command", command.encode())]),
+        }
+    }
+}
+
+impl Decode for Command {
+    fn decode(value: &Value) -> Result<Self, DecodeError> {
+        match value.kind()? {
+            "ListToken" => Ok(Command::ListToken {
+                ticker: TokenTicker::decode(value.field("ticker")?)?,
+            }),
+            "SubmitOrder" => Ok(Command::SubmitOrder {
+                ticker: TokenTicker::decode(value.field("ticker")?)?,
+                side: BuyOrSell::decode(value.field("side")?)?,
+                price: value.field("price")?.as_f64()?,
+                quantity: value.field("quantity")?.as_u32()?,
+                time_in_force: TimeInForce::decode(value.field("time_in_force")?)?,
+                metadata: metadata(value)?,
+            }),
+            "CancelOrder" => Ok(Command::CancelOrder {
+                ticker: TokenTicker::decode(value.field("ticker")?)?,
+                order_id: value.field("order_id")?.as_u64()?,
+                expected_sequence: expected_sequence(value)?,
+            }),
+            "ReplaceOrder" => Ok(Command::ReplaceOrder {
+                ticker: TokenTicker::decode(value.field("ticker")?)?,
+                order_id: value.field("order_id")?.as_u64()?,
+                price: value.field("price")?.as_f64()?,
+                quantity: value.field("quantity")?.as_u32()?,
+                expected_sequence: expected_sequence(value)?,
+            }),
+            "MatchOrders" => Ok(Command::MatchOrders),
+            "Admin" => Ok(Command::Admin(AdminCommand::decode(
+                value.field("command")?,
+            )?)),
+            kind => Err(unknown("command", kind)),
+        }
+    }
+}
+
+// Results are only produced, never read back.
+impl Encode for CommandResult {
+    fn encode(&self) -> Value {
+        match self {
+            CommandResult::TokenListed => tagged("TokenListed", vec![]),
+            CommandResult::AdminApplied => tagged("AdminApplied", vec![]),
+            CommandResult::OrderAccepted { order_id } => {
+                tagged("OrderAccepted", vec![("order_id", Value::UInt(*order_id))])
+            }
+            CommandResult::OrderRejected { reason } => tagged(
+                "OrderRejected",
+                vec![("reason", Value::Str(format!("{:?}", reason)))],
+            ),
+            CommandResult::OrderCancelled {
+                order_id,
+                remaining,
+            } => tagged(
+                "OrderCancelled",
+                vec![
+                    ("order_id", Value::UInt(*order_id)),
+                    ("remaining", Value::UInt(*remaining as u64)),
+                ],
+            ),
+            CommandResult::OrderReplaced {
+                order_id,
+                new_order_id,
+            } => tagged(
+                "OrderReplaced",
+                vec![
+                    ("order_id", Value::UInt(*order_id)),
+                    ("new_order_id", Value::UInt(*new_order_id)),
+                ],
+            ),
+            CommandResult::OrderNotFound { order_id } => {
+                tagged("OrderNotFound", vec![("order_id", Value::UInt(*order_id))])
+            }
+            CommandResult::StaleSequence { expected, current } => tagged(
+                "StaleSequence",
+                vec![
+                    ("expected", Value::UInt(*expected)),
+                    ("current", Value::UInt(*current)),
+                ],
+            ),
+            CommandResult::Matched { trades } => tagged(
+                "Matched",
+                vec![(
+                    "trades",
+                    Value::Array(
+                        trades
+                            .iter()
+                            .map(|(buy_order_id, sell_order_id, price, quantity)| {
+                                Value::Map(vec![
+                                    (String::from("buy_order_id"), Value::UInt(*buy_order_id)),
+                                    (String::from("sell_order_id"), Value::UInt(*sell_order_id)),
+                                    (String::from("price"), Value::Float(*price)),
+                                    (String::from("quantity"), Value::UInt(*quantity as u64)),
+                                ])
+                            })
+                            .collect(),
+                    ),
+                )],
+            ),
+        }
+    }
+}
+
+impl Encode for WalRecord {
+    fn encode(&self) -> Value {
+        Value::Map(vec![
+            (String::from("sequence"), Value::UInt(self.sequence)),
+            (String::from("timestamp"), Value::UInt(self.timestamp)),
+            (String::from("command"), self.command.encode()),
+        ])
+    }
+}
+
+impl Decode for WalRecord {
+    fn decode(value: &Value) -> Result<Self, DecodeError> {
+        Ok(WalRecord {
+            sequence: value.field("sequence")?.as_u64()?,
+            timestamp: value.field("timestamp")?.as_u64()?,
+            command: Command::decode(value.field("command")?)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    fn formats() -> Vec<Format> {
+        vec![
+            #[cfg(feature = "json")]
+            Format::Json,
+            #[cfg(feature = "msgpack")]
+            Format::MessagePack,
+            #[cfg(feature = "cbor")]
+            Format::Cbor,
+        ]
+    }
+
+    #[test]
+    fn test_round_trip_in_every_format() {
+        let records = [
+            WalRecord {
+                sequence: 1,
+                timestamp: 1_700_000_000_000,
+                command: Command::SubmitOrder {
+                    ticker: TokenTicker::lp(Pair::new(TokenTicker::ETH, TokenTicker::USDT)),
+                    side: BuyOrSell::Sell,
+                    price: 0.1,
+                    quantity: u32::MAX,
+                    time_in_force: TimeInForce::Day,
+                    metadata: Some(vec![(String::from("client_id"), String::from("c-1"))].into()),
+                },
+            },
+            WalRecord {
+                sequence: 2,
+                timestamp: 0,
+                command: Command::Admin(AdminCommand::SetOpenNotionalLimit {
+                    ticker: TokenTicker::Basket(String::from("Ünïcode \"basket\"\n")),
+                    limit: None,
+                }),
+            },
+        ];
+        let event = BookEvent::OrderFilled {
+            order_id: 7,
+            side: BuyOrSell::Buy,
+            price: -1.5e-9,
+            filled: 300,
+            remaining: 70_000,
+            timestamp: 12,
+            metadata: Some(
+                vec![
+                    (String::from("strategy"), String::from("mm-3")),
+                    (String::from("desk"), String::new()),
+                ]
+                .into(),
+            ),
+        };
+        let untagged = BookEvent::OrderCancelled {
+            order_id: 8,
+            side: BuyOrSell::Sell,
+            price: 2.0,
+            remaining: 1,
+            timestamp: 13,
+            metadata: None,
+        };
+        assert!(untagged.encode().get("metadata").is_none());
+        for format in formats() {
+            for record in records.iter() {
+                let bytes = to_bytes(record, format);
+                assert_eq!(from_bytes::<WalRecord>(&bytes, format).as_ref(), Ok(record));
+            }
+            for event in [&event, &untagged] {
+                let bytes = to_bytes(event, format);
+                assert_eq!(from_bytes::<BookEvent>(&bytes, format).as_ref(), Ok(event));
+                assert!(from_bytes::<BookEvent>(&bytes[..bytes.len() - 1], format).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn test_upgrades_version_1_orders() {
+        let order = tagged(
+            "SubmitOrder",
+            vec![
+                ("ticker", TokenTicker::ETH.encode()),
+                ("side", BuyOrSell::Buy.encode()),
+                ("price", Value::Float(10.0)),
+                ("quantity", Value::UInt(2)),
+            ],
+        );
+        for format in formats() {
+            let bytes = format.write(&Value::Map(vec![
+                (String::from("version"), Value::UInt(1)),
+                (String::from("data"), order.clone()),
+            ]));
+            assert_eq!(
+                from_bytes::<Command>(&bytes, format),
+                Ok(Command::SubmitOrder {
+                    ticker: TokenTicker::ETH,
+                    side: BuyOrSell::Buy,
+                    price: 10.0,
+                    quantity: 2,
+                    time_in_force: TimeInForce::GoodTillCancel,
+                    metadata: None,
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn test_rejects_unknown_version() {
+        for format in formats() {
+            let bytes = format.write(&Value::Map(vec![
+                (String::from("version"), Value::UInt(SCHEMA_VERSION + 1)),
+                (String::from("data"), Command::MatchOrders.encode()),
+            ]));
+            assert_eq!(
+                from_bytes::<Command>(&bytes, format),
+                Err(DecodeError::UnsupportedVersion(SCHEMA_VERSION + 1))
+            );
+        }
+    }
+}
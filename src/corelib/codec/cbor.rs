@@ -0,0 +1,216 @@
+use super::{DecodeError, Value, MAX_DEPTH};
+
+// CBOR (RFC 8949) with definite lengths only. Floats are always double
+// precision; tags and byte strings are not used.
+pub fn write(value: &Value) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_value(&mut out, value);
+    out
+}
+
+fn write_header(out: &mut Vec<u8>, major: u8, n: u64) {
+    let major = major << 5;
+    if n < 24 {
+        out.push(major | n as u8);
+    } else if n <= u8::MAX as u64 {
+        out.extend_from_slice(&[major | 24, n as u8]);
+    } else if n <= u16::MAX as u64 {
+        out.push(major | 25);
+        out.extend_from_slice(&(n as u16).to_be_bytes());
+    } else if n <= u32::MAX as u64 {
+        out.push(major | 26);
+        out.extend_from_slice(&(n as u32).to_be_bytes());
+    } else {
+        out.push(major | 27);
+        out.extend_from_slice(&n.to_be_bytes());
+    }
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) {
+    write_header(out, 3, s.len() as u64);
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn write_value(out: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::Null => out.push(0xf6),
+        Value::Bool(false) => out.push(0xf4),
+        Value::Bool(true) => out.push(0xf5),
+        Value::UInt(n) => write_header(out, 0, *n),
+        Value::Int(n) if *n >= 0 => write_header(out, 0, *n as u64),
+        // negative integers are stored as -1 - n
+        Value::Int(n) => write_header(out, 1, !*n as u64),
+        Value::Float(x) => {
+            out.push(0xfb);
+            out.extend_from_slice(&x.to_be_bytes());
+        }
+        Value::Str(s) => write_str(out, s),
+        Value::Array(items) => {
+            write_header(out, 4, items.len() as u64);
+            for item in items {
+                write_value(out, item);
+            }
+        }
+        Value::Map(entries) => {
+            write_header(out, 5, entries.len() as u64);
+            for (key, item) in entries {
+                write_str(out, key);
+                write_value(out, item);
+            }
+        }
+    }
+}
+
+pub fn read(bytes: &[u8]) -> Result<Value, DecodeError> {
+    let mut reader = Reader {
+        bytes,
+        position: 0,
+        depth: 0,
+    };
+    let value = reader.value()?;
+    if reader.position != bytes.len() {
+        return Err(DecodeError::Malformed);
+    }
+    Ok(value)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    // Arrays and maps currently open.
+    depth: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], DecodeError> {
+        let end = self
+            .position
+            .checked_add(length)
+            .ok_or(DecodeError::Malformed)?;
+        let bytes = self
+            .bytes
+            .get(self.position..end)
+            .ok_or(DecodeError::Malformed)?;
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn nested(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<Value, DecodeError>,
+    ) -> Result<Value, DecodeError> {
+        if self.depth == MAX_DEPTH {
+            return Err(DecodeError::Malformed);
+        }
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn uint(&mut self, length: usize) -> Result<u64, DecodeError> {
+        Ok(self
+            .take(length)?
+            .iter()
+            .fold(0, |n, byte| n << 8 | *byte as u64))
+    }
+
+    // The argument encoded in the low five bits of the initial byte.
+    fn argument(&mut self, info: u8) -> Result<u64, DecodeError> {
+        match info {
+            0..=23 => Ok(info as u64),
+            24..=27 => self.uint(1 << (info - 24)),
+            // indefinite lengths and reserved values
+            _ => Err(DecodeError::Malformed),
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, DecodeError> {
+        let initial = self.uint(1)? as u8;
+        let (major, info) = (initial >> 5, initial & 0x1f);
+        if major == 7 {
+            return match info {
+                20 => Ok(Value::Bool(false)),
+                21 => Ok(Value::Bool(true)),
+                22 => Ok(Value::Null),
+                26 => Ok(Value::Float(f32::from_bits(self.uint(4)? as u32) as f64)),
+                27 => Ok(Value::Float(f64::from_bits(self.uint(8)?))),
+                _ => Err(DecodeError::Malformed),
+            };
+        }
+        let argument = self.argument(info)?;
+        match major {
+            0 => Ok(Value::UInt(argument)),
+            1 => i64::try_from(argument)
+                .map(|n| Value::Int(!n))
+                .map_err(|_| DecodeError::Malformed),
+            3 => Ok(Value::Str(self.string(argument)?)),
+            4 => self.nested(|reader| {
+                let mut items = Vec::new();
+                for _ in 0..argument {
+                    items.push(reader.value()?);
+                }
+                Ok(Value::Array(items))
+            }),
+            5 => self.nested(|reader| {
+                let mut entries = Vec::new();
+                for _ in 0..argument {
+                    let Value::Str(key) = reader.value()? else {
+                        return Err(DecodeError::Malformed);
+                    };
+                    entries.push((key, reader.value()?));
+                }
+                Ok(Value::Map(entries))
+            }),
+            _ => Err(DecodeError::Malformed),
+        }
+    }
+
+    fn string(&mut self, length: u64) -> Result<String, DecodeError> {
+        let length = usize::try_from(length).map_err(|_| DecodeError::Malformed)?;
+        String::from_utf8(self.take(length)?.to_vec()).map_err(|_| DecodeError::Malformed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_rfc_examples() {
+        // from RFC 8949 appendix A
+        assert_eq!(
+            write(&Value::UInt(1_000_000)),
+            vec![0x1a, 0x00, 0x0f, 0x42, 0x40]
+        );
+        assert_eq!(write(&Value::Int(-100)), vec![0x38, 0x63]);
+        assert_eq!(
+            write(&Value::Str(String::from("IETF"))),
+            b"\x64IETF".to_vec()
+        );
+        assert_eq!(
+            read(&[0xa1, 0x61, 0x61, 0x82, 0x02, 0xf5]),
+            Ok(Value::Map(vec![(
+                String::from("a"),
+                Value::Array(vec![Value::UInt(2), Value::Bool(true)])
+            )]))
+        );
+        // indefinite-length array
+        assert_eq!(read(&[0x9f, 0x01, 0xff]), Err(DecodeError::Malformed));
+    }
+
+    #[test]
+    fn test_rejects_hostile_input() {
+        // a text string claiming u64::MAX bytes
+        assert_eq!(
+            read(&[0x7b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]),
+            Err(DecodeError::Malformed)
+        );
+        let deep = vec![0x81; 200_000];
+        assert_eq!(read(&deep), Err(DecodeError::Malformed));
+        let mut nested = vec![0x81; MAX_DEPTH - 1];
+        nested.push(0x80);
+        assert!(read(&nested).is_ok());
+    }
+}
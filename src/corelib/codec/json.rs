@@ -0,0 +1,310 @@
+use std::fmt::Write;
+
+use super::{DecodeError, Value, MAX_DEPTH};
+
+// Floats print in Rust's shortest round-trip form, which is valid JSON and
+// always carries a `.` or exponent, so they read back as floats. JSON has no
+// NaN or infinity; those are written as null.
+pub fn write(value: &Value) -> Vec<u8> {
+    let mut out = String::new();
+    write_value(&mut out, value);
+    out.into_bytes()
+}
+
+fn write_value(out: &mut String, value: &Value) {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(true) => out.push_str("true"),
+        Value::Bool(false) => out.push_str("false"),
+        Value::UInt(n) => write!(out, "{}", n).unwrap(),
+        Value::Int(n) => write!(out, "{}", n).unwrap(),
+        Value::Float(x) if x.is_finite() => write!(out, "{:?}", x).unwrap(),
+        Value::Float(_) => out.push_str("null"),
+        Value::Str(s) => write_str(out, s),
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_value(out, item);
+            }
+            out.push(']');
+        }
+        Value::Map(entries) => {
+            out.push('{');
+            for (i, (key, item)) in entries.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_str(out, key);
+                out.push(':');
+                write_value(out, item);
+            }
+            out.push('}');
+        }
+    }
+}
+
+fn write_str(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+pub fn read(bytes: &[u8]) -> Result<Value, DecodeError> {
+    let mut parser = Parser {
+        bytes,
+        position: 0,
+        depth: 0,
+    };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.position != bytes.len() {
+        return Err(DecodeError::Malformed);
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    // Arrays and objects currently open.
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Result<u8, DecodeError> {
+        let byte = self.peek().ok_or(DecodeError::Malformed)?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), DecodeError> {
+        self.skip_whitespace();
+        match self.next()? == byte {
+            true => Ok(()),
+            false => Err(DecodeError::Malformed),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .peek()
+            .is_some_and(|byte| matches!(byte, b' ' | b'\t' | b'\n' | b'\r'))
+        {
+            self.position += 1;
+        }
+    }
+
+    fn literal(&mut self, literal: &str, value: Value) -> Result<Value, DecodeError> {
+        let end = self.position + literal.len();
+        if self.bytes.get(self.position..end) != Some(literal.as_bytes()) {
+            return Err(DecodeError::Malformed);
+        }
+        self.position = end;
+        Ok(value)
+    }
+
+    fn value(&mut self) -> Result<Value, DecodeError> {
+        self.skip_whitespace();
+        match self.peek().ok_or(DecodeError::Malformed)? {
+            b'n' => self.literal("null", Value::Null),
+            b't' => self.literal("true", Value::Bool(true)),
+            b'f' => self.literal("false", Value::Bool(false)),
+            b'"' => Ok(Value::Str(self.string()?)),
+            b'[' => self.nested(Self::array),
+            b'{' => self.nested(Self::object),
+            b'-' | b'0'..=b'9' => self.number(),
+            _ => Err(DecodeError::Malformed),
+        }
+    }
+
+    fn nested(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<Value, DecodeError>,
+    ) -> Result<Value, DecodeError> {
+        if self.depth == MAX_DEPTH {
+            return Err(DecodeError::Malformed);
+        }
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn array(&mut self) -> Result<Value, DecodeError> {
+        self.expect(b'[')?;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.position += 1;
+            return Ok(Value::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.skip_whitespace();
+            match self.next()? {
+                b',' => continue,
+                b']' => return Ok(Value::Array(items)),
+                _ => return Err(DecodeError::Malformed),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Value, DecodeError> {
+        self.expect(b'{')?;
+        let mut entries = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.position += 1;
+            return Ok(Value::Map(entries));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.expect(b':')?;
+            entries.push((key, self.value()?));
+            self.skip_whitespace();
+            match self.next()? {
+                b',' => continue,
+                b'}' => return Ok(Value::Map(entries)),
+                _ => return Err(DecodeError::Malformed),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, DecodeError> {
+        if self.next()? != b'"' {
+            return Err(DecodeError::Malformed);
+        }
+        let mut bytes = Vec::new();
+        loop {
+            match self.next()? {
+                b'"' => break,
+                b'\\' => {
+                    let c = match self.next()? {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => self.escaped_char()?,
+                        _ => return Err(DecodeError::Malformed),
+                    };
+                    let mut buffer = [0; 4];
+                    bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+                }
+                byte => bytes.push(byte),
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| DecodeError::Malformed)
+    }
+
+    fn hex4(&mut self) -> Result<u32, DecodeError> {
+        let digits = self
+            .bytes
+            .get(self.position..self.position + 4)
+            .ok_or(DecodeError::Malformed)?;
+        let digits = std::str::from_utf8(digits).map_err(|_| DecodeError::Malformed)?;
+        let code = u32::from_str_radix(digits, 16).map_err(|_| DecodeError::Malformed)?;
+        self.position += 4;
+        Ok(code)
+    }
+
+    // The code point after `\u`, joining a surrogate pair.
+    fn escaped_char(&mut self) -> Result<char, DecodeError> {
+        let mut code = self.hex4()?;
+        if (0xd800..0xdc00).contains(&code) {
+            if self.next()? != b'\\' || self.next()? != b'u' {
+                return Err(DecodeError::Malformed);
+            }
+            let low = self.hex4()?;
+            if !(0xdc00..0xe000).contains(&low) {
+                return Err(DecodeError::Malformed);
+            }
+            code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+        }
+        char::from_u32(code).ok_or(DecodeError::Malformed)
+    }
+
+    fn number(&mut self) -> Result<Value, DecodeError> {
+        let start = self.position;
+        while self
+            .peek()
+            .is_some_and(|byte| matches!(byte, b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E'))
+        {
+            self.position += 1;
+        }
+        let text = std::str::from_utf8(&self.bytes[start..self.position])
+            .map_err(|_| DecodeError::Malformed)?;
+        let value = if text.contains(['.', 'e', 'E']) {
+            text.parse().map(Value::Float).ok()
+        } else if text.starts_with('-') {
+            text.parse().map(Value::Int).ok()
+        } else {
+            text.parse().map(Value::UInt).ok()
+        };
+        value.ok_or(DecodeError::Malformed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_reads_escapes_and_numbers() {
+        let value =
+            read(r#" {"a": [1, -2, 2.5e3, true, null], "b": "\u00e9\ud83d\ude00\t"} "#.as_bytes())
+                .unwrap();
+        assert_eq!(
+            value,
+            Value::Map(vec![
+                (
+                    String::from("a"),
+                    Value::Array(vec![
+                        Value::UInt(1),
+                        Value::Int(-2),
+                        Value::Float(2500.0),
+                        Value::Bool(true),
+                        Value::Null,
+                    ])
+                ),
+                (String::from("b"), Value::Str(String::from("é😀\t"))),
+            ])
+        );
+        assert_eq!(read(&write(&value)), Ok(value));
+        assert_eq!(read(b"[1,]"), Err(DecodeError::Malformed));
+    }
+
+    #[test]
+    fn test_rejects_hostile_input() {
+        let deep = "[".repeat(200_000);
+        assert_eq!(read(deep.as_bytes()), Err(DecodeError::Malformed));
+        let nested = format!("{}{}", "[".repeat(MAX_DEPTH), "]".repeat(MAX_DEPTH));
+        assert!(read(nested.as_bytes()).is_ok());
+        let deeper = format!(
+            "{{\"a\":{}1{}}}",
+            "[".repeat(MAX_DEPTH),
+            "]".repeat(MAX_DEPTH)
+        );
+        assert_eq!(read(deeper.as_bytes()), Err(DecodeError::Malformed));
+    }
+}
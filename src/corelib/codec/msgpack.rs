@@ -0,0 +1,254 @@
+use super::{DecodeError, Value, MAX_DEPTH};
+
+// MessagePack with the smallest encoding for every integer, string and
+// collection length. Floats are always float 64.
+pub fn write(value: &Value) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_value(&mut out, value);
+    out
+}
+
+fn write_value(out: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::Null => out.push(0xc0),
+        Value::Bool(false) => out.push(0xc2),
+        Value::Bool(true) => out.push(0xc3),
+        Value::UInt(n) => write_uint(out, *n),
+        Value::Int(n) if *n >= 0 => write_uint(out, *n as u64),
+        Value::Int(n) => write_negative(out, *n),
+        Value::Float(x) => {
+            out.push(0xcb);
+            out.extend_from_slice(&x.to_be_bytes());
+        }
+        Value::Str(s) => write_str(out, s),
+        Value::Array(items) => {
+            write_collection(out, items.len(), 0x90, 0xdc);
+            for item in items {
+                write_value(out, item);
+            }
+        }
+        Value::Map(entries) => {
+            write_collection(out, entries.len(), 0x80, 0xde);
+            for (key, item) in entries {
+                write_str(out, key);
+                write_value(out, item);
+            }
+        }
+    }
+}
+
+fn write_uint(out: &mut Vec<u8>, n: u64) {
+    if n < 0x80 {
+        out.push(n as u8);
+    } else if n <= u8::MAX as u64 {
+        out.extend_from_slice(&[0xcc, n as u8]);
+    } else if n <= u16::MAX as u64 {
+        out.push(0xcd);
+        out.extend_from_slice(&(n as u16).to_be_bytes());
+    } else if n <= u32::MAX as u64 {
+        out.push(0xce);
+        out.extend_from_slice(&(n as u32).to_be_bytes());
+    } else {
+        out.push(0xcf);
+        out.extend_from_slice(&n.to_be_bytes());
+    }
+}
+
+fn write_negative(out: &mut Vec<u8>, n: i64) {
+    if n >= -32 {
+        out.push(n as i8 as u8);
+    } else if n >= i8::MIN as i64 {
+        out.extend_from_slice(&[0xd0, n as i8 as u8]);
+    } else if n >= i16::MIN as i64 {
+        out.push(0xd1);
+        out.extend_from_slice(&(n as i16).to_be_bytes());
+    } else if n >= i32::MIN as i64 {
+        out.push(0xd2);
+        out.extend_from_slice(&(n as i32).to_be_bytes());
+    } else {
+        out.push(0xd3);
+        out.extend_from_slice(&n.to_be_bytes());
+    }
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) {
+    let len = s.len();
+    if len < 32 {
+        out.push(0xa0 | len as u8);
+    } else if len <= u8::MAX as usize {
+        out.extend_from_slice(&[0xd9, len as u8]);
+    } else if len <= u16::MAX as usize {
+        out.push(0xda);
+        out.extend_from_slice(&(len as u16).to_be_bytes());
+    } else {
+        out.push(0xdb);
+        out.extend_from_slice(&(len as u32).to_be_bytes());
+    }
+    out.extend_from_slice(s.as_bytes());
+}
+
+// `fixed` is the fix-size marker; `marker16` is followed by the 32-bit one.
+fn write_collection(out: &mut Vec<u8>, len: usize, fixed: u8, marker16: u8) {
+    if len < 16 {
+        out.push(fixed | len as u8);
+    } else if len <= u16::MAX as usize {
+        out.push(marker16);
+        out.extend_from_slice(&(len as u16).to_be_bytes());
+    } else {
+        out.push(marker16 + 1);
+        out.extend_from_slice(&(len as u32).to_be_bytes());
+    }
+}
+
+pub fn read(bytes: &[u8]) -> Result<Value, DecodeError> {
+    let mut reader = Reader {
+        bytes,
+        position: 0,
+        depth: 0,
+    };
+    let value = reader.value()?;
+    if reader.position != bytes.len() {
+        return Err(DecodeError::Malformed);
+    }
+    Ok(value)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    // Arrays and maps currently open.
+    depth: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], DecodeError> {
+        let end = self
+            .position
+            .checked_add(length)
+            .ok_or(DecodeError::Malformed)?;
+        let bytes = self
+            .bytes
+            .get(self.position..end)
+            .ok_or(DecodeError::Malformed)?;
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn nested(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<Value, DecodeError>,
+    ) -> Result<Value, DecodeError> {
+        if self.depth == MAX_DEPTH {
+            return Err(DecodeError::Malformed);
+        }
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    // Big-endian unsigned integer of `length` bytes.
+    fn uint(&mut self, length: usize) -> Result<u64, DecodeError> {
+        Ok(self
+            .take(length)?
+            .iter()
+            .fold(0, |n, byte| n << 8 | *byte as u64))
+    }
+
+    fn value(&mut self) -> Result<Value, DecodeError> {
+        let marker = self.uint(1)? as u8;
+        Ok(match marker {
+            0x00..=0x7f => Value::UInt(marker as u64),
+            0x80..=0x8f => self.nested(|reader| reader.map((marker & 0x0f) as usize))?,
+            0x90..=0x9f => self.nested(|reader| reader.array((marker & 0x0f) as usize))?,
+            0xa0..=0xbf => Value::Str(self.string((marker & 0x1f) as usize)?),
+            0xc0 => Value::Null,
+            0xc2 => Value::Bool(false),
+            0xc3 => Value::Bool(true),
+            0xca => Value::Float(f32::from_bits(self.uint(4)? as u32) as f64),
+            0xcb => Value::Float(f64::from_bits(self.uint(8)?)),
+            0xcc..=0xcf => Value::UInt(self.uint(1 << (marker - 0xcc))?),
+            0xd0 => Value::Int(self.uint(1)? as u8 as i8 as i64),
+            0xd1 => Value::Int(self.uint(2)? as u16 as i16 as i64),
+            0xd2 => Value::Int(self.uint(4)? as u32 as i32 as i64),
+            0xd3 => Value::Int(self.uint(8)? as i64),
+            0xd9..=0xdb => {
+                let length = self.uint(1 << (marker - 0xd9))? as usize;
+                Value::Str(self.string(length)?)
+            }
+            0xdc | 0xdd => {
+                let length = self.uint(2 << (marker - 0xdc))? as usize;
+                self.nested(|reader| reader.array(length))?
+            }
+            0xde | 0xdf => {
+                let length = self.uint(2 << (marker - 0xde))? as usize;
+                self.nested(|reader| reader.map(length))?
+            }
+            0xe0..=0xff => Value::Int(marker as i8 as i64),
+            _ => return Err(DecodeError::Malformed),
+        })
+    }
+
+    fn string(&mut self, length: usize) -> Result<String, DecodeError> {
+        String::from_utf8(self.take(length)?.to_vec()).map_err(|_| DecodeError::Malformed)
+    }
+
+    fn array(&mut self, length: usize) -> Result<Value, DecodeError> {
+        let mut items = Vec::new();
+        for _ in 0..length {
+            items.push(self.value()?);
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn map(&mut self, length: usize) -> Result<Value, DecodeError> {
+        let mut entries = Vec::new();
+        for _ in 0..length {
+            let Value::Str(key) = self.value()? else {
+                return Err(DecodeError::Malformed);
+            };
+            entries.push((key, self.value()?));
+        }
+        Ok(Value::Map(entries))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_smallest_encodings() {
+        let value = Value::Map(vec![(
+            String::from("n"),
+            Value::Array(vec![
+                Value::UInt(5),
+                Value::Int(-3),
+                Value::UInt(300),
+                Value::Int(-200),
+            ]),
+        )]);
+        let bytes = write(&value);
+        assert_eq!(
+            bytes,
+            vec![0x81, 0xa1, b'n', 0x94, 0x05, 0xfd, 0xcd, 0x01, 0x2c, 0xd1, 0xff, 0x38]
+        );
+        assert_eq!(read(&bytes), Ok(value));
+        assert_eq!(read(&[0x92, 0x01]), Err(DecodeError::Malformed));
+    }
+
+    #[test]
+    fn test_rejects_hostile_input() {
+        // a str 32 claiming u32::MAX bytes
+        assert_eq!(
+            read(&[0xdb, 0xff, 0xff, 0xff, 0xff]),
+            Err(DecodeError::Malformed)
+        );
+        let deep = vec![0x91; 200_000];
+        assert_eq!(read(&deep), Err(DecodeError::Malformed));
+        let mut nested = vec![0x91; MAX_DEPTH - 1];
+        nested.push(0x90);
+        assert!(read(&nested).is_ok());
+    }
+}
@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+
+use super::engine::TradeEngine;
+use super::margin::MarginEngine;
+use super::order::Wallet;
+use super::token::TokenTicker;
+
+// Tokens accepted as margin collateral with the fraction of their value
+// knocked off. Anything not listed counts for nothing.
+#[derive(Debug, Clone, Default)]
+pub struct CrossCollateral {
+    haircuts: HashMap<TokenTicker, f64>,
+}
+
+impl CrossCollateral {
+    pub fn new() -> CrossCollateral {
+        CrossCollateral::default()
+    }
+
+    // Rejects haircuts outside `0.0..=1.0`.
+    pub fn set_haircut(&mut self, token: TokenTicker, haircut: f64) -> bool {
+        if !(0.0..=1.0).contains(&haircut) {
+            return false;
+        }
+        self.haircuts.insert(token, haircut);
+        true
+    }
+
+    pub fn remove(&mut self, token: &TokenTicker) -> Option<f64> {
+        self.haircuts.remove(token)
+    }
+
+    pub fn haircut(&self, token: &TokenTicker) -> Option<f64> {
+        self.haircuts.get(token).copied()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CollateralComponent {
+    pub token: TokenTicker,
+    pub amount: u64,
+    pub price: f64,
+    pub haircut: f64,
+    // In the quote token, after the haircut.
+    pub value: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CollateralValuation {
+    pub value: f64,
+    pub components: Vec<CollateralComponent>,
+    // Held collateral tokens with no index price, left out of `value`.
+    pub unpriced: Vec<TokenTicker>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrossMargin {
+    pub collateral: CollateralValuation,
+    pub requirement: u64,
+    // Collateral value above the requirement; negative when short.
+    pub excess: f64,
+}
+
+impl TradeEngine {
+    // Value the wallet's free balances of every accepted collateral token at
+    // the index, in the quote token. The quote token itself is worth 1.
+    pub fn collateral_value(&self, wallet: &Wallet) -> CollateralValuation {
+        let mut tokens: Vec<(&TokenTicker, f64)> = self
+            .collateral
+            .haircuts
+            .iter()
+            .map(|(token, haircut)| (token, *haircut))
+            .collect();
+        tokens.sort_by_key(|(token, _)| *token);
+
+        let mut valuation = CollateralValuation::default();
+        for (token, haircut) in tokens {
+            let amount = self.ledger.free_balance(wallet, token);
+            if amount == 0 {
+                continue;
+            }
+            let price = if *token == self.quote_token {
+                Some(1.0)
+            } else {
+                self.index.latest(token)
+            };
+            let Some(price) = price else {
+                valuation.unpriced.push(token.clone());
+                continue;
+            };
+            let value = amount as f64 * price * (1.0 - haircut);
+            valuation.value += value;
+            valuation.components.push(CollateralComponent {
+                token: token.clone(),
+                amount,
+                price,
+                haircut,
+                value,
+            });
+        }
+        valuation
+    }
+
+    // The wallet's portfolio margin against its whole collateral pool.
+    pub fn cross_margin(&self, wallet: &Wallet, margin: &MarginEngine) -> CrossMargin {
+        let collateral = self.collateral_value(wallet);
+        let requirement = margin.portfolio_margin(wallet, &self.positions).requirement;
+        CrossMargin {
+            excess: collateral.value - requirement as f64,
+            collateral,
+            requirement,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::margin::{InstrumentKind, Position};
+
+    #[test]
+    fn test_haircut_collateral_covers_margin() {
+        let mut engine = TradeEngine::new();
+        let wallet = Wallet::new(String::from("crosswallet"));
+        assert!(!engine.collateral.set_haircut(TokenTicker::ETH, 1.5));
+        engine
+            .collateral
+            .set_haircut(engine.quote_token.clone(), 0.0);
+        engine.collateral.set_haircut(TokenTicker::ETH, 0.2);
+        engine.collateral.set_haircut(TokenTicker::SOL, 0.5);
+        let quote = engine.quote_token.clone();
+        engine.ledger.deposit(wallet.clone(), quote, 1_000).unwrap();
+        engine
+            .ledger
+            .deposit(wallet.clone(), TokenTicker::ETH, 2)
+            .unwrap();
+        engine
+            .ledger
+            .deposit(wallet.clone(), TokenTicker::SOL, 10)
+            .unwrap();
+        // not accepted as collateral
+        engine
+            .ledger
+            .deposit(wallet.clone(), TokenTicker::BTC, 1)
+            .unwrap();
+        engine.publish_index_price(TokenTicker::ETH, 3_000.0);
+        engine.publish_index_price(TokenTicker::BTC, 50_000.0);
+
+        let valuation = engine.collateral_value(&wallet);
+        assert_eq!(valuation.value, 1_000.0 + 4_800.0);
+        assert_eq!(valuation.unpriced, vec![TokenTicker::SOL]);
+
+        engine.positions.push(Position {
+            wallet: wallet.clone(),
+            underlying: TokenTicker::BTC,
+            kind: InstrumentKind::Perpetual,
+            quantity: 1,
+            entry_price: 50_000.0,
+            mark_price: 50_000.0,
+            collateral: 0,
+        });
+        let margin = engine.cross_margin(&wallet, &MarginEngine::new());
+        assert_eq!(margin.requirement, 7_500);
+        assert_eq!(margin.excess, -1_700.0);
+
+        engine.publish_index_price(TokenTicker::SOL, 400.0);
+        assert_eq!(
+            engine.cross_margin(&wallet, &MarginEngine::new()).excess,
+            300.0
+        );
+    }
+}
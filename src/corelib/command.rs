@@ -0,0 +1,717 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, VecDeque};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender, TrySendError};
+use std::thread::{self, JoinHandle};
+
+use super::admin::AdminCommand;
+use super::amend::AmendError;
+#[cfg(debug_assertions)]
+use super::conservation::check_conserved;
+use super::engine::{OrderError, TradeEngine};
+use super::order::{BuyOrSell, OrderMetadata, TimeInForce};
+use super::speed_bump::SpeedBump;
+use super::token::TokenTicker;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    ListToken {
+        ticker: TokenTicker,
+    },
+    SubmitOrder {
+        ticker: TokenTicker,
+        side: BuyOrSell,
+        price: f64,
+        quantity: u32,
+        time_in_force: TimeInForce,
+        metadata: Option<OrderMetadata>,
+    },
+    // With an expected sequence, cancels and replaces are refused once the
+    // book has moved past it.
+    CancelOrder {
+        ticker: TokenTicker,
+        order_id: u64,
+        expected_sequence: Option<u64>,
+    },
+    ReplaceOrder {
+        ticker: TokenTicker,
+        order_id: u64,
+        price: f64,
+        quantity: u32,
+        expected_sequence: Option<u64>,
+    },
+    MatchOrders,
+    Admin(AdminCommand),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandResult {
+    TokenListed,
+    AdminApplied,
+    OrderAccepted { order_id: u64 },
+    OrderRejected { reason: OrderError },
+    OrderCancelled { order_id: u64, remaining: u32 },
+    OrderReplaced { order_id: u64, new_order_id: u64 },
+    // The order is not resting on the book, or the book does not exist.
+    OrderNotFound { order_id: u64 },
+    StaleSequence { expected: u64, current: u64 },
+    Matched { trades: Vec<(u64, u64, f64, u32)> },
+}
+
+// Rejection returned instead of buffering past the configured capacity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueFull {
+    pub capacity: usize,
+}
+
+// Why `EngineHandle::try_submit` refused a command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubmitError {
+    QueueFull { capacity: usize },
+    // The engine thread is gone, e.g. after a conservation check panicked.
+    EngineStopped,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueEvent {
+    // Depth rose to or past the high watermark: producers should slow
+    // down.
+    HighWatermark { depth: usize },
+    QueueFull { capacity: usize },
+}
+
+impl TradeEngine {
+    // Debug builds check that the command neither created nor destroyed
+    // value.
+    pub fn execute(&mut self, command: Command) -> CommandResult {
+        #[cfg(debug_assertions)]
+        let before = self.value_totals();
+        let result = self.execute_unchecked(command);
+        #[cfg(debug_assertions)]
+        if let Err(err) = check_conserved(&before, &self.value_totals()) {
+            panic!("command broke conservation of value: {:?}", err);
+        }
+        result
+    }
+
+    fn execute_unchecked(&mut self, command: Command) -> CommandResult {
+        match command {
+            Command::ListToken { ticker } => {
+                self.list_new_token(ticker);
+                CommandResult::TokenListed
+            }
+            Command::SubmitOrder {
+                ticker,
+                side,
+                price,
+                quantity,
+                time_in_force,
+                metadata,
+            } => {
+                let placed = match metadata {
+                    Some(metadata) => self.submit_tagged_order(
+                        None,
+                        &ticker,
+                        side,
+                        price,
+                        quantity,
+                        time_in_force,
+                        metadata,
+                    ),
+                    None => {
+                        self.submit_order_with_tif(&ticker, side, price, quantity, time_in_force)
+                    }
+                };
+                match placed {
+                    Ok(order_id) => CommandResult::OrderAccepted { order_id },
+                    Err(reason) => CommandResult::OrderRejected { reason },
+                }
+            }
+            Command::CancelOrder {
+                ticker,
+                order_id,
+                expected_sequence,
+            } => match self.cancel_order_checked(&ticker, order_id, expected_sequence) {
+                Ok(order) => CommandResult::OrderCancelled {
+                    order_id,
+                    remaining: order.quantity,
+                },
+                Err(err) => CommandResult::amend_failed(order_id, err),
+            },
+            Command::ReplaceOrder {
+                ticker,
+                order_id,
+                price,
+                quantity,
+                expected_sequence,
+            } => match self.replace_order(&ticker, order_id, price, quantity, expected_sequence) {
+                Ok(new_order_id) => CommandResult::OrderReplaced {
+                    order_id,
+                    new_order_id,
+                },
+                Err(err) => CommandResult::amend_failed(order_id, err),
+            },
+            Command::MatchOrders => CommandResult::Matched {
+                trades: self.match_orders(),
+            },
+            Command::Admin(command) => {
+                self.apply_admin(command);
+                CommandResult::AdminApplied
+            }
+        }
+    }
+}
+
+impl CommandResult {
+    fn amend_failed(order_id: u64, err: AmendError) -> CommandResult {
+        match err {
+            AmendError::Rejected(reason) => CommandResult::OrderRejected { reason },
+            AmendError::OrderNotFound => CommandResult::OrderNotFound { order_id },
+            AmendError::StaleSequence { expected, current } => {
+                CommandResult::StaleSequence { expected, current }
+            }
+        }
+    }
+}
+
+// An aggressive order held by the speed bump. The heap yields the earliest
+// release first, ties in sequence order.
+struct HeldOrder {
+    release: u64,
+    sequence: u64,
+    command: Command,
+}
+
+impl Ord for HeldOrder {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.release, other.sequence).cmp(&(self.release, self.sequence))
+    }
+}
+
+impl PartialOrd for HeldOrder {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for HeldOrder {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for HeldOrder {}
+
+// Bounded, sequenced buffer of commands waiting for the engine. Pushing into
+// a full queue fails with `QueueFull` so overload is visible to the producer
+// instead of growing memory without bound. Orders held by the speed bump
+// still count against the capacity.
+pub struct CommandQueue {
+    queue: VecDeque<(u64, Command)>,
+    // Cancels waiting ahead of `queue` when cancel priority is on.
+    cancels: VecDeque<(u64, Command)>,
+    cancel_priority: bool,
+    capacity: usize,
+    high_watermark: usize,
+    next_sequence: u64,
+    rejected: u64,
+    events: Vec<QueueEvent>,
+    speed_bump: Option<SpeedBump>,
+    held: BinaryHeap<HeldOrder>,
+}
+
+impl CommandQueue {
+    // The high watermark defaults to 80% of capacity.
+    pub fn new(capacity: usize) -> CommandQueue {
+        CommandQueue {
+            queue: VecDeque::with_capacity(capacity),
+            cancels: VecDeque::new(),
+            cancel_priority: false,
+            capacity,
+            high_watermark: capacity * 4 / 5,
+            next_sequence: 1,
+            rejected: 0,
+            events: Vec::new(),
+            speed_bump: None,
+            held: BinaryHeap::new(),
+        }
+    }
+
+    // Delay orders that would cross the book before they are applied.
+    pub fn set_speed_bump(&mut self, speed_bump: Option<SpeedBump>) {
+        self.speed_bump = speed_bump;
+    }
+
+    // Let cancels overtake queued orders, so a cancel waits for at most one
+    // `process` batch however deep the queue is. A cancel that overtakes the
+    // order it names finds nothing to cancel. Turning it off keeps already
+    // queued cancels ahead.
+    pub fn set_cancel_priority(&mut self, enabled: bool) {
+        self.cancel_priority = enabled;
+    }
+
+    // Orders currently held by the speed bump.
+    pub fn held_len(&self) -> usize {
+        self.held.len()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // Commands waiting, including orders held by the speed bump.
+    pub fn len(&self) -> usize {
+        self.queue.len() + self.cancels.len() + self.held.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len() >= self.capacity
+    }
+
+    // True once the depth is at or above the high watermark.
+    pub fn under_pressure(&self) -> bool {
+        self.len() >= self.high_watermark
+    }
+
+    pub fn rejected_count(&self) -> u64 {
+        self.rejected
+    }
+
+    pub fn events(&self) -> &[QueueEvent] {
+        &self.events
+    }
+
+    // Enqueue a command and return its sequence number.
+    pub fn try_push(&mut self, command: Command) -> Result<u64, QueueFull> {
+        if self.is_full() {
+            self.rejected += 1;
+            self.events.push(QueueEvent::QueueFull {
+                capacity: self.capacity,
+            });
+            return Err(QueueFull {
+                capacity: self.capacity,
+            });
+        }
+        let depth_before = self.len();
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        match command {
+            Command::CancelOrder { .. } if self.cancel_priority => {
+                self.cancels.push_back((sequence, command))
+            }
+            command => self.queue.push_back((sequence, command)),
+        }
+        if depth_before < self.high_watermark && self.len() >= self.high_watermark {
+            self.events
+                .push(QueueEvent::HighWatermark { depth: self.len() });
+        }
+        Ok(sequence)
+    }
+
+    // Queued and held orders for the instrument.
+    fn orders_waiting(&self, ticker: &TokenTicker) -> usize {
+        self.queue
+            .iter()
+            .map(|(_, command)| command)
+            .chain(self.held.iter().map(|held| &held.command))
+            .filter(|command| {
+                matches!(command, Command::SubmitOrder { ticker: queued, .. }
+                    | Command::ReplaceOrder { ticker: queued, .. } if queued == ticker)
+            })
+            .count()
+    }
+
+    // Priority cancels first, then the rest in sequence order.
+    pub fn pop(&mut self) -> Option<(u64, Command)> {
+        self.cancels.pop_front().or_else(|| self.queue.pop_front())
+    }
+
+    // Apply up to `max` queued commands to the engine in sequence order.
+    // Priority cancels go before everything else. With a speed bump, held
+    // orders whose delay has elapsed on the engine clock go next, and newly
+    // popped aggressive orders are held instead.
+    pub fn process(&mut self, engine: &mut TradeEngine, max: usize) -> Vec<(u64, CommandResult)> {
+        for ticker in engine.throttled_instruments() {
+            engine.observe_queue_depth(&ticker, self.orders_waiting(&ticker));
+        }
+        let mut results = Vec::new();
+        while results.len() < max {
+            let Some((sequence, command)) = self.cancels.pop_front() else {
+                break;
+            };
+            results.push((sequence, engine.execute(command)));
+        }
+        let now = engine.now();
+        while results.len() < max && self.held.peek().is_some_and(|held| held.release <= now) {
+            let held = self.held.pop().unwrap();
+            results.push((held.sequence, engine.execute(held.command)));
+        }
+        while results.len() < max {
+            let Some((sequence, command)) = self.pop() else {
+                break;
+            };
+            match self.speed_bump.as_mut() {
+                Some(speed_bump) if SpeedBump::is_aggressive(engine, &command) => {
+                    self.held.push(HeldOrder {
+                        release: now + speed_bump.next_delay(),
+                        sequence,
+                        command,
+                    });
+                }
+                _ => results.push((sequence, engine.execute(command))),
+            }
+        }
+        results
+    }
+}
+
+type Envelope = (Command, Sender<CommandResult>);
+
+// Runs an engine on its own thread behind a bounded channel.
+pub struct EngineHandle {
+    sender: SyncSender<Envelope>,
+    capacity: usize,
+    worker: JoinHandle<TradeEngine>,
+}
+
+impl EngineHandle {
+    pub fn spawn(mut engine: TradeEngine, capacity: usize) -> EngineHandle {
+        let (sender, receiver) = mpsc::sync_channel::<Envelope>(capacity);
+        let worker = thread::spawn(move || {
+            for (command, reply) in receiver {
+                // the caller may have dropped its receiver; the command still ran
+                let _ = reply.send(engine.execute(command));
+            }
+            engine
+        });
+        EngineHandle {
+            sender,
+            capacity,
+            worker,
+        }
+    }
+
+    // Queue a command without blocking. The result arrives on the returned
+    // receiver once the engine thread has applied it.
+    pub fn try_submit(&self, command: Command) -> Result<Receiver<CommandResult>, SubmitError> {
+        let (reply, result) = mpsc::channel();
+        match self.sender.try_send((command, reply)) {
+            Ok(()) => Ok(result),
+            Err(TrySendError::Full(_)) => Err(SubmitError::QueueFull {
+                capacity: self.capacity,
+            }),
+            Err(TrySendError::Disconnected(_)) => Err(SubmitError::EngineStopped),
+        }
+    }
+
+    // Stop accepting commands, let the engine drain its queue and hand it back.
+    pub fn shutdown(self) -> TradeEngine {
+        drop(self.sender);
+        self.worker.join().expect("engine thread panicked")
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::clock::SimulatedClock;
+    use crate::corelib::speed_bump::SpeedBumpDelay;
+
+    fn buy(ticker: TokenTicker) -> Command {
+        Command::SubmitOrder {
+            ticker,
+            side: BuyOrSell::Buy,
+            price: 10.0,
+            quantity: 1,
+            time_in_force: TimeInForce::GoodTillCancel,
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_queue_rejects_when_full() {
+        let mut engine = TradeEngine::new();
+        engine.list_new_token(TokenTicker::ETH);
+        let mut queue = CommandQueue::new(5);
+
+        for _ in 0..5 {
+            queue.try_push(buy(TokenTicker::ETH)).unwrap();
+        }
+        assert!(queue.under_pressure());
+        assert_eq!(
+            queue.try_push(buy(TokenTicker::ETH)),
+            Err(QueueFull { capacity: 5 })
+        );
+        assert_eq!(queue.rejected_count(), 1);
+        assert_eq!(
+            queue.events(),
+            &[
+                QueueEvent::HighWatermark { depth: 4 },
+                QueueEvent::QueueFull { capacity: 5 }
+            ]
+        );
+
+        let results = queue.process(&mut engine, 3);
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].0, 1);
+        assert_eq!(queue.len(), 2);
+        assert!(queue.try_push(buy(TokenTicker::BTC)).is_ok());
+
+        let results = queue.process(&mut engine, usize::MAX);
+        assert_eq!(
+            results.last().unwrap().1,
+            CommandResult::OrderRejected {
+                reason: OrderError::UnknownTicker
+            }
+        );
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_speed_bump_holds_aggressive_orders() {
+        let clock = SimulatedClock::new(0);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        engine.list_new_token(TokenTicker::ETH);
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Sell, 10.0, 1)
+            .unwrap();
+        let mut queue = CommandQueue::new(8);
+        queue.set_speed_bump(Some(SpeedBump::new(SpeedBumpDelay::Fixed { millis: 350 })));
+
+        let taker = queue.try_push(buy(TokenTicker::ETH)).unwrap();
+        let passive = queue
+            .try_push(Command::SubmitOrder {
+                ticker: TokenTicker::ETH,
+                side: BuyOrSell::Buy,
+                price: 9.0,
+                quantity: 1,
+                time_in_force: TimeInForce::GoodTillCancel,
+                metadata: None,
+            })
+            .unwrap();
+        let results = queue.process(&mut engine, usize::MAX);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, passive);
+        assert_eq!(queue.held_len(), 1);
+
+        clock.advance(349);
+        assert!(queue.process(&mut engine, usize::MAX).is_empty());
+        clock.advance(1);
+        let results = queue.process(&mut engine, usize::MAX);
+        assert_eq!(results[0].0, taker);
+        assert_eq!(queue.held_len(), 0);
+    }
+
+    #[test]
+    fn test_speed_bump_holds_crossing_replaces() {
+        let clock = SimulatedClock::new(0);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        engine.list_new_token(TokenTicker::ETH);
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Sell, 10.0, 1)
+            .unwrap();
+        let resting = engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Buy, 8.0, 1)
+            .unwrap();
+        let mut queue = CommandQueue::new(8);
+        queue.set_speed_bump(Some(SpeedBump::new(SpeedBumpDelay::Fixed { millis: 350 })));
+        let replace = |order_id, price| Command::ReplaceOrder {
+            ticker: TokenTicker::ETH,
+            order_id,
+            price,
+            quantity: 1,
+            expected_sequence: None,
+        };
+
+        let passive = queue.try_push(replace(resting, 9.0)).unwrap();
+        let results = queue.process(&mut engine, usize::MAX);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, passive);
+        let CommandResult::OrderReplaced { new_order_id, .. } = results[0].1 else {
+            panic!("replace failed: {:?}", results[0].1);
+        };
+
+        let crossing = queue.try_push(replace(new_order_id, 10.0)).unwrap();
+        assert!(queue.process(&mut engine, usize::MAX).is_empty());
+        assert_eq!(queue.held_len(), 1);
+        clock.advance(350);
+        let results = queue.process(&mut engine, usize::MAX);
+        assert_eq!(results[0].0, crossing);
+        assert_eq!(queue.held_len(), 0);
+    }
+
+    #[test]
+    fn test_held_orders_count_against_capacity() {
+        let clock = SimulatedClock::new(0);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        engine.list_new_token(TokenTicker::ETH);
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Sell, 10.0, 3)
+            .unwrap();
+        let mut queue = CommandQueue::new(2);
+        queue.set_speed_bump(Some(SpeedBump::new(SpeedBumpDelay::Random {
+            min_millis: 100,
+            max_millis: 200,
+            seed: 3,
+        })));
+
+        let first = queue.try_push(buy(TokenTicker::ETH)).unwrap();
+        let second = queue.try_push(buy(TokenTicker::ETH)).unwrap();
+        assert!(queue.process(&mut engine, usize::MAX).is_empty());
+        assert_eq!(queue.held_len(), 2);
+        assert!(queue.is_full());
+        assert_eq!(
+            queue.try_push(buy(TokenTicker::ETH)),
+            Err(QueueFull { capacity: 2 })
+        );
+
+        clock.advance(200);
+        let mut released: Vec<u64> = queue
+            .process(&mut engine, usize::MAX)
+            .into_iter()
+            .map(|(sequence, _)| sequence)
+            .collect();
+        released.sort();
+        assert_eq!(released, vec![first, second]);
+        assert!(queue.is_empty());
+    }
+
+    // Milliseconds from queueing a cancel behind `backlog` orders to it
+    // being applied, with one 10-command batch processed per millisecond.
+    fn cancel_latency(cancel_priority: bool, backlog: usize) -> u64 {
+        let clock = SimulatedClock::new(0);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        engine.list_new_token(TokenTicker::ETH);
+        let resting = engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Sell, 20.0, 5)
+            .unwrap();
+        let mut queue = CommandQueue::new(backlog + 1);
+        queue.set_cancel_priority(cancel_priority);
+        for _ in 0..backlog {
+            queue.try_push(buy(TokenTicker::ETH)).unwrap();
+        }
+        let cancel = queue
+            .try_push(Command::CancelOrder {
+                ticker: TokenTicker::ETH,
+                order_id: resting,
+                expected_sequence: None,
+            })
+            .unwrap();
+        loop {
+            clock.advance(1);
+            let results = queue.process(&mut engine, 10);
+            if let Some((_, result)) = results.iter().find(|(sequence, _)| *sequence == cancel) {
+                assert_eq!(
+                    *result,
+                    CommandResult::OrderCancelled {
+                        order_id: resting,
+                        remaining: 5
+                    }
+                );
+                return engine.now();
+            }
+        }
+    }
+
+    #[test]
+    fn test_cancels_overtake_queued_orders() {
+        assert_eq!(cancel_latency(false, 1_000), 101);
+        assert_eq!(cancel_latency(true, 1_000), 1);
+        assert_eq!(cancel_latency(true, 10), 1);
+
+        // a cancel that overtakes its order finds nothing to cancel
+        let mut engine = TradeEngine::new();
+        engine.list_new_token(TokenTicker::ETH);
+        let mut queue = CommandQueue::new(4);
+        queue.set_cancel_priority(true);
+        queue.try_push(buy(TokenTicker::ETH)).unwrap();
+        queue
+            .try_push(Command::CancelOrder {
+                ticker: TokenTicker::ETH,
+                order_id: 1,
+                expected_sequence: None,
+            })
+            .unwrap();
+        let results = queue.process(&mut engine, usize::MAX);
+        assert_eq!(
+            results,
+            vec![
+                (2, CommandResult::OrderNotFound { order_id: 1 }),
+                (1, CommandResult::OrderAccepted { order_id: 1 })
+            ]
+        );
+    }
+
+    #[test]
+    fn test_random_speed_bump_is_reproducible() {
+        let delay = SpeedBumpDelay::Random {
+            min_millis: 100,
+            max_millis: 200,
+            seed: 7,
+        };
+        let draw = || {
+            let mut speed_bump = SpeedBump::new(delay);
+            (0..20)
+                .map(|_| speed_bump.next_delay())
+                .collect::<Vec<u64>>()
+        };
+        let delays = draw();
+        assert_eq!(delays, draw());
+        assert!(delays.iter().all(|delay| (100..=200).contains(delay)));
+    }
+
+    #[test]
+    fn test_engine_handle_round_trip() {
+        let mut engine = TradeEngine::new();
+        engine.list_new_token(TokenTicker::ETH);
+        let handle = EngineHandle::spawn(engine, 16);
+
+        let result = handle.try_submit(buy(TokenTicker::ETH)).unwrap();
+        assert_eq!(
+            result.recv().unwrap(),
+            CommandResult::OrderAccepted { order_id: 1 }
+        );
+
+        let engine = handle.shutdown();
+        assert_eq!(
+            engine.order_books[&TokenTicker::ETH].level_count(BuyOrSell::Buy),
+            1
+        );
+    }
+
+    #[test]
+    fn test_submit_after_the_engine_thread_died() {
+        // a worker that panicked drops the receiving end of the channel
+        let (sender, receiver) = mpsc::sync_channel::<Envelope>(4);
+        drop(receiver);
+        let handle = EngineHandle {
+            sender,
+            capacity: 4,
+            worker: thread::spawn(TradeEngine::new),
+        };
+        assert_eq!(
+            handle.try_submit(buy(TokenTicker::ETH)).map(|_| ()),
+            Err(SubmitError::EngineStopped)
+        );
+    }
+
+    #[test]
+    fn test_high_watermark_fires_when_crossed() {
+        let mut queue = CommandQueue::new(5);
+        queue.set_cancel_priority(true);
+        for order_id in 0..5 {
+            queue
+                .try_push(Command::CancelOrder {
+                    ticker: TokenTicker::ETH,
+                    order_id,
+                    expected_sequence: None,
+                })
+                .unwrap();
+        }
+        assert_eq!(queue.events(), &[QueueEvent::HighWatermark { depth: 4 }]);
+    }
+}
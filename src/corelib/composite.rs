@@ -0,0 +1,171 @@
+use std::collections::BTreeMap;
+
+use ordered_float::OrderedFloat;
+
+use super::depth::{DepthLevel, DepthSnapshot};
+use super::order::BuyOrSell;
+use super::orderbook::OrderBook;
+
+// One consolidated price level with the quantity each venue shows there,
+// largest first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConsolidatedLevel {
+    pub price: f64,
+    pub quantity: u64,
+    pub venues: Vec<(String, u64)>,
+}
+
+// Bids best (highest) first, asks best (lowest) first.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConsolidatedBook {
+    pub bids: Vec<ConsolidatedLevel>,
+    pub asks: Vec<ConsolidatedLevel>,
+}
+
+// Part of an order routed to one venue at one price.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoutedSlice {
+    pub venue: String,
+    pub price: f64,
+    pub quantity: u64,
+}
+
+// Latest depth of the same instrument on several venues, e.g. other engines
+// or imported external feeds.
+#[derive(Debug, Clone, Default)]
+pub struct CompositeBook {
+    venues: BTreeMap<String, DepthSnapshot>,
+}
+
+impl CompositeBook {
+    pub fn new() -> CompositeBook {
+        CompositeBook::default()
+    }
+
+    // Replace the depth shown for `venue`.
+    pub fn update(&mut self, venue: &str, depth: DepthSnapshot) {
+        self.venues.insert(String::from(venue), depth);
+    }
+
+    pub fn update_from_book(&mut self, venue: &str, orderbook: &OrderBook) {
+        self.update(venue, orderbook.depth_snapshot());
+    }
+
+    pub fn remove_venue(&mut self, venue: &str) -> Option<DepthSnapshot> {
+        self.venues.remove(venue)
+    }
+
+    pub fn venues(&self) -> impl Iterator<Item = &String> {
+        self.venues.keys()
+    }
+
+    pub fn consolidated(&self) -> ConsolidatedBook {
+        let merge = |side: fn(&DepthSnapshot) -> &Vec<DepthLevel>| {
+            let mut levels: BTreeMap<OrderedFloat<f64>, Vec<(String, u64)>> = BTreeMap::new();
+            for (venue, depth) in self.venues.iter() {
+                for level in side(depth).iter().filter(|level| level.quantity > 0) {
+                    levels
+                        .entry(OrderedFloat(level.price))
+                        .or_default()
+                        .push((venue.clone(), level.quantity));
+                }
+            }
+            levels
+                .into_iter()
+                .map(|(price, mut venues)| {
+                    venues.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+                    ConsolidatedLevel {
+                        price: price.into_inner(),
+                        quantity: venues.iter().map(|(_, quantity)| quantity).sum(),
+                        venues,
+                    }
+                })
+                .collect::<Vec<_>>()
+        };
+        let mut bids = merge(|depth| &depth.bids);
+        bids.reverse();
+        ConsolidatedBook {
+            bids,
+            asks: merge(|depth| &depth.asks),
+        }
+    }
+
+    // Split an order of `side` across venues, taking the best consolidated
+    // prices first and, within a level, the venues showing the most. May
+    // return less than `quantity` when the composite book runs out.
+    pub fn route(&self, side: BuyOrSell, quantity: u64) -> Vec<RoutedSlice> {
+        let book = self.consolidated();
+        let levels = match side {
+            BuyOrSell::Buy => book.asks,
+            BuyOrSell::Sell => book.bids,
+        };
+        let mut remaining = quantity;
+        let mut slices = Vec::new();
+        for level in levels {
+            for (venue, available) in level.venues {
+                if remaining == 0 {
+                    return slices;
+                }
+                let quantity = available.min(remaining);
+                remaining -= quantity;
+                slices.push(RoutedSlice {
+                    venue,
+                    price: level.price,
+                    quantity,
+                });
+            }
+        }
+        slices
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_consolidated_levels_and_routing() {
+        let mut local = OrderBook::new();
+        local.add_order(BuyOrSell::Buy, 99.0, 5, 0);
+        local.add_order(BuyOrSell::Sell, 101.0, 2, 0);
+        let mut external = OrderBook::new();
+        external.add_order(BuyOrSell::Buy, 99.0, 3, 0);
+        external.add_order(BuyOrSell::Buy, 100.0, 1, 0);
+        external.add_order(BuyOrSell::Sell, 101.0, 4, 0);
+        external.add_order(BuyOrSell::Sell, 102.0, 6, 0);
+
+        let mut composite = CompositeBook::new();
+        composite.update_from_book("local", &local);
+        composite.update_from_book("external", &external);
+
+        let book = composite.consolidated();
+        assert_eq!(book.bids[0].price, 100.0);
+        assert_eq!(
+            book.bids[1],
+            ConsolidatedLevel {
+                price: 99.0,
+                quantity: 8,
+                venues: vec![(String::from("local"), 5), (String::from("external"), 3)],
+            }
+        );
+        assert_eq!(book.asks[0].quantity, 6);
+
+        let slices = composite.route(BuyOrSell::Buy, 8);
+        let routed: Vec<(&str, f64, u64)> = slices
+            .iter()
+            .map(|slice| (slice.venue.as_str(), slice.price, slice.quantity))
+            .collect();
+        assert_eq!(
+            routed,
+            vec![
+                ("external", 101.0, 4),
+                ("local", 101.0, 2),
+                ("external", 102.0, 2)
+            ]
+        );
+
+        composite.remove_venue("external");
+        assert_eq!(composite.consolidated().bids.len(), 1);
+    }
+}
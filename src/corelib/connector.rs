@@ -0,0 +1,314 @@
+use std::collections::{BTreeMap, HashMap};
+
+use super::composite::CompositeBook;
+use super::depth::DepthSnapshot;
+use super::engine::{OrderError, TradeEngine};
+use super::order::BuyOrSell;
+use super::token::TokenTicker;
+use super::trade::Trade;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConnectorError {
+    UnknownTicker,
+    UnknownOrder,
+    // The venue refused the order.
+    Rejected(String),
+}
+
+impl From<OrderError> for ConnectorError {
+    fn from(err: OrderError) -> Self {
+        match err {
+            OrderError::UnknownTicker => ConnectorError::UnknownTicker,
+            err => ConnectorError::Rejected(format!("{:?}", err)),
+        }
+    }
+}
+
+// A venue orders can be sent to and market data read from. The internal
+// engine and external exchanges both implement it, so routing and arbitrage
+// code can treat them alike; real exchange connectors live outside this
+// crate.
+pub trait ExchangeConnector {
+    fn venue(&self) -> &str;
+
+    // Place a limit order and return the venue's order id.
+    fn submit(
+        &mut self,
+        ticker: &TokenTicker,
+        side: BuyOrSell,
+        price: f64,
+        quantity: u32,
+    ) -> Result<u64, ConnectorError>;
+
+    fn cancel(&mut self, ticker: &TokenTicker, order_id: u64) -> Result<(), ConnectorError>;
+
+    // Current depth of the instrument.
+    fn book(&self, ticker: &TokenTicker) -> Result<DepthSnapshot, ConnectorError>;
+
+    // Trades on the instrument since the previous poll, oldest first.
+    fn poll_trades(&mut self, ticker: &TokenTicker) -> Result<Vec<Trade>, ConnectorError>;
+}
+
+// The internal engine seen as a venue.
+pub struct EngineConnector {
+    pub engine: TradeEngine,
+    venue: String,
+    // Last trade id handed out per instrument.
+    trade_cursors: HashMap<TokenTicker, u64>,
+}
+
+impl EngineConnector {
+    pub fn new(venue: &str, engine: TradeEngine) -> EngineConnector {
+        EngineConnector {
+            engine,
+            venue: String::from(venue),
+            trade_cursors: HashMap::new(),
+        }
+    }
+}
+
+impl ExchangeConnector for EngineConnector {
+    fn venue(&self) -> &str {
+        &self.venue
+    }
+
+    fn submit(
+        &mut self,
+        ticker: &TokenTicker,
+        side: BuyOrSell,
+        price: f64,
+        quantity: u32,
+    ) -> Result<u64, ConnectorError> {
+        Ok(self.engine.submit_order(ticker, side, price, quantity)?)
+    }
+
+    fn cancel(&mut self, ticker: &TokenTicker, order_id: u64) -> Result<(), ConnectorError> {
+        let now = self.engine.now();
+        let orderbook = self
+            .engine
+            .order_books
+            .get_mut(ticker)
+            .ok_or(ConnectorError::UnknownTicker)?;
+        orderbook
+            .cancel_order(order_id, now)
+            .map(|_| ())
+            .ok_or(ConnectorError::UnknownOrder)
+    }
+
+    fn book(&self, ticker: &TokenTicker) -> Result<DepthSnapshot, ConnectorError> {
+        self.engine
+            .order_books
+            .get(ticker)
+            .map(|orderbook| orderbook.depth_snapshot())
+            .ok_or(ConnectorError::UnknownTicker)
+    }
+
+    fn poll_trades(&mut self, ticker: &TokenTicker) -> Result<Vec<Trade>, ConnectorError> {
+        if !self.engine.order_books.contains_key(ticker) {
+            return Err(ConnectorError::UnknownTicker);
+        }
+        let cursor = self.trade_cursors.entry(ticker.clone()).or_insert(0);
+        let trades: Vec<Trade> = self
+            .engine
+            .trade_feed
+            .trades_for(ticker)
+            .filter(|trade| trade.id > *cursor)
+            .cloned()
+            .collect();
+        if let Some(last) = trades.last() {
+            *cursor = last.id;
+        }
+        Ok(trades)
+    }
+}
+
+// Order accepted by a `MockConnector`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockOrder {
+    pub ticker: TokenTicker,
+    pub side: BuyOrSell,
+    pub price: f64,
+    pub quantity: u32,
+}
+
+// Scripted venue for tests: depth and trades are set by the test, orders are
+// recorded and never filled.
+#[derive(Debug, Clone, Default)]
+pub struct MockConnector {
+    venue: String,
+    books: HashMap<TokenTicker, DepthSnapshot>,
+    pending_trades: HashMap<TokenTicker, Vec<Trade>>,
+    pub open_orders: BTreeMap<u64, MockOrder>,
+    next_order_id: u64,
+    // Reason the next submit is refused with, if set.
+    reject_next: Option<String>,
+}
+
+impl MockConnector {
+    pub fn new(venue: &str) -> MockConnector {
+        MockConnector {
+            venue: String::from(venue),
+            next_order_id: 1,
+            ..MockConnector::default()
+        }
+    }
+
+    // Listing happens implicitly through the first book set for a ticker.
+    pub fn set_book(&mut self, ticker: TokenTicker, depth: DepthSnapshot) {
+        self.books.insert(ticker, depth);
+    }
+
+    pub fn push_trade(&mut self, trade: Trade) {
+        self.pending_trades
+            .entry(trade.ticker.clone())
+            .or_default()
+            .push(trade);
+    }
+
+    pub fn reject_next(&mut self, reason: &str) {
+        self.reject_next = Some(String::from(reason));
+    }
+}
+
+impl ExchangeConnector for MockConnector {
+    fn venue(&self) -> &str {
+        &self.venue
+    }
+
+    fn submit(
+        &mut self,
+        ticker: &TokenTicker,
+        side: BuyOrSell,
+        price: f64,
+        quantity: u32,
+    ) -> Result<u64, ConnectorError> {
+        if !self.books.contains_key(ticker) {
+            return Err(ConnectorError::UnknownTicker);
+        }
+        if let Some(reason) = self.reject_next.take() {
+            return Err(ConnectorError::Rejected(reason));
+        }
+        let order_id = self.next_order_id;
+        self.next_order_id += 1;
+        self.open_orders.insert(
+            order_id,
+            MockOrder {
+                ticker: ticker.clone(),
+                side,
+                price,
+                quantity,
+            },
+        );
+        Ok(order_id)
+    }
+
+    fn cancel(&mut self, ticker: &TokenTicker, order_id: u64) -> Result<(), ConnectorError> {
+        match self.open_orders.get(&order_id) {
+            Some(order) if &order.ticker == ticker => {
+                self.open_orders.remove(&order_id);
+                Ok(())
+            }
+            _ => Err(ConnectorError::UnknownOrder),
+        }
+    }
+
+    fn book(&self, ticker: &TokenTicker) -> Result<DepthSnapshot, ConnectorError> {
+        self.books
+            .get(ticker)
+            .cloned()
+            .ok_or(ConnectorError::UnknownTicker)
+    }
+
+    fn poll_trades(&mut self, ticker: &TokenTicker) -> Result<Vec<Trade>, ConnectorError> {
+        if !self.books.contains_key(ticker) {
+            return Err(ConnectorError::UnknownTicker);
+        }
+        Ok(self.pending_trades.remove(ticker).unwrap_or_default())
+    }
+}
+
+impl CompositeBook {
+    // Refresh the depth shown for the connector's venue.
+    pub fn update_from_connector(
+        &mut self,
+        connector: &dyn ExchangeConnector,
+        ticker: &TokenTicker,
+    ) -> Result<(), ConnectorError> {
+        let depth = connector.book(ticker)?;
+        self.update(connector.venue(), depth);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::depth::DepthLevel;
+
+    #[test]
+    fn test_engine_and_mock_behave_alike() {
+        let mut engine = TradeEngine::new();
+        engine.list_new_token(TokenTicker::ETH);
+        let mut mock = MockConnector::new("mock");
+        mock.set_book(
+            TokenTicker::ETH,
+            DepthSnapshot {
+                bids: vec![DepthLevel {
+                    price: 99.0,
+                    quantity: 4,
+                }],
+                asks: Vec::new(),
+            },
+        );
+        let mut venues: Vec<Box<dyn ExchangeConnector>> = vec![
+            Box::new(EngineConnector::new("internal", engine)),
+            Box::new(mock),
+        ];
+
+        let mut composite = CompositeBook::new();
+        for venue in venues.iter_mut() {
+            let order_id = venue
+                .submit(&TokenTicker::ETH, BuyOrSell::Buy, 100.0, 2)
+                .unwrap();
+            assert_eq!(
+                venue.submit(&TokenTicker::BTC, BuyOrSell::Buy, 100.0, 2),
+                Err(ConnectorError::UnknownTicker)
+            );
+            let extra = venue
+                .submit(&TokenTicker::ETH, BuyOrSell::Buy, 98.0, 1)
+                .unwrap();
+            venue.cancel(&TokenTicker::ETH, extra).unwrap();
+            assert_eq!(
+                venue.cancel(&TokenTicker::ETH, extra),
+                Err(ConnectorError::UnknownOrder)
+            );
+            assert!(order_id > 0);
+            composite
+                .update_from_connector(venue.as_ref(), &TokenTicker::ETH)
+                .unwrap();
+        }
+        let book = composite.consolidated();
+        assert_eq!(book.bids[0].venues, vec![(String::from("internal"), 2)]);
+        assert_eq!(book.bids[1].venues, vec![(String::from("mock"), 4)]);
+    }
+
+    #[test]
+    fn test_engine_connector_polls_new_trades_once() {
+        let mut engine = TradeEngine::new();
+        engine.list_new_token(TokenTicker::ETH);
+        let mut connector = EngineConnector::new("internal", engine);
+        connector
+            .submit(&TokenTicker::ETH, BuyOrSell::Buy, 100.0, 3)
+            .unwrap();
+        connector
+            .submit(&TokenTicker::ETH, BuyOrSell::Sell, 100.0, 3)
+            .unwrap();
+        connector.engine.match_orders();
+
+        let trades = connector.poll_trades(&TokenTicker::ETH).unwrap();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].quantity, 3);
+        assert!(connector.poll_trades(&TokenTicker::ETH).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::engine::TradeEngine;
+use super::token::TokenTicker;
+
+// Total units of one token held anywhere in the engine.
+pub type ValueTotals = BTreeMap<TokenTicker, u64>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConservationError {
+    pub token: TokenTicker,
+    pub before: u64,
+    pub after: u64,
+}
+
+impl TradeEngine {
+    // Per token: ledger balances (free and locked, so wallets, fee account,
+    // treasury and reservations), AMM reserves, staking reward pools and
+    // unclaimed rewards, emission budgets and claimables, and referral
+    // rewards not yet paid.
+    pub fn value_totals(&self) -> ValueTotals {
+        let mut tokens: BTreeSet<TokenTicker> = self.ledger.tokens().into_iter().collect();
+        for pool in self.amm_pools.values().chain(self.tier_pools.values()) {
+            tokens.extend(pool.reserves().map(|(token, _)| token.clone()));
+        }
+        for pool in self.staking_pools.values() {
+            tokens.insert(pool.reward_token.clone());
+        }
+        for schedule in self.emissions.schedules() {
+            tokens.insert(schedule.reward_token.clone());
+        }
+
+        tokens
+            .into_iter()
+            .map(|token| {
+                let mut total = self.ledger.total_supply(&token);
+                for pool in self.amm_pools.values().chain(self.tier_pools.values()) {
+                    total += pool.reserve(&token).unwrap_or(0);
+                }
+                for pool in self
+                    .staking_pools
+                    .values()
+                    .filter(|pool| pool.reward_token == token)
+                {
+                    total += pool.reward_pool() + pool.outstanding_rewards();
+                }
+                total += self.emissions.budget(&token) + self.emissions.total_claimable(&token);
+                total += self.fees.total_accrued_referrals(&token);
+                (token, total)
+            })
+            .collect()
+    }
+
+    // Check that no value was created or destroyed since the previous call;
+    // the first call only takes the baseline. Deposits and withdrawals move
+    // value in and out legitimately, so take a new baseline after them with
+    // `reset_conservation_baseline`.
+    pub fn verify_conservation(&mut self) -> Result<(), ConservationError> {
+        let after = self.value_totals();
+        let Some(before) = self.conservation_baseline.replace(after.clone()) else {
+            return Ok(());
+        };
+        check_conserved(&before, &after)
+    }
+
+    pub fn reset_conservation_baseline(&mut self) {
+        self.conservation_baseline = Some(self.value_totals());
+    }
+}
+
+pub(crate) fn check_conserved(
+    before: &ValueTotals,
+    after: &ValueTotals,
+) -> Result<(), ConservationError> {
+    for token in before.keys().chain(after.keys()) {
+        let before = before.get(token).copied().unwrap_or(0);
+        let after = after.get(token).copied().unwrap_or(0);
+        if before != after {
+            return Err(ConservationError {
+                token: token.clone(),
+                before,
+                after,
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::command::Command;
+    use crate::corelib::order::{BuyOrSell, TimeInForce, Wallet};
+    use crate::corelib::token::Pair;
+
+    // Small xorshift generator so the sequences are reproducible.
+    struct Sequence(u64);
+
+    impl Sequence {
+        fn next(&mut self, bound: u64) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0 % bound
+        }
+    }
+
+    #[test]
+    fn test_random_command_sequences_conserve_value() {
+        for seed in 1..20 {
+            let mut engine = TradeEngine::new();
+            let buyer = Wallet::new(String::from("conservebuyer"));
+            let seller = Wallet::new(String::from("conserveseller"));
+            engine.list_new_token(TokenTicker::ETH);
+            engine
+                .ledger
+                .deposit(buyer.clone(), TokenTicker::USDT, 10_000_000)
+                .unwrap();
+            engine
+                .ledger
+                .deposit(seller.clone(), TokenTicker::ETH, 10_000)
+                .unwrap();
+            let pool = engine
+                .amm_pools
+                .entry(Pair::new(TokenTicker::ETH, TokenTicker::USDT))
+                .or_default();
+            pool.add_liquidity(TokenTicker::ETH, 1_000);
+            pool.add_liquidity(TokenTicker::USDT, 3_000_000);
+            engine.verify_conservation().unwrap();
+
+            let mut sequence = Sequence(seed);
+            for _ in 0..50 {
+                let price = 2_900.0 + sequence.next(200) as f64;
+                match sequence.next(4) {
+                    0 => {
+                        let side = if sequence.next(2) == 0 {
+                            BuyOrSell::Buy
+                        } else {
+                            BuyOrSell::Sell
+                        };
+                        engine.execute(Command::SubmitOrder {
+                            ticker: TokenTicker::ETH,
+                            side,
+                            price,
+                            quantity: 1 + sequence.next(10) as u32,
+                            time_in_force: TimeInForce::GoodTillCancel,
+                            metadata: None,
+                        });
+                    }
+                    1 => {
+                        engine.execute(Command::MatchOrders);
+                    }
+                    2 => {
+                        let _ = engine.report_block_trade(
+                            TokenTicker::ETH,
+                            buyer.clone(),
+                            seller.clone(),
+                            price,
+                            1 + sequence.next(5),
+                        );
+                    }
+                    _ => {
+                        let _ = engine.charge_taker_fee(
+                            &buyer,
+                            &TokenTicker::USDT,
+                            sequence.next(1_000_000),
+                        );
+                    }
+                }
+                engine.verify_conservation().unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_detects_created_value() {
+        let mut engine = TradeEngine::new();
+        let wallet = Wallet::new(String::from("conservewallet"));
+        engine
+            .ledger
+            .deposit(wallet.clone(), TokenTicker::ETH, 5)
+            .unwrap();
+        engine.verify_conservation().unwrap();
+
+        engine.ledger.deposit(wallet, TokenTicker::ETH, 1).unwrap();
+        assert_eq!(
+            engine.verify_conservation(),
+            Err(ConservationError {
+                token: TokenTicker::ETH,
+                before: 5,
+                after: 6,
+            })
+        );
+        engine.reset_conservation_baseline();
+        assert_eq!(engine.verify_conservation(), Ok(()));
+    }
+}
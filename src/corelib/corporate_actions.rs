@@ -0,0 +1,417 @@
+use std::collections::HashMap;
+
+use super::engine::TradeEngine;
+use super::order::Wallet;
+use super::token::TokenTicker;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenEvent {
+    // Every `old_units` of the token became `new_units`. Supplies are the
+    // ledger totals around the event; any difference beyond the ratio is
+    // dust lost to rounding down.
+    Redenominated {
+        token: TokenTicker,
+        new_units: u64,
+        old_units: u64,
+        supply_before: u64,
+        supply_after: u64,
+        timestamp: u64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RedenominationError {
+    InvalidRatio,
+    // A resting order quantity would no longer fit an order after scaling.
+    QuantityOverflow,
+    // A balance, stake or reserve would no longer fit a u64 after scaling.
+    AmountOverflow,
+    // An open RFQ request trades the token; its quotes are priced in the old
+    // units.
+    OpenQuoteRequests,
+}
+
+impl TradeEngine {
+    // Redenominate `token` so that `old_units` become `new_units` (a 1000:1
+    // split is `redenominate(token, 1000, 1)`). Ledger balances, the escrows
+    // and pending withdrawals locked in them, accrued referral rewards,
+    // staking positions, AMM reserves and resting orders are rescaled
+    // together; nothing is touched if any part of the rescale would fail.
+    // Refused while an RFQ request trades the token.
+    pub fn redenominate(
+        &mut self,
+        token: TokenTicker,
+        new_units: u64,
+        old_units: u64,
+    ) -> Result<TokenEvent, RedenominationError> {
+        if new_units == 0 || old_units == 0 {
+            return Err(RedenominationError::InvalidRatio);
+        }
+        // the token is the base of its own book; as the quote token it
+        // prices every book
+        let quoted_books = token == self.quote_token;
+        if self.rfq.has_open_requests(&token) {
+            return Err(RedenominationError::OpenQuoteRequests);
+        }
+        if let Some(order_book) = self.order_books.get(&token) {
+            if !order_book.can_rescale_quantities(new_units, old_units) {
+                return Err(RedenominationError::QuantityOverflow);
+            }
+        }
+        let amounts_fit = self.ledger.can_rescale_token(&token, new_units, old_units)
+            && self
+                .ledger
+                .escrows
+                .can_rescale_token(&token, new_units, old_units)
+            && self
+                .withdrawals
+                .can_rescale_token(&token, new_units, old_units)
+            && self.fees.can_rescale_token(&token, new_units, old_units)
+            && self
+                .staking_pools
+                .values()
+                .all(|pool| pool.can_rescale_token(&token, new_units, old_units))
+            && self
+                .amm_pools
+                .values()
+                .chain(self.tier_pools.values())
+                .all(|pool| pool.can_rescale_token(&token, new_units, old_units));
+        if !amounts_fit {
+            return Err(RedenominationError::AmountOverflow);
+        }
+
+        let timestamp = self.now();
+        let supply_before = self.ledger.total_supply(&token);
+        let mut reserved: HashMap<Wallet, Vec<u64>> = HashMap::new();
+        for request in self.withdrawals.pending_in(&token) {
+            reserved
+                .entry(request.wallet.clone())
+                .or_default()
+                .push(request.amount);
+        }
+        self.ledger
+            .rescale_token(&token, new_units, old_units, &reserved);
+        self.ledger
+            .escrows
+            .rescale_token(&token, new_units, old_units);
+        self.withdrawals.rescale_token(&token, new_units, old_units);
+        self.fees.rescale_token(&token, new_units, old_units);
+        for pool in self.staking_pools.values_mut() {
+            pool.rescale_token(&token, new_units, old_units);
+        }
+        for pool in self
+            .amm_pools
+            .values_mut()
+            .chain(self.tier_pools.values_mut())
+        {
+            pool.rescale_token(&token, new_units, old_units);
+        }
+        if let Some(order_book) = self.order_books.get_mut(&token) {
+            let price_factor = old_units as f64 / new_units as f64;
+            order_book.rescale(price_factor, new_units, old_units, timestamp);
+        }
+        if quoted_books {
+            let price_factor = new_units as f64 / old_units as f64;
+            for (ticker, order_book) in self.order_books.iter_mut() {
+                if ticker != &token {
+                    order_book.rescale(price_factor, 1, 1, timestamp);
+                }
+            }
+        }
+
+        let event = TokenEvent::Redenominated {
+            token: token.clone(),
+            new_units,
+            old_units,
+            supply_before,
+            supply_after: self.ledger.total_supply(&token),
+            timestamp,
+        };
+        self.token_events.push(event.clone());
+        Ok(event)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::ledger::Balance;
+    use crate::corelib::order::{BuyOrSell, Wallet};
+    use crate::corelib::orderbook::OrderBookTrait;
+    use crate::corelib::token::Pair;
+    use ordered_float::OrderedFloat;
+
+    #[test]
+    fn test_split_rescales_balances_orders_and_reserves() {
+        let mut engine = TradeEngine::new();
+        let wallet = Wallet::new(String::from("splitwallet"));
+        engine.list_new_token(TokenTicker::BTC);
+        engine
+            .ledger
+            .deposit(wallet.clone(), TokenTicker::BTC, 3)
+            .unwrap();
+        engine.ledger.lock(&wallet, &TokenTicker::BTC, 1).unwrap();
+        engine
+            .submit_order(&TokenTicker::BTC, BuyOrSell::Sell, 60_000.0, 2)
+            .unwrap();
+
+        let pair = Pair::new(TokenTicker::BTC, TokenTicker::USDT);
+        let pool = engine.amm_pools.entry(pair.clone()).or_default();
+        pool.add_liquidity(TokenTicker::BTC, 10);
+        pool.add_liquidity(TokenTicker::USDT, 600_000);
+
+        let event = engine.redenominate(TokenTicker::BTC, 1000, 1).unwrap();
+        assert!(matches!(
+            event,
+            TokenEvent::Redenominated {
+                supply_before: 3,
+                supply_after: 3_000,
+                ..
+            }
+        ));
+        assert_eq!(engine.token_events, vec![event]);
+
+        assert_eq!(
+            engine.ledger.free_balance(&wallet, &TokenTicker::BTC),
+            2_000
+        );
+        assert_eq!(
+            engine.ledger.locked_balance(&wallet, &TokenTicker::BTC),
+            1_000
+        );
+
+        let order_book = engine.get_token_order_book(&TokenTicker::BTC).unwrap();
+        assert_eq!(order_book.best_sell_price(), Some(OrderedFloat(60.0)));
+        assert_eq!(order_book.sell_volume(), Some(2_000));
+
+        let pool = &engine.amm_pools[&pair];
+        assert_eq!(pool.reserve(&TokenTicker::BTC), Some(10_000));
+        assert_eq!(pool.reserve(&TokenTicker::USDT), Some(600_000));
+    }
+
+    #[test]
+    fn test_reverse_split_truncates_and_rejects_overflow() {
+        let mut engine = TradeEngine::new();
+        let wallet = Wallet::new(String::from("reversewallet"));
+        engine.list_new_token(TokenTicker::Doge);
+        engine
+            .ledger
+            .deposit(wallet.clone(), TokenTicker::Doge, 1_999)
+            .unwrap();
+        engine
+            .submit_order(&TokenTicker::Doge, BuyOrSell::Buy, 0.1, 500)
+            .unwrap();
+        engine
+            .submit_order(&TokenTicker::Doge, BuyOrSell::Buy, 0.1, 4_000_000_000)
+            .unwrap();
+
+        assert_eq!(
+            engine.redenominate(TokenTicker::Doge, 2, 1),
+            Err(RedenominationError::QuantityOverflow)
+        );
+        assert_eq!(
+            engine.ledger.free_balance(&wallet, &TokenTicker::Doge),
+            1_999
+        );
+
+        engine.redenominate(TokenTicker::Doge, 1, 1000).unwrap();
+        assert_eq!(engine.ledger.free_balance(&wallet, &TokenTicker::Doge), 1);
+
+        // the 500 lot rounds to nothing and is cancelled
+        let order_book = engine.get_token_order_book(&TokenTicker::Doge).unwrap();
+        assert_eq!(order_book.buy_volume(), Some(4_000_000));
+        assert_eq!(order_book.best_buy_price(), Some(OrderedFloat(100.0)));
+    }
+
+    #[test]
+    fn test_split_rejects_amounts_that_overflow() {
+        let mut engine = TradeEngine::new();
+        let wallet = Wallet::new(String::from("overflowwallet"));
+        engine.list_new_token(TokenTicker::BTC);
+        engine
+            .ledger
+            .deposit(wallet.clone(), TokenTicker::BTC, 10)
+            .unwrap();
+
+        let pair = Pair::new(TokenTicker::BTC, TokenTicker::USDT);
+        let pool = engine.amm_pools.entry(pair.clone()).or_default();
+        pool.add_liquidity(TokenTicker::BTC, u64::MAX / 2 + 1);
+        pool.add_liquidity(TokenTicker::USDT, 1_000);
+
+        assert_eq!(
+            engine.redenominate(TokenTicker::BTC, 2, 1),
+            Err(RedenominationError::AmountOverflow)
+        );
+        assert_eq!(engine.ledger.free_balance(&wallet, &TokenTicker::BTC), 10);
+        assert_eq!(
+            engine.amm_pools[&pair].reserve(&TokenTicker::BTC),
+            Some(u64::MAX / 2 + 1)
+        );
+        assert!(engine.token_events.is_empty());
+
+        engine
+            .ledger
+            .deposit(wallet.clone(), TokenTicker::USDT, u64::MAX / 3)
+            .unwrap();
+        assert_eq!(
+            engine.redenominate(TokenTicker::USDT, 4, 1),
+            Err(RedenominationError::AmountOverflow)
+        );
+        assert_eq!(
+            engine.ledger.free_balance(&wallet, &TokenTicker::USDT),
+            u64::MAX / 3
+        );
+    }
+
+    #[test]
+    fn test_reverse_split_rescales_escrows_withdrawals_and_referrals() {
+        use crate::corelib::escrow::EscrowCondition;
+
+        let mut engine = TradeEngine::new();
+        let payer = Wallet::new(String::from("splitpayer"));
+        let payee = Wallet::new(String::from("splitpayee"));
+        engine.list_new_token(TokenTicker::ETH);
+        engine
+            .ledger
+            .deposit(payer.clone(), TokenTicker::ETH, 5_000)
+            .unwrap();
+        let escrow = engine
+            .ledger
+            .create_escrow(
+                &payer,
+                &payee,
+                &TokenTicker::ETH,
+                3_000,
+                EscrowCondition::TimeLock {
+                    release_at_millis: 0,
+                },
+                0,
+            )
+            .unwrap();
+        let withdrawal = engine
+            .request_withdrawal(&payer, &TokenTicker::ETH, 2_000)
+            .unwrap();
+
+        let request = engine
+            .rfq
+            .request_quote(
+                payee.clone(),
+                TokenTicker::ETH,
+                TokenTicker::USDT,
+                BuyOrSell::Buy,
+                1,
+                0,
+            )
+            .unwrap();
+        assert_eq!(
+            engine.redenominate(TokenTicker::ETH, 1, 1000),
+            Err(RedenominationError::OpenQuoteRequests)
+        );
+        engine
+            .rfq
+            .cancel_request(&mut engine.ledger, &payee, request)
+            .unwrap();
+
+        engine.redenominate(TokenTicker::ETH, 1, 1000).unwrap();
+        assert_eq!(engine.ledger.locked_balance(&payer, &TokenTicker::ETH), 5);
+        assert_eq!(engine.ledger.escrows.get(escrow).unwrap().amount, 3);
+        assert_eq!(engine.withdrawals.request(withdrawal).unwrap().amount, 2);
+
+        engine.ledger.release_escrow(escrow, &payee, 0).unwrap();
+        assert_eq!(engine.ledger.free_balance(&payee, &TokenTicker::ETH), 3);
+        engine.process_withdrawals();
+        engine.approve_withdrawal(withdrawal).unwrap();
+        assert_eq!(engine.ledger.balance(&payer, &TokenTicker::ETH).total(), 0);
+    }
+
+    #[test]
+    fn test_reverse_split_rounds_each_reservation_and_frees_the_rest() {
+        use crate::corelib::escrow::EscrowCondition;
+
+        let mut engine = TradeEngine::new();
+        let payer = Wallet::new(String::from("splitreserver"));
+        let payee = Wallet::new(String::from("splitreceiver"));
+        engine.list_new_token(TokenTicker::ETH);
+        engine
+            .ledger
+            .deposit(payer.clone(), TokenTicker::ETH, 10_000)
+            .unwrap();
+        let escrow = engine
+            .ledger
+            .create_escrow(
+                &payer,
+                &payee,
+                &TokenTicker::ETH,
+                1_500,
+                EscrowCondition::TimeLock {
+                    release_at_millis: 0,
+                },
+                0,
+            )
+            .unwrap();
+        let withdrawal = engine
+            .request_withdrawal(&payer, &TokenTicker::ETH, 1_500)
+            .unwrap();
+        engine.create_staking_pool(TokenTicker::ETH, TokenTicker::USDT, 0, 0);
+        engine
+            .staking_pools
+            .get_mut(&TokenTicker::ETH)
+            .unwrap()
+            .stake(&mut engine.ledger, &payer, 1_500)
+            .unwrap();
+        engine
+            .ledger
+            .lock(&payer, &TokenTicker::ETH, 1_500)
+            .unwrap();
+
+        // 6_000 locked would round to 6, but its four parts round to 1 each
+        engine.redenominate(TokenTicker::ETH, 1, 1000).unwrap();
+        assert_eq!(
+            engine.ledger.balance(&payer, &TokenTicker::ETH),
+            Balance { free: 6, locked: 4 }
+        );
+
+        engine.ledger.release_escrow(escrow, &payee, 0).unwrap();
+        engine.process_withdrawals();
+        engine.approve_withdrawal(withdrawal).unwrap();
+        engine
+            .staking_pools
+            .get_mut(&TokenTicker::ETH)
+            .unwrap()
+            .unstake(&mut engine.ledger, &payer, 1)
+            .unwrap();
+        engine.ledger.unlock(&payer, &TokenTicker::ETH, 1).unwrap();
+        assert_eq!(
+            engine.ledger.balance(&payer, &TokenTicker::ETH),
+            Balance { free: 8, locked: 0 }
+        );
+    }
+
+    #[test]
+    fn test_split_keeps_priority_fee_queue_order() {
+        let mut engine = TradeEngine::new();
+        let wallet = Wallet::new(String::from("splitpriority"));
+        engine.list_new_token(TokenTicker::BTC);
+        engine.priority_fees_enabled = true;
+        engine
+            .ledger
+            .deposit(wallet.clone(), TokenTicker::USDT, 1_000)
+            .unwrap();
+
+        let first = engine
+            .submit_order(&TokenTicker::BTC, BuyOrSell::Buy, 100.0, 1)
+            .unwrap();
+        let rich = engine
+            .submit_priority_order(&wallet, &TokenTicker::BTC, BuyOrSell::Buy, 100.0, 1, 5)
+            .unwrap();
+
+        engine.redenominate(TokenTicker::BTC, 10, 1).unwrap();
+        let queue: Vec<u64> = engine.order_books[&TokenTicker::BTC]
+            .level(BuyOrSell::Buy, 10.0)
+            .iter()
+            .map(|order| order.id)
+            .collect();
+        assert_eq!(queue, vec![rich, first]);
+    }
+}
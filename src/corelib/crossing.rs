@@ -0,0 +1,299 @@
+use super::engine::{OrderError, TradeEngine};
+use super::order::{BuyOrSell, Wallet};
+use super::token::TokenTicker;
+use super::trade::{Trade, TradeKind};
+use super::validation::validate_price;
+
+// How an order takes part in crossing sessions.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CrossingFlags {
+    // Skip sessions whose reference price is worse than this.
+    pub limit_price: Option<f64>,
+    // Cancel whatever is left after the next session instead of carrying
+    // it into later ones.
+    pub next_session_only: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrossingOrder {
+    pub id: u64,
+    pub wallet: Wallet,
+    pub side: BuyOrSell,
+    pub quantity: u32,
+    pub flags: CrossingFlags,
+    pub timestamp: u64,
+}
+
+impl CrossingOrder {
+    fn accepts(&self, price: f64) -> bool {
+        match (self.side, self.flags.limit_price) {
+            (_, None) => true,
+            (BuyOrSell::Buy, Some(limit)) => price <= limit,
+            (BuyOrSell::Sell, Some(limit)) => price >= limit,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrossingFill {
+    pub buy_order: u64,
+    pub sell_order: u64,
+    pub price: f64,
+    pub quantity: u32,
+}
+
+// Interest in one instrument held away from its continuous book and
+// crossed at the external reference price at scheduled points in time.
+// Sessions are aligned to multiples of `interval_millis` on the engine
+// clock.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrossingNetwork {
+    pub interval_millis: u64,
+    pub next_session_millis: u64,
+    orders: Vec<CrossingOrder>,
+    next_order_id: u64,
+}
+
+impl CrossingNetwork {
+    pub fn new(interval_millis: u64, now: u64) -> CrossingNetwork {
+        CrossingNetwork {
+            interval_millis,
+            next_session_millis: (now / interval_millis + 1) * interval_millis,
+            orders: Vec::new(),
+            next_order_id: 0,
+        }
+    }
+
+    pub fn orders(&self) -> &[CrossingOrder] {
+        &self.orders
+    }
+
+    pub fn due(&self, now: u64) -> bool {
+        now >= self.next_session_millis
+    }
+
+    pub fn add_order(
+        &mut self,
+        wallet: Wallet,
+        side: BuyOrSell,
+        quantity: u32,
+        flags: CrossingFlags,
+        timestamp: u64,
+    ) -> u64 {
+        self.next_order_id += 1;
+        self.orders.push(CrossingOrder {
+            id: self.next_order_id,
+            wallet,
+            side,
+            quantity,
+            flags,
+            timestamp,
+        });
+        self.next_order_id
+    }
+
+    pub fn cancel_order(&mut self, order_id: u64) -> Option<CrossingOrder> {
+        let index = self.orders.iter().position(|order| order.id == order_id)?;
+        Some(self.orders.remove(index))
+    }
+
+    // Cross buys against sells at `price` in time priority, skipping orders
+    // whose limit it does not meet, then drop filled and single-session
+    // orders. Missed sessions run once and the next is scheduled after
+    // `now`.
+    pub fn cross(&mut self, price: Option<f64>, now: u64) -> Vec<CrossingFill> {
+        self.next_session_millis = (now / self.interval_millis + 1) * self.interval_millis;
+        let mut fills = Vec::new();
+        if let Some(price) = price {
+            for buy in 0..self.orders.len() {
+                for sell in 0..self.orders.len() {
+                    let (buyer, seller) = (&self.orders[buy], &self.orders[sell]);
+                    if buyer.side != BuyOrSell::Buy
+                        || seller.side != BuyOrSell::Sell
+                        || !buyer.accepts(price)
+                        || !seller.accepts(price)
+                    {
+                        continue;
+                    }
+                    let quantity = buyer.quantity.min(seller.quantity);
+                    if quantity == 0 {
+                        continue;
+                    }
+                    fills.push(CrossingFill {
+                        buy_order: buyer.id,
+                        sell_order: seller.id,
+                        price,
+                        quantity,
+                    });
+                    self.orders[buy].quantity -= quantity;
+                    self.orders[sell].quantity -= quantity;
+                }
+            }
+        }
+        self.orders
+            .retain(|order| order.quantity > 0 && !order.flags.next_session_only);
+        fills
+    }
+}
+
+impl TradeEngine {
+    pub fn enable_crossing_network(&mut self, ticker: TokenTicker, interval_millis: u64) {
+        let now = self.now();
+        self.crossing_networks
+            .insert(ticker, CrossingNetwork::new(interval_millis, now));
+    }
+
+    pub fn disable_crossing_network(&mut self, ticker: &TokenTicker) -> Option<CrossingNetwork> {
+        self.crossing_networks.remove(ticker)
+    }
+
+    pub fn submit_crossing_order(
+        &mut self,
+        wallet: &Wallet,
+        ticker: &TokenTicker,
+        side: BuyOrSell,
+        quantity: u32,
+        flags: CrossingFlags,
+    ) -> Result<u64, OrderError> {
+        if self.is_halted(ticker) {
+            return Err(OrderError::InstrumentHalted);
+        }
+        if quantity == 0 {
+            return Err(OrderError::ZeroQuantity);
+        }
+        if let Some(limit) = flags.limit_price {
+            validate_price(limit)?;
+        }
+        let timestamp = self.now();
+        let network = self
+            .crossing_networks
+            .get_mut(ticker)
+            .ok_or(OrderError::UnknownTicker)?;
+        Ok(network.add_order(wallet.clone(), side, quantity, flags, timestamp))
+    }
+
+    // Run every crossing session that is due at the instrument's latest
+    // index price, publishing the executions as crossing trades. Without
+    // an index price the session passes with no trades. Called at the end
+    // of `match_orders`.
+    pub fn run_crossing_sessions(&mut self) -> Vec<u64> {
+        let now = self.now();
+        let mut tickers: Vec<TokenTicker> = self
+            .crossing_networks
+            .iter()
+            .filter(|(ticker, network)| network.due(now) && !self.is_halted(ticker))
+            .map(|(ticker, _)| ticker.clone())
+            .collect();
+        tickers.sort();
+        let mut trade_ids = Vec::new();
+        for ticker in tickers {
+            let price = self.index.latest(&ticker);
+            let network = self.crossing_networks.get_mut(&ticker).unwrap();
+            let wallets: Vec<(u64, Wallet)> = network
+                .orders()
+                .iter()
+                .map(|order| (order.id, order.wallet.clone()))
+                .collect();
+            let wallet_of = |id: u64| {
+                wallets
+                    .iter()
+                    .find(|(order_id, _)| *order_id == id)
+                    .map(|(_, wallet)| wallet.clone())
+            };
+            for fill in network.cross(price, now) {
+                trade_ids.push(self.trade_feed.publish(Trade {
+                    id: 0,
+                    ticker: ticker.clone(),
+                    price: fill.price,
+                    quantity: fill.quantity as u64,
+                    buyer: wallet_of(fill.buy_order),
+                    seller: wallet_of(fill.sell_order),
+                    // crossing order ids are not lit order ids
+                    buy_order_id: None,
+                    sell_order_id: None,
+                    timestamp: now,
+                    kind: TradeKind::Crossing,
+                    buy_metadata: None,
+                    sell_metadata: None,
+                    settled_notional: None,
+                }));
+            }
+        }
+        trade_ids
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::clock::SimulatedClock;
+
+    #[test]
+    fn test_interest_crosses_at_reference_price_on_schedule() {
+        let clock = SimulatedClock::new(500);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        engine.list_new_token(TokenTicker::ETH);
+        engine.enable_crossing_network(TokenTicker::ETH, 1_000);
+        let buyer = Wallet::new(String::from("crossbuyer"));
+        let seller = Wallet::new(String::from("crossseller"));
+        let picky = Wallet::new(String::from("crosspicky"));
+
+        let submit = |engine: &mut TradeEngine, wallet: &Wallet, side, quantity, flags| {
+            engine
+                .submit_crossing_order(wallet, &TokenTicker::ETH, side, quantity, flags)
+                .unwrap()
+        };
+        submit(
+            &mut engine,
+            &buyer,
+            BuyOrSell::Buy,
+            10,
+            CrossingFlags::default(),
+        );
+        let limited = CrossingFlags {
+            limit_price: Some(105.0),
+            next_session_only: false,
+        };
+        submit(&mut engine, &picky, BuyOrSell::Sell, 5, limited);
+        let once = CrossingFlags {
+            limit_price: None,
+            next_session_only: true,
+        };
+        submit(&mut engine, &seller, BuyOrSell::Sell, 4, once);
+        engine.publish_index_price(TokenTicker::ETH, 100.0);
+
+        // nothing crosses before the session, and never on the lit book
+        assert!(engine.match_orders().is_empty());
+        assert!(engine.trade_feed.trades().is_empty());
+
+        clock.set(1_000);
+        engine.match_orders();
+        let trades = engine.trade_feed.trades();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].kind, TradeKind::Crossing);
+        assert_eq!((trades[0].price, trades[0].quantity), (100.0, 4));
+        assert_eq!(trades[0].seller, Some(seller));
+
+        let network = &engine.crossing_networks[&TokenTicker::ETH];
+        assert_eq!(network.next_session_millis, 2_000);
+        let resting: Vec<(u64, u32)> = network
+            .orders()
+            .iter()
+            .map(|order| (order.id, order.quantity))
+            .collect();
+        assert_eq!(resting, vec![(1, 6), (2, 5)]);
+
+        // the limit is met at the next session
+        engine.publish_index_price(TokenTicker::ETH, 106.0);
+        clock.set(2_500);
+        engine.match_orders();
+        let trade = engine.trade_feed.last_trade(&TokenTicker::ETH).unwrap();
+        assert_eq!((trade.price, trade.quantity), (106.0, 5));
+        assert_eq!(trade.seller, Some(picky));
+        assert_eq!(
+            engine.crossing_networks[&TokenTicker::ETH].next_session_millis,
+            3_000
+        );
+    }
+}
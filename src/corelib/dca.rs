@@ -0,0 +1,214 @@
+use std::collections::BTreeMap;
+
+use super::engine::TradeEngine;
+use super::order::{BuyOrSell, Wallet};
+use super::router::RouteError;
+use super::token::TokenTicker;
+
+// Recurring market order: every `interval_millis` buy `base` with `amount`
+// of `quote`, or sell `amount` of `base` for `quote`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecurringOrder {
+    pub wallet: Wallet,
+    pub base: TokenTicker,
+    pub quote: TokenTicker,
+    pub side: BuyOrSell,
+    pub amount: u64,
+    pub interval_millis: u64,
+    pub next_run_millis: u64,
+    pub cancelled: bool,
+    pub history: Vec<RecurringExecution>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecurringOutcome {
+    Executed { spent: u64, received: u64 },
+    Failed { reason: RouteError },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecurringExecution {
+    pub timestamp: u64,
+    pub outcome: RecurringOutcome,
+}
+
+#[derive(Default)]
+pub struct RecurringOrders {
+    schedules: BTreeMap<u64, RecurringOrder>,
+    next_id: u64,
+}
+
+impl RecurringOrders {
+    pub fn new() -> RecurringOrders {
+        RecurringOrders::default()
+    }
+
+    pub fn get(&self, id: u64) -> Option<&RecurringOrder> {
+        self.schedules.get(&id)
+    }
+
+    pub fn for_wallet<'a>(
+        &'a self,
+        wallet: &'a Wallet,
+    ) -> impl Iterator<Item = (u64, &'a RecurringOrder)> {
+        self.schedules
+            .iter()
+            .filter(move |(_, schedule)| &schedule.wallet == wallet)
+            .map(|(id, schedule)| (*id, schedule))
+    }
+}
+
+impl TradeEngine {
+    // The first execution is due immediately. Returns the schedule id.
+    #[allow(clippy::too_many_arguments)]
+    pub fn schedule_recurring_order(
+        &mut self,
+        wallet: &Wallet,
+        base: TokenTicker,
+        quote: TokenTicker,
+        side: BuyOrSell,
+        amount: u64,
+        interval_millis: u64,
+    ) -> u64 {
+        let now = self.now();
+        let orders = &mut self.recurring_orders;
+        orders.next_id += 1;
+        orders.schedules.insert(
+            orders.next_id,
+            RecurringOrder {
+                wallet: wallet.clone(),
+                base,
+                quote,
+                side,
+                amount,
+                interval_millis: interval_millis.max(1),
+                next_run_millis: now,
+                cancelled: false,
+                history: Vec::new(),
+            },
+        );
+        orders.next_id
+    }
+
+    // Stop future executions; the history is kept. False if unknown or
+    // already cancelled.
+    pub fn cancel_recurring_order(&mut self, id: u64) -> bool {
+        match self.recurring_orders.schedules.get_mut(&id) {
+            Some(schedule) if !schedule.cancelled => {
+                schedule.cancelled = true;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Execute every schedule that is due through the router. A schedule
+    // runs at most once per call: intervals missed while nobody drove the
+    // engine are skipped rather than executed in a burst.
+    pub fn run_recurring_orders(&mut self) -> Vec<(u64, RecurringExecution)> {
+        let now = self.now();
+        let due: Vec<u64> = self
+            .recurring_orders
+            .schedules
+            .iter()
+            .filter(|(_, schedule)| !schedule.cancelled && schedule.next_run_millis <= now)
+            .map(|(id, _)| *id)
+            .collect();
+
+        let mut executions = Vec::new();
+        for id in due {
+            let schedule = self.recurring_orders.schedules[&id].clone();
+            let (token_in, token_out) = match schedule.side {
+                BuyOrSell::Buy => (&schedule.quote, &schedule.base),
+                BuyOrSell::Sell => (&schedule.base, &schedule.quote),
+            };
+            let outcome = match self.swap_exact_in(
+                &schedule.wallet,
+                token_in,
+                token_out,
+                schedule.amount,
+                0,
+                now,
+            ) {
+                Ok(received) => RecurringOutcome::Executed {
+                    spent: schedule.amount,
+                    received,
+                },
+                Err(reason) => RecurringOutcome::Failed { reason },
+            };
+            let execution = RecurringExecution {
+                timestamp: now,
+                outcome,
+            };
+            let schedule = self.recurring_orders.schedules.get_mut(&id).unwrap();
+            let elapsed = now - schedule.next_run_millis;
+            schedule.next_run_millis +=
+                (elapsed / schedule.interval_millis + 1) * schedule.interval_millis;
+            schedule.history.push(execution.clone());
+            executions.push((id, execution));
+        }
+        executions
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::clock::SimulatedClock;
+    use crate::corelib::ledger::LedgerError;
+    use crate::corelib::token::Pair;
+
+    #[test]
+    fn test_recurring_buys_follow_the_clock() {
+        let clock = SimulatedClock::new(0);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        let wallet = Wallet::new(String::from("dcawallet"));
+        let pool = engine
+            .amm_pools
+            .entry(Pair::new(TokenTicker::ETH, TokenTicker::USDT))
+            .or_default();
+        pool.add_liquidity(TokenTicker::ETH, 1_000_000);
+        pool.add_liquidity(TokenTicker::USDT, 1_000_000);
+        engine
+            .ledger
+            .deposit(wallet.clone(), TokenTicker::USDT, 25)
+            .unwrap();
+
+        let id = engine.schedule_recurring_order(
+            &wallet,
+            TokenTicker::ETH,
+            TokenTicker::USDT,
+            BuyOrSell::Buy,
+            10,
+            3_600_000,
+        );
+        assert_eq!(engine.run_recurring_orders().len(), 1);
+        clock.advance(1_000);
+        assert!(engine.run_recurring_orders().is_empty());
+
+        // two missed intervals only execute once
+        clock.advance(3 * 3_600_000);
+        assert_eq!(engine.run_recurring_orders().len(), 1);
+        assert_eq!(engine.ledger.free_balance(&wallet, &TokenTicker::ETH), 18);
+        assert_eq!(
+            engine.recurring_orders.get(id).unwrap().next_run_millis,
+            4 * 3_600_000
+        );
+
+        clock.advance(3_600_000);
+        let executions = engine.run_recurring_orders();
+        assert!(matches!(
+            executions[0].1.outcome,
+            RecurringOutcome::Failed {
+                reason: RouteError::Ledger(LedgerError::InsufficientFree { .. })
+            }
+        ));
+        assert_eq!(engine.recurring_orders.get(id).unwrap().history.len(), 3);
+
+        assert!(engine.cancel_recurring_order(id));
+        clock.advance(3_600_000);
+        assert!(engine.run_recurring_orders().is_empty());
+        assert!(!engine.cancel_recurring_order(id));
+    }
+}
@@ -0,0 +1,335 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use ordered_float::OrderedFloat;
+
+use super::order::BuyOrSell;
+
+// Tolerance applied before rounding so that prices sitting exactly on a band
+// edge (e.g. 100.5 / 0.5) are not pushed into the neighbouring band by float
+// representation error.
+pub(crate) const BAND_EPSILON: f64 = 1e-9;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DepthLevel {
+    pub price: f64,
+    pub quantity: u64,
+}
+
+// How a level is turned into synthetic orders when a book is built from a
+// snapshot. Orders never exceed `u32::MAX`; larger amounts are split further.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LevelSplit {
+    // One order for the whole level.
+    Single,
+    // `count` orders of near-equal size, the remainder on the first ones.
+    Even { count: u32 },
+    // Orders of `max_quantity`, the last one taking what is left.
+    MaxSize { max_quantity: u32 },
+}
+
+impl LevelSplit {
+    // Order quantities for a level of `quantity`, summing to it.
+    pub fn sizes(self, quantity: u64) -> Vec<u32> {
+        let parts: Vec<u64> = match self {
+            LevelSplit::Single => vec![quantity],
+            LevelSplit::Even { count } => {
+                let count = count.max(1) as u64;
+                (0..count)
+                    .map(|index| quantity / count + u64::from(index < quantity % count))
+                    .collect()
+            }
+            LevelSplit::MaxSize { max_quantity } => {
+                let max_quantity = max_quantity.max(1) as u64;
+                (0..quantity.div_ceil(max_quantity))
+                    .map(|index| max_quantity.min(quantity - index * max_quantity))
+                    .collect()
+            }
+        };
+        parts
+            .into_iter()
+            .filter(|part| *part > 0)
+            .flat_map(|part| {
+                let chunk = u32::MAX as u64;
+                (0..part.div_ceil(chunk)).map(move |index| chunk.min(part - index * chunk) as u32)
+            })
+            .collect()
+    }
+}
+
+// New total quantity at a level; zero removes the level.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LevelChange {
+    pub side: BuyOrSell,
+    pub price: f64,
+    pub quantity: u64,
+}
+
+// Aggregated view of a book: bids best (highest) first, asks best (lowest)
+// first.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DepthSnapshot {
+    pub bids: Vec<DepthLevel>,
+    pub asks: Vec<DepthLevel>,
+}
+
+impl fmt::Display for DepthSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in self.ladder(usize::MAX) {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+impl DepthSnapshot {
+    pub fn best_bid(&self) -> Option<&DepthLevel> {
+        self.bids.first()
+    }
+
+    pub fn best_ask(&self) -> Option<&DepthLevel> {
+        self.asks.first()
+    }
+
+    // Header, then up to `levels` levels a side: asks from the top of the
+    // ladder down to the best ask, then bids from the best bid down.
+    pub fn ladder(&self, levels: usize) -> Vec<String> {
+        let mut lines = vec![format!("{:>10} {:>14} {:>10}", "BID", "PRICE", "ASK")];
+        for level in self.asks.iter().take(levels).rev() {
+            lines.push(format!(
+                "{:>10} {:>14} {:>10}",
+                "", level.price, level.quantity
+            ));
+        }
+        for level in self.bids.iter().take(levels) {
+            lines.push(format!(
+                "{:>10} {:>14} {:>10}",
+                level.quantity, level.price, ""
+            ));
+        }
+        lines
+    }
+
+    // Minimal level changes from `earlier` to this snapshot: one per price
+    // whose quantity differs, bids then asks, best price first.
+    pub fn diff(&self, earlier: &DepthSnapshot) -> Vec<LevelChange> {
+        let mut changes = Self::diff_side(BuyOrSell::Buy, &earlier.bids, &self.bids);
+        changes.extend(Self::diff_side(BuyOrSell::Sell, &earlier.asks, &self.asks));
+        changes
+    }
+
+    fn diff_side(
+        side: BuyOrSell,
+        earlier: &[DepthLevel],
+        later: &[DepthLevel],
+    ) -> Vec<LevelChange> {
+        let mut levels: BTreeMap<OrderedFloat<f64>, (u64, u64)> = BTreeMap::new();
+        for level in earlier {
+            levels.entry(OrderedFloat(level.price)).or_default().0 = level.quantity;
+        }
+        for level in later {
+            levels.entry(OrderedFloat(level.price)).or_default().1 = level.quantity;
+        }
+        let changes = levels
+            .into_iter()
+            .filter(|(_, (before, after))| before != after)
+            .map(|(price, (_, quantity))| LevelChange {
+                side,
+                price: price.into_inner(),
+                quantity,
+            });
+        match side {
+            BuyOrSell::Buy => changes.rev().collect(),
+            BuyOrSell::Sell => changes.collect(),
+        }
+    }
+
+    // Apply changes produced by `diff`, keeping levels in book order.
+    pub fn apply_changes(&mut self, changes: &[LevelChange]) {
+        for change in changes {
+            let levels = match change.side {
+                BuyOrSell::Buy => &mut self.bids,
+                BuyOrSell::Sell => &mut self.asks,
+            };
+            match levels.iter().position(|level| level.price == change.price) {
+                Some(index) if change.quantity == 0 => {
+                    levels.remove(index);
+                }
+                Some(index) => levels[index].quantity = change.quantity,
+                None if change.quantity == 0 => {}
+                None => {
+                    let index = levels
+                        .iter()
+                        .position(|level| match change.side {
+                            BuyOrSell::Buy => change.price > level.price,
+                            BuyOrSell::Sell => change.price < level.price,
+                        })
+                        .unwrap_or(levels.len());
+                    levels.insert(
+                        index,
+                        DepthLevel {
+                            price: change.price,
+                            quantity: change.quantity,
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    // Re-bucket the levels to a coarser tick spacing for display. Bids are
+    // rounded down and asks up to the band edge, so an aggregated level never
+    // shows a better price than the orders it contains.
+    //
+    // Panics if `tick` is not a positive finite number.
+    pub fn aggregate(&self, tick: f64) -> DepthSnapshot {
+        assert!(
+            tick.is_finite() && tick > 0.0,
+            "aggregation tick must be positive"
+        );
+        DepthSnapshot {
+            bids: Self::rebucket(&self.bids, tick, |band| (band + BAND_EPSILON).floor(), true),
+            asks: Self::rebucket(&self.asks, tick, |band| (band - BAND_EPSILON).ceil(), false),
+        }
+    }
+
+    fn rebucket(
+        levels: &[DepthLevel],
+        tick: f64,
+        round: impl Fn(f64) -> f64,
+        descending: bool,
+    ) -> Vec<DepthLevel> {
+        let mut bands: BTreeMap<i64, u64> = BTreeMap::new();
+        for level in levels {
+            let band = round(level.price / tick) as i64;
+            *bands.entry(band).or_insert(0) += level.quantity;
+        }
+        let aggregated = bands.into_iter().map(|(band, quantity)| DepthLevel {
+            price: band as f64 * tick,
+            quantity,
+        });
+        if descending {
+            aggregated.rev().collect()
+        } else {
+            aggregated.collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::orderbook::OrderBook;
+
+    fn level(price: f64, quantity: u64) -> DepthLevel {
+        DepthLevel { price, quantity }
+    }
+
+    #[test]
+    fn test_aggregate_rounds_away_from_the_spread() {
+        let snapshot = DepthSnapshot {
+            bids: vec![
+                level(100.49, 5),
+                level(100.01, 3),
+                level(100.0, 2),
+                level(99.99, 7),
+            ],
+            asks: vec![
+                level(100.5, 1),
+                level(100.51, 4),
+                level(100.99, 6),
+                level(101.01, 8),
+            ],
+        };
+
+        let aggregated = snapshot.aggregate(0.5);
+
+        assert_eq!(aggregated.bids, vec![level(100.0, 10), level(99.5, 7)]);
+        assert_eq!(
+            aggregated.asks,
+            vec![level(100.5, 1), level(101.0, 10), level(101.5, 8)]
+        );
+    }
+
+    #[test]
+    fn test_diff_is_minimal_and_applies() {
+        let earlier = DepthSnapshot {
+            bids: vec![level(100.0, 5), level(99.0, 3)],
+            asks: vec![level(101.0, 2), level(102.0, 4)],
+        };
+        let later = DepthSnapshot {
+            bids: vec![level(100.5, 1), level(100.0, 5), level(99.0, 1)],
+            asks: vec![level(102.0, 4)],
+        };
+        let changes = later.diff(&earlier);
+        let change = |side, price, quantity| LevelChange {
+            side,
+            price,
+            quantity,
+        };
+        assert_eq!(
+            changes,
+            vec![
+                change(BuyOrSell::Buy, 100.5, 1),
+                change(BuyOrSell::Buy, 99.0, 1),
+                change(BuyOrSell::Sell, 101.0, 0),
+            ]
+        );
+        let mut patched = earlier.clone();
+        patched.apply_changes(&changes);
+        assert_eq!(patched, later);
+        assert!(later.diff(&later).is_empty());
+    }
+
+    #[test]
+    fn test_warm_start_reproduces_snapshot() {
+        let snapshot = DepthSnapshot {
+            bids: vec![level(100.0, 10), level(99.5, 3)],
+            asks: vec![level(100.5, 7)],
+        };
+        let orderbook = OrderBook::from_depth_snapshot(&snapshot);
+        assert_eq!(orderbook.depth_snapshot(), snapshot);
+        assert_eq!(orderbook.level(BuyOrSell::Buy, 100.0).len(), 1);
+
+        let split =
+            OrderBook::from_depth_snapshot_split(&snapshot, LevelSplit::Even { count: 4 }, 5);
+        assert_eq!(split.depth_snapshot(), snapshot);
+        let sizes: Vec<u32> = split
+            .level(BuyOrSell::Buy, 100.0)
+            .iter()
+            .map(|order| order.quantity)
+            .collect();
+        assert_eq!(sizes, vec![3, 3, 2, 2]);
+        assert_eq!(split.level(BuyOrSell::Sell, 100.5).len(), 4);
+        assert_eq!(split.level(BuyOrSell::Buy, 99.5).len(), 3);
+        assert_eq!(
+            LevelSplit::MaxSize { max_quantity: 4 }.sizes(10),
+            vec![4, 4, 2]
+        );
+        assert_eq!(
+            LevelSplit::Single.sizes(u32::MAX as u64 + 1),
+            vec![u32::MAX, 1]
+        );
+    }
+
+    #[test]
+    fn test_book_prints_as_a_ladder() {
+        let mut book = OrderBook::new();
+        book.add_order(BuyOrSell::Buy, 99.5, 3, 0);
+        book.add_order(BuyOrSell::Buy, 99.5, 2, 0);
+        book.add_order(BuyOrSell::Buy, 98.0, 7, 0);
+        book.add_order(BuyOrSell::Sell, 101.25, 4, 0);
+        assert_eq!(
+            book.to_string(),
+            [
+                "       BID          PRICE        ASK",
+                "                   101.25          4",
+                "         5           99.5           ",
+                "         7             98           ",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+}
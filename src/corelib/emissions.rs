@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+
+use super::engine::TradeEngine;
+use super::ledger::{Ledger, LedgerError};
+use super::order::Wallet;
+use super::token::{Pair, TokenTicker};
+
+// Reward rate over time, in reward token units per second since the
+// schedule started.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EmissionCurve {
+    Constant {
+        per_second: f64,
+    },
+    // Rate halves every `half_life_secs`.
+    Halving {
+        initial_per_second: f64,
+        half_life_secs: f64,
+    },
+    // Rate falls linearly to zero over `duration_secs`.
+    LinearDecay {
+        initial_per_second: f64,
+        duration_secs: f64,
+    },
+}
+
+impl EmissionCurve {
+    // Amount emitted between `from` and `to` seconds after the start.
+    pub fn emitted_between(&self, from: f64, to: f64) -> f64 {
+        if to <= from {
+            return 0.0;
+        }
+        match *self {
+            EmissionCurve::Constant { per_second } => per_second * (to - from),
+            EmissionCurve::Halving {
+                initial_per_second,
+                half_life_secs,
+            } => {
+                let k = std::f64::consts::LN_2 / half_life_secs;
+                initial_per_second / k * ((-k * from).exp() - (-k * to).exp())
+            }
+            EmissionCurve::LinearDecay {
+                initial_per_second,
+                duration_secs,
+            } => {
+                let cumulative = |t: f64| {
+                    let t = t.min(duration_secs);
+                    initial_per_second * (t - t * t / (2.0 * duration_secs))
+                };
+                cumulative(to) - cumulative(from)
+            }
+        }
+    }
+}
+
+pub struct EmissionSchedule {
+    pub pair: Pair,
+    pub reward_token: TokenTicker,
+    pub curve: EmissionCurve,
+    pub start_millis: u64,
+    last_update_millis: u64,
+    // Emitted by the curve but not yet distributed because of rounding.
+    carry: f64,
+}
+
+// Distributes reward tokens to LP holders of each scheduled pair, pro rata to
+// their LP balance at every update. Rewards are paid out of a budget funded
+// per reward token and accrue here until claimed into the ledger.
+#[derive(Default)]
+pub struct Emissions {
+    schedules: Vec<EmissionSchedule>,
+    budgets: HashMap<TokenTicker, u64>,
+    claimable: HashMap<Wallet, HashMap<TokenTicker, u64>>,
+}
+
+impl Emissions {
+    pub fn new() -> Emissions {
+        Emissions::default()
+    }
+
+    pub fn add_schedule(
+        &mut self,
+        pair: Pair,
+        reward_token: TokenTicker,
+        curve: EmissionCurve,
+        start_millis: u64,
+    ) {
+        self.schedules.push(EmissionSchedule {
+            pair,
+            reward_token,
+            curve,
+            start_millis,
+            last_update_millis: start_millis,
+            carry: 0.0,
+        });
+    }
+
+    pub fn schedules(&self) -> &[EmissionSchedule] {
+        &self.schedules
+    }
+
+    pub fn budget(&self, token: &TokenTicker) -> u64 {
+        self.budgets.get(token).copied().unwrap_or(0)
+    }
+
+    pub fn fund(
+        &mut self,
+        ledger: &mut Ledger,
+        funder: &Wallet,
+        token: &TokenTicker,
+        amount: u64,
+    ) -> Result<(), LedgerError> {
+        ledger.debit_free(funder, token, amount)?;
+        *self.budgets.entry(token.clone()).or_insert(0) += amount;
+        Ok(())
+    }
+
+    // Rewards of `token` distributed and not yet claimed.
+    pub fn total_claimable(&self, token: &TokenTicker) -> u64 {
+        self.claimable
+            .values()
+            .filter_map(|tokens| tokens.get(token))
+            .sum()
+    }
+
+    pub fn claimable(&self, wallet: &Wallet, token: &TokenTicker) -> u64 {
+        self.claimable
+            .get(wallet)
+            .and_then(|tokens| tokens.get(token))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    // Emit everything due up to `now_millis`. `holders` returns the LP
+    // holders of a pair with their balances.
+    pub fn update(&mut self, now_millis: u64, holders: impl Fn(&Pair) -> Vec<(Wallet, u64)>) {
+        for schedule in self.schedules.iter_mut() {
+            if now_millis <= schedule.last_update_millis {
+                continue;
+            }
+            let from = (schedule.last_update_millis - schedule.start_millis) as f64 / 1_000.0;
+            let to = (now_millis - schedule.start_millis) as f64 / 1_000.0;
+            schedule.last_update_millis = now_millis;
+
+            let holders = holders(&schedule.pair);
+            let total_lp: u64 = holders.iter().map(|(_, amount)| amount).sum();
+            if total_lp == 0 {
+                // nobody to pay: emissions for this period are skipped
+                continue;
+            }
+
+            let budget = self
+                .budgets
+                .entry(schedule.reward_token.clone())
+                .or_insert(0);
+            let emitted = schedule.curve.emitted_between(from, to) + schedule.carry;
+            let amount = (emitted.floor() as u64).min(*budget);
+            schedule.carry = emitted - emitted.floor();
+
+            let mut distributed = 0;
+            for (wallet, lp) in holders.iter() {
+                let share = (amount as u128 * *lp as u128 / total_lp as u128) as u64;
+                if share == 0 {
+                    continue;
+                }
+                *self
+                    .claimable
+                    .entry(wallet.clone())
+                    .or_default()
+                    .entry(schedule.reward_token.clone())
+                    .or_insert(0) += share;
+                distributed += share;
+            }
+            *budget -= distributed;
+        }
+    }
+
+    // Move everything claimable by `wallet` to its free ledger balance.
+    pub fn claim(&mut self, ledger: &mut Ledger, wallet: &Wallet) -> Vec<(TokenTicker, u64)> {
+        let mut claimed: Vec<(TokenTicker, u64)> = self
+            .claimable
+            .remove(wallet)
+            .unwrap_or_default()
+            .into_iter()
+            .collect();
+        claimed.sort();
+        for (token, amount) in claimed.iter() {
+            ledger.credit(wallet, token, *amount);
+        }
+        claimed
+    }
+}
+
+impl TradeEngine {
+    pub fn update_emissions(&mut self) {
+        let now = self.now();
+        let (pools, ledger) = (&self.amm_pools, &self.ledger);
+        self.emissions.update(now, |pair| {
+            let mut holders = pools
+                .get(pair)
+                .map(|pool| pool.lp_holders(pair))
+                .unwrap_or_default();
+            // wrapped LP tokens earn for whoever holds them in the ledger
+            let lp = TokenTicker::lp(pair.clone());
+            holders.extend(ledger.wallets().filter_map(|wallet| {
+                let held = ledger.balance(wallet, &lp).total();
+                (held > 0).then(|| (wallet.clone(), held))
+            }));
+            holders
+        });
+    }
+
+    // Bring emissions up to date and pay the wallet's rewards into the ledger.
+    pub fn claim_rewards(&mut self, wallet: &Wallet) -> Vec<(TokenTicker, u64)> {
+        self.update_emissions();
+        self.emissions.claim(&mut self.ledger, wallet)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::clock::SimulatedClock;
+
+    #[test]
+    fn test_emission_curves() {
+        let constant = EmissionCurve::Constant { per_second: 2.0 };
+        assert_eq!(constant.emitted_between(0.0, 10.0), 20.0);
+
+        let halving = EmissionCurve::Halving {
+            initial_per_second: 10.0,
+            half_life_secs: 100.0,
+        };
+        let first = halving.emitted_between(0.0, 100.0);
+        let second = halving.emitted_between(100.0, 200.0);
+        assert!((first / second - 2.0).abs() < 1e-9);
+
+        let linear = EmissionCurve::LinearDecay {
+            initial_per_second: 10.0,
+            duration_secs: 100.0,
+        };
+        assert_eq!(linear.emitted_between(0.0, 1_000.0), 500.0);
+        assert_eq!(linear.emitted_between(100.0, 200.0), 0.0);
+    }
+
+    #[test]
+    fn test_rewards_split_by_lp_share_and_claimed() {
+        let clock = SimulatedClock::new(0);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        let treasury = Wallet::new(String::from("emissionstreasury"));
+        let alice = Wallet::new(String::from("emissionsalice"));
+        let bob = Wallet::new(String::from("emissionsbob"));
+        let pair = Pair::new(TokenTicker::ETH, TokenTicker::USDT);
+
+        let pool = engine.amm_pools.entry(pair.clone()).or_default();
+        pool.add_liquidity_pair(
+            alice.clone(),
+            TokenTicker::ETH,
+            100,
+            TokenTicker::USDT,
+            200,
+            2.0,
+            0.01,
+        )
+        .unwrap();
+        pool.add_liquidity_pair(
+            bob.clone(),
+            TokenTicker::ETH,
+            50,
+            TokenTicker::USDT,
+            100,
+            2.0,
+            0.01,
+        )
+        .unwrap();
+
+        engine
+            .ledger
+            .deposit(treasury.clone(), TokenTicker::UNI, 1_000)
+            .unwrap();
+        engine
+            .emissions
+            .fund(&mut engine.ledger, &treasury, &TokenTicker::UNI, 1_000)
+            .unwrap();
+        engine.emissions.add_schedule(
+            pair,
+            TokenTicker::UNI,
+            EmissionCurve::Constant { per_second: 3.0 },
+            0,
+        );
+
+        clock.advance(100_000);
+        assert_eq!(engine.claim_rewards(&alice), vec![(TokenTicker::UNI, 200)]);
+        assert_eq!(engine.ledger.free_balance(&alice, &TokenTicker::UNI), 200);
+        assert_eq!(engine.emissions.claimable(&bob, &TokenTicker::UNI), 100);
+        assert_eq!(engine.emissions.budget(&TokenTicker::UNI), 700);
+
+        // emissions stop when the budget runs out
+        clock.advance(1_000_000);
+        engine.update_emissions();
+        assert_eq!(engine.emissions.budget(&TokenTicker::UNI), 1);
+        assert_eq!(engine.emissions.claimable(&alice, &TokenTicker::UNI), 466);
+    }
+}
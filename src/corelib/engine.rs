@@ -1,149 +1,891 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Instant;
 
+use arc_swap::ArcSwap;
 use ordered_float::OrderedFloat;
 
+use super::algos::ExecutionAlgos;
 use super::amm::AMMPool;
+use super::analytics::{estimate_hidden_liquidity, HiddenLiquidityEstimate};
+use super::audit::AuditLog;
+use super::basket::{Basket, BasketOrders};
+use super::batch_auction::BatchAuction;
+use super::cancel_timer::CancelTimer;
+use super::clock::{Clock, SharedClock, SystemClock};
+use super::collateral::CrossCollateral;
+use super::conservation::ValueTotals;
+use super::corporate_actions::TokenEvent;
+use super::crossing::CrossingNetwork;
+use super::dca::RecurringOrders;
+use super::emissions::Emissions;
+use super::execution_quality::{book_mid, OrderArrival, OrderArrivals};
+use super::expiry::DatedContract;
+use super::fees::FeeEngine;
+use super::funding::PerpetualFunding;
+use super::heatmap::DepthHeatmap;
+use super::index::IndexFeed;
+use super::latency::LatencyStats;
+use super::ledger::{Ledger, LedgerError};
+use super::margin::Position;
+use super::mark_price::MarkPriceConfig;
+use super::market_data_handle::MarketDataView;
+use super::market_order::MarketRemainderPolicy;
+use super::mass_quote::MassQuotes;
+use super::midpoint::MidpointBook;
+use super::open_interest::OpenInterestTracker;
+use super::pool_registry::PoolRegistry;
+use super::price_policy::PricePolicy;
+use super::retention::{Candle, RetentionPolicy};
+use super::rfq::{RfqDesk, RfqError, RfqFill};
+use super::rng::SeededRng;
+use super::rolling_stats::RollingStatsFeed;
+use super::rounding::{Flow, Rounding};
+use super::session::{ScheduleError, SessionSchedule, SessionState, SessionTransition};
+use super::spread::SpreadInstrument;
+use super::staking::StakingPool;
+use super::throttle::{InstrumentThrottle, StressEvent};
 use super::token::{Pair, TokenTicker};
-use super::{order::Order, orderbook::OrderBook};
+use super::trade::{Trade, TradeFeed, TradeKind};
+use super::trade_bust::TradeBust;
+use super::validation::validate_order;
+use super::withdrawals::WithdrawalQueue;
+use super::{
+    order::{BuyOrSell, Order, OrderMetadata, TimeInForce, Wallet},
+    orderbook::{BookRejection, Fill, OrderBook, OrderBookTrait},
+};
 
-pub struct TradeEngine {
-    pub order_books: HashMap<TokenTicker, OrderBook>,
-    pub amm_pools: HashMap<Pair, AMMPool>,
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrderError {
+    UnknownTicker,
+    MarketClosed,
+    InstrumentHalted,
+    // The wallet's resting notional on the instrument would exceed `limit`.
+    OpenNotionalExceeded {
+        exposure: u64,
+        order_notional: u64,
+        limit: u64,
+    },
+    // The instrument's price policy refuses zero and negative prices.
+    NonPositivePrice {
+        price: f64,
+    },
+    NanPrice,
+    InfinitePrice,
+    SubnormalPrice,
+    // The price's magnitude is above `validation::MAX_PRICE`.
+    PriceTooLarge {
+        price: f64,
+    },
+    ZeroQuantity,
+    // Refused by the book's own tick, lot or level configuration.
+    Book(BookRejection),
+    // The wallet already entered `limit` orders on the instrument within
+    // the throttle window.
+    Throttled {
+        limit: u32,
+        window_millis: u64,
+    },
 }
 
-pub trait Amm {
-    fn token_swap(
-        &mut self,
-        token_in: TokenTicker,
-        token_out: TokenTicker,
-        amount_in: u64,
-    ) -> Option<u64>;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeadlineExpired {
+    pub deadline: u64,
+    pub now: u64,
+}
 
-    fn add_liquidity_pair(
-        &mut self,
-        token_a: TokenTicker,
-        amount_a: u64,
-        token_b: TokenTicker,
-        amount_b: u64,
-        target_ratio: f64,
-        tolerance: f64,
-    ) -> u64;
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlockTradeError {
+    UnknownTicker,
+    InvalidQuantity,
+    SelfTrade,
+    NoReferencePrice,
+    PriceOutsideBand { reference: f64, low: f64, high: f64 },
+    // Settlement moves quote tokens from buyer to seller, so a price at or
+    // below zero has nothing to settle.
+    NonPositiveNotional { notional: f64 },
+    // The instrument is closed or halted.
+    Rejected(OrderError),
+    Ledger(LedgerError),
 }
 
-impl Amm for TradeEngine {
-    fn token_swap(
-        &mut self,
-        token_in: TokenTicker,
-        token_out: TokenTicker,
-        amount_in: u64,
-    ) -> Option<u64> {
-        todo!()
+impl From<LedgerError> for BlockTradeError {
+    fn from(err: LedgerError) -> Self {
+        BlockTradeError::Ledger(err)
     }
+}
 
-    fn add_liquidity_pair(
-        &mut self,
-        token_a: TokenTicker,
-        amount_a: u64,
-        token_b: TokenTicker,
-        amount_b: u64,
-        target_ratio: f64,
-        tolerance: f64,
-    ) -> u64 {
-        todo!()
+pub struct TradeEngine {
+    pub order_books: HashMap<TokenTicker, OrderBook>,
+    pub amm_pools: HashMap<Pair, AMMPool>,
+    // Parallel pools of a pair at other fee tiers, keyed by pair and fee.
+    pub tier_pools: HashMap<(Pair, u64), AMMPool>,
+    // Creation policy and metadata of pools opened by wallets.
+    pub pool_registry: PoolRegistry,
+    pub ledger: Ledger,
+    pub fees: FeeEngine,
+    pub staking_pools: HashMap<TokenTicker, StakingPool>,
+    pub emissions: Emissions,
+    pub rfq: RfqDesk,
+    pub trade_feed: TradeFeed,
+    pub token_events: Vec<TokenEvent>,
+    // Instruments entering and leaving stress under their throttle policy.
+    pub stress_events: Vec<StressEvent>,
+    pub audit_log: AuditLog,
+    pub withdrawals: WithdrawalQueue,
+    pub index: IndexFeed,
+    pub perpetuals: HashMap<TokenTicker, PerpetualFunding>,
+    pub mark_price_configs: HashMap<TokenTicker, MarkPriceConfig>,
+    pub dated_contracts: HashMap<TokenTicker, DatedContract>,
+    // Open derivatives positions.
+    pub positions: Vec<Position>,
+    // Books of expired instruments, kept for reference.
+    pub archived_books: HashMap<TokenTicker, OrderBook>,
+    // Token every order book is priced and settled in.
+    pub quote_token: TokenTicker,
+    pub recurring_orders: RecurringOrders,
+    pub retention_policies: HashMap<TokenTicker, RetentionPolicy>,
+    // Trades compacted out of the trade feed.
+    pub candles: HashMap<TokenTicker, Vec<Candle>>,
+    // Depth heatmap recorders per instrument.
+    pub heatmaps: HashMap<TokenTicker, DepthHeatmap>,
+    // Hidden midpoint books, for instruments that enabled one.
+    pub midpoint_books: HashMap<TokenTicker, MidpointBook>,
+    // Parent orders worked by execution algos.
+    pub algos: ExecutionAlgos,
+    // Market maker mass quotes and their protection state.
+    pub mass_quotes: MassQuotes,
+    // Dead man's switches armed by wallets.
+    pub cancel_timers: HashMap<Wallet, CancelTimer>,
+    // Book mid at each order's arrival, for execution-quality reports.
+    pub order_arrivals: OrderArrivals,
+    // Rolling volume, range and volatility per tracked instrument.
+    pub rolling_stats: RollingStatsFeed,
+    // View shared with market data handles, once one has been taken.
+    pub(crate) market_data: Option<Arc<ArcSwap<MarketDataView>>>,
+    // Net contracts per wallet on derivative instruments.
+    pub open_interest: OpenInterestTracker,
+    // Tokens accepted as margin collateral and their haircuts.
+    pub collateral: CrossCollateral,
+    // Synthetic baskets by ticker, traded through their constituents.
+    pub baskets: HashMap<TokenTicker, Basket>,
+    // Basket orders by their children, so busted child fills can be taken
+    // back out of basket positions.
+    pub(crate) basket_orders: BasketOrders,
+    // Instruments matched in frequent batch auctions instead of
+    // continuously.
+    pub batch_auctions: HashMap<TokenTicker, BatchAuction>,
+    // Source of seeds for stochastic features; reseed with `seed_rng`.
+    pub(crate) rng: SeededRng,
+    // Research mode: orders may pay a priority fee to jump the queue.
+    pub priority_fees_enabled: bool,
+    // Fills one matching step may produce across all books, batch clears
+    // included; None matches every crossed book out in one step.
+    pub max_fills_per_step: Option<usize>,
+    // Books left crossed, or batch clears left unfinished, by the last step
+    // because it ran out of fills.
+    pub unfinished_matches: HashSet<TokenTicker>,
+    // Scratch space of the matching step, kept to reuse its allocation.
+    fill_buffer: Vec<Fill>,
+    // Instruments halted by an admin command.
+    pub halted_instruments: HashSet<TokenTicker>,
+    // Maximum resting notional a single wallet may hold on an instrument.
+    pub open_notional_limits: HashMap<TokenTicker, u64>,
+    pub(crate) throttles: HashMap<TokenTicker, InstrumentThrottle>,
+    // How order entry treats zero and negative prices, per instrument.
+    pub price_policies: HashMap<TokenTicker, PricePolicy>,
+    // Maximum relative distance of a block trade from the lit reference price.
+    pub block_trade_band: f64,
+    // What happens to the part of a protected market order that would trade
+    // beyond its protection price.
+    pub market_remainder: MarketRemainderPolicy,
+    // How long after execution a trade may still be busted.
+    pub bust_window_millis: u64,
+    // Trades busted so far, in the order they were busted.
+    pub trade_busts: Vec<TradeBust>,
+    // Spread instruments by name, matched against each other and, through
+    // implied prices, against their legs' books.
+    pub spreads: HashMap<String, SpreadInstrument>,
+    // Scheduled crossing sessions, for instruments that enabled one.
+    pub crossing_networks: HashMap<TokenTicker, CrossingNetwork>,
+    rounding: Rounding,
+    // Totals at the last `verify_conservation`.
+    pub(crate) conservation_baseline: Option<ValueTotals>,
+    pub(crate) clock: SharedClock,
+    latency: Option<LatencyStats>,
+    // Instruments without a schedule trade around the clock.
+    sessions: HashMap<TokenTicker, SessionSchedule>,
+    session_states: HashMap<TokenTicker, SessionState>,
+    // When `update_sessions` last looked at each scheduled instrument.
+    sessions_evaluated_at: HashMap<TokenTicker, u64>,
+}
+
+impl Default for TradeEngine {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl TradeEngine {
     pub fn new() -> TradeEngine {
+        TradeEngine::with_clock(Box::new(SystemClock))
+    }
+
+    pub fn with_clock(clock: Box<dyn Clock>) -> TradeEngine {
         TradeEngine {
             order_books: HashMap::new(),
             amm_pools: HashMap::new(),
+            tier_pools: HashMap::new(),
+            pool_registry: PoolRegistry::new(),
+            ledger: Ledger::new(),
+            fees: FeeEngine::new(Wallet::new(String::from("fee-account"))),
+            staking_pools: HashMap::new(),
+            emissions: Emissions::new(),
+            rfq: RfqDesk::new(),
+            trade_feed: TradeFeed::new(),
+            token_events: Vec::new(),
+            stress_events: Vec::new(),
+            audit_log: AuditLog::new(),
+            withdrawals: WithdrawalQueue::new(),
+            index: IndexFeed::new(),
+            perpetuals: HashMap::new(),
+            mark_price_configs: HashMap::new(),
+            dated_contracts: HashMap::new(),
+            positions: Vec::new(),
+            archived_books: HashMap::new(),
+            quote_token: TokenTicker::USDT,
+            recurring_orders: RecurringOrders::new(),
+            algos: ExecutionAlgos::new(),
+            mass_quotes: MassQuotes::new(),
+            midpoint_books: HashMap::new(),
+            heatmaps: HashMap::new(),
+            retention_policies: HashMap::new(),
+            candles: HashMap::new(),
+            cancel_timers: HashMap::new(),
+            order_arrivals: OrderArrivals::new(),
+            rolling_stats: RollingStatsFeed::new(),
+            market_data: None,
+            open_interest: OpenInterestTracker::new(),
+            collateral: CrossCollateral::new(),
+            baskets: HashMap::new(),
+            basket_orders: BasketOrders::default(),
+            batch_auctions: HashMap::new(),
+            rng: SeededRng::new(0),
+            priority_fees_enabled: false,
+            max_fills_per_step: None,
+            unfinished_matches: HashSet::new(),
+            fill_buffer: Vec::new(),
+            halted_instruments: HashSet::new(),
+            open_notional_limits: HashMap::new(),
+            throttles: HashMap::new(),
+            price_policies: HashMap::new(),
+            block_trade_band: 0.05,
+            market_remainder: MarketRemainderPolicy::Cancel,
+            bust_window_millis: 30 * 60 * 1_000,
+            trade_busts: Vec::new(),
+            spreads: HashMap::new(),
+            crossing_networks: HashMap::new(),
+            rounding: Rounding::default(),
+            conservation_baseline: None,
+            clock: SharedClock::new(clock),
+            latency: None,
+            sessions: HashMap::new(),
+            session_states: HashMap::new(),
+            sessions_evaluated_at: HashMap::new(),
         }
     }
+
+    pub fn now(&self) -> u64 {
+        self.clock.now_millis()
+    }
+
+    // Operations carrying a deadline refuse to run once the clock is past
+    // it, so a stale request replayed later cannot execute at old prices.
+    pub(crate) fn check_deadline(&self, deadline: u64) -> Result<(), DeadlineExpired> {
+        let now = self.now();
+        if now > deadline {
+            return Err(DeadlineExpired { deadline, now });
+        }
+        Ok(())
+    }
+
+    pub fn submit_quote(
+        &mut self,
+        maker: Wallet,
+        request_id: u64,
+        price: f64,
+        ttl_millis: u64,
+    ) -> Result<u64, RfqError> {
+        let now = self.now();
+        self.rfq
+            .submit_quote(&mut self.ledger, maker, request_id, price, ttl_millis, now)
+    }
+
+    pub fn lift_quote(&mut self, taker: &Wallet, quote_id: u64) -> Result<RfqFill, RfqError> {
+        let now = self.now();
+        self.rfq.lift_quote(&mut self.ledger, taker, quote_id, now)
+    }
+
+    // Drop expired RFQ quotes and release what they reserved.
+    pub fn expire_quotes(&mut self) -> Vec<u64> {
+        let now = self.now();
+        self.rfq.purge_expired(&mut self.ledger, now)
+    }
+
+    pub fn create_staking_pool(
+        &mut self,
+        token: TokenTicker,
+        reward_token: TokenTicker,
+        reward_per_interval: u64,
+        start_interval: u64,
+    ) -> &mut StakingPool {
+        self.staking_pools.entry(token.clone()).or_insert_with(|| {
+            StakingPool::new(token, reward_token, reward_per_interval, start_interval)
+        })
+    }
+
+    // Start recording internal timings. Off by default so the hot path pays
+    // nothing unless asked to.
+    pub fn enable_latency_tracking(&mut self) {
+        self.latency.get_or_insert_with(LatencyStats::default);
+    }
+
+    pub fn disable_latency_tracking(&mut self) {
+        self.latency = None;
+    }
+
+    pub fn latency_stats(&self) -> Option<&LatencyStats> {
+        self.latency.as_ref()
+    }
+
+    pub fn set_clock(&mut self, clock: Box<dyn Clock>) {
+        self.clock.replace(clock);
+    }
+
+    // Apply a rounding policy to fees, settlement amounts and everything
+    // else the engine rounds.
+    pub fn set_rounding(&mut self, rounding: Rounding) {
+        self.rounding = rounding;
+        self.fees.rounding = rounding;
+        self.rfq.rounding = rounding;
+    }
+
+    pub fn rounding(&self) -> Rounding {
+        self.rounding
+    }
+
     pub fn list_new_token(&mut self, token_ticker: TokenTicker) {
-        self.order_books
-            .entry(token_ticker)
-            .or_insert(OrderBook::new());
+        self.order_books.entry(token_ticker).or_default();
     }
 
     pub fn get_token_order_book(&mut self, token_ticker: &TokenTicker) -> Option<&mut OrderBook> {
         self.order_books.get_mut(token_ticker)
     }
 
+    // Place an order on a listed token's book, stamped with the engine clock.
+    // Returns the order id.
+    pub fn submit_order(
+        &mut self,
+        ticker: &TokenTicker,
+        side: BuyOrSell,
+        price: f64,
+        quantity: u32,
+    ) -> Result<u64, OrderError> {
+        self.submit_order_with_tif(ticker, side, price, quantity, TimeInForce::GoodTillCancel)
+    }
+
+    // Take a resting order off a book, stamped with the engine clock.
+    pub fn cancel_order(&mut self, ticker: &TokenTicker, order_id: u64) -> Option<Order> {
+        let now = self.now();
+        self.order_books
+            .get_mut(ticker)?
+            .cancel_order(order_id, now)
+    }
+
+    pub fn submit_order_with_tif(
+        &mut self,
+        ticker: &TokenTicker,
+        side: BuyOrSell,
+        price: f64,
+        quantity: u32,
+        time_in_force: TimeInForce,
+    ) -> Result<u64, OrderError> {
+        self.place_order(None, ticker, side, price, quantity, time_in_force, None)
+    }
+
+    // Place an order on behalf of a wallet, subject to the wallet's risk
+    // limits.
+    pub fn submit_wallet_order(
+        &mut self,
+        wallet: &Wallet,
+        ticker: &TokenTicker,
+        side: BuyOrSell,
+        price: f64,
+        quantity: u32,
+        time_in_force: TimeInForce,
+    ) -> Result<u64, OrderError> {
+        self.place_order(
+            Some(wallet),
+            ticker,
+            side,
+            price,
+            quantity,
+            time_in_force,
+            None,
+        )
+    }
+
+    // Place an order carrying `metadata` through to its fills, trades and
+    // book events.
+    #[allow(clippy::too_many_arguments)]
+    pub fn submit_tagged_order(
+        &mut self,
+        wallet: Option<&Wallet>,
+        ticker: &TokenTicker,
+        side: BuyOrSell,
+        price: f64,
+        quantity: u32,
+        time_in_force: TimeInForce,
+        metadata: OrderMetadata,
+    ) -> Result<u64, OrderError> {
+        self.place_order(
+            wallet,
+            ticker,
+            side,
+            price,
+            quantity,
+            time_in_force,
+            Some(metadata),
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn place_order(
+        &mut self,
+        wallet: Option<&Wallet>,
+        ticker: &TokenTicker,
+        side: BuyOrSell,
+        price: f64,
+        quantity: u32,
+        time_in_force: TimeInForce,
+        metadata: Option<OrderMetadata>,
+    ) -> Result<u64, OrderError> {
+        self.place_order_throttled(
+            wallet,
+            ticker,
+            side,
+            price,
+            quantity,
+            time_in_force,
+            metadata,
+            true,
+        )
+    }
+
+    // `place_order`, counting the order against the wallet's throttle only
+    // if `count_throttle`; a market order's remainder was already counted
+    // when the market order entered.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn place_order_throttled(
+        &mut self,
+        wallet: Option<&Wallet>,
+        ticker: &TokenTicker,
+        side: BuyOrSell,
+        price: f64,
+        quantity: u32,
+        time_in_force: TimeInForce,
+        metadata: Option<OrderMetadata>,
+        count_throttle: bool,
+    ) -> Result<u64, OrderError> {
+        let entered = self.latency.as_ref().map(|_| Instant::now());
+        self.update_sessions();
+        self.update_cancel_timers();
+        self.check_accepting_orders(ticker)?;
+        validate_order(price, quantity)?;
+        let price = self.apply_price_policy(wallet, ticker, price)?;
+        self.order_books[ticker]
+            .check_order(side, price, quantity)
+            .map_err(OrderError::Book)?;
+        if let Some(wallet) = wallet {
+            self.check_open_notional(wallet, ticker, price, quantity)?;
+            if count_throttle {
+                self.check_throttle(wallet, ticker)?;
+            }
+        }
+        let timestamp = self.now();
+        let orderbook = self.order_books.get_mut(ticker).unwrap();
+        let arrival = OrderArrival {
+            wallet: wallet.cloned(),
+            side,
+            timestamp,
+            mid: book_mid(orderbook),
+        };
+        let order_id = match metadata {
+            Some(metadata) => orderbook.add_tagged_order(
+                wallet.cloned(),
+                side,
+                price,
+                quantity,
+                timestamp,
+                time_in_force,
+                metadata,
+            ),
+            None => match wallet {
+                Some(wallet) => orderbook.add_wallet_order(
+                    wallet.clone(),
+                    side,
+                    price,
+                    quantity,
+                    timestamp,
+                    time_in_force,
+                ),
+                None => {
+                    orderbook.add_order_with_tif(side, price, quantity, timestamp, time_in_force)
+                }
+            },
+        };
+        self.order_arrivals.record(ticker, order_id, arrival);
+        if let (Some(stats), Some(entered)) = (self.latency.as_mut(), entered) {
+            stats.enter_to_ack.record(entered.elapsed());
+        }
+        Ok(order_id)
+    }
+
+    // Whether the instrument's book takes orders right now. Callers bring
+    // sessions and cancel timers up to date first.
+    pub(crate) fn check_accepting_orders(&self, ticker: &TokenTicker) -> Result<(), OrderError> {
+        if self.session_state(ticker) == SessionState::Closed {
+            return Err(OrderError::MarketClosed);
+        }
+        if self.is_halted(ticker) {
+            return Err(OrderError::InstrumentHalted);
+        }
+        if !self.order_books.contains_key(ticker) {
+            return Err(OrderError::UnknownTicker);
+        }
+        Ok(())
+    }
+
+    pub fn set_session_schedule(
+        &mut self,
+        ticker: TokenTicker,
+        schedule: SessionSchedule,
+    ) -> Result<(), ScheduleError> {
+        schedule.validate()?;
+        self.sessions.insert(ticker, schedule);
+        self.update_sessions();
+        Ok(())
+    }
+
+    pub fn session_schedule(&self, ticker: &TokenTicker) -> Option<&SessionSchedule> {
+        self.sessions.get(ticker)
+    }
+
+    pub fn session_state(&self, ticker: &TokenTicker) -> SessionState {
+        self.session_states
+            .get(ticker)
+            .copied()
+            .unwrap_or(SessionState::Open)
+    }
+
+    // Move every scheduled instrument to the state dictated by the clock.
+    // Closing a session expires its day orders, including closes that fell
+    // between two calls. Called on order entry and matching, so transitions
+    // happen without an external driver.
+    pub fn update_sessions(&mut self) -> Vec<SessionTransition> {
+        let now = self.now();
+        let mut transitions = Vec::new();
+        for (ticker, schedule) in self.sessions.iter() {
+            let to = schedule.state_at(now);
+            let from = self
+                .session_states
+                .insert(ticker.clone(), to)
+                .unwrap_or(SessionState::Open);
+            let missed_close = self
+                .sessions_evaluated_at
+                .insert(ticker.clone(), now)
+                .and_then(|evaluated_at| {
+                    schedule
+                        .last_close_at(now)
+                        .filter(|close| *close > evaluated_at)
+                });
+            let closes = (from != to && to == SessionState::Closed) || missed_close.is_some();
+            let expired_orders = match self.order_books.get_mut(ticker) {
+                Some(order_book) if closes => order_book.expire_day_orders(now),
+                _ => Vec::new(),
+            };
+            match missed_close {
+                // the session closed and reopened since the last call
+                Some(close) if from != SessionState::Closed && to != SessionState::Closed => {
+                    transitions.push(SessionTransition {
+                        ticker: ticker.clone(),
+                        from,
+                        to: SessionState::Closed,
+                        timestamp: close,
+                        expired_orders,
+                    });
+                    transitions.push(SessionTransition {
+                        ticker: ticker.clone(),
+                        from: SessionState::Closed,
+                        to,
+                        timestamp: now,
+                        expired_orders: Vec::new(),
+                    });
+                }
+                _ if from != to || !expired_orders.is_empty() => {
+                    transitions.push(SessionTransition {
+                        ticker: ticker.clone(),
+                        from,
+                        to,
+                        timestamp: now,
+                        expired_orders,
+                    });
+                }
+                _ => {}
+            }
+        }
+        transitions
+    }
+
+    // Price a block trade is checked against: the lit mid when both sides
+    // are quoted, else the one quoted side, else the last trade.
+    pub fn reference_price(&self, ticker: &TokenTicker) -> Option<f64> {
+        let order_book = self.order_books.get(ticker)?;
+        let bid = order_book.best_buy_price().map(|price| price.into_inner());
+        let ask = order_book.best_sell_price().map(|price| price.into_inner());
+        match (bid, ask) {
+            (Some(bid), Some(ask)) => Some((bid + ask) / 2.0),
+            (Some(price), None) | (None, Some(price)) => Some(price),
+            (None, None) => self.trade_feed.last_trade(ticker).map(|trade| trade.price),
+        }
+    }
+
+    // Record a privately negotiated trade: the price must sit within
+    // `block_trade_band` of the lit market, the legs settle through the
+    // ledger in `quote_token`, and the trade is published as off-book.
+    pub fn report_block_trade(
+        &mut self,
+        ticker: TokenTicker,
+        buyer: Wallet,
+        seller: Wallet,
+        price: f64,
+        quantity: u64,
+    ) -> Result<u64, BlockTradeError> {
+        if !self.order_books.contains_key(&ticker) {
+            return Err(BlockTradeError::UnknownTicker);
+        }
+        self.update_sessions();
+        self.update_cancel_timers();
+        self.check_accepting_orders(&ticker)
+            .map_err(BlockTradeError::Rejected)?;
+        if quantity == 0 {
+            return Err(BlockTradeError::InvalidQuantity);
+        }
+        if buyer == seller {
+            return Err(BlockTradeError::SelfTrade);
+        }
+        let reference = self
+            .reference_price(&ticker)
+            .ok_or(BlockTradeError::NoReferencePrice)?;
+        // measured from the reference's magnitude, so the band stays the
+        // right way round for negative prices
+        let width = reference.abs() * self.block_trade_band;
+        let low = reference - width;
+        let high = reference + width;
+        if !(low..=high).contains(&price) {
+            return Err(BlockTradeError::PriceOutsideBand {
+                reference,
+                low,
+                high,
+            });
+        }
+
+        let notional = price * quantity as f64;
+        if notional <= 0.0 {
+            return Err(BlockTradeError::NonPositiveNotional { notional });
+        }
+        let notional = self.rounding.amount(notional, Flow::Transfer);
+        self.ledger.settle(
+            &buyer,
+            &seller,
+            &ticker,
+            quantity,
+            &self.quote_token,
+            notional,
+        )?;
+
+        let timestamp = self.now();
+        Ok(self.trade_feed.publish(Trade {
+            id: 0,
+            ticker,
+            price,
+            quantity,
+            buyer: Some(buyer),
+            seller: Some(seller),
+            buy_order_id: None,
+            sell_order_id: None,
+            timestamp,
+            kind: TradeKind::OffBook,
+            buy_metadata: None,
+            sell_metadata: None,
+            settled_notional: Some(notional),
+        }))
+    }
+
+    // See `analytics::estimate_hidden_liquidity`.
+    pub fn hidden_liquidity(
+        &self,
+        ticker: &TokenTicker,
+        side: BuyOrSell,
+        price: f64,
+        window_millis: u64,
+    ) -> Option<HiddenLiquidityEstimate> {
+        let order_book = self.order_books.get(ticker)?;
+        Some(estimate_hidden_liquidity(
+            order_book.events(),
+            side,
+            price,
+            window_millis,
+        ))
+    }
+
     pub fn match_orders(&mut self) -> Vec<(u64, u64, f64, u32)> {
         let mut matched_trades = Vec::new();
-        for (_, orderbook) in self.order_books.iter_mut() {
-            let buy_prices: Vec<OrderedFloat<f64>> = orderbook.buy_orders.keys().copied().collect();
-            let sell_prices: Vec<OrderedFloat<f64>> =
-                orderbook.sell_orders.keys().copied().collect();
-
-            let mut buy_iter = buy_prices.iter().copied();
-            let mut sell_iter = sell_prices.iter().copied();
-
-            while let (Some(buy_price), Some(sell_price)) = (buy_iter.next(), sell_iter.next()) {
-                if buy_price >= sell_price {
-                    let buy_orders = orderbook.buy_orders.entry(buy_price).or_insert(Vec::new());
-                    let sell_orders = orderbook
-                        .sell_orders
-                        .entry(sell_price)
-                        .or_insert(Vec::new());
-
-                    let buy_order = buy_orders.pop().unwrap();
-                    let sell_order = sell_orders.pop().unwrap();
-
-                    let quantity_traded = buy_order.quantity.min(sell_order.quantity);
-
-                    matched_trades.push((
-                        buy_order.id,
-                        sell_order.id,
-                        sell_order.price,
-                        quantity_traded,
-                    ));
-
-                    if buy_order.quantity > quantity_traded {
-                        buy_orders.push(Order {
-                            quantity: buy_order.quantity - quantity_traded,
-                            ..buy_order
-                        });
-                    }
+        self.match_orders_into(&mut matched_trades);
+        matched_trades
+    }
 
-                    if sell_order.quantity > quantity_traded {
-                        sell_orders.push(Order {
-                            quantity: sell_order.quantity - quantity_traded,
-                            ..sell_order
-                        });
+    // `match_orders` appending (buy order id, sell order id, price,
+    // quantity) to a buffer the caller reuses across steps.
+    pub fn match_orders_into(&mut self, matched_trades: &mut Vec<(u64, u64, f64, u32)>) {
+        let started = self.latency.as_ref().map(|_| Instant::now());
+        let mut fills = std::mem::take(&mut self.fill_buffer);
+        self.update_sessions();
+        self.update_cancel_timers();
+        let timestamp = self.now();
+        let mut budget = self.max_fills_per_step.unwrap_or(usize::MAX);
+        // a fixed order so a limited step always favours the same books
+        let mut books: Vec<(&TokenTicker, &mut OrderBook)> = self.order_books.iter_mut().collect();
+        books.sort_by(|a, b| a.0.cmp(b.0));
+        // match passes on throttled books, reported once the books are free
+        let mut pass_timings = Vec::new();
+        for (ticker, orderbook) in books {
+            let pass_started = self.throttles.contains_key(ticker).then(Instant::now);
+            // only continuous sessions match; pre-open orders wait for the open
+            let state = self.session_states.get(ticker);
+            if state.is_some_and(|state| *state != SessionState::Open)
+                || self.halted_instruments.contains(ticker)
+            {
+                continue;
+            }
+            let kind = match self.batch_auctions.get_mut(ticker) {
+                Some(batch) => {
+                    let price = match batch.unfinished_clear {
+                        Some(price) => Some(price.into_inner()),
+                        // batch instruments rest until their interval ends
+                        None if !batch.due(timestamp) => continue,
+                        None => {
+                            let since = batch.interval_start();
+                            batch.advance(timestamp);
+                            let reference =
+                                self.trade_feed.last_trade(ticker).map(|trade| trade.price);
+                            orderbook.indicative_uncross(reference).map(|uncross| {
+                                batch.shuffle_arrivals(orderbook, uncross.price, since);
+                                uncross.price
+                            })
+                        }
+                    };
+                    if let Some(price) = price {
+                        budget -= orderbook.uncross_at_into(price, timestamp, budget, &mut fills);
+                        batch.unfinished_clear = orderbook
+                            .is_crossed_at(price)
+                            .then_some(OrderedFloat(price));
+                    }
+                    if batch.unfinished_clear.is_some() {
+                        self.unfinished_matches.insert(ticker.clone());
+                    } else {
+                        self.unfinished_matches.remove(ticker);
+                    }
+                    TradeKind::Auction
+                }
+                None => {
+                    budget -= orderbook.match_orders_into(timestamp, budget, &mut fills);
+                    if orderbook.is_crossed() {
+                        self.unfinished_matches.insert(ticker.clone());
+                    } else {
+                        self.unfinished_matches.remove(ticker);
                     }
-                } else {
-                    break;
+                    TradeKind::Lit
                 }
+            };
+            for fill in fills.drain(..) {
+                matched_trades.push((
+                    fill.buy_order_id,
+                    fill.sell_order_id,
+                    fill.price,
+                    fill.quantity,
+                ));
+                self.trade_feed.publish(Trade {
+                    id: 0,
+                    ticker: ticker.clone(),
+                    price: fill.price,
+                    quantity: fill.quantity as u64,
+                    buyer: fill.buy_wallet,
+                    seller: fill.sell_wallet,
+                    buy_order_id: Some(fill.buy_order_id),
+                    sell_order_id: Some(fill.sell_order_id),
+                    timestamp,
+                    kind,
+                    buy_metadata: fill.buy_metadata,
+                    sell_metadata: fill.sell_metadata,
+                    settled_notional: None,
+                });
+            }
+            if let Some(pass_started) = pass_started {
+                let nanos = pass_started.elapsed().as_nanos().min(u64::MAX as u128) as u64;
+                pass_timings.push((ticker.clone(), nanos));
             }
         }
+        self.fill_buffer = fills;
+        for (ticker, nanos) in pass_timings {
+            self.observe_match_latency(&ticker, nanos);
+        }
+        self.match_spreads();
+        self.match_midpoint_books();
+        self.run_crossing_sessions();
+        self.update_quote_protection();
+        self.update_open_interest();
+        self.update_rolling_stats();
+        self.publish_market_data();
 
-        matched_trades
+        if let (Some(stats), Some(started)) = (self.latency.as_mut(), started) {
+            stats.match_loop.record(started.elapsed());
+        }
     }
 }
 
 #[cfg(test)]
 mod test {
 
-    use crate::corelib::token::{Category, CryptoExchange, Market, Token};
+    use crate::corelib::amm::LiquidityError;
+    use crate::corelib::token::{Category, CryptoExchange, Market, Token, USExchange};
 
     use self::{TokenTicker, TradeEngine};
     use super::super::order::BuyOrSell;
     use super::super::orderbook::OrderBookTrait;
     use super::*;
+    use crate::corelib::admin::AdminCommand;
     use crate::corelib::order::Wallet;
     use chrono::Utc;
 
     #[test]
     #[ignore]
-
     fn test_token_listing() {
         // Test listing of tokens
         let mut engine_1 = TradeEngine::new();
         let new_token = Token::new(
             TokenTicker::BTC,
-            Category::Infatrusture,
+            Category::Infrastructure,
             Market::OtherMarket(CryptoExchange::Binance),
         );
         engine_1.list_new_token(new_token.ticker.clone());
@@ -215,8 +957,8 @@ mod test {
         let mut engine = TradeEngine::new();
         let new_token = Token::new(
             TokenTicker::DOT,
-            Category::Infatrusture,
-            Market::OtherMarket(CryptoExchange::Coinbase),
+            Category::Infrastructure,
+            Market::USMarket(USExchange::Coinbase),
         );
         engine.list_new_token(new_token.ticker.clone());
         assert_eq!(engine.order_books.len(), 1);
@@ -281,7 +1023,6 @@ mod test {
             20
         );
         let orders_traded = engine.match_orders();
-        println!("{:?}", orders_traded);
         assert_eq!(orders_traded.len(), 1);
     }
 
@@ -296,11 +1037,11 @@ mod test {
             TokenTicker::ETH,
             1000,
             TokenTicker::USDT,
-            5000,
+            2000,
             2.0,
             0.1,
         );
-        assert_eq!(lp_tokens, 10); // Assuming total liquidity is 10000 and each token contributes equally
+        assert_eq!(lp_tokens, Ok(3000)); // One LP token per unit of each token in a new pool
 
         // Add liquidity pair with mismatched ratio (should fail)
         let lp_tokens_fail = pool.add_liquidity_pair(
@@ -312,7 +1053,14 @@ mod test {
             2.0,
             0.1,
         );
-        assert_eq!(lp_tokens_fail, 0); // Should return 0 LP tokens due to ratio mismatch
+        // Should be rejected due to ratio mismatch
+        assert_eq!(
+            lp_tokens_fail,
+            Err(LiquidityError::RatioOutOfTolerance {
+                actual: 4.0,
+                target: 2.0
+            })
+        );
     }
 
     #[test]
@@ -324,17 +1072,357 @@ mod test {
             TokenTicker::ETH,
             1000,
             TokenTicker::USDT,
-            5000,
+            2000,
             2.0,
             0.1,
-        );
+        )
+        .unwrap();
 
         // Swap ETH for USDT
         let amount_out = pool.token_swap(TokenTicker::ETH, TokenTicker::USDT, 100);
-        assert_eq!(amount_out, Some(200)); // Assuming a constant product model with 1:2 ratio
+        assert_eq!(amount_out, Some(200)); // At the pool's 1:2 ratio
 
         // Swap USDT for ETH
         let amount_out = pool.token_swap(TokenTicker::USDT, TokenTicker::ETH, 1000);
-        assert_eq!(amount_out, Some(50)); // Assuming a constant product model with 1:2 ratio
+        assert_eq!(amount_out, Some(611)); // At the 1100:1800 ratio left by the first swap
+    }
+
+    #[test]
+    fn test_report_block_trade() {
+        let mut engine = TradeEngine::new();
+        let buyer = Wallet::new(String::from("blockbuyer"));
+        let seller = Wallet::new(String::from("blockseller"));
+        engine.list_new_token(TokenTicker::ETH);
+        engine
+            .ledger
+            .deposit(buyer.clone(), TokenTicker::USDT, 1_000_000)
+            .unwrap();
+        engine
+            .ledger
+            .deposit(seller.clone(), TokenTicker::ETH, 500)
+            .unwrap();
+
+        // no lit market to validate against yet
+        assert_eq!(
+            engine.report_block_trade(TokenTicker::ETH, buyer.clone(), seller.clone(), 100.0, 10),
+            Err(BlockTradeError::NoReferencePrice)
+        );
+
+        let order_book = engine.get_token_order_book(&TokenTicker::ETH).unwrap();
+        order_book.add_order(BuyOrSell::Buy, 99.0, 5, 1);
+        order_book.add_order(BuyOrSell::Sell, 101.0, 5, 2);
+
+        assert!(matches!(
+            engine.report_block_trade(TokenTicker::ETH, buyer.clone(), seller.clone(), 110.0, 10),
+            Err(BlockTradeError::PriceOutsideBand { .. })
+        ));
+
+        engine.apply_admin(AdminCommand::HaltInstrument {
+            ticker: TokenTicker::ETH,
+        });
+        assert_eq!(
+            engine.report_block_trade(TokenTicker::ETH, buyer.clone(), seller.clone(), 102.0, 10),
+            Err(BlockTradeError::Rejected(OrderError::InstrumentHalted))
+        );
+        engine.apply_admin(AdminCommand::ResumeInstrument {
+            ticker: TokenTicker::ETH,
+        });
+
+        let trade_id = engine
+            .report_block_trade(TokenTicker::ETH, buyer.clone(), seller.clone(), 102.0, 400)
+            .unwrap();
+        let trade = engine.trade_feed.last_trade(&TokenTicker::ETH).unwrap();
+        assert_eq!(trade.id, trade_id);
+        assert_eq!(trade.kind, TradeKind::OffBook);
+        assert_eq!(engine.ledger.free_balance(&buyer, &TokenTicker::ETH), 400);
+        assert_eq!(
+            engine.ledger.free_balance(&seller, &TokenTicker::USDT),
+            40_800
+        );
+        // the lit book is untouched
+        assert_eq!(
+            engine
+                .get_token_order_book(&TokenTicker::ETH)
+                .unwrap()
+                .buy_volume(),
+            Some(5)
+        );
+    }
+
+    #[test]
+    fn test_block_trade_band_around_a_negative_reference() {
+        let mut engine = TradeEngine::new();
+        let buyer = Wallet::new(String::from("blockbuyer"));
+        let seller = Wallet::new(String::from("blockseller"));
+        engine.list_new_token(TokenTicker::ETH);
+        engine.block_trade_band = 0.1;
+        let order_book = engine.get_token_order_book(&TokenTicker::ETH).unwrap();
+        order_book.add_order(BuyOrSell::Buy, -11.0, 5, 1);
+        order_book.add_order(BuyOrSell::Sell, -9.0, 5, 2);
+
+        assert_eq!(
+            engine.report_block_trade(TokenTicker::ETH, buyer.clone(), seller.clone(), -12.0, 1),
+            Err(BlockTradeError::PriceOutsideBand {
+                reference: -10.0,
+                low: -11.0,
+                high: -9.0
+            })
+        );
+        assert_eq!(
+            engine.report_block_trade(TokenTicker::ETH, buyer, seller, -10.5, 2),
+            Err(BlockTradeError::NonPositiveNotional { notional: -21.0 })
+        );
+    }
+
+    #[test]
+    fn test_latency_stats() {
+        let mut engine = TradeEngine::new();
+        engine.list_new_token(TokenTicker::SOL);
+        assert!(engine.latency_stats().is_none());
+
+        engine.enable_latency_tracking();
+        engine
+            .submit_order(&TokenTicker::SOL, BuyOrSell::Buy, 10.0, 5)
+            .unwrap();
+        engine
+            .submit_order(&TokenTicker::SOL, BuyOrSell::Sell, 11.0, 5)
+            .unwrap();
+        assert_eq!(
+            engine.submit_order(&TokenTicker::BTC, BuyOrSell::Sell, 11.0, 5),
+            Err(OrderError::UnknownTicker)
+        );
+        engine.match_orders();
+
+        let stats = engine.latency_stats().unwrap();
+        assert_eq!(stats.enter_to_ack.count(), 2);
+        assert_eq!(stats.match_loop.count(), 1);
+    }
+
+    #[test]
+    fn test_order_metadata_reaches_fills_and_events() {
+        let mut engine = TradeEngine::new();
+        engine.list_new_token(TokenTicker::ETH);
+        let wallet = Wallet::new(String::from("strategies"));
+        let metadata: OrderMetadata = vec![(String::from("strategy"), String::from("mm-3"))].into();
+        let order_id = engine
+            .submit_tagged_order(
+                Some(&wallet),
+                &TokenTicker::ETH,
+                BuyOrSell::Buy,
+                100.0,
+                5,
+                TimeInForce::GoodTillCancel,
+                metadata.clone(),
+            )
+            .unwrap();
+        // a replacement keeps the tags
+        let order_id = engine
+            .replace_order(&TokenTicker::ETH, order_id, 101.0, 5, None)
+            .unwrap();
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Sell, 101.0, 2)
+            .unwrap();
+        engine.match_orders();
+
+        let trade = engine.trade_feed.last_trade(&TokenTicker::ETH).unwrap();
+        assert_eq!(trade.buy_metadata, Some(metadata.clone()));
+        assert_eq!(trade.sell_metadata, None);
+        let book = engine.get_token_order_book(&TokenTicker::ETH).unwrap();
+        assert_eq!(
+            book.find_order(order_id).unwrap().1.metadata,
+            Some(metadata.clone())
+        );
+        let tagged = book
+            .events()
+            .iter()
+            .filter(|event| event.metadata() == Some(&metadata))
+            .count();
+        // the add, its cancel and the replacement's add and fill
+        assert_eq!(tagged, 4);
+    }
+
+    #[test]
+    fn test_sweep_matched_across_steps() {
+        let mut engine = TradeEngine::new();
+        engine.list_new_token(TokenTicker::ETH);
+        engine.max_fills_per_step = Some(2);
+        for price in [10.0, 11.0, 12.0, 13.0, 14.0] {
+            engine
+                .submit_order(&TokenTicker::ETH, BuyOrSell::Sell, price, 1)
+                .unwrap();
+        }
+        let sweep = engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Buy, 20.0, 5)
+            .unwrap();
+
+        let prices: Vec<f64> = engine.match_orders().iter().map(|trade| trade.2).collect();
+        assert_eq!(prices, vec![10.0, 11.0]);
+        assert!(engine.unfinished_matches.contains(&TokenTicker::ETH));
+        // the rest of the sweep keeps its place at the top of the book
+        let book = &engine.order_books[&TokenTicker::ETH];
+        assert_eq!(book.level(BuyOrSell::Buy, 20.0)[0].id, sweep);
+        assert_eq!(book.buy_volume(), Some(3));
+
+        assert_eq!(engine.match_orders().len(), 2);
+        assert_eq!(engine.match_orders().len(), 1);
+        assert!(engine.unfinished_matches.is_empty());
+        assert_eq!(engine.trade_feed.trades().len(), 5);
+    }
+
+    #[test]
+    fn test_match_into_reused_buffer() {
+        let mut engine = TradeEngine::new();
+        engine.list_new_token(TokenTicker::ETH);
+        let maker = Wallet::new(String::from("maker"));
+        engine
+            .ledger
+            .deposit(maker.clone(), TokenTicker::ETH, 10)
+            .unwrap();
+        let resting = engine
+            .submit_wallet_order(
+                &maker,
+                &TokenTicker::ETH,
+                BuyOrSell::Sell,
+                100.0,
+                10,
+                TimeInForce::GoodTillCancel,
+            )
+            .unwrap();
+        let mut matched = Vec::with_capacity(4);
+        for _ in 0..3 {
+            let taker = engine
+                .submit_order(&TokenTicker::ETH, BuyOrSell::Buy, 100.0, 3)
+                .unwrap();
+            matched.clear();
+            engine.match_orders_into(&mut matched);
+            assert_eq!(matched, vec![(taker, resting, 100.0, 3)]);
+        }
+        assert_eq!(matched.capacity(), 4);
+        // the partially filled maker kept its place and its wallet
+        let book = &engine.order_books[&TokenTicker::ETH];
+        assert_eq!(book.level(BuyOrSell::Sell, 100.0)[0].quantity, 1);
+        assert!(engine
+            .trade_feed
+            .trades()
+            .iter()
+            .all(|trade| trade.seller.as_ref() == Some(&maker)));
+    }
+
+    #[test]
+    fn test_session_transitions_and_day_orders() {
+        use crate::corelib::clock::SimulatedClock;
+        use chrono::{Duration, NaiveDate, NaiveTime};
+
+        // Friday 2024-01-05, 08:00 UTC
+        let start = NaiveDate::from_ymd_opt(2024, 1, 5)
+            .unwrap()
+            .and_hms_opt(8, 0, 0)
+            .unwrap()
+            .and_utc()
+            .timestamp_millis() as u64;
+        let clock = SimulatedClock::new(start);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        engine.list_new_token(TokenTicker::ETH);
+        engine
+            .set_session_schedule(
+                TokenTicker::ETH,
+                SessionSchedule::new(
+                    NaiveTime::from_hms_opt(9, 30, 0).unwrap(),
+                    NaiveTime::from_hms_opt(16, 0, 0).unwrap(),
+                )
+                .with_pre_open(Duration::minutes(30)),
+            )
+            .unwrap();
+
+        assert_eq!(
+            engine.session_state(&TokenTicker::ETH),
+            SessionState::Closed
+        );
+        assert_eq!(
+            engine.submit_order(&TokenTicker::ETH, BuyOrSell::Buy, 10.0, 1),
+            Err(OrderError::MarketClosed)
+        );
+
+        // pre-open: orders rest without matching
+        clock.advance(Duration::minutes(70).num_milliseconds() as u64);
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Buy, 10.0, 1)
+            .unwrap();
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Sell, 10.0, 1)
+            .unwrap();
+        assert_eq!(
+            engine.session_state(&TokenTicker::ETH),
+            SessionState::PreOpen
+        );
+        assert!(engine.match_orders().is_empty());
+        assert_eq!(
+            engine
+                .indicative_open(&TokenTicker::ETH)
+                .map(|uncross| (uncross.price, uncross.matched_quantity)),
+            Some((10.0, 1))
+        );
+
+        clock.advance(Duration::minutes(30).num_milliseconds() as u64);
+        assert_eq!(engine.match_orders().len(), 1);
+
+        let day_order = engine
+            .submit_order_with_tif(&TokenTicker::ETH, BuyOrSell::Buy, 9.0, 1, TimeInForce::Day)
+            .unwrap();
+        let gtc_order = engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Buy, 8.0, 1)
+            .unwrap();
+
+        clock.advance(Duration::hours(7).num_milliseconds() as u64);
+        let transitions = engine.update_sessions();
+        assert_eq!(transitions.len(), 1);
+        assert_eq!(transitions[0].from, SessionState::Open);
+        assert_eq!(transitions[0].to, SessionState::Closed);
+        assert_eq!(transitions[0].expired_orders, vec![day_order]);
+
+        let order_book = engine.get_token_order_book(&TokenTicker::ETH).unwrap();
+        assert!(order_book.cancel_order(day_order, 0).is_none());
+        assert!(order_book.cancel_order(gtc_order, 0).is_some());
+    }
+
+    #[test]
+    fn test_day_orders_expire_on_a_missed_close() {
+        use crate::corelib::clock::SimulatedClock;
+        use chrono::{Duration, NaiveDate, NaiveTime};
+
+        // Friday 2024-01-05, 10:00 UTC
+        let start = NaiveDate::from_ymd_opt(2024, 1, 5)
+            .unwrap()
+            .and_hms_opt(10, 0, 0)
+            .unwrap()
+            .and_utc()
+            .timestamp_millis() as u64;
+        let clock = SimulatedClock::new(start);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        engine.list_new_token(TokenTicker::ETH);
+        let nine = NaiveTime::from_hms_opt(9, 0, 0).unwrap();
+        let five = NaiveTime::from_hms_opt(17, 0, 0).unwrap();
+        assert_eq!(
+            engine.set_session_schedule(TokenTicker::ETH, SessionSchedule::new(five, nine)),
+            Err(ScheduleError::CloseNotAfterOpen)
+        );
+        engine
+            .set_session_schedule(TokenTicker::ETH, SessionSchedule::new(nine, five))
+            .unwrap();
+        let day_order = engine
+            .submit_order_with_tif(&TokenTicker::ETH, BuyOrSell::Buy, 9.0, 1, TimeInForce::Day)
+            .unwrap();
+
+        // Monday 10:00: the session is open again, but Friday's close passed
+        clock.advance(Duration::days(3).num_milliseconds() as u64);
+        let transitions = engine.update_sessions();
+        assert_eq!(transitions.len(), 2);
+        assert_eq!(transitions[0].to, SessionState::Closed);
+        assert_eq!(transitions[0].timestamp, start + 7 * 60 * 60 * 1_000);
+        assert_eq!(transitions[0].expired_orders, vec![day_order]);
+        assert_eq!(transitions[1].from, SessionState::Closed);
+        assert_eq!(transitions[1].to, SessionState::Open);
+        assert_eq!(engine.order_books[&TokenTicker::ETH].buy_volume(), Some(0));
+        assert!(engine.update_sessions().is_empty());
     }
 }
@@ -0,0 +1,282 @@
+use std::collections::BTreeMap;
+
+use super::ledger::{rescale_amount, Ledger, LedgerError};
+use super::order::Wallet;
+use super::token::TokenTicker;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EscrowCondition {
+    // Anyone may release once the clock reaches `release_at_millis`.
+    TimeLock { release_at_millis: u64 },
+    // Only `arbiter` may release, or refund to the payer.
+    Authorized { arbiter: Wallet },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Escrow {
+    pub id: u64,
+    pub payer: Wallet,
+    pub payee: Wallet,
+    pub token: TokenTicker,
+    pub amount: u64,
+    pub condition: EscrowCondition,
+    pub created_at: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EscrowError {
+    UnknownEscrow,
+    // The time lock has not expired yet.
+    Locked { release_at_millis: u64 },
+    NotAuthorized,
+    Ledger(LedgerError),
+}
+
+impl From<LedgerError> for EscrowError {
+    fn from(err: LedgerError) -> Self {
+        EscrowError::Ledger(err)
+    }
+}
+
+// Open escrows. Escrowed funds stay in the payer's locked balance until the
+// escrow is released to the payee or refunded.
+#[derive(Debug, Clone, Default)]
+pub struct Escrows {
+    open: BTreeMap<u64, Escrow>,
+    next_id: u64,
+}
+
+impl Escrows {
+    pub fn get(&self, id: u64) -> Option<&Escrow> {
+        self.open.get(&id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Escrow> {
+        self.open.values()
+    }
+
+    // Whether every escrowed amount of `token` still fits a u64 after
+    // `rescale_token` with the same ratio.
+    pub(crate) fn can_rescale_token(
+        &self,
+        token: &TokenTicker,
+        numerator: u64,
+        denominator: u64,
+    ) -> bool {
+        self.open
+            .values()
+            .filter(|escrow| &escrow.token == token)
+            .all(|escrow| rescale_amount(escrow.amount, numerator, denominator).is_some())
+    }
+
+    // Multiply every escrowed amount of `token` by `numerator / denominator`,
+    // rounding down like the locked balances that back them.
+    pub(crate) fn rescale_token(&mut self, token: &TokenTicker, numerator: u64, denominator: u64) {
+        for escrow in self
+            .open
+            .values_mut()
+            .filter(|escrow| &escrow.token == token)
+        {
+            escrow.amount = rescale_amount(escrow.amount, numerator, denominator)
+                .expect("rescale checked beforehand");
+        }
+    }
+}
+
+impl Ledger {
+    // Lock `amount` of the payer's free funds for the payee under
+    // `condition`. Returns the escrow id.
+    pub fn create_escrow(
+        &mut self,
+        payer: &Wallet,
+        payee: &Wallet,
+        token: &TokenTicker,
+        amount: u64,
+        condition: EscrowCondition,
+        now: u64,
+    ) -> Result<u64, EscrowError> {
+        self.lock(payer, token, amount)?;
+        self.escrows.next_id += 1;
+        let id = self.escrows.next_id;
+        self.escrows.open.insert(
+            id,
+            Escrow {
+                id,
+                payer: payer.clone(),
+                payee: payee.clone(),
+                token: token.clone(),
+                amount,
+                condition,
+                created_at: now,
+            },
+        );
+        Ok(id)
+    }
+
+    // Pay the escrowed funds to the payee once the condition allows it.
+    pub fn release_escrow(
+        &mut self,
+        id: u64,
+        caller: &Wallet,
+        now: u64,
+    ) -> Result<Escrow, EscrowError> {
+        let escrow = self.escrows.get(id).ok_or(EscrowError::UnknownEscrow)?;
+        match &escrow.condition {
+            EscrowCondition::TimeLock { release_at_millis } if now < *release_at_millis => {
+                return Err(EscrowError::Locked {
+                    release_at_millis: *release_at_millis,
+                })
+            }
+            EscrowCondition::Authorized { arbiter } if arbiter != caller => {
+                return Err(EscrowError::NotAuthorized)
+            }
+            _ => {}
+        }
+        // Move the funds before dropping the record, so a failed debit
+        // leaves the escrow open and the payer's funds recoverable.
+        let (payer, payee, token, amount) = (
+            escrow.payer.clone(),
+            escrow.payee.clone(),
+            escrow.token.clone(),
+            escrow.amount,
+        );
+        self.debit_locked(&payer, &token, amount)?;
+        self.credit(&payee, &token, amount);
+        Ok(self.escrows.open.remove(&id).unwrap())
+    }
+
+    // Return the escrowed funds to the payer. Only the arbiter of an
+    // authorized escrow may refund; time locks cannot be unwound.
+    pub fn refund_escrow(&mut self, id: u64, caller: &Wallet) -> Result<Escrow, EscrowError> {
+        let escrow = self.escrows.get(id).ok_or(EscrowError::UnknownEscrow)?;
+        match &escrow.condition {
+            EscrowCondition::Authorized { arbiter } if arbiter == caller => {}
+            _ => return Err(EscrowError::NotAuthorized),
+        }
+        let (payer, token, amount) = (escrow.payer.clone(), escrow.token.clone(), escrow.amount);
+        self.unlock(&payer, &token, amount)?;
+        Ok(self.escrows.open.remove(&id).unwrap())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_time_locked_and_authorized_escrows() {
+        let mut ledger = Ledger::new();
+        let payer = Wallet::new(String::from("escrowpayer"));
+        let payee = Wallet::new(String::from("escrowpayee"));
+        let arbiter = Wallet::new(String::from("escrowarbiter"));
+        ledger
+            .deposit(payer.clone(), TokenTicker::USDT, 100)
+            .unwrap();
+
+        let timed = ledger
+            .create_escrow(
+                &payer,
+                &payee,
+                &TokenTicker::USDT,
+                40,
+                EscrowCondition::TimeLock {
+                    release_at_millis: 5_000,
+                },
+                0,
+            )
+            .unwrap();
+        let authorized = ledger
+            .create_escrow(
+                &payer,
+                &payee,
+                &TokenTicker::USDT,
+                50,
+                EscrowCondition::Authorized {
+                    arbiter: arbiter.clone(),
+                },
+                0,
+            )
+            .unwrap();
+        assert!(matches!(
+            ledger.create_escrow(
+                &payer,
+                &payee,
+                &TokenTicker::USDT,
+                20,
+                EscrowCondition::TimeLock {
+                    release_at_millis: 0
+                },
+                0
+            ),
+            Err(EscrowError::Ledger(LedgerError::InsufficientFree { .. }))
+        ));
+        assert_eq!(ledger.locked_balance(&payer, &TokenTicker::USDT), 90);
+
+        assert_eq!(
+            ledger.release_escrow(timed, &payee, 4_999),
+            Err(EscrowError::Locked {
+                release_at_millis: 5_000
+            })
+        );
+        ledger.release_escrow(timed, &payee, 5_000).unwrap();
+        assert_eq!(ledger.free_balance(&payee, &TokenTicker::USDT), 40);
+
+        assert_eq!(
+            ledger.release_escrow(authorized, &payee, 5_000),
+            Err(EscrowError::NotAuthorized)
+        );
+        assert_eq!(
+            ledger.refund_escrow(authorized, &payer),
+            Err(EscrowError::NotAuthorized)
+        );
+        ledger.refund_escrow(authorized, &arbiter).unwrap();
+        assert_eq!(ledger.balance(&payer, &TokenTicker::USDT).free, 60);
+        assert_eq!(ledger.locked_balance(&payer, &TokenTicker::USDT), 0);
+        assert_eq!(
+            ledger.release_escrow(authorized, &arbiter, 5_000),
+            Err(EscrowError::UnknownEscrow)
+        );
+        assert_eq!(ledger.escrows.iter().count(), 0);
+    }
+
+    #[test]
+    fn test_failed_release_keeps_the_escrow_open() {
+        let mut ledger = Ledger::new();
+        let payer = Wallet::new(String::from("escrowpayer"));
+        let payee = Wallet::new(String::from("escrowpayee"));
+        let arbiter = Wallet::new(String::from("escrowarbiter"));
+        ledger.deposit(payer.clone(), TokenTicker::ETH, 3).unwrap();
+        let id = ledger
+            .create_escrow(
+                &payer,
+                &payee,
+                &TokenTicker::ETH,
+                3,
+                EscrowCondition::Authorized {
+                    arbiter: arbiter.clone(),
+                },
+                0,
+            )
+            .unwrap();
+
+        // Something else took the locked funds out from under the escrow.
+        ledger.unlock(&payer, &TokenTicker::ETH, 2).unwrap();
+        assert!(matches!(
+            ledger.release_escrow(id, &arbiter, 0),
+            Err(EscrowError::Ledger(LedgerError::InsufficientLocked { .. }))
+        ));
+        assert!(matches!(
+            ledger.refund_escrow(id, &arbiter),
+            Err(EscrowError::Ledger(LedgerError::InsufficientLocked { .. }))
+        ));
+        assert_eq!(ledger.escrows.get(id).map(|escrow| escrow.amount), Some(3));
+        assert_eq!(ledger.locked_balance(&payer, &TokenTicker::ETH), 1);
+        assert_eq!(ledger.free_balance(&payee, &TokenTicker::ETH), 0);
+
+        ledger.lock(&payer, &TokenTicker::ETH, 2).unwrap();
+        ledger.refund_escrow(id, &arbiter).unwrap();
+        assert_eq!(ledger.free_balance(&payer, &TokenTicker::ETH), 3);
+        assert!(ledger.escrows.get(id).is_none());
+    }
+}
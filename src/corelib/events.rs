@@ -0,0 +1,87 @@
+use super::order::{BuyOrSell, OrderMetadata};
+use super::price::PriceLike;
+
+// Order-level (L3) book events, in the order they were applied to the book.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BookEvent<P: PriceLike = f64> {
+    OrderAdded {
+        order_id: u64,
+        side: BuyOrSell,
+        price: P,
+        quantity: u32,
+        timestamp: u64,
+        metadata: Option<OrderMetadata>,
+    },
+    OrderFilled {
+        order_id: u64,
+        side: BuyOrSell,
+        price: P,
+        filled: u32,
+        remaining: u32,
+        timestamp: u64,
+        metadata: Option<OrderMetadata>,
+    },
+    OrderCancelled {
+        order_id: u64,
+        side: BuyOrSell,
+        price: P,
+        remaining: u32,
+        timestamp: u64,
+        metadata: Option<OrderMetadata>,
+    },
+    // A resting order rewritten by a redenomination. Orders left with no
+    // quantity are cancelled right after.
+    OrderRescaled {
+        order_id: u64,
+        side: BuyOrSell,
+        old_price: P,
+        price: P,
+        old_quantity: u32,
+        quantity: u32,
+        timestamp: u64,
+        metadata: Option<OrderMetadata>,
+    },
+    // A resting order moved ahead of the orders at its price paying a lower
+    // priority fee. Depth is unchanged.
+    PriorityFeeSet {
+        order_id: u64,
+        side: BuyOrSell,
+        price: P,
+        quantity: u32,
+        priority_fee: u64,
+        timestamp: u64,
+        metadata: Option<OrderMetadata>,
+    },
+}
+
+impl<P: PriceLike> BookEvent<P> {
+    pub fn order_id(&self) -> u64 {
+        match self {
+            BookEvent::OrderAdded { order_id, .. }
+            | BookEvent::OrderFilled { order_id, .. }
+            | BookEvent::OrderCancelled { order_id, .. }
+            | BookEvent::OrderRescaled { order_id, .. }
+            | BookEvent::PriorityFeeSet { order_id, .. } => *order_id,
+        }
+    }
+
+    pub fn timestamp(&self) -> u64 {
+        match self {
+            BookEvent::OrderAdded { timestamp, .. }
+            | BookEvent::OrderFilled { timestamp, .. }
+            | BookEvent::OrderCancelled { timestamp, .. }
+            | BookEvent::OrderRescaled { timestamp, .. }
+            | BookEvent::PriorityFeeSet { timestamp, .. } => *timestamp,
+        }
+    }
+
+    pub fn metadata(&self) -> Option<&OrderMetadata> {
+        match self {
+            BookEvent::OrderAdded { metadata, .. }
+            | BookEvent::OrderFilled { metadata, .. }
+            | BookEvent::OrderCancelled { metadata, .. }
+            | BookEvent::OrderRescaled { metadata, .. }
+            | BookEvent::PriorityFeeSet { metadata, .. } => metadata.as_ref(),
+        }
+    }
+}
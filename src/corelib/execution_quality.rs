@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+
+use super::engine::TradeEngine;
+use super::order::{BuyOrSell, Wallet};
+use super::orderbook::{OrderBook, OrderBookTrait};
+use super::token::TokenTicker;
+
+// State of the book when an order was received, kept to benchmark its fills.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderArrival {
+    pub wallet: Option<Wallet>,
+    pub side: BuyOrSell,
+    pub timestamp: u64,
+    // None when either side of the book was empty.
+    pub mid: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OrderArrivals {
+    arrivals: HashMap<(TokenTicker, u64), OrderArrival>,
+}
+
+impl OrderArrivals {
+    pub fn new() -> OrderArrivals {
+        OrderArrivals::default()
+    }
+
+    pub fn get(&self, ticker: &TokenTicker, order_id: u64) -> Option<&OrderArrival> {
+        self.arrivals.get(&(ticker.clone(), order_id))
+    }
+
+    pub(crate) fn record(&mut self, ticker: &TokenTicker, order_id: u64, arrival: OrderArrival) {
+        self.arrivals.insert((ticker.clone(), order_id), arrival);
+    }
+}
+
+pub fn book_mid(orderbook: &OrderBook) -> Option<f64> {
+    match (orderbook.best_buy_price(), orderbook.best_sell_price()) {
+        (Some(bid), Some(ask)) => Some((bid.into_inner() + ask.into_inner()) / 2.0),
+        _ => None,
+    }
+}
+
+// Implementation shortfall of one wallet's taker fills on one instrument.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecutionQuality {
+    pub wallet: Wallet,
+    pub ticker: TokenTicker,
+    pub fills: usize,
+    pub quantity: u64,
+    // Cost against the arrival mid in the quote token; positive is worse.
+    pub shortfall: f64,
+    // `shortfall` over the fills' notional at the arrival mid.
+    pub shortfall_bps: f64,
+}
+
+impl TradeEngine {
+    // Compare every taker fill in the trade log with the mid when the taking
+    // order arrived. The taker is whichever order arrived last. Fills by
+    // anonymous orders, or orders that arrived at a one-sided book, are left
+    // out.
+    pub fn execution_quality_report(&self) -> Vec<ExecutionQuality> {
+        let mut report: HashMap<(Wallet, TokenTicker), (ExecutionQuality, f64)> = HashMap::new();
+        for trade in self.trade_feed.trades() {
+            let arrival = |order_id: Option<u64>| {
+                order_id.and_then(|order_id| {
+                    self.order_arrivals
+                        .get(&trade.ticker, order_id)
+                        .map(|arrival| (order_id, arrival))
+                })
+            };
+            let (Some(buy), Some(sell)) =
+                (arrival(trade.buy_order_id), arrival(trade.sell_order_id))
+            else {
+                continue;
+            };
+            let (_, taker) = if (buy.1.timestamp, buy.0) > (sell.1.timestamp, sell.0) {
+                buy
+            } else {
+                sell
+            };
+            let (Some(wallet), Some(mid)) = (&taker.wallet, taker.mid) else {
+                continue;
+            };
+            let quantity = trade.quantity as f64;
+            let shortfall = match taker.side {
+                BuyOrSell::Buy => (trade.price - mid) * quantity,
+                BuyOrSell::Sell => (mid - trade.price) * quantity,
+            };
+            let (entry, benchmark) = report
+                .entry((wallet.clone(), trade.ticker.clone()))
+                .or_insert_with(|| {
+                    (
+                        ExecutionQuality {
+                            wallet: wallet.clone(),
+                            ticker: trade.ticker.clone(),
+                            fills: 0,
+                            quantity: 0,
+                            shortfall: 0.0,
+                            shortfall_bps: 0.0,
+                        },
+                        0.0,
+                    )
+                });
+            entry.fills += 1;
+            entry.quantity += trade.quantity;
+            entry.shortfall += shortfall;
+            *benchmark += mid * quantity;
+            entry.shortfall_bps = entry.shortfall / *benchmark * 10_000.0;
+        }
+        let mut report: Vec<ExecutionQuality> =
+            report.into_values().map(|(entry, _)| entry).collect();
+        report.sort_by(|a, b| {
+            a.wallet
+                .address
+                .cmp(&b.wallet.address)
+                .then(a.ticker.cmp(&b.ticker))
+        });
+        report
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::order::TimeInForce;
+
+    #[test]
+    fn test_taker_shortfall_against_arrival_mid() {
+        let mut engine = TradeEngine::new();
+        engine.list_new_token(TokenTicker::ETH);
+        let maker = Wallet::new(String::from("eq-maker"));
+        let taker = Wallet::new(String::from("eq-taker"));
+        let mut order = |wallet: &Wallet, side, price, quantity| {
+            engine
+                .submit_wallet_order(
+                    wallet,
+                    &TokenTicker::ETH,
+                    side,
+                    price,
+                    quantity,
+                    TimeInForce::GoodTillCancel,
+                )
+                .unwrap();
+        };
+        order(&maker, BuyOrSell::Buy, 99.0, 10);
+        order(&maker, BuyOrSell::Sell, 101.0, 2);
+        order(&maker, BuyOrSell::Sell, 103.0, 2);
+        // arrives with the mid at 100
+        order(&taker, BuyOrSell::Buy, 103.0, 4);
+        engine.match_orders();
+
+        let report = engine.execution_quality_report();
+        assert_eq!(report.len(), 1);
+        let quality = &report[0];
+        assert_eq!(quality.wallet, taker);
+        assert_eq!((quality.fills, quality.quantity), (2, 4));
+        assert_eq!(quality.shortfall, 2.0 + 6.0);
+        assert_eq!(quality.shortfall_bps, 200.0);
+    }
+}
@@ -0,0 +1,302 @@
+use super::engine::TradeEngine;
+use super::margin::{InstrumentKind, Position};
+use super::order::Wallet;
+use super::rounding::{Flow, Rounding};
+use super::token::TokenTicker;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DatedContract {
+    pub expiry_millis: u64,
+    // The settlement price is the index TWAP over this window before expiry.
+    pub settlement_window_millis: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExpiryError {
+    UnknownInstrument,
+    NotExpired { expiry_millis: u64 },
+    NoSettlementPrice,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SettledPosition {
+    pub wallet: Wallet,
+    pub quantity: i64,
+    // P&L at the settlement price: owed to (positive) or by (negative) the
+    // wallet.
+    pub pnl: i64,
+    // Cash actually paid to (positive) or collected from (negative) the
+    // wallet, after any shortfall or haircut.
+    pub paid: i64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpirySettlement {
+    pub ticker: TokenTicker,
+    pub settlement_price: f64,
+    pub timestamp: u64,
+    pub positions: Vec<SettledPosition>,
+    pub cancelled_orders: Vec<u64>,
+    // Losses that could not be collected from the wallets' free balances.
+    pub shortfall: u64,
+    // Paid from the treasury towards the shortfall.
+    pub insurance: u64,
+    // Winnings left unpaid because losses and insurance fell short.
+    pub haircut: u64,
+    // Collected but not paid out, credited to the treasury: losses above
+    // the winnings and rounding dust.
+    pub rounding_residue: u64,
+}
+
+impl TradeEngine {
+    // List `ticker` as a dated future expiring at `contract.expiry_millis`.
+    pub fn list_future(&mut self, ticker: TokenTicker, contract: DatedContract) {
+        self.list_new_token(ticker.clone());
+        self.dated_contracts.insert(ticker, contract);
+    }
+
+    // Expire a dated instrument: settle at the index TWAP over the
+    // settlement window, pay or collect every open position's P&L in the
+    // quote token, cancel resting orders and move the book to the archive.
+    pub fn expire_instrument(
+        &mut self,
+        ticker: &TokenTicker,
+    ) -> Result<ExpirySettlement, ExpiryError> {
+        let contract = *self
+            .dated_contracts
+            .get(ticker)
+            .ok_or(ExpiryError::UnknownInstrument)?;
+        let now = self.now();
+        if now < contract.expiry_millis {
+            return Err(ExpiryError::NotExpired {
+                expiry_millis: contract.expiry_millis,
+            });
+        }
+        let settlement_price = self
+            .index
+            .twap(
+                ticker,
+                contract
+                    .expiry_millis
+                    .saturating_sub(contract.settlement_window_millis),
+                contract.expiry_millis,
+            )
+            .ok_or(ExpiryError::NoSettlementPrice)?;
+
+        let (expiring, open): (Vec<Position>, Vec<Position>) =
+            self.positions.drain(..).partition(|position| {
+                &position.underlying == ticker && position.kind == InstrumentKind::Future
+            });
+        self.positions = open;
+        let quote = self.quote_token.clone();
+        let mut settled = Vec::new();
+        let mut collected = 0;
+        let mut shortfall = 0;
+        let mut winnings = 0;
+        // Collect losses first: winners are paid only from what losers paid
+        // and what the treasury can add.
+        for position in expiring {
+            let pnl = position.quantity as f64 * (settlement_price - position.entry_price);
+            let pnl =
+                self.rounding().amount(pnl.abs(), Flow::Transfer) as i64 * pnl.signum() as i64;
+            let mut paid = 0;
+            if pnl > 0 {
+                winnings += pnl as u64;
+            } else if pnl < 0 {
+                let owed = pnl.unsigned_abs();
+                let collectable = owed.min(self.ledger.free_balance(&position.wallet, &quote));
+                self.ledger
+                    .debit_free(&position.wallet, &quote, collectable)
+                    .unwrap();
+                collected += collectable;
+                shortfall += owed - collectable;
+                paid = -(collectable as i64);
+            }
+            settled.push((position, pnl, paid));
+        }
+
+        // The treasury acts as insurance fund for the part losers could not
+        // pay, as far as its free balance goes.
+        let treasury = self.ledger.treasury().clone();
+        let insurance = winnings
+            .saturating_sub(collected)
+            .min(self.ledger.treasury_balance(&quote));
+        self.ledger
+            .debit_free(&treasury, &quote, insurance)
+            .unwrap();
+        let pot = collected + insurance;
+
+        // Short of the full winnings, every winner takes the same haircut.
+        // Pro rata shares round down whatever the policy, so they never add
+        // up to more than the pot.
+        let mut paid_out = 0;
+        let mut positions = Vec::new();
+        for (position, pnl, mut paid) in settled {
+            if pnl > 0 {
+                let payout = if pot >= winnings {
+                    pnl as u64
+                } else {
+                    Rounding::Truncate.ratio(pnl as u64, pot, winnings, Flow::FromHouse)
+                };
+                self.ledger.credit(&position.wallet, &quote, payout);
+                paid_out += payout;
+                paid = payout as i64;
+            }
+            positions.push(SettledPosition {
+                wallet: position.wallet,
+                quantity: position.quantity,
+                pnl,
+                paid,
+            });
+        }
+        // Losses beyond the winnings and the dust of the pro rata shares
+        // stay with the house.
+        let rounding_residue = pot - paid_out;
+        self.ledger.credit(&treasury, &quote, rounding_residue);
+
+        let cancelled_orders = match self.order_books.remove(ticker) {
+            Some(mut order_book) => {
+                let cancelled = order_book.cancel_all(now);
+                self.archived_books.insert(ticker.clone(), order_book);
+                cancelled
+            }
+            None => Vec::new(),
+        };
+        self.dated_contracts.remove(ticker);
+        Ok(ExpirySettlement {
+            ticker: ticker.clone(),
+            settlement_price,
+            timestamp: now,
+            positions,
+            cancelled_orders,
+            shortfall,
+            insurance,
+            haircut: winnings - paid_out,
+            rounding_residue,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::clock::SimulatedClock;
+    use crate::corelib::order::BuyOrSell;
+
+    fn future(wallet: &Wallet, quantity: i64) -> Position {
+        Position {
+            wallet: wallet.clone(),
+            underlying: TokenTicker::ETH,
+            kind: InstrumentKind::Future,
+            quantity,
+            entry_price: 100.0,
+            mark_price: 100.0,
+            collateral: 0,
+        }
+    }
+
+    #[test]
+    fn test_expiry_settles_cancels_and_archives() {
+        let clock = SimulatedClock::new(0);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        let long = Wallet::new(String::from("expirylong"));
+        let short = Wallet::new(String::from("expiryshort"));
+        engine.list_future(
+            TokenTicker::ETH,
+            DatedContract {
+                expiry_millis: 10_000,
+                settlement_window_millis: 4_000,
+            },
+        );
+        engine
+            .ledger
+            .deposit(short.clone(), TokenTicker::USDT, 50)
+            .unwrap();
+        let treasury = engine.ledger.treasury().clone();
+        engine
+            .ledger
+            .deposit(treasury, TokenTicker::USDT, 60)
+            .unwrap();
+        engine.positions.push(future(&long, 10));
+        engine.positions.push(future(&short, -10));
+        let order_id = engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Buy, 99.0, 3)
+            .unwrap();
+
+        engine.publish_index_price(TokenTicker::ETH, 100.0);
+        clock.set(6_000);
+        engine.publish_index_price(TokenTicker::ETH, 110.0);
+        clock.set(8_000);
+        engine.publish_index_price(TokenTicker::ETH, 120.0);
+
+        assert_eq!(
+            engine.expire_instrument(&TokenTicker::ETH),
+            Err(ExpiryError::NotExpired {
+                expiry_millis: 10_000
+            })
+        );
+        clock.set(10_000);
+        engine.verify_conservation().unwrap();
+        let settlement = engine.expire_instrument(&TokenTicker::ETH).unwrap();
+        engine.verify_conservation().unwrap();
+        assert_eq!(settlement.settlement_price, 115.0);
+        assert_eq!(settlement.cancelled_orders, vec![order_id]);
+        assert_eq!(settlement.shortfall, 100);
+        assert_eq!(settlement.insurance, 60);
+        assert_eq!(settlement.haircut, 40);
+        assert_eq!(settlement.rounding_residue, 0);
+        assert_eq!(settlement.positions[0].pnl, 150);
+        assert_eq!(settlement.positions[0].paid, 110);
+        assert_eq!(settlement.positions[1].pnl, -150);
+        assert_eq!(settlement.positions[1].paid, -50);
+
+        assert_eq!(engine.ledger.free_balance(&long, &TokenTicker::USDT), 110);
+        assert_eq!(engine.ledger.free_balance(&short, &TokenTicker::USDT), 0);
+        assert_eq!(engine.ledger.treasury_balance(&TokenTicker::USDT), 0);
+        assert!(engine.positions.is_empty());
+        assert!(engine.get_token_order_book(&TokenTicker::ETH).is_none());
+        assert!(engine.archived_books.contains_key(&TokenTicker::ETH));
+        assert_eq!(
+            engine.expire_instrument(&TokenTicker::ETH),
+            Err(ExpiryError::UnknownInstrument)
+        );
+    }
+
+    #[test]
+    fn test_expiry_haircuts_winners_without_creating_value() {
+        let clock = SimulatedClock::new(0);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        let first = Wallet::new(String::from("expiryfirst"));
+        let second = Wallet::new(String::from("expirysecond"));
+        let short = Wallet::new(String::from("expiryhaircutshort"));
+        engine.list_future(
+            TokenTicker::ETH,
+            DatedContract {
+                expiry_millis: 1_000,
+                settlement_window_millis: 1_000,
+            },
+        );
+        engine
+            .ledger
+            .deposit(short.clone(), TokenTicker::USDT, 15)
+            .unwrap();
+        engine.positions.push(future(&first, 1));
+        engine.positions.push(future(&second, 1));
+        engine.positions.push(future(&short, -2));
+        engine.publish_index_price(TokenTicker::ETH, 110.0);
+        clock.set(1_000);
+
+        engine.verify_conservation().unwrap();
+        let settlement = engine.expire_instrument(&TokenTicker::ETH).unwrap();
+        engine.verify_conservation().unwrap();
+        // 15 collected against 20 owed, split evenly with a unit left over
+        assert_eq!(settlement.shortfall, 5);
+        assert_eq!(settlement.insurance, 0);
+        assert_eq!(settlement.haircut, 6);
+        assert_eq!(settlement.rounding_residue, 1);
+        assert_eq!(engine.ledger.free_balance(&first, &TokenTicker::USDT), 7);
+        assert_eq!(engine.ledger.free_balance(&second, &TokenTicker::USDT), 7);
+        assert_eq!(engine.ledger.treasury_balance(&TokenTicker::USDT), 1);
+    }
+}
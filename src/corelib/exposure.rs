@@ -0,0 +1,101 @@
+use super::engine::{OrderError, TradeEngine};
+use super::order::Wallet;
+use super::rounding::Flow;
+use super::token::TokenTicker;
+
+impl TradeEngine {
+    pub fn set_open_notional_limit(&mut self, ticker: TokenTicker, limit: u64) {
+        self.open_notional_limits.insert(ticker, limit);
+    }
+
+    // Notional of the wallet's resting orders on the instrument, both sides.
+    pub fn open_notional(&self, wallet: &Wallet, ticker: &TokenTicker) -> u64 {
+        self.order_books
+            .get(ticker)
+            .map(|orderbook| {
+                orderbook
+                    .wallet_orders(wallet)
+                    .map(|order| self.order_notional(order.price, order.quantity))
+                    .sum()
+            })
+            .unwrap_or(0)
+    }
+
+    // Reject a new order that would take the wallet's resting notional on
+    // the instrument past its limit.
+    pub(crate) fn check_open_notional(
+        &self,
+        wallet: &Wallet,
+        ticker: &TokenTicker,
+        price: f64,
+        quantity: u32,
+    ) -> Result<(), OrderError> {
+        let Some(limit) = self.open_notional_limits.get(ticker).copied() else {
+            return Ok(());
+        };
+        let exposure = self.open_notional(wallet, ticker);
+        let order_notional = self.order_notional(price, quantity);
+        if exposure.saturating_add(order_notional) > limit {
+            return Err(OrderError::OpenNotionalExceeded {
+                exposure,
+                order_notional,
+                limit,
+            });
+        }
+        Ok(())
+    }
+
+    pub(crate) fn order_notional(&self, price: f64, quantity: u32) -> u64 {
+        self.rounding()
+            .amount(price * quantity as f64, Flow::Transfer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::order::{BuyOrSell, TimeInForce};
+
+    #[test]
+    fn test_orders_rejected_past_open_notional_limit() {
+        let mut engine = TradeEngine::new();
+        let wallet = Wallet::new(String::from("exposurewallet"));
+        let other = Wallet::new(String::from("exposureother"));
+        engine.list_new_token(TokenTicker::ETH);
+        engine.set_open_notional_limit(TokenTicker::ETH, 1_000);
+
+        let submit = |engine: &mut TradeEngine, wallet: &Wallet, side, price, quantity| {
+            engine.submit_wallet_order(
+                wallet,
+                &TokenTicker::ETH,
+                side,
+                price,
+                quantity,
+                TimeInForce::GoodTillCancel,
+            )
+        };
+        submit(&mut engine, &wallet, BuyOrSell::Buy, 100.0, 6).unwrap();
+        let ask = submit(&mut engine, &wallet, BuyOrSell::Sell, 110.0, 2).unwrap();
+        assert_eq!(engine.open_notional(&wallet, &TokenTicker::ETH), 820);
+
+        assert_eq!(
+            submit(&mut engine, &wallet, BuyOrSell::Buy, 100.0, 2),
+            Err(OrderError::OpenNotionalExceeded {
+                exposure: 820,
+                order_notional: 200,
+                limit: 1_000,
+            })
+        );
+        // limits are per wallet
+        submit(&mut engine, &other, BuyOrSell::Buy, 100.0, 10).unwrap();
+
+        // cancelling frees up exposure
+        engine
+            .get_token_order_book(&TokenTicker::ETH)
+            .unwrap()
+            .cancel_order(ask, 0);
+        submit(&mut engine, &wallet, BuyOrSell::Buy, 100.0, 2).unwrap();
+        assert_eq!(engine.open_notional(&wallet, &TokenTicker::ETH), 800);
+    }
+}
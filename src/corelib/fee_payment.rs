@@ -0,0 +1,170 @@
+use super::audit::AuditAction;
+use super::engine::TradeEngine;
+use super::ledger::LedgerError;
+use super::order::Wallet;
+use super::rounding::Flow;
+use super::token::{Pair, TokenTicker};
+
+const BPS_DENOMINATOR: u64 = 10_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeePayment {
+    // Token and amount debited from the wallet.
+    pub token: TokenTicker,
+    pub amount: u64,
+    // The fee in the quote token, after any discount.
+    pub quote_amount: u64,
+}
+
+impl TradeEngine {
+    // Charge the taker fee for `notional` of `quote`. Wallets that opted in
+    // pay the discounted fee in the fee token instead, swapped into `quote`
+    // through the fee token's pool; they fall back to paying in `quote` when
+    // the pool or their fee token balance cannot cover it.
+    pub fn charge_taker_fee(
+        &mut self,
+        wallet: &Wallet,
+        quote: &TokenTicker,
+        notional: u64,
+    ) -> Result<FeePayment, LedgerError> {
+        if let Some(payment) = self.pay_fee_in_fee_token(wallet, quote, notional) {
+            return Ok(payment);
+        }
+        let amount = self
+            .fees
+            .charge_taker_fee(&mut self.ledger, wallet, quote, notional)?;
+        Ok(FeePayment {
+            token: quote.clone(),
+            amount,
+            quote_amount: amount,
+        })
+    }
+
+    fn pay_fee_in_fee_token(
+        &mut self,
+        wallet: &Wallet,
+        quote: &TokenTicker,
+        notional: u64,
+    ) -> Option<FeePayment> {
+        let config = self.fees.fee_token()?.clone();
+        if !self.fees.pays_in_fee_token(wallet) || &config.token == quote {
+            return None;
+        }
+        let fee = self.fees.taker_fee(notional);
+        let discount =
+            self.fees
+                .rounding
+                .ratio(fee, config.discount_bps, BPS_DENOMINATOR, Flow::FromHouse);
+        let quote_amount = fee - discount;
+        if quote_amount == 0 {
+            return None;
+        }
+        let pool = self
+            .amm_pools
+            .get_mut(&Pair::new(config.token.clone(), quote.clone()))?;
+        let amount = pool.quote_exact_out(&config.token, quote, quote_amount)?;
+        if self.ledger.free_balance(wallet, &config.token) < amount {
+            return None;
+        }
+        pool.swap_exact_out(&config.token, quote, quote_amount)?;
+        self.ledger.debit_free(wallet, &config.token, amount).ok()?;
+        self.fees
+            .distribute_taker_fee(&mut self.ledger, wallet, quote, quote_amount);
+
+        let now = self.now();
+        self.audit_log.record(
+            now,
+            AuditAction::FeeConverted {
+                wallet: wallet.clone(),
+                fee_token: config.token.clone(),
+                fee_token_amount: amount,
+                quote_token: quote.clone(),
+                quote_amount,
+            },
+        );
+        Some(FeePayment {
+            token: config.token,
+            amount,
+            quote_amount,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::fees::{FeeError, FeeTokenConfig};
+
+    #[test]
+    fn test_fee_paid_in_fee_token_at_discount() {
+        let mut engine = TradeEngine::new();
+        let wallet = Wallet::new(String::from("feetokenwallet"));
+        let pair = Pair::new(TokenTicker::BNB, TokenTicker::USDT);
+        let pool = engine.amm_pools.entry(pair).or_default();
+        pool.add_liquidity(TokenTicker::BNB, 10_000);
+        pool.add_liquidity(TokenTicker::USDT, 40_000);
+        assert_eq!(
+            engine.fees.set_fee_token(Some(FeeTokenConfig {
+                token: TokenTicker::BNB,
+                discount_bps: 10_001,
+            })),
+            Err(FeeError::InvalidShare { bps: 10_001 })
+        );
+        engine
+            .fees
+            .set_fee_token(Some(FeeTokenConfig {
+                token: TokenTicker::BNB,
+                discount_bps: 2_500,
+            }))
+            .unwrap();
+        engine
+            .ledger
+            .deposit(wallet.clone(), TokenTicker::BNB, 100)
+            .unwrap();
+        engine
+            .ledger
+            .deposit(wallet.clone(), TokenTicker::USDT, 1_000)
+            .unwrap();
+
+        // not opted in: the full fee in the quote token
+        let payment = engine
+            .charge_taker_fee(&wallet, &TokenTicker::USDT, 200_000)
+            .unwrap();
+        assert_eq!(payment.amount, 400);
+        assert_eq!(payment.token, TokenTicker::USDT);
+
+        engine.fees.set_pay_in_fee_token(&wallet, true);
+        let payment = engine
+            .charge_taker_fee(&wallet, &TokenTicker::USDT, 200_000)
+            .unwrap();
+        assert_eq!(
+            payment,
+            FeePayment {
+                token: TokenTicker::BNB,
+                amount: 76,
+                quote_amount: 300,
+            }
+        );
+        assert_eq!(engine.ledger.free_balance(&wallet, &TokenTicker::BNB), 24);
+        assert_eq!(engine.ledger.free_balance(&wallet, &TokenTicker::USDT), 600);
+        let fee_account = engine.fees.fee_account.clone();
+        assert_eq!(
+            engine.ledger.free_balance(&fee_account, &TokenTicker::USDT),
+            700
+        );
+        assert!(matches!(
+            engine.audit_log.records()[0].action,
+            AuditAction::FeeConverted {
+                fee_token_amount: 76,
+                ..
+            }
+        ));
+
+        // not enough of the fee token left: falls back to the quote token
+        let payment = engine
+            .charge_taker_fee(&wallet, &TokenTicker::USDT, 200_000)
+            .unwrap();
+        assert_eq!(payment.token, TokenTicker::USDT);
+    }
+}
@@ -0,0 +1,420 @@
+use std::collections::{HashMap, HashSet};
+
+use super::ledger::{rescale_amount, Ledger, LedgerError};
+use super::order::Wallet;
+use super::rounding::{Flow, Rounding};
+use super::token::TokenTicker;
+
+const BPS_DENOMINATOR: u64 = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeSchedule {
+    pub maker_bps: u64,
+    pub taker_bps: u64,
+}
+
+impl Default for FeeSchedule {
+    fn default() -> Self {
+        FeeSchedule {
+            maker_bps: 10,
+            taker_bps: 20,
+        }
+    }
+}
+
+// Share of collected fees, in basis points, credited to the ledger treasury
+// instead of the fee account.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FeeRouting {
+    pub trading_treasury_bps: u64,
+    pub swap_treasury_bps: u64,
+}
+
+// Token wallets may opt to pay fees in, at `discount_bps` off the fee.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeeTokenConfig {
+    pub token: TokenTicker,
+    pub discount_bps: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeeSource {
+    Trading,
+    Swap,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FeeSplit {
+    pub treasury: u64,
+    pub fee_account: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FeeError {
+    SelfReferral,
+    AlreadyReferred { referrer: Wallet },
+    // A share above 10_000 bps would hand out more than the fee.
+    InvalidShare { bps: u64 },
+}
+
+// Collects trading fees into `fee_account`. Wallets that registered a
+// referrer give up `referral_share_bps` of every taker fee to that referrer;
+// the share accrues here until claimed or settled. What is left is split
+// between the fee account and the treasury according to `routing`.
+pub struct FeeEngine {
+    pub schedule: FeeSchedule,
+    pub fee_account: Wallet,
+    referral_share_bps: u64,
+    routing: FeeRouting,
+    fee_token: Option<FeeTokenConfig>,
+    pub rounding: Rounding,
+    pays_in_fee_token: HashSet<Wallet>,
+    referrers: HashMap<Wallet, Wallet>,
+    accrued_referrals: HashMap<Wallet, HashMap<TokenTicker, u64>>,
+}
+
+impl FeeEngine {
+    pub fn new(fee_account: Wallet) -> FeeEngine {
+        FeeEngine {
+            schedule: FeeSchedule::default(),
+            fee_account,
+            referral_share_bps: 0,
+            routing: FeeRouting::default(),
+            fee_token: None,
+            rounding: Rounding::default(),
+            pays_in_fee_token: HashSet::new(),
+            referrers: HashMap::new(),
+            accrued_referrals: HashMap::new(),
+        }
+    }
+
+    pub fn maker_fee(&self, notional: u64) -> u64 {
+        self.rounding.ratio(
+            notional,
+            self.schedule.maker_bps,
+            BPS_DENOMINATOR,
+            Flow::ToHouse,
+        )
+    }
+
+    pub fn taker_fee(&self, notional: u64) -> u64 {
+        self.rounding.ratio(
+            notional,
+            self.schedule.taker_bps,
+            BPS_DENOMINATOR,
+            Flow::ToHouse,
+        )
+    }
+
+    pub fn referral_share_bps(&self) -> u64 {
+        self.referral_share_bps
+    }
+
+    pub fn set_referral_share_bps(&mut self, bps: u64) -> Result<(), FeeError> {
+        if bps > BPS_DENOMINATOR {
+            return Err(FeeError::InvalidShare { bps });
+        }
+        self.referral_share_bps = bps;
+        Ok(())
+    }
+
+    pub fn routing(&self) -> FeeRouting {
+        self.routing
+    }
+
+    pub fn set_routing(&mut self, routing: FeeRouting) -> Result<(), FeeError> {
+        let bps = routing.trading_treasury_bps.max(routing.swap_treasury_bps);
+        if bps > BPS_DENOMINATOR {
+            return Err(FeeError::InvalidShare { bps });
+        }
+        self.routing = routing;
+        Ok(())
+    }
+
+    pub fn fee_token(&self) -> Option<&FeeTokenConfig> {
+        self.fee_token.as_ref()
+    }
+
+    // A discount above 10_000 bps would pay wallets for taking liquidity.
+    pub fn set_fee_token(&mut self, fee_token: Option<FeeTokenConfig>) -> Result<(), FeeError> {
+        if let Some(config) = &fee_token {
+            if config.discount_bps > BPS_DENOMINATOR {
+                return Err(FeeError::InvalidShare {
+                    bps: config.discount_bps,
+                });
+            }
+        }
+        self.fee_token = fee_token;
+        Ok(())
+    }
+
+    // Opt a wallet in or out of paying fees in the fee token.
+    pub fn set_pay_in_fee_token(&mut self, wallet: &Wallet, enabled: bool) {
+        if enabled {
+            self.pays_in_fee_token.insert(wallet.clone());
+        } else {
+            self.pays_in_fee_token.remove(wallet);
+        }
+    }
+
+    pub fn pays_in_fee_token(&self, wallet: &Wallet) -> bool {
+        self.pays_in_fee_token.contains(wallet)
+    }
+
+    // A wallet can be referred once and never by itself.
+    pub fn register_referrer(&mut self, wallet: Wallet, referrer: Wallet) -> Result<(), FeeError> {
+        if wallet == referrer {
+            return Err(FeeError::SelfReferral);
+        }
+        if let Some(existing) = self.referrers.get(&wallet) {
+            return Err(FeeError::AlreadyReferred {
+                referrer: existing.clone(),
+            });
+        }
+        self.referrers.insert(wallet, referrer);
+        Ok(())
+    }
+
+    pub fn referrer_of(&self, wallet: &Wallet) -> Option<&Wallet> {
+        self.referrers.get(wallet)
+    }
+
+    // Credit an already collected fee to the treasury and the fee account.
+    pub fn route_fee(
+        &self,
+        ledger: &mut Ledger,
+        source: FeeSource,
+        token: &TokenTicker,
+        amount: u64,
+    ) -> FeeSplit {
+        let share_bps = match source {
+            FeeSource::Trading => self.routing.trading_treasury_bps,
+            FeeSource::Swap => self.routing.swap_treasury_bps,
+        };
+        let treasury = self
+            .rounding
+            .ratio(amount, share_bps, BPS_DENOMINATOR, Flow::Transfer);
+        let split = FeeSplit {
+            treasury,
+            fee_account: amount - treasury,
+        };
+        if split.treasury > 0 {
+            let treasury = ledger.treasury().clone();
+            ledger.credit(&treasury, token, split.treasury);
+        }
+        ledger.credit(&self.fee_account, token, split.fee_account);
+        split
+    }
+
+    // Debit the taker fee for `notional` from the wallet, splitting it
+    // between the wallet's referrer, the treasury and the fee account.
+    // Returns the fee.
+    pub fn charge_taker_fee(
+        &mut self,
+        ledger: &mut Ledger,
+        wallet: &Wallet,
+        token: &TokenTicker,
+        notional: u64,
+    ) -> Result<u64, LedgerError> {
+        let fee = self.taker_fee(notional);
+        ledger.debit_free(wallet, token, fee)?;
+        self.distribute_taker_fee(ledger, wallet, token, fee);
+        Ok(fee)
+    }
+
+    // Split a taker fee already collected from `wallet`.
+    pub(crate) fn distribute_taker_fee(
+        &mut self,
+        ledger: &mut Ledger,
+        wallet: &Wallet,
+        token: &TokenTicker,
+        fee: u64,
+    ) {
+        let referral = match self.referrers.get(wallet) {
+            Some(referrer) => {
+                let share = self.rounding.ratio(
+                    fee,
+                    self.referral_share_bps,
+                    BPS_DENOMINATOR,
+                    Flow::FromHouse,
+                );
+                if share > 0 {
+                    *self
+                        .accrued_referrals
+                        .entry(referrer.clone())
+                        .or_default()
+                        .entry(token.clone())
+                        .or_insert(0) += share;
+                }
+                share
+            }
+            None => 0,
+        };
+        self.route_fee(ledger, FeeSource::Trading, token, fee - referral);
+    }
+
+    pub fn accrued_referral(&self, referrer: &Wallet, token: &TokenTicker) -> u64 {
+        self.accrued_referrals
+            .get(referrer)
+            .and_then(|tokens| tokens.get(token))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    // Referral rewards of `token` accrued and not yet paid out.
+    pub fn total_accrued_referrals(&self, token: &TokenTicker) -> u64 {
+        self.accrued_referrals
+            .values()
+            .filter_map(|tokens| tokens.get(token))
+            .sum()
+    }
+
+    // Credit everything accrued by `referrer` to its ledger balance.
+    pub fn claim_referral_rewards(
+        &mut self,
+        ledger: &mut Ledger,
+        referrer: &Wallet,
+    ) -> Vec<(TokenTicker, u64)> {
+        let mut claimed: Vec<(TokenTicker, u64)> = self
+            .accrued_referrals
+            .remove(referrer)
+            .unwrap_or_default()
+            .into_iter()
+            .collect();
+        claimed.sort();
+        for (token, amount) in claimed.iter() {
+            ledger.credit(referrer, token, *amount);
+        }
+        claimed
+    }
+
+    // Whether every accrued referral reward of `token` still fits a u64
+    // after `rescale_token` with the same ratio.
+    pub(crate) fn can_rescale_token(
+        &self,
+        token: &TokenTicker,
+        numerator: u64,
+        denominator: u64,
+    ) -> bool {
+        self.accrued_referrals
+            .values()
+            .filter_map(|tokens| tokens.get(token))
+            .all(|amount| rescale_amount(*amount, numerator, denominator).is_some())
+    }
+
+    // Multiply accrued referral rewards of `token` by
+    // `numerator / denominator`, rounding down.
+    pub(crate) fn rescale_token(&mut self, token: &TokenTicker, numerator: u64, denominator: u64) {
+        for amount in self
+            .accrued_referrals
+            .values_mut()
+            .filter_map(|tokens| tokens.get_mut(token))
+        {
+            *amount = rescale_amount(*amount, numerator, denominator)
+                .expect("rescale checked beforehand");
+        }
+    }
+
+    // Periodic settlement: pay out every referrer at once. Returns the
+    // number of referrers that were credited.
+    pub fn settle_referrals(&mut self, ledger: &mut Ledger) -> usize {
+        let referrers: Vec<Wallet> = self.accrued_referrals.keys().cloned().collect();
+        for referrer in referrers.iter() {
+            self.claim_referral_rewards(ledger, referrer);
+        }
+        referrers.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_referral_share_of_taker_fees() {
+        let mut ledger = Ledger::new();
+        let house = Wallet::new(String::from("housewallet"));
+        let taker = Wallet::new(String::from("takerwallet"));
+        let referrer = Wallet::new(String::from("referrerwallet"));
+        ledger
+            .deposit(taker.clone(), TokenTicker::USDT, 100_000)
+            .unwrap();
+
+        let mut fees = FeeEngine::new(house.clone());
+        fees.set_referral_share_bps(2_500).unwrap(); // 25% of taker fees
+        fees.register_referrer(taker.clone(), referrer.clone())
+            .unwrap();
+
+        let fee = fees
+            .charge_taker_fee(&mut ledger, &taker, &TokenTicker::USDT, 50_000)
+            .unwrap();
+        assert_eq!(fee, 100);
+        assert_eq!(ledger.free_balance(&taker, &TokenTicker::USDT), 99_900);
+        assert_eq!(ledger.free_balance(&house, &TokenTicker::USDT), 75);
+        assert_eq!(fees.accrued_referral(&referrer, &TokenTicker::USDT), 25);
+
+        let claimed = fees.claim_referral_rewards(&mut ledger, &referrer);
+        assert_eq!(claimed, vec![(TokenTicker::USDT, 25)]);
+        assert_eq!(ledger.free_balance(&referrer, &TokenTicker::USDT), 25);
+        assert_eq!(fees.accrued_referral(&referrer, &TokenTicker::USDT), 0);
+    }
+
+    #[test]
+    fn test_register_referrer_rules() {
+        let mut fees = FeeEngine::new(Wallet::new(String::from("housewallet")));
+        let a = Wallet::new(String::from("walleta"));
+        let b = Wallet::new(String::from("walletb"));
+
+        assert_eq!(
+            fees.register_referrer(a.clone(), a.clone()),
+            Err(FeeError::SelfReferral)
+        );
+        fees.register_referrer(a.clone(), b.clone()).unwrap();
+        assert_eq!(
+            fees.register_referrer(a.clone(), Wallet::new(String::from("walletc"))),
+            Err(FeeError::AlreadyReferred { referrer: b })
+        );
+    }
+
+    #[test]
+    fn test_shares_above_the_whole_fee_are_refused() {
+        let mut fees = FeeEngine::new(Wallet::new(String::from("housewallet")));
+        assert_eq!(
+            fees.set_referral_share_bps(10_001),
+            Err(FeeError::InvalidShare { bps: 10_001 })
+        );
+        assert_eq!(
+            fees.set_routing(FeeRouting {
+                trading_treasury_bps: 0,
+                swap_treasury_bps: 20_000,
+            }),
+            Err(FeeError::InvalidShare { bps: 20_000 })
+        );
+        assert_eq!(fees.referral_share_bps(), 0);
+        assert_eq!(fees.routing(), FeeRouting::default());
+
+        fees.set_referral_share_bps(10_000).unwrap();
+        fees.set_routing(FeeRouting {
+            trading_treasury_bps: 10_000,
+            swap_treasury_bps: 10_000,
+        })
+        .unwrap();
+        let mut ledger = Ledger::new();
+        let taker = Wallet::new(String::from("takerwallet"));
+        ledger
+            .deposit(taker.clone(), TokenTicker::USDT, 1_000)
+            .unwrap();
+        fees.register_referrer(taker.clone(), Wallet::new(String::from("referrer")))
+            .unwrap();
+        fees.distribute_taker_fee(&mut ledger, &taker, &TokenTicker::USDT, 7);
+        let split = fees.route_fee(&mut ledger, FeeSource::Swap, &TokenTicker::USDT, 9);
+        assert_eq!(
+            split,
+            FeeSplit {
+                treasury: 9,
+                fee_account: 0
+            }
+        );
+    }
+}
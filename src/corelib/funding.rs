@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+
+use super::engine::TradeEngine;
+use super::token::TokenTicker;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FundingConfig {
+    pub interval_millis: u64,
+    // Rates are clamped to +/- this per interval.
+    pub max_rate: f64,
+}
+
+impl Default for FundingConfig {
+    fn default() -> Self {
+        FundingConfig {
+            interval_millis: 8 * 60 * 60 * 1_000,
+            max_rate: 0.0075,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FundingRate {
+    pub timestamp: u64,
+    // Premium of the perpetual over the index at the funding time.
+    pub premium: f64,
+    // Paid by longs to shorts when positive.
+    pub rate: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FundingInfo {
+    pub history: Vec<FundingRate>,
+    // Rate the next funding would apply if the premium stayed where it is.
+    pub projected_rate: Option<f64>,
+    pub next_funding_at: u64,
+}
+
+pub struct PerpetualFunding {
+    pub config: FundingConfig,
+    history: Vec<FundingRate>,
+    next_funding_at: u64,
+}
+
+impl PerpetualFunding {
+    pub fn new(config: FundingConfig, now: u64) -> PerpetualFunding {
+        let interval = config.interval_millis;
+        PerpetualFunding {
+            config,
+            history: Vec::new(),
+            next_funding_at: (now / interval + 1) * interval,
+        }
+    }
+
+    pub fn history(&self) -> &[FundingRate] {
+        &self.history
+    }
+
+    pub fn next_funding_at(&self) -> u64 {
+        self.next_funding_at
+    }
+
+    pub fn rate_for(&self, premium: f64) -> f64 {
+        premium.clamp(-self.config.max_rate, self.config.max_rate)
+    }
+}
+
+impl TradeEngine {
+    // List `ticker` as a perpetual: it trades on its own book and funds
+    // against the index every `config.interval_millis`.
+    pub fn list_perpetual(&mut self, ticker: TokenTicker, config: FundingConfig) {
+        self.list_new_token(ticker.clone());
+        let now = self.now();
+        self.perpetuals
+            .insert(ticker, PerpetualFunding::new(config, now));
+    }
+
+    // Premium of the perpetual's book over its index price.
+    pub fn funding_premium(&self, ticker: &TokenTicker) -> Option<f64> {
+        let index = self.index.latest(ticker)?;
+        let price = self.reference_price(ticker)?;
+        Some((price - index) / index)
+    }
+
+    // Record a funding rate for every perpetual whose funding time has
+    // passed. Missed intervals are skipped, not replayed.
+    pub fn settle_funding(&mut self) -> Vec<(TokenTicker, FundingRate)> {
+        let now = self.now();
+        let premiums: HashMap<TokenTicker, Option<f64>> = self
+            .perpetuals
+            .keys()
+            .map(|ticker| (ticker.clone(), self.funding_premium(ticker)))
+            .collect();
+        let mut settled = Vec::new();
+        for (ticker, funding) in self.perpetuals.iter_mut() {
+            if now < funding.next_funding_at {
+                continue;
+            }
+            let interval = funding.config.interval_millis;
+            let timestamp = funding.next_funding_at;
+            funding.next_funding_at = (now / interval + 1) * interval;
+            let Some(premium) = premiums[ticker] else {
+                continue;
+            };
+            let rate = FundingRate {
+                timestamp,
+                premium,
+                rate: funding.rate_for(premium),
+            };
+            funding.history.push(rate);
+            settled.push((ticker.clone(), rate));
+        }
+        settled
+    }
+
+    pub fn funding(&self, ticker: &TokenTicker) -> Option<FundingInfo> {
+        let funding = self.perpetuals.get(ticker)?;
+        Some(FundingInfo {
+            history: funding.history.clone(),
+            projected_rate: self
+                .funding_premium(ticker)
+                .map(|premium| funding.rate_for(premium)),
+            next_funding_at: funding.next_funding_at,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::clock::SimulatedClock;
+    use crate::corelib::order::BuyOrSell;
+
+    #[test]
+    fn test_funding_history_and_projection() {
+        let clock = SimulatedClock::new(500);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        let config = FundingConfig {
+            interval_millis: 1_000,
+            max_rate: 0.01,
+        };
+        engine.list_perpetual(TokenTicker::BTC, config);
+        engine.publish_index_price(TokenTicker::BTC, 100.0);
+        engine
+            .submit_order(&TokenTicker::BTC, BuyOrSell::Buy, 100.4, 1)
+            .unwrap();
+        engine
+            .submit_order(&TokenTicker::BTC, BuyOrSell::Sell, 100.6, 1)
+            .unwrap();
+
+        let info = engine.funding(&TokenTicker::BTC).unwrap();
+        assert!(info.history.is_empty());
+        assert_eq!(info.next_funding_at, 1_000);
+        assert!((info.projected_rate.unwrap() - 0.005).abs() < 1e-12);
+
+        // nothing is due before the funding time
+        assert!(engine.settle_funding().is_empty());
+        clock.set(1_200);
+        let settled = engine.settle_funding();
+        assert_eq!(settled.len(), 1);
+        assert_eq!(settled[0].1.timestamp, 1_000);
+
+        // a premium beyond the cap is clamped
+        engine.publish_index_price(TokenTicker::BTC, 90.0);
+        clock.set(3_500);
+        engine.settle_funding();
+        let info = engine.funding(&TokenTicker::BTC).unwrap();
+        assert_eq!(info.history.len(), 2);
+        assert_eq!(info.history[1].rate, 0.01);
+        assert_eq!(info.next_funding_at, 4_000);
+        assert_eq!(engine.funding(&TokenTicker::ETH), None);
+    }
+}
@@ -0,0 +1,180 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use super::depth::DepthSnapshot;
+use super::engine::TradeEngine;
+use super::token::TokenTicker;
+
+// Depth sampled at a fixed cadence and bucketed to `tick`, one column per
+// sample.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepthHeatmap {
+    pub tick: f64,
+    pub cadence_millis: u64,
+    timestamps: Vec<u64>,
+    // Per price bucket index: bid and ask quantity of every sample.
+    bids: BTreeMap<i64, Vec<u64>>,
+    asks: BTreeMap<i64, Vec<u64>>,
+}
+
+// Dense price × time matrix: `bids[row][column]` is the bid quantity at
+// `prices[row]` in the sample taken at `timestamps[column]`. Prices are
+// ascending.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeatmapMatrix {
+    pub prices: Vec<f64>,
+    pub timestamps: Vec<u64>,
+    pub bids: Vec<Vec<u64>>,
+    pub asks: Vec<Vec<u64>>,
+}
+
+impl DepthHeatmap {
+    // Panics if `tick` is not a positive finite number.
+    pub fn new(tick: f64, cadence_millis: u64) -> DepthHeatmap {
+        assert!(
+            tick.is_finite() && tick > 0.0,
+            "heatmap tick must be positive"
+        );
+        DepthHeatmap {
+            tick,
+            cadence_millis,
+            timestamps: Vec::new(),
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+        }
+    }
+
+    // Whether a sample is due at `now`.
+    pub fn due(&self, now: u64) -> bool {
+        self.timestamps
+            .last()
+            .is_none_or(|last| now >= last + self.cadence_millis)
+    }
+
+    pub fn record(&mut self, timestamp: u64, snapshot: &DepthSnapshot) {
+        let column = self.timestamps.len();
+        self.timestamps.push(timestamp);
+        let aggregated = snapshot.aggregate(self.tick);
+        for (rows, levels) in [
+            (&mut self.bids, &aggregated.bids),
+            (&mut self.asks, &aggregated.asks),
+        ] {
+            for level in levels {
+                let row = rows
+                    .entry((level.price / self.tick).round() as i64)
+                    .or_insert_with(|| vec![0; column]);
+                row.resize(column, 0);
+                row.push(level.quantity);
+            }
+            for row in rows.values_mut() {
+                row.resize(column + 1, 0);
+            }
+        }
+    }
+
+    pub fn matrix(&self) -> HeatmapMatrix {
+        let mut buckets: Vec<i64> = self.bids.keys().chain(self.asks.keys()).copied().collect();
+        buckets.sort();
+        buckets.dedup();
+        let empty = vec![0; self.timestamps.len()];
+        let rows = |side: &BTreeMap<i64, Vec<u64>>| -> Vec<Vec<u64>> {
+            buckets
+                .iter()
+                .map(|bucket| side.get(bucket).unwrap_or(&empty).clone())
+                .collect()
+        };
+        HeatmapMatrix {
+            prices: buckets
+                .iter()
+                .map(|bucket| *bucket as f64 * self.tick)
+                .collect(),
+            timestamps: self.timestamps.clone(),
+            bids: rows(&self.bids),
+            asks: rows(&self.asks),
+        }
+    }
+
+    // One row per side and price, one column per sample:
+    // `side,price,<timestamp>,<timestamp>,...`.
+    pub fn to_csv(&self) -> String {
+        let matrix = self.matrix();
+        let mut csv = String::from("side,price");
+        for timestamp in matrix.timestamps.iter() {
+            write!(csv, ",{}", timestamp).unwrap();
+        }
+        csv.push('\n');
+        for (side, rows) in [("bid", &matrix.bids), ("ask", &matrix.asks)] {
+            for (price, row) in matrix.prices.iter().zip(rows.iter()) {
+                if row.iter().all(|quantity| *quantity == 0) {
+                    continue;
+                }
+                write!(csv, "{},{}", side, price).unwrap();
+                for quantity in row {
+                    write!(csv, ",{}", quantity).unwrap();
+                }
+                csv.push('\n');
+            }
+        }
+        csv
+    }
+}
+
+impl TradeEngine {
+    pub fn record_heatmap(&mut self, ticker: TokenTicker, tick: f64, cadence_millis: u64) {
+        self.heatmaps
+            .insert(ticker, DepthHeatmap::new(tick, cadence_millis));
+    }
+
+    // Take a depth sample for every recorder whose cadence has elapsed.
+    pub fn sample_heatmaps(&mut self) {
+        let now = self.now();
+        for (ticker, heatmap) in self.heatmaps.iter_mut() {
+            if !heatmap.due(now) {
+                continue;
+            }
+            if let Some(orderbook) = self.order_books.get(ticker) {
+                heatmap.record(now, &orderbook.depth_snapshot());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::clock::SimulatedClock;
+    use crate::corelib::order::BuyOrSell;
+
+    #[test]
+    fn test_heatmap_samples_at_cadence() {
+        let clock = SimulatedClock::new(0);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        engine.list_new_token(TokenTicker::ETH);
+        engine.record_heatmap(TokenTicker::ETH, 1.0, 1_000);
+        let submit = |engine: &mut TradeEngine, side, price, quantity| {
+            engine
+                .submit_order(&TokenTicker::ETH, side, price, quantity)
+                .unwrap()
+        };
+        submit(&mut engine, BuyOrSell::Buy, 99.5, 3);
+        submit(&mut engine, BuyOrSell::Sell, 101.0, 2);
+        engine.sample_heatmaps();
+
+        clock.advance(500);
+        submit(&mut engine, BuyOrSell::Buy, 98.0, 4);
+        engine.sample_heatmaps();
+        clock.advance(500);
+        engine.sample_heatmaps();
+
+        let matrix = engine.heatmaps[&TokenTicker::ETH].matrix();
+        assert_eq!(matrix.timestamps, vec![0, 1_000]);
+        assert_eq!(matrix.prices, vec![98.0, 99.0, 101.0]);
+        assert_eq!(matrix.bids, vec![vec![0, 4], vec![3, 3], vec![0, 0]]);
+        assert_eq!(matrix.asks, vec![vec![0, 0], vec![0, 0], vec![2, 2]]);
+        assert_eq!(
+            engine.heatmaps[&TokenTicker::ETH].to_csv(),
+            "side,price,0,1000\nbid,98,0,4\nbid,99,3,3\nask,101,2,2\n"
+        );
+    }
+}
@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+
+use super::engine::TradeEngine;
+use super::token::TokenTicker;
+
+// External index prices per underlying, as (timestamp, price) observations in
+// publication order.
+#[derive(Default)]
+pub struct IndexFeed {
+    observations: HashMap<TokenTicker, Vec<(u64, f64)>>,
+}
+
+impl IndexFeed {
+    pub fn new() -> IndexFeed {
+        IndexFeed::default()
+    }
+
+    pub fn publish(&mut self, ticker: TokenTicker, timestamp: u64, price: f64) {
+        self.observations
+            .entry(ticker)
+            .or_default()
+            .push((timestamp, price));
+    }
+
+    pub fn latest(&self, ticker: &TokenTicker) -> Option<f64> {
+        self.observations
+            .get(ticker)
+            .and_then(|observations| observations.last())
+            .map(|(_, price)| *price)
+    }
+
+    pub fn observations(&self, ticker: &TokenTicker) -> &[(u64, f64)] {
+        self.observations
+            .get(ticker)
+            .map(|observations| observations.as_slice())
+            .unwrap_or_default()
+    }
+
+    // Time-weighted average over `[from, to)`. Each observation holds until
+    // the next one; the price in force at `from` counts from `from`.
+    pub fn twap(&self, ticker: &TokenTicker, from: u64, to: u64) -> Option<f64> {
+        if to <= from {
+            return None;
+        }
+        let observations = self.observations(ticker);
+        let mut weighted = 0.0;
+        let mut covered = 0;
+        for (i, (timestamp, price)) in observations.iter().enumerate() {
+            let end = observations
+                .get(i + 1)
+                .map(|(next, _)| *next)
+                .unwrap_or(to)
+                .min(to);
+            let start = (*timestamp).max(from);
+            if end <= start {
+                continue;
+            }
+            weighted += price * (end - start) as f64;
+            covered += end - start;
+        }
+        if covered == 0 {
+            return None;
+        }
+        Some(weighted / covered as f64)
+    }
+}
+
+impl TradeEngine {
+    pub fn publish_index_price(&mut self, ticker: TokenTicker, price: f64) {
+        let now = self.now();
+        self.index.publish(ticker, now, price);
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_twap_weights_by_time() {
+        let mut feed = IndexFeed::new();
+        feed.publish(TokenTicker::BTC, 0, 100.0);
+        feed.publish(TokenTicker::BTC, 30, 200.0);
+        feed.publish(TokenTicker::BTC, 90, 400.0);
+
+        assert_eq!(feed.latest(&TokenTicker::BTC), Some(400.0));
+        assert_eq!(feed.twap(&TokenTicker::BTC, 0, 120), Some(225.0));
+        assert_eq!(feed.twap(&TokenTicker::BTC, 60, 90), Some(200.0));
+        assert_eq!(feed.twap(&TokenTicker::ETH, 0, 120), None);
+    }
+}
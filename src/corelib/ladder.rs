@@ -0,0 +1,414 @@
+use std::collections::HashMap;
+
+use super::events::BookEvent;
+use super::order::BuyOrSell;
+use super::replay::BookBackend;
+
+// Prices are snapped to the nearest tick when within this fraction of one.
+const TICK_EPSILON: f64 = 1e-6;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OffLadder {
+    pub price: f64,
+}
+
+// The ladder needs a finite bottom price and a finite, positive tick.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InvalidLadder {
+    pub min_price: f64,
+    pub tick_size: f64,
+}
+
+// Orders resting at one tick, oldest first, stored column by column so a
+// sweep or a cancel lookup only pulls in the column it reads. Orders leave
+// by dropping to zero quantity; those before `head` are gone and the prefix
+// is compacted away once it is half the level. Ids only grow, so `ids` is
+// sorted.
+#[derive(Debug, Clone, Default)]
+struct Level {
+    head: usize,
+    live: usize,
+    ids: Vec<u64>,
+    prices: Vec<f64>,
+    quantities: Vec<u32>,
+    timestamps: Vec<u64>,
+}
+
+impl Level {
+    fn is_empty(&self) -> bool {
+        self.live == 0
+    }
+
+    fn push(&mut self, id: u64, price: f64, quantity: u32, timestamp: u64) {
+        self.ids.push(id);
+        self.prices.push(price);
+        self.quantities.push(quantity);
+        self.timestamps.push(timestamp);
+        self.live += 1;
+    }
+
+    // Take up to `quantity` from the oldest order; returns its id, price
+    // and what is left of it.
+    fn fill_front(&mut self, quantity: u32) -> (u64, f64, u32) {
+        let index = self.head;
+        self.quantities[index] -= quantity;
+        let filled = (self.ids[index], self.prices[index], self.quantities[index]);
+        if filled.2 == 0 {
+            self.live -= 1;
+            self.advance();
+        }
+        filled
+    }
+
+    fn front_quantity(&self) -> u32 {
+        self.quantities[self.head]
+    }
+
+    fn front_price(&self) -> f64 {
+        self.prices[self.head]
+    }
+
+    // Take a resting order out; returns its price and remaining quantity.
+    fn remove(&mut self, id: u64) -> Option<(f64, u32)> {
+        let index = self.head + self.ids[self.head..].binary_search(&id).ok()?;
+        let remaining = std::mem::take(&mut self.quantities[index]);
+        if remaining == 0 {
+            return None;
+        }
+        let price = self.prices[index];
+        self.live -= 1;
+        if index == self.head {
+            self.advance();
+        }
+        Some((price, remaining))
+    }
+
+    // Skip departed orders at the front.
+    fn advance(&mut self) {
+        while self.head < self.ids.len() && self.quantities[self.head] == 0 {
+            self.head += 1;
+        }
+        if self.head * 2 >= self.ids.len() {
+            let head = std::mem::take(&mut self.head);
+            self.ids.drain(..head);
+            self.prices.drain(..head);
+            self.quantities.drain(..head);
+            self.timestamps.drain(..head);
+        }
+    }
+
+    // (id, remaining, timestamp) of the resting orders, oldest first.
+    fn orders(&self) -> impl Iterator<Item = (u64, u32, u64)> + '_ {
+        (self.head..self.ids.len())
+            .filter(|index| self.quantities[*index] > 0)
+            .map(|index| {
+                (
+                    self.ids[index],
+                    self.quantities[index],
+                    self.timestamps[index],
+                )
+            })
+    }
+}
+
+// Book for instruments with a bounded price range and a fixed tick. Levels
+// are pre-allocated and indexed by tick offset from `min_price`, so matching
+// walks arrays instead of looking levels up in a map. Same price-time
+// priority and events as `OrderBook`.
+#[derive(Debug, Clone)]
+pub struct LadderBook {
+    min_price: f64,
+    tick_size: f64,
+    bids: Vec<Level>,
+    asks: Vec<Level>,
+    // Offsets of the best occupied levels.
+    best_bid: Option<usize>,
+    best_ask: Option<usize>,
+    // Side and offset of every resting order, for cancels.
+    locations: HashMap<u64, (BuyOrSell, usize)>,
+    next_order_id: u64,
+    events: Vec<BookEvent>,
+}
+
+impl LadderBook {
+    // `levels` ticks from `min_price` up.
+    pub fn new(min_price: f64, tick_size: f64, levels: usize) -> Result<LadderBook, InvalidLadder> {
+        if !min_price.is_finite() || !tick_size.is_finite() || tick_size <= 0.0 {
+            return Err(InvalidLadder {
+                min_price,
+                tick_size,
+            });
+        }
+        Ok(LadderBook {
+            min_price,
+            tick_size,
+            bids: vec![Level::default(); levels],
+            asks: vec![Level::default(); levels],
+            best_bid: None,
+            best_ask: None,
+            locations: HashMap::new(),
+            next_order_id: 1,
+            events: Vec::new(),
+        })
+    }
+
+    // Tick offset of `price`, if it lies on the ladder. NaN and infinite
+    // prices never do.
+    pub fn offset(&self, price: f64) -> Option<usize> {
+        let ticks = (price - self.min_price) / self.tick_size;
+        if !ticks.is_finite() {
+            return None;
+        }
+        let offset = ticks.round();
+        if offset < 0.0 || (ticks - offset).abs() > TICK_EPSILON {
+            return None;
+        }
+        let offset = offset as usize;
+        (offset < self.bids.len()).then_some(offset)
+    }
+
+    pub fn try_submit(
+        &mut self,
+        side: BuyOrSell,
+        price: f64,
+        quantity: u32,
+        timestamp: u64,
+    ) -> Result<u64, OffLadder> {
+        let offset = self.offset(price).ok_or(OffLadder { price })?;
+        let id = self.next_order_id;
+        self.next_order_id += 1;
+        match side {
+            BuyOrSell::Buy => {
+                self.bids[offset].push(id, price, quantity, timestamp);
+                self.best_bid = self.best_bid.max(Some(offset));
+            }
+            BuyOrSell::Sell => {
+                self.asks[offset].push(id, price, quantity, timestamp);
+                self.best_ask = Some(self.best_ask.map_or(offset, |best| best.min(offset)));
+            }
+        }
+        self.locations.insert(id, (side, offset));
+        self.events.push(BookEvent::OrderAdded {
+            order_id: id,
+            side,
+            price,
+            quantity,
+            timestamp,
+            metadata: None,
+        });
+        Ok(id)
+    }
+
+    pub fn best_bid(&self) -> Option<f64> {
+        self.best_bid.map(|offset| self.bids[offset].front_price())
+    }
+
+    pub fn best_ask(&self) -> Option<f64> {
+        self.best_ask.map(|offset| self.asks[offset].front_price())
+    }
+
+    // (id, remaining, timestamp) of the orders resting at `price`, in
+    // priority order.
+    pub fn queue(&self, side: BuyOrSell, price: f64) -> Vec<(u64, u32, u64)> {
+        let Some(offset) = self.offset(price) else {
+            return Vec::new();
+        };
+        match side {
+            BuyOrSell::Buy => self.bids[offset].orders().collect(),
+            BuyOrSell::Sell => self.asks[offset].orders().collect(),
+        }
+    }
+
+    // Next occupied bid level at or below `from`.
+    fn next_bid(&self, from: usize) -> Option<usize> {
+        (0..=from)
+            .rev()
+            .find(|offset| !self.bids[*offset].is_empty())
+    }
+
+    // Next occupied ask level at or above `from`.
+    fn next_ask(&self, from: usize) -> Option<usize> {
+        (from..self.asks.len()).find(|offset| !self.asks[*offset].is_empty())
+    }
+
+    // Remove a resting order; false if it is not on the book.
+    pub fn cancel_order(&mut self, order_id: u64, timestamp: u64) -> bool {
+        let Some((side, offset)) = self.locations.remove(&order_id) else {
+            return false;
+        };
+        let level = match side {
+            BuyOrSell::Buy => &mut self.bids[offset],
+            BuyOrSell::Sell => &mut self.asks[offset],
+        };
+        let (price, remaining) = level.remove(order_id).unwrap();
+        if level.is_empty() {
+            match side {
+                BuyOrSell::Buy if self.best_bid == Some(offset) => {
+                    self.best_bid = offset.checked_sub(1).and_then(|from| self.next_bid(from));
+                }
+                BuyOrSell::Sell if self.best_ask == Some(offset) => {
+                    self.best_ask = self.next_ask(offset + 1);
+                }
+                _ => {}
+            }
+        }
+        self.events.push(BookEvent::OrderCancelled {
+            order_id,
+            side,
+            price,
+            remaining,
+            timestamp,
+            metadata: None,
+        });
+        true
+    }
+
+    // Cross the best bid with the best ask until the book is uncrossed,
+    // oldest order first within a level. Returns the number of fills.
+    pub fn match_orders(&mut self, timestamp: u64) -> usize {
+        let mut fills = 0;
+        while let (Some(bid), Some(ask)) = (self.best_bid, self.best_ask) {
+            if bid < ask {
+                break;
+            }
+            let quantity = self.bids[bid]
+                .front_quantity()
+                .min(self.asks[ask].front_quantity());
+            let buy = self.bids[bid].fill_front(quantity);
+            let sell = self.asks[ask].fill_front(quantity);
+            for (side, (order_id, price, remaining)) in
+                [(BuyOrSell::Buy, buy), (BuyOrSell::Sell, sell)]
+            {
+                self.events.push(BookEvent::OrderFilled {
+                    order_id,
+                    side,
+                    price,
+                    filled: quantity,
+                    remaining,
+                    timestamp,
+                    metadata: None,
+                });
+                if remaining == 0 {
+                    self.locations.remove(&order_id);
+                }
+            }
+            if self.bids[bid].is_empty() {
+                self.best_bid = bid.checked_sub(1).and_then(|from| self.next_bid(from));
+            }
+            if self.asks[ask].is_empty() {
+                self.best_ask = self.next_ask(ask + 1);
+            }
+            fills += 1;
+        }
+        fills
+    }
+}
+
+// Prices must lie on the ladder; submitting one that does not panics.
+impl BookBackend for LadderBook {
+    fn submit(&mut self, side: BuyOrSell, price: f64, quantity: u32, timestamp: u64) -> u64 {
+        self.try_submit(side, price, quantity, timestamp)
+            .expect("price on the ladder")
+    }
+
+    fn cancel(&mut self, order_id: u64, timestamp: u64) {
+        self.cancel_order(order_id, timestamp);
+    }
+
+    fn match_orders(&mut self, timestamp: u64) {
+        LadderBook::match_orders(self, timestamp);
+    }
+
+    fn events(&self) -> &[BookEvent] {
+        &self.events
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::orderbook::OrderBook;
+    use crate::corelib::replay::{differential_replay, BookCommand};
+    use crate::corelib::rng::SeededRng;
+
+    #[test]
+    fn test_ladder_matches_order_book() {
+        for seed in 1..50 {
+            let mut rng = SeededRng::new(seed);
+            let mut submitted = 0;
+            let commands: Vec<(u64, BookCommand)> = (0..300)
+                .map(|timestamp| {
+                    let command = match rng.between(0, 9) {
+                        0..=5 => {
+                            submitted += 1;
+                            BookCommand::Submit {
+                                side: if rng.between(0, 1) == 0 {
+                                    BuyOrSell::Buy
+                                } else {
+                                    BuyOrSell::Sell
+                                },
+                                price: 99.0 + rng.between(0, 20) as f64 * 0.25,
+                                quantity: rng.between(1, 20) as u32,
+                            }
+                        }
+                        6 | 7 if submitted > 0 => BookCommand::Cancel {
+                            order_id: rng.between(1, submitted),
+                        },
+                        _ => BookCommand::Match,
+                    };
+                    (timestamp, command)
+                })
+                .collect();
+            let result = differential_replay(
+                &mut OrderBook::new(),
+                &mut LadderBook::new(95.0, 0.25, 64).unwrap(),
+                &commands,
+            );
+            assert_eq!(result, Ok(()), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn test_rejects_prices_off_the_ladder() {
+        let mut ladder = LadderBook::new(100.0, 0.5, 10).unwrap();
+        assert_eq!(ladder.offset(101.5), Some(3));
+        assert_eq!(
+            ladder.try_submit(BuyOrSell::Buy, 101.2, 1, 0),
+            Err(OffLadder { price: 101.2 })
+        );
+        assert!(ladder.try_submit(BuyOrSell::Buy, 105.0, 1, 0).is_err());
+        assert_eq!(ladder.offset(f64::NAN), None);
+        assert_eq!(ladder.offset(f64::INFINITY), None);
+        assert!(ladder.try_submit(BuyOrSell::Buy, f64::NAN, 1, 0).is_err());
+        assert_eq!(ladder.best_bid(), None);
+        assert!(LadderBook::new(100.0, 0.0, 10).is_err());
+        assert!(LadderBook::new(100.0, -0.5, 10).is_err());
+        assert!(LadderBook::new(f64::NAN, 0.5, 10).is_err());
+        assert!(ladder.try_submit(BuyOrSell::Buy, 99.5, 1, 0).is_err());
+        ladder.try_submit(BuyOrSell::Sell, 104.5, 1, 0).unwrap();
+        assert_eq!(ladder.best_ask(), Some(104.5));
+        assert_eq!(ladder.best_bid(), None);
+    }
+
+    #[test]
+    fn test_queue_keeps_priority_through_fills_and_cancels() {
+        let mut ladder = LadderBook::new(100.0, 1.0, 10).unwrap();
+        for timestamp in 0..5 {
+            ladder
+                .try_submit(BuyOrSell::Buy, 105.0, 10, timestamp)
+                .unwrap();
+        }
+        assert!(ladder.cancel_order(2, 5));
+        assert!(!ladder.cancel_order(2, 5));
+        ladder.try_submit(BuyOrSell::Sell, 105.0, 15, 6).unwrap();
+        assert_eq!(ladder.match_orders(7), 2);
+        assert!(ladder.cancel_order(4, 8));
+        assert_eq!(
+            ladder.queue(BuyOrSell::Buy, 105.0),
+            vec![(3, 5, 2), (5, 10, 4)]
+        );
+        assert_eq!(ladder.queue(BuyOrSell::Sell, 105.0), vec![]);
+        assert_eq!(ladder.best_bid(), Some(105.0));
+    }
+}
@@ -0,0 +1,151 @@
+use std::time::Duration;
+
+// Each power of two is split into 2^SUB_BUCKET_BITS linear sub-buckets, which
+// bounds the relative error of any recorded value to about 6%.
+const SUB_BUCKET_BITS: u32 = 4;
+const SUB_BUCKETS: u64 = 1 << SUB_BUCKET_BITS;
+const BUCKET_COUNT: usize = (SUB_BUCKETS + (64 - SUB_BUCKET_BITS as u64) * SUB_BUCKETS) as usize;
+
+// Log-linear histogram of nanosecond latencies in the spirit of HdrHistogram:
+// constant memory, O(1) recording, percentiles with bounded relative error.
+#[derive(Debug, Clone)]
+pub struct LatencyHistogram {
+    buckets: Vec<u64>,
+    count: u64,
+    sum: u128,
+    min: u64,
+    max: u64,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LatencyHistogram {
+    pub fn new() -> LatencyHistogram {
+        LatencyHistogram {
+            buckets: vec![0; BUCKET_COUNT],
+            count: 0,
+            sum: 0,
+            min: u64::MAX,
+            max: 0,
+        }
+    }
+
+    pub fn record(&mut self, duration: Duration) {
+        self.record_nanos(duration.as_nanos().min(u64::MAX as u128) as u64);
+    }
+
+    pub fn record_nanos(&mut self, nanos: u64) {
+        self.buckets[Self::bucket_index(nanos)] += 1;
+        self.count += 1;
+        self.sum += nanos as u128;
+        self.min = self.min.min(nanos);
+        self.max = self.max.max(nanos);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn min(&self) -> Option<u64> {
+        (self.count > 0).then_some(self.min)
+    }
+
+    pub fn max(&self) -> Option<u64> {
+        (self.count > 0).then_some(self.max)
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum as f64 / self.count as f64)
+    }
+
+    // Highest value equivalent to the recorded value at `quantile` (0.0-1.0).
+    pub fn percentile(&self, quantile: f64) -> Option<u64> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = ((quantile.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (index, bucket) in self.buckets.iter().enumerate() {
+            seen += bucket;
+            if seen >= rank {
+                return Some(Self::bucket_upper_bound(index).min(self.max));
+            }
+        }
+        Some(self.max)
+    }
+
+    pub fn reset(&mut self) {
+        *self = LatencyHistogram::new();
+    }
+
+    fn bucket_index(nanos: u64) -> usize {
+        if nanos < SUB_BUCKETS {
+            return nanos as usize;
+        }
+        let shift = 63 - nanos.leading_zeros() - SUB_BUCKET_BITS;
+        let mantissa = nanos >> shift;
+        (SUB_BUCKETS + shift as u64 * SUB_BUCKETS + (mantissa - SUB_BUCKETS)) as usize
+    }
+
+    fn bucket_upper_bound(index: usize) -> u64 {
+        let index = index as u64;
+        if index < SUB_BUCKETS {
+            return index;
+        }
+        let shift = (index - SUB_BUCKETS) / SUB_BUCKETS;
+        let mantissa = SUB_BUCKETS + (index - SUB_BUCKETS) % SUB_BUCKETS;
+        ((mantissa + 1) << shift).saturating_sub(1)
+    }
+}
+
+// Internal timings collected by the engine when latency tracking is enabled.
+#[derive(Debug, Clone, Default)]
+pub struct LatencyStats {
+    // From an order entering `TradeEngine::submit_order` until it rests on
+    // the book and its id is returned.
+    pub enter_to_ack: LatencyHistogram,
+    // Duration of one `TradeEngine::match_orders` pass.
+    pub match_loop: LatencyHistogram,
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_percentiles_within_relative_error() {
+        let mut histogram = LatencyHistogram::new();
+        for nanos in 1..=10_000u64 {
+            histogram.record_nanos(nanos);
+        }
+
+        assert_eq!(histogram.count(), 10_000);
+        assert_eq!(histogram.min(), Some(1));
+        assert_eq!(histogram.max(), Some(10_000));
+        assert_eq!(histogram.mean(), Some(5_000.5));
+
+        for (quantile, exact) in [(0.5, 5_000.0), (0.9, 9_000.0), (0.99, 9_900.0)] {
+            let value = histogram.percentile(quantile).unwrap() as f64;
+            assert!(
+                (value - exact).abs() / exact < 0.07,
+                "p{quantile} = {value}"
+            );
+        }
+        assert_eq!(histogram.percentile(1.0), Some(10_000));
+    }
+
+    #[test]
+    fn test_small_values_are_exact() {
+        let mut histogram = LatencyHistogram::new();
+        histogram.record_nanos(3);
+        histogram.record_nanos(7);
+        assert_eq!(histogram.percentile(0.5), Some(3));
+        assert_eq!(histogram.percentile(1.0), Some(7));
+        assert_eq!(LatencyHistogram::new().percentile(0.5), None);
+    }
+}
@@ -0,0 +1,768 @@
+use std::collections::{HashMap, HashSet};
+
+use super::balance_history::BalanceHistory;
+use super::clock::Clock;
+use super::escrow::Escrows;
+use super::order::Wallet;
+use super::token::TokenTicker;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Balance {
+    pub free: u64,
+    pub locked: u64,
+}
+
+impl Balance {
+    pub fn total(&self) -> u64 {
+        self.free + self.locked
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LedgerError {
+    InsufficientFree {
+        token: TokenTicker,
+        available: u64,
+        requested: u64,
+    },
+    InsufficientLocked {
+        token: TokenTicker,
+        available: u64,
+        requested: u64,
+    },
+}
+
+// Why a mint or burn was refused. Nothing changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SupplyError {
+    NotAuthorized {
+        token: TokenTicker,
+        authority: Wallet,
+    },
+    // Minting would take the token's supply above its cap.
+    SupplyCapExceeded {
+        token: TokenTicker,
+        supply: u64,
+        amount: u64,
+        cap: u64,
+    },
+    Ledger(LedgerError),
+    Deposit(DepositError),
+}
+
+impl From<LedgerError> for SupplyError {
+    fn from(err: LedgerError) -> Self {
+        SupplyError::Ledger(err)
+    }
+}
+
+impl From<DepositError> for SupplyError {
+    fn from(err: DepositError) -> Self {
+        SupplyError::Deposit(err)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepositOutcome {
+    Credited,
+    // The reference was credited before; nothing changed.
+    AlreadyCredited,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DepositError {
+    // The reference was already credited with different details.
+    ConflictingReference {
+        external_ref: String,
+    },
+    // The token's total supply would no longer fit in a u64. Nothing was
+    // credited.
+    SupplyOverflow {
+        token: TokenTicker,
+        supply: u64,
+        amount: u64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CreditedDeposit {
+    wallet: Wallet,
+    token: TokenTicker,
+    amount: u64,
+}
+
+// Wallet balances per token. Locked amounts are held by a subsystem
+// (staking, open orders, ...) and cannot be traded or withdrawn. The
+// treasury is the protocol's own account: it receives its share of fees and
+// owns the liquidity the protocol puts into AMM pools.
+pub struct Ledger {
+    balances: HashMap<Wallet, HashMap<TokenTicker, Balance>>,
+    // Part of each locked balance held by staking pools. Only `unstake`
+    // releases it, so other subsystems cannot unlock staked funds.
+    staked: HashMap<Wallet, HashMap<TokenTicker, u64>>,
+    treasury: Wallet,
+    // External deposits by the reference of the bridge or custodian.
+    credited_deposits: HashMap<String, CreditedDeposit>,
+    // Conditional payments funded from payers' locked balances.
+    pub escrows: Escrows,
+    // Wallets allowed to create and destroy each token's supply, e.g. a
+    // bridge for the wrapped asset.
+    mint_authorities: HashMap<TokenTicker, HashSet<Wallet>>,
+    supply_caps: HashMap<TokenTicker, u64>,
+    history: Option<BalanceHistory>,
+}
+
+// `amount * numerator / denominator` rounded down, or None if it no longer
+// fits a u64.
+pub(crate) fn rescale_amount(amount: u64, numerator: u64, denominator: u64) -> Option<u64> {
+    u64::try_from(amount as u128 * numerator as u128 / denominator as u128).ok()
+}
+
+impl Default for Ledger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Ledger {
+    pub fn new() -> Ledger {
+        Ledger {
+            balances: HashMap::new(),
+            staked: HashMap::new(),
+            treasury: Wallet::new(String::from("treasury")),
+            credited_deposits: HashMap::new(),
+            escrows: Escrows::default(),
+            mint_authorities: HashMap::new(),
+            supply_caps: HashMap::new(),
+            history: None,
+        }
+    }
+
+    pub fn treasury(&self) -> &Wallet {
+        &self.treasury
+    }
+
+    pub fn treasury_balance(&self, token: &TokenTicker) -> u64 {
+        self.free_balance(&self.treasury, token)
+    }
+
+    pub fn balance(&self, wallet: &Wallet, token: &TokenTicker) -> Balance {
+        self.balances
+            .get(wallet)
+            .and_then(|tokens| tokens.get(token))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn free_balance(&self, wallet: &Wallet, token: &TokenTicker) -> u64 {
+        self.balance(wallet, token).free
+    }
+
+    pub fn locked_balance(&self, wallet: &Wallet, token: &TokenTicker) -> u64 {
+        self.balance(wallet, token).locked
+    }
+
+    // Part of the locked balance held by staking pools.
+    pub fn staked_balance(&self, wallet: &Wallet, token: &TokenTicker) -> u64 {
+        self.staked
+            .get(wallet)
+            .and_then(|tokens| tokens.get(token))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    // Locked funds no staking pool holds, which `unlock` and `debit_locked`
+    // may release.
+    fn unstaked_locked(&self, wallet: &Wallet, token: &TokenTicker) -> u64 {
+        self.locked_balance(wallet, token) - self.staked_balance(wallet, token)
+    }
+
+    // Bring `amount` of `token` into the ledger. The total supply bounds
+    // every balance, so checking it keeps all of them from overflowing.
+    pub fn deposit(
+        &mut self,
+        wallet: Wallet,
+        token: TokenTicker,
+        amount: u64,
+    ) -> Result<(), DepositError> {
+        let supply = self.total_supply(&token);
+        if supply.checked_add(amount).is_none() {
+            return Err(DepositError::SupplyOverflow {
+                token,
+                supply,
+                amount,
+            });
+        }
+        self.credit(&wallet, &token, amount);
+        Ok(())
+    }
+
+    // Credit value the engine already accounts for: funds debited from
+    // another balance, a pool's reserves or a funded reward schedule.
+    pub(crate) fn credit(&mut self, wallet: &Wallet, token: &TokenTicker, amount: u64) {
+        self.update(wallet, token, |balance| balance.free += amount);
+    }
+
+    // Credit an external deposit at most once per `external_ref`, so
+    // integrations can retry until they see a success.
+    pub fn credit_deposit(
+        &mut self,
+        wallet: Wallet,
+        token: TokenTicker,
+        amount: u64,
+        external_ref: &str,
+    ) -> Result<DepositOutcome, DepositError> {
+        let deposit = CreditedDeposit {
+            wallet,
+            token,
+            amount,
+        };
+        if let Some(credited) = self.credited_deposits.get(external_ref) {
+            if *credited != deposit {
+                return Err(DepositError::ConflictingReference {
+                    external_ref: external_ref.to_string(),
+                });
+            }
+            return Ok(DepositOutcome::AlreadyCredited);
+        }
+        self.deposit(deposit.wallet.clone(), deposit.token.clone(), amount)?;
+        self.credited_deposits
+            .insert(external_ref.to_string(), deposit);
+        Ok(DepositOutcome::Credited)
+    }
+
+    pub fn is_deposit_credited(&self, external_ref: &str) -> bool {
+        self.credited_deposits.contains_key(external_ref)
+    }
+
+    pub(crate) fn grant_mint_authority(&mut self, token: TokenTicker, authority: Wallet) {
+        self.mint_authorities
+            .entry(token)
+            .or_default()
+            .insert(authority);
+    }
+
+    // False if `authority` did not hold it.
+    pub(crate) fn revoke_mint_authority(
+        &mut self,
+        token: &TokenTicker,
+        authority: &Wallet,
+    ) -> bool {
+        self.mint_authorities
+            .get_mut(token)
+            .is_some_and(|authorities| authorities.remove(authority))
+    }
+
+    pub fn is_mint_authority(&self, token: &TokenTicker, authority: &Wallet) -> bool {
+        self.mint_authorities
+            .get(token)
+            .is_some_and(|authorities| authorities.contains(authority))
+    }
+
+    // Most of `token` that minting may bring into existence; None lifts the
+    // cap. A cap below the current supply only stops further minting.
+    pub(crate) fn set_supply_cap(&mut self, token: TokenTicker, cap: Option<u64>) {
+        match cap {
+            Some(cap) => self.supply_caps.insert(token, cap),
+            None => self.supply_caps.remove(&token),
+        };
+    }
+
+    pub fn supply_cap(&self, token: &TokenTicker) -> Option<u64> {
+        self.supply_caps.get(token).copied()
+    }
+
+    pub fn supply_caps(&self) -> impl Iterator<Item = (&TokenTicker, u64)> {
+        self.supply_caps.iter().map(|(token, cap)| (token, *cap))
+    }
+
+    // Every token's mint authorities, one pair per grant.
+    pub fn mint_authorities(&self) -> impl Iterator<Item = (&TokenTicker, &Wallet)> {
+        self.mint_authorities
+            .iter()
+            .flat_map(|(token, authorities)| authorities.iter().map(move |wallet| (token, wallet)))
+    }
+
+    // Create `amount` of `token` in `wallet`'s free balance, on the word of
+    // one of the token's mint authorities and within its supply cap. Go
+    // through `TradeEngine::mint` so it is audited.
+    pub(crate) fn mint(
+        &mut self,
+        token: &TokenTicker,
+        wallet: &Wallet,
+        amount: u64,
+        authority: &Wallet,
+    ) -> Result<(), SupplyError> {
+        self.check_mint_authority(token, authority)?;
+        let supply = self.total_supply(token);
+        if let Some(cap) = self.supply_cap(token) {
+            if supply.checked_add(amount).is_none_or(|total| total > cap) {
+                return Err(SupplyError::SupplyCapExceeded {
+                    token: token.clone(),
+                    supply,
+                    amount,
+                    cap,
+                });
+            }
+        }
+        self.deposit(wallet.clone(), token.clone(), amount)?;
+        Ok(())
+    }
+
+    // Destroy `amount` of `token` from `wallet`'s free balance, e.g. when
+    // the bridge releases the underlying asset.
+    pub(crate) fn burn(
+        &mut self,
+        token: &TokenTicker,
+        wallet: &Wallet,
+        amount: u64,
+        authority: &Wallet,
+    ) -> Result<(), SupplyError> {
+        self.check_mint_authority(token, authority)?;
+        self.debit_free(wallet, token, amount)?;
+        Ok(())
+    }
+
+    fn check_mint_authority(
+        &self,
+        token: &TokenTicker,
+        authority: &Wallet,
+    ) -> Result<(), SupplyError> {
+        if !self.is_mint_authority(token, authority) {
+            return Err(SupplyError::NotAuthorized {
+                token: token.clone(),
+                authority: authority.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    pub fn withdraw(
+        &mut self,
+        wallet: &Wallet,
+        token: &TokenTicker,
+        amount: u64,
+    ) -> Result<(), LedgerError> {
+        self.debit_free(wallet, token, amount)
+    }
+
+    // Move free funds into the locked bucket.
+    pub fn lock(
+        &mut self,
+        wallet: &Wallet,
+        token: &TokenTicker,
+        amount: u64,
+    ) -> Result<(), LedgerError> {
+        self.debit_free(wallet, token, amount)?;
+        self.update(wallet, token, |balance| balance.locked += amount);
+        Ok(())
+    }
+
+    // Release locked funds back to the free bucket. Staked funds are left
+    // alone.
+    pub fn unlock(
+        &mut self,
+        wallet: &Wallet,
+        token: &TokenTicker,
+        amount: u64,
+    ) -> Result<(), LedgerError> {
+        let available = self.unstaked_locked(wallet, token);
+        if available < amount {
+            return Err(LedgerError::InsufficientLocked {
+                token: token.clone(),
+                available,
+                requested: amount,
+            });
+        }
+        self.update(wallet, token, |balance| {
+            balance.locked -= amount;
+            balance.free += amount;
+        });
+        Ok(())
+    }
+
+    // Lock free funds on behalf of a staking pool.
+    pub(crate) fn stake(
+        &mut self,
+        wallet: &Wallet,
+        token: &TokenTicker,
+        amount: u64,
+    ) -> Result<(), LedgerError> {
+        self.lock(wallet, token, amount)?;
+        *self
+            .staked
+            .entry(wallet.clone())
+            .or_default()
+            .entry(token.clone())
+            .or_insert(0) += amount;
+        Ok(())
+    }
+
+    // Release staked funds back to the free bucket.
+    pub(crate) fn unstake(
+        &mut self,
+        wallet: &Wallet,
+        token: &TokenTicker,
+        amount: u64,
+    ) -> Result<(), LedgerError> {
+        let available = self.staked_balance(wallet, token);
+        if available < amount {
+            return Err(LedgerError::InsufficientLocked {
+                token: token.clone(),
+                available,
+                requested: amount,
+            });
+        }
+        *self.staked.get_mut(wallet).unwrap().get_mut(token).unwrap() -= amount;
+        self.update(wallet, token, |balance| {
+            balance.locked -= amount;
+            balance.free += amount;
+        });
+        Ok(())
+    }
+
+    // Move free funds between wallets without a trade or fee.
+    pub fn transfer(
+        &mut self,
+        from: &Wallet,
+        to: &Wallet,
+        token: &TokenTicker,
+        amount: u64,
+    ) -> Result<(), LedgerError> {
+        self.debit_free(from, token, amount)?;
+        self.credit(to, token, amount);
+        Ok(())
+    }
+
+    // Deliver `quantity` of `base` from seller to buyer against `notional`
+    // of `quote`. Both legs are checked before anything moves.
+    pub(crate) fn settle(
+        &mut self,
+        buyer: &Wallet,
+        seller: &Wallet,
+        base: &TokenTicker,
+        quantity: u64,
+        quote: &TokenTicker,
+        notional: u64,
+    ) -> Result<(), LedgerError> {
+        let seller_base = self.free_balance(seller, base);
+        if seller_base < quantity {
+            return Err(LedgerError::InsufficientFree {
+                token: base.clone(),
+                available: seller_base,
+                requested: quantity,
+            });
+        }
+        self.debit_free(buyer, quote, notional)?;
+        self.debit_free(seller, base, quantity)?;
+        self.credit(buyer, base, quantity);
+        self.credit(seller, quote, notional);
+        Ok(())
+    }
+
+    pub fn wallets(&self) -> impl Iterator<Item = &Wallet> {
+        self.balances.keys()
+    }
+
+    // Every token the wallet holds, free and locked.
+    pub fn holdings(&self, wallet: &Wallet) -> Vec<(TokenTicker, Balance)> {
+        self.balances
+            .get(wallet)
+            .map(|tokens| {
+                tokens
+                    .iter()
+                    .map(|(token, balance)| (token.clone(), *balance))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    // Start keeping a history of every balance change, stamped by `clock`,
+    // with a full checkpoint every `checkpoint_every` changes to bound how
+    // much a query replays. Restarts the history if one was kept.
+    pub fn record_history(&mut self, clock: Box<dyn Clock>, checkpoint_every: usize) {
+        self.history = Some(BalanceHistory::new(clock, checkpoint_every, &self.balances));
+    }
+
+    // The wallet's balance of `token` as of `timestamp`, from the history.
+    // None without a history reaching back that far.
+    pub fn balance_at(
+        &self,
+        wallet: &Wallet,
+        token: &TokenTicker,
+        timestamp: u64,
+    ) -> Option<Balance> {
+        let positions = self.positions_at(wallet, timestamp)?;
+        Some(
+            positions
+                .into_iter()
+                .find(|(held, _)| held == token)
+                .map_or_else(Balance::default, |(_, balance)| balance),
+        )
+    }
+
+    // `holdings` as of `timestamp`, from the history.
+    pub fn positions_at(
+        &self,
+        wallet: &Wallet,
+        timestamp: u64,
+    ) -> Option<Vec<(TokenTicker, Balance)>> {
+        self.history.as_ref()?.positions_at(wallet, timestamp)
+    }
+
+    // Tokens with a balance in any wallet.
+    pub fn tokens(&self) -> HashSet<TokenTicker> {
+        self.balances
+            .values()
+            .flat_map(|tokens| tokens.keys().cloned())
+            .collect()
+    }
+
+    // Total of `token` held across all wallets, free and locked.
+    pub fn total_supply(&self, token: &TokenTicker) -> u64 {
+        self.balances
+            .values()
+            .filter_map(|tokens| tokens.get(token))
+            .map(|balance| balance.total())
+            .sum()
+    }
+
+    // Part of `total_supply` in locked balances.
+    pub fn total_locked(&self, token: &TokenTicker) -> u64 {
+        self.balances
+            .values()
+            .filter_map(|tokens| tokens.get(token))
+            .map(|balance| balance.locked)
+            .sum()
+    }
+
+    // Whether every balance of `token`, and its total supply, still fits a
+    // u64 after `rescale_token` with the same ratio.
+    pub(crate) fn can_rescale_token(
+        &self,
+        token: &TokenTicker,
+        numerator: u64,
+        denominator: u64,
+    ) -> bool {
+        let mut supply: u64 = 0;
+        for balance in self
+            .balances
+            .values()
+            .filter_map(|tokens| tokens.get(token))
+        {
+            let rescaled = rescale_amount(balance.free, numerator, denominator).and_then(|free| {
+                rescale_amount(balance.locked, numerator, denominator)
+                    .and_then(|locked| free.checked_add(locked))
+            });
+            match rescaled.and_then(|total| supply.checked_add(total)) {
+                Some(total) => supply = total,
+                None => return false,
+            }
+        }
+        true
+    }
+
+    // Multiply every balance of `token` by `numerator / denominator`,
+    // rounding down. A locked balance is made of reservations: escrows and
+    // stakes here, plus the `reserved` amounts other subsystems hold (e.g.
+    // pending withdrawals). Each is rounded on its own, as its owner rounds
+    // it, so locked becomes their sum plus the rescaled unreserved rest and
+    // releasing them all still empties it. What that takes off the rounded
+    // locked balance goes to free.
+    pub(crate) fn rescale_token(
+        &mut self,
+        token: &TokenTicker,
+        numerator: u64,
+        denominator: u64,
+        reserved: &HashMap<Wallet, Vec<u64>>,
+    ) {
+        let rescale = |amount: u64| {
+            rescale_amount(amount, numerator, denominator).expect("rescale checked beforehand")
+        };
+        let holders: Vec<Wallet> = self
+            .balances
+            .iter()
+            .filter(|(_, tokens)| tokens.contains_key(token))
+            .map(|(wallet, _)| wallet.clone())
+            .collect();
+        for wallet in holders {
+            let reservations: Vec<u64> = reserved
+                .get(&wallet)
+                .into_iter()
+                .flatten()
+                .copied()
+                .chain(
+                    self.escrows
+                        .iter()
+                        .filter(|escrow| escrow.payer == wallet && &escrow.token == token)
+                        .map(|escrow| escrow.amount),
+                )
+                .chain([self.staked_balance(&wallet, token)])
+                .collect();
+            self.update(&wallet, token, |balance| {
+                let unreserved = balance.locked.saturating_sub(reservations.iter().sum());
+                let locked = reservations
+                    .iter()
+                    .map(|amount| rescale(*amount))
+                    .sum::<u64>()
+                    .saturating_add(rescale(unreserved))
+                    .min(rescale(balance.locked));
+                balance.free = rescale(balance.free) + rescale(balance.locked) - locked;
+                balance.locked = locked;
+            });
+        }
+        // Rounded per wallet like the stakes themselves, and never above the
+        // rescaled locked balance that contains it.
+        for staked in self
+            .staked
+            .values_mut()
+            .filter_map(|tokens| tokens.get_mut(token))
+        {
+            *staked = rescale(*staked);
+        }
+    }
+
+    pub(crate) fn debit_free(
+        &mut self,
+        wallet: &Wallet,
+        token: &TokenTicker,
+        amount: u64,
+    ) -> Result<(), LedgerError> {
+        let available = self.free_balance(wallet, token);
+        if available < amount {
+            return Err(LedgerError::InsufficientFree {
+                token: token.clone(),
+                available,
+                requested: amount,
+            });
+        }
+        self.update(wallet, token, |balance| balance.free -= amount);
+        Ok(())
+    }
+
+    // Remove locked funds from the ledger, e.g. once a withdrawal leaves.
+    // Staked funds are left alone.
+    pub(crate) fn debit_locked(
+        &mut self,
+        wallet: &Wallet,
+        token: &TokenTicker,
+        amount: u64,
+    ) -> Result<(), LedgerError> {
+        let available = self.unstaked_locked(wallet, token);
+        if available < amount {
+            return Err(LedgerError::InsufficientLocked {
+                token: token.clone(),
+                available,
+                requested: amount,
+            });
+        }
+        self.update(wallet, token, |balance| balance.locked -= amount);
+        Ok(())
+    }
+
+    // Every balance change goes through here, so the history sees it.
+    fn update(&mut self, wallet: &Wallet, token: &TokenTicker, change: impl FnOnce(&mut Balance)) {
+        let balance = self
+            .balances
+            .entry(wallet.clone())
+            .or_default()
+            .entry(token.clone())
+            .or_default();
+        change(balance);
+        let balance = *balance;
+        if let Some(history) = &mut self.history {
+            history.record(wallet, token, balance, &self.balances);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_lock_and_unlock() {
+        let mut ledger = Ledger::new();
+        let wallet = Wallet::new(String::from("walletledger"));
+        ledger
+            .deposit(wallet.clone(), TokenTicker::ETH, 100)
+            .unwrap();
+
+        ledger.lock(&wallet, &TokenTicker::ETH, 60).unwrap();
+        assert_eq!(ledger.free_balance(&wallet, &TokenTicker::ETH), 40);
+        assert_eq!(ledger.locked_balance(&wallet, &TokenTicker::ETH), 60);
+
+        // locked funds cannot be withdrawn
+        assert!(ledger.withdraw(&wallet, &TokenTicker::ETH, 50).is_err());
+
+        ledger.unlock(&wallet, &TokenTicker::ETH, 60).unwrap();
+        assert_eq!(ledger.free_balance(&wallet, &TokenTicker::ETH), 100);
+        assert!(ledger.unlock(&wallet, &TokenTicker::ETH, 1).is_err());
+    }
+
+    #[test]
+    fn test_staked_funds_only_unstake() {
+        let mut ledger = Ledger::new();
+        let wallet = Wallet::new(String::from("walletstaker"));
+        ledger
+            .deposit(wallet.clone(), TokenTicker::ETH, 100)
+            .unwrap();
+        ledger.stake(&wallet, &TokenTicker::ETH, 60).unwrap();
+        ledger.lock(&wallet, &TokenTicker::ETH, 10).unwrap();
+        assert_eq!(ledger.locked_balance(&wallet, &TokenTicker::ETH), 70);
+
+        // other subsystems only reach their own part of the locked balance
+        assert_eq!(
+            ledger.unlock(&wallet, &TokenTicker::ETH, 11),
+            Err(LedgerError::InsufficientLocked {
+                token: TokenTicker::ETH,
+                available: 10,
+                requested: 11,
+            })
+        );
+        assert!(ledger.debit_locked(&wallet, &TokenTicker::ETH, 11).is_err());
+        ledger.unlock(&wallet, &TokenTicker::ETH, 10).unwrap();
+
+        assert!(ledger.unstake(&wallet, &TokenTicker::ETH, 61).is_err());
+        ledger.unstake(&wallet, &TokenTicker::ETH, 60).unwrap();
+        assert_eq!(ledger.free_balance(&wallet, &TokenTicker::ETH), 100);
+        assert_eq!(ledger.staked_balance(&wallet, &TokenTicker::ETH), 0);
+    }
+
+    #[test]
+    fn test_credit_deposit_is_idempotent() {
+        let mut ledger = Ledger::new();
+        let wallet = Wallet::new(String::from("walletbridge"));
+
+        let credit = |ledger: &mut Ledger, amount: u64| {
+            ledger.credit_deposit(wallet.clone(), TokenTicker::ETH, amount, "0xabc:1")
+        };
+        assert_eq!(credit(&mut ledger, 5), Ok(DepositOutcome::Credited));
+        assert_eq!(credit(&mut ledger, 5), Ok(DepositOutcome::AlreadyCredited));
+        assert_eq!(ledger.free_balance(&wallet, &TokenTicker::ETH), 5);
+
+        // a retry must carry the same details
+        assert_eq!(
+            credit(&mut ledger, 6),
+            Err(DepositError::ConflictingReference {
+                external_ref: String::from("0xabc:1")
+            })
+        );
+        assert!(ledger.is_deposit_credited("0xabc:1"));
+        assert!(!ledger.is_deposit_credited("0xabc:2"));
+
+        // a deposit the supply cannot hold is refused, not wrapped
+        let other = Wallet::new(String::from("walletwhale"));
+        assert_eq!(
+            ledger.credit_deposit(other.clone(), TokenTicker::ETH, u64::MAX, "0xabc:2"),
+            Err(DepositError::SupplyOverflow {
+                token: TokenTicker::ETH,
+                supply: 5,
+                amount: u64::MAX,
+            })
+        );
+        assert_eq!(ledger.free_balance(&other, &TokenTicker::ETH), 0);
+        assert!(!ledger.is_deposit_credited("0xabc:2"));
+    }
+}
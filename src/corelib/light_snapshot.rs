@@ -0,0 +1,626 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::amm::AMMPool;
+use super::audit::AuditAction;
+use super::clock::Clock;
+use super::engine::TradeEngine;
+use super::order::{OrderMetadata, Wallet};
+use super::token::Pair;
+use super::wal::{encode_snapshot, encode_ticker, restore_snapshot, Reader, WalError};
+
+const LIGHT_MAGIC: &[u8; 4] = b"TLIT";
+// 1: books, balances and pools.
+// 2: wrapped LP supplies, book configuration and order metadata and
+//    priority fees.
+// 3: mint authorities and supply caps.
+const LIGHT_VERSION: u16 = 3;
+const PAIR_POOL: u8 = 0;
+const TIER_POOL: u8 = 1;
+
+// Lighter alternative to the WAL: the open orders, balances and AMM pools
+// as of one moment, written to `path` at most every `every_millis`.
+// Recovering from it restores books, balances and pools but not the trades,
+// events or anything else that happened since the last save. Mint
+// authorities and supply caps are kept. Escrows, withdrawals, stakes and RFQ
+// quotes are not, so the funds they locked come back free.
+pub struct LightSnapshots {
+    path: PathBuf,
+    every_millis: u64,
+    last_saved: Option<u64>,
+}
+
+impl LightSnapshots {
+    pub fn new(path: impl AsRef<Path>, every_millis: u64) -> LightSnapshots {
+        LightSnapshots {
+            path: path.as_ref().to_path_buf(),
+            every_millis,
+            last_saved: None,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Save if the interval has passed since the last save. Returns whether
+    // it saved.
+    pub fn maybe_save(&mut self, engine: &TradeEngine) -> Result<bool, WalError> {
+        let now = engine.now();
+        if self
+            .last_saved
+            .is_some_and(|last| now.saturating_sub(last) < self.every_millis)
+        {
+            return Ok(false);
+        }
+        engine.save_light(&self.path)?;
+        self.last_saved = Some(now);
+        Ok(true)
+    }
+}
+
+impl TradeEngine {
+    // Write the books, configuration, balances and pools to `path`.
+    pub fn save_light(&self, path: impl AsRef<Path>) -> Result<(), WalError> {
+        let path = path.as_ref();
+        let partial = path.with_extension("tmp");
+        fs::write(&partial, encode_light(self))?;
+        fs::rename(&partial, path)?;
+        Ok(())
+    }
+
+    // Engine holding the state of a light snapshot, running on `clock`.
+    // Trade history, events and audit records are not restored. Locked
+    // balances are released to free, each with a `LockReleasedOnRecovery`
+    // audit record.
+    pub fn recover_light(
+        path: impl AsRef<Path>,
+        clock: Box<dyn Clock>,
+    ) -> Result<TradeEngine, WalError> {
+        let path = path.as_ref();
+        let mut engine = TradeEngine::with_clock(clock);
+        restore_light(&mut engine, &fs::read(path)?).ok_or(WalError::Corrupt {
+            file: path.to_path_buf(),
+        })?;
+        Ok(engine)
+    }
+}
+
+fn encode_light(engine: &TradeEngine) -> Vec<u8> {
+    let mut buffer = LIGHT_MAGIC.to_vec();
+    buffer.extend_from_slice(&LIGHT_VERSION.to_le_bytes());
+    let books = encode_snapshot(0, engine);
+    buffer.extend_from_slice(&(books.len() as u32).to_le_bytes());
+    buffer.extend_from_slice(&books);
+
+    let mut balances: Vec<_> = engine
+        .ledger
+        .wallets()
+        .flat_map(|wallet| {
+            engine
+                .ledger
+                .holdings(wallet)
+                .into_iter()
+                .map(move |(token, balance)| (wallet, token, balance))
+        })
+        .collect();
+    balances.sort_by(|left, right| (&left.0.address, &left.1).cmp(&(&right.0.address, &right.1)));
+    buffer.extend_from_slice(&(balances.len() as u32).to_le_bytes());
+    for (wallet, token, balance) in balances {
+        encode_string(&mut buffer, &wallet.address);
+        encode_ticker(&mut buffer, &token);
+        buffer.extend_from_slice(&balance.free.to_le_bytes());
+        buffer.extend_from_slice(&balance.locked.to_le_bytes());
+    }
+
+    let mut pools: Vec<_> = engine
+        .amm_pools
+        .iter()
+        .map(|(pair, pool)| (pair, None, pool))
+        .chain(
+            engine
+                .tier_pools
+                .iter()
+                .map(|((pair, tier), pool)| (pair, Some(*tier), pool)),
+        )
+        .collect();
+    pools.sort_by_key(|(pair, tier, _)| (*pair, *tier));
+    buffer.extend_from_slice(&(pools.len() as u32).to_le_bytes());
+    for (pair, tier, pool) in pools {
+        match tier {
+            None => buffer.push(PAIR_POOL),
+            Some(tier) => {
+                buffer.push(TIER_POOL);
+                buffer.extend_from_slice(&tier.to_le_bytes());
+            }
+        }
+        encode_pair(&mut buffer, pair);
+        encode_pool(&mut buffer, pool);
+    }
+
+    // what the shared book encoding leaves out
+    let mut books: Vec<_> = engine.order_books.iter().collect();
+    books.sort_by_key(|(ticker, _)| *ticker);
+    buffer.extend_from_slice(&(books.len() as u32).to_le_bytes());
+    for (ticker, orderbook) in books {
+        encode_ticker(&mut buffer, ticker);
+        encode_optional(&mut buffer, orderbook.tick_size().map(f64::to_le_bytes));
+        encode_optional(&mut buffer, orderbook.lot_size().map(u32::to_le_bytes));
+        encode_optional(
+            &mut buffer,
+            orderbook
+                .max_levels()
+                .map(|max_levels| (max_levels as u64).to_le_bytes()),
+        );
+        let orders: Vec<_> = orderbook
+            .resting_orders()
+            .into_iter()
+            .map(|(_, order)| order)
+            .filter(|order| order.priority_fee > 0 || order.metadata.is_some())
+            .collect();
+        buffer.extend_from_slice(&(orders.len() as u32).to_le_bytes());
+        for order in orders {
+            buffer.extend_from_slice(&order.id.to_le_bytes());
+            buffer.extend_from_slice(&order.priority_fee.to_le_bytes());
+            match &order.metadata {
+                None => buffer.push(0),
+                Some(metadata) => {
+                    buffer.push(1);
+                    buffer.extend_from_slice(&(metadata.len() as u32).to_le_bytes());
+                    for (key, value) in metadata.iter() {
+                        encode_string(&mut buffer, key);
+                        encode_string(&mut buffer, value);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut authorities: Vec<_> = engine.ledger.mint_authorities().collect();
+    authorities.sort_by(|left, right| (left.0, &left.1.address).cmp(&(right.0, &right.1.address)));
+    buffer.extend_from_slice(&(authorities.len() as u32).to_le_bytes());
+    for (token, authority) in authorities {
+        encode_ticker(&mut buffer, token);
+        encode_string(&mut buffer, &authority.address);
+    }
+    let mut caps: Vec<_> = engine.ledger.supply_caps().collect();
+    caps.sort();
+    buffer.extend_from_slice(&(caps.len() as u32).to_le_bytes());
+    for (token, cap) in caps {
+        encode_ticker(&mut buffer, token);
+        buffer.extend_from_slice(&cap.to_le_bytes());
+    }
+    buffer
+}
+
+fn encode_optional<const N: usize>(buffer: &mut Vec<u8>, value: Option<[u8; N]>) {
+    match value {
+        None => buffer.push(0),
+        Some(bytes) => {
+            buffer.push(1);
+            buffer.extend_from_slice(&bytes);
+        }
+    }
+}
+
+fn encode_pool(buffer: &mut Vec<u8>, pool: &AMMPool) {
+    buffer.extend_from_slice(&pool.fee_bps.to_le_bytes());
+    let mut reserves: Vec<_> = pool.reserves().collect();
+    reserves.sort();
+    buffer.extend_from_slice(&(reserves.len() as u32).to_le_bytes());
+    for (token, reserve) in reserves {
+        encode_ticker(buffer, token);
+        buffer.extend_from_slice(&reserve.to_le_bytes());
+    }
+    let mut supplies: Vec<_> = pool.lp_supplies().collect();
+    supplies.sort();
+    buffer.extend_from_slice(&(supplies.len() as u32).to_le_bytes());
+    for (pair, total) in supplies {
+        encode_pair(buffer, pair);
+        buffer.extend_from_slice(&total.to_le_bytes());
+    }
+    let mut positions: Vec<_> = pool.lp_positions().collect();
+    positions.sort_by(|left, right| (&left.0.address, left.1).cmp(&(&right.0.address, right.1)));
+    buffer.extend_from_slice(&(positions.len() as u32).to_le_bytes());
+    for (wallet, pair, amount) in positions {
+        encode_string(buffer, &wallet.address);
+        encode_pair(buffer, pair);
+        buffer.extend_from_slice(&amount.to_le_bytes());
+    }
+    let mut wrapped: Vec<_> = pool.wrapped_lp_supplies().collect();
+    wrapped.sort();
+    buffer.extend_from_slice(&(wrapped.len() as u32).to_le_bytes());
+    for (pair, amount) in wrapped {
+        encode_pair(buffer, pair);
+        buffer.extend_from_slice(&amount.to_le_bytes());
+    }
+}
+
+fn encode_pair(buffer: &mut Vec<u8>, pair: &Pair) {
+    encode_ticker(buffer, &pair.ticker_a);
+    encode_ticker(buffer, &pair.ticker_b);
+}
+
+fn encode_string(buffer: &mut Vec<u8>, value: &str) {
+    buffer.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    buffer.extend_from_slice(value.as_bytes());
+}
+
+// Load a light snapshot into an empty engine.
+fn restore_light(engine: &mut TradeEngine, bytes: &[u8]) -> Option<()> {
+    let mut reader = Reader::new(bytes);
+    if reader.take(LIGHT_MAGIC.len())? != LIGHT_MAGIC {
+        return None;
+    }
+    let version = u16::from_le_bytes(reader.take(2)?.try_into().ok()?);
+    if version == 0 || version > LIGHT_VERSION {
+        return None;
+    }
+    let length = reader.u32()? as usize;
+    restore_snapshot(engine, reader.take(length)?)?;
+
+    for _ in 0..reader.u32()? {
+        let wallet = read_wallet(&mut reader)?;
+        let token = reader.ticker()?;
+        let free = reader.u64()?;
+        let locked = reader.u64()?;
+        engine
+            .ledger
+            .deposit(wallet.clone(), token.clone(), free.checked_add(locked)?)
+            .ok()?;
+        // the escrows, withdrawals, stakes and RFQ quotes holding locked
+        // funds are not saved, so nothing would ever release them
+        if locked > 0 {
+            let timestamp = engine.now();
+            engine.audit_log.record(
+                timestamp,
+                AuditAction::LockReleasedOnRecovery {
+                    wallet,
+                    token,
+                    amount: locked,
+                },
+            );
+        }
+    }
+
+    for _ in 0..reader.u32()? {
+        let tier = match reader.u8()? {
+            PAIR_POOL => None,
+            TIER_POOL => Some(reader.u64()?),
+            _ => return None,
+        };
+        let pair = read_pair(&mut reader)?;
+        let pool = read_pool(&mut reader, version)?;
+        match tier {
+            None => engine.amm_pools.insert(pair, pool),
+            Some(tier) => engine.tier_pools.insert((pair, tier), pool),
+        };
+    }
+    if version >= 2 {
+        for _ in 0..reader.u32()? {
+            restore_book_details(engine, &mut reader)?;
+        }
+    }
+    if version >= 3 {
+        for _ in 0..reader.u32()? {
+            let token = reader.ticker()?;
+            let authority = read_wallet(&mut reader)?;
+            engine.ledger.grant_mint_authority(token, authority);
+        }
+        for _ in 0..reader.u32()? {
+            let token = reader.ticker()?;
+            let cap = reader.u64()?;
+            engine.ledger.set_supply_cap(token, Some(cap));
+        }
+    }
+    Some(())
+}
+
+fn restore_book_details(engine: &mut TradeEngine, reader: &mut Reader) -> Option<()> {
+    let ticker = reader.ticker()?;
+    let tick_size = read_optional(reader, Reader::f64)?;
+    let lot_size = read_optional(reader, Reader::u32)?;
+    let max_levels = read_optional(reader, Reader::u64)?;
+    let orderbook = engine.order_books.get_mut(&ticker)?;
+    orderbook.restore_configuration(
+        tick_size,
+        lot_size,
+        max_levels.map(usize::try_from).transpose().ok()?,
+    );
+    for _ in 0..reader.u32()? {
+        let order_id = reader.u64()?;
+        let priority_fee = reader.u64()?;
+        if priority_fee > 0 && !orderbook.restore_priority_fee(order_id, priority_fee) {
+            return None;
+        }
+        let metadata = read_optional(reader, |reader| {
+            let mut tags = Vec::new();
+            for _ in 0..reader.u32()? {
+                tags.push((read_string(reader)?, read_string(reader)?));
+            }
+            Some(OrderMetadata::from(tags))
+        })?;
+        if let Some(metadata) = metadata {
+            if !orderbook.restore_metadata(order_id, metadata) {
+                return None;
+            }
+        }
+    }
+    Some(())
+}
+
+fn read_optional<'a, T>(
+    reader: &mut Reader<'a>,
+    read: impl FnOnce(&mut Reader<'a>) -> Option<T>,
+) -> Option<Option<T>> {
+    match reader.u8()? {
+        0 => Some(None),
+        1 => Some(Some(read(reader)?)),
+        _ => None,
+    }
+}
+
+fn read_pool(reader: &mut Reader, version: u16) -> Option<AMMPool> {
+    let fee_bps = reader.u64()?;
+    let mut reserves = Vec::new();
+    for _ in 0..reader.u32()? {
+        reserves.push((reader.ticker()?, reader.u64()?));
+    }
+    let mut supplies = Vec::new();
+    for _ in 0..reader.u32()? {
+        supplies.push((read_pair(reader)?, reader.u64()?));
+    }
+    let mut positions = Vec::new();
+    for _ in 0..reader.u32()? {
+        positions.push((read_wallet(reader)?, read_pair(reader)?, reader.u64()?));
+    }
+    let mut wrapped = Vec::new();
+    let wrapped_pairs = if version >= 2 { reader.u32()? } else { 0 };
+    for _ in 0..wrapped_pairs {
+        wrapped.push((read_pair(reader)?, reader.u64()?));
+    }
+    Some(AMMPool::restore(
+        fee_bps, reserves, supplies, positions, wrapped,
+    ))
+}
+
+fn read_pair(reader: &mut Reader) -> Option<Pair> {
+    Some(Pair::new(reader.ticker()?, reader.ticker()?))
+}
+
+fn read_wallet(reader: &mut Reader) -> Option<Wallet> {
+    Some(Wallet::new(read_string(reader)?))
+}
+
+fn read_string(reader: &mut Reader) -> Option<String> {
+    let length = reader.u32()? as usize;
+    String::from_utf8(reader.take(length)?.to_vec()).ok()
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::book_state::assert_books_equal;
+    use crate::corelib::clock::SimulatedClock;
+    use crate::corelib::ledger::Balance;
+    use crate::corelib::order::{BuyOrSell, TimeInForce};
+    use crate::corelib::token::TokenTicker;
+
+    #[test]
+    fn test_light_recovery_restores_books_balances_and_pools() {
+        let path = std::env::temp_dir().join(format!("light-{}.snap", std::process::id()));
+        let clock = SimulatedClock::new(1_000);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        let alice = Wallet::new(String::from("lightalice"));
+        let bob = Wallet::new(String::from("lightbob"));
+        let pair = Pair::new(TokenTicker::ETH, TokenTicker::USDT);
+        engine.list_new_token(TokenTicker::ETH);
+        engine
+            .ledger
+            .deposit(alice.clone(), TokenTicker::USDT, 9_000)
+            .unwrap();
+        engine
+            .ledger
+            .deposit(bob.clone(), TokenTicker::ETH, 40)
+            .unwrap();
+        engine.ledger.lock(&bob, &TokenTicker::ETH, 15).unwrap();
+        engine
+            .submit_wallet_order(
+                &alice,
+                &TokenTicker::ETH,
+                BuyOrSell::Buy,
+                2_900.0,
+                2,
+                TimeInForce::GoodTillCancel,
+            )
+            .unwrap();
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Sell, 3_100.0, 4)
+            .unwrap();
+        let pool = engine.amm_pools.entry(pair.clone()).or_default();
+        pool.fee_bps = 30;
+        pool.deposit(&alice, &pair, 10, 30_000, u64::MAX, 0);
+        engine.wrap_lp(&alice, &pair, 100, u64::MAX).unwrap();
+
+        let mut snapshots = LightSnapshots::new(&path, 5_000);
+        assert_eq!(snapshots.maybe_save(&engine), Ok(true));
+        clock.advance(1_000);
+        assert_eq!(snapshots.maybe_save(&engine), Ok(false));
+
+        let recovered = TradeEngine::recover_light(&path, Box::new(clock.clone())).unwrap();
+        assert_books_equal(
+            &recovered.order_books[&TokenTicker::ETH],
+            &engine.order_books[&TokenTicker::ETH],
+        );
+        let mut holdings = engine.ledger.holdings(&alice);
+        let mut restored = recovered.ledger.holdings(&alice);
+        holdings.sort_by(|left, right| left.0.cmp(&right.0));
+        restored.sort_by(|left, right| left.0.cmp(&right.0));
+        assert_eq!(restored, holdings);
+        // nothing restored owns bob's lock, so it comes back free
+        assert_eq!(
+            recovered.ledger.balance(&bob, &TokenTicker::ETH),
+            Balance {
+                free: 40,
+                locked: 0
+            }
+        );
+        let releases: Vec<_> = recovered
+            .audit_log
+            .records()
+            .iter()
+            .map(|record| record.action.clone())
+            .collect();
+        assert_eq!(
+            releases,
+            vec![AuditAction::LockReleasedOnRecovery {
+                wallet: bob.clone(),
+                token: TokenTicker::ETH,
+                amount: 15
+            }]
+        );
+        let (pool, restored) = (&engine.amm_pools[&pair], &recovered.amm_pools[&pair]);
+        assert_eq!(restored.fee_bps, 30);
+        assert_eq!(restored.reserve(&TokenTicker::USDT), Some(30_000));
+        assert_eq!(
+            restored.lp_balance(&alice, &pair),
+            pool.lp_balance(&alice, &pair)
+        );
+        assert_eq!(restored.total_lp_tokens(&pair), pool.total_lp_tokens(&pair));
+        assert_eq!(restored.wrapped_lp(&pair), 100);
+
+        fs::write(&path, b"TWAL").unwrap();
+        assert_eq!(
+            TradeEngine::recover_light(&path, Box::new(clock)).err(),
+            Some(WalError::Corrupt { file: path.clone() })
+        );
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_light_recovery_keeps_book_configuration_and_metadata() {
+        use crate::corelib::engine::OrderError;
+        use crate::corelib::market_order::PriceProtection;
+        use crate::corelib::order::OrderMetadata;
+        use crate::corelib::orderbook::{BookRejection, OrderBook};
+
+        let path = std::env::temp_dir().join(format!("light-config-{}.snap", std::process::id()));
+        let clock = SimulatedClock::new(1_000);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        let alice = Wallet::new(String::from("configalice"));
+        let orderbook = OrderBook::builder()
+            .tick_size(0.5)
+            .lot_size(2)
+            .max_levels(3)
+            .seed_order(BuyOrSell::Sell, 100.0, 2, 0)
+            .seed_order(BuyOrSell::Sell, 100.0, 2, 0)
+            .build()
+            .unwrap();
+        engine.list_new_token_with_book(TokenTicker::ETH, orderbook);
+        let metadata = OrderMetadata::from(vec![(String::from("desk"), String::from("a"))]);
+        let tagged = engine
+            .submit_tagged_order(
+                Some(&alice),
+                &TokenTicker::ETH,
+                BuyOrSell::Sell,
+                100.5,
+                4,
+                TimeInForce::GoodTillCancel,
+                metadata.clone(),
+            )
+            .unwrap();
+        let book = engine.order_books.get_mut(&TokenTicker::ETH).unwrap();
+        assert!(book.set_priority_fee(2, 5, 0));
+        let sequence = book.sequence();
+        engine.save_light(&path).unwrap();
+
+        let mut recovered = TradeEngine::recover_light(&path, Box::new(clock.clone())).unwrap();
+        let book = &recovered.order_books[&TokenTicker::ETH];
+        assert_eq!(
+            (book.tick_size(), book.lot_size(), book.max_levels()),
+            (Some(0.5), Some(2), Some(3))
+        );
+        assert_eq!(book.sequence(), sequence);
+        let queue: Vec<_> = book
+            .level(BuyOrSell::Sell, 100.0)
+            .iter()
+            .map(|order| (order.id, order.priority_fee))
+            .collect();
+        assert_eq!(queue, vec![(2, 5), (1, 0)]);
+        assert_eq!(book.find_order(tagged).unwrap().1.metadata, Some(metadata));
+        assert_eq!(
+            recovered.submit_order(&TokenTicker::ETH, BuyOrSell::Buy, 99.3, 2),
+            Err(OrderError::Book(BookRejection::OffTick {
+                price: 99.3,
+                tick_size: 0.5
+            }))
+        );
+        let execution = recovered
+            .submit_market_order(
+                None,
+                &TokenTicker::ETH,
+                BuyOrSell::Buy,
+                2,
+                Some(PriceProtection::Ticks(1)),
+            )
+            .unwrap();
+        assert_eq!(execution.protection_price, Some(100.5));
+
+        // a balance whose free and locked parts overflow together
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        engine
+            .ledger
+            .deposit(alice.clone(), TokenTicker::USDT, 10)
+            .unwrap();
+        engine.ledger.lock(&alice, &TokenTicker::USDT, 5).unwrap();
+        engine.save_light(&path).unwrap();
+        let mut bytes = fs::read(&path).unwrap();
+        let balance = [5u64.to_le_bytes(), 5u64.to_le_bytes()].concat();
+        let at = bytes
+            .windows(balance.len())
+            .position(|window| window == balance)
+            .unwrap();
+        bytes[at..at + 16].fill(0xff);
+        fs::write(&path, bytes).unwrap();
+        assert_eq!(
+            TradeEngine::recover_light(&path, Box::new(clock)).err(),
+            Some(WalError::Corrupt { file: path.clone() })
+        );
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_light_recovery_keeps_mint_authorities_and_supply_caps() {
+        use crate::corelib::ledger::SupplyError;
+
+        let path = std::env::temp_dir().join(format!("light-supply-{}.snap", std::process::id()));
+        let clock = SimulatedClock::new(1_000);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        let bridge = Wallet::new(String::from("lightbridge"));
+        let alice = Wallet::new(String::from("lightsupplyalice"));
+        engine.grant_mint_authority(&TokenTicker::BTC, &bridge);
+        engine.set_supply_cap(&TokenTicker::BTC, Some(100));
+        engine.mint(&TokenTicker::BTC, &alice, 60, &bridge).unwrap();
+        engine.save_light(&path).unwrap();
+
+        let mut recovered = TradeEngine::recover_light(&path, Box::new(clock)).unwrap();
+        let _ = fs::remove_file(path);
+        assert!(recovered
+            .ledger
+            .is_mint_authority(&TokenTicker::BTC, &bridge));
+        assert_eq!(recovered.ledger.supply_cap(&TokenTicker::BTC), Some(100));
+        assert_eq!(
+            recovered.mint(&TokenTicker::BTC, &alice, 41, &bridge),
+            Err(SupplyError::SupplyCapExceeded {
+                token: TokenTicker::BTC,
+                supply: 60,
+                amount: 41,
+                cap: 100
+            })
+        );
+        assert!(recovered
+            .mint(&TokenTicker::BTC, &alice, 40, &bridge)
+            .is_ok());
+    }
+}
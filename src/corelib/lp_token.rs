@@ -0,0 +1,193 @@
+use super::engine::{DeadlineExpired, TradeEngine};
+use super::ledger::LedgerError;
+use super::order::Wallet;
+use super::token::{Pair, TokenTicker};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LpError {
+    UnknownPool,
+    InsufficientLp { held: u64, requested: u64 },
+    DeadlineExpired(DeadlineExpired),
+    Ledger(LedgerError),
+}
+
+impl From<LedgerError> for LpError {
+    fn from(err: LedgerError) -> Self {
+        LpError::Ledger(err)
+    }
+}
+
+impl From<DeadlineExpired> for LpError {
+    fn from(err: DeadlineExpired) -> Self {
+        LpError::DeadlineExpired(err)
+    }
+}
+
+// LP positions of primary pools can be moved into the ledger as
+// `TokenTicker::Lp(pair)`, where they transfer between wallets and trade on
+// an order book like any other token. The pool counts them as wrapped until
+// they are moved back for redemption; emissions pay whoever holds them in
+// the ledger.
+impl TradeEngine {
+    pub fn wrap_lp(
+        &mut self,
+        wallet: &Wallet,
+        pair: &Pair,
+        amount: u64,
+        deadline: u64,
+    ) -> Result<(), LpError> {
+        self.check_deadline(deadline)?;
+        let pool = self.amm_pools.get_mut(pair).ok_or(LpError::UnknownPool)?;
+        if !pool.wrap_lp(wallet, pair, amount) {
+            return Err(LpError::InsufficientLp {
+                held: pool.lp_balance(wallet, pair),
+                requested: amount,
+            });
+        }
+        self.ledger
+            .credit(wallet, &TokenTicker::lp(pair.clone()), amount);
+        Ok(())
+    }
+
+    // Return ledger LP tokens to the pool so the wallet can withdraw
+    // liquidity with them.
+    pub fn unwrap_lp(
+        &mut self,
+        wallet: &Wallet,
+        pair: &Pair,
+        amount: u64,
+        deadline: u64,
+    ) -> Result<(), LpError> {
+        self.check_deadline(deadline)?;
+        let pool = self.amm_pools.get_mut(pair).ok_or(LpError::UnknownPool)?;
+        self.ledger
+            .debit_free(wallet, &TokenTicker::lp(pair.clone()), amount)?;
+        pool.unwrap_lp(wallet, pair, amount);
+        Ok(())
+    }
+
+    // Open an order book for the pool's LP token.
+    pub fn list_lp_token(&mut self, pair: &Pair) -> Result<TokenTicker, LpError> {
+        if !self.amm_pools.contains_key(pair) {
+            return Err(LpError::UnknownPool);
+        }
+        let ticker = TokenTicker::lp(pair.clone());
+        self.list_new_token(ticker.clone());
+        Ok(ticker)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::order::BuyOrSell;
+
+    #[test]
+    fn test_lp_tokens_move_through_ledger() {
+        let mut engine = TradeEngine::new();
+        let alice = Wallet::new(String::from("lpalice"));
+        let bob = Wallet::new(String::from("lpbob"));
+        let pair = Pair::new(TokenTicker::ETH, TokenTicker::USDT);
+        let minted = engine.amm_pools.entry(pair.clone()).or_default().deposit(
+            &alice,
+            &pair,
+            1_000,
+            3_000,
+            u64::MAX,
+            0,
+        );
+        let lp = TokenTicker::lp(pair.clone());
+
+        assert_eq!(
+            engine.wrap_lp(&alice, &pair, minted + 1, u64::MAX),
+            Err(LpError::InsufficientLp {
+                held: 3_000,
+                requested: 3_001,
+            })
+        );
+        engine.wrap_lp(&alice, &pair, 100, u64::MAX).unwrap();
+        engine.ledger.transfer(&alice, &bob, &lp, 40).unwrap();
+        engine.unwrap_lp(&bob, &pair, 40, u64::MAX).unwrap();
+        assert!(engine.unwrap_lp(&bob, &pair, 1, u64::MAX).is_err());
+
+        let now = engine.now();
+        assert!(matches!(
+            engine.wrap_lp(&alice, &pair, 100, now - 1),
+            Err(LpError::DeadlineExpired(_))
+        ));
+        assert!(matches!(
+            engine.unwrap_lp(&alice, &pair, 10, now - 1),
+            Err(LpError::DeadlineExpired(_))
+        ));
+
+        let pool = engine.amm_pools.get_mut(&pair).unwrap();
+        assert_eq!(pool.lp_balance(&alice, &pair), 2_900);
+        assert_eq!(pool.withdraw(&bob, &pair, 40, u64::MAX, 0), Some((10, 30)));
+        assert_eq!(engine.ledger.free_balance(&alice, &lp), 60);
+
+        let ticker = engine.list_lp_token(&pair).unwrap();
+        assert!(engine
+            .submit_order(&ticker, BuyOrSell::Sell, 0.5, 60)
+            .is_ok());
+        assert_eq!(
+            engine.list_lp_token(&Pair::new(TokenTicker::BTC, TokenTicker::USDT)),
+            Err(LpError::UnknownPool)
+        );
+    }
+
+    #[test]
+    fn test_wrapped_lp_earns_for_ledger_holders() {
+        use crate::corelib::clock::SimulatedClock;
+        use crate::corelib::emissions::EmissionCurve;
+
+        let clock = SimulatedClock::new(0);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        let alice = Wallet::new(String::from("wrapalice"));
+        let bob = Wallet::new(String::from("wrapbob"));
+        let treasury = Wallet::new(String::from("wraptreasury"));
+        let pair = Pair::new(TokenTicker::ETH, TokenTicker::USDT);
+        engine.amm_pools.entry(pair.clone()).or_default().deposit(
+            &alice,
+            &pair,
+            1_000,
+            3_000,
+            u64::MAX,
+            0,
+        );
+        engine.wrap_lp(&alice, &pair, 1_000, u64::MAX).unwrap();
+        engine
+            .ledger
+            .transfer(&alice, &bob, &TokenTicker::lp(pair.clone()), 1_000)
+            .unwrap();
+        assert_eq!(engine.amm_pools[&pair].wrapped_lp(&pair), 1_000);
+        assert_eq!(engine.amm_pools[&pair].lp_holders(&pair).len(), 1);
+
+        engine
+            .ledger
+            .deposit(treasury.clone(), TokenTicker::UNI, 1_000)
+            .unwrap();
+        engine
+            .emissions
+            .fund(&mut engine.ledger, &treasury, &TokenTicker::UNI, 1_000)
+            .unwrap();
+        engine.emissions.add_schedule(
+            pair.clone(),
+            TokenTicker::UNI,
+            EmissionCurve::Constant { per_second: 3.0 },
+            0,
+        );
+        clock.advance(100_000);
+        assert_eq!(engine.claim_rewards(&alice), vec![(TokenTicker::UNI, 200)]);
+        assert_eq!(engine.claim_rewards(&bob), vec![(TokenTicker::UNI, 100)]);
+
+        // nobody can pose as the pool's holder of wrapped tokens
+        let impostor = Wallet::new(String::from("lp-custody"));
+        assert!(engine.claim_rewards(&impostor).is_empty());
+        let pool = engine.amm_pools.get_mut(&pair).unwrap();
+        assert_eq!(pool.withdraw(&impostor, &pair, 1, u64::MAX, 0), None);
+        engine.unwrap_lp(&bob, &pair, 1_000, u64::MAX).unwrap();
+        assert_eq!(engine.amm_pools[&pair].lp_balance(&bob, &pair), 1_000);
+        assert_eq!(engine.amm_pools[&pair].wrapped_lp(&pair), 0);
+    }
+}
@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+
+use super::options::Greeks;
+use super::order::Wallet;
+use super::token::TokenTicker;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InstrumentKind {
+    Spot,
+    Perpetual,
+    // Dated future, cash-settled at expiry.
+    Future,
+}
+
+// Signed exposure to an underlying: positive quantity is long, negative is
+// short. Prices are in the quote token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Position {
+    pub wallet: Wallet,
+    pub underlying: TokenTicker,
+    pub kind: InstrumentKind,
+    pub quantity: i64,
+    pub entry_price: f64,
+    pub mark_price: f64,
+    // Quote token posted against the position.
+    pub collateral: u64,
+}
+
+impl Position {
+    pub fn notional(&self) -> f64 {
+        self.quantity.unsigned_abs() as f64 * self.mark_price
+    }
+
+    pub fn unrealized_pnl(&self) -> f64 {
+        self.quantity as f64 * (self.mark_price - self.entry_price)
+    }
+
+    // Notional per unit of equity; infinite once the equity is gone.
+    pub fn effective_leverage(&self) -> f64 {
+        let equity = self.collateral as f64 + self.unrealized_pnl();
+        if equity <= 0.0 {
+            return f64::INFINITY;
+        }
+        self.notional() / equity
+    }
+}
+
+// One row of the scenario matrix: relative price moves per underlying, with
+// `default_move` for underlyings not listed. Perpetuals and futures
+// additionally move by `perp_basis` to account for the basis to spot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scenario {
+    pub moves: HashMap<TokenTicker, f64>,
+    pub default_move: f64,
+    pub perp_basis: f64,
+}
+
+impl Scenario {
+    // The same move for every underlying.
+    pub fn uniform(price_move: f64) -> Scenario {
+        Scenario {
+            moves: HashMap::new(),
+            default_move: price_move,
+            perp_basis: 0.0,
+        }
+    }
+
+    pub fn underlying_move(&self, underlying: &TokenTicker) -> f64 {
+        self.moves
+            .get(underlying)
+            .copied()
+            .unwrap_or(self.default_move)
+    }
+
+    pub fn price_move(&self, position: &Position) -> f64 {
+        let price_move = self.underlying_move(&position.underlying);
+        match position.kind {
+            InstrumentKind::Spot => price_move,
+            InstrumentKind::Perpetual | InstrumentKind::Future => price_move + self.perp_basis,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PortfolioMargin {
+    // Margin charged for the portfolio as a whole.
+    pub requirement: u64,
+    // Sum of the margins of every position on its own.
+    pub standalone: u64,
+    // Index of the scenario with the largest loss, if any loses.
+    pub worst_scenario: Option<usize>,
+}
+
+// Margins a wallet's positions together: the requirement is the worst loss of
+// the whole portfolio across the scenario matrix, so a long spot hedged by a
+// short perpetual on the same underlying largely offsets. It never falls below
+// `minimum_rate` of the gross notional.
+pub struct MarginEngine {
+    pub scenarios: Vec<Scenario>,
+    pub minimum_rate: f64,
+    // Margin rate of a position held on its own.
+    pub standalone_rate: f64,
+}
+
+impl Default for MarginEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MarginEngine {
+    pub fn new() -> MarginEngine {
+        MarginEngine {
+            scenarios: [-0.15, -0.10, -0.05, 0.05, 0.10, 0.15]
+                .into_iter()
+                .map(Scenario::uniform)
+                .collect(),
+            minimum_rate: 0.01,
+            standalone_rate: 0.15,
+        }
+    }
+
+    pub fn position_margin(&self, position: &Position) -> u64 {
+        (position.notional() * self.standalone_rate).ceil() as u64
+    }
+
+    // Profit or loss of the positions if `scenario` happened.
+    pub fn scenario_pnl(&self, scenario: &Scenario, positions: &[&Position]) -> f64 {
+        positions
+            .iter()
+            .map(|position| {
+                position.quantity as f64 * position.mark_price * scenario.price_move(position)
+            })
+            .sum()
+    }
+
+    // Requirement for an options book on `underlying` from its net greeks:
+    // the worst delta-gamma loss over the scenario matrix plus the loss on a
+    // `vol_shock` (in vol points) move against the vega.
+    pub fn greeks_requirement(
+        &self,
+        underlying: &TokenTicker,
+        greeks: &Greeks,
+        underlying_price: f64,
+        vol_shock: f64,
+    ) -> u64 {
+        let worst_price_loss = self
+            .scenarios
+            .iter()
+            .map(|scenario| {
+                let change = underlying_price * scenario.underlying_move(underlying);
+                -(greeks.delta * change + 0.5 * greeks.gamma * change * change)
+            })
+            .fold(0.0, f64::max);
+        let vega_loss = greeks.vega.abs() * vol_shock * 100.0;
+        (worst_price_loss + vega_loss).ceil() as u64
+    }
+
+    pub fn portfolio_margin(&self, wallet: &Wallet, positions: &[Position]) -> PortfolioMargin {
+        let positions: Vec<&Position> = positions
+            .iter()
+            .filter(|position| &position.wallet == wallet)
+            .collect();
+        let standalone = positions
+            .iter()
+            .map(|position| self.position_margin(position))
+            .sum();
+
+        let mut worst_loss = 0.0;
+        let mut worst_scenario = None;
+        for (index, scenario) in self.scenarios.iter().enumerate() {
+            let loss = -self.scenario_pnl(scenario, &positions);
+            if loss > worst_loss {
+                worst_loss = loss;
+                worst_scenario = Some(index);
+            }
+        }
+        let gross: f64 = positions.iter().map(|position| position.notional()).sum();
+        let floor = gross * self.minimum_rate;
+        PortfolioMargin {
+            requirement: worst_loss.max(floor).ceil() as u64,
+            standalone,
+            worst_scenario,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    fn position(wallet: &Wallet, kind: InstrumentKind, quantity: i64, mark_price: f64) -> Position {
+        Position {
+            wallet: wallet.clone(),
+            underlying: TokenTicker::BTC,
+            kind,
+            quantity,
+            entry_price: mark_price,
+            mark_price,
+            collateral: 0,
+        }
+    }
+
+    #[test]
+    fn test_spot_hedged_by_short_perp_offsets() {
+        let engine = MarginEngine::new();
+        let wallet = Wallet::new(String::from("marginwallet"));
+        let other = Wallet::new(String::from("otherwallet"));
+        let positions = vec![
+            position(&wallet, InstrumentKind::Spot, 2, 50_000.0),
+            position(&wallet, InstrumentKind::Perpetual, -2, 50_000.0),
+            position(&other, InstrumentKind::Perpetual, 100, 50_000.0),
+        ];
+
+        let margin = engine.portfolio_margin(&wallet, &positions);
+        assert_eq!(margin.standalone, 30_000);
+        // fully hedged: only the floor on 200_000 gross notional remains
+        assert_eq!(margin.requirement, 2_000);
+        assert_eq!(margin.worst_scenario, None);
+
+        // an unhedged long loses most in the deepest down move
+        let margin = engine.portfolio_margin(&wallet, &positions[..1]);
+        assert_eq!(margin.requirement, 15_000);
+        assert_eq!(margin.worst_scenario, Some(0));
+    }
+
+    #[test]
+    fn test_custom_scenario_matrix_with_basis() {
+        let wallet = Wallet::new(String::from("basiswallet"));
+        let mut engine = MarginEngine::new();
+        engine.minimum_rate = 0.0;
+        let mut crash = Scenario::uniform(-0.2);
+        crash.moves.insert(TokenTicker::ETH, -0.3);
+        crash.perp_basis = -0.01;
+        engine.scenarios = vec![crash];
+
+        let mut eth = position(&wallet, InstrumentKind::Spot, 10, 3_000.0);
+        eth.underlying = TokenTicker::ETH;
+        let positions = vec![
+            eth,
+            position(&wallet, InstrumentKind::Perpetual, -1, 50_000.0),
+        ];
+        // ETH spot loses 9_000, the BTC short gains 10_500
+        let margin = engine.portfolio_margin(&wallet, &positions);
+        assert_eq!(margin.requirement, 0);
+        assert_eq!(margin.standalone, 4_500 + 7_500);
+    }
+}
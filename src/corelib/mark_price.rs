@@ -0,0 +1,153 @@
+use super::engine::TradeEngine;
+use super::margin::Position;
+use super::token::TokenTicker;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MarkPriceConfig {
+    // The mark never strays further than this from the index, relative.
+    pub clamp_band: f64,
+}
+
+impl Default for MarkPriceConfig {
+    fn default() -> Self {
+        MarkPriceConfig { clamp_band: 0.005 }
+    }
+}
+
+fn median(mut prices: Vec<f64>) -> f64 {
+    prices.sort_by(|a, b| a.total_cmp(b));
+    let mid = prices.len() / 2;
+    if prices.len().is_multiple_of(2) {
+        (prices[mid - 1] + prices[mid]) / 2.0
+    } else {
+        prices[mid]
+    }
+}
+
+impl TradeEngine {
+    pub fn set_mark_price_config(&mut self, ticker: TokenTicker, config: MarkPriceConfig) {
+        self.mark_price_configs.insert(ticker, config);
+    }
+
+    // Index plus the basis implied by the funding still to accrue before the
+    // next funding time; just the index for instruments without funding.
+    pub fn fair_basis_price(&self, ticker: &TokenTicker) -> Option<f64> {
+        let index = self.index.latest(ticker)?;
+        let Some(funding) = self.funding(ticker) else {
+            return Some(index);
+        };
+        let interval = self.perpetuals[ticker].config.interval_millis as f64;
+        let remaining = funding.next_funding_at.saturating_sub(self.now()) as f64 / interval;
+        Some(index * (1.0 + funding.projected_rate.unwrap_or(0.0) * remaining))
+    }
+
+    // Median of the last trade, the index and the fair basis price, clamped
+    // to the instrument's band around the index. None without an index.
+    pub fn mark_price(&self, ticker: &TokenTicker) -> Option<f64> {
+        let index = self.index.latest(ticker)?;
+        let mut prices = vec![index, self.fair_basis_price(ticker)?];
+        if let Some(trade) = self.trade_feed.last_trade(ticker) {
+            prices.push(trade.price);
+        }
+        let band = self
+            .mark_price_configs
+            .get(ticker)
+            .copied()
+            .unwrap_or_default()
+            .clamp_band;
+        Some(median(prices).clamp(index * (1.0 - band), index * (1.0 + band)))
+    }
+
+    // Revalue positions at the mark; positions without a mark keep theirs.
+    pub fn mark_positions(&self, positions: &mut [Position]) {
+        for position in positions.iter_mut() {
+            if let Some(mark) = self.mark_price(&position.underlying) {
+                position.mark_price = mark;
+            }
+        }
+    }
+
+    // Whether the position's equity at the mark has fallen below
+    // `maintenance_rate` of its notional.
+    pub fn liquidation_triggered(&self, position: &Position, maintenance_rate: f64) -> bool {
+        let mut position = position.clone();
+        if let Some(mark) = self.mark_price(&position.underlying) {
+            position.mark_price = mark;
+        }
+        let equity = position.collateral as f64 + position.unrealized_pnl();
+        equity < position.notional() * maintenance_rate
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::clock::SimulatedClock;
+    use crate::corelib::margin::InstrumentKind;
+    use crate::corelib::order::Wallet;
+    use crate::corelib::trade::{Trade, TradeKind};
+
+    fn trade_at(engine: &mut TradeEngine, price: f64) {
+        let timestamp = engine.now();
+        engine.trade_feed.publish(Trade {
+            id: 0,
+            ticker: TokenTicker::BTC,
+            price,
+            quantity: 1,
+            buyer: None,
+            seller: None,
+            buy_order_id: None,
+            sell_order_id: None,
+            timestamp,
+            kind: TradeKind::Lit,
+            buy_metadata: None,
+            sell_metadata: None,
+            settled_notional: None,
+        });
+    }
+
+    #[test]
+    fn test_mark_is_median_clamped_to_index() {
+        let mut engine = TradeEngine::new();
+        engine.list_new_token(TokenTicker::BTC);
+        assert_eq!(engine.mark_price(&TokenTicker::BTC), None);
+
+        engine.publish_index_price(TokenTicker::BTC, 100.0);
+        trade_at(&mut engine, 100.2);
+        // median of 100, 100 and 100.2
+        assert_eq!(engine.mark_price(&TokenTicker::BTC), Some(100.0));
+
+        engine.set_mark_price_config(TokenTicker::BTC, MarkPriceConfig { clamp_band: 0.01 });
+        engine.publish_index_price(TokenTicker::BTC, 90.0);
+        trade_at(&mut engine, 150.0);
+        assert_eq!(engine.mark_price(&TokenTicker::BTC), Some(90.0));
+    }
+
+    #[test]
+    fn test_liquidation_uses_mark_not_last_trade() {
+        let clock = SimulatedClock::new(0);
+        let mut engine = TradeEngine::with_clock(Box::new(clock));
+        engine.list_new_token(TokenTicker::BTC);
+        engine.publish_index_price(TokenTicker::BTC, 100.0);
+        let position = Position {
+            wallet: Wallet::new(String::from("markwallet")),
+            underlying: TokenTicker::BTC,
+            kind: InstrumentKind::Perpetual,
+            quantity: 10,
+            entry_price: 100.0,
+            mark_price: 100.0,
+            collateral: 60,
+        };
+
+        // a single print far below the index does not liquidate
+        trade_at(&mut engine, 80.0);
+        assert!(!engine.liquidation_triggered(&position, 0.05));
+        let mut positions = vec![position.clone()];
+        engine.mark_positions(&mut positions);
+        assert_eq!(positions[0].mark_price, 100.0);
+
+        engine.publish_index_price(TokenTicker::BTC, 95.0);
+        assert!(engine.liquidation_triggered(&position, 0.05));
+    }
+}
@@ -0,0 +1,236 @@
+use std::collections::BTreeMap;
+
+use super::depth::{DepthLevel, DepthSnapshot};
+use super::engine::TradeEngine;
+use super::events::BookEvent;
+use super::order::BuyOrSell;
+use super::token::TokenTicker;
+
+// Depth of a book as of event `sequence`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BookSnapshot {
+    pub sequence: u64,
+    pub depth: DepthSnapshot,
+}
+
+impl DepthSnapshot {
+    // Apply one L3 event to the aggregated levels.
+    pub fn apply(&mut self, event: &BookEvent) {
+        match *event {
+            BookEvent::OrderAdded {
+                side,
+                price,
+                quantity,
+                ..
+            } => self.adjust(side, price, quantity as u64, 0),
+            BookEvent::OrderFilled {
+                side,
+                price,
+                filled,
+                ..
+            } => self.adjust(side, price, 0, filled as u64),
+            BookEvent::OrderCancelled {
+                side,
+                price,
+                remaining,
+                ..
+            } => self.adjust(side, price, 0, remaining as u64),
+            BookEvent::OrderRescaled {
+                side,
+                old_price,
+                price,
+                old_quantity,
+                quantity,
+                ..
+            } => {
+                self.adjust(side, old_price, 0, old_quantity as u64);
+                self.adjust(side, price, quantity as u64, 0);
+            }
+            BookEvent::PriorityFeeSet { .. } => {}
+        }
+    }
+
+    fn adjust(&mut self, side: BuyOrSell, price: f64, added: u64, removed: u64) {
+        let levels = match side {
+            BuyOrSell::Buy => &mut self.bids,
+            BuyOrSell::Sell => &mut self.asks,
+        };
+        // bids are kept descending and asks ascending
+        let position = levels.iter().position(|level| match side {
+            BuyOrSell::Buy => level.price <= price,
+            BuyOrSell::Sell => level.price >= price,
+        });
+        let index = match position {
+            Some(index) if levels[index].price == price => index,
+            Some(index) => {
+                levels.insert(index, DepthLevel { price, quantity: 0 });
+                index
+            }
+            None => {
+                levels.push(DepthLevel { price, quantity: 0 });
+                levels.len() - 1
+            }
+        };
+        levels[index].quantity = (levels[index].quantity + added).saturating_sub(removed);
+        if levels[index].quantity == 0 {
+            levels.remove(index);
+        }
+    }
+}
+
+impl TradeEngine {
+    pub fn book_snapshot(&self, ticker: &TokenTicker) -> Option<BookSnapshot> {
+        let orderbook = self.order_books.get(ticker)?;
+        Some(BookSnapshot {
+            sequence: orderbook.sequence(),
+            depth: orderbook.depth_snapshot(),
+        })
+    }
+
+    // Deltas after `sequence`. None when the instrument is unknown or the
+    // deltas are no longer retained, in which case the consumer must start
+    // over from a snapshot.
+    pub fn book_deltas_since(
+        &self,
+        ticker: &TokenTicker,
+        sequence: u64,
+    ) -> Option<Vec<(u64, BookEvent)>> {
+        self.order_books.get(ticker)?.events_since(sequence)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncStatus {
+    // Waiting for a snapshot; deltas are buffered meanwhile.
+    AwaitingSnapshot,
+    Live { sequence: u64 },
+    // A delta was missed: request a new snapshot.
+    ResnapshotRequired { expected: u64, received: u64 },
+}
+
+// Consumer side of the feed. Deltas that arrive before the snapshot are
+// buffered; those after the snapshot sequence are replayed on top of it.
+// A hole in the sequence drops the book and asks for a new snapshot.
+#[derive(Debug, Clone, Default)]
+pub struct FeedSubscriber {
+    book: Option<BookSnapshot>,
+    buffered: BTreeMap<u64, BookEvent>,
+}
+
+impl FeedSubscriber {
+    pub fn new() -> FeedSubscriber {
+        FeedSubscriber::default()
+    }
+
+    pub fn depth(&self) -> Option<&DepthSnapshot> {
+        self.book.as_ref().map(|book| &book.depth)
+    }
+
+    pub fn status(&self) -> SyncStatus {
+        match &self.book {
+            Some(book) => SyncStatus::Live {
+                sequence: book.sequence,
+            },
+            None => SyncStatus::AwaitingSnapshot,
+        }
+    }
+
+    pub fn on_delta(&mut self, sequence: u64, event: BookEvent) -> SyncStatus {
+        let Some(book) = self.book.as_mut() else {
+            self.buffered.insert(sequence, event);
+            return SyncStatus::AwaitingSnapshot;
+        };
+        if sequence <= book.sequence {
+            // duplicate
+        } else if sequence == book.sequence + 1 {
+            book.depth.apply(&event);
+            book.sequence = sequence;
+        } else {
+            let expected = book.sequence + 1;
+            self.book = None;
+            self.buffered.clear();
+            self.buffered.insert(sequence, event);
+            return SyncStatus::ResnapshotRequired {
+                expected,
+                received: sequence,
+            };
+        }
+        self.status()
+    }
+
+    pub fn on_snapshot(&mut self, snapshot: BookSnapshot) -> SyncStatus {
+        let mut book = snapshot;
+        for (sequence, event) in self.buffered.range(book.sequence + 1..) {
+            if *sequence != book.sequence + 1 {
+                // the snapshot is older than what was buffered
+                return SyncStatus::ResnapshotRequired {
+                    expected: book.sequence + 1,
+                    received: *sequence,
+                };
+            }
+            book.depth.apply(event);
+            book.sequence = *sequence;
+        }
+        self.buffered.clear();
+        self.book = Some(book);
+        self.status()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_late_joiner_recovers_from_dropped_delta() {
+        let mut engine = TradeEngine::new();
+        engine.list_new_token(TokenTicker::ETH);
+        let submit = |engine: &mut TradeEngine, side, price, quantity| {
+            engine
+                .submit_order(&TokenTicker::ETH, side, price, quantity)
+                .unwrap()
+        };
+        submit(&mut engine, BuyOrSell::Buy, 99.0, 5);
+        submit(&mut engine, BuyOrSell::Sell, 101.0, 5);
+
+        // the subscriber joins: deltas stream in before the snapshot
+        let mut subscriber = FeedSubscriber::new();
+        submit(&mut engine, BuyOrSell::Buy, 100.0, 3);
+        let snapshot = engine.book_snapshot(&TokenTicker::ETH).unwrap();
+        submit(&mut engine, BuyOrSell::Sell, 100.0, 2);
+        engine.match_orders();
+        for (sequence, event) in engine.book_deltas_since(&TokenTicker::ETH, 2).unwrap() {
+            subscriber.on_delta(sequence, event);
+        }
+        assert_eq!(
+            subscriber.on_snapshot(snapshot),
+            SyncStatus::Live { sequence: 6 }
+        );
+        assert_eq!(
+            subscriber.depth(),
+            Some(&engine.order_books[&TokenTicker::ETH].depth_snapshot())
+        );
+
+        // a dropped delta is detected on the next one
+        submit(&mut engine, BuyOrSell::Buy, 98.0, 1);
+        submit(&mut engine, BuyOrSell::Buy, 97.0, 1);
+        let deltas = engine.book_deltas_since(&TokenTicker::ETH, 6).unwrap();
+        assert_eq!(
+            subscriber.on_delta(deltas[1].0, deltas[1].1.clone()),
+            SyncStatus::ResnapshotRequired {
+                expected: 7,
+                received: 8
+            }
+        );
+        let snapshot = engine.book_snapshot(&TokenTicker::ETH).unwrap();
+        assert_eq!(
+            subscriber.on_snapshot(snapshot),
+            SyncStatus::Live { sequence: 8 }
+        );
+        assert_eq!(
+            subscriber.depth(),
+            Some(&engine.order_books[&TokenTicker::ETH].depth_snapshot())
+        );
+    }
+}
@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+
+use super::depth::DepthLevel;
+use super::engine::TradeEngine;
+use super::market_data::BookSnapshot;
+use super::token::TokenTicker;
+use super::trade::Trade;
+
+// Market data as the engine last published it. Books that did not change
+// between publications share their snapshot with the previous view.
+#[derive(Debug, Clone, Default)]
+pub struct MarketDataView {
+    // Engine clock at publication.
+    pub timestamp: u64,
+    pub books: HashMap<TokenTicker, Arc<BookSnapshot>>,
+    pub last_trades: HashMap<TokenTicker, Arc<Trade>>,
+    // Highest trade id folded into `last_trades`.
+    last_trade_id: u64,
+}
+
+// Best bid and offer with the quantity resting at each.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bbo {
+    pub bid: Option<DepthLevel>,
+    pub ask: Option<DepthLevel>,
+}
+
+// Read-only market data for other threads. Queries read the latest
+// published view without taking a lock, and publishing swaps in a new view
+// without waiting for readers. Answers from separate calls may come from
+// different publications; take a `view` to query one consistently.
+#[derive(Debug, Clone)]
+pub struct MarketDataHandle {
+    view: Arc<ArcSwap<MarketDataView>>,
+}
+
+impl MarketDataHandle {
+    pub fn view(&self) -> Arc<MarketDataView> {
+        self.view.load_full()
+    }
+
+    pub fn depth(&self, ticker: &TokenTicker) -> Option<Arc<BookSnapshot>> {
+        self.view.load().books.get(ticker).cloned()
+    }
+
+    pub fn bbo(&self, ticker: &TokenTicker) -> Option<Bbo> {
+        let view = self.view.load();
+        let book = view.books.get(ticker)?;
+        Some(Bbo {
+            bid: book.depth.bids.first().copied(),
+            ask: book.depth.asks.first().copied(),
+        })
+    }
+
+    pub fn last_trade(&self, ticker: &TokenTicker) -> Option<Arc<Trade>> {
+        self.view.load().last_trades.get(ticker).cloned()
+    }
+}
+
+impl TradeEngine {
+    // Handle answering from the engine's published market data. The first
+    // call starts publishing; the view is refreshed after every matching
+    // step and by `publish_market_data`.
+    pub fn market_data_handle(&mut self) -> MarketDataHandle {
+        if self.market_data.is_none() {
+            self.market_data = Some(Arc::new(ArcSwap::from_pointee(MarketDataView::default())));
+            self.publish_market_data();
+        }
+        MarketDataHandle {
+            view: self.market_data.clone().unwrap(),
+        }
+    }
+
+    // Publish the current books and last trades to every handle. Nothing
+    // to do until a handle has been taken.
+    pub fn publish_market_data(&mut self) {
+        let Some(published) = self.market_data.as_ref() else {
+            return;
+        };
+        let previous = published.load();
+        let books = self
+            .order_books
+            .iter()
+            .map(|(ticker, orderbook)| {
+                let snapshot = match previous.books.get(ticker) {
+                    Some(snapshot) if snapshot.sequence == orderbook.sequence() => snapshot.clone(),
+                    _ => Arc::new(BookSnapshot {
+                        sequence: orderbook.sequence(),
+                        depth: orderbook.depth_snapshot(),
+                    }),
+                };
+                (ticker.clone(), snapshot)
+            })
+            .collect();
+        let mut last_trades = previous.last_trades.clone();
+        let mut last_trade_id = previous.last_trade_id;
+        for trade in self.trade_feed.trades_after(previous.last_trade_id) {
+            last_trade_id = trade.id;
+            last_trades.insert(trade.ticker.clone(), Arc::new(trade.clone()));
+        }
+        let view = MarketDataView {
+            timestamp: self.now(),
+            books,
+            last_trades,
+            last_trade_id,
+        };
+        published.store(Arc::new(view));
+    }
+}
+
+impl TradeEngine {
+    // Take a busted trade out of the published last trades, falling back to
+    // the instrument's previous trade. Call after the trade left the feed.
+    pub(crate) fn unpublish_trade(&mut self, trade: &Trade) {
+        let Some(published) = self.market_data.as_ref() else {
+            return;
+        };
+        let previous = published.load();
+        if previous
+            .last_trades
+            .get(&trade.ticker)
+            .is_none_or(|last| last.id != trade.id)
+        {
+            return;
+        }
+        let mut view = MarketDataView::clone(&previous);
+        match self
+            .trade_feed
+            .trades_for(&trade.ticker)
+            .filter(|earlier| earlier.id <= view.last_trade_id)
+            .last()
+        {
+            Some(earlier) => view
+                .last_trades
+                .insert(trade.ticker.clone(), Arc::new(earlier.clone())),
+            None => view.last_trades.remove(&trade.ticker),
+        };
+        published.store(Arc::new(view));
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::order::BuyOrSell;
+    use std::thread;
+
+    #[test]
+    fn test_readers_see_published_views() {
+        let mut engine = TradeEngine::new();
+        engine.list_new_token(TokenTicker::ETH);
+        let handle = engine.market_data_handle();
+        let empty = Bbo {
+            bid: None,
+            ask: None,
+        };
+        assert_eq!(handle.bbo(&TokenTicker::ETH), Some(empty));
+        assert_eq!(handle.bbo(&TokenTicker::BTC), None);
+
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Buy, 100.0, 5)
+            .unwrap();
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Sell, 99.0, 2)
+            .unwrap();
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Sell, 101.0, 4)
+            .unwrap();
+        // not published until the next step
+        assert_eq!(handle.bbo(&TokenTicker::ETH), Some(empty));
+        engine.match_orders();
+
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let handle = handle.clone();
+                thread::spawn(move || {
+                    let trade = handle.last_trade(&TokenTicker::ETH).unwrap();
+                    (handle.bbo(&TokenTicker::ETH).unwrap(), trade.price)
+                })
+            })
+            .collect();
+        for reader in readers {
+            let (bbo, last_price) = reader.join().unwrap();
+            assert_eq!(
+                bbo.bid,
+                Some(DepthLevel {
+                    price: 100.0,
+                    quantity: 3
+                })
+            );
+            assert_eq!(
+                bbo.ask,
+                Some(DepthLevel {
+                    price: 101.0,
+                    quantity: 4
+                })
+            );
+            assert_eq!(last_price, 99.0);
+        }
+
+        // an unchanged book keeps its snapshot across publications
+        let before = handle.depth(&TokenTicker::ETH).unwrap();
+        engine.publish_market_data();
+        assert!(Arc::ptr_eq(
+            &before,
+            &handle.depth(&TokenTicker::ETH).unwrap()
+        ));
+    }
+
+    #[test]
+    fn test_redenomination_refreshes_published_depth() {
+        let mut engine = TradeEngine::new();
+        engine.list_new_token(TokenTicker::BTC);
+        engine
+            .submit_order(&TokenTicker::BTC, BuyOrSell::Sell, 60_000.0, 2)
+            .unwrap();
+        let handle = engine.market_data_handle();
+        let before = engine.book_snapshot(&TokenTicker::BTC).unwrap();
+
+        engine.redenominate(TokenTicker::BTC, 1000, 1).unwrap();
+        engine.publish_market_data();
+        let published = handle.depth(&TokenTicker::BTC).unwrap();
+        assert!(published.sequence > before.sequence);
+        assert_eq!(
+            published.depth.asks,
+            vec![DepthLevel {
+                price: 60.0,
+                quantity: 2_000
+            }]
+        );
+
+        // the deltas carry the book from the old snapshot to the new one
+        let mut depth = before.depth;
+        for (_, event) in engine
+            .book_deltas_since(&TokenTicker::BTC, before.sequence)
+            .unwrap()
+        {
+            depth.apply(&event);
+        }
+        assert_eq!(depth.asks, published.depth.asks);
+    }
+}
@@ -0,0 +1,424 @@
+use super::depth::BAND_EPSILON;
+use super::engine::{OrderError, TradeEngine};
+use super::order::{BuyOrSell, TimeInForce, Wallet};
+use super::session::SessionState;
+use super::token::TokenTicker;
+use super::trade::{Trade, TradeKind};
+
+// How far through the touch a market order may trade.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PriceProtection {
+    // Whole ticks of the book's tick size.
+    Ticks(u32),
+    // Percent of the touch, e.g. 0.5 for half a percent.
+    Percent(f64),
+}
+
+// What happens to the part of a market order its protection stops.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MarketRemainderPolicy {
+    #[default]
+    Cancel,
+    // Rest on the book as a limit order at the protection price.
+    RestAsLimit,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MarketRemainder {
+    Filled,
+    Cancelled {
+        quantity: u32,
+    },
+    // Meant to rest, but refused as a limit order.
+    Rejected {
+        quantity: u32,
+        reason: OrderError,
+    },
+    Resting {
+        order_id: u64,
+        price: f64,
+        quantity: u32,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarketExecution {
+    pub filled: u32,
+    pub trade_ids: Vec<u64>,
+    // Worst price the order could trade at; None without protection.
+    pub protection_price: Option<f64>,
+    pub remainder: MarketRemainder,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MarketOrderError {
+    Order(OrderError),
+    // Nothing rests on the other side to trade against.
+    NoLiquidity,
+    // Protection in ticks on a book without a tick size.
+    NoTickSize,
+    // A negative, infinite or NaN percent.
+    InvalidProtection,
+    // Pre-open, or the instrument trades in batch auctions.
+    NotContinuous,
+}
+
+impl From<OrderError> for MarketOrderError {
+    fn from(err: OrderError) -> Self {
+        MarketOrderError::Order(err)
+    }
+}
+
+impl TradeEngine {
+    // Trade `quantity` against the other side of the book at whatever prices
+    // rest there, stopping at the protection price. Unprotected orders walk
+    // the whole book and cancel what is left; with protection the remainder
+    // follows `market_remainder`. Market orders only trade in the continuous
+    // session: pre-open, halted and batch auction instruments refuse them.
+    pub fn submit_market_order(
+        &mut self,
+        wallet: Option<&Wallet>,
+        ticker: &TokenTicker,
+        side: BuyOrSell,
+        quantity: u32,
+        protection: Option<PriceProtection>,
+    ) -> Result<MarketExecution, MarketOrderError> {
+        self.update_sessions();
+        self.update_cancel_timers();
+        self.check_accepting_orders(ticker)?;
+        if self.session_state(ticker) != SessionState::Open
+            || self.batch_auctions.contains_key(ticker)
+        {
+            return Err(MarketOrderError::NotContinuous);
+        }
+        if quantity == 0 {
+            return Err(OrderError::ZeroQuantity.into());
+        }
+        let orderbook = &self.order_books[ticker];
+        let opposite = match side {
+            BuyOrSell::Buy => BuyOrSell::Sell,
+            BuyOrSell::Sell => BuyOrSell::Buy,
+        };
+        let touch = orderbook
+            .best_order(opposite)
+            .map(|order| order.price)
+            .ok_or(MarketOrderError::NoLiquidity)?;
+        let protection_price = match protection {
+            None => None,
+            Some(protection) => Some(protection_price(
+                protection,
+                side,
+                touch,
+                orderbook.tick_size(),
+            )?),
+        };
+        if let Some(wallet) = wallet {
+            self.check_throttle(wallet, ticker)?;
+        }
+
+        let timestamp = self.now();
+        let orderbook = self.order_books.get_mut(ticker).unwrap();
+        let mut remaining = quantity;
+        let mut trade_ids = Vec::new();
+        while remaining > 0 {
+            let Some(best) = orderbook.best_order(opposite) else {
+                break;
+            };
+            let within = protection_price.is_none_or(|limit| match side {
+                BuyOrSell::Buy => best.price <= limit,
+                BuyOrSell::Sell => best.price >= limit,
+            });
+            if !within {
+                break;
+            }
+            let fill = remaining.min(best.quantity);
+            let maker = orderbook.fill_best(opposite, fill, timestamp).unwrap();
+            remaining -= fill;
+            let (buyer, seller, buy_order_id, sell_order_id, buy_metadata, sell_metadata) =
+                match side {
+                    BuyOrSell::Buy => (
+                        wallet.cloned(),
+                        maker.wallet,
+                        None,
+                        Some(maker.id),
+                        None,
+                        maker.metadata,
+                    ),
+                    BuyOrSell::Sell => (
+                        maker.wallet,
+                        wallet.cloned(),
+                        Some(maker.id),
+                        None,
+                        maker.metadata,
+                        None,
+                    ),
+                };
+            trade_ids.push(self.trade_feed.publish(Trade {
+                id: 0,
+                ticker: ticker.clone(),
+                price: maker.price,
+                quantity: fill as u64,
+                buyer,
+                seller,
+                buy_order_id,
+                sell_order_id,
+                timestamp,
+                kind: TradeKind::Lit,
+                buy_metadata,
+                sell_metadata,
+                settled_notional: None,
+            }));
+        }
+
+        let remainder = match (remaining, protection_price, self.market_remainder) {
+            (0, _, _) => MarketRemainder::Filled,
+            // the rest enters like any limit order, price policy and
+            // exposure limits included; the throttle already counted it
+            (_, Some(price), MarketRemainderPolicy::RestAsLimit) => match self
+                .place_order_throttled(
+                    wallet,
+                    ticker,
+                    side,
+                    price,
+                    remaining,
+                    TimeInForce::GoodTillCancel,
+                    None,
+                    false,
+                ) {
+                Ok(order_id) => MarketRemainder::Resting {
+                    order_id,
+                    price,
+                    quantity: remaining,
+                },
+                Err(reason) => MarketRemainder::Rejected {
+                    quantity: remaining,
+                    reason,
+                },
+            },
+            _ => MarketRemainder::Cancelled {
+                quantity: remaining,
+            },
+        };
+        Ok(MarketExecution {
+            filled: quantity - remaining,
+            trade_ids,
+            protection_price,
+            remainder,
+        })
+    }
+}
+
+// Worst price a protected order may trade at, `protection` through `touch`.
+// On a book with a tick size the limit is a whole number of ticks, rounded
+// back towards the touch.
+fn protection_price(
+    protection: PriceProtection,
+    side: BuyOrSell,
+    touch: f64,
+    tick_size: Option<f64>,
+) -> Result<f64, MarketOrderError> {
+    let direction = match side {
+        BuyOrSell::Buy => 1.0,
+        BuyOrSell::Sell => -1.0,
+    };
+    match (protection, tick_size) {
+        (PriceProtection::Ticks(_), None) => Err(MarketOrderError::NoTickSize),
+        (PriceProtection::Ticks(ticks), Some(tick)) => {
+            let touch_ticks = (touch / tick).round();
+            Ok(tick_price(touch_ticks + direction * ticks as f64, tick))
+        }
+        (PriceProtection::Percent(percent), _) if !(percent >= 0.0 && percent.is_finite()) => {
+            Err(MarketOrderError::InvalidProtection)
+        }
+        (PriceProtection::Percent(percent), None) => {
+            Ok(touch * (1.0 + direction * percent / 100.0))
+        }
+        (PriceProtection::Percent(percent), Some(tick)) => {
+            let ticks = touch * (1.0 + direction * percent / 100.0) / tick;
+            // tolerate representation error, e.g. 100.29999999999998 / 0.1
+            let ticks = match side {
+                BuyOrSell::Buy => (ticks + BAND_EPSILON).floor(),
+                BuyOrSell::Sell => (ticks - BAND_EPSILON).ceil(),
+            };
+            Ok(tick_price(ticks, tick))
+        }
+    }
+}
+
+// `ticks` whole ticks as a price. Decimal ticks like 0.1 divide by their
+// inverse instead of multiplying, which gives the float nearest the decimal
+// price, i.e. the same level key an order entered at that price gets.
+fn tick_price(ticks: f64, tick: f64) -> f64 {
+    let per_unit = (1.0 / tick).round();
+    if per_unit >= 1.0 && (per_unit * tick - 1.0).abs() <= BAND_EPSILON {
+        ticks / per_unit
+    } else {
+        ticks * tick
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::orderbook::{OrderBook, OrderBookTrait};
+
+    #[test]
+    fn test_protection_stops_market_orders_at_the_limit() {
+        let mut engine = TradeEngine::new();
+        let wallet = Wallet::new(String::from("marketwallet"));
+        let orderbook = OrderBook::builder()
+            .tick_size(0.5)
+            .seed_order(BuyOrSell::Sell, 100.0, 2, 0)
+            .seed_order(BuyOrSell::Sell, 100.5, 2, 0)
+            .seed_order(BuyOrSell::Sell, 101.5, 5, 0)
+            .seed_order(BuyOrSell::Buy, 99.0, 5, 0)
+            .build()
+            .unwrap();
+        engine.list_new_token_with_book(TokenTicker::ETH, orderbook);
+
+        // one tick through the touch takes the first two levels only
+        let execution = engine
+            .submit_market_order(
+                Some(&wallet),
+                &TokenTicker::ETH,
+                BuyOrSell::Buy,
+                6,
+                Some(PriceProtection::Ticks(1)),
+            )
+            .unwrap();
+        assert_eq!(execution.filled, 4);
+        assert_eq!(execution.protection_price, Some(100.5));
+        assert_eq!(
+            execution.remainder,
+            MarketRemainder::Cancelled { quantity: 2 }
+        );
+        let trades = engine.trade_feed.trades();
+        assert_eq!(trades.len(), 2);
+        assert_eq!(trades[1].buyer, Some(wallet.clone()));
+        assert_eq!((trades[1].price, trades[1].sell_order_id), (100.5, Some(2)));
+
+        // 2% from 101.5 is 103.53, rounded in to 103.5; the rest rests there
+        engine.market_remainder = MarketRemainderPolicy::RestAsLimit;
+        let execution = engine
+            .submit_market_order(
+                None,
+                &TokenTicker::ETH,
+                BuyOrSell::Buy,
+                7,
+                Some(PriceProtection::Percent(2.0)),
+            )
+            .unwrap();
+        assert_eq!(execution.filled, 5);
+        assert_eq!(
+            execution.remainder,
+            MarketRemainder::Resting {
+                order_id: 5,
+                price: 103.5,
+                quantity: 2
+            }
+        );
+        assert_eq!(
+            engine.order_books[&TokenTicker::ETH].best_buy_price(),
+            Some(103.5.into())
+        );
+
+        // nothing left to buy from
+        assert_eq!(
+            engine.submit_market_order(None, &TokenTicker::ETH, BuyOrSell::Buy, 1, None),
+            Err(MarketOrderError::NoLiquidity)
+        );
+        let unprotected = engine
+            .submit_market_order(None, &TokenTicker::ETH, BuyOrSell::Sell, 10, None)
+            .unwrap();
+        assert_eq!(unprotected.filled, 7);
+        assert_eq!(
+            unprotected.remainder,
+            MarketRemainder::Cancelled { quantity: 3 }
+        );
+    }
+
+    #[test]
+    fn test_market_orders_only_trade_continuously_within_limits() {
+        use crate::corelib::clock::SimulatedClock;
+        use crate::corelib::session::SessionSchedule;
+        use chrono::{Duration, NaiveDate, NaiveTime};
+
+        // Friday 2024-01-05, 09:15 UTC, pre-open
+        let start = NaiveDate::from_ymd_opt(2024, 1, 5)
+            .unwrap()
+            .and_hms_opt(9, 15, 0)
+            .unwrap()
+            .and_utc()
+            .timestamp_millis() as u64;
+        let clock = SimulatedClock::new(start);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        let wallet = Wallet::new(String::from("limitedwallet"));
+        let orderbook = OrderBook::builder()
+            .tick_size(0.1)
+            .seed_order(BuyOrSell::Sell, 100.0, 1, 0)
+            .seed_order(BuyOrSell::Sell, 100.4, 1, 0)
+            .build()
+            .unwrap();
+        engine.list_new_token_with_book(TokenTicker::ETH, orderbook);
+        engine
+            .set_session_schedule(
+                TokenTicker::ETH,
+                SessionSchedule::new(
+                    NaiveTime::from_hms_opt(9, 30, 0).unwrap(),
+                    NaiveTime::from_hms_opt(16, 0, 0).unwrap(),
+                )
+                .with_pre_open(Duration::minutes(30)),
+            )
+            .unwrap();
+        let buy = |engine: &mut TradeEngine, protection| {
+            engine.submit_market_order(
+                Some(&wallet),
+                &TokenTicker::ETH,
+                BuyOrSell::Buy,
+                3,
+                Some(protection),
+            )
+        };
+        assert_eq!(
+            buy(&mut engine, PriceProtection::Percent(0.3)),
+            Err(MarketOrderError::NotContinuous)
+        );
+
+        clock.advance(Duration::minutes(15).num_milliseconds() as u64);
+        engine.enable_batch_auction(TokenTicker::ETH, 1_000);
+        assert_eq!(
+            buy(&mut engine, PriceProtection::Percent(0.3)),
+            Err(MarketOrderError::NotContinuous)
+        );
+        engine.disable_batch_auction(&TokenTicker::ETH);
+        assert_eq!(
+            buy(&mut engine, PriceProtection::Percent(-1.0)),
+            Err(MarketOrderError::InvalidProtection)
+        );
+        assert_eq!(
+            buy(&mut engine, PriceProtection::Percent(f64::NAN)),
+            Err(MarketOrderError::InvalidProtection)
+        );
+
+        // 0.3% through 100.0 is exactly 100.3, despite 100.29999999999998 / 0.1;
+        // the rest would breach the wallet's resting notional cap
+        engine.market_remainder = MarketRemainderPolicy::RestAsLimit;
+        engine.set_open_notional_limit(TokenTicker::ETH, 150);
+        let execution = buy(&mut engine, PriceProtection::Percent(0.3)).unwrap();
+        assert_eq!(execution.filled, 1);
+        assert_eq!(execution.protection_price, Some(100.3));
+        assert_eq!(
+            execution.remainder,
+            MarketRemainder::Rejected {
+                quantity: 2,
+                reason: OrderError::OpenNotionalExceeded {
+                    exposure: 0,
+                    order_notional: 201,
+                    limit: 150,
+                },
+            }
+        );
+        assert_eq!(engine.open_notional(&wallet, &TokenTicker::ETH), 0);
+    }
+}
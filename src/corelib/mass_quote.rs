@@ -0,0 +1,286 @@
+use std::collections::{HashMap, VecDeque};
+
+use super::engine::{OrderError, TradeEngine};
+use super::order::{BuyOrSell, TimeInForce, Wallet};
+use super::token::TokenTicker;
+
+// Two-sided quote on one instrument; either side may be left out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuoteEntry {
+    pub ticker: TokenTicker,
+    // (price, quantity)
+    pub bid: Option<(f64, u32)>,
+    pub ask: Option<(f64, u32)>,
+}
+
+// Pull every quote of a maker once `max_executions` of them execute within
+// `window_millis`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuoteProtection {
+    pub max_executions: usize,
+    pub window_millis: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuotesInvalidated {
+    pub maker: Wallet,
+    pub timestamp: u64,
+    pub cancelled: Vec<(TokenTicker, u64)>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MakerQuotes {
+    // Resting orders from the maker's latest mass quote.
+    pub orders: Vec<(TokenTicker, u64)>,
+    pub protection: Option<QuoteProtection>,
+    // Times of recent executions against the quotes.
+    executions: VecDeque<u64>,
+    // Set when protection pulled the quotes; cleared by the next mass quote.
+    pub invalidated: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MassQuotes {
+    pub makers: HashMap<Wallet, MakerQuotes>,
+    // Last trade already checked against quotes.
+    last_trade_id: u64,
+}
+
+impl MassQuotes {
+    pub fn new() -> MassQuotes {
+        MassQuotes::default()
+    }
+}
+
+impl TradeEngine {
+    pub fn set_quote_protection(&mut self, maker: &Wallet, protection: Option<QuoteProtection>) {
+        self.mass_quotes
+            .makers
+            .entry(maker.clone())
+            .or_default()
+            .protection = protection;
+    }
+
+    // Replace all of a maker's quotes at once. Either every new quote rests
+    // and the previous ones are cancelled, or nothing changes. Returns the new
+    // order ids.
+    pub fn mass_quote(
+        &mut self,
+        maker: &Wallet,
+        quotes: &[QuoteEntry],
+    ) -> Result<Vec<(TokenTicker, u64)>, OrderError> {
+        let mut placed = Vec::new();
+        for quote in quotes {
+            for (side, level) in [(BuyOrSell::Buy, quote.bid), (BuyOrSell::Sell, quote.ask)] {
+                let Some((price, quantity)) = level else {
+                    continue;
+                };
+                match self.submit_wallet_order(
+                    maker,
+                    &quote.ticker,
+                    side,
+                    price,
+                    quantity,
+                    TimeInForce::GoodTillCancel,
+                ) {
+                    Ok(order_id) => placed.push((quote.ticker.clone(), order_id)),
+                    Err(err) => {
+                        self.cancel_quote_orders(&placed);
+                        return Err(err);
+                    }
+                }
+            }
+        }
+        let entry = self.mass_quotes.makers.entry(maker.clone()).or_default();
+        let previous = std::mem::replace(&mut entry.orders, placed.clone());
+        entry.executions.clear();
+        entry.invalidated = false;
+        self.cancel_quote_orders(&previous);
+        Ok(placed)
+    }
+
+    // Count executions against quotes since the last check and pull the
+    // quotes of every maker that breached its protection. Called after
+    // matching.
+    pub fn update_quote_protection(&mut self) -> Vec<QuotesInvalidated> {
+        let now = self.now();
+        let last_trade_id = self.mass_quotes.last_trade_id;
+        let mut executions: HashMap<Wallet, Vec<u64>> = HashMap::new();
+        for trade in self.trade_feed.trades_after(last_trade_id) {
+            self.mass_quotes.last_trade_id = trade.id;
+            for (maker, quotes) in self.mass_quotes.makers.iter() {
+                let hit = [trade.buy_order_id, trade.sell_order_id]
+                    .into_iter()
+                    .flatten()
+                    .any(|order_id| quotes.orders.contains(&(trade.ticker.clone(), order_id)));
+                if hit {
+                    executions
+                        .entry(maker.clone())
+                        .or_default()
+                        .push(trade.timestamp);
+                }
+            }
+        }
+
+        let mut breached = Vec::new();
+        for (maker, quotes) in self.mass_quotes.makers.iter_mut() {
+            quotes
+                .executions
+                .extend(executions.remove(maker).unwrap_or_default());
+            let Some(protection) = quotes.protection else {
+                continue;
+            };
+            while quotes
+                .executions
+                .front()
+                .is_some_and(|at| at + protection.window_millis <= now)
+            {
+                quotes.executions.pop_front();
+            }
+            if !quotes.orders.is_empty() && quotes.executions.len() >= protection.max_executions {
+                quotes.executions.clear();
+                quotes.invalidated = true;
+                breached.push((maker.clone(), std::mem::take(&mut quotes.orders)));
+            }
+        }
+        breached.sort_by(|a, b| a.0.address.cmp(&b.0.address));
+
+        breached
+            .into_iter()
+            .map(|(maker, orders)| QuotesInvalidated {
+                cancelled: self.cancel_quote_orders(&orders),
+                maker,
+                timestamp: now,
+            })
+            .collect()
+    }
+
+    // Cancel quote orders still resting, returning those that were.
+    fn cancel_quote_orders(&mut self, orders: &[(TokenTicker, u64)]) -> Vec<(TokenTicker, u64)> {
+        let now = self.now();
+        orders
+            .iter()
+            .filter(|(ticker, order_id)| {
+                self.order_books
+                    .get_mut(ticker)
+                    .and_then(|orderbook| orderbook.cancel_order(*order_id, now))
+                    .is_some()
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::clock::SimulatedClock;
+
+    fn two_sided(ticker: TokenTicker, bid: f64, ask: f64) -> QuoteEntry {
+        QuoteEntry {
+            ticker,
+            bid: Some((bid, 5)),
+            ask: Some((ask, 5)),
+        }
+    }
+
+    #[test]
+    fn test_mass_quote_is_atomic() {
+        let mut engine = TradeEngine::new();
+        engine.list_new_token(TokenTicker::ETH);
+        engine.list_new_token(TokenTicker::BTC);
+        let maker = Wallet::new(String::from("quotemaker"));
+
+        let first = engine
+            .mass_quote(
+                &maker,
+                &[
+                    two_sided(TokenTicker::ETH, 99.0, 101.0),
+                    two_sided(TokenTicker::BTC, 49.0, 51.0),
+                ],
+            )
+            .unwrap();
+        assert_eq!(first.len(), 4);
+
+        let rejected = engine.mass_quote(
+            &maker,
+            &[
+                two_sided(TokenTicker::ETH, 98.0, 102.0),
+                two_sided(TokenTicker::SOL, 9.0, 11.0),
+            ],
+        );
+        assert_eq!(rejected, Err(OrderError::UnknownTicker));
+        assert_eq!(
+            engine.order_books[&TokenTicker::ETH]
+                .wallet_orders(&maker)
+                .count(),
+            2
+        );
+        assert_eq!(engine.mass_quotes.makers[&maker].orders, first);
+
+        engine
+            .mass_quote(&maker, &[two_sided(TokenTicker::ETH, 98.0, 102.0)])
+            .unwrap();
+        assert_eq!(
+            engine.order_books[&TokenTicker::BTC]
+                .wallet_orders(&maker)
+                .count(),
+            0
+        );
+        assert_eq!(
+            engine.order_books[&TokenTicker::ETH]
+                .wallet_orders(&maker)
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_quotes_pulled_after_too_many_executions() {
+        let clock = SimulatedClock::new(0);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        engine.list_new_token(TokenTicker::ETH);
+        engine.list_new_token(TokenTicker::BTC);
+        let maker = Wallet::new(String::from("quotemaker"));
+        engine.set_quote_protection(
+            &maker,
+            Some(QuoteProtection {
+                max_executions: 2,
+                window_millis: 1_000,
+            }),
+        );
+        engine
+            .mass_quote(
+                &maker,
+                &[
+                    two_sided(TokenTicker::ETH, 99.0, 101.0),
+                    two_sided(TokenTicker::BTC, 49.0, 51.0),
+                ],
+            )
+            .unwrap();
+
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Buy, 101.0, 1)
+            .unwrap();
+        engine.match_orders();
+        // the first execution ages out of the window
+        clock.advance(1_000);
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Sell, 99.0, 1)
+            .unwrap();
+        engine.match_orders();
+        assert!(!engine.mass_quotes.makers[&maker].invalidated);
+
+        engine
+            .submit_order(&TokenTicker::BTC, BuyOrSell::Sell, 49.0, 1)
+            .unwrap();
+        engine.match_orders();
+        let quotes = &engine.mass_quotes.makers[&maker];
+        assert!(quotes.invalidated);
+        assert!(quotes.orders.is_empty());
+        for orderbook in engine.order_books.values() {
+            assert_eq!(orderbook.wallet_orders(&maker).count(), 0);
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use super::engine::{OrderError, TradeEngine};
+use super::order::{BuyOrSell, Wallet};
+use super::orderbook::OrderBookTrait;
+use super::token::TokenTicker;
+use super::trade::{Trade, TradeKind};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HiddenOrder {
+    pub id: u64,
+    pub wallet: Wallet,
+    pub side: BuyOrSell,
+    pub quantity: u32,
+    // Smallest execution the order accepts. Once less than this remains,
+    // the remainder may fill in one go.
+    pub min_quantity: u32,
+    pub timestamp: u64,
+}
+
+impl HiddenOrder {
+    fn accepts(&self, quantity: u32) -> bool {
+        quantity >= self.min_quantity.min(self.quantity)
+    }
+}
+
+// Hidden orders for one instrument, crossed at the lit mid in time priority.
+#[derive(Debug, Clone, Default)]
+pub struct MidpointBook {
+    orders: Vec<HiddenOrder>,
+    next_order_id: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MidpointFill {
+    pub buy_order: u64,
+    pub sell_order: u64,
+    pub price: f64,
+    pub quantity: u32,
+}
+
+impl MidpointBook {
+    pub fn new() -> MidpointBook {
+        MidpointBook::default()
+    }
+
+    pub fn orders(&self) -> &[HiddenOrder] {
+        &self.orders
+    }
+
+    pub fn add_order(
+        &mut self,
+        wallet: Wallet,
+        side: BuyOrSell,
+        quantity: u32,
+        min_quantity: u32,
+        timestamp: u64,
+    ) -> u64 {
+        self.next_order_id += 1;
+        self.orders.push(HiddenOrder {
+            id: self.next_order_id,
+            wallet,
+            side,
+            quantity,
+            min_quantity,
+            timestamp,
+        });
+        self.next_order_id
+    }
+
+    pub fn cancel_order(&mut self, order_id: u64) -> Option<HiddenOrder> {
+        let index = self.orders.iter().position(|order| order.id == order_id)?;
+        Some(self.orders.remove(index))
+    }
+
+    // Cross buys against sells at `mid`. Each buy, oldest first, takes the
+    // oldest sells whose minimum quantities the execution satisfies.
+    pub fn cross(&mut self, mid: f64) -> Vec<MidpointFill> {
+        let mut fills = Vec::new();
+        for buy in 0..self.orders.len() {
+            if self.orders[buy].side != BuyOrSell::Buy {
+                continue;
+            }
+            for sell in 0..self.orders.len() {
+                let (buyer, seller) = (&self.orders[buy], &self.orders[sell]);
+                if seller.side != BuyOrSell::Sell || seller.quantity == 0 || buyer.quantity == 0 {
+                    continue;
+                }
+                let quantity = buyer.quantity.min(seller.quantity);
+                if !buyer.accepts(quantity) || !seller.accepts(quantity) {
+                    continue;
+                }
+                fills.push(MidpointFill {
+                    buy_order: buyer.id,
+                    sell_order: seller.id,
+                    price: mid,
+                    quantity,
+                });
+                self.orders[buy].quantity -= quantity;
+                self.orders[sell].quantity -= quantity;
+            }
+        }
+        self.orders.retain(|order| order.quantity > 0);
+        fills
+    }
+}
+
+impl TradeEngine {
+    pub fn enable_midpoint_book(&mut self, ticker: TokenTicker) {
+        self.midpoint_books.entry(ticker).or_default();
+    }
+
+    pub fn submit_hidden_order(
+        &mut self,
+        wallet: &Wallet,
+        ticker: &TokenTicker,
+        side: BuyOrSell,
+        quantity: u32,
+        min_quantity: u32,
+    ) -> Result<u64, OrderError> {
+        let timestamp = self.now();
+        let book = self
+            .midpoint_books
+            .get_mut(ticker)
+            .ok_or(OrderError::UnknownTicker)?;
+        Ok(book.add_order(wallet.clone(), side, quantity, min_quantity, timestamp))
+    }
+
+    // Mid of the lit book, only when both sides are quoted.
+    pub fn lit_mid(&self, ticker: &TokenTicker) -> Option<f64> {
+        let orderbook = self.order_books.get(ticker)?;
+        let bid = orderbook.best_buy_price()?.into_inner();
+        let ask = orderbook.best_sell_price()?.into_inner();
+        Some((bid + ask) / 2.0)
+    }
+
+    // Cross every midpoint book whose lit book is two-sided, publishing
+    // the executions as dark trades. Called at the end of `match_orders`.
+    pub fn match_midpoint_books(&mut self) -> Vec<u64> {
+        let timestamp = self.now();
+        let mut tickers: Vec<TokenTicker> = self.midpoint_books.keys().cloned().collect();
+        tickers.sort();
+        let mut trade_ids = Vec::new();
+        for ticker in tickers {
+            let Some(mid) = self.lit_mid(&ticker) else {
+                continue;
+            };
+            let book = self.midpoint_books.get_mut(&ticker).unwrap();
+            let wallets: Vec<(u64, Wallet)> = book
+                .orders()
+                .iter()
+                .map(|order| (order.id, order.wallet.clone()))
+                .collect();
+            let wallet_of = |id: u64| {
+                wallets
+                    .iter()
+                    .find(|(order_id, _)| *order_id == id)
+                    .map(|(_, wallet)| wallet.clone())
+            };
+            for fill in book.cross(mid) {
+                trade_ids.push(self.trade_feed.publish(Trade {
+                    id: 0,
+                    ticker: ticker.clone(),
+                    price: fill.price,
+                    quantity: fill.quantity as u64,
+                    buyer: wallet_of(fill.buy_order),
+                    seller: wallet_of(fill.sell_order),
+                    // hidden order ids are not lit order ids
+                    buy_order_id: None,
+                    sell_order_id: None,
+                    timestamp,
+                    kind: TradeKind::Dark,
+                    buy_metadata: None,
+                    sell_metadata: None,
+                    settled_notional: None,
+                }));
+            }
+        }
+        trade_ids
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_hidden_orders_cross_at_lit_mid() {
+        let mut engine = TradeEngine::new();
+        let buyer = Wallet::new(String::from("darkbuyer"));
+        let seller = Wallet::new(String::from("darkseller"));
+        let small = Wallet::new(String::from("darksmall"));
+        engine.list_new_token(TokenTicker::ETH);
+        engine.enable_midpoint_book(TokenTicker::ETH);
+
+        engine
+            .submit_hidden_order(&buyer, &TokenTicker::ETH, BuyOrSell::Buy, 100, 50)
+            .unwrap();
+        engine
+            .submit_hidden_order(&small, &TokenTicker::ETH, BuyOrSell::Sell, 20, 1)
+            .unwrap();
+        engine
+            .submit_hidden_order(&seller, &TokenTicker::ETH, BuyOrSell::Sell, 60, 10)
+            .unwrap();
+
+        // no lit mid yet
+        engine.match_orders();
+        assert!(engine.trade_feed.trades().is_empty());
+
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Buy, 99.0, 1)
+            .unwrap();
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Sell, 101.0, 1)
+            .unwrap();
+        engine.match_orders();
+
+        // the 20 lot is below the buyer's minimum and is skipped
+        let trades = engine.trade_feed.trades();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].kind, TradeKind::Dark);
+        assert_eq!(trades[0].price, 100.0);
+        assert_eq!(trades[0].quantity, 60);
+        assert_eq!(trades[0].seller, Some(seller.clone()));
+
+        // 40 is left, under the buyer's minimum: only the whole remainder
+        // may fill
+        engine.match_orders();
+        assert_eq!(engine.trade_feed.trades().len(), 1);
+        engine
+            .submit_hidden_order(&seller, &TokenTicker::ETH, BuyOrSell::Sell, 50, 1)
+            .unwrap();
+        engine.match_orders();
+        assert_eq!(engine.trade_feed.trades()[1].quantity, 40);
+        let remaining: Vec<u32> = engine.midpoint_books[&TokenTicker::ETH]
+            .orders()
+            .iter()
+            .map(|order| order.quantity)
+            .collect();
+        assert_eq!(remaining, vec![20, 10]);
+    }
+}
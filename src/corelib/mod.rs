@@ -1,5 +1,88 @@
+pub mod adl;
+pub mod admin;
+pub mod algos;
+pub mod amend;
 pub mod amm;
+pub mod analytics;
+pub mod arbitrage;
+pub mod auction;
+pub mod audit;
+pub mod balance_history;
+pub mod basket;
+pub mod batch_auction;
+pub mod book_builder;
+pub mod book_state;
+pub mod cancel_timer;
+pub mod clock;
+pub mod codec;
+pub mod collateral;
+pub mod command;
+pub mod composite;
+pub mod connector;
+pub mod conservation;
+pub mod corporate_actions;
+pub mod crossing;
+pub mod dca;
+pub mod depth;
+pub mod emissions;
 pub mod engine;
+pub mod escrow;
+pub mod events;
+pub mod execution_quality;
+pub mod expiry;
+pub mod exposure;
+pub mod fee_payment;
+pub mod fees;
+pub mod funding;
+pub mod heatmap;
+pub mod index;
+pub mod ladder;
+pub mod latency;
+pub mod ledger;
+pub mod light_snapshot;
+pub mod lp_token;
+pub mod margin;
+pub mod mark_price;
+pub mod market_data;
+pub mod market_data_handle;
+pub mod market_order;
+pub mod mass_quote;
+pub mod midpoint;
+pub mod open_interest;
+pub mod options;
 pub mod order;
+pub mod order_group;
 pub mod orderbook;
+pub mod paper;
+pub mod pool_drift;
+pub mod pool_registry;
+pub mod pool_sync;
+pub mod price;
+pub mod price_policy;
+pub mod priority_fee;
+pub mod receipt;
+pub mod replay;
+pub mod retention;
+pub mod rfq;
+pub mod rng;
+pub mod rolling_stats;
+pub mod rounding;
+pub mod router;
+pub mod schema;
+pub mod session;
+pub mod speed_bump;
+pub mod spread;
+pub mod staking;
+pub mod stress;
+pub mod summary;
+pub mod supply;
+pub mod tenants;
+pub mod throttle;
 pub mod token;
+pub mod trade;
+pub mod trade_bust;
+pub mod transfer;
+pub mod treasury;
+pub mod validation;
+pub mod wal;
+pub mod withdrawals;
@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+
+use super::engine::TradeEngine;
+use super::order::Wallet;
+use super::token::TokenTicker;
+use super::trade::Trade;
+
+// Net contracts per wallet for each derivative instrument, built from the
+// trade feed. Fills without a wallet on both sides are not attributed.
+#[derive(Debug, Clone, Default)]
+pub struct OpenInterestTracker {
+    nets: HashMap<TokenTicker, HashMap<Wallet, i64>>,
+    last_trade_id: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OpenInterest {
+    // Contracts held long, equal to those held short.
+    pub contracts: u64,
+    pub long_wallets: usize,
+    pub short_wallets: usize,
+}
+
+impl OpenInterest {
+    // Wallets net long per wallet net short; None without shorts.
+    pub fn long_short_ratio(&self) -> Option<f64> {
+        (self.short_wallets > 0).then(|| self.long_wallets as f64 / self.short_wallets as f64)
+    }
+}
+
+impl OpenInterestTracker {
+    pub fn new() -> OpenInterestTracker {
+        OpenInterestTracker::default()
+    }
+
+    fn open_interest(&self, ticker: &TokenTicker) -> OpenInterest {
+        let mut open_interest = OpenInterest {
+            contracts: 0,
+            long_wallets: 0,
+            short_wallets: 0,
+        };
+        for net in self
+            .nets
+            .get(ticker)
+            .into_iter()
+            .flat_map(|nets| nets.values())
+        {
+            if *net > 0 {
+                open_interest.contracts += *net as u64;
+                open_interest.long_wallets += 1;
+            } else if *net < 0 {
+                open_interest.short_wallets += 1;
+            }
+        }
+        open_interest
+    }
+}
+
+impl TradeEngine {
+    pub fn is_derivative(&self, ticker: &TokenTicker) -> bool {
+        self.perpetuals.contains_key(ticker) || self.dated_contracts.contains_key(ticker)
+    }
+
+    // Apply fills published since the last update. Called after every
+    // matching step.
+    pub fn update_open_interest(&mut self) {
+        let last_trade_id = self.open_interest.last_trade_id;
+        for trade in self.trade_feed.trades_after(last_trade_id) {
+            self.open_interest.last_trade_id = trade.id;
+            if !self.is_derivative(&trade.ticker) {
+                continue;
+            }
+            let (Some(buyer), Some(seller)) = (&trade.buyer, &trade.seller) else {
+                continue;
+            };
+            let nets = self
+                .open_interest
+                .nets
+                .entry(trade.ticker.clone())
+                .or_default();
+            *nets.entry(buyer.clone()).or_insert(0) += trade.quantity as i64;
+            *nets.entry(seller.clone()).or_insert(0) -= trade.quantity as i64;
+        }
+    }
+
+    // Take a busted trade back out of the nets if it was already applied.
+    pub(crate) fn unwind_open_interest(&mut self, trade: &Trade) {
+        if trade.id > self.open_interest.last_trade_id || !self.is_derivative(&trade.ticker) {
+            return;
+        }
+        let (Some(buyer), Some(seller)) = (&trade.buyer, &trade.seller) else {
+            return;
+        };
+        let Some(nets) = self.open_interest.nets.get_mut(&trade.ticker) else {
+            return;
+        };
+        for (wallet, change) in [
+            (buyer, -(trade.quantity as i64)),
+            (seller, trade.quantity as i64),
+        ] {
+            if let Some(net) = nets.get_mut(wallet) {
+                *net += change;
+            }
+        }
+    }
+
+    pub fn open_interest(&self, ticker: &TokenTicker) -> Option<OpenInterest> {
+        self.is_derivative(ticker)
+            .then(|| self.open_interest.open_interest(ticker))
+    }
+
+    // Long/short wallet ratio across every derivative instrument, counting
+    // a wallet once per instrument it holds.
+    pub fn aggregate_long_short_ratio(&self) -> Option<f64> {
+        let (long, short) = self
+            .open_interest
+            .nets
+            .keys()
+            .map(|ticker| self.open_interest.open_interest(ticker))
+            .fold((0, 0), |(long, short), open_interest| {
+                (
+                    long + open_interest.long_wallets,
+                    short + open_interest.short_wallets,
+                )
+            });
+        (short > 0).then(|| long as f64 / short as f64)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::funding::FundingConfig;
+    use crate::corelib::order::{BuyOrSell, TimeInForce};
+
+    #[test]
+    fn test_open_interest_follows_fills() {
+        let mut engine = TradeEngine::new();
+        engine.list_perpetual(TokenTicker::BTC, FundingConfig::default());
+        engine.list_new_token(TokenTicker::ETH);
+        let wallets: Vec<Wallet> = ["oi-a", "oi-b", "oi-c"]
+            .into_iter()
+            .map(|name| Wallet::new(String::from(name)))
+            .collect();
+        let order = |engine: &mut TradeEngine, wallet: usize, ticker, side, quantity| {
+            engine
+                .submit_wallet_order(
+                    &wallets[wallet],
+                    &ticker,
+                    side,
+                    100.0,
+                    quantity,
+                    TimeInForce::GoodTillCancel,
+                )
+                .unwrap();
+        };
+
+        order(&mut engine, 0, TokenTicker::BTC, BuyOrSell::Buy, 5);
+        order(&mut engine, 1, TokenTicker::BTC, BuyOrSell::Sell, 3);
+        order(&mut engine, 2, TokenTicker::BTC, BuyOrSell::Sell, 2);
+        order(&mut engine, 0, TokenTicker::ETH, BuyOrSell::Buy, 4);
+        order(&mut engine, 1, TokenTicker::ETH, BuyOrSell::Sell, 4);
+        engine.match_orders();
+
+        let open_interest = engine.open_interest(&TokenTicker::BTC).unwrap();
+        assert_eq!(open_interest.contracts, 5);
+        assert_eq!(open_interest.long_short_ratio(), Some(0.5));
+        assert_eq!(engine.open_interest(&TokenTicker::ETH), None);
+
+        // the long sells back to one of the shorts, closing 2 contracts
+        order(&mut engine, 0, TokenTicker::BTC, BuyOrSell::Sell, 2);
+        order(&mut engine, 2, TokenTicker::BTC, BuyOrSell::Buy, 2);
+        engine.match_orders();
+        let summary = engine.market_summary();
+        let btc = summary
+            .iter()
+            .find(|summary| summary.ticker == TokenTicker::BTC)
+            .unwrap();
+        assert_eq!(btc.open_interest, Some(3));
+        assert_eq!(btc.long_short_ratio, Some(1.0));
+        assert_eq!(engine.aggregate_long_short_ratio(), Some(1.0));
+    }
+}
@@ -0,0 +1,213 @@
+use std::ops::Add;
+
+use super::engine::TradeEngine;
+use super::order::Wallet;
+use super::token::TokenTicker;
+
+const MILLIS_PER_YEAR: f64 = 365.0 * 24.0 * 60.0 * 60.0 * 1_000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OptionKind {
+    Call,
+    Put,
+}
+
+// European option cash-settled against the index of `underlying`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OptionContract {
+    pub underlying: TokenTicker,
+    pub kind: OptionKind,
+    pub strike: f64,
+    pub expiry_millis: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OptionPosition {
+    pub wallet: Wallet,
+    pub contract: OptionContract,
+    pub quantity: i64,
+}
+
+// Sensitivities of one contract, or of a book of them. Vega is per vol point
+// (0.01) and theta per day.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Greeks {
+    pub delta: f64,
+    pub gamma: f64,
+    pub vega: f64,
+    pub theta: f64,
+}
+
+impl Greeks {
+    pub fn scale(&self, factor: f64) -> Greeks {
+        Greeks {
+            delta: self.delta * factor,
+            gamma: self.gamma * factor,
+            vega: self.vega * factor,
+            theta: self.theta * factor,
+        }
+    }
+}
+
+impl Add for Greeks {
+    type Output = Greeks;
+
+    fn add(self, other: Greeks) -> Greeks {
+        Greeks {
+            delta: self.delta + other.delta,
+            gamma: self.gamma + other.gamma,
+            vega: self.vega + other.vega,
+            theta: self.theta + other.theta,
+        }
+    }
+}
+
+fn normal_pdf(x: f64) -> f64 {
+    (-0.5 * x * x).exp() / (2.0 * std::f64::consts::PI).sqrt()
+}
+
+// Abramowitz and Stegun 26.2.17, accurate to about 1e-7.
+fn normal_cdf(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.2316419 * x.abs());
+    let poly = t
+        * (0.319381530
+            + t * (-0.356563782 + t * (1.781477937 + t * (-1.821255978 + t * 1.330274429))));
+    let upper = normal_pdf(x) * poly;
+    if x >= 0.0 {
+        1.0 - upper
+    } else {
+        upper
+    }
+}
+
+// Black-Scholes greeks with a zero interest rate. Expired contracts have no
+// sensitivities left.
+pub fn black_scholes_greeks(
+    contract: &OptionContract,
+    spot: f64,
+    volatility: f64,
+    now_millis: u64,
+) -> Greeks {
+    let years = contract.expiry_millis.saturating_sub(now_millis) as f64 / MILLIS_PER_YEAR;
+    if years <= 0.0 || volatility <= 0.0 || spot <= 0.0 {
+        return Greeks::default();
+    }
+    let sqrt_t = years.sqrt();
+    let d1 = ((spot / contract.strike).ln() + 0.5 * volatility * volatility * years)
+        / (volatility * sqrt_t);
+    let pdf = normal_pdf(d1);
+    let delta = match contract.kind {
+        OptionKind::Call => normal_cdf(d1),
+        OptionKind::Put => normal_cdf(d1) - 1.0,
+    };
+    Greeks {
+        delta,
+        gamma: pdf / (spot * volatility * sqrt_t),
+        vega: spot * pdf * sqrt_t / 100.0,
+        theta: -spot * pdf * volatility / (2.0 * sqrt_t) / 365.0,
+    }
+}
+
+impl TradeEngine {
+    // Greeks of one contract against the latest index price.
+    pub fn option_greeks(&self, contract: &OptionContract, volatility: f64) -> Option<Greeks> {
+        let spot = self.index.latest(&contract.underlying)?;
+        Some(black_scholes_greeks(contract, spot, volatility, self.now()))
+    }
+
+    // Net greeks of a wallet's option positions on `underlying`, with the
+    // implied volatility of each contract given by `volatility`.
+    pub fn wallet_greeks(
+        &self,
+        wallet: &Wallet,
+        underlying: &TokenTicker,
+        positions: &[OptionPosition],
+        volatility: impl Fn(&OptionContract) -> f64,
+    ) -> Option<Greeks> {
+        let spot = self.index.latest(underlying)?;
+        let now = self.now();
+        Some(
+            positions
+                .iter()
+                .filter(|position| {
+                    &position.wallet == wallet && &position.contract.underlying == underlying
+                })
+                .map(|position| {
+                    black_scholes_greeks(
+                        &position.contract,
+                        spot,
+                        volatility(&position.contract),
+                        now,
+                    )
+                    .scale(position.quantity as f64)
+                })
+                .fold(Greeks::default(), |total, greeks| total + greeks),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::clock::SimulatedClock;
+    use crate::corelib::margin::MarginEngine;
+
+    fn contract(kind: OptionKind, strike: f64) -> OptionContract {
+        OptionContract {
+            underlying: TokenTicker::BTC,
+            kind,
+            strike,
+            expiry_millis: MILLIS_PER_YEAR as u64,
+        }
+    }
+
+    #[test]
+    fn test_black_scholes_greeks() {
+        let call = black_scholes_greeks(&contract(OptionKind::Call, 100.0), 100.0, 0.2, 0);
+        let put = black_scholes_greeks(&contract(OptionKind::Put, 100.0), 100.0, 0.2, 0);
+        assert!((call.delta - 0.5398).abs() < 1e-4);
+        assert!((call.delta - put.delta - 1.0).abs() < 1e-9);
+        assert!((call.gamma - 0.019848).abs() < 1e-5);
+        assert!((call.vega - 0.39695).abs() < 1e-4);
+        assert_eq!(call.gamma, put.gamma);
+        assert!(call.theta < 0.0);
+
+        let expired =
+            black_scholes_greeks(&contract(OptionKind::Call, 100.0), 100.0, 0.2, u64::MAX);
+        assert_eq!(expired, Greeks::default());
+    }
+
+    #[test]
+    fn test_wallet_greeks_and_margin() {
+        let mut engine = TradeEngine::with_clock(Box::new(SimulatedClock::new(0)));
+        engine.publish_index_price(TokenTicker::BTC, 100.0);
+        let wallet = Wallet::new(String::from("optionswallet"));
+        let positions = vec![
+            OptionPosition {
+                wallet: wallet.clone(),
+                contract: contract(OptionKind::Call, 100.0),
+                quantity: 10,
+            },
+            OptionPosition {
+                wallet: wallet.clone(),
+                contract: contract(OptionKind::Put, 100.0),
+                quantity: 10,
+            },
+        ];
+
+        // a long straddle is close to delta neutral but long gamma and vega
+        let greeks = engine
+            .wallet_greeks(&wallet, &TokenTicker::BTC, &positions, |_| 0.2)
+            .unwrap();
+        assert!(greeks.delta.abs() < 1.0);
+        assert!(greeks.gamma > 0.0 && greeks.vega > 0.0);
+
+        let margin = MarginEngine::new();
+        let short = greeks.scale(-1.0);
+        assert!(
+            margin.greeks_requirement(&TokenTicker::BTC, &short, 100.0, 0.1)
+                > margin.greeks_requirement(&TokenTicker::BTC, &greeks, 100.0, 0.1)
+        );
+    }
+}
@@ -1,4 +1,14 @@
-#[derive(Debug, Clone)]
+use std::sync::Arc;
+
+use super::price::PriceLike;
+
+// Opaque key-value tags an integrator attaches to an order, e.g. strategy
+// ids, desk tags or routing info. The engine never reads them; it copies
+// them into the order's fills and events, and sharing them keeps those
+// copies from allocating.
+pub type OrderMetadata = Arc<[(String, String)]>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum BuyOrSell {
     Buy,
     Sell,
@@ -13,28 +23,43 @@ impl Wallet {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum TimeInForce {
+    #[default]
+    GoodTillCancel,
+    // Cancelled when the instrument's trading session closes.
+    Day,
+}
+
 #[derive(Debug, Clone)]
-pub struct Order {
+pub struct Order<P: PriceLike = f64> {
     pub quantity: u32,
-    pub price: f64,
+    pub price: P,
     pub id: u64,
     pub timestamp: u64,
     pub wallet: Option<Wallet>,
+    pub time_in_force: TimeInForce,
+    // Paid to jump ahead of cheaper orders at the same price.
+    pub priority_fee: u64,
+    pub metadata: Option<OrderMetadata>,
 }
 
-impl Order {
-    pub fn new(id: u64, quantity: u32, price: f64, time: u64) -> Order {
+impl<P: PriceLike> Order<P> {
+    pub fn new(id: u64, quantity: u32, price: P, time: u64) -> Order<P> {
         Order {
-            quantity: quantity,
-            price: price,
-            id: id,
+            quantity,
+            price,
+            id,
             timestamp: time,
             wallet: None,
+            time_in_force: TimeInForce::GoodTillCancel,
+            priority_fee: 0,
+            metadata: None,
         }
     }
 }
 
-impl Ord for Order {
+impl<P: PriceLike> Ord for Order<P> {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
         if self.price != other.price {
             // higher price takes priority
@@ -49,15 +74,15 @@ impl Ord for Order {
     }
 }
 
-impl PartialOrd for Order {
+impl<P: PriceLike> PartialOrd for Order<P> {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         Some(self.cmp(other))
     }
 }
 
-impl PartialEq for Order {
+impl<P: PriceLike> PartialEq for Order<P> {
     fn eq(&self, other: &Self) -> bool {
         self.id == other.id
     }
 }
-impl Eq for Order {}
+impl<P: PriceLike> Eq for Order<P> {}
@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+
+use super::engine::{OrderError, TradeEngine};
+use super::order::{BuyOrSell, TimeInForce, Wallet};
+use super::token::TokenTicker;
+use super::validation::validate_order;
+
+// One order of a group.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupLeg {
+    pub ticker: TokenTicker,
+    pub side: BuyOrSell,
+    pub price: f64,
+    pub quantity: u32,
+    pub time_in_force: TimeInForce,
+}
+
+// The first leg that failed its checks; no leg was placed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupRejected {
+    pub leg: usize,
+    pub reason: OrderError,
+}
+
+impl TradeEngine {
+    // Place orders across instruments all or nothing, e.g. both legs of a
+    // pairs trade. Every leg is checked before any is placed, and legs on
+    // the same instrument count together against the wallet's notional
+    // limit and order throttle, so a rejected group leaves no trace on any
+    // book or throttle window. Returns the order ids in leg order.
+    pub fn submit_order_group(
+        &mut self,
+        wallet: Option<&Wallet>,
+        legs: &[GroupLeg],
+    ) -> Result<Vec<u64>, GroupRejected> {
+        self.update_sessions();
+        self.update_cancel_timers();
+        let mut exposures: HashMap<&TokenTicker, u64> = HashMap::new();
+        let mut entries: HashMap<&TokenTicker, usize> = HashMap::new();
+        for (leg, order) in legs.iter().enumerate() {
+            let rejected = |reason| GroupRejected { leg, reason };
+            self.check_accepting_orders(&order.ticker)
+                .map_err(rejected)?;
+            validate_order(order.price, order.quantity).map_err(rejected)?;
+            let price = self
+                .check_price(&order.ticker, order.price)
+                .map_err(rejected)?;
+            self.order_books[&order.ticker]
+                .check_order(order.side, price, order.quantity)
+                .map_err(|rejection| rejected(OrderError::Book(rejection)))?;
+            let Some(wallet) = wallet else {
+                continue;
+            };
+            let entered = entries.entry(&order.ticker).or_insert(0);
+            *entered += 1;
+            self.check_throttle_headroom(wallet, &order.ticker, *entered)
+                .map_err(rejected)?;
+            let Some(limit) = self.open_notional_limits.get(&order.ticker).copied() else {
+                continue;
+            };
+            let exposure = *exposures
+                .entry(&order.ticker)
+                .or_insert_with(|| self.open_notional(wallet, &order.ticker));
+            let order_notional = self.order_notional(price, order.quantity);
+            if exposure.saturating_add(order_notional) > limit {
+                return Err(rejected(OrderError::OpenNotionalExceeded {
+                    exposure,
+                    order_notional,
+                    limit,
+                }));
+            }
+            exposures.insert(&order.ticker, exposure + order_notional);
+        }
+
+        // throttle entries are counted once the whole group is in
+        let mut placed = Vec::new();
+        for (leg, order) in legs.iter().enumerate() {
+            let result = self.place_order_throttled(
+                wallet,
+                &order.ticker,
+                order.side,
+                order.price,
+                order.quantity,
+                order.time_in_force,
+                None,
+                false,
+            );
+            match result {
+                Ok(order_id) => placed.push(order_id),
+                Err(reason) => {
+                    for (order, order_id) in legs.iter().zip(placed) {
+                        self.cancel_order(&order.ticker, order_id);
+                    }
+                    return Err(GroupRejected { leg, reason });
+                }
+            }
+        }
+        if let Some(wallet) = wallet {
+            for order in legs {
+                self.record_throttle_entry(wallet, &order.ticker);
+            }
+        }
+        Ok(placed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::admin::AdminCommand;
+
+    fn leg(ticker: TokenTicker, side: BuyOrSell, price: f64, quantity: u32) -> GroupLeg {
+        GroupLeg {
+            ticker,
+            side,
+            price,
+            quantity,
+            time_in_force: TimeInForce::GoodTillCancel,
+        }
+    }
+
+    #[test]
+    fn test_group_is_placed_all_or_nothing() {
+        let mut engine = TradeEngine::new();
+        engine.list_new_token(TokenTicker::ETH);
+        engine.list_new_token(TokenTicker::BTC);
+        let wallet = Wallet::new(String::from("pairs"));
+        engine.open_notional_limits.insert(TokenTicker::ETH, 1_000);
+        let pair = [
+            leg(TokenTicker::ETH, BuyOrSell::Buy, 100.0, 6),
+            leg(TokenTicker::BTC, BuyOrSell::Sell, 2_000.0, 1),
+        ];
+
+        engine.apply_admin(AdminCommand::HaltInstrument {
+            ticker: TokenTicker::BTC,
+        });
+        assert_eq!(
+            engine.submit_order_group(Some(&wallet), &pair),
+            Err(GroupRejected {
+                leg: 1,
+                reason: OrderError::InstrumentHalted
+            })
+        );
+        assert_eq!(engine.book_sequence(&TokenTicker::ETH), Some(0));
+        engine.apply_admin(AdminCommand::ResumeInstrument {
+            ticker: TokenTicker::BTC,
+        });
+
+        let ids = engine.submit_order_group(Some(&wallet), &pair).unwrap();
+        assert_eq!(ids, vec![1, 1]);
+
+        // two more ETH legs fit the limit on their own but not together
+        let more = [
+            leg(TokenTicker::ETH, BuyOrSell::Buy, 100.0, 2),
+            leg(TokenTicker::BTC, BuyOrSell::Sell, 2_000.0, 1),
+            leg(TokenTicker::ETH, BuyOrSell::Sell, 100.0, 3),
+        ];
+        assert_eq!(
+            engine.submit_order_group(Some(&wallet), &more),
+            Err(GroupRejected {
+                leg: 2,
+                reason: OrderError::OpenNotionalExceeded {
+                    exposure: 800,
+                    order_notional: 300,
+                    limit: 1_000
+                }
+            })
+        );
+        assert_eq!(engine.book_sequence(&TokenTicker::ETH), Some(1));
+        assert_eq!(engine.book_sequence(&TokenTicker::BTC), Some(1));
+    }
+
+    #[test]
+    fn test_throttled_leg_leaves_no_trace() {
+        use crate::corelib::throttle::ThrottlePolicy;
+
+        let mut engine = TradeEngine::new();
+        engine.list_new_token(TokenTicker::ETH);
+        engine.list_new_token(TokenTicker::BTC);
+        let wallet = Wallet::new(String::from("throttledpairs"));
+        let policy = |normal_limit| ThrottlePolicy {
+            window_millis: 1_000,
+            normal_limit,
+            stressed_limit: normal_limit,
+            max_match_nanos: u64::MAX,
+            max_queue_depth: usize::MAX,
+        };
+        engine.set_throttle_policy(TokenTicker::ETH, Some(policy(1)));
+        engine.set_throttle_policy(TokenTicker::BTC, Some(policy(0)));
+        let throttled = OrderError::Throttled {
+            limit: 0,
+            window_millis: 1_000,
+        };
+
+        let pair = [
+            leg(TokenTicker::ETH, BuyOrSell::Buy, 100.0, 1),
+            leg(TokenTicker::BTC, BuyOrSell::Sell, 2_000.0, 1),
+        ];
+        assert_eq!(
+            engine.submit_order_group(Some(&wallet), &pair),
+            Err(GroupRejected {
+                leg: 1,
+                reason: throttled
+            })
+        );
+        assert_eq!(engine.book_sequence(&TokenTicker::ETH), Some(0));
+
+        // two ETH legs only fit the window one at a time
+        let twice = [
+            leg(TokenTicker::ETH, BuyOrSell::Buy, 100.0, 1),
+            leg(TokenTicker::ETH, BuyOrSell::Buy, 99.0, 1),
+        ];
+        assert!(matches!(
+            engine.submit_order_group(Some(&wallet), &twice),
+            Err(GroupRejected {
+                leg: 1,
+                reason: OrderError::Throttled { limit: 1, .. }
+            })
+        ));
+        engine
+            .submit_order_group(Some(&wallet), &twice[..1])
+            .unwrap();
+        assert!(matches!(
+            engine.submit_order_group(Some(&wallet), &twice[1..]),
+            Err(GroupRejected {
+                leg: 0,
+                reason: OrderError::Throttled { limit: 1, .. }
+            })
+        ));
+    }
+}
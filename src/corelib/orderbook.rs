@@ -1,32 +1,63 @@
-use super::order::{BuyOrSell, Order};
+use super::depth::{DepthLevel, DepthSnapshot, LevelChange, LevelSplit};
+use super::events::BookEvent;
+use super::order::{BuyOrSell, Order, OrderMetadata, TimeInForce, Wallet};
+use super::price::PriceLike;
 use ordered_float::OrderedFloat;
 use std::collections::HashMap;
+use std::fmt;
 
 pub trait OrderBookTrait {
-    fn best_buy_price(&self) -> Option<OrderedFloat<f64>>;
-    fn best_sell_price(&self) -> Option<OrderedFloat<f64>>;
+    // Keys of the book's price levels; OrderedFloat<f64> for the default
+    // book.
+    type Key;
+
+    fn best_buy_price(&self) -> Option<Self::Key>;
+    fn best_sell_price(&self) -> Option<Self::Key>;
     fn sell_volume(&self) -> Option<u32>;
     fn buy_volume(&self) -> Option<u32>;
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OrderStrategy {
     FIFO, // "First-In-First-Out"
     PTP,  //Price-Time Priority
 }
 
-pub struct OrderBook {
-    pub buy_orders: HashMap<OrderedFloat<f64>, Vec<Order>>,
-    pub sell_orders: HashMap<OrderedFloat<f64>, Vec<Order>>,
-    pub orders_matching_strategy: OrderStrategy,
+// Limit order book with prices of type `P`. The engine's books use f64;
+// see `PriceLike` for the alternatives.
+pub struct OrderBook<P: PriceLike = f64> {
+    pub(crate) buy_orders: HashMap<P::Key, Vec<Order<P>>>,
+    pub(crate) sell_orders: HashMap<P::Key, Vec<Order<P>>>,
+    pub(crate) orders_matching_strategy: OrderStrategy,
     next_order_id: u64,
+    events: Vec<BookEvent<P>>,
+    // Events dropped from the front of `events` by retention.
+    evicted_events: u64,
+    // Set through `OrderBookBuilder` and enforced by `check_order`.
+    tick_size: Option<P>,
+    lot_size: Option<u32>,
+    max_levels: Option<usize>,
+}
+
+// Why a book's configuration refuses an order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BookRejection<P: PriceLike = f64> {
+    OffTick { price: P, tick_size: P },
+    OffLot { quantity: u32, lot_size: u32 },
+    // The order would open a price level beyond the side's cap.
+    TooManyLevels { max_levels: usize },
+    // A seeded order would meet the other side of the book before it opens.
+    Crossed { price: P },
 }
-impl OrderBookTrait for OrderBook {
-    fn best_buy_price(&self) -> Option<OrderedFloat<f64>> {
+impl<P: PriceLike> OrderBookTrait for OrderBook<P> {
+    type Key = P::Key;
+
+    fn best_buy_price(&self) -> Option<P::Key> {
         // Get the maximum price from the buy_orders HashMap
         self.buy_orders.keys().max().cloned()
     }
 
-    fn best_sell_price(&self) -> Option<OrderedFloat<f64>> {
+    fn best_sell_price(&self) -> Option<P::Key> {
         self.sell_orders.keys().min().cloned()
     }
 
@@ -51,39 +82,844 @@ impl OrderBookTrait for OrderBook {
     }
 }
 
-impl OrderBook {
-    pub fn new() -> OrderBook {
+// One execution between two resting orders. The wallets are moved out of
+// orders the fill completes, so only a partially filled wallet order copies
+// its wallet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fill<P: PriceLike = f64> {
+    pub buy_order_id: u64,
+    pub sell_order_id: u64,
+    pub buy_wallet: Option<Wallet>,
+    pub sell_wallet: Option<Wallet>,
+    pub price: P,
+    pub quantity: u32,
+    pub buy_metadata: Option<OrderMetadata>,
+    pub sell_metadata: Option<OrderMetadata>,
+}
+
+// The order `OrderBook::fill_best` took from.
+pub(crate) struct FilledOrder<P: PriceLike = f64> {
+    pub id: u64,
+    pub price: P,
+    pub wallet: Option<Wallet>,
+    pub metadata: Option<OrderMetadata>,
+}
+
+// The aggregated ladder, as for `DepthSnapshot`.
+impl<P: PriceLike> fmt::Display for OrderBook<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.depth_snapshot())
+    }
+}
+
+// `OrderBook::<Decimal>::default()` and the like for books on other price
+// types.
+impl<P: PriceLike> Default for OrderBook<P> {
+    fn default() -> Self {
         OrderBook {
             buy_orders: HashMap::new(),
             sell_orders: HashMap::new(),
             next_order_id: 1,
             orders_matching_strategy: OrderStrategy::PTP,
+            events: Vec::new(),
+            evicted_events: 0,
+            tick_size: None,
+            lot_size: None,
+            max_levels: None,
         }
     }
+}
+
+impl OrderBook {
+    pub fn new() -> OrderBook {
+        OrderBook::default()
+    }
+
+    // Book holding one synthetic order per level of an L2 snapshot, so a
+    // simulation can start from a market state captured elsewhere.
+    pub fn from_depth_snapshot(snapshot: &DepthSnapshot) -> OrderBook {
+        OrderBook::from_depth_snapshot_split(snapshot, LevelSplit::Single, 0)
+    }
+
+    // As `from_depth_snapshot`, splitting each level into orders stamped
+    // `timestamp`. Better levels are placed first.
+    pub fn from_depth_snapshot_split(
+        snapshot: &DepthSnapshot,
+        split: LevelSplit,
+        timestamp: u64,
+    ) -> OrderBook {
+        let mut orderbook = OrderBook::new();
+        let sides = [
+            (BuyOrSell::Buy, &snapshot.bids),
+            (BuyOrSell::Sell, &snapshot.asks),
+        ];
+        for (side, levels) in sides {
+            for level in levels {
+                for quantity in split.sizes(level.quantity) {
+                    orderbook.add_order(side, level.price, quantity, timestamp);
+                }
+            }
+        }
+        orderbook
+    }
+
+    // Rewrite resting orders after a redenomination: prices are multiplied
+    // by `price_factor` and quantities by `numerator / denominator`, rounding
+    // down. Orders left with no quantity are cancelled. Each level keeps its
+    // queue order; levels that land on the same price are merged by
+    // priority fee, then time.
+    pub(crate) fn rescale(
+        &mut self,
+        price_factor: f64,
+        numerator: u64,
+        denominator: u64,
+        timestamp: u64,
+    ) {
+        let mut emptied = Vec::new();
+        let mut rescaled = Vec::new();
+        for (order_side, side) in [
+            (BuyOrSell::Buy, &mut self.buy_orders),
+            (BuyOrSell::Sell, &mut self.sell_orders),
+        ] {
+            let levels = std::mem::take(side);
+            for (price, mut orders) in levels {
+                let old_price = price.0;
+                let price = old_price * price_factor;
+                for order in orders.iter_mut() {
+                    let old_quantity = order.quantity;
+                    order.price = price;
+                    order.quantity =
+                        (order.quantity as u128 * numerator as u128 / denominator as u128) as u32;
+                    if order.quantity == 0 {
+                        emptied.push(order.id);
+                    }
+                    if (old_price, old_quantity) != (price, order.quantity) {
+                        rescaled.push(BookEvent::OrderRescaled {
+                            order_id: order.id,
+                            side: order_side,
+                            old_price,
+                            price,
+                            old_quantity,
+                            quantity: order.quantity,
+                            timestamp,
+                            metadata: order.metadata.clone(),
+                        });
+                    }
+                }
+                let level = side.entry(OrderedFloat(price)).or_default();
+                if level.is_empty() {
+                    *level = orders;
+                } else {
+                    level.extend(orders);
+                    level.sort_by_key(|order| {
+                        (
+                            std::cmp::Reverse(order.priority_fee),
+                            order.timestamp,
+                            order.id,
+                        )
+                    });
+                }
+            }
+        }
+        self.events.extend(rescaled);
+        for order_id in emptied {
+            self.cancel_order(order_id, timestamp);
+        }
+    }
+}
+
+impl<P: PriceLike> OrderBook<P> {
+    // Empty book with the configuration an `OrderBookBuilder` collected.
+    pub(crate) fn configured(
+        tick_size: Option<P>,
+        lot_size: Option<u32>,
+        max_levels: Option<usize>,
+    ) -> OrderBook<P> {
+        OrderBook {
+            tick_size,
+            lot_size,
+            max_levels,
+            ..OrderBook::default()
+        }
+    }
+
+    pub fn matching_strategy(&self) -> OrderStrategy {
+        self.orders_matching_strategy
+    }
+
+    pub fn tick_size(&self) -> Option<P> {
+        self.tick_size
+    }
+
+    pub fn lot_size(&self) -> Option<u32> {
+        self.lot_size
+    }
 
-    pub fn add_order(&mut self, order_type: BuyOrSell, price: f64, quantity: u32, timestamp: u64) {
+    pub fn max_levels(&self) -> Option<usize> {
+        self.max_levels
+    }
+
+    // Put back the configuration of a book rebuilt from its resting orders.
+    pub(crate) fn restore_configuration(
+        &mut self,
+        tick_size: Option<P>,
+        lot_size: Option<u32>,
+        max_levels: Option<usize>,
+    ) {
+        self.tick_size = tick_size;
+        self.lot_size = lot_size;
+        self.max_levels = max_levels;
+    }
+
+    // Attach metadata to a resting order of a rebuilt book.
+    pub(crate) fn restore_metadata(&mut self, order_id: u64, metadata: OrderMetadata) -> bool {
+        let order = self
+            .buy_orders
+            .values_mut()
+            .chain(self.sell_orders.values_mut())
+            .flatten()
+            .find(|order| order.id == order_id);
+        match order {
+            Some(order) => {
+                order.metadata = Some(metadata);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn side(&self, side: BuyOrSell) -> &HashMap<P::Key, Vec<Order<P>>> {
+        match side {
+            BuyOrSell::Buy => &self.buy_orders,
+            BuyOrSell::Sell => &self.sell_orders,
+        }
+    }
+
+    // Orders resting at `price` on `side`, in queue order.
+    pub fn level(&self, side: BuyOrSell, price: P) -> &[Order<P>] {
+        self.side(side)
+            .get(&price.key())
+            .map_or(&[], |orders| orders.as_slice())
+    }
+
+    // Price levels on `side`, best first, each in queue order.
+    pub fn levels(&self, side: BuyOrSell) -> Vec<(P, &[Order<P>])> {
+        let mut levels: Vec<(&P::Key, &Vec<Order<P>>)> = self.side(side).iter().collect();
+        levels.sort_by_key(|(price, _)| **price);
+        if side == BuyOrSell::Buy {
+            levels.reverse();
+        }
+        levels
+            .into_iter()
+            .map(|(price, orders)| (P::from_key(*price), orders.as_slice()))
+            .collect()
+    }
+
+    pub fn level_count(&self, side: BuyOrSell) -> usize {
+        self.side(side).len()
+    }
+
+    // Every order resting on `side`, in no particular order.
+    pub fn orders(&self, side: BuyOrSell) -> impl Iterator<Item = &Order<P>> {
+        self.side(side).values().flatten()
+    }
+
+    // Whether an order fits the book's tick and lot sizes and level cap.
+    // The add methods do not check; order entry calls this first.
+    pub fn check_order(
+        &self,
+        side: BuyOrSell,
+        price: P,
+        quantity: u32,
+    ) -> Result<(), BookRejection<P>> {
+        if let Some(tick_size) = self.tick_size {
+            if !price.is_on_tick(tick_size) {
+                return Err(BookRejection::OffTick { price, tick_size });
+            }
+        }
+        if let Some(lot_size) = self.lot_size {
+            if quantity.checked_rem(lot_size) != Some(0) {
+                return Err(BookRejection::OffLot { quantity, lot_size });
+            }
+        }
+        if let Some(max_levels) = self.max_levels {
+            let levels = self.side(side);
+            if !levels.contains_key(&price.key()) && levels.len() >= max_levels {
+                return Err(BookRejection::TooManyLevels { max_levels });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn add_order(
+        &mut self,
+        order_type: BuyOrSell,
+        price: P,
+        quantity: u32,
+        timestamp: u64,
+    ) -> u64 {
+        self.add_order_with_tif(
+            order_type,
+            price,
+            quantity,
+            timestamp,
+            TimeInForce::GoodTillCancel,
+        )
+    }
+
+    pub fn add_order_with_tif(
+        &mut self,
+        order_type: BuyOrSell,
+        price: P,
+        quantity: u32,
+        timestamp: u64,
+        time_in_force: TimeInForce,
+    ) -> u64 {
+        self.insert_order(
+            None,
+            order_type,
+            price,
+            quantity,
+            timestamp,
+            time_in_force,
+            None,
+        )
+    }
+
+    // Place an order owned by `wallet`.
+    pub fn add_wallet_order(
+        &mut self,
+        wallet: Wallet,
+        order_type: BuyOrSell,
+        price: P,
+        quantity: u32,
+        timestamp: u64,
+        time_in_force: TimeInForce,
+    ) -> u64 {
+        self.insert_order(
+            Some(wallet),
+            order_type,
+            price,
+            quantity,
+            timestamp,
+            time_in_force,
+            None,
+        )
+    }
+
+    // Place an order carrying `metadata` into its fills and events.
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_tagged_order(
+        &mut self,
+        wallet: Option<Wallet>,
+        order_type: BuyOrSell,
+        price: P,
+        quantity: u32,
+        timestamp: u64,
+        time_in_force: TimeInForce,
+        metadata: OrderMetadata,
+    ) -> u64 {
+        self.insert_order(
+            wallet,
+            order_type,
+            price,
+            quantity,
+            timestamp,
+            time_in_force,
+            Some(metadata),
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn insert_order(
+        &mut self,
+        wallet: Option<Wallet>,
+        order_type: BuyOrSell,
+        price: P,
+        quantity: u32,
+        timestamp: u64,
+        time_in_force: TimeInForce,
+        metadata: Option<OrderMetadata>,
+    ) -> u64 {
         let id: u64 = self.next_order_id;
         self.next_order_id += 1;
 
-        let order = Order::new(id, quantity, price, timestamp);
+        self.events.push(BookEvent::OrderAdded {
+            order_id: id,
+            side: order_type,
+            price,
+            quantity,
+            timestamp,
+            metadata: metadata.clone(),
+        });
+        let order = Order {
+            wallet,
+            time_in_force,
+            metadata,
+            ..Order::new(id, quantity, price, timestamp)
+        };
 
         match order_type {
-            BuyOrSell::Buy => match self.buy_orders.get_mut(&OrderedFloat(price)) {
+            BuyOrSell::Buy => match self.buy_orders.get_mut(&price.key()) {
                 Some(orders) => {
                     orders.push(order);
                 }
                 None => {
-                    self.buy_orders.insert(OrderedFloat(price), vec![order]);
+                    self.buy_orders.insert(price.key(), vec![order]);
                 }
             },
-            BuyOrSell::Sell => match self.sell_orders.get_mut(&OrderedFloat(price)) {
+            BuyOrSell::Sell => match self.sell_orders.get_mut(&price.key()) {
                 Some(orders) => {
                     orders.push(order);
                 }
                 None => {
-                    self.sell_orders.insert(OrderedFloat(price), vec![order]);
+                    self.sell_orders.insert(price.key(), vec![order]);
                 }
             },
         }
+        id
+    }
+
+    // Cross the best bid with the best ask until the book is uncrossed,
+    // oldest order first within a level. Fills execute at the sell price.
+    pub fn match_orders(&mut self, timestamp: u64) -> Vec<Fill<P>> {
+        self.match_orders_limited(timestamp, usize::MAX)
+    }
+
+    // Match at most `max_fills` fills. Whatever is still crossed afterwards
+    // stays resting, in priority order, for the next call.
+    pub fn match_orders_limited(&mut self, timestamp: u64, max_fills: usize) -> Vec<Fill<P>> {
+        let mut fills = Vec::new();
+        self.match_orders_into(timestamp, max_fills, &mut fills);
+        fills
+    }
+
+    // `match_orders_limited` appending to a buffer the caller reuses across
+    // steps; returns the number of fills added. Once the buffer and the
+    // event log have grown to a step's size, matching does not allocate.
+    pub fn match_orders_into(
+        &mut self,
+        timestamp: u64,
+        max_fills: usize,
+        fills: &mut Vec<Fill<P>>,
+    ) -> usize {
+        self.match_while(timestamp, max_fills, None, fills)
+    }
+
+    // Uncross the book at one price: every bid at or above `price` trades
+    // with every ask at or below it, in priority order, all at `price`.
+    pub fn uncross_at(&mut self, price: P, timestamp: u64) -> Vec<Fill<P>> {
+        let mut fills = Vec::new();
+        self.uncross_at_into(price, timestamp, usize::MAX, &mut fills);
+        fills
+    }
+
+    // `uncross_at` stopping after `max_fills` fills; `is_crossed_at` tells
+    // whether it finished.
+    pub fn uncross_at_into(
+        &mut self,
+        price: P,
+        timestamp: u64,
+        max_fills: usize,
+        fills: &mut Vec<Fill<P>>,
+    ) -> usize {
+        self.match_while(timestamp, max_fills, Some(price), fills)
+    }
+
+    // Fills execute at the resting ask's price, or at `clearing_price` when
+    // given, which also bounds the levels that take part. Partially filled
+    // orders are reduced where they rest and keep their place.
+    fn match_while(
+        &mut self,
+        timestamp: u64,
+        max_fills: usize,
+        clearing_price: Option<P>,
+        fills: &mut Vec<Fill<P>>,
+    ) -> usize {
+        let mut count = 0;
+        while let (Some(buy_price), Some(sell_price)) =
+            (self.best_buy_price(), self.best_sell_price())
+        {
+            if buy_price < sell_price || count >= max_fills {
+                break;
+            }
+            if clearing_price
+                .is_some_and(|price| buy_price < price.key() || sell_price > price.key())
+            {
+                break;
+            }
+            let quantity = self.buy_orders[&buy_price][0]
+                .quantity
+                .min(self.sell_orders[&sell_price][0].quantity);
+            let buy = self.fill_best(BuyOrSell::Buy, quantity, timestamp).unwrap();
+            let sell = self
+                .fill_best(BuyOrSell::Sell, quantity, timestamp)
+                .unwrap();
+            fills.push(Fill {
+                buy_order_id: buy.id,
+                sell_order_id: sell.id,
+                buy_wallet: buy.wallet,
+                sell_wallet: sell.wallet,
+                price: clearing_price.unwrap_or(sell.price),
+                quantity,
+                buy_metadata: buy.metadata,
+                sell_metadata: sell.metadata,
+            });
+            count += 1;
+        }
+        count
+    }
+
+    // The order first in line on `side`.
+    pub fn best_order(&self, side: BuyOrSell) -> Option<&Order<P>> {
+        let (orders, price) = match side {
+            BuyOrSell::Buy => (&self.buy_orders, self.best_buy_price()?),
+            BuyOrSell::Sell => (&self.sell_orders, self.best_sell_price()?),
+        };
+        orders[&price].first()
+    }
+
+    // Take `quantity` from the order first in line on `side`, which must have
+    // that much left. A partially filled order keeps its place; the wallet is
+    // moved out of an order the fill completes.
+    pub(crate) fn fill_best(
+        &mut self,
+        side: BuyOrSell,
+        quantity: u32,
+        timestamp: u64,
+    ) -> Option<FilledOrder<P>> {
+        let (price, orders) = match side {
+            BuyOrSell::Buy => (self.best_buy_price()?, &mut self.buy_orders),
+            BuyOrSell::Sell => (self.best_sell_price()?, &mut self.sell_orders),
+        };
+        let level = orders.get_mut(&price).unwrap();
+        let order = &mut level[0];
+        order.quantity -= quantity;
+        self.events.push(BookEvent::OrderFilled {
+            order_id: order.id,
+            side,
+            price: order.price,
+            filled: quantity,
+            remaining: order.quantity,
+            timestamp,
+            metadata: order.metadata.clone(),
+        });
+        if order.quantity > 0 {
+            return Some(FilledOrder {
+                id: order.id,
+                price: order.price,
+                wallet: order.wallet.clone(),
+                metadata: order.metadata.clone(),
+            });
+        }
+        let order = level.remove(0);
+        if level.is_empty() {
+            orders.remove(&price);
+        }
+        Some(FilledOrder {
+            id: order.id,
+            price: order.price,
+            wallet: order.wallet,
+            metadata: order.metadata,
+        })
+    }
+
+    // Give a resting order a priority fee and move it ahead of every order at
+    // its price paying less, keeping time priority among equal fees.
+    pub(crate) fn set_priority_fee(
+        &mut self,
+        order_id: u64,
+        priority_fee: u64,
+        timestamp: u64,
+    ) -> bool {
+        let Some((side, order)) = self.requeue(order_id, priority_fee) else {
+            return false;
+        };
+        self.events.push(BookEvent::PriorityFeeSet {
+            order_id,
+            side,
+            price: order.price,
+            quantity: order.quantity,
+            priority_fee,
+            timestamp,
+            metadata: order.metadata,
+        });
+        true
+    }
+
+    // `set_priority_fee` for a book rebuilt from its resting orders, where
+    // the fee is not news.
+    pub(crate) fn restore_priority_fee(&mut self, order_id: u64, priority_fee: u64) -> bool {
+        self.requeue(order_id, priority_fee).is_some()
+    }
+
+    fn requeue(&mut self, order_id: u64, priority_fee: u64) -> Option<(BuyOrSell, Order<P>)> {
+        for (side, orders) in [
+            (BuyOrSell::Buy, &mut self.buy_orders),
+            (BuyOrSell::Sell, &mut self.sell_orders),
+        ] {
+            for level in orders.values_mut() {
+                let Some(index) = level.iter().position(|order| order.id == order_id) else {
+                    continue;
+                };
+                let mut order = level.remove(index);
+                order.priority_fee = priority_fee;
+                let position = level
+                    .iter()
+                    .position(|other| other.priority_fee < priority_fee)
+                    .unwrap_or(level.len());
+                level.insert(position, order.clone());
+                return Some((side, order));
+            }
+        }
+        None
+    }
+
+    // Whether the best bid meets or crosses the best ask.
+    pub fn is_crossed(&self) -> bool {
+        match (self.best_buy_price(), self.best_sell_price()) {
+            (Some(bid), Some(ask)) => bid >= ask,
+            _ => false,
+        }
+    }
+
+    // Whether uncrossing at `price` would still fill anything.
+    pub fn is_crossed_at(&self, price: P) -> bool {
+        match (self.best_buy_price(), self.best_sell_price()) {
+            (Some(bid), Some(ask)) => bid >= ask && bid >= price.key() && ask <= price.key(),
+            _ => false,
+        }
+    }
+
+    // Remove a resting order from the book.
+    pub fn cancel_order(&mut self, order_id: u64, timestamp: u64) -> Option<Order<P>> {
+        for (side, orders) in [
+            (BuyOrSell::Buy, &mut self.buy_orders),
+            (BuyOrSell::Sell, &mut self.sell_orders),
+        ] {
+            let found = orders.iter().find_map(|(price, level)| {
+                level
+                    .iter()
+                    .position(|order| order.id == order_id)
+                    .map(|index| (*price, index))
+            });
+            if let Some((price, index)) = found {
+                let level = orders.get_mut(&price).unwrap();
+                let order = level.remove(index);
+                if level.is_empty() {
+                    orders.remove(&price);
+                }
+                self.events.push(BookEvent::OrderCancelled {
+                    order_id,
+                    side,
+                    price: order.price,
+                    remaining: order.quantity,
+                    timestamp,
+                    metadata: order.metadata.clone(),
+                });
+                return Some(order);
+            }
+        }
+        None
+    }
+
+    // Side and current state of a resting order.
+    pub fn find_order(&self, order_id: u64) -> Option<(BuyOrSell, &Order<P>)> {
+        [
+            (BuyOrSell::Buy, &self.buy_orders),
+            (BuyOrSell::Sell, &self.sell_orders),
+        ]
+        .into_iter()
+        .find_map(|(side, orders)| {
+            orders
+                .values()
+                .flatten()
+                .find(|order| order.id == order_id)
+                .map(|order| (side, order))
+        })
+    }
+
+    // Cancel a resting order and enter a new one on the same side, for the
+    // same wallet, time in force and metadata. The new order joins the back of its
+    // level. Returns its id.
+    pub fn replace_order(
+        &mut self,
+        order_id: u64,
+        price: P,
+        quantity: u32,
+        timestamp: u64,
+    ) -> Option<u64> {
+        let (side, _) = self.find_order(order_id)?;
+        let order = self.cancel_order(order_id, timestamp)?;
+        Some(self.insert_order(
+            order.wallet,
+            side,
+            price,
+            quantity,
+            timestamp,
+            order.time_in_force,
+            order.metadata,
+        ))
+    }
+
+    // Cancel every resting day order, returning their ids.
+    pub fn expire_day_orders(&mut self, timestamp: u64) -> Vec<u64> {
+        self.cancel_where(timestamp, |order| order.time_in_force == TimeInForce::Day)
+    }
+
+    // Cancel every resting order, returning their ids.
+    pub fn cancel_all(&mut self, timestamp: u64) -> Vec<u64> {
+        self.cancel_where(timestamp, |_| true)
+    }
+
+    // Cancel every resting order owned by `wallet`, returning their ids.
+    pub fn cancel_wallet_orders(&mut self, wallet: &Wallet, timestamp: u64) -> Vec<u64> {
+        self.cancel_where(timestamp, |order| order.wallet.as_ref() == Some(wallet))
+    }
+
+    fn cancel_where(&mut self, timestamp: u64, cancel: impl Fn(&Order<P>) -> bool) -> Vec<u64> {
+        let mut cancelled: Vec<u64> = self
+            .buy_orders
+            .values()
+            .chain(self.sell_orders.values())
+            .flatten()
+            .filter(|order| cancel(order))
+            .map(|order| order.id)
+            .collect();
+        cancelled.sort();
+        for order_id in cancelled.iter() {
+            self.cancel_order(*order_id, timestamp);
+        }
+        cancelled
+    }
+
+    // Whether every resting quantity still fits in a u32 after scaling by
+    // `numerator / denominator`.
+    pub(crate) fn can_rescale_quantities(&self, numerator: u64, denominator: u64) -> bool {
+        self.buy_orders
+            .values()
+            .chain(self.sell_orders.values())
+            .flatten()
+            .all(|order| {
+                order.quantity as u128 * numerator as u128 / denominator as u128 <= u32::MAX as u128
+            })
+    }
+
+    // Resting orders owned by `wallet` on both sides.
+    pub fn wallet_orders<'a>(&'a self, wallet: &'a Wallet) -> impl Iterator<Item = &'a Order<P>> {
+        self.buy_orders
+            .values()
+            .chain(self.sell_orders.values())
+            .flatten()
+            .filter(move |order| order.wallet.as_ref() == Some(wallet))
+    }
+
+    // L3 event stream of everything applied to this book.
+    pub fn events(&self) -> &[BookEvent<P>] {
+        &self.events
+    }
+
+    // Resting orders with their side, in priority order within each level.
+    pub(crate) fn resting_orders(&self) -> Vec<(BuyOrSell, Order<P>)> {
+        let mut orders: Vec<(BuyOrSell, Order<P>)> = self
+            .buy_orders
+            .values()
+            .flatten()
+            .map(|order| (BuyOrSell::Buy, order.clone()))
+            .chain(
+                self.sell_orders
+                    .values()
+                    .flatten()
+                    .map(|order| (BuyOrSell::Sell, order.clone())),
+            )
+            .collect();
+        orders.sort_by_key(|(_, order)| order.id);
+        orders
+    }
+
+    pub(crate) fn next_order_id(&self) -> u64 {
+        self.next_order_id
+    }
+
+    // Rebuild a book from resting orders, without events.
+    pub(crate) fn restore(next_order_id: u64, orders: Vec<(BuyOrSell, Order<P>)>) -> OrderBook<P> {
+        let mut orderbook = OrderBook {
+            next_order_id,
+            ..OrderBook::default()
+        };
+        for (side, order) in orders {
+            let levels = match side {
+                BuyOrSell::Buy => &mut orderbook.buy_orders,
+                BuyOrSell::Sell => &mut orderbook.sell_orders,
+            };
+            levels.entry(order.price.key()).or_default().push(order);
+        }
+        orderbook
+    }
+
+    // Continue numbering events after `sequence`, as of a restored book.
+    pub(crate) fn resume_sequence(&mut self, sequence: u64) {
+        self.evicted_events = sequence.saturating_sub(self.events.len() as u64);
+    }
+
+    // Drop the oldest `count` events.
+    pub(crate) fn evict_events(&mut self, count: usize) {
+        let count = count.min(self.events.len());
+        self.events.drain(..count);
+        self.evicted_events += count as u64;
+    }
+
+    // Sequence number of the latest event; events are numbered from 1.
+    pub fn sequence(&self) -> u64 {
+        self.evicted_events + self.events.len() as u64
+    }
+
+    // Events after `sequence`, numbered. None once some of them have been
+    // evicted.
+    pub fn events_since(&self, sequence: u64) -> Option<Vec<(u64, BookEvent<P>)>> {
+        let start = sequence.checked_sub(self.evicted_events)? as usize;
+        Some(
+            self.events
+                .get(start..)
+                .unwrap_or(&[])
+                .iter()
+                .enumerate()
+                .map(|(index, event)| (sequence + 1 + index as u64, event.clone()))
+                .collect(),
+        )
+    }
+
+    // The event after `sequence`, borrowed rather than cloned. None once it
+    // has been evicted, Some(None) while there is none yet.
+    pub fn event_after(&self, sequence: u64) -> Option<Option<(u64, &BookEvent<P>)>> {
+        let index = sequence.checked_sub(self.evicted_events)? as usize;
+        Some(self.events.get(index).map(|event| (sequence + 1, event)))
+    }
+
+    // Aggregate resting quantity per price level.
+    pub fn depth_snapshot(&self) -> DepthSnapshot {
+        let levels = |side: &HashMap<P::Key, Vec<Order<P>>>| -> Vec<DepthLevel> {
+            let mut levels: Vec<DepthLevel> = side
+                .iter()
+                .filter(|(_, orders)| !orders.is_empty())
+                .map(|(price, orders)| DepthLevel {
+                    price: P::from_key(*price).to_f64(),
+                    quantity: orders.iter().map(|order| order.quantity as u64).sum(),
+                })
+                .collect();
+            levels.sort_by(|a, b| a.price.total_cmp(&b.price));
+            levels
+        };
+        let mut bids = levels(&self.buy_orders);
+        bids.reverse();
+        DepthSnapshot {
+            bids,
+            asks: levels(&self.sell_orders),
+        }
+    }
+
+    // Level changes that turn `earlier` into the book as it stands.
+    pub fn diff(&self, earlier: &DepthSnapshot) -> Vec<LevelChange> {
+        self.depth_snapshot().diff(earlier)
     }
 }
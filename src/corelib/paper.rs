@@ -0,0 +1,246 @@
+use std::collections::{BTreeMap, HashMap};
+
+use super::connector::{ConnectorError, ExchangeConnector};
+use super::order::BuyOrSell;
+use super::token::TokenTicker;
+use super::trade::Trade;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaperOrder {
+    pub id: u64,
+    pub ticker: TokenTicker,
+    pub side: BuyOrSell,
+    pub price: f64,
+    pub quantity: u64,
+    pub filled: u64,
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaperFill {
+    pub order_id: u64,
+    pub ticker: TokenTicker,
+    pub side: BuyOrSell,
+    pub price: f64,
+    pub quantity: u64,
+    // Id and time of the market trade that triggered the fill.
+    pub trade_id: u64,
+    pub timestamp: u64,
+}
+
+// Simulated orders filled against imported market trades. Nothing touches
+// an order book: a resting buy fills at its limit once the market trades at
+// or below it, a sell once the market trades at or above it, for at most the
+// traded quantity. Orders are filled in price then time priority.
+#[derive(Debug, Clone)]
+pub struct PaperTrader {
+    orders: BTreeMap<u64, PaperOrder>,
+    fills: Vec<PaperFill>,
+    next_order_id: u64,
+}
+
+impl Default for PaperTrader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PaperTrader {
+    pub fn new() -> PaperTrader {
+        PaperTrader {
+            orders: BTreeMap::new(),
+            fills: Vec::new(),
+            next_order_id: 1,
+        }
+    }
+
+    pub fn submit(
+        &mut self,
+        ticker: TokenTicker,
+        side: BuyOrSell,
+        price: f64,
+        quantity: u64,
+        timestamp: u64,
+    ) -> u64 {
+        let id = self.next_order_id;
+        self.next_order_id += 1;
+        self.orders.insert(
+            id,
+            PaperOrder {
+                id,
+                ticker,
+                side,
+                price,
+                quantity,
+                filled: 0,
+                timestamp,
+            },
+        );
+        id
+    }
+
+    pub fn cancel(&mut self, order_id: u64) -> Option<PaperOrder> {
+        self.orders.remove(&order_id)
+    }
+
+    pub fn open_orders(&self) -> impl Iterator<Item = &PaperOrder> {
+        self.orders.values()
+    }
+
+    pub fn fills(&self) -> &[PaperFill] {
+        &self.fills
+    }
+
+    // Net filled quantity, long positive.
+    pub fn position(&self, ticker: &TokenTicker) -> i64 {
+        self.fills
+            .iter()
+            .filter(|fill| &fill.ticker == ticker)
+            .map(|fill| match fill.side {
+                BuyOrSell::Buy => fill.quantity as i64,
+                BuyOrSell::Sell => -(fill.quantity as i64),
+            })
+            .sum()
+    }
+
+    // Quote spent on buys minus quote received on sells.
+    pub fn cash_flow(&self) -> HashMap<TokenTicker, f64> {
+        let mut flows = HashMap::new();
+        for fill in self.fills.iter() {
+            let notional = fill.price * fill.quantity as f64;
+            *flows.entry(fill.ticker.clone()).or_insert(0.0) += match fill.side {
+                BuyOrSell::Buy => -notional,
+                BuyOrSell::Sell => notional,
+            };
+        }
+        flows
+    }
+
+    // Fill whatever the market trade crosses.
+    pub fn on_trade(&mut self, trade: &Trade) -> Vec<PaperFill> {
+        let mut crossed: Vec<&PaperOrder> = self
+            .orders
+            .values()
+            .filter(|order| order.ticker == trade.ticker)
+            .filter(|order| match order.side {
+                BuyOrSell::Buy => trade.price <= order.price,
+                BuyOrSell::Sell => trade.price >= order.price,
+            })
+            .collect();
+        crossed.sort_by(|a, b| {
+            let price = match a.side {
+                BuyOrSell::Buy => b.price.total_cmp(&a.price),
+                BuyOrSell::Sell => a.price.total_cmp(&b.price),
+            };
+            price
+                .then(a.timestamp.cmp(&b.timestamp))
+                .then(a.id.cmp(&b.id))
+        });
+        // buys and sells are filled independently from the same trade
+        let crossed: Vec<u64> = crossed.iter().map(|order| order.id).collect();
+        let mut available = HashMap::from([
+            (BuyOrSell::Buy, trade.quantity),
+            (BuyOrSell::Sell, trade.quantity),
+        ]);
+        let mut fills = Vec::new();
+        for id in crossed {
+            let order = self.orders.get_mut(&id).unwrap();
+            let available = available.get_mut(&order.side).unwrap();
+            let quantity = (order.quantity - order.filled).min(*available);
+            if quantity == 0 {
+                continue;
+            }
+            *available -= quantity;
+            order.filled += quantity;
+            fills.push(PaperFill {
+                order_id: id,
+                ticker: order.ticker.clone(),
+                side: order.side,
+                price: order.price,
+                quantity,
+                trade_id: trade.id,
+                timestamp: trade.timestamp,
+            });
+            if order.filled == order.quantity {
+                self.orders.remove(&id);
+            }
+        }
+        self.fills.extend(fills.iter().cloned());
+        fills
+    }
+
+    // Poll a venue's live trades and fill against them.
+    pub fn poll(
+        &mut self,
+        connector: &mut dyn ExchangeConnector,
+        ticker: &TokenTicker,
+    ) -> Result<Vec<PaperFill>, ConnectorError> {
+        let mut fills = Vec::new();
+        for trade in connector.poll_trades(ticker)? {
+            fills.extend(self.on_trade(&trade));
+        }
+        Ok(fills)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::connector::MockConnector;
+    use crate::corelib::depth::DepthSnapshot;
+    use crate::corelib::trade::TradeKind;
+
+    fn market_trade(id: u64, price: f64, quantity: u64) -> Trade {
+        Trade {
+            id,
+            ticker: TokenTicker::ETH,
+            price,
+            quantity,
+            buyer: None,
+            seller: None,
+            buy_order_id: None,
+            sell_order_id: None,
+            timestamp: id * 1_000,
+            kind: TradeKind::Lit,
+            buy_metadata: None,
+            sell_metadata: None,
+            settled_notional: None,
+        }
+    }
+
+    #[test]
+    fn test_fills_when_market_crosses() {
+        let mut venue = MockConnector::new("external");
+        venue.set_book(TokenTicker::ETH, DepthSnapshot::default());
+        let mut paper = PaperTrader::new();
+        let low = paper.submit(TokenTicker::ETH, BuyOrSell::Buy, 95.0, 5, 0);
+        let high = paper.submit(TokenTicker::ETH, BuyOrSell::Buy, 99.0, 5, 0);
+        let sell = paper.submit(TokenTicker::ETH, BuyOrSell::Sell, 110.0, 2, 0);
+
+        venue.push_trade(market_trade(1, 100.0, 10));
+        venue.push_trade(market_trade(2, 98.0, 3));
+        let fills = paper.poll(&mut venue, &TokenTicker::ETH).unwrap();
+        assert_eq!(fills.len(), 1);
+        assert_eq!((fills[0].order_id, fills[0].quantity), (high, 3));
+
+        // the best bid fills first
+        venue.push_trade(market_trade(3, 94.0, 4));
+        let fills = paper.poll(&mut venue, &TokenTicker::ETH).unwrap();
+        let filled: Vec<(u64, u64)> = fills
+            .iter()
+            .map(|fill| (fill.order_id, fill.quantity))
+            .collect();
+        assert_eq!(filled, vec![(high, 2), (low, 2)]);
+        assert_eq!(fills[0].price, 99.0);
+
+        assert_eq!(paper.position(&TokenTicker::ETH), 7);
+        assert_eq!(
+            paper.cash_flow()[&TokenTicker::ETH],
+            -(99.0 * 5.0 + 95.0 * 2.0)
+        );
+        let open: Vec<u64> = paper.open_orders().map(|order| order.id).collect();
+        assert_eq!(open, vec![low, sell]);
+        assert!(paper.cancel(sell).is_some());
+    }
+}
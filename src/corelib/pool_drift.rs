@@ -0,0 +1,191 @@
+use super::audit::AuditAction;
+use super::engine::TradeEngine;
+use super::ledger::LedgerError;
+use super::token::{Pair, TokenTicker};
+
+const BPS: f64 = 10_000.0;
+
+// A pool whose implied price strayed from the market.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DriftAlert {
+    pub pair: Pair,
+    // Base token priced against the engine's quote token.
+    pub base: TokenTicker,
+    pub pool_price: f64,
+    pub reference_price: f64,
+    // Positive when the pool prices the base token above the reference.
+    pub drift_bps: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rebalance {
+    pub pair: Pair,
+    pub token_in: TokenTicker,
+    pub amount_in: u64,
+    pub amount_out: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RebalanceError {
+    UnknownPool,
+    // Neither side of the pair is the engine's quote token, or nothing
+    // prices the base token.
+    NoReferencePrice,
+    // The pool is already at the reference price.
+    InLine,
+    Ledger(LedgerError),
+}
+
+impl From<LedgerError> for RebalanceError {
+    fn from(err: LedgerError) -> Self {
+        RebalanceError::Ledger(err)
+    }
+}
+
+impl TradeEngine {
+    // Price a pool is checked against: the oracle index when published,
+    // else the order book reference price.
+    pub fn pool_reference_price(&self, base: &TokenTicker) -> Option<f64> {
+        self.index
+            .latest(base)
+            .or_else(|| self.reference_price(base))
+    }
+
+    // Base token of a pool quoted in the engine's quote token, with the
+    // pool's reserves of (base, quote).
+    fn quoted_reserves(&self, pair: &Pair) -> Option<(TokenTicker, u64, u64)> {
+        let pool = self.amm_pools.get(pair)?;
+        let base = if pair.ticker_b == self.quote_token {
+            &pair.ticker_a
+        } else if pair.ticker_a == self.quote_token {
+            &pair.ticker_b
+        } else {
+            return None;
+        };
+        let reserve_base = pool.reserve(base).filter(|reserve| *reserve > 0)?;
+        let reserve_quote = pool
+            .reserve(&self.quote_token)
+            .filter(|reserve| *reserve > 0)?;
+        Some((base.clone(), reserve_base, reserve_quote))
+    }
+
+    // Pools quoted in the engine's quote token whose implied price is more
+    // than `threshold_bps` away from the reference price, largest drift
+    // first.
+    pub fn pool_drift_alerts(&self, threshold_bps: f64) -> Vec<DriftAlert> {
+        let mut alerts: Vec<DriftAlert> = self
+            .amm_pools
+            .keys()
+            .filter_map(|pair| {
+                let (base, reserve_base, reserve_quote) = self.quoted_reserves(pair)?;
+                let reference_price = self.pool_reference_price(&base)?;
+                let pool_price = reserve_quote as f64 / reserve_base as f64;
+                let drift_bps = (pool_price - reference_price) / reference_price * BPS;
+                (drift_bps.abs() > threshold_bps).then(|| DriftAlert {
+                    pair: pair.clone(),
+                    base,
+                    pool_price,
+                    reference_price,
+                    drift_bps,
+                })
+            })
+            .collect();
+        alerts.sort_by(|a, b| b.drift_bps.abs().total_cmp(&a.drift_bps.abs()));
+        alerts
+    }
+
+    // Keeper: trade treasury funds against the pool until its implied price
+    // matches the reference price, ignoring the pool fee. The treasury
+    // keeps the proceeds.
+    pub fn rebalance_pool(&mut self, pair: &Pair) -> Result<Rebalance, RebalanceError> {
+        if !self.amm_pools.contains_key(pair) {
+            return Err(RebalanceError::UnknownPool);
+        }
+        let (base, reserve_base, reserve_quote) = self
+            .quoted_reserves(pair)
+            .ok_or(RebalanceError::NoReferencePrice)?;
+        let reference_price = self
+            .pool_reference_price(&base)
+            .ok_or(RebalanceError::NoReferencePrice)?;
+        // constant product: the target reserves keep x * y and set y / x
+        let product = reserve_base as f64 * reserve_quote as f64;
+        let target_base = (product / reference_price).sqrt() as u64;
+        let target_quote = (product * reference_price).sqrt() as u64;
+        let (token_in, token_out, amount_in) = if target_base > reserve_base {
+            (base, self.quote_token.clone(), target_base - reserve_base)
+        } else if target_quote > reserve_quote {
+            (self.quote_token.clone(), base, target_quote - reserve_quote)
+        } else {
+            return Err(RebalanceError::InLine);
+        };
+
+        let treasury = self.ledger.treasury().clone();
+        let pool = self.amm_pools.get_mut(pair).unwrap();
+        let amount_out = pool
+            .quote_exact_in(&token_in, &token_out, amount_in)
+            .filter(|amount_out| *amount_out > 0)
+            .ok_or(RebalanceError::InLine)?;
+        self.ledger.debit_free(&treasury, &token_in, amount_in)?;
+        pool.swap_exact_in(&token_in, &token_out, amount_in);
+        self.ledger.credit(&treasury, &token_out, amount_out);
+
+        let now = self.now();
+        self.audit_log.record(
+            now,
+            AuditAction::PoolRebalanced {
+                pair: pair.clone(),
+                token_in: token_in.clone(),
+                amount_in,
+                amount_out,
+            },
+        );
+        Ok(Rebalance {
+            pair: pair.clone(),
+            token_in,
+            amount_in,
+            amount_out,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_drift_alert_and_keeper_rebalance() {
+        let mut engine = TradeEngine::new();
+        let pair = Pair::new(TokenTicker::ETH, TokenTicker::USDT);
+        let pool = engine.amm_pools.entry(pair.clone()).or_default();
+        pool.add_liquidity(TokenTicker::ETH, 1_000);
+        pool.add_liquidity(TokenTicker::USDT, 2_500_000);
+        let treasury = engine.ledger.treasury().clone();
+        engine
+            .ledger
+            .deposit(treasury.clone(), TokenTicker::ETH, 1_000)
+            .unwrap();
+
+        assert!(engine.pool_drift_alerts(100.0).is_empty());
+        engine.publish_index_price(TokenTicker::ETH, 2_000.0);
+        let alerts = engine.pool_drift_alerts(100.0);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].pool_price, 2_500.0);
+        assert_eq!(alerts[0].drift_bps, 2_500.0);
+        assert!(engine.pool_drift_alerts(3_000.0).is_empty());
+
+        // the pool overprices ETH, so the keeper sells ETH into it
+        let rebalance = engine.rebalance_pool(&pair).unwrap();
+        assert_eq!(rebalance.token_in, TokenTicker::ETH);
+        assert_eq!(rebalance.amount_in, 118);
+        assert!(engine.pool_drift_alerts(10.0).is_empty());
+        assert_eq!(
+            engine.ledger.free_balance(&treasury, &TokenTicker::USDT),
+            rebalance.amount_out
+        );
+        assert!(matches!(
+            engine.audit_log.records()[0].action,
+            AuditAction::PoolRebalanced { amount_in: 118, .. }
+        ));
+    }
+}
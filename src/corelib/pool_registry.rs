@@ -0,0 +1,247 @@
+use std::collections::HashSet;
+
+use super::amm::MINIMUM_LIQUIDITY;
+use super::engine::{DeadlineExpired, TradeEngine};
+use super::ledger::LedgerError;
+use super::order::Wallet;
+use super::token::Pair;
+
+// Fees a pool may charge. A pair can have one pool per tier.
+pub const FEE_TIERS_BPS: [u64; 3] = [5, 30, 100];
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum CreatorPolicy {
+    #[default]
+    Permissionless,
+    AllowListed(HashSet<Wallet>),
+}
+
+// Rules a wallet must satisfy to open a new pool.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PoolCreationPolicy {
+    pub creators: CreatorPolicy,
+    // LP tokens the initial deposit must mint, i.e. the sum of both sides.
+    pub min_initial_liquidity: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolInfo {
+    pub pair: Pair,
+    pub creator: Wallet,
+    pub created_at: u64,
+    pub fee_bps: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PoolError {
+    AlreadyExists,
+    UnsupportedFeeTier { fee_bps: u64 },
+    DeadlineExpired(DeadlineExpired),
+    CreatorNotAllowed,
+    InsufficientInitialLiquidity { minimum: u64, provided: u64 },
+    Ledger(LedgerError),
+}
+
+impl From<LedgerError> for PoolError {
+    fn from(err: LedgerError) -> Self {
+        PoolError::Ledger(err)
+    }
+}
+
+impl From<DeadlineExpired> for PoolError {
+    fn from(err: DeadlineExpired) -> Self {
+        PoolError::DeadlineExpired(err)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PoolRegistry {
+    pub policy: PoolCreationPolicy,
+    // In creation order.
+    pools: Vec<PoolInfo>,
+}
+
+impl PoolRegistry {
+    pub fn new() -> PoolRegistry {
+        PoolRegistry::default()
+    }
+}
+
+impl TradeEngine {
+    // Open a pool for `pair` at one of the fee tiers, funded from the
+    // creator's free balances. The pair's first pool is its primary pool;
+    // later ones at other tiers trade in parallel. The creator receives the
+    // initial LP tokens; returns how many.
+    pub fn create_pool(
+        &mut self,
+        creator: &Wallet,
+        pair: Pair,
+        fee_bps: u64,
+        amount_a: u64,
+        amount_b: u64,
+        deadline: u64,
+    ) -> Result<u64, PoolError> {
+        self.check_deadline(deadline)?;
+        if !FEE_TIERS_BPS.contains(&fee_bps) {
+            return Err(PoolError::UnsupportedFeeTier { fee_bps });
+        }
+        let policy = &self.pool_registry.policy;
+        if let CreatorPolicy::AllowListed(creators) = &policy.creators {
+            if !creators.contains(creator) {
+                return Err(PoolError::CreatorNotAllowed);
+            }
+        }
+        // the first deposit must mint more than the locked minimum
+        let minimum = policy.min_initial_liquidity.max(MINIMUM_LIQUIDITY + 1);
+        let provided = amount_a.saturating_add(amount_b);
+        if amount_a == 0 || amount_b == 0 || provided < minimum {
+            return Err(PoolError::InsufficientInitialLiquidity { minimum, provided });
+        }
+        let existing = self.pools_for(&pair.ticker_a, &pair.ticker_b);
+        if existing
+            .iter()
+            .filter_map(|(pair, tier)| self.pool(pair, *tier))
+            .any(|pool| pool.fee_bps == fee_bps)
+        {
+            return Err(PoolError::AlreadyExists);
+        }
+        let available = self.ledger.free_balance(creator, &pair.ticker_b);
+        if available < amount_b {
+            return Err(LedgerError::InsufficientFree {
+                token: pair.ticker_b.clone(),
+                available,
+                requested: amount_b,
+            }
+            .into());
+        }
+        self.ledger.debit_free(creator, &pair.ticker_a, amount_a)?;
+        self.ledger.debit_free(creator, &pair.ticker_b, amount_b)?;
+
+        let created_at = self.now();
+        let pool = if existing.is_empty() {
+            self.amm_pools.entry(pair.clone()).or_default()
+        } else {
+            self.tier_pools.entry((pair.clone(), fee_bps)).or_default()
+        };
+        pool.fee_bps = fee_bps;
+        let lp_minted = pool.deposit(creator, &pair, amount_a, amount_b, deadline, created_at);
+        self.pool_registry.pools.push(PoolInfo {
+            pair,
+            creator: creator.clone(),
+            created_at,
+            fee_bps,
+        });
+        Ok(lp_minted)
+    }
+
+    // Pools opened through `create_pool`, oldest first.
+    pub fn list_pools(&self) -> &[PoolInfo] {
+        &self.pool_registry.pools
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::clock::SimulatedClock;
+    use crate::corelib::token::TokenTicker;
+
+    #[test]
+    fn test_create_pool_under_policy() {
+        let mut engine = TradeEngine::with_clock(Box::new(SimulatedClock::new(7_000)));
+        let listed = Wallet::new(String::from("poolcreator"));
+        let stranger = Wallet::new(String::from("poolstranger"));
+        for wallet in [&listed, &stranger] {
+            engine
+                .ledger
+                .deposit(wallet.clone(), TokenTicker::ETH, 100)
+                .unwrap();
+            engine
+                .ledger
+                .deposit(wallet.clone(), TokenTicker::USDT, 200_000)
+                .unwrap();
+        }
+        engine.pool_registry.policy = PoolCreationPolicy {
+            creators: CreatorPolicy::AllowListed(HashSet::from([listed.clone()])),
+            min_initial_liquidity: 2_000,
+        };
+        let pair = Pair::new(TokenTicker::ETH, TokenTicker::USDT);
+
+        assert_eq!(
+            engine.create_pool(&stranger, pair.clone(), 30, 10, 20_000, u64::MAX),
+            Err(PoolError::CreatorNotAllowed)
+        );
+        assert_eq!(
+            engine.create_pool(&listed, pair.clone(), 30, 1, 500, u64::MAX),
+            Err(PoolError::InsufficientInitialLiquidity {
+                minimum: 2_000,
+                provided: 501,
+            })
+        );
+        assert_eq!(
+            engine.create_pool(&listed, pair.clone(), 30, 10, 20_000, u64::MAX),
+            Ok(19_010)
+        );
+        assert_eq!(
+            engine.create_pool(
+                &listed,
+                Pair::new(TokenTicker::USDT, TokenTicker::ETH),
+                30,
+                20_000,
+                10,
+                u64::MAX
+            ),
+            Err(PoolError::AlreadyExists)
+        );
+        assert_eq!(
+            engine.create_pool(&listed, pair.clone(), 25, 10, 20_000, u64::MAX),
+            Err(PoolError::UnsupportedFeeTier { fee_bps: 25 })
+        );
+
+        assert_eq!(
+            engine.list_pools(),
+            &[PoolInfo {
+                pair: pair.clone(),
+                creator: listed.clone(),
+                created_at: 7_000,
+                fee_bps: 30,
+            }]
+        );
+        assert_eq!(engine.amm_pools[&pair].lp_balance(&listed, &pair), 19_010);
+        assert_eq!(engine.ledger.free_balance(&listed, &TokenTicker::ETH), 90);
+    }
+
+    #[test]
+    fn test_parallel_pools_at_other_tiers() {
+        let mut engine = TradeEngine::new();
+        let creator = Wallet::new(String::from("tiercreator"));
+        engine
+            .ledger
+            .deposit(creator.clone(), TokenTicker::ETH, 100)
+            .unwrap();
+        engine
+            .ledger
+            .deposit(creator.clone(), TokenTicker::USDT, 200_000)
+            .unwrap();
+        let pair = Pair::new(TokenTicker::ETH, TokenTicker::USDT);
+
+        engine
+            .create_pool(&creator, pair.clone(), 30, 10, 20_000, u64::MAX)
+            .unwrap();
+        engine
+            .create_pool(&creator, pair.clone(), 5, 10, 20_000, u64::MAX)
+            .unwrap();
+        assert_eq!(engine.tier_pools[&(pair.clone(), 5)].fee_bps, 5);
+        assert_eq!(
+            engine.pools_for(&TokenTicker::USDT, &TokenTicker::ETH),
+            vec![(pair.clone(), None), (pair, Some(5))]
+        );
+        let fees: Vec<u64> = engine
+            .list_pools()
+            .iter()
+            .map(|pool| pool.fee_bps)
+            .collect();
+        assert_eq!(fees, vec![30, 5]);
+    }
+}
@@ -0,0 +1,123 @@
+use super::engine::TradeEngine;
+use super::lp_token::LpError;
+use super::order::Wallet;
+use super::token::Pair;
+
+// Ledger account of a pool. Credits sent here, e.g. donations or transfers
+// from outside the engine, are not part of the pool's reserves until the
+// pool is synced.
+pub fn pool_account(pair: &Pair) -> Wallet {
+    Wallet::new(format!("pool:{:?}/{:?}", pair.ticker_a, pair.ticker_b))
+}
+
+impl TradeEngine {
+    // Free balances of the pool account of `pair`, side a then side b.
+    pub fn pool_account_balances(&self, pair: &Pair) -> (u64, u64) {
+        let account = pool_account(pair);
+        (
+            self.ledger.free_balance(&account, &pair.ticker_a),
+            self.ledger.free_balance(&account, &pair.ticker_b),
+        )
+    }
+
+    // Fold everything credited to the pool account into the reserves, so
+    // the recorded reserves match what the pool holds. Returns the amounts
+    // absorbed.
+    pub fn sync(&mut self, pair: &Pair, deadline: u64) -> Result<(u64, u64), LpError> {
+        self.check_deadline(deadline)?;
+        if !self.amm_pools.contains_key(pair) {
+            return Err(LpError::UnknownPool);
+        }
+        let account = pool_account(pair);
+        let (amount_a, amount_b) = self.pool_account_balances(pair);
+        let pool = self.amm_pools.get_mut(pair).unwrap();
+        for (token, amount) in [(&pair.ticker_a, amount_a), (&pair.ticker_b, amount_b)] {
+            if amount > 0 {
+                self.ledger.debit_free(&account, token, amount)?;
+                pool.add_liquidity(token.clone(), amount);
+            }
+        }
+        Ok((amount_a, amount_b))
+    }
+
+    // Pay out whatever the pool account holds beyond the reserves, leaving
+    // the reserves as they are. Returns the amounts sent to `to`.
+    pub fn skim(&mut self, pair: &Pair, to: &Wallet, deadline: u64) -> Result<(u64, u64), LpError> {
+        self.check_deadline(deadline)?;
+        if !self.amm_pools.contains_key(pair) {
+            return Err(LpError::UnknownPool);
+        }
+        let account = pool_account(pair);
+        let (amount_a, amount_b) = self.pool_account_balances(pair);
+        for (token, amount) in [(&pair.ticker_a, amount_a), (&pair.ticker_b, amount_b)] {
+            self.ledger.transfer(&account, to, token, amount)?;
+        }
+        Ok((amount_a, amount_b))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::token::TokenTicker;
+
+    #[test]
+    fn test_sync_absorbs_and_skim_pays_out_donations() {
+        let mut engine = TradeEngine::new();
+        let pair = Pair::new(TokenTicker::ETH, TokenTicker::USDT);
+        let pool = engine.amm_pools.entry(pair.clone()).or_default();
+        pool.add_liquidity(TokenTicker::ETH, 100);
+        pool.add_liquidity(TokenTicker::USDT, 200_000);
+        let keeper = Wallet::new(String::from("skimkeeper"));
+        let donor = Wallet::new(String::from("pooldonor"));
+        engine
+            .ledger
+            .deposit(donor.clone(), TokenTicker::USDT, 6_000)
+            .unwrap();
+        engine.verify_conservation().unwrap();
+
+        engine
+            .ledger
+            .transfer(&donor, &pool_account(&pair), &TokenTicker::USDT, 2_000)
+            .unwrap();
+        assert_eq!(engine.skim(&pair, &keeper, u64::MAX), Ok((0, 2_000)));
+        assert_eq!(
+            engine.ledger.free_balance(&keeper, &TokenTicker::USDT),
+            2_000
+        );
+        assert_eq!(
+            engine.amm_pools[&pair].reserve(&TokenTicker::USDT),
+            Some(200_000)
+        );
+
+        engine
+            .ledger
+            .transfer(&donor, &pool_account(&pair), &TokenTicker::USDT, 3_000)
+            .unwrap();
+        assert_eq!(engine.sync(&pair, u64::MAX), Ok((0, 3_000)));
+        assert_eq!(
+            engine.amm_pools[&pair].reserve(&TokenTicker::USDT),
+            Some(203_000)
+        );
+        assert_eq!(engine.pool_account_balances(&pair), (0, 0));
+        engine.verify_conservation().unwrap();
+
+        // a stale keeper call neither absorbs nor pays out
+        engine
+            .ledger
+            .transfer(&donor, &pool_account(&pair), &TokenTicker::USDT, 1_000)
+            .unwrap();
+        let now = engine.now();
+        for result in [
+            engine.sync(&pair, now - 1),
+            engine.skim(&pair, &keeper, now - 1),
+        ] {
+            assert!(matches!(result, Err(LpError::DeadlineExpired(_))));
+        }
+        assert_eq!(engine.pool_account_balances(&pair), (0, 1_000));
+
+        let missing = Pair::new(TokenTicker::BTC, TokenTicker::USDT);
+        assert_eq!(engine.sync(&missing, u64::MAX), Err(LpError::UnknownPool));
+    }
+}
@@ -0,0 +1,123 @@
+use std::fmt::Debug;
+use std::hash::Hash;
+
+use ordered_float::OrderedFloat;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+
+// A numeric type an `OrderBook` can keep its prices in. Books default to
+// f64, which the engine uses throughout; a book on its own can instead use
+// integer ticks or decimals, so prices never pick up rounding error.
+pub trait PriceLike: Copy + PartialOrd + Debug {
+    // Totally ordered form of the price that keys the book's levels.
+    type Key: Copy + Ord + Hash + Debug;
+
+    fn key(self) -> Self::Key;
+    fn from_key(key: Self::Key) -> Self;
+    // For reporting, e.g. depth snapshots.
+    fn to_f64(self) -> f64;
+    // Whether the price is a whole number of positive `tick_size` steps.
+    fn is_on_tick(self, tick_size: Self) -> bool;
+}
+
+impl PriceLike for f64 {
+    type Key = OrderedFloat<f64>;
+
+    fn key(self) -> OrderedFloat<f64> {
+        OrderedFloat(self)
+    }
+
+    fn from_key(key: OrderedFloat<f64>) -> f64 {
+        key.into_inner()
+    }
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    fn is_on_tick(self, tick_size: f64) -> bool {
+        // allow for the representation error of decimal ticks like 0.01
+        let ticks = self / tick_size;
+        tick_size > 0.0 && (ticks - ticks.round()).abs() <= 1e-9 * ticks.abs().max(1.0)
+    }
+}
+
+// Fixed point: a whole number of ticks, e.g. cents. The caller picks the
+// scale and converts at the edges.
+impl PriceLike for i64 {
+    type Key = i64;
+
+    fn key(self) -> i64 {
+        self
+    }
+
+    fn from_key(key: i64) -> i64 {
+        key
+    }
+
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    fn is_on_tick(self, tick_size: i64) -> bool {
+        tick_size > 0 && self % tick_size == 0
+    }
+}
+
+impl PriceLike for Decimal {
+    type Key = Decimal;
+
+    fn key(self) -> Decimal {
+        // 1.50 and 1.5 must share a level
+        self.normalize()
+    }
+
+    fn from_key(key: Decimal) -> Decimal {
+        key
+    }
+
+    fn to_f64(self) -> f64 {
+        ToPrimitive::to_f64(&self).unwrap_or(f64::NAN)
+    }
+
+    fn is_on_tick(self, tick_size: Decimal) -> bool {
+        tick_size > Decimal::ZERO && (self % tick_size).is_zero()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::order::BuyOrSell;
+    use crate::corelib::orderbook::{OrderBook, OrderBookTrait};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_books_match_on_exact_prices() {
+        let mut book = OrderBook::<Decimal>::default();
+        book.add_order(BuyOrSell::Sell, dec!(0.30), 5, 0);
+        book.add_order(BuyOrSell::Sell, dec!(0.3), 2, 1);
+        // 0.1 + 0.2 is exactly 0.3 here, unlike in f64
+        book.add_order(BuyOrSell::Buy, dec!(0.1) + dec!(0.2), 6, 2);
+        assert_eq!(book.level_count(BuyOrSell::Sell), 1);
+        let fills = book.match_orders(3);
+        assert_eq!(
+            fills
+                .iter()
+                .map(|fill| (fill.sell_order_id, fill.price, fill.quantity))
+                .collect::<Vec<_>>(),
+            vec![(1, dec!(0.30), 5), (2, dec!(0.3), 1)]
+        );
+
+        let mut book = OrderBook::<i64>::default();
+        book.add_order(BuyOrSell::Buy, 10_050, 3, 0);
+        book.add_order(BuyOrSell::Sell, 10_025, 1, 1);
+        book.add_order(BuyOrSell::Sell, 10_075, 1, 2);
+        let fills = book.uncross_at(10_040, 3);
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].price, 10_040);
+        assert_eq!(book.best_buy_price(), Some(10_050));
+        assert_eq!(book.depth_snapshot().asks[0].price, 10_075.0);
+    }
+}
@@ -0,0 +1,140 @@
+use super::audit::AuditAction;
+use super::engine::{OrderError, TradeEngine};
+use super::order::Wallet;
+use super::token::TokenTicker;
+
+// What order entry does with zero and negative prices on an instrument.
+// Instruments without a policy allow them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PricePolicy {
+    // Any price, e.g. for commodities that can trade below zero.
+    Allow,
+    // Raise prices below `floor` to it.
+    Clamp { floor: f64 },
+    // Refuse prices at or below zero.
+    Reject,
+}
+
+impl TradeEngine {
+    pub fn set_price_policy(&mut self, ticker: TokenTicker, policy: PricePolicy) {
+        self.price_policies.insert(ticker.clone(), policy);
+        let now = self.now();
+        self.audit_log
+            .record(now, AuditAction::PricePolicySet { ticker, policy });
+    }
+
+    pub fn price_policy(&self, ticker: &TokenTicker) -> PricePolicy {
+        self.price_policies
+            .get(ticker)
+            .copied()
+            .unwrap_or(PricePolicy::Allow)
+    }
+
+    // The price an order on `ticker` enters the book at under the
+    // instrument's policy.
+    pub(crate) fn check_price(&self, ticker: &TokenTicker, price: f64) -> Result<f64, OrderError> {
+        match self.price_policy(ticker) {
+            PricePolicy::Clamp { floor } if price < floor => Ok(floor),
+            PricePolicy::Reject if price <= 0.0 => Err(OrderError::NonPositivePrice { price }),
+            _ => Ok(price),
+        }
+    }
+
+    // `check_price` for order entry, recording every clamp and rejection
+    // in the audit log.
+    pub(crate) fn apply_price_policy(
+        &mut self,
+        wallet: Option<&Wallet>,
+        ticker: &TokenTicker,
+        price: f64,
+    ) -> Result<f64, OrderError> {
+        let checked = self.check_price(ticker, price);
+        let action = match checked {
+            Ok(entered) if entered == price => return checked,
+            Ok(entered) => AuditAction::PriceClamped {
+                ticker: ticker.clone(),
+                wallet: wallet.cloned(),
+                price,
+                entered,
+            },
+            Err(_) => AuditAction::PriceRejected {
+                ticker: ticker.clone(),
+                wallet: wallet.cloned(),
+                price,
+            },
+        };
+        let now = self.now();
+        self.audit_log.record(now, action);
+        checked
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::order::{BuyOrSell, TimeInForce};
+
+    #[test]
+    fn test_policies_allow_clamp_or_reject() {
+        let mut engine = TradeEngine::new();
+        engine.list_new_token(TokenTicker::ETH);
+        engine.list_new_token(TokenTicker::BTC);
+        engine.list_new_token(TokenTicker::SOL);
+        engine.set_price_policy(TokenTicker::BTC, PricePolicy::Clamp { floor: 0.01 });
+        engine.set_price_policy(TokenTicker::SOL, PricePolicy::Reject);
+        let wallet = Wallet::new(String::from("pricepolicy"));
+        let mut submit = |ticker: TokenTicker, price| {
+            engine.submit_wallet_order(
+                &wallet,
+                &ticker,
+                BuyOrSell::Sell,
+                price,
+                1,
+                TimeInForce::GoodTillCancel,
+            )
+        };
+
+        let eth = submit(TokenTicker::ETH, -3.5).unwrap();
+        let btc = submit(TokenTicker::BTC, -3.5).unwrap();
+        assert_eq!(
+            submit(TokenTicker::SOL, 0.0),
+            Err(OrderError::NonPositivePrice { price: 0.0 })
+        );
+        submit(TokenTicker::SOL, 2.0).unwrap();
+
+        let price = |engine: &TradeEngine, ticker, order_id| {
+            engine.order_books[&ticker]
+                .find_order(order_id)
+                .unwrap()
+                .1
+                .price
+        };
+        assert_eq!(price(&engine, TokenTicker::ETH, eth), -3.5);
+        assert_eq!(price(&engine, TokenTicker::BTC, btc), 0.01);
+
+        let actions: Vec<&AuditAction> = engine
+            .audit_log
+            .records()
+            .iter()
+            .map(|record| &record.action)
+            .skip(2)
+            .collect();
+        assert_eq!(
+            actions,
+            vec![
+                &AuditAction::PriceClamped {
+                    ticker: TokenTicker::BTC,
+                    wallet: Some(wallet.clone()),
+                    price: -3.5,
+                    entered: 0.01
+                },
+                &AuditAction::PriceRejected {
+                    ticker: TokenTicker::SOL,
+                    wallet: Some(wallet.clone()),
+                    price: 0.0
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,131 @@
+use super::engine::{OrderError, TradeEngine};
+use super::ledger::LedgerError;
+use super::order::{BuyOrSell, TimeInForce, Wallet};
+use super::token::TokenTicker;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PriorityOrderError {
+    // Priority fees are a research mode, off unless enabled.
+    Disabled,
+    Order(OrderError),
+    Ledger(LedgerError),
+}
+
+impl From<OrderError> for PriorityOrderError {
+    fn from(err: OrderError) -> Self {
+        PriorityOrderError::Order(err)
+    }
+}
+
+impl From<LedgerError> for PriorityOrderError {
+    fn from(err: LedgerError) -> Self {
+        PriorityOrderError::Ledger(err)
+    }
+}
+
+impl TradeEngine {
+    // Experimental: place an order that pays `priority_fee` of the quote
+    // token to queue ahead of orders at its price paying less. The fee goes
+    // to the treasury when the order is accepted, whether or not it fills.
+    pub fn submit_priority_order(
+        &mut self,
+        wallet: &Wallet,
+        ticker: &TokenTicker,
+        side: BuyOrSell,
+        price: f64,
+        quantity: u32,
+        priority_fee: u64,
+    ) -> Result<u64, PriorityOrderError> {
+        if !self.priority_fees_enabled {
+            return Err(PriorityOrderError::Disabled);
+        }
+        let quote = self.quote_token.clone();
+        let available = self.ledger.free_balance(wallet, &quote);
+        if available < priority_fee {
+            return Err(LedgerError::InsufficientFree {
+                token: quote,
+                available,
+                requested: priority_fee,
+            }
+            .into());
+        }
+        let order_id = self.submit_wallet_order(
+            wallet,
+            ticker,
+            side,
+            price,
+            quantity,
+            TimeInForce::GoodTillCancel,
+        )?;
+        let timestamp = self.now();
+        self.order_books.get_mut(ticker).unwrap().set_priority_fee(
+            order_id,
+            priority_fee,
+            timestamp,
+        );
+        let treasury = self.ledger.treasury().clone();
+        self.ledger
+            .transfer(wallet, &treasury, &quote, priority_fee)?;
+        Ok(order_id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::events::BookEvent;
+
+    #[test]
+    fn test_priority_fee_jumps_the_queue() {
+        let mut engine = TradeEngine::new();
+        engine.list_new_token(TokenTicker::ETH);
+        let wallet = Wallet::new(String::from("priorityjumper"));
+        engine
+            .ledger
+            .deposit(wallet.clone(), TokenTicker::USDT, 50)
+            .unwrap();
+        assert_eq!(
+            engine.submit_priority_order(&wallet, &TokenTicker::ETH, BuyOrSell::Buy, 10.0, 1, 5),
+            Err(PriorityOrderError::Disabled)
+        );
+        engine.priority_fees_enabled = true;
+
+        let first = engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Buy, 10.0, 1)
+            .unwrap();
+        let cheap = engine
+            .submit_priority_order(&wallet, &TokenTicker::ETH, BuyOrSell::Buy, 10.0, 1, 5)
+            .unwrap();
+        let rich = engine
+            .submit_priority_order(&wallet, &TokenTicker::ETH, BuyOrSell::Buy, 10.0, 1, 20)
+            .unwrap();
+        let queue: Vec<u64> = engine.order_books[&TokenTicker::ETH]
+            .level(BuyOrSell::Buy, 10.0)
+            .iter()
+            .map(|order| order.id)
+            .collect();
+        assert_eq!(queue, vec![rich, cheap, first]);
+        // each requeue is a book event of its own
+        let events = engine.order_books[&TokenTicker::ETH].events();
+        assert_eq!(events.len(), 5);
+        assert!(matches!(
+            events[4],
+            BookEvent::PriorityFeeSet {
+                order_id,
+                priority_fee: 20,
+                ..
+            } if order_id == rich
+        ));
+        assert_eq!(engine.ledger.treasury_balance(&TokenTicker::USDT), 25);
+
+        assert!(matches!(
+            engine.submit_priority_order(&wallet, &TokenTicker::ETH, BuyOrSell::Buy, 10.0, 1, 26),
+            Err(PriorityOrderError::Ledger(_))
+        ));
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Sell, 10.0, 1)
+            .unwrap();
+        assert_eq!(engine.match_orders()[0].0, rich);
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::HashSet;
+
+use super::engine::TradeEngine;
+use super::order::Wallet;
+use super::rounding::{Flow, Rounding};
+use super::router::{PoolQuote, RouteError};
+use super::token::{Pair, TokenTicker};
+
+const BPS: f64 = 10_000.0;
+
+// One hop of an executed route.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteLeg {
+    pub pair: Pair,
+    // Fee tier of a parallel pool; None for the pair's primary pool.
+    pub tier: Option<u64>,
+    pub token_in: TokenTicker,
+    pub token_out: TokenTicker,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    // Units of `token_out` received per unit of `token_in`.
+    pub price: f64,
+    // Part of `amount_in` kept by the pool as its fee.
+    pub fee: u64,
+    // Shortfall of `price` against the pool's spot price after its fee,
+    // i.e. the price impact of the leg.
+    pub slippage_bps: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwapReceipt {
+    pub token_in: TokenTicker,
+    pub token_out: TokenTicker,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub legs: Vec<RouteLeg>,
+}
+
+impl TradeEngine {
+    // Swap exactly `amount_in` along `path` (first token in, last token out),
+    // each hop through the best pool for its size. Every hop is quoted before
+    // anything moves; the receipt breaks the trade down per leg.
+    pub fn swap_path(
+        &mut self,
+        wallet: &Wallet,
+        path: &[TokenTicker],
+        amount_in: u64,
+        min_out: u64,
+        deadline: u64,
+    ) -> Result<SwapReceipt, RouteError> {
+        self.check_deadline(deadline)?;
+        if amount_in == 0 {
+            return Err(RouteError::ZeroAmount);
+        }
+        // a token visited twice would quote the same pool twice
+        let distinct: HashSet<&TokenTicker> = path.iter().collect();
+        if path.len() < 2 || distinct.len() != path.len() {
+            return Err(RouteError::NoRoute);
+        }
+
+        let mut legs = Vec::new();
+        let mut amount = amount_in;
+        for hop in path.windows(2) {
+            let (token_in, token_out) = (&hop[0], &hop[1]);
+            let PoolQuote {
+                pair,
+                tier,
+                fee_bps,
+                amount_out,
+            } = self
+                .best_pool(token_in, token_out, amount)
+                .ok_or(RouteError::NoRoute)?;
+            let pool = self.pool(&pair, tier).unwrap();
+            let spot = pool.reserve(token_out).unwrap() as f64
+                / pool.reserve(token_in).unwrap() as f64
+                * (1.0 - fee_bps as f64 / BPS);
+            let price = amount_out as f64 / amount as f64;
+            legs.push(RouteLeg {
+                pair,
+                tier,
+                token_in: token_in.clone(),
+                token_out: token_out.clone(),
+                amount_in: amount,
+                amount_out,
+                price,
+                fee: Rounding::Truncate.ratio(amount, fee_bps, 10_000, Flow::ToHouse),
+                slippage_bps: (1.0 - price / spot) * BPS,
+            });
+            amount = amount_out;
+        }
+        if amount < min_out {
+            return Err(RouteError::Slippage {
+                amount_out: amount,
+                min_out,
+            });
+        }
+
+        let token_in = path.first().unwrap().clone();
+        let token_out = path.last().unwrap().clone();
+        self.ledger.debit_free(wallet, &token_in, amount_in)?;
+        for leg in legs.iter() {
+            self.pool_mut(&leg.pair, leg.tier).unwrap().swap_exact_in(
+                &leg.token_in,
+                &leg.token_out,
+                leg.amount_in,
+            );
+        }
+        self.ledger.credit(wallet, &token_out, amount);
+        Ok(SwapReceipt {
+            token_in,
+            token_out,
+            amount_in,
+            amount_out: amount,
+            legs,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_multi_hop_receipt() {
+        let mut engine = TradeEngine::new();
+        let wallet = Wallet::new(String::from("receiptwallet"));
+        for (base, reserve_base, reserve_usdt, fee_bps) in [
+            (TokenTicker::ETH, 1_000, 2_000_000, 30),
+            (TokenTicker::SOL, 100_000, 10_000_000, 100),
+        ] {
+            let pool = engine
+                .amm_pools
+                .entry(Pair::new(base.clone(), TokenTicker::USDT))
+                .or_default();
+            pool.add_liquidity(base, reserve_base);
+            pool.add_liquidity(TokenTicker::USDT, reserve_usdt);
+            pool.fee_bps = fee_bps;
+        }
+        engine
+            .ledger
+            .deposit(wallet.clone(), TokenTicker::ETH, 10)
+            .unwrap();
+        let path = [TokenTicker::ETH, TokenTicker::USDT, TokenTicker::SOL];
+
+        assert!(matches!(
+            engine.swap_path(&wallet, &path, 10, 1_000, u64::MAX),
+            Err(RouteError::Slippage { .. })
+        ));
+        assert_eq!(engine.ledger.free_balance(&wallet, &TokenTicker::ETH), 10);
+
+        let receipt = engine.swap_path(&wallet, &path, 10, 0, u64::MAX).unwrap();
+        assert_eq!(receipt.legs.len(), 2);
+        let (first, second) = (&receipt.legs[0], &receipt.legs[1]);
+        assert_eq!(first.fee, 0);
+        assert_eq!(first.amount_out, 19_743);
+        assert_eq!(second.amount_in, first.amount_out);
+        assert_eq!(second.fee, 197);
+        assert_eq!(receipt.amount_out, second.amount_out);
+        assert!(first.slippage_bps > 0.0 && first.slippage_bps < 100.0);
+        assert_eq!(
+            engine.ledger.free_balance(&wallet, &TokenTicker::SOL),
+            receipt.amount_out
+        );
+        assert_eq!(
+            engine.swap_path(
+                &wallet,
+                &[TokenTicker::ETH, TokenTicker::ETH],
+                1,
+                0,
+                u64::MAX
+            ),
+            Err(RouteError::NoRoute)
+        );
+    }
+
+    #[test]
+    fn test_receipt_fee_past_u64_product() {
+        let mut engine = TradeEngine::new();
+        let wallet = Wallet::new(String::from("receiptwhale"));
+        let pool = engine
+            .amm_pools
+            .entry(Pair::new(TokenTicker::ETH, TokenTicker::USDT))
+            .or_default();
+        pool.add_liquidity(TokenTicker::ETH, 100_000_000_000_000);
+        pool.add_liquidity(TokenTicker::USDT, 100_000_000_000_000);
+        pool.fee_bps = 9_999;
+        // amount * fee_bps no longer fits a u64
+        let amount = u64::MAX / 9_999 + 1;
+        engine
+            .ledger
+            .deposit(wallet.clone(), TokenTicker::ETH, amount)
+            .unwrap();
+
+        let receipt = engine
+            .swap_path(
+                &wallet,
+                &[TokenTicker::ETH, TokenTicker::USDT],
+                amount,
+                0,
+                u64::MAX,
+            )
+            .unwrap();
+        assert_eq!(
+            receipt.legs[0].fee,
+            (amount as u128 * 9_999 / 10_000) as u64
+        );
+    }
+}
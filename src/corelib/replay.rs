@@ -0,0 +1,406 @@
+use super::codec::{json, Encode};
+use super::events::BookEvent;
+use super::order::BuyOrSell;
+use super::orderbook::OrderBook;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BookCommand {
+    Submit {
+        side: BuyOrSell,
+        price: f64,
+        quantity: u32,
+    },
+    Cancel {
+        order_id: u64,
+    },
+    Match,
+}
+
+// Storage and matching of a single instrument's book. Every backend must
+// emit exactly the same events for the same commands.
+pub trait BookBackend {
+    fn submit(&mut self, side: BuyOrSell, price: f64, quantity: u32, timestamp: u64) -> u64;
+    fn cancel(&mut self, order_id: u64, timestamp: u64);
+    fn match_orders(&mut self, timestamp: u64);
+    fn events(&self) -> &[BookEvent];
+}
+
+impl BookBackend for OrderBook {
+    fn submit(&mut self, side: BuyOrSell, price: f64, quantity: u32, timestamp: u64) -> u64 {
+        self.add_order(side, price, quantity, timestamp)
+    }
+
+    fn cancel(&mut self, order_id: u64, timestamp: u64) {
+        self.cancel_order(order_id, timestamp);
+    }
+
+    fn match_orders(&mut self, timestamp: u64) {
+        OrderBook::match_orders(self, timestamp);
+    }
+
+    fn events(&self) -> &[BookEvent] {
+        OrderBook::events(self)
+    }
+}
+
+// One JSON event per line, so outputs can be compared byte for byte and
+// diffs point at the first divergent event.
+pub fn encode_events(events: &[BookEvent]) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    for event in events {
+        encoded.extend_from_slice(&json::write(&event.encode()));
+        encoded.push(b'\n');
+    }
+    encoded
+}
+
+// Apply timestamped commands in order and return the encoded events.
+pub fn replay(backend: &mut impl BookBackend, commands: &[(u64, BookCommand)]) -> Vec<u8> {
+    for (timestamp, command) in commands {
+        match command {
+            BookCommand::Submit {
+                side,
+                price,
+                quantity,
+            } => {
+                backend.submit(*side, *price, *quantity, *timestamp);
+            }
+            BookCommand::Cancel { order_id } => backend.cancel(*order_id, *timestamp),
+            BookCommand::Match => backend.match_orders(*timestamp),
+        }
+    }
+    encode_events(backend.events())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    // One-based line number in the command log.
+    pub line: usize,
+    pub reason: String,
+}
+
+// Parse a recorded command log. Each non-empty line that does not start
+// with `#` is one of:
+//   <timestamp> submit <buy|sell> <price> <quantity>
+//   <timestamp> cancel <order id>
+//   <timestamp> match
+pub fn parse_command_log(log: &str) -> Result<Vec<(u64, BookCommand)>, ParseError> {
+    let mut commands = Vec::new();
+    for (index, line) in log.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = |reason: &str| ParseError {
+            line: index + 1,
+            reason: String::from(reason),
+        };
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let timestamp = fields[0].parse().map_err(|_| error("invalid timestamp"))?;
+        let command = match fields[1..] {
+            ["submit", side, price, quantity] => BookCommand::Submit {
+                side: match side {
+                    "buy" => BuyOrSell::Buy,
+                    "sell" => BuyOrSell::Sell,
+                    _ => return Err(error("invalid side")),
+                },
+                price: price.parse().map_err(|_| error("invalid price"))?,
+                quantity: quantity.parse().map_err(|_| error("invalid quantity"))?,
+            },
+            ["cancel", order_id] => BookCommand::Cancel {
+                order_id: order_id.parse().map_err(|_| error("invalid order id"))?,
+            },
+            ["match"] => BookCommand::Match,
+            _ => return Err(error("unknown command")),
+        };
+        commands.push((timestamp, command));
+    }
+    Ok(commands)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    // Zero-based index of the first differing event line.
+    pub line: usize,
+    pub left: Option<String>,
+    pub right: Option<String>,
+}
+
+// Compare two encoded event streams line by line.
+pub fn first_divergence(left: &[u8], right: &[u8]) -> Option<Divergence> {
+    let left = String::from_utf8_lossy(left);
+    let right = String::from_utf8_lossy(right);
+    let mut left_lines = left.lines();
+    let mut right_lines = right.lines();
+    let mut line = 0;
+    loop {
+        match (left_lines.next(), right_lines.next()) {
+            (None, None) => return None,
+            (l, r) if l == r => line += 1,
+            (l, r) => {
+                return Some(Divergence {
+                    line,
+                    left: l.map(String::from),
+                    right: r.map(String::from),
+                })
+            }
+        }
+    }
+}
+
+// Feed the same commands to two backends and report where their events
+// first differ.
+pub fn differential_replay(
+    left: &mut impl BookBackend,
+    right: &mut impl BookBackend,
+    commands: &[(u64, BookCommand)],
+) -> Result<(), Divergence> {
+    match first_divergence(&replay(left, commands), &replay(right, commands)) {
+        Some(divergence) => Err(divergence),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::collections::{BTreeMap, VecDeque};
+
+    use super::*;
+    use ordered_float::OrderedFloat;
+
+    // Replay `tests/fixtures/sessions/<name>.log` and compare the events with
+    // `<name>.golden`. Run with UPDATE_GOLDEN=1 to rewrite the golden file
+    // after an intended change in execution semantics.
+    fn assert_golden_session(name: &str) {
+        let directory =
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/sessions");
+        let log = std::fs::read_to_string(directory.join(format!("{}.log", name))).unwrap();
+        let commands = parse_command_log(&log).unwrap();
+        let actual = replay(&mut OrderBook::new(), &commands);
+
+        let golden_path = directory.join(format!("{}.golden", name));
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(&golden_path, &actual).unwrap();
+            return;
+        }
+        let golden = std::fs::read(&golden_path).unwrap();
+        if let Some(divergence) = first_divergence(&golden, &actual) {
+            panic!(
+                "session {} diverges from its golden file: {:?}",
+                name, divergence
+            );
+        }
+    }
+
+    // Straightforward price-time priority book kept ordered at all times.
+    #[derive(Default)]
+    struct ReferenceBook {
+        bids: BTreeMap<OrderedFloat<f64>, VecDeque<(u64, u32)>>,
+        asks: BTreeMap<OrderedFloat<f64>, VecDeque<(u64, u32)>>,
+        next_id: u64,
+        events: Vec<BookEvent>,
+    }
+
+    impl BookBackend for ReferenceBook {
+        fn submit(&mut self, side: BuyOrSell, price: f64, quantity: u32, timestamp: u64) -> u64 {
+            self.next_id += 1;
+            let levels = match side {
+                BuyOrSell::Buy => &mut self.bids,
+                BuyOrSell::Sell => &mut self.asks,
+            };
+            levels
+                .entry(OrderedFloat(price))
+                .or_default()
+                .push_back((self.next_id, quantity));
+            self.events.push(BookEvent::OrderAdded {
+                order_id: self.next_id,
+                side,
+                price,
+                quantity,
+                timestamp,
+                metadata: None,
+            });
+            self.next_id
+        }
+
+        fn cancel(&mut self, order_id: u64, timestamp: u64) {
+            for (side, levels) in [
+                (BuyOrSell::Buy, &mut self.bids),
+                (BuyOrSell::Sell, &mut self.asks),
+            ] {
+                for (price, level) in levels.iter_mut() {
+                    if let Some(index) = level.iter().position(|(id, _)| *id == order_id) {
+                        let (_, remaining) = level.remove(index).unwrap();
+                        let price = *price;
+                        if level.is_empty() {
+                            levels.remove(&price);
+                        }
+                        self.events.push(BookEvent::OrderCancelled {
+                            order_id,
+                            side,
+                            price: price.into_inner(),
+                            remaining,
+                            timestamp,
+                            metadata: None,
+                        });
+                        return;
+                    }
+                }
+            }
+        }
+
+        fn match_orders(&mut self, timestamp: u64) {
+            loop {
+                let (Some(mut bid), Some(mut ask)) =
+                    (self.bids.last_entry(), self.asks.first_entry())
+                else {
+                    return;
+                };
+                if bid.key() < ask.key() {
+                    return;
+                }
+                let (bid_price, ask_price) = (bid.key().into_inner(), ask.key().into_inner());
+                let (buy_id, buy_quantity) = bid.get_mut()[0];
+                let (sell_id, sell_quantity) = ask.get_mut()[0];
+                let filled = buy_quantity.min(sell_quantity);
+                bid.get_mut()[0].1 -= filled;
+                ask.get_mut()[0].1 -= filled;
+                if bid.get()[0].1 == 0 {
+                    bid.get_mut().pop_front();
+                    if bid.get().is_empty() {
+                        bid.remove();
+                    }
+                }
+                if ask.get()[0].1 == 0 {
+                    ask.get_mut().pop_front();
+                    if ask.get().is_empty() {
+                        ask.remove();
+                    }
+                }
+                for (order_id, side, price, quantity) in [
+                    (buy_id, BuyOrSell::Buy, bid_price, buy_quantity),
+                    (sell_id, BuyOrSell::Sell, ask_price, sell_quantity),
+                ] {
+                    self.events.push(BookEvent::OrderFilled {
+                        order_id,
+                        side,
+                        price,
+                        filled,
+                        remaining: quantity - filled,
+                        timestamp,
+                        metadata: None,
+                    });
+                }
+            }
+        }
+
+        fn events(&self) -> &[BookEvent] {
+            &self.events
+        }
+    }
+
+    struct Sequence(u64);
+
+    impl Sequence {
+        fn next(&mut self, bound: u64) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0 % bound
+        }
+    }
+
+    fn random_commands(seed: u64, count: usize) -> Vec<(u64, BookCommand)> {
+        let mut sequence = Sequence(seed);
+        let mut submitted = 0;
+        (0..count as u64)
+            .map(|timestamp| {
+                let command = match sequence.next(10) {
+                    0..=5 => {
+                        submitted += 1;
+                        BookCommand::Submit {
+                            side: if sequence.next(2) == 0 {
+                                BuyOrSell::Buy
+                            } else {
+                                BuyOrSell::Sell
+                            },
+                            price: 95.0 + sequence.next(10) as f64,
+                            quantity: 1 + sequence.next(20) as u32,
+                        }
+                    }
+                    6 | 7 if submitted > 0 => BookCommand::Cancel {
+                        order_id: 1 + sequence.next(submitted),
+                    },
+                    _ => BookCommand::Match,
+                };
+                (timestamp, command)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_order_book_matches_reference_backend() {
+        for seed in 1..50 {
+            let commands = random_commands(seed, 200);
+            let result = differential_replay(
+                &mut OrderBook::new(),
+                &mut ReferenceBook::default(),
+                &commands,
+            );
+            assert_eq!(result, Ok(()), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn test_recorded_sessions_match_golden_files() {
+        assert_golden_session("partial_fills");
+        assert_golden_session("cancels_and_price_priority");
+    }
+
+    #[test]
+    fn test_parse_command_log() {
+        let commands =
+            parse_command_log("# header\n1 submit buy 10.5 3\n\n2 cancel 1\n3 match\n").unwrap();
+        assert_eq!(
+            commands,
+            vec![
+                (
+                    1,
+                    BookCommand::Submit {
+                        side: BuyOrSell::Buy,
+                        price: 10.5,
+                        quantity: 3
+                    }
+                ),
+                (2, BookCommand::Cancel { order_id: 1 }),
+                (3, BookCommand::Match),
+            ]
+        );
+        assert_eq!(
+            parse_command_log("1 match\n2 submit hold 1 1"),
+            Err(ParseError {
+                line: 2,
+                reason: String::from("invalid side")
+            })
+        );
+    }
+
+    #[test]
+    fn test_reports_first_divergence() {
+        let left = b"a\nb\nc\n";
+        assert_eq!(first_divergence(left, left), None);
+        assert_eq!(
+            first_divergence(left, b"a\nx\n"),
+            Some(Divergence {
+                line: 1,
+                left: Some(String::from("b")),
+                right: Some(String::from("x")),
+            })
+        );
+        assert_eq!(
+            first_divergence(b"a\n", b"a\nb\n").map(|divergence| divergence.left),
+            Some(None)
+        );
+    }
+}
@@ -0,0 +1,276 @@
+use std::collections::HashSet;
+
+use super::engine::TradeEngine;
+use super::token::TokenTicker;
+use super::trade::Trade;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Retention {
+    // Keep the most recent trades and book events, this many of each.
+    Count(usize),
+    // Keep what happened in the last `millis`.
+    Duration { millis: u64 },
+}
+
+// How much raw history an instrument keeps. Evicted trades are folded into
+// candles of `candle_millis` first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    pub retention: Retention,
+    pub candle_millis: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candle {
+    pub start_millis: u64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: u64,
+    pub trades: u64,
+}
+
+impl Candle {
+    fn new(start_millis: u64, trade: &Trade) -> Candle {
+        Candle {
+            start_millis,
+            open: trade.price,
+            high: trade.price,
+            low: trade.price,
+            close: trade.price,
+            volume: trade.quantity,
+            trades: 1,
+        }
+    }
+
+    fn add(&mut self, trade: &Trade) {
+        self.high = self.high.max(trade.price);
+        self.low = self.low.min(trade.price);
+        self.close = trade.price;
+        self.volume += trade.quantity;
+        self.trades += 1;
+    }
+}
+
+// Append trades, oldest first, to the candles of one instrument.
+pub fn fold_into_candles(candles: &mut Vec<Candle>, trades: &[Trade], candle_millis: u64) {
+    let candle_millis = candle_millis.max(1);
+    for trade in trades {
+        let start_millis = trade.timestamp - trade.timestamp % candle_millis;
+        match candles.last_mut() {
+            Some(candle) if candle.start_millis == start_millis => candle.add(trade),
+            _ => candles.push(Candle::new(start_millis, trade)),
+        }
+    }
+}
+
+impl TradeEngine {
+    pub fn set_retention_policy(&mut self, ticker: TokenTicker, policy: RetentionPolicy) {
+        self.retention_policies.insert(ticker, policy);
+    }
+
+    // Candles of trades compacted out of the trade feed.
+    pub fn candles(&self, ticker: &TokenTicker) -> &[Candle] {
+        self.candles.get(ticker).map_or(&[], |candles| candles)
+    }
+
+    // Apply every retention policy: trades past retention become candles and
+    // are dropped from the feed, book events past retention are dropped.
+    // Trades still inside the bust window stay in the feed whatever the
+    // policy says, so they can still be busted. Meant to be called
+    // periodically; returns the number of trades compacted.
+    pub fn compact_history(&mut self) -> usize {
+        let now = self.now();
+        let bust_window_millis = self.bust_window_millis;
+        let bustable = |trade: &Trade| now <= trade.timestamp.saturating_add(bust_window_millis);
+        let mut compacted = 0;
+        let mut policies: Vec<(TokenTicker, RetentionPolicy)> = self
+            .retention_policies
+            .iter()
+            .map(|(ticker, policy)| (ticker.clone(), *policy))
+            .collect();
+        policies.sort_by(|a, b| a.0.cmp(&b.0));
+
+        for (ticker, policy) in policies {
+            let evicted_trades: HashSet<u64> = match policy.retention {
+                Retention::Count(keep) => {
+                    let ids: Vec<u64> = self
+                        .trade_feed
+                        .trades_for(&ticker)
+                        .map(|trade| trade.id)
+                        .collect();
+                    ids[..ids.len().saturating_sub(keep)]
+                        .iter()
+                        .copied()
+                        .collect()
+                }
+                Retention::Duration { millis } => self
+                    .trade_feed
+                    .trades_for(&ticker)
+                    .filter(|trade| trade.timestamp < now.saturating_sub(millis))
+                    .map(|trade| trade.id)
+                    .collect(),
+            };
+            let trades = self
+                .trade_feed
+                .evict(|trade| evicted_trades.contains(&trade.id) && !bustable(trade));
+            fold_into_candles(
+                self.candles.entry(ticker.clone()).or_default(),
+                &trades,
+                policy.candle_millis,
+            );
+            compacted += trades.len();
+
+            if let Some(orderbook) = self.order_books.get_mut(&ticker) {
+                let events = orderbook.events();
+                let evicted_events = match policy.retention {
+                    Retention::Count(keep) => events.len().saturating_sub(keep),
+                    Retention::Duration { millis } => events
+                        .partition_point(|event| event.timestamp() < now.saturating_sub(millis)),
+                };
+                orderbook.evict_events(evicted_events);
+            }
+        }
+        compacted
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::clock::SimulatedClock;
+    use crate::corelib::order::BuyOrSell;
+
+    #[test]
+    fn test_old_trades_compacted_into_candles() {
+        let clock = SimulatedClock::new(0);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        engine.bust_window_millis = 0;
+        engine.list_new_token(TokenTicker::ETH);
+        engine.list_new_token(TokenTicker::BTC);
+        engine.set_retention_policy(
+            TokenTicker::ETH,
+            RetentionPolicy {
+                retention: Retention::Duration { millis: 60_000 },
+                candle_millis: 60_000,
+            },
+        );
+        for (price, quantity) in [(100.0, 2), (104.0, 1), (98.0, 3), (101.0, 1)] {
+            engine
+                .submit_order(&TokenTicker::ETH, BuyOrSell::Buy, price, quantity)
+                .unwrap();
+            engine
+                .submit_order(&TokenTicker::ETH, BuyOrSell::Sell, price, quantity)
+                .unwrap();
+            engine
+                .submit_order(&TokenTicker::BTC, BuyOrSell::Buy, price, quantity)
+                .unwrap();
+            engine
+                .submit_order(&TokenTicker::BTC, BuyOrSell::Sell, price, quantity)
+                .unwrap();
+            engine.match_orders();
+            clock.advance(30_000);
+        }
+
+        assert_eq!(engine.compact_history(), 2);
+        assert_eq!(
+            engine.candles(&TokenTicker::ETH),
+            &[Candle {
+                start_millis: 0,
+                open: 100.0,
+                high: 104.0,
+                low: 100.0,
+                close: 104.0,
+                volume: 3,
+                trades: 2,
+            }]
+        );
+        assert_eq!(engine.trade_feed.trades_for(&TokenTicker::ETH).count(), 2);
+        assert_eq!(engine.trade_feed.trades_for(&TokenTicker::BTC).count(), 4);
+        let events = engine.order_books[&TokenTicker::ETH].events();
+        assert!(events.iter().all(|event| event.timestamp() >= 60_000));
+        assert!(engine.candles(&TokenTicker::BTC).is_empty());
+    }
+
+    #[test]
+    fn test_count_retention() {
+        let clock = SimulatedClock::new(0);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        engine.bust_window_millis = 0;
+        engine.list_new_token(TokenTicker::ETH);
+        engine.set_retention_policy(
+            TokenTicker::ETH,
+            RetentionPolicy {
+                retention: Retention::Count(1),
+                candle_millis: 1_000,
+            },
+        );
+        for _ in 0..3 {
+            engine
+                .submit_order(&TokenTicker::ETH, BuyOrSell::Buy, 10.0, 1)
+                .unwrap();
+            engine
+                .submit_order(&TokenTicker::ETH, BuyOrSell::Sell, 10.0, 1)
+                .unwrap();
+            engine.match_orders();
+        }
+        clock.advance(1);
+        assert_eq!(engine.compact_history(), 2);
+        assert_eq!(engine.trade_feed.trades().len(), 1);
+        assert_eq!(engine.order_books[&TokenTicker::ETH].events().len(), 1);
+        let volume: u64 = engine
+            .candles(&TokenTicker::ETH)
+            .iter()
+            .map(|candle| candle.volume)
+            .sum();
+        assert_eq!(volume, 2);
+    }
+
+    #[test]
+    fn test_compaction_keeps_bustable_trades_and_summary_reads_candles() {
+        let clock = SimulatedClock::new(0);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        engine.bust_window_millis = 60_000;
+        engine.list_new_token(TokenTicker::ETH);
+        engine.set_retention_policy(
+            TokenTicker::ETH,
+            RetentionPolicy {
+                retention: Retention::Count(0),
+                candle_millis: 10_000,
+            },
+        );
+        for (price, quantity) in [(100.0, 2), (110.0, 1), (95.0, 4)] {
+            engine
+                .submit_order(&TokenTicker::ETH, BuyOrSell::Buy, price, quantity)
+                .unwrap();
+            engine
+                .submit_order(&TokenTicker::ETH, BuyOrSell::Sell, price, quantity)
+                .unwrap();
+            engine.match_orders();
+            clock.advance(40_000);
+        }
+
+        // trades at 0 and 40s are past the bust window at 120s, the one at
+        // 80s is not
+        assert_eq!(engine.compact_history(), 2);
+        let kept: Vec<u64> = engine
+            .trade_feed
+            .trades_for(&TokenTicker::ETH)
+            .map(|trade| trade.timestamp)
+            .collect();
+        assert_eq!(kept, vec![80_000]);
+        let trade_id = engine.trade_feed.trades()[0].id;
+        assert!(engine.bust_trade(trade_id).is_ok());
+
+        let summary = engine.market_summary();
+        assert_eq!(summary[0].volume, 3);
+        assert_eq!(
+            (summary[0].high, summary[0].low),
+            (Some(110.0), Some(100.0))
+        );
+        assert_eq!(summary[0].last_price, Some(110.0));
+        assert_eq!(summary[0].change_percent, Some(10.0));
+    }
+}
@@ -0,0 +1,550 @@
+use std::collections::{HashMap, HashSet};
+
+use super::ledger::{Ledger, LedgerError};
+use super::order::{BuyOrSell, Wallet};
+use super::rounding::{Flow, Rounding};
+use super::token::TokenTicker;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RfqError {
+    UnknownMaker,
+    UnknownRequest,
+    UnknownQuote,
+    RequestClosed,
+    QuoteExpired,
+    NotRequester,
+    // The maker of a quote, or a maker quoting its own request.
+    NotMaker,
+    OwnRequest,
+    InvalidPrice,
+    InvalidQuantity,
+    // Base and quote are the same token.
+    SameToken,
+    Ledger(LedgerError),
+}
+
+impl From<LedgerError> for RfqError {
+    fn from(err: LedgerError) -> Self {
+        RfqError::Ledger(err)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct QuoteRequest {
+    pub id: u64,
+    pub taker: Wallet,
+    pub base: TokenTicker,
+    pub quote: TokenTicker,
+    // Side from the taker's point of view.
+    pub side: BuyOrSell,
+    pub quantity: u64,
+    pub created_at: u64,
+    pub open: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Quote {
+    pub id: u64,
+    pub request_id: u64,
+    pub maker: Wallet,
+    pub price: f64,
+    pub expires_at: u64,
+    // What the maker has locked to back the quote: the base it would sell
+    // or the quote token it would pay.
+    pub reserved_token: TokenTicker,
+    pub reserved: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RfqFill {
+    pub request_id: u64,
+    pub quote_id: u64,
+    pub maker: Wallet,
+    pub taker: Wallet,
+    pub price: f64,
+    pub quantity: u64,
+    pub notional: u64,
+}
+
+// Request-for-quote desk. Takers ask for a size, registered makers answer
+// with firm quotes valid until `expires_at`, and lifting a quote settles the
+// trade directly between the two wallets in the ledger. A quote locks the
+// maker's side until it is lifted, cancelled or purged after expiry.
+pub struct RfqDesk {
+    pub rounding: Rounding,
+    makers: HashSet<Wallet>,
+    requests: HashMap<u64, QuoteRequest>,
+    quotes: HashMap<u64, Quote>,
+    next_id: u64,
+}
+
+impl Default for RfqDesk {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RfqDesk {
+    pub fn new() -> RfqDesk {
+        RfqDesk {
+            rounding: Rounding::default(),
+            makers: HashSet::new(),
+            requests: HashMap::new(),
+            quotes: HashMap::new(),
+            next_id: 1,
+        }
+    }
+
+    pub fn register_maker(&mut self, maker: Wallet) {
+        self.makers.insert(maker);
+    }
+
+    pub fn unregister_maker(&mut self, maker: &Wallet) {
+        self.makers.remove(maker);
+    }
+
+    pub fn request(&self, request_id: u64) -> Option<&QuoteRequest> {
+        self.requests.get(&request_id)
+    }
+
+    // Whether an open request trades `token` on either side.
+    pub fn has_open_requests(&self, token: &TokenTicker) -> bool {
+        self.requests
+            .values()
+            .any(|request| request.open && (&request.base == token || &request.quote == token))
+    }
+
+    pub fn request_quote(
+        &mut self,
+        taker: Wallet,
+        base: TokenTicker,
+        quote: TokenTicker,
+        side: BuyOrSell,
+        quantity: u64,
+        now: u64,
+    ) -> Result<u64, RfqError> {
+        if quantity == 0 {
+            return Err(RfqError::InvalidQuantity);
+        }
+        if base == quote {
+            return Err(RfqError::SameToken);
+        }
+        let id = self.next_id();
+        self.requests.insert(
+            id,
+            QuoteRequest {
+                id,
+                taker,
+                base,
+                quote,
+                side,
+                quantity,
+                created_at: now,
+                open: true,
+            },
+        );
+        Ok(id)
+    }
+
+    pub fn submit_quote(
+        &mut self,
+        ledger: &mut Ledger,
+        maker: Wallet,
+        request_id: u64,
+        price: f64,
+        ttl_millis: u64,
+        now: u64,
+    ) -> Result<u64, RfqError> {
+        if !self.makers.contains(&maker) {
+            return Err(RfqError::UnknownMaker);
+        }
+        if !price.is_finite() || price <= 0.0 {
+            return Err(RfqError::InvalidPrice);
+        }
+        // expired quotes give their reservations back first
+        self.purge_expired(ledger, now);
+        let request = self
+            .requests
+            .get(&request_id)
+            .ok_or(RfqError::UnknownRequest)?;
+        if !request.open {
+            return Err(RfqError::RequestClosed);
+        }
+        if request.taker == maker {
+            return Err(RfqError::OwnRequest);
+        }
+        let (reserved_token, reserved) = match request.side {
+            BuyOrSell::Buy => (request.base.clone(), request.quantity),
+            BuyOrSell::Sell => (
+                request.quote.clone(),
+                self.notional(price, request.quantity),
+            ),
+        };
+        ledger.lock(&maker, &reserved_token, reserved)?;
+        let id = self.next_id();
+        self.quotes.insert(
+            id,
+            Quote {
+                id,
+                request_id,
+                maker,
+                price,
+                // a ttl past the end of the clock never expires
+                expires_at: now.saturating_add(ttl_millis),
+                reserved_token,
+                reserved,
+            },
+        );
+        Ok(id)
+    }
+
+    // Withdraw a quote and release what it reserved.
+    pub fn cancel_quote(
+        &mut self,
+        ledger: &mut Ledger,
+        maker: &Wallet,
+        quote_id: u64,
+    ) -> Result<(), RfqError> {
+        let quote = self.quotes.get(&quote_id).ok_or(RfqError::UnknownQuote)?;
+        if &quote.maker != maker {
+            return Err(RfqError::NotMaker);
+        }
+        self.release(ledger, quote_id);
+        Ok(())
+    }
+
+    // Drop every quote expired at `now`, releasing what they reserved.
+    // Returns the ids dropped.
+    pub fn purge_expired(&mut self, ledger: &mut Ledger, now: u64) -> Vec<u64> {
+        let mut expired: Vec<u64> = self
+            .quotes
+            .values()
+            .filter(|quote| quote.expires_at <= now)
+            .map(|quote| quote.id)
+            .collect();
+        expired.sort();
+        for quote_id in expired.iter() {
+            self.release(ledger, *quote_id);
+        }
+        expired
+    }
+
+    // Quotes on a request that are still firm at `now`, best price first.
+    pub fn live_quotes(&self, request_id: u64, now: u64) -> Vec<&Quote> {
+        let request = match self.requests.get(&request_id) {
+            Some(request) if request.open => request,
+            _ => return Vec::new(),
+        };
+        let mut quotes: Vec<&Quote> = self
+            .quotes
+            .values()
+            .filter(|quote| quote.request_id == request_id && quote.expires_at > now)
+            .collect();
+        match request.side {
+            BuyOrSell::Buy => quotes.sort_by(|a, b| a.price.total_cmp(&b.price)),
+            BuyOrSell::Sell => quotes.sort_by(|a, b| b.price.total_cmp(&a.price)),
+        }
+        quotes
+    }
+
+    // Accept a quote and settle it through the ledger. The request is
+    // closed and all of its other quotes are discarded.
+    pub fn lift_quote(
+        &mut self,
+        ledger: &mut Ledger,
+        taker: &Wallet,
+        quote_id: u64,
+        now: u64,
+    ) -> Result<RfqFill, RfqError> {
+        let quote = self.quotes.get(&quote_id).ok_or(RfqError::UnknownQuote)?;
+        let request = self
+            .requests
+            .get(&quote.request_id)
+            .ok_or(RfqError::UnknownRequest)?;
+        if &request.taker != taker {
+            return Err(RfqError::NotRequester);
+        }
+        if !request.open {
+            return Err(RfqError::RequestClosed);
+        }
+        if quote.expires_at <= now {
+            self.purge_expired(ledger, now);
+            return Err(RfqError::QuoteExpired);
+        }
+
+        let notional = self.notional(quote.price, request.quantity);
+        let (buyer, seller) = match request.side {
+            BuyOrSell::Buy => (taker, &quote.maker),
+            BuyOrSell::Sell => (&quote.maker, taker),
+        };
+        // the reservation backs the maker's leg; take it back if the
+        // taker's leg cannot settle
+        ledger.unlock(&quote.maker, &quote.reserved_token, quote.reserved)?;
+        if let Err(err) = ledger.settle(
+            buyer,
+            seller,
+            &request.base,
+            request.quantity,
+            &request.quote,
+            notional,
+        ) {
+            ledger
+                .lock(&quote.maker, &quote.reserved_token, quote.reserved)
+                .expect("reservation was just released");
+            return Err(err.into());
+        }
+
+        let fill = RfqFill {
+            request_id: request.id,
+            quote_id,
+            maker: quote.maker.clone(),
+            taker: taker.clone(),
+            price: quote.price,
+            quantity: request.quantity,
+            notional,
+        };
+        let request_id = request.id;
+        self.quotes.remove(&quote_id);
+        self.close(ledger, request_id);
+        self.purge_expired(ledger, now);
+        Ok(fill)
+    }
+
+    pub fn cancel_request(
+        &mut self,
+        ledger: &mut Ledger,
+        taker: &Wallet,
+        request_id: u64,
+    ) -> Result<(), RfqError> {
+        let request = self
+            .requests
+            .get_mut(&request_id)
+            .ok_or(RfqError::UnknownRequest)?;
+        if &request.taker != taker {
+            return Err(RfqError::NotRequester);
+        }
+        self.close(ledger, request_id);
+        Ok(())
+    }
+
+    fn notional(&self, price: f64, quantity: u64) -> u64 {
+        self.rounding
+            .amount(price * quantity as f64, Flow::Transfer)
+    }
+
+    // Close the request and drop its remaining quotes.
+    fn close(&mut self, ledger: &mut Ledger, request_id: u64) {
+        if let Some(request) = self.requests.get_mut(&request_id) {
+            request.open = false;
+        }
+        let quote_ids: Vec<u64> = self
+            .quotes
+            .values()
+            .filter(|quote| quote.request_id == request_id)
+            .map(|quote| quote.id)
+            .collect();
+        for quote_id in quote_ids {
+            self.release(ledger, quote_id);
+        }
+    }
+
+    // Remove a quote and unlock its reservation.
+    fn release(&mut self, ledger: &mut Ledger, quote_id: u64) {
+        if let Some(quote) = self.quotes.remove(&quote_id) {
+            ledger
+                .unlock(&quote.maker, &quote.reserved_token, quote.reserved)
+                .expect("quote reservations stay locked");
+        }
+    }
+
+    fn next_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_best_quote_lifted_and_settled() {
+        let mut ledger = Ledger::new();
+        let taker = Wallet::new(String::from("rfqtaker"));
+        let maker_a = Wallet::new(String::from("rfqmakera"));
+        let maker_b = Wallet::new(String::from("rfqmakerb"));
+        ledger
+            .deposit(taker.clone(), TokenTicker::USDT, 1_000_000)
+            .unwrap();
+        ledger
+            .deposit(maker_a.clone(), TokenTicker::BTC, 10)
+            .unwrap();
+        ledger
+            .deposit(maker_b.clone(), TokenTicker::BTC, 10)
+            .unwrap();
+
+        let mut desk = RfqDesk::new();
+        desk.register_maker(maker_a.clone());
+        desk.register_maker(maker_b.clone());
+
+        let request = desk
+            .request_quote(
+                taker.clone(),
+                TokenTicker::BTC,
+                TokenTicker::USDT,
+                BuyOrSell::Buy,
+                5,
+                1_000,
+            )
+            .unwrap();
+        desk.submit_quote(&mut ledger, maker_a.clone(), request, 60_100.0, 500, 1_010)
+            .unwrap();
+        let best = desk
+            .submit_quote(&mut ledger, maker_b.clone(), request, 60_000.0, 500, 1_020)
+            .unwrap();
+        // each quote locks the base its maker would deliver
+        assert_eq!(ledger.locked_balance(&maker_a, &TokenTicker::BTC), 5);
+
+        let live = desk.live_quotes(request, 1_100);
+        assert_eq!(live.len(), 2);
+        assert_eq!(live[0].id, best);
+
+        let fill = desk.lift_quote(&mut ledger, &taker, best, 1_200).unwrap();
+        assert_eq!(fill.notional, 300_000);
+        assert_eq!(ledger.free_balance(&taker, &TokenTicker::BTC), 5);
+        assert_eq!(ledger.free_balance(&taker, &TokenTicker::USDT), 700_000);
+        assert_eq!(ledger.free_balance(&maker_b, &TokenTicker::BTC), 5);
+        assert_eq!(ledger.free_balance(&maker_b, &TokenTicker::USDT), 300_000);
+
+        // the request is closed once filled, releasing the losing quote
+        assert!(desk.live_quotes(request, 1_200).is_empty());
+        assert_eq!(ledger.locked_balance(&maker_b, &TokenTicker::BTC), 0);
+        assert_eq!(ledger.free_balance(&maker_a, &TokenTicker::BTC), 10);
+    }
+
+    #[test]
+    fn test_expired_quote_and_unregistered_maker() {
+        let mut ledger = Ledger::new();
+        let taker = Wallet::new(String::from("rfqtaker"));
+        let maker = Wallet::new(String::from("rfqmaker"));
+        let mut desk = RfqDesk::new();
+
+        let request = desk
+            .request_quote(
+                taker.clone(),
+                TokenTicker::ETH,
+                TokenTicker::USDT,
+                BuyOrSell::Sell,
+                1,
+                0,
+            )
+            .unwrap();
+        assert_eq!(
+            desk.submit_quote(&mut ledger, maker.clone(), request, 3_000.0, 100, 0),
+            Err(RfqError::UnknownMaker)
+        );
+
+        desk.register_maker(maker.clone());
+        ledger
+            .deposit(maker.clone(), TokenTicker::USDT, 6_000)
+            .unwrap();
+        let quote = desk
+            .submit_quote(&mut ledger, maker.clone(), request, 3_000.0, 100, 0)
+            .unwrap();
+        // a maker buying from the taker locks the notional
+        assert_eq!(ledger.locked_balance(&maker, &TokenTicker::USDT), 3_000);
+        assert_eq!(
+            desk.lift_quote(&mut ledger, &taker, quote, 100),
+            Err(RfqError::QuoteExpired)
+        );
+        assert_eq!(ledger.locked_balance(&maker, &TokenTicker::USDT), 0);
+
+        let quote = desk
+            .submit_quote(&mut ledger, maker.clone(), request, 3_000.0, u64::MAX, 100)
+            .unwrap();
+        assert_eq!(desk.live_quotes(request, u64::MAX - 1)[0].id, quote);
+        assert_eq!(
+            desk.submit_quote(&mut ledger, maker.clone(), request, 4_000.0, 100, 100),
+            Err(RfqError::Ledger(LedgerError::InsufficientFree {
+                token: TokenTicker::USDT,
+                available: 3_000,
+                requested: 4_000,
+            }))
+        );
+        desk.cancel_quote(&mut ledger, &maker, quote).unwrap();
+        assert_eq!(ledger.free_balance(&maker, &TokenTicker::USDT), 6_000);
+    }
+
+    #[test]
+    fn test_invalid_requests_and_own_quotes_refused() {
+        let mut ledger = Ledger::new();
+        let wallet = Wallet::new(String::from("rfqboth"));
+        ledger
+            .deposit(wallet.clone(), TokenTicker::ETH, 10)
+            .unwrap();
+        let mut desk = RfqDesk::new();
+        desk.register_maker(wallet.clone());
+
+        let request = |desk: &mut RfqDesk, base: TokenTicker, quantity: u64| {
+            desk.request_quote(
+                wallet.clone(),
+                base,
+                TokenTicker::USDT,
+                BuyOrSell::Buy,
+                quantity,
+                0,
+            )
+        };
+        assert_eq!(
+            request(&mut desk, TokenTicker::ETH, 0),
+            Err(RfqError::InvalidQuantity)
+        );
+        assert_eq!(
+            request(&mut desk, TokenTicker::USDT, 1),
+            Err(RfqError::SameToken)
+        );
+        let request = request(&mut desk, TokenTicker::ETH, 1).unwrap();
+        assert_eq!(
+            desk.submit_quote(&mut ledger, wallet.clone(), request, 3_000.0, 100, 0),
+            Err(RfqError::OwnRequest)
+        );
+        assert_eq!(ledger.locked_balance(&wallet, &TokenTicker::ETH), 0);
+    }
+
+    #[test]
+    fn test_purge_releases_expired_quotes() {
+        let mut ledger = Ledger::new();
+        let taker = Wallet::new(String::from("rfqpurgetaker"));
+        let maker = Wallet::new(String::from("rfqpurgemaker"));
+        ledger.deposit(maker.clone(), TokenTicker::ETH, 3).unwrap();
+        let mut desk = RfqDesk::new();
+        desk.register_maker(maker.clone());
+
+        let request = desk
+            .request_quote(
+                taker,
+                TokenTicker::ETH,
+                TokenTicker::USDT,
+                BuyOrSell::Buy,
+                2,
+                0,
+            )
+            .unwrap();
+        let short = desk
+            .submit_quote(&mut ledger, maker.clone(), request, 3_000.0, 100, 0)
+            .unwrap();
+        desk.submit_quote(&mut ledger, maker.clone(), request, 3_000.0, 500, 0)
+            .unwrap_err();
+        assert_eq!(desk.purge_expired(&mut ledger, 100), vec![short]);
+        assert_eq!(ledger.free_balance(&maker, &TokenTicker::ETH), 3);
+
+        // a new quote sweeps expired ones first, so freed funds back it
+        desk.submit_quote(&mut ledger, maker.clone(), request, 3_000.0, 100, 100)
+            .unwrap();
+        desk.submit_quote(&mut ledger, maker.clone(), request, 2_900.0, 100, 200)
+            .unwrap();
+        assert_eq!(ledger.locked_balance(&maker, &TokenTicker::ETH), 2);
+    }
+}
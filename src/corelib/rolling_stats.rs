@@ -0,0 +1,287 @@
+use std::collections::{HashMap, VecDeque};
+
+use super::engine::TradeEngine;
+use super::token::TokenTicker;
+use super::trade::Trade;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RollingStats {
+    pub window_millis: u64,
+    pub trade_count: usize,
+    pub volume: u64,
+    pub high: Option<f64>,
+    pub low: Option<f64>,
+    // Square root of the summed squared log returns between consecutive
+    // trades in the window, not annualized.
+    pub realized_volatility: f64,
+}
+
+// Trades of one instrument over a sliding window. Volume and the sum of
+// squared returns are kept as running totals; high and low come from
+// monotonic queues, so both updates and queries are amortized O(1).
+#[derive(Debug, Clone)]
+pub struct RollingWindow {
+    pub window_millis: u64,
+    trades: VecDeque<WindowTrade>,
+    volume: u64,
+    squared_returns: f64,
+    // (timestamp, price), prices decreasing for the high and increasing for
+    // the low.
+    highs: VecDeque<(u64, f64)>,
+    lows: VecDeque<(u64, f64)>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct WindowTrade {
+    id: u64,
+    timestamp: u64,
+    quantity: u64,
+    price: f64,
+    // Against the instrument's previous trade.
+    squared_return: f64,
+}
+
+fn squared_return(previous_price: Option<f64>, price: f64) -> f64 {
+    previous_price
+        .filter(|previous| *previous > 0.0 && price > 0.0)
+        .map_or(0.0, |previous| (price / previous).ln().powi(2))
+}
+
+impl RollingWindow {
+    pub fn new(window_millis: u64) -> RollingWindow {
+        RollingWindow {
+            window_millis,
+            trades: VecDeque::new(),
+            volume: 0,
+            squared_returns: 0.0,
+            highs: VecDeque::new(),
+            lows: VecDeque::new(),
+        }
+    }
+
+    fn push(&mut self, trade: &Trade, previous_price: Option<f64>) {
+        let squared_return = squared_return(previous_price, trade.price);
+        self.trades.push_back(WindowTrade {
+            id: trade.id,
+            timestamp: trade.timestamp,
+            quantity: trade.quantity,
+            price: trade.price,
+            squared_return,
+        });
+        self.volume += trade.quantity;
+        self.squared_returns += squared_return;
+        self.track_extremes(trade.timestamp, trade.price);
+    }
+
+    fn track_extremes(&mut self, timestamp: u64, price: f64) {
+        while self.highs.back().is_some_and(|(_, high)| *high <= price) {
+            self.highs.pop_back();
+        }
+        self.highs.push_back((timestamp, price));
+        while self.lows.back().is_some_and(|(_, low)| *low >= price) {
+            self.lows.pop_back();
+        }
+        self.lows.push_back((timestamp, price));
+    }
+
+    // Take a busted trade out of the window. The trade after it now returns
+    // against `previous_price`, the price before the busted trade.
+    fn remove(&mut self, trade_id: u64, previous_price: Option<f64>) {
+        let Some(index) = self.trades.iter().position(|trade| trade.id == trade_id) else {
+            return;
+        };
+        let removed = self.trades.remove(index).unwrap();
+        self.volume -= removed.quantity;
+        if let Some(next) = self.trades.get_mut(index) {
+            next.squared_return = squared_return(previous_price, next.price);
+        }
+        // resummed rather than adjusted, so no float error is left behind
+        self.squared_returns = self.trades.iter().map(|trade| trade.squared_return).sum();
+        self.highs.clear();
+        self.lows.clear();
+        for index in 0..self.trades.len() {
+            let trade = self.trades[index];
+            self.track_extremes(trade.timestamp, trade.price);
+        }
+    }
+
+    // Drop trades that fell out of the window ending at `now`.
+    fn evict(&mut self, now: u64) {
+        let expired = |timestamp: u64| timestamp + self.window_millis <= now;
+        while let Some(trade) = self.trades.front().copied() {
+            if !expired(trade.timestamp) {
+                break;
+            }
+            self.trades.pop_front();
+            self.volume -= trade.quantity;
+            self.squared_returns -= trade.squared_return;
+        }
+        if self.trades.is_empty() {
+            // don't let float error build up across windows
+            self.squared_returns = 0.0;
+        }
+        while self
+            .highs
+            .front()
+            .is_some_and(|(timestamp, _)| expired(*timestamp))
+        {
+            self.highs.pop_front();
+        }
+        while self
+            .lows
+            .front()
+            .is_some_and(|(timestamp, _)| expired(*timestamp))
+        {
+            self.lows.pop_front();
+        }
+    }
+
+    pub fn stats(&self) -> RollingStats {
+        RollingStats {
+            window_millis: self.window_millis,
+            trade_count: self.trades.len(),
+            volume: self.volume,
+            high: self.highs.front().map(|(_, price)| *price),
+            low: self.lows.front().map(|(_, price)| *price),
+            realized_volatility: self.squared_returns.max(0.0).sqrt(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RollingStatsFeed {
+    windows: HashMap<TokenTicker, Vec<RollingWindow>>,
+    last_prices: HashMap<TokenTicker, f64>,
+    last_trade_id: u64,
+}
+
+impl RollingStatsFeed {
+    pub fn new() -> RollingStatsFeed {
+        RollingStatsFeed::default()
+    }
+}
+
+impl TradeEngine {
+    // Start tracking `ticker` over `window_millis`. Only trades published
+    // from now on are counted.
+    pub fn track_rolling_stats(&mut self, ticker: TokenTicker, window_millis: u64) {
+        let windows = self.rolling_stats.windows.entry(ticker).or_default();
+        if windows
+            .iter()
+            .all(|window| window.window_millis != window_millis)
+        {
+            windows.push(RollingWindow::new(window_millis));
+        }
+        self.rolling_stats.last_trade_id = self
+            .rolling_stats
+            .last_trade_id
+            .max(self.trade_feed.trades().last().map_or(0, |trade| trade.id));
+    }
+
+    // Fold in trades published since the last update and slide every window
+    // to the engine clock. Called after every matching step.
+    pub fn update_rolling_stats(&mut self) {
+        let now = self.now();
+        let last_trade_id = self.rolling_stats.last_trade_id;
+        let feed = &mut self.rolling_stats;
+        for trade in self.trade_feed.trades_after(last_trade_id) {
+            feed.last_trade_id = trade.id;
+            let previous = feed.last_prices.insert(trade.ticker.clone(), trade.price);
+            if let Some(windows) = feed.windows.get_mut(&trade.ticker) {
+                for window in windows.iter_mut() {
+                    window.push(trade, previous);
+                }
+            }
+        }
+        for window in self.rolling_stats.windows.values_mut().flatten() {
+            window.evict(now);
+        }
+    }
+
+    // Take a busted trade back out of every window that already counted
+    // it. Call after the trade left the feed.
+    pub(crate) fn unwind_rolling_stats(&mut self, trade: &Trade) {
+        let feed = &mut self.rolling_stats;
+        if trade.id > feed.last_trade_id {
+            return;
+        }
+        let previous_price = self
+            .trade_feed
+            .trades_for(&trade.ticker)
+            .filter(|earlier| earlier.id < trade.id)
+            .last()
+            .map(|earlier| earlier.price);
+        let was_last = !self
+            .trade_feed
+            .trades_for(&trade.ticker)
+            .any(|later| later.id > trade.id && later.id <= feed.last_trade_id);
+        if was_last {
+            match previous_price {
+                Some(price) => feed.last_prices.insert(trade.ticker.clone(), price),
+                None => feed.last_prices.remove(&trade.ticker),
+            };
+        }
+        for window in feed.windows.get_mut(&trade.ticker).into_iter().flatten() {
+            window.remove(trade.id, previous_price);
+        }
+    }
+
+    // Statistics as of the last update.
+    pub fn rolling_stats(&self, ticker: &TokenTicker, window_millis: u64) -> Option<RollingStats> {
+        self.rolling_stats
+            .windows
+            .get(ticker)?
+            .iter()
+            .find(|window| window.window_millis == window_millis)
+            .map(|window| window.stats())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::clock::SimulatedClock;
+    use crate::corelib::order::BuyOrSell;
+
+    #[test]
+    fn test_rolling_window_slides_with_clock() {
+        let clock = SimulatedClock::new(0);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        engine.list_new_token(TokenTicker::ETH);
+        engine.track_rolling_stats(TokenTicker::ETH, 1_000);
+        engine.track_rolling_stats(TokenTicker::ETH, 10_000);
+        let trade_at = |engine: &mut TradeEngine, price: f64, quantity: u32| {
+            engine
+                .submit_order(&TokenTicker::ETH, BuyOrSell::Sell, price, quantity)
+                .unwrap();
+            engine
+                .submit_order(&TokenTicker::ETH, BuyOrSell::Buy, price, quantity)
+                .unwrap();
+            engine.match_orders();
+        };
+
+        trade_at(&mut engine, 100.0, 5);
+        clock.advance(600);
+        trade_at(&mut engine, 110.0, 2);
+        clock.advance(600);
+        trade_at(&mut engine, 99.0, 1);
+
+        let short = engine.rolling_stats(&TokenTicker::ETH, 1_000).unwrap();
+        assert_eq!(short.trade_count, 2);
+        assert_eq!(short.volume, 3);
+        assert_eq!((short.high, short.low), (Some(110.0), Some(99.0)));
+        let expected = ((1.1f64).ln().powi(2) + (0.9f64).ln().powi(2)).sqrt();
+        let long = engine.rolling_stats(&TokenTicker::ETH, 10_000).unwrap();
+        assert!((long.realized_volatility - expected).abs() < 1e-12);
+        assert_eq!(long.volume, 8);
+        assert_eq!(long.low, Some(99.0));
+
+        clock.advance(2_000);
+        engine.update_rolling_stats();
+        let short = engine.rolling_stats(&TokenTicker::ETH, 1_000).unwrap();
+        assert_eq!((short.trade_count, short.high), (0, None));
+        assert_eq!(short.realized_volatility, 0.0);
+        assert_eq!(engine.rolling_stats(&TokenTicker::ETH, 5_000), None);
+    }
+}
@@ -0,0 +1,207 @@
+// Direction value moves in when an amount is rounded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    // Paid by a wallet to the house: fees, pool inputs.
+    ToHouse,
+    // Paid out by the house: rebates, pool outputs, rewards.
+    FromHouse,
+    // Moved between wallets, e.g. a settlement notional. Both legs use the
+    // same rounded amount, so nothing is created or lost.
+    Transfer,
+}
+
+// How fractional amounts become whole token units. Every rounding in the
+// engine goes through here.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Rounding {
+    // Charges round up, payouts round down, transfers to nearest (half up).
+    #[default]
+    TowardHouse,
+    // Nearest, ties to even.
+    HalfEven,
+    // Nearest, ties up.
+    HalfUp,
+    // Always down.
+    Truncate,
+}
+
+impl Rounding {
+    // `amount * numerator / denominator`, rounded exactly.
+    pub fn ratio(&self, amount: u64, numerator: u64, denominator: u64, flow: Flow) -> u64 {
+        let product = amount as u128 * numerator as u128;
+        let denominator = denominator as u128;
+        let quotient = product / denominator;
+        let remainder = product % denominator;
+        (quotient + self.rounds_up(quotient, remainder, denominator, flow) as u128) as u64
+    }
+
+    // `ratio` for amounts past u64, e.g. AMM reserves scaled by the fee
+    // denominator, through a 256-bit product. None if the result does not
+    // fit a u128.
+    #[cfg(feature = "precise-amm")]
+    pub fn ratio_wide(
+        &self,
+        amount: u128,
+        numerator: u128,
+        denominator: u128,
+        flow: Flow,
+    ) -> Option<u128> {
+        let (quotient, remainder) = mul_div_wide(amount, numerator, denominator)?;
+        quotient.checked_add(self.rounds_up(quotient, remainder, denominator, flow) as u128)
+    }
+
+    // Whether `quotient + remainder / denominator` rounds to `quotient + 1`.
+    fn rounds_up(&self, quotient: u128, remainder: u128, denominator: u128, flow: Flow) -> bool {
+        // remainder * 2 against denominator, without overflowing
+        let half = remainder.cmp(&(denominator - remainder));
+        match (self, flow) {
+            (_, _) if remainder == 0 => false,
+            (Rounding::TowardHouse, Flow::ToHouse) => true,
+            (Rounding::TowardHouse, Flow::FromHouse) | (Rounding::Truncate, _) => false,
+            (Rounding::TowardHouse, Flow::Transfer) | (Rounding::HalfUp, _) => half.is_ge(),
+            (Rounding::HalfEven, _) => half.is_gt() || (half.is_eq() && quotient % 2 == 1),
+        }
+    }
+
+    // Round a non-negative float amount, e.g. a price times a quantity.
+    pub fn amount(&self, value: f64, flow: Flow) -> u64 {
+        let floor = value.floor();
+        let fraction = value - floor;
+        let round_up = match (self, flow) {
+            (_, _) if fraction == 0.0 => false,
+            (Rounding::TowardHouse, Flow::ToHouse) => true,
+            (Rounding::TowardHouse, Flow::FromHouse) | (Rounding::Truncate, _) => false,
+            (Rounding::TowardHouse, Flow::Transfer) | (Rounding::HalfUp, _) => fraction >= 0.5,
+            (Rounding::HalfEven, _) => fraction > 0.5 || (fraction == 0.5 && floor % 2.0 == 1.0),
+        };
+        (floor + round_up as u8 as f64) as u64
+    }
+}
+
+// `a * b / d` and its remainder, exactly. None if the quotient does not fit
+// a u128.
+#[cfg(feature = "precise-amm")]
+fn mul_div_wide(a: u128, b: u128, d: u128) -> Option<(u128, u128)> {
+    // 256-bit product from 64-bit halves
+    let mask = u64::MAX as u128;
+    let (a_hi, a_lo, b_hi, b_lo) = (a >> 64, a & mask, b >> 64, b & mask);
+    let low = a_lo * b_lo;
+    let (middle, middle_carry) = (a_hi * b_lo).overflowing_add(a_lo * b_hi);
+    let (low, low_carry) = low.overflowing_add(middle << 64);
+    let high = a_hi * b_hi + (middle >> 64) + ((middle_carry as u128) << 64) + low_carry as u128;
+    if high >= d {
+        return None;
+    }
+    // long division, one bit of `low` at a time
+    let (mut remainder, mut quotient) = (high, 0u128);
+    for bit in (0..128).rev() {
+        let carry = remainder >> 127;
+        remainder = (remainder << 1) | ((low >> bit) & 1);
+        quotient <<= 1;
+        if carry == 1 || remainder >= d {
+            remainder = remainder.wrapping_sub(d);
+            quotient |= 1;
+        }
+    }
+    Some((quotient, remainder))
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::engine::TradeEngine;
+    use crate::corelib::fees::FeeRouting;
+    use crate::corelib::order::{BuyOrSell, Wallet};
+    use crate::corelib::token::TokenTicker;
+
+    #[test]
+    fn test_rounding_modes() {
+        // 7 * 5 / 2 = 17.5
+        let results: Vec<(u64, u64, u64)> = [
+            Rounding::TowardHouse,
+            Rounding::HalfEven,
+            Rounding::HalfUp,
+            Rounding::Truncate,
+        ]
+        .iter()
+        .map(|rounding| {
+            (
+                rounding.ratio(7, 5, 2, Flow::ToHouse),
+                rounding.ratio(7, 5, 2, Flow::FromHouse),
+                rounding.ratio(7, 5, 2, Flow::Transfer),
+            )
+        })
+        .collect();
+        assert_eq!(
+            results,
+            vec![(18, 17, 18), (18, 18, 18), (18, 18, 18), (17, 17, 17)]
+        );
+        assert_eq!(Rounding::HalfEven.ratio(5, 1, 2, Flow::Transfer), 2);
+        assert_eq!(Rounding::HalfEven.amount(2.5, Flow::Transfer), 2);
+        assert_eq!(Rounding::HalfEven.amount(3.5, Flow::Transfer), 4);
+        assert_eq!(Rounding::TowardHouse.amount(2.01, Flow::ToHouse), 3);
+        assert_eq!(Rounding::TowardHouse.amount(2.99, Flow::FromHouse), 2);
+        assert_eq!(Rounding::Truncate.ratio(10, 3, 3, Flow::ToHouse), 10);
+    }
+
+    #[test]
+    fn test_trade_legs_conserve_value_under_every_policy() {
+        for rounding in [
+            Rounding::TowardHouse,
+            Rounding::HalfEven,
+            Rounding::HalfUp,
+            Rounding::Truncate,
+        ] {
+            let mut engine = TradeEngine::new();
+            engine.set_rounding(rounding);
+            engine.fees.set_referral_share_bps(3_333).unwrap();
+            engine
+                .fees
+                .set_routing(FeeRouting {
+                    trading_treasury_bps: 1_111,
+                    swap_treasury_bps: 0,
+                })
+                .unwrap();
+            let buyer = Wallet::new(String::from("roundingbuyer"));
+            let seller = Wallet::new(String::from("roundingseller"));
+            engine
+                .fees
+                .register_referrer(buyer.clone(), Wallet::new(String::from("roundingref")))
+                .unwrap();
+            engine.list_new_token(TokenTicker::ETH);
+            engine
+                .submit_order(&TokenTicker::ETH, BuyOrSell::Sell, 33.35, 1)
+                .unwrap();
+            engine
+                .ledger
+                .deposit(buyer.clone(), TokenTicker::USDT, 1_000_000)
+                .unwrap();
+            engine
+                .ledger
+                .deposit(seller.clone(), TokenTicker::ETH, 1_000)
+                .unwrap();
+
+            let supply =
+                |engine: &TradeEngine, token: &TokenTicker| engine.ledger.total_supply(token);
+            let usdt_before = supply(&engine, &TokenTicker::USDT);
+            let eth_before = supply(&engine, &TokenTicker::ETH);
+            engine
+                .report_block_trade(TokenTicker::ETH, buyer.clone(), seller.clone(), 33.35, 333)
+                .unwrap();
+            let notional = engine.ledger.free_balance(&seller, &TokenTicker::USDT);
+            let fee = engine
+                .charge_taker_fee(&buyer, &TokenTicker::USDT, notional)
+                .unwrap()
+                .amount;
+            engine.fees.settle_referrals(&mut engine.ledger);
+
+            assert_eq!(supply(&engine, &TokenTicker::ETH), eth_before);
+            assert_eq!(supply(&engine, &TokenTicker::USDT), usdt_before);
+            assert_eq!(
+                engine.ledger.free_balance(&buyer, &TokenTicker::USDT),
+                1_000_000 - notional - fee
+            );
+        }
+    }
+}
@@ -0,0 +1,266 @@
+use super::amm::AMMPool;
+use super::engine::{DeadlineExpired, TradeEngine};
+use super::ledger::LedgerError;
+use super::order::Wallet;
+use super::token::{Pair, TokenTicker};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RouteError {
+    ZeroAmount,
+    // No pool trades the pair, or it cannot fill the amount.
+    NoRoute,
+    // The output would be below the caller's minimum.
+    Slippage { amount_out: u64, min_out: u64 },
+    DeadlineExpired(DeadlineExpired),
+    Ledger(LedgerError),
+}
+
+impl From<LedgerError> for RouteError {
+    fn from(err: LedgerError) -> Self {
+        RouteError::Ledger(err)
+    }
+}
+
+impl From<DeadlineExpired> for RouteError {
+    fn from(err: DeadlineExpired) -> Self {
+        RouteError::DeadlineExpired(err)
+    }
+}
+
+// Where a swap would execute and what it would pay.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolQuote {
+    pub pair: Pair,
+    // Fee tier of a parallel pool; None for the pair's primary pool.
+    pub tier: Option<u64>,
+    pub fee_bps: u64,
+    pub amount_out: u64,
+}
+
+impl TradeEngine {
+    // Pool trading `a` against `b`, whichever way round the pair was listed.
+    pub fn pool_pair(&self, a: &TokenTicker, b: &TokenTicker) -> Option<Pair> {
+        [
+            Pair::new(a.clone(), b.clone()),
+            Pair::new(b.clone(), a.clone()),
+        ]
+        .into_iter()
+        .find(|pair| self.amm_pools.contains_key(pair))
+    }
+
+    // Every pool trading `a` against `b`: the primary pool and any parallel
+    // pools at other fee tiers.
+    pub fn pools_for(&self, a: &TokenTicker, b: &TokenTicker) -> Vec<(Pair, Option<u64>)> {
+        let mut pools: Vec<(Pair, Option<u64>)> = self
+            .pool_pair(a, b)
+            .map(|pair| (pair, None))
+            .into_iter()
+            .collect();
+        let mut tiers: Vec<(Pair, Option<u64>)> = self
+            .tier_pools
+            .keys()
+            .filter(|(pair, _)| {
+                (&pair.ticker_a, &pair.ticker_b) == (a, b)
+                    || (&pair.ticker_a, &pair.ticker_b) == (b, a)
+            })
+            .map(|(pair, tier)| (pair.clone(), Some(*tier)))
+            .collect();
+        tiers.sort_by_key(|(_, tier)| *tier);
+        pools.extend(tiers);
+        pools
+    }
+
+    pub(crate) fn pool(&self, pair: &Pair, tier: Option<u64>) -> Option<&AMMPool> {
+        match tier {
+            None => self.amm_pools.get(pair),
+            Some(tier) => self.tier_pools.get(&(pair.clone(), tier)),
+        }
+    }
+
+    pub(crate) fn pool_mut(&mut self, pair: &Pair, tier: Option<u64>) -> Option<&mut AMMPool> {
+        match tier {
+            None => self.amm_pools.get_mut(pair),
+            Some(tier) => self.tier_pools.get_mut(&(pair.clone(), tier)),
+        }
+    }
+
+    // The pool paying the most for `amount_in`. Deeper pools win on large
+    // trades despite higher fees; ties go to the lower fee.
+    pub fn best_pool(
+        &self,
+        token_in: &TokenTicker,
+        token_out: &TokenTicker,
+        amount_in: u64,
+    ) -> Option<PoolQuote> {
+        self.pools_for(token_in, token_out)
+            .into_iter()
+            .filter_map(|(pair, tier)| {
+                let pool = self.pool(&pair, tier)?;
+                let amount_out = pool
+                    .quote_exact_in(token_in, token_out, amount_in)
+                    .filter(|amount_out| *amount_out > 0)?;
+                Some(PoolQuote {
+                    fee_bps: pool.fee_bps,
+                    pair,
+                    tier,
+                    amount_out,
+                })
+            })
+            .max_by(|a, b| {
+                a.amount_out
+                    .cmp(&b.amount_out)
+                    .then(b.fee_bps.cmp(&a.fee_bps))
+            })
+    }
+
+    // Market swap: sell exactly `amount_in` of the wallet's `token_in` for
+    // `token_out` through the best pool for the size. Returns the amount
+    // received.
+    pub fn swap_exact_in(
+        &mut self,
+        wallet: &Wallet,
+        token_in: &TokenTicker,
+        token_out: &TokenTicker,
+        amount_in: u64,
+        min_out: u64,
+        deadline: u64,
+    ) -> Result<u64, RouteError> {
+        self.check_deadline(deadline)?;
+        if amount_in == 0 {
+            return Err(RouteError::ZeroAmount);
+        }
+        let PoolQuote {
+            pair,
+            tier,
+            amount_out,
+            ..
+        } = self
+            .best_pool(token_in, token_out, amount_in)
+            .ok_or(RouteError::NoRoute)?;
+        if amount_out < min_out {
+            return Err(RouteError::Slippage {
+                amount_out,
+                min_out,
+            });
+        }
+        self.ledger.debit_free(wallet, token_in, amount_in)?;
+        self.pool_mut(&pair, tier)
+            .unwrap()
+            .swap_exact_in(token_in, token_out, amount_in);
+        self.ledger.credit(wallet, token_out, amount_out);
+        Ok(amount_out)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_swap_exact_in_through_pool() {
+        let mut engine = TradeEngine::new();
+        let wallet = Wallet::new(String::from("routerwallet"));
+        let pool = engine
+            .amm_pools
+            .entry(Pair::new(TokenTicker::ETH, TokenTicker::USDT))
+            .or_default();
+        pool.add_liquidity(TokenTicker::ETH, 1_000);
+        pool.add_liquidity(TokenTicker::USDT, 2_000_000);
+        pool.fee_bps = 30;
+        engine
+            .ledger
+            .deposit(wallet.clone(), TokenTicker::USDT, 1_000_000)
+            .unwrap();
+
+        assert_eq!(
+            engine.swap_exact_in(
+                &wallet,
+                &TokenTicker::USDT,
+                &TokenTicker::ETH,
+                200_000,
+                100,
+                u64::MAX
+            ),
+            Err(RouteError::Slippage {
+                amount_out: 90,
+                min_out: 100
+            })
+        );
+        // listed as ETH/USDT, routed either way round
+        let received = engine
+            .swap_exact_in(
+                &wallet,
+                &TokenTicker::USDT,
+                &TokenTicker::ETH,
+                200_000,
+                90,
+                u64::MAX,
+            )
+            .unwrap();
+        assert_eq!(received, 90);
+        assert_eq!(
+            engine.ledger.free_balance(&wallet, &TokenTicker::USDT),
+            800_000
+        );
+        assert_eq!(engine.ledger.free_balance(&wallet, &TokenTicker::ETH), 90);
+        assert_eq!(
+            engine.swap_exact_in(
+                &wallet,
+                &TokenTicker::USDT,
+                &TokenTicker::BTC,
+                1,
+                0,
+                u64::MAX
+            ),
+            Err(RouteError::NoRoute)
+        );
+    }
+
+    #[test]
+    fn test_router_picks_fee_tier_by_size() {
+        let mut engine = TradeEngine::new();
+        let wallet = Wallet::new(String::from("tierwallet"));
+        let pair = Pair::new(TokenTicker::ETH, TokenTicker::USDT);
+        for (tier, eth, usdt) in [(5, 100, 200_000), (30, 10_000, 20_000_000)] {
+            let pool = engine.tier_pools.entry((pair.clone(), tier)).or_default();
+            pool.add_liquidity(TokenTicker::ETH, eth);
+            pool.add_liquidity(TokenTicker::USDT, usdt);
+            pool.fee_bps = tier;
+        }
+        engine
+            .ledger
+            .deposit(wallet.clone(), TokenTicker::USDT, 10_000_000)
+            .unwrap();
+
+        // small trades take the cheap shallow pool, large ones the deep pool
+        let small = engine
+            .best_pool(&TokenTicker::USDT, &TokenTicker::ETH, 20_000)
+            .unwrap();
+        assert_eq!(small.tier, Some(5));
+        let large = engine
+            .best_pool(&TokenTicker::USDT, &TokenTicker::ETH, 1_000_000)
+            .unwrap();
+        assert_eq!(large.tier, Some(30));
+
+        let received = engine
+            .swap_exact_in(
+                &wallet,
+                &TokenTicker::USDT,
+                &TokenTicker::ETH,
+                1_000_000,
+                0,
+                u64::MAX,
+            )
+            .unwrap();
+        assert_eq!(received, large.amount_out);
+        assert_eq!(
+            engine.tier_pools[&(pair.clone(), 30)].reserve(&TokenTicker::USDT),
+            Some(21_000_000)
+        );
+        assert_eq!(
+            engine.tier_pools[&(pair, 5)].reserve(&TokenTicker::USDT),
+            Some(200_000)
+        );
+    }
+}
@@ -0,0 +1,165 @@
+use super::command::Command;
+use super::order::{BuyOrSell, TimeInForce};
+use super::token::TokenTicker;
+use super::wal::WalRecord;
+
+// Record model written by this build. Each bump comes with a variant in
+// `VersionedRecord` and an upgrade from the previous version, so logs from
+// older builds still replay.
+//
+// 1: the original model.
+// 2: logged orders carry a time in force.
+// 3: logged orders carry their metadata.
+pub const WAL_VERSION: u16 = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandV1 {
+    SubmitOrder {
+        ticker: TokenTicker,
+        side: BuyOrSell,
+        price: f64,
+        quantity: u32,
+    },
+    // Commands whose shape has not changed since.
+    Unchanged(Command),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WalRecordV1 {
+    pub sequence: u64,
+    pub timestamp: u64,
+    pub command: CommandV1,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandV2 {
+    SubmitOrder {
+        ticker: TokenTicker,
+        side: BuyOrSell,
+        price: f64,
+        quantity: u32,
+        time_in_force: TimeInForce,
+    },
+    // Commands whose shape has not changed since.
+    Unchanged(Command),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WalRecordV2 {
+    pub sequence: u64,
+    pub timestamp: u64,
+    pub command: CommandV2,
+}
+
+// A record in the model of the version that wrote it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VersionedRecord {
+    V1(WalRecordV1),
+    V2(WalRecordV2),
+    V3(WalRecord),
+}
+
+impl VersionedRecord {
+    pub fn version(&self) -> u16 {
+        match self {
+            VersionedRecord::V1(_) => 1,
+            VersionedRecord::V2(_) => 2,
+            VersionedRecord::V3(_) => 3,
+        }
+    }
+
+    // Bring the record up to the current model one version at a time.
+    pub fn upgrade(self) -> WalRecord {
+        match self {
+            VersionedRecord::V1(record) => VersionedRecord::V2(upgrade_v1(record)).upgrade(),
+            VersionedRecord::V2(record) => VersionedRecord::V3(upgrade_v2(record)).upgrade(),
+            VersionedRecord::V3(record) => record,
+        }
+    }
+}
+
+// Orders logged before version 2 were all good till cancel.
+pub fn upgrade_v1(record: WalRecordV1) -> WalRecordV2 {
+    let command = match record.command {
+        CommandV1::SubmitOrder {
+            ticker,
+            side,
+            price,
+            quantity,
+        } => CommandV2::SubmitOrder {
+            ticker,
+            side,
+            price,
+            quantity,
+            time_in_force: TimeInForce::GoodTillCancel,
+        },
+        CommandV1::Unchanged(command) => CommandV2::Unchanged(command),
+    };
+    WalRecordV2 {
+        sequence: record.sequence,
+        timestamp: record.timestamp,
+        command,
+    }
+}
+
+// Orders logged before version 3 carried no metadata.
+pub fn upgrade_v2(record: WalRecordV2) -> WalRecord {
+    let command = match record.command {
+        CommandV2::SubmitOrder {
+            ticker,
+            side,
+            price,
+            quantity,
+            time_in_force,
+        } => Command::SubmitOrder {
+            ticker,
+            side,
+            price,
+            quantity,
+            time_in_force,
+            metadata: None,
+        },
+        CommandV2::Unchanged(command) => command,
+    };
+    WalRecord {
+        sequence: record.sequence,
+        timestamp: record.timestamp,
+        command,
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_upgrade_v1_defaults_time_in_force_and_metadata() {
+        let record = VersionedRecord::V1(WalRecordV1 {
+            sequence: 4,
+            timestamp: 20,
+            command: CommandV1::SubmitOrder {
+                ticker: TokenTicker::ETH,
+                side: BuyOrSell::Sell,
+                price: 12.5,
+                quantity: 3,
+            },
+        });
+        assert_eq!(record.version(), 1);
+        assert_eq!(
+            record.upgrade(),
+            WalRecord {
+                sequence: 4,
+                timestamp: 20,
+                command: Command::SubmitOrder {
+                    ticker: TokenTicker::ETH,
+                    side: BuyOrSell::Sell,
+                    price: 12.5,
+                    quantity: 3,
+                    time_in_force: TimeInForce::GoodTillCancel,
+                    metadata: None,
+                },
+            }
+        );
+    }
+}
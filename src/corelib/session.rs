@@ -0,0 +1,213 @@
+use chrono::{DateTime, Datelike, Duration, NaiveTime, Weekday};
+
+use super::token::TokenTicker;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionState {
+    // No order entry.
+    Closed,
+    // Orders are accepted but not matched until the open.
+    PreOpen,
+    // Continuous trading.
+    Open,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionTransition {
+    pub ticker: TokenTicker,
+    pub from: SessionState,
+    pub to: SessionState,
+    pub timestamp: u64,
+    // Day orders cancelled because the session closed.
+    pub expired_orders: Vec<u64>,
+}
+
+// Why a session schedule was refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduleError {
+    // The session must close after it opens on the same day.
+    CloseNotAfterOpen,
+    NegativePreOpen,
+    // The pre-open window would start on the previous day.
+    PreOpenCrossesMidnight,
+}
+
+// Daily trading hours for one instrument, in UTC. The session must open and
+// close on the same day; days not listed in `trading_days` stay closed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionSchedule {
+    pub open: NaiveTime,
+    pub close: NaiveTime,
+    pub pre_open: Duration,
+    pub trading_days: Vec<Weekday>,
+}
+
+impl SessionSchedule {
+    // Weekday session without a pre-open window.
+    pub fn new(open: NaiveTime, close: NaiveTime) -> SessionSchedule {
+        SessionSchedule {
+            open,
+            close,
+            pre_open: Duration::zero(),
+            trading_days: vec![
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+                Weekday::Fri,
+            ],
+        }
+    }
+
+    pub fn with_pre_open(mut self, pre_open: Duration) -> SessionSchedule {
+        self.pre_open = pre_open;
+        self
+    }
+
+    pub fn with_trading_days(mut self, trading_days: Vec<Weekday>) -> SessionSchedule {
+        self.trading_days = trading_days;
+        self
+    }
+
+    // Whether the schedule fits within a day, as `state_at` assumes.
+    pub fn validate(&self) -> Result<(), ScheduleError> {
+        if self.close <= self.open {
+            return Err(ScheduleError::CloseNotAfterOpen);
+        }
+        if self.pre_open < Duration::zero() {
+            return Err(ScheduleError::NegativePreOpen);
+        }
+        if self.pre_open > self.open - NaiveTime::MIN {
+            return Err(ScheduleError::PreOpenCrossesMidnight);
+        }
+        Ok(())
+    }
+
+    pub fn state_at(&self, millis: u64) -> SessionState {
+        let now = match DateTime::from_timestamp_millis(millis as i64) {
+            Some(now) => now,
+            None => return SessionState::Closed,
+        };
+        if !self.trading_days.contains(&now.weekday()) {
+            return SessionState::Closed;
+        }
+        let time = now.time();
+        let pre_open_start = self.open - self.pre_open;
+        if self.open <= time && time < self.close {
+            SessionState::Open
+        } else if pre_open_start <= time && time < self.open && pre_open_start <= self.open {
+            SessionState::PreOpen
+        } else {
+            SessionState::Closed
+        }
+    }
+
+    // Time of the most recent open at or before `millis`.
+    pub fn last_open_at(&self, millis: u64) -> Option<u64> {
+        let now = DateTime::from_timestamp_millis(millis as i64)?;
+        (0..=7).find_map(|days_back| {
+            let date = now.date_naive() - Duration::days(days_back);
+            let open = date.and_time(self.open).and_utc();
+            (self.trading_days.contains(&date.weekday()) && open <= now)
+                .then(|| open.timestamp_millis() as u64)
+        })
+    }
+
+    // Time of the most recent close at or before `millis`.
+    pub fn last_close_at(&self, millis: u64) -> Option<u64> {
+        let now = DateTime::from_timestamp_millis(millis as i64)?;
+        (0..=7).find_map(|days_back| {
+            let date = now.date_naive() - Duration::days(days_back);
+            let close = date.and_time(self.close).and_utc();
+            (self.trading_days.contains(&date.weekday()) && close <= now)
+                .then(|| close.timestamp_millis() as u64)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    // 2024-01-05 is a Friday.
+    fn millis(day: u32, hour: u32, minute: u32) -> u64 {
+        chrono::NaiveDate::from_ymd_opt(2024, 1, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+            .and_utc()
+            .timestamp_millis() as u64
+    }
+
+    #[test]
+    fn test_state_at() {
+        let schedule = SessionSchedule::new(
+            NaiveTime::from_hms_opt(9, 30, 0).unwrap(),
+            NaiveTime::from_hms_opt(16, 0, 0).unwrap(),
+        )
+        .with_pre_open(Duration::minutes(30));
+
+        assert_eq!(schedule.state_at(millis(5, 8, 59)), SessionState::Closed);
+        assert_eq!(schedule.state_at(millis(5, 9, 0)), SessionState::PreOpen);
+        assert_eq!(schedule.state_at(millis(5, 9, 30)), SessionState::Open);
+        assert_eq!(schedule.state_at(millis(5, 15, 59)), SessionState::Open);
+        assert_eq!(schedule.state_at(millis(5, 16, 0)), SessionState::Closed);
+        // Saturday
+        assert_eq!(schedule.state_at(millis(6, 10, 0)), SessionState::Closed);
+
+        assert_eq!(
+            schedule.last_open_at(millis(5, 12, 0)),
+            Some(millis(5, 9, 30))
+        );
+        // over the weekend the last open is Friday's
+        assert_eq!(
+            schedule.last_open_at(millis(8, 9, 0)),
+            Some(millis(5, 9, 30))
+        );
+    }
+
+    #[test]
+    fn test_validate_and_last_close() {
+        let nine = NaiveTime::from_hms_opt(9, 0, 0).unwrap();
+        let five = NaiveTime::from_hms_opt(17, 0, 0).unwrap();
+        assert_eq!(SessionSchedule::new(nine, five).validate(), Ok(()));
+        assert_eq!(
+            SessionSchedule::new(five, nine).validate(),
+            Err(ScheduleError::CloseNotAfterOpen)
+        );
+        assert_eq!(
+            SessionSchedule::new(nine, nine).validate(),
+            Err(ScheduleError::CloseNotAfterOpen)
+        );
+        assert_eq!(
+            SessionSchedule::new(nine, five)
+                .with_pre_open(Duration::hours(10))
+                .validate(),
+            Err(ScheduleError::PreOpenCrossesMidnight)
+        );
+        assert_eq!(
+            SessionSchedule::new(nine, five)
+                .with_pre_open(Duration::hours(9))
+                .validate(),
+            Ok(())
+        );
+        assert_eq!(
+            SessionSchedule::new(nine, five)
+                .with_pre_open(Duration::minutes(-5))
+                .validate(),
+            Err(ScheduleError::NegativePreOpen)
+        );
+
+        // over the weekend the last close is Friday's
+        let schedule = SessionSchedule::new(nine, five);
+        assert_eq!(
+            schedule.last_close_at(millis(8, 10, 0)),
+            Some(millis(5, 17, 0))
+        );
+        assert_eq!(
+            schedule.last_close_at(millis(8, 17, 0)),
+            Some(millis(8, 17, 0))
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use super::command::Command;
+use super::engine::TradeEngine;
+use super::order::BuyOrSell;
+use super::orderbook::OrderBookTrait;
+use super::rng::SeededRng;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpeedBumpDelay {
+    Fixed {
+        millis: u64,
+    },
+    // Uniform in `min_millis..=max_millis`, drawn from a seeded generator so
+    // replays under a simulated clock hold orders for the same time.
+    Random {
+        min_millis: u64,
+        max_millis: u64,
+        seed: u64,
+    },
+}
+
+// Holds orders that would take liquidity for a delay before they reach the
+// book, giving resting orders time to reprice.
+#[derive(Debug, Clone)]
+pub struct SpeedBump {
+    pub delay: SpeedBumpDelay,
+    rng: SeededRng,
+}
+
+impl SpeedBump {
+    pub fn new(delay: SpeedBumpDelay) -> SpeedBump {
+        let seed = match delay {
+            SpeedBumpDelay::Fixed { .. } => 0,
+            SpeedBumpDelay::Random { seed, .. } => seed,
+        };
+        SpeedBump {
+            delay,
+            rng: SeededRng::new(seed),
+        }
+    }
+
+    pub fn next_delay(&mut self) -> u64 {
+        match self.delay {
+            SpeedBumpDelay::Fixed { millis } => millis,
+            SpeedBumpDelay::Random {
+                min_millis,
+                max_millis,
+                ..
+            } => self.rng.between(min_millis, max_millis),
+        }
+    }
+
+    // Whether the command is an order, or a replace moving a resting order,
+    // that would cross the book as it stands.
+    pub fn is_aggressive(engine: &TradeEngine, command: &Command) -> bool {
+        let (orderbook, side, price) = match command {
+            Command::SubmitOrder {
+                ticker,
+                side,
+                price,
+                ..
+            } => (engine.order_books.get(ticker), *side, price),
+            // a replace re-enters the book on the resting order's side
+            Command::ReplaceOrder {
+                ticker,
+                order_id,
+                price,
+                ..
+            } => {
+                let orderbook = engine.order_books.get(ticker);
+                let Some((side, _)) =
+                    orderbook.and_then(|orderbook| orderbook.find_order(*order_id))
+                else {
+                    return false;
+                };
+                (orderbook, side, price)
+            }
+            _ => return false,
+        };
+        let Some(orderbook) = orderbook else {
+            return false;
+        };
+        match side {
+            BuyOrSell::Buy => orderbook
+                .best_sell_price()
+                .is_some_and(|ask| *price >= ask.into_inner()),
+            BuyOrSell::Sell => orderbook
+                .best_buy_price()
+                .is_some_and(|bid| *price <= bid.into_inner()),
+        }
+    }
+}
+
+impl TradeEngine {
+    // Random delay with a seed drawn from the engine's generator.
+    pub fn random_speed_bump(&mut self, min_millis: u64, max_millis: u64) -> SpeedBump {
+        SpeedBump::new(SpeedBumpDelay::Random {
+            min_millis,
+            max_millis,
+            seed: self.draw_seed(),
+        })
+    }
+}
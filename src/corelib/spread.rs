@@ -0,0 +1,600 @@
+use super::engine::{OrderError, TradeEngine};
+use super::order::{BuyOrSell, Order, OrderMetadata, TimeInForce, Wallet};
+use super::orderbook::OrderBook;
+use super::session::SessionState;
+use super::token::TokenTicker;
+use super::trade::{Trade, TradeKind};
+use super::validation::validate_order;
+
+// Buying one unit of a spread buys one unit of `front` and sells one of
+// `back`, e.g. a calendar spread between two dated contracts or an
+// inter-product spread between related tokens. Spread prices are front
+// minus back and may be negative.
+pub struct SpreadInstrument {
+    pub name: String,
+    pub front: TokenTicker,
+    pub back: TokenTicker,
+    pub book: OrderBook,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpreadError {
+    AlreadyDefined,
+    UnknownSpread,
+    UnknownLeg(TokenTicker),
+    SameLegs,
+    Order(OrderError),
+}
+
+impl From<OrderError> for SpreadError {
+    fn from(err: OrderError) -> Self {
+        SpreadError::Order(err)
+    }
+}
+
+// Best price on one side of a book and the quantity available there.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImpliedLevel {
+    pub price: f64,
+    pub quantity: u32,
+}
+
+// Prices implied into the spread by both legs' books (implied in), and
+// into each leg by the spread book and the other leg (implied out).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImpliedQuotes {
+    pub spread_bid: Option<ImpliedLevel>,
+    pub spread_ask: Option<ImpliedLevel>,
+    pub front_bid: Option<ImpliedLevel>,
+    pub front_ask: Option<ImpliedLevel>,
+    pub back_bid: Option<ImpliedLevel>,
+    pub back_ask: Option<ImpliedLevel>,
+}
+
+// One spread execution. The legs traded at `front_price` and `back_price`,
+// whose difference is `price`. An order id is None on the side filled
+// from the legs' books.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpreadFill {
+    pub spread: String,
+    pub buy_order_id: Option<u64>,
+    pub sell_order_id: Option<u64>,
+    pub price: f64,
+    pub quantity: u32,
+    pub front_price: f64,
+    pub back_price: f64,
+}
+
+fn best_level(orderbook: &OrderBook, side: BuyOrSell) -> Option<ImpliedLevel> {
+    let price = orderbook.best_order(side)?.price;
+    Some(ImpliedLevel {
+        price,
+        quantity: orderbook
+            .level(side, price)
+            .iter()
+            .map(|order| order.quantity)
+            .sum(),
+    })
+}
+
+// Level implied by trading against both `a` and `b`, at `a` plus `sign`
+// times `b`.
+fn implied(a: Option<ImpliedLevel>, b: Option<ImpliedLevel>, sign: f64) -> Option<ImpliedLevel> {
+    let (a, b) = (a?, b?);
+    Some(ImpliedLevel {
+        price: a.price + sign * b.price,
+        quantity: a.quantity.min(b.quantity),
+    })
+}
+
+// The newest of the three orders in an implied match, which trades at the
+// price the other two imply for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Aggressor {
+    Spread,
+    Front,
+    Back,
+}
+
+fn leg_trade(
+    ticker: &TokenTicker,
+    price: f64,
+    quantity: u32,
+    timestamp: u64,
+    (buyer, buy_order_id, buy_metadata): (Option<Wallet>, Option<u64>, Option<OrderMetadata>),
+    (seller, sell_order_id, sell_metadata): (Option<Wallet>, Option<u64>, Option<OrderMetadata>),
+) -> Trade {
+    Trade {
+        id: 0,
+        ticker: ticker.clone(),
+        price,
+        quantity: quantity as u64,
+        buyer,
+        seller,
+        buy_order_id,
+        sell_order_id,
+        timestamp,
+        kind: TradeKind::Implied,
+        buy_metadata,
+        sell_metadata,
+        settled_notional: None,
+    }
+}
+
+impl TradeEngine {
+    pub fn define_spread(
+        &mut self,
+        name: &str,
+        front: TokenTicker,
+        back: TokenTicker,
+    ) -> Result<(), SpreadError> {
+        if self.spreads.contains_key(name) {
+            return Err(SpreadError::AlreadyDefined);
+        }
+        if front == back {
+            return Err(SpreadError::SameLegs);
+        }
+        if let Some(missing) = [&front, &back]
+            .into_iter()
+            .find(|leg| !self.order_books.contains_key(leg))
+        {
+            return Err(SpreadError::UnknownLeg(missing.clone()));
+        }
+        self.spreads.insert(
+            String::from(name),
+            SpreadInstrument {
+                name: String::from(name),
+                front,
+                back,
+                book: OrderBook::new(),
+            },
+        );
+        Ok(())
+    }
+
+    // Rest an order on the spread book. Both legs must be taking orders.
+    pub fn submit_spread_order(
+        &mut self,
+        wallet: &Wallet,
+        name: &str,
+        side: BuyOrSell,
+        price: f64,
+        quantity: u32,
+    ) -> Result<u64, SpreadError> {
+        self.update_sessions();
+        self.update_cancel_timers();
+        let spread = self.spreads.get(name).ok_or(SpreadError::UnknownSpread)?;
+        self.check_accepting_orders(&spread.front)?;
+        self.check_accepting_orders(&spread.back)?;
+        validate_order(price, quantity)?;
+        let timestamp = self.now();
+        let spread = self.spreads.get_mut(name).unwrap();
+        Ok(spread.book.add_wallet_order(
+            wallet.clone(),
+            side,
+            price,
+            quantity,
+            timestamp,
+            TimeInForce::GoodTillCancel,
+        ))
+    }
+
+    pub fn cancel_spread_order(&mut self, name: &str, order_id: u64) -> Option<Order> {
+        let timestamp = self.now();
+        self.spreads
+            .get_mut(name)?
+            .book
+            .cancel_order(order_id, timestamp)
+    }
+
+    pub fn implied_quotes(&self, name: &str) -> Option<ImpliedQuotes> {
+        let spread = self.spreads.get(name)?;
+        let front = &self.order_books[&spread.front];
+        let back = &self.order_books[&spread.back];
+        let spread_bid = best_level(&spread.book, BuyOrSell::Buy);
+        let spread_ask = best_level(&spread.book, BuyOrSell::Sell);
+        let front_bid = best_level(front, BuyOrSell::Buy);
+        let front_ask = best_level(front, BuyOrSell::Sell);
+        let back_bid = best_level(back, BuyOrSell::Buy);
+        let back_ask = best_level(back, BuyOrSell::Sell);
+        Some(ImpliedQuotes {
+            spread_bid: implied(front_bid, back_ask, -1.0),
+            spread_ask: implied(front_ask, back_bid, -1.0),
+            front_bid: implied(spread_bid, back_bid, 1.0),
+            front_ask: implied(spread_ask, back_ask, 1.0),
+            back_bid: implied(front_bid, spread_ask, -1.0),
+            back_ask: implied(front_ask, spread_bid, -1.0),
+        })
+    }
+
+    // Match every spread whose legs are both trading, publishing the leg
+    // executions as implied trades. Crossed spread orders trade with each
+    // other first, with the back leg at its reference price; then spread
+    // orders trade against the best orders of both legs (implied in), and
+    // leg orders against the spread book and the other leg (implied out).
+    // Called from `match_orders` after the lit books.
+    pub fn match_spreads(&mut self) -> Vec<SpreadFill> {
+        let timestamp = self.now();
+        let mut names: Vec<String> = self.spreads.keys().cloned().collect();
+        names.sort();
+        let mut fills = Vec::new();
+        for name in names {
+            let spread = &self.spreads[&name];
+            let (front, back) = (spread.front.clone(), spread.back.clone());
+            if [&front, &back]
+                .into_iter()
+                .any(|leg| self.session_state(leg) != SessionState::Open || self.is_halted(leg))
+            {
+                continue;
+            }
+            self.match_spread_book(&name, &front, &back, timestamp, &mut fills);
+            self.match_implied(&name, &front, &back, timestamp, &mut fills);
+        }
+        fills
+    }
+
+    fn match_spread_book(
+        &mut self,
+        name: &str,
+        front: &TokenTicker,
+        back: &TokenTicker,
+        timestamp: u64,
+        fills: &mut Vec<SpreadFill>,
+    ) {
+        // without a back leg price the legs cannot be priced; wait
+        let Some(back_price) = self.reference_price(back) else {
+            return;
+        };
+        let spread = self.spreads.get_mut(name).unwrap();
+        for fill in spread.book.match_orders(timestamp) {
+            let front_price = back_price + fill.price;
+            let buyer = (fill.buy_wallet.clone(), None, fill.buy_metadata.clone());
+            let seller = (fill.sell_wallet.clone(), None, fill.sell_metadata.clone());
+            self.trade_feed.publish(leg_trade(
+                front,
+                front_price,
+                fill.quantity,
+                timestamp,
+                buyer.clone(),
+                seller.clone(),
+            ));
+            self.trade_feed.publish(leg_trade(
+                back,
+                back_price,
+                fill.quantity,
+                timestamp,
+                seller,
+                buyer,
+            ));
+            fills.push(SpreadFill {
+                spread: String::from(name),
+                buy_order_id: Some(fill.buy_order_id),
+                sell_order_id: Some(fill.sell_order_id),
+                price: fill.price,
+                quantity: fill.quantity,
+                front_price,
+                back_price,
+            });
+        }
+    }
+
+    // Buying the spread takes the front ask and the back bid; selling it
+    // takes the front bid and the back ask. Whichever of the three orders
+    // arrived last is the aggressor: a spread order fills the legs at their
+    // own prices, a leg order fills at the price the spread order and the
+    // other leg imply for it. Ties go to the spread order.
+    fn match_implied(
+        &mut self,
+        name: &str,
+        front: &TokenTicker,
+        back: &TokenTicker,
+        timestamp: u64,
+        fills: &mut Vec<SpreadFill>,
+    ) {
+        loop {
+            let spread = &self.spreads[name].book;
+            let (front_book, back_book) = (&self.order_books[front], &self.order_books[back]);
+            let crossing = [
+                (BuyOrSell::Buy, BuyOrSell::Sell, BuyOrSell::Buy),
+                (BuyOrSell::Sell, BuyOrSell::Buy, BuyOrSell::Sell),
+            ]
+            .into_iter()
+            .find_map(|(side, front_side, back_side)| {
+                let order = spread.best_order(side)?;
+                let front_order = front_book.best_order(front_side)?;
+                let back_order = back_book.best_order(back_side)?;
+                let legs = front_order.price - back_order.price;
+                let crosses = match side {
+                    BuyOrSell::Buy => order.price >= legs,
+                    BuyOrSell::Sell => order.price <= legs,
+                };
+                let quantity = order
+                    .quantity
+                    .min(front_order.quantity)
+                    .min(back_order.quantity);
+                let aggressor = if front_order.timestamp > order.timestamp.max(back_order.timestamp)
+                {
+                    Aggressor::Front
+                } else if back_order.timestamp > order.timestamp.max(front_order.timestamp) {
+                    Aggressor::Back
+                } else {
+                    Aggressor::Spread
+                };
+                crosses.then_some((side, front_side, back_side, quantity, aggressor))
+            });
+            let Some((side, front_side, back_side, quantity, aggressor)) = crossing else {
+                return;
+            };
+
+            let spread = &mut self.spreads.get_mut(name).unwrap().book;
+            let order = spread.fill_best(side, quantity, timestamp).unwrap();
+            let front_order = self
+                .order_books
+                .get_mut(front)
+                .unwrap()
+                .fill_best(front_side, quantity, timestamp)
+                .unwrap();
+            let back_order = self
+                .order_books
+                .get_mut(back)
+                .unwrap()
+                .fill_best(back_side, quantity, timestamp)
+                .unwrap();
+            let (front_price, back_price) = match aggressor {
+                Aggressor::Spread => (front_order.price, back_order.price),
+                Aggressor::Front => (back_order.price + order.price, back_order.price),
+                Aggressor::Back => (front_order.price, front_order.price - order.price),
+            };
+            let holder = (order.wallet, None, order.metadata);
+            let front_leg = (
+                front_order.wallet,
+                Some(front_order.id),
+                front_order.metadata,
+            );
+            let back_leg = (back_order.wallet, Some(back_order.id), back_order.metadata);
+            let (front_trade, back_trade) = match side {
+                BuyOrSell::Buy => (
+                    leg_trade(
+                        front,
+                        front_price,
+                        quantity,
+                        timestamp,
+                        holder.clone(),
+                        front_leg,
+                    ),
+                    leg_trade(back, back_price, quantity, timestamp, back_leg, holder),
+                ),
+                BuyOrSell::Sell => (
+                    leg_trade(
+                        front,
+                        front_price,
+                        quantity,
+                        timestamp,
+                        front_leg,
+                        holder.clone(),
+                    ),
+                    leg_trade(back, back_price, quantity, timestamp, holder, back_leg),
+                ),
+            };
+            self.trade_feed.publish(front_trade);
+            self.trade_feed.publish(back_trade);
+            let (buy_order_id, sell_order_id) = match side {
+                BuyOrSell::Buy => (Some(order.id), None),
+                BuyOrSell::Sell => (None, Some(order.id)),
+            };
+            fills.push(SpreadFill {
+                spread: String::from(name),
+                buy_order_id,
+                sell_order_id,
+                price: front_price - back_price,
+                quantity,
+                front_price,
+                back_price,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    fn calendar() -> TradeEngine {
+        let mut engine = TradeEngine::new();
+        engine.list_new_token(TokenTicker::ETH);
+        engine.list_new_token(TokenTicker::BTC);
+        engine
+            .define_spread("ETH-BTC", TokenTicker::ETH, TokenTicker::BTC)
+            .unwrap();
+        engine
+    }
+
+    #[test]
+    fn test_spread_order_fills_against_both_legs() {
+        let mut engine = calendar();
+        let trader = Wallet::new(String::from("spreadtrader"));
+        let front = Wallet::new(String::from("frontseller"));
+        let back = Wallet::new(String::from("backbuyer"));
+        engine
+            .submit_wallet_order(
+                &front,
+                &TokenTicker::ETH,
+                BuyOrSell::Sell,
+                105.0,
+                5,
+                TimeInForce::GoodTillCancel,
+            )
+            .unwrap();
+        engine
+            .submit_wallet_order(
+                &back,
+                &TokenTicker::BTC,
+                BuyOrSell::Buy,
+                100.0,
+                3,
+                TimeInForce::GoodTillCancel,
+            )
+            .unwrap();
+        assert_eq!(
+            engine.implied_quotes("ETH-BTC").unwrap().spread_ask,
+            Some(ImpliedLevel {
+                price: 5.0,
+                quantity: 3
+            })
+        );
+
+        let order = engine
+            .submit_spread_order(&trader, "ETH-BTC", BuyOrSell::Buy, 6.0, 4)
+            .unwrap();
+        engine.match_orders();
+        let trades = engine.trade_feed.trades();
+        assert_eq!(trades.len(), 2);
+        assert!(trades.iter().all(|trade| trade.kind == TradeKind::Implied));
+        let (eth, btc) = (&trades[0], &trades[1]);
+        assert_eq!((eth.price, eth.quantity), (105.0, 3));
+        assert_eq!(
+            (eth.buyer.as_ref(), eth.seller.as_ref()),
+            (Some(&trader), Some(&front))
+        );
+        assert_eq!((btc.price, btc.quantity), (100.0, 3));
+        assert_eq!(
+            (btc.buyer.as_ref(), btc.seller.as_ref()),
+            (Some(&back), Some(&trader))
+        );
+
+        let spread = &engine.spreads["ETH-BTC"].book;
+        assert_eq!(spread.find_order(order).unwrap().1.quantity, 1);
+        assert!(engine.order_books[&TokenTicker::BTC]
+            .best_order(BuyOrSell::Buy)
+            .is_none());
+        assert_eq!(
+            engine.order_books[&TokenTicker::ETH]
+                .best_order(BuyOrSell::Sell)
+                .unwrap()
+                .quantity,
+            2
+        );
+    }
+
+    #[test]
+    fn test_leg_order_trades_against_implied_price() {
+        let mut engine = calendar();
+        let trader = Wallet::new(String::from("spreadseller"));
+        let back = Wallet::new(String::from("backseller"));
+        engine
+            .submit_spread_order(&trader, "ETH-BTC", BuyOrSell::Sell, 2.0, 10)
+            .unwrap();
+        engine
+            .submit_wallet_order(
+                &back,
+                &TokenTicker::BTC,
+                BuyOrSell::Sell,
+                50.0,
+                10,
+                TimeInForce::GoodTillCancel,
+            )
+            .unwrap();
+        // selling the spread and buying back offers the front at 52
+        let quotes = engine.implied_quotes("ETH-BTC").unwrap();
+        assert_eq!(
+            quotes.front_ask,
+            Some(ImpliedLevel {
+                price: 52.0,
+                quantity: 10
+            })
+        );
+        assert_eq!(quotes.front_bid, None);
+
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Buy, 51.0, 4)
+            .unwrap();
+        assert!(engine.match_spreads().is_empty());
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Buy, 52.0, 4)
+            .unwrap();
+        let fills = engine.match_spreads();
+        assert_eq!(
+            fills,
+            vec![SpreadFill {
+                spread: String::from("ETH-BTC"),
+                buy_order_id: None,
+                sell_order_id: Some(1),
+                price: 2.0,
+                quantity: 4,
+                front_price: 52.0,
+                back_price: 50.0,
+            }]
+        );
+        assert_eq!(
+            engine.implied_quotes("ETH-BTC").unwrap().front_ask,
+            Some(ImpliedLevel {
+                price: 52.0,
+                quantity: 6
+            })
+        );
+    }
+
+    #[test]
+    fn test_leg_aggressor_trades_at_the_implied_out_price() {
+        use crate::corelib::clock::SimulatedClock;
+
+        let clock = SimulatedClock::new(0);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        engine.list_new_token(TokenTicker::ETH);
+        engine.list_new_token(TokenTicker::BTC);
+        engine
+            .define_spread("ETH-BTC", TokenTicker::ETH, TokenTicker::BTC)
+            .unwrap();
+        let trader = Wallet::new(String::from("spreadseller"));
+        engine
+            .submit_spread_order(&trader, "ETH-BTC", BuyOrSell::Sell, 2.0, 10)
+            .unwrap();
+        clock.advance(1);
+        engine
+            .submit_order(&TokenTicker::BTC, BuyOrSell::Sell, 50.0, 10)
+            .unwrap();
+        clock.advance(1);
+        // the front bid pays the implied 52, not its own limit
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Buy, 53.0, 4)
+            .unwrap();
+        let fills = engine.match_spreads();
+        assert_eq!(
+            fills
+                .iter()
+                .map(|fill| (fill.price, fill.front_price, fill.back_price))
+                .collect::<Vec<_>>(),
+            vec![(2.0, 52.0, 50.0)]
+        );
+
+        // and a back bid lifts the implied back offer of a spread bid
+        let clock = SimulatedClock::new(0);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        engine.list_new_token(TokenTicker::ETH);
+        engine.list_new_token(TokenTicker::BTC);
+        engine
+            .define_spread("ETH-BTC", TokenTicker::ETH, TokenTicker::BTC)
+            .unwrap();
+        let buyer = Wallet::new(String::from("spreadbuyer"));
+        engine
+            .submit_spread_order(&buyer, "ETH-BTC", BuyOrSell::Buy, 5.0, 3)
+            .unwrap();
+        clock.advance(1);
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Sell, 105.0, 3)
+            .unwrap();
+        clock.advance(1);
+        engine
+            .submit_order(&TokenTicker::BTC, BuyOrSell::Buy, 101.0, 3)
+            .unwrap();
+        let fills = engine.match_spreads();
+        assert_eq!(
+            fills
+                .iter()
+                .map(|fill| (fill.price, fill.front_price, fill.back_price))
+                .collect::<Vec<_>>(),
+            vec![(5.0, 105.0, 100.0)]
+        );
+    }
+}
@@ -0,0 +1,340 @@
+use std::collections::HashMap;
+
+use super::ledger::{rescale_amount, Ledger, LedgerError};
+use super::order::Wallet;
+use super::token::TokenTicker;
+
+// Fixed point scale used for the reward-per-share accumulator.
+const REWARD_SCALE: u128 = 1_000_000_000_000;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stake {
+    pub amount: u64,
+    pub pending_rewards: u64,
+    reward_debt: u128,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StakingError {
+    Ledger(LedgerError),
+    InsufficientStake { staked: u64, requested: u64 },
+}
+
+impl From<LedgerError> for StakingError {
+    fn from(err: LedgerError) -> Self {
+        StakingError::Ledger(err)
+    }
+}
+
+// Wallets stake `token` in the ledger and earn `reward_token` from the reward
+// pool. Every elapsed interval releases `reward_per_interval` (bounded by
+// what is left in the pool), shared pro rata between the stakers.
+pub struct StakingPool {
+    pub token: TokenTicker,
+    pub reward_token: TokenTicker,
+    pub reward_per_interval: u64,
+    reward_pool: u64,
+    // Emitted from the reward pool but not claimed yet, including rounding
+    // dust no staker can claim.
+    outstanding_rewards: u64,
+    total_staked: u64,
+    acc_reward_per_share: u128,
+    last_interval: u64,
+    stakes: HashMap<Wallet, Stake>,
+}
+
+impl StakingPool {
+    pub fn new(
+        token: TokenTicker,
+        reward_token: TokenTicker,
+        reward_per_interval: u64,
+        start_interval: u64,
+    ) -> StakingPool {
+        StakingPool {
+            token,
+            reward_token,
+            reward_per_interval,
+            reward_pool: 0,
+            outstanding_rewards: 0,
+            total_staked: 0,
+            acc_reward_per_share: 0,
+            last_interval: start_interval,
+            stakes: HashMap::new(),
+        }
+    }
+
+    pub fn reward_pool(&self) -> u64 {
+        self.reward_pool
+    }
+
+    pub fn outstanding_rewards(&self) -> u64 {
+        self.outstanding_rewards
+    }
+
+    pub fn total_staked(&self) -> u64 {
+        self.total_staked
+    }
+
+    pub fn stake_of(&self, wallet: &Wallet) -> Stake {
+        self.stakes.get(wallet).cloned().unwrap_or_default()
+    }
+
+    // Rewards accrued by `wallet` up to the last call to `advance`.
+    pub fn pending_rewards(&self, wallet: &Wallet) -> u64 {
+        match self.stakes.get(wallet) {
+            Some(stake) => stake.pending_rewards + self.unsettled(stake),
+            None => 0,
+        }
+    }
+
+    // Move reward tokens from the funder's free balance into the reward pool.
+    pub fn fund(
+        &mut self,
+        ledger: &mut Ledger,
+        funder: &Wallet,
+        amount: u64,
+    ) -> Result<(), StakingError> {
+        ledger.debit_free(funder, &self.reward_token, amount)?;
+        self.reward_pool += amount;
+        Ok(())
+    }
+
+    // Accrue rewards for every interval elapsed since the last call.
+    pub fn advance(&mut self, interval: u64) {
+        if interval <= self.last_interval {
+            return;
+        }
+        let elapsed = interval - self.last_interval;
+        self.last_interval = interval;
+
+        if self.total_staked == 0 {
+            return;
+        }
+        let emitted = self
+            .reward_per_interval
+            .saturating_mul(elapsed)
+            .min(self.reward_pool);
+        self.reward_pool -= emitted;
+        self.outstanding_rewards += emitted;
+        self.acc_reward_per_share += emitted as u128 * REWARD_SCALE / self.total_staked as u128;
+    }
+
+    pub fn stake(
+        &mut self,
+        ledger: &mut Ledger,
+        wallet: &Wallet,
+        amount: u64,
+    ) -> Result<(), StakingError> {
+        ledger.stake(wallet, &self.token, amount)?;
+        self.settle(wallet);
+        let acc = self.acc_reward_per_share;
+        let stake = self.stakes.entry(wallet.clone()).or_default();
+        stake.amount += amount;
+        stake.reward_debt = stake.amount as u128 * acc;
+        self.total_staked += amount;
+        Ok(())
+    }
+
+    pub fn unstake(
+        &mut self,
+        ledger: &mut Ledger,
+        wallet: &Wallet,
+        amount: u64,
+    ) -> Result<(), StakingError> {
+        let staked = self.stake_of(wallet).amount;
+        if staked < amount {
+            return Err(StakingError::InsufficientStake {
+                staked,
+                requested: amount,
+            });
+        }
+        ledger.unstake(wallet, &self.token, amount)?;
+        self.settle(wallet);
+        let acc = self.acc_reward_per_share;
+        let stake = self.stakes.entry(wallet.clone()).or_default();
+        stake.amount -= amount;
+        stake.reward_debt = stake.amount as u128 * acc;
+        self.total_staked -= amount;
+        Ok(())
+    }
+
+    // Credit all pending rewards to the wallet's free balance.
+    pub fn claim(&mut self, ledger: &mut Ledger, wallet: &Wallet) -> u64 {
+        self.settle(wallet);
+        let claimed = match self.stakes.get_mut(wallet) {
+            Some(stake) => std::mem::take(&mut stake.pending_rewards),
+            None => 0,
+        };
+        self.outstanding_rewards -= claimed;
+        if claimed > 0 {
+            ledger.credit(wallet, &self.reward_token, claimed);
+        }
+        claimed
+    }
+
+    // Whether `rescale_token` with the same ratio leaves every stake, reward
+    // balance and reward debt in range.
+    pub(crate) fn can_rescale_token(
+        &self,
+        token: &TokenTicker,
+        numerator: u64,
+        denominator: u64,
+    ) -> bool {
+        let scale = |amount: u64, applies: bool| {
+            if applies {
+                rescale_amount(amount, numerator, denominator)
+            } else {
+                Some(amount)
+            }
+        };
+        let staked = token == &self.token;
+        let rewarded = token == &self.reward_token;
+        let mut acc = Some(self.acc_reward_per_share);
+        if staked {
+            acc = acc.map(|acc| acc * denominator as u128 / numerator as u128);
+        }
+        if rewarded {
+            acc = acc
+                .and_then(|acc| acc.checked_mul(numerator as u128))
+                .map(|acc| acc / denominator as u128);
+        }
+        let Some(acc) = acc else {
+            return false;
+        };
+        if scale(self.reward_pool, rewarded).is_none()
+            || scale(self.outstanding_rewards, rewarded).is_none()
+        {
+            return false;
+        }
+        let mut total_staked: u64 = 0;
+        for stake in self.stakes.values() {
+            let pending = stake.pending_rewards.checked_add(self.unsettled(stake));
+            let amount = scale(stake.amount, staked);
+            let fits = pending
+                .and_then(|pending| scale(pending, rewarded))
+                .is_some()
+                && amount
+                    .and_then(|amount| (amount as u128).checked_mul(acc))
+                    .is_some();
+            match amount.and_then(|amount| total_staked.checked_add(amount)) {
+                Some(total) if fits => total_staked = total,
+                _ => return false,
+            }
+        }
+        true
+    }
+
+    // Apply a redenomination of `token` (`numerator / denominator` new units
+    // per old unit) to stakes and rewards, rounding down.
+    pub(crate) fn rescale_token(&mut self, token: &TokenTicker, numerator: u64, denominator: u64) {
+        let rescale = |amount: u64| {
+            rescale_amount(amount, numerator, denominator).expect("rescale checked beforehand")
+        };
+        let wallets: Vec<Wallet> = self.stakes.keys().cloned().collect();
+        for wallet in wallets.iter() {
+            self.settle(wallet);
+        }
+        if token == &self.token {
+            self.total_staked = 0;
+            for stake in self.stakes.values_mut() {
+                stake.amount = rescale(stake.amount);
+                self.total_staked += stake.amount;
+            }
+            self.acc_reward_per_share =
+                self.acc_reward_per_share * denominator as u128 / numerator as u128;
+        }
+        if token == &self.reward_token {
+            self.reward_pool = rescale(self.reward_pool);
+            self.outstanding_rewards = rescale(self.outstanding_rewards);
+            for stake in self.stakes.values_mut() {
+                stake.pending_rewards = rescale(stake.pending_rewards);
+            }
+            self.acc_reward_per_share =
+                self.acc_reward_per_share * numerator as u128 / denominator as u128;
+        }
+        let acc = self.acc_reward_per_share;
+        for stake in self.stakes.values_mut() {
+            stake.reward_debt = stake.amount as u128 * acc;
+        }
+    }
+
+    fn unsettled(&self, stake: &Stake) -> u64 {
+        ((stake.amount as u128 * self.acc_reward_per_share - stake.reward_debt) / REWARD_SCALE)
+            as u64
+    }
+
+    // Fold accrued rewards into `pending_rewards` and reset the debt.
+    fn settle(&mut self, wallet: &Wallet) {
+        let acc = self.acc_reward_per_share;
+        if let Some(stake) = self.stakes.get(wallet) {
+            let accrued = self.unsettled(stake);
+            let stake = self.stakes.get_mut(wallet).unwrap();
+            stake.pending_rewards += accrued;
+            stake.reward_debt = stake.amount as u128 * acc;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_rewards_shared_pro_rata() {
+        let mut ledger = Ledger::new();
+        let treasury = Wallet::new(String::from("treasurywallet"));
+        let alice = Wallet::new(String::from("alicewallet"));
+        let bob = Wallet::new(String::from("bobwallet"));
+        ledger
+            .deposit(treasury.clone(), TokenTicker::USDT, 1000)
+            .unwrap();
+        ledger
+            .deposit(alice.clone(), TokenTicker::ETH, 300)
+            .unwrap();
+        ledger.deposit(bob.clone(), TokenTicker::ETH, 100).unwrap();
+
+        let mut pool = StakingPool::new(TokenTicker::ETH, TokenTicker::USDT, 100, 0);
+        pool.fund(&mut ledger, &treasury, 1000).unwrap();
+        pool.stake(&mut ledger, &alice, 300).unwrap();
+        pool.stake(&mut ledger, &bob, 100).unwrap();
+
+        // staked tokens are locked in the ledger
+        assert_eq!(ledger.free_balance(&alice, &TokenTicker::ETH), 0);
+        assert_eq!(ledger.locked_balance(&alice, &TokenTicker::ETH), 300);
+
+        pool.advance(4);
+        assert_eq!(pool.pending_rewards(&alice), 300);
+        assert_eq!(pool.pending_rewards(&bob), 100);
+        assert_eq!(pool.reward_pool(), 600);
+
+        assert_eq!(pool.claim(&mut ledger, &alice), 300);
+        assert_eq!(ledger.free_balance(&alice, &TokenTicker::USDT), 300);
+        assert_eq!(pool.pending_rewards(&alice), 0);
+    }
+
+    #[test]
+    fn test_unstake_and_exhausted_pool() {
+        let mut ledger = Ledger::new();
+        let treasury = Wallet::new(String::from("treasurywallet"));
+        let alice = Wallet::new(String::from("alicewallet"));
+        ledger
+            .deposit(treasury.clone(), TokenTicker::USDT, 150)
+            .unwrap();
+        ledger.deposit(alice.clone(), TokenTicker::ETH, 50).unwrap();
+
+        let mut pool = StakingPool::new(TokenTicker::ETH, TokenTicker::USDT, 100, 0);
+        pool.fund(&mut ledger, &treasury, 150).unwrap();
+        pool.stake(&mut ledger, &alice, 50).unwrap();
+
+        // only what is left in the reward pool can be emitted
+        pool.advance(10);
+        assert_eq!(pool.pending_rewards(&alice), 150);
+        assert_eq!(pool.reward_pool(), 0);
+
+        assert!(pool.unstake(&mut ledger, &alice, 51).is_err());
+        pool.unstake(&mut ledger, &alice, 50).unwrap();
+        assert_eq!(ledger.free_balance(&alice, &TokenTicker::ETH), 50);
+        assert_eq!(pool.pending_rewards(&alice), 150);
+    }
+}
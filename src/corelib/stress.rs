@@ -0,0 +1,227 @@
+use std::collections::HashSet;
+
+use super::amm::AMMPool;
+use super::engine::TradeEngine;
+use super::margin::Scenario;
+use super::order::Wallet;
+use super::token::{Pair, TokenTicker};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WalletStress {
+    pub wallet: Wallet,
+    // Equity in the quote token: balances, LP shares, position collateral
+    // and unrealized PnL.
+    pub equity_before: f64,
+    pub equity_after: f64,
+    pub impact: f64,
+    // Position notional after the shock.
+    pub notional_after: f64,
+    pub breaches_maintenance: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct StressReport {
+    // Wallets by address.
+    pub wallets: Vec<WalletStress>,
+    // Held tokens with no index price, valued at zero.
+    pub unpriced: Vec<TokenTicker>,
+}
+
+impl StressReport {
+    pub fn breaches(&self) -> Vec<&Wallet> {
+        self.wallets
+            .iter()
+            .filter(|stress| stress.breaches_maintenance)
+            .map(|stress| &stress.wallet)
+            .collect()
+    }
+}
+
+impl TradeEngine {
+    // Revalue every wallet under `scenario`. Token prices come from the index
+    // (the quote token is the numeraire and never moves), positions move by
+    // the scenario's price move, and pools are rebalanced by arbitrage to the
+    // shocked price ratio, so LP shares carry their impermanent loss. A wallet
+    // breaches when its shocked equity is below `maintenance_rate` of its
+    // shocked position notional.
+    pub fn stress_test(&self, scenario: &Scenario, maintenance_rate: f64) -> StressReport {
+        let mut wallets: HashSet<Wallet> = self.ledger.wallets().cloned().collect();
+        wallets.extend(
+            self.positions
+                .iter()
+                .map(|position| position.wallet.clone()),
+        );
+        let pools: Vec<(&Pair, &AMMPool)> = self
+            .amm_pools
+            .iter()
+            .chain(self.tier_pools.iter().map(|((pair, _), pool)| (pair, pool)))
+            .collect();
+        for (pair, pool) in pools.iter() {
+            wallets.extend(pool.lp_holders(pair).into_iter().map(|(wallet, _)| wallet));
+        }
+
+        let mut report = StressReport::default();
+        let mut unpriced = HashSet::new();
+        for wallet in wallets {
+            let mut equity = [0.0, 0.0];
+            for (index, shocked) in [false, true].into_iter().enumerate() {
+                for (token, balance) in self.ledger.holdings(&wallet) {
+                    match self.stressed_price(&token, scenario, shocked) {
+                        Some(price) => equity[index] += balance.total() as f64 * price,
+                        None => {
+                            unpriced.insert(token);
+                        }
+                    }
+                }
+                for (pair, pool) in pools.iter() {
+                    let lp = pool.lp_balance(&wallet, pair);
+                    if lp > 0 {
+                        equity[index] += self.pool_value(pair, pool, scenario, shocked) * lp as f64
+                            / pool.total_lp_tokens(pair) as f64;
+                    }
+                }
+            }
+            let mut notional_after = 0.0;
+            for position in self.positions.iter().filter(|p| p.wallet == wallet) {
+                let shocked_mark = position.mark_price * (1.0 + scenario.price_move(position));
+                equity[0] += position.collateral as f64 + position.unrealized_pnl();
+                equity[1] += position.collateral as f64
+                    + position.quantity as f64 * (shocked_mark - position.entry_price);
+                notional_after += position.quantity.unsigned_abs() as f64 * shocked_mark;
+            }
+            report.wallets.push(WalletStress {
+                wallet,
+                equity_before: equity[0],
+                equity_after: equity[1],
+                impact: equity[1] - equity[0],
+                notional_after,
+                breaches_maintenance: notional_after > 0.0
+                    && equity[1] < notional_after * maintenance_rate,
+            });
+        }
+        report
+            .wallets
+            .sort_by(|a, b| a.wallet.address.cmp(&b.wallet.address));
+        report.unpriced = unpriced.into_iter().collect();
+        report.unpriced.sort();
+        report
+    }
+
+    fn stressed_price(
+        &self,
+        token: &TokenTicker,
+        scenario: &Scenario,
+        shocked: bool,
+    ) -> Option<f64> {
+        if *token == self.quote_token {
+            return Some(1.0);
+        }
+        if let TokenTicker::Lp(pair) = token {
+            let pool = self.amm_pools.get(pair)?;
+            let supply = pool.total_lp_tokens(pair);
+            if supply == 0 {
+                return None;
+            }
+            return Some(self.pool_value(pair, pool, scenario, shocked) / supply as f64);
+        }
+        let price = self.index.latest(token)?;
+        if !shocked {
+            return Some(price);
+        }
+        Some(price * (1.0 + scenario.underlying_move(token)))
+    }
+
+    // Reserves valued in the quote token. Under a shock the constant product
+    // stays put while arbitrage moves the pool price by the ratio of the two
+    // tokens' moves: the reserve of the token that gained shrinks by the
+    // square root of that ratio and the other grows by it.
+    fn pool_value(&self, pair: &Pair, pool: &AMMPool, scenario: &Scenario, shocked: bool) -> f64 {
+        let reserve_a = pool.reserve(&pair.ticker_a).unwrap_or(0) as f64;
+        let reserve_b = pool.reserve(&pair.ticker_b).unwrap_or(0) as f64;
+        let (Some(price_a), Some(price_b)) = (
+            self.stressed_price(&pair.ticker_a, scenario, shocked),
+            self.stressed_price(&pair.ticker_b, scenario, shocked),
+        ) else {
+            return 0.0;
+        };
+        if !shocked {
+            return reserve_a * price_a + reserve_b * price_b;
+        }
+        let (Some(before_a), Some(before_b)) = (
+            self.stressed_price(&pair.ticker_a, scenario, false),
+            self.stressed_price(&pair.ticker_b, scenario, false),
+        ) else {
+            return 0.0;
+        };
+        let ratio = (price_a / before_a) / (price_b / before_b);
+        let root = ratio.sqrt();
+        reserve_a / root * price_a + reserve_b * root * price_b
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::margin::{InstrumentKind, Position};
+
+    #[test]
+    fn test_shock_reports_impact_and_breaches() {
+        let mut engine = TradeEngine::new();
+        let quote = engine.quote_token.clone();
+        let holder = Wallet::new(String::from("holder"));
+        let trader = Wallet::new(String::from("trader"));
+        let lp = Wallet::new(String::from("lp"));
+        engine.publish_index_price(TokenTicker::ETH, 100.0);
+        engine
+            .ledger
+            .deposit(holder.clone(), TokenTicker::ETH, 10)
+            .unwrap();
+        engine
+            .ledger
+            .deposit(holder.clone(), quote.clone(), 500)
+            .unwrap();
+        engine.positions.push(Position {
+            wallet: trader.clone(),
+            underlying: TokenTicker::ETH,
+            kind: InstrumentKind::Perpetual,
+            quantity: 10,
+            entry_price: 100.0,
+            mark_price: 100.0,
+            collateral: 250,
+        });
+        let pair = Pair::new(TokenTicker::ETH, quote.clone());
+        engine.amm_pools.entry(pair.clone()).or_default().deposit(
+            &lp,
+            &pair,
+            100_000,
+            10_000_000,
+            u64::MAX,
+            0,
+        );
+
+        let mut scenario = Scenario::uniform(0.0);
+        scenario.moves.insert(TokenTicker::ETH, -0.2);
+        let report = engine.stress_test(&scenario, 0.1);
+        let by_name = |name: &str| {
+            report
+                .wallets
+                .iter()
+                .find(|stress| stress.wallet.address == name)
+                .unwrap()
+        };
+
+        assert_eq!(by_name("holder").equity_before, 1_500.0);
+        assert_eq!(by_name("holder").impact, -200.0);
+        // 250 collateral minus 200 loss against 800 shocked notional
+        let trader_stress = by_name("trader");
+        assert_eq!(trader_stress.equity_after, 50.0);
+        assert!(trader_stress.breaches_maintenance);
+        assert_eq!(report.breaches(), vec![&trader]);
+
+        // the LP loses sqrt(0.8) - 1, less than the 10% holding would
+        let lp_stress = by_name("lp");
+        let loss = lp_stress.impact / lp_stress.equity_before;
+        assert!((loss - (0.8f64.sqrt() - 1.0)).abs() < 1e-9);
+    }
+}
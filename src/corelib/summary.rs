@@ -0,0 +1,162 @@
+use super::engine::TradeEngine;
+use super::retention::Candle;
+use super::token::TokenTicker;
+
+const DAY_MILLIS: u64 = 24 * 60 * 60 * 1_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarketSummary {
+    pub ticker: TokenTicker,
+    pub last_price: Option<f64>,
+    // Over the last 24 hours.
+    pub volume: u64,
+    pub high: Option<f64>,
+    pub low: Option<f64>,
+    // Last price against the last trade before the window, or the first
+    // trade in it for instruments that did not trade before.
+    pub change_percent: Option<f64>,
+    // Derivatives only.
+    pub open_interest: Option<u64>,
+    pub long_short_ratio: Option<f64>,
+}
+
+impl TradeEngine {
+    // Rolling 24h summary of every listed instrument, by ticker.
+    pub fn market_summary(&self) -> Vec<MarketSummary> {
+        let now = self.now();
+        let since = now.saturating_sub(DAY_MILLIS);
+        let mut tickers: Vec<&TokenTicker> = self.order_books.keys().collect();
+        tickers.sort();
+        tickers
+            .into_iter()
+            .map(|ticker| {
+                let mut summary = MarketSummary {
+                    ticker: ticker.clone(),
+                    last_price: None,
+                    volume: 0,
+                    high: None,
+                    low: None,
+                    change_percent: None,
+                    open_interest: None,
+                    long_short_ratio: None,
+                };
+                if let Some(open_interest) = self.open_interest(ticker) {
+                    summary.open_interest = Some(open_interest.contracts);
+                    summary.long_short_ratio = open_interest.long_short_ratio();
+                }
+                // Compacted history first, as (start, open, high, low, close,
+                // volume); a candle counts as in the window if it starts in it.
+                let candles = self.candles(ticker).iter().map(|candle| {
+                    let Candle {
+                        start_millis,
+                        open,
+                        high,
+                        low,
+                        close,
+                        volume,
+                        ..
+                    } = *candle;
+                    (start_millis, open, high, low, close, volume)
+                });
+                let trades = self.trade_feed.trades_for(ticker).map(|trade| {
+                    let price = trade.price;
+                    (trade.timestamp, price, price, price, price, trade.quantity)
+                });
+                let mut open = None;
+                for (timestamp, first, high, low, last, volume) in candles.chain(trades) {
+                    if timestamp < since {
+                        open = Some(last);
+                        continue;
+                    }
+                    open.get_or_insert(first);
+                    summary.volume += volume;
+                    summary.high = Some(summary.high.map_or(high, |h| h.max(high)));
+                    summary.low = Some(summary.low.map_or(low, |l| l.min(low)));
+                    summary.last_price = Some(last);
+                }
+                summary.last_price = summary.last_price.or(open);
+                summary.change_percent = match (open, summary.last_price) {
+                    (Some(open), Some(last)) if open != 0.0 => Some((last - open) / open * 100.0),
+                    _ => None,
+                };
+                summary
+            })
+            .collect()
+    }
+
+    // The `n` instruments with the largest absolute 24h change.
+    pub fn top_movers(&self, n: usize) -> Vec<MarketSummary> {
+        let mut movers: Vec<MarketSummary> = self
+            .market_summary()
+            .into_iter()
+            .filter(|summary| summary.change_percent.is_some())
+            .collect();
+        movers.sort_by(|a, b| {
+            let change = |summary: &MarketSummary| summary.change_percent.unwrap().abs();
+            change(b).total_cmp(&change(a))
+        });
+        movers.truncate(n);
+        movers
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::clock::SimulatedClock;
+    use crate::corelib::trade::{Trade, TradeKind};
+
+    #[test]
+    fn test_market_summary_over_rolling_day() {
+        let clock = SimulatedClock::new(0);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        for ticker in [TokenTicker::ETH, TokenTicker::BTC, TokenTicker::SOL] {
+            engine.list_new_token(ticker);
+        }
+        let mut trade = |ticker: TokenTicker, price: f64, quantity: u64, timestamp: u64| {
+            engine.trade_feed.publish(Trade {
+                id: 0,
+                ticker,
+                price,
+                quantity,
+                buyer: None,
+                seller: None,
+                buy_order_id: None,
+                sell_order_id: None,
+                timestamp,
+                kind: TradeKind::Lit,
+                buy_metadata: None,
+                sell_metadata: None,
+                settled_notional: None,
+            });
+        };
+        trade(TokenTicker::ETH, 100.0, 5, 0);
+        trade(TokenTicker::ETH, 120.0, 2, DAY_MILLIS + 10);
+        trade(TokenTicker::ETH, 90.0, 3, DAY_MILLIS + 20);
+        trade(TokenTicker::BTC, 50.0, 1, DAY_MILLIS + 10);
+        trade(TokenTicker::BTC, 52.0, 1, DAY_MILLIS + 20);
+        clock.advance(DAY_MILLIS + 100);
+
+        let summary = engine.market_summary();
+        assert_eq!(summary.len(), 3);
+        let eth = summary
+            .iter()
+            .find(|s| s.ticker == TokenTicker::ETH)
+            .unwrap();
+        assert_eq!(eth.volume, 5);
+        assert_eq!((eth.high, eth.low), (Some(120.0), Some(90.0)));
+        assert_eq!(eth.last_price, Some(90.0));
+        assert_eq!(eth.change_percent, Some(-10.0));
+        let sol = summary
+            .iter()
+            .find(|s| s.ticker == TokenTicker::SOL)
+            .unwrap();
+        assert_eq!(sol.last_price, None);
+
+        let movers = engine.top_movers(1);
+        assert_eq!(movers.len(), 1);
+        assert_eq!(movers[0].ticker, TokenTicker::ETH);
+        assert_eq!(engine.top_movers(5).len(), 2);
+    }
+}
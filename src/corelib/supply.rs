@@ -0,0 +1,307 @@
+use std::collections::BTreeMap;
+
+use super::audit::AuditAction;
+use super::engine::TradeEngine;
+use super::ledger::SupplyError;
+use super::order::{BuyOrSell, Wallet};
+use super::token::TokenTicker;
+
+// Where one token's supply sits.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TokenSupply {
+    // Held in the ledger, free and locked.
+    pub ledger: u64,
+    // Part of `ledger` locked by stakes, withdrawals, escrows and the like.
+    pub locked: u64,
+    // AMM reserves, the token's TVL.
+    pub in_pools: u64,
+    // Promised by wallets' resting orders: the base token for asks, the
+    // quote notional for bids. Orders do not lock ledger funds, so this is
+    // a claim on free balances rather than a separate holding.
+    pub reserved_for_orders: u64,
+    // Free ledger balance not promised to resting orders.
+    pub free_float: u64,
+}
+
+impl TokenSupply {
+    pub fn total(&self) -> u64 {
+        self.ledger + self.in_pools
+    }
+}
+
+impl TradeEngine {
+    // Supply breakdown for every token the engine holds, including tokens
+    // only found in pools.
+    pub fn supply_report(&self) -> BTreeMap<TokenTicker, TokenSupply> {
+        let mut report: BTreeMap<TokenTicker, TokenSupply> = self
+            .value_totals()
+            .into_keys()
+            .map(|token| {
+                let ledger = self.ledger.total_supply(&token);
+                let locked = self.ledger.total_locked(&token);
+                let in_pools = self
+                    .amm_pools
+                    .values()
+                    .chain(self.tier_pools.values())
+                    .map(|pool| pool.reserve(&token).unwrap_or(0))
+                    .sum();
+                let supply = TokenSupply {
+                    ledger,
+                    locked,
+                    in_pools,
+                    ..TokenSupply::default()
+                };
+                (token, supply)
+            })
+            .collect();
+
+        for (ticker, orderbook) in &self.order_books {
+            for side in [BuyOrSell::Buy, BuyOrSell::Sell] {
+                for order in orderbook
+                    .orders(side)
+                    .filter(|order| order.wallet.is_some())
+                {
+                    let (token, amount) = match side {
+                        BuyOrSell::Buy => (
+                            &self.quote_token,
+                            self.order_notional(order.price, order.quantity),
+                        ),
+                        BuyOrSell::Sell => (ticker, order.quantity as u64),
+                    };
+                    report.entry(token.clone()).or_default().reserved_for_orders += amount;
+                }
+            }
+        }
+        for supply in report.values_mut() {
+            supply.free_float =
+                (supply.ledger - supply.locked).saturating_sub(supply.reserved_for_orders);
+        }
+        report
+    }
+
+    pub fn grant_mint_authority(&mut self, token: &TokenTicker, authority: &Wallet) -> u64 {
+        self.ledger
+            .grant_mint_authority(token.clone(), authority.clone());
+        let now = self.now();
+        self.audit_log.record(
+            now,
+            AuditAction::MintAuthorityGranted {
+                token: token.clone(),
+                authority: authority.clone(),
+            },
+        )
+    }
+
+    // None if `authority` did not hold it; nothing is recorded then.
+    pub fn revoke_mint_authority(
+        &mut self,
+        token: &TokenTicker,
+        authority: &Wallet,
+    ) -> Option<u64> {
+        if !self.ledger.revoke_mint_authority(token, authority) {
+            return None;
+        }
+        let now = self.now();
+        Some(self.audit_log.record(
+            now,
+            AuditAction::MintAuthorityRevoked {
+                token: token.clone(),
+                authority: authority.clone(),
+            },
+        ))
+    }
+
+    pub fn set_supply_cap(&mut self, token: &TokenTicker, cap: Option<u64>) -> u64 {
+        self.ledger.set_supply_cap(token.clone(), cap);
+        let now = self.now();
+        self.audit_log.record(
+            now,
+            AuditAction::SupplyCapSet {
+                token: token.clone(),
+                cap,
+            },
+        )
+    }
+
+    // Audited `Ledger::mint`. Returns the audit sequence number; refusals
+    // are not recorded.
+    pub fn mint(
+        &mut self,
+        token: &TokenTicker,
+        wallet: &Wallet,
+        amount: u64,
+        authority: &Wallet,
+    ) -> Result<u64, SupplyError> {
+        self.ledger.mint(token, wallet, amount, authority)?;
+        let now = self.now();
+        Ok(self.audit_log.record(
+            now,
+            AuditAction::Minted {
+                token: token.clone(),
+                wallet: wallet.clone(),
+                amount,
+                authority: authority.clone(),
+            },
+        ))
+    }
+
+    pub fn burn(
+        &mut self,
+        token: &TokenTicker,
+        wallet: &Wallet,
+        amount: u64,
+        authority: &Wallet,
+    ) -> Result<u64, SupplyError> {
+        self.ledger.burn(token, wallet, amount, authority)?;
+        let now = self.now();
+        Ok(self.audit_log.record(
+            now,
+            AuditAction::Burned {
+                token: token.clone(),
+                wallet: wallet.clone(),
+                amount,
+                authority: authority.clone(),
+            },
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::ledger::{DepositError, LedgerError};
+    use crate::corelib::order::TimeInForce;
+    use crate::corelib::token::Pair;
+
+    #[test]
+    fn test_mint_and_burn_need_authority_and_respect_cap() {
+        let mut engine = TradeEngine::new();
+        let bridge = Wallet::new(String::from("supplybridge"));
+        let alice = Wallet::new(String::from("supplyalice"));
+        let token = TokenTicker::ETH;
+        let supply = engine.ledger.total_supply(&token);
+
+        assert_eq!(
+            engine.mint(&token, &alice, 10, &bridge),
+            Err(SupplyError::NotAuthorized {
+                token: token.clone(),
+                authority: bridge.clone(),
+            })
+        );
+        engine.grant_mint_authority(&token, &bridge);
+        engine.set_supply_cap(&token, Some(supply + 100));
+
+        let sequence = engine.mint(&token, &alice, 80, &bridge).unwrap();
+        assert_eq!(engine.ledger.free_balance(&alice, &token), 80);
+        assert_eq!(
+            engine.audit_log.records()[sequence as usize - 1].action,
+            AuditAction::Minted {
+                token: token.clone(),
+                wallet: alice.clone(),
+                amount: 80,
+                authority: bridge.clone(),
+            }
+        );
+        assert_eq!(
+            engine.mint(&token, &alice, 21, &bridge),
+            Err(SupplyError::SupplyCapExceeded {
+                token: token.clone(),
+                supply: supply + 80,
+                amount: 21,
+                cap: supply + 100,
+            })
+        );
+
+        // burning frees room under the cap
+        engine.burn(&token, &alice, 30, &bridge).unwrap();
+        assert_eq!(engine.ledger.total_supply(&token), supply + 50);
+        assert!(engine.mint(&token, &alice, 50, &bridge).is_ok());
+        assert_eq!(
+            engine.burn(&token, &alice, 101, &bridge),
+            Err(SupplyError::Ledger(LedgerError::InsufficientFree {
+                token: token.clone(),
+                available: 100,
+                requested: 101,
+            }))
+        );
+
+        // without a cap, the supply still has to fit in a u64
+        engine.set_supply_cap(&token, None);
+        let supply = engine.ledger.total_supply(&token);
+        assert_eq!(
+            engine.mint(&token, &alice, u64::MAX - supply + 1, &bridge),
+            Err(SupplyError::Deposit(DepositError::SupplyOverflow {
+                token: token.clone(),
+                supply,
+                amount: u64::MAX - supply + 1,
+            }))
+        );
+        assert_eq!(engine.ledger.total_supply(&token), supply);
+
+        assert_eq!(engine.revoke_mint_authority(&token, &bridge), Some(7));
+        assert_eq!(engine.revoke_mint_authority(&token, &bridge), None);
+        assert!(engine.burn(&token, &alice, 1, &bridge).is_err());
+        assert_eq!(engine.audit_log.records().len(), 7);
+    }
+
+    #[test]
+    fn test_supply_report_splits_pools_orders_and_free_float() {
+        let mut engine = TradeEngine::new();
+        let alice = Wallet::new(String::from("reportalice"));
+        engine.list_new_token(TokenTicker::ETH);
+        engine
+            .ledger
+            .deposit(alice.clone(), TokenTicker::ETH, 100)
+            .unwrap();
+        engine
+            .ledger
+            .deposit(alice.clone(), TokenTicker::USDT, 5_000)
+            .unwrap();
+        engine
+            .request_withdrawal(&alice, &TokenTicker::ETH, 10)
+            .unwrap();
+        let pool = engine
+            .amm_pools
+            .entry(Pair::new(TokenTicker::ETH, TokenTicker::USDT))
+            .or_default();
+        pool.add_liquidity(TokenTicker::ETH, 40);
+        pool.add_liquidity(TokenTicker::USDT, 120_000);
+        for (side, price, quantity) in [(BuyOrSell::Sell, 3_100.0, 30), (BuyOrSell::Buy, 20.0, 50)]
+        {
+            engine
+                .submit_wallet_order(
+                    &alice,
+                    &TokenTicker::ETH,
+                    side,
+                    price,
+                    quantity,
+                    TimeInForce::GoodTillCancel,
+                )
+                .unwrap();
+        }
+        // anonymous orders are backed by no balance
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Sell, 3_200.0, 5)
+            .unwrap();
+
+        let report = engine.supply_report();
+        assert_eq!(
+            report[&TokenTicker::ETH],
+            TokenSupply {
+                ledger: 100,
+                locked: 10,
+                in_pools: 40,
+                reserved_for_orders: 30,
+                free_float: 60,
+            }
+        );
+        assert_eq!(report[&TokenTicker::ETH].total(), 140);
+        assert_eq!(report[&TokenTicker::USDT].reserved_for_orders, 1_000);
+        assert_eq!(report[&TokenTicker::USDT].free_float, 4_000);
+        assert_eq!(
+            engine.value_totals()[&TokenTicker::USDT],
+            report[&TokenTicker::USDT].total()
+        );
+    }
+}
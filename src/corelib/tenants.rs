@@ -0,0 +1,184 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::clock::{Clock, SharedClock, SystemClock};
+use super::command::{Command, CommandResult};
+use super::engine::TradeEngine;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TenantId(String);
+
+impl TenantId {
+    pub fn new(id: impl Into<String>) -> TenantId {
+        TenantId(id.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for TenantId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TenantError {
+    UnknownTenant { tenant: TenantId },
+    TenantExists { tenant: TenantId },
+}
+
+// Isolated engines hosted in one process, keyed by tenant. Each tenant has
+// its own instruments, ledger, pools, fee schedule and audit log; nothing
+// but the clock is shared, and every command names the tenant it runs in.
+pub struct TenantEngines {
+    clock: SharedClock,
+    tenants: BTreeMap<TenantId, TradeEngine>,
+}
+
+impl Default for TenantEngines {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TenantEngines {
+    pub fn new() -> TenantEngines {
+        TenantEngines::with_clock(Box::new(SystemClock))
+    }
+
+    pub fn with_clock(clock: Box<dyn Clock>) -> TenantEngines {
+        TenantEngines {
+            clock: SharedClock::new(clock),
+            tenants: BTreeMap::new(),
+        }
+    }
+
+    // Move every tenant onto `clock`. A tenant that set its own clock
+    // keeps it.
+    pub fn set_clock(&mut self, clock: Box<dyn Clock>) {
+        self.clock.replace(clock);
+    }
+
+    // Open an empty engine for `tenant`, to be configured through
+    // `tenant_mut`.
+    pub fn create_tenant(&mut self, tenant: TenantId) -> Result<&mut TradeEngine, TenantError> {
+        if self.tenants.contains_key(&tenant) {
+            return Err(TenantError::TenantExists { tenant });
+        }
+        let engine = TradeEngine::with_clock(Box::new(self.clock.clone()));
+        Ok(self.tenants.entry(tenant).or_insert(engine))
+    }
+
+    // Close the tenant and hand back its engine.
+    pub fn remove_tenant(&mut self, tenant: &TenantId) -> Option<TradeEngine> {
+        self.tenants.remove(tenant)
+    }
+
+    pub fn tenants(&self) -> impl Iterator<Item = &TenantId> {
+        self.tenants.keys()
+    }
+
+    pub fn tenant(&self, tenant: &TenantId) -> Result<&TradeEngine, TenantError> {
+        self.tenants
+            .get(tenant)
+            .ok_or_else(|| TenantError::UnknownTenant {
+                tenant: tenant.clone(),
+            })
+    }
+
+    pub fn tenant_mut(&mut self, tenant: &TenantId) -> Result<&mut TradeEngine, TenantError> {
+        self.tenants
+            .get_mut(tenant)
+            .ok_or_else(|| TenantError::UnknownTenant {
+                tenant: tenant.clone(),
+            })
+    }
+
+    // Apply the command to the tenant's engine only.
+    pub fn execute(
+        &mut self,
+        tenant: &TenantId,
+        command: Command,
+    ) -> Result<CommandResult, TenantError> {
+        Ok(self.tenant_mut(tenant)?.execute(command))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::clock::SimulatedClock;
+    use crate::corelib::engine::OrderError;
+    use crate::corelib::order::{BuyOrSell, TimeInForce, Wallet};
+    use crate::corelib::token::TokenTicker;
+
+    #[test]
+    fn test_tenants_share_only_the_clock() {
+        let clock = SimulatedClock::new(500);
+        let mut engines = TenantEngines::with_clock(Box::new(clock.clone()));
+        let acme = TenantId::new("acme");
+        let globex = TenantId::new("globex");
+        engines
+            .create_tenant(acme.clone())
+            .unwrap()
+            .list_new_token(TokenTicker::ETH);
+        engines.create_tenant(globex.clone()).unwrap();
+        assert_eq!(
+            engines.create_tenant(acme.clone()).err(),
+            Some(TenantError::TenantExists {
+                tenant: acme.clone()
+            })
+        );
+
+        let buy = Command::SubmitOrder {
+            ticker: TokenTicker::ETH,
+            side: BuyOrSell::Buy,
+            price: 10.0,
+            quantity: 1,
+            time_in_force: TimeInForce::GoodTillCancel,
+            metadata: None,
+        };
+        assert_eq!(
+            engines.execute(&acme, buy.clone()),
+            Ok(CommandResult::OrderAccepted { order_id: 1 })
+        );
+        assert_eq!(
+            engines.execute(&globex, buy.clone()),
+            Ok(CommandResult::OrderRejected {
+                reason: OrderError::UnknownTicker
+            })
+        );
+        let initech = TenantId::new("initech");
+        assert_eq!(
+            engines.execute(&initech, buy),
+            Err(TenantError::UnknownTenant { tenant: initech })
+        );
+
+        // same wallet, separate ledgers
+        let wallet = Wallet::new(String::from("tenantwallet"));
+        engines
+            .tenant_mut(&acme)
+            .unwrap()
+            .ledger
+            .deposit(wallet.clone(), TokenTicker::USDT, 100)
+            .unwrap();
+        let balance = |engines: &TenantEngines, tenant| {
+            engines
+                .tenant(tenant)
+                .unwrap()
+                .ledger
+                .free_balance(&wallet, &TokenTicker::USDT)
+        };
+        assert_eq!(balance(&engines, &acme), 100);
+        assert_eq!(balance(&engines, &globex), 0);
+
+        clock.set(900);
+        assert!(engines
+            .tenants()
+            .all(|tenant| engines.tenant(tenant).unwrap().now() == 900));
+    }
+}
@@ -0,0 +1,295 @@
+use std::collections::{HashMap, VecDeque};
+
+use super::engine::{OrderError, TradeEngine};
+use super::order::Wallet;
+use super::token::TokenTicker;
+
+// Per-wallet order entry limits on one instrument, tightened while the
+// instrument is under stress. The instrument is stressed once a match pass
+// on its book takes longer than `max_match_nanos` or more than
+// `max_queue_depth` of its orders wait in the command queue, and relaxes
+// once both are back within bounds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThrottlePolicy {
+    pub window_millis: u64,
+    // Orders one wallet may enter per window.
+    pub normal_limit: u32,
+    pub stressed_limit: u32,
+    pub max_match_nanos: u64,
+    pub max_queue_depth: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StressEvent {
+    Stressed {
+        ticker: TokenTicker,
+        match_nanos: u64,
+        queue_depth: usize,
+        timestamp: u64,
+    },
+    Relaxed {
+        ticker: TokenTicker,
+        timestamp: u64,
+    },
+}
+
+pub(crate) struct InstrumentThrottle {
+    policy: ThrottlePolicy,
+    stressed: bool,
+    // Latest observations.
+    match_nanos: u64,
+    queue_depth: usize,
+    // Entry times within the current window, oldest first.
+    entries: HashMap<Wallet, VecDeque<u64>>,
+}
+
+impl InstrumentThrottle {
+    fn limit(&self) -> u32 {
+        if self.stressed {
+            self.policy.stressed_limit
+        } else {
+            self.policy.normal_limit
+        }
+    }
+}
+
+impl TradeEngine {
+    // Throttle wallets' order entry on the instrument, or stop throttling
+    // with None. Replacing a policy keeps the current stress state.
+    pub fn set_throttle_policy(&mut self, ticker: TokenTicker, policy: Option<ThrottlePolicy>) {
+        let Some(policy) = policy else {
+            self.throttles.remove(&ticker);
+            return;
+        };
+        self.throttles
+            .entry(ticker)
+            .and_modify(|throttle| throttle.policy = policy)
+            .or_insert_with(|| InstrumentThrottle {
+                policy,
+                stressed: false,
+                match_nanos: 0,
+                queue_depth: 0,
+                entries: HashMap::new(),
+            });
+    }
+
+    pub fn throttle_policy(&self, ticker: &TokenTicker) -> Option<ThrottlePolicy> {
+        self.throttles.get(ticker).map(|throttle| throttle.policy)
+    }
+
+    pub fn is_stressed(&self, ticker: &TokenTicker) -> bool {
+        self.throttles
+            .get(ticker)
+            .is_some_and(|throttle| throttle.stressed)
+    }
+
+    // Orders one wallet may currently enter per window on the instrument.
+    pub fn current_order_limit(&self, ticker: &TokenTicker) -> Option<u32> {
+        self.throttles.get(ticker).map(InstrumentThrottle::limit)
+    }
+
+    pub(crate) fn throttled_instruments(&self) -> Vec<TokenTicker> {
+        self.throttles.keys().cloned().collect()
+    }
+
+    // Duration of the latest match pass on the instrument's book. The
+    // engine reports its own passes; ignored without a policy.
+    pub fn observe_match_latency(&mut self, ticker: &TokenTicker, nanos: u64) {
+        if let Some(throttle) = self.throttles.get_mut(ticker) {
+            throttle.match_nanos = nanos;
+            self.update_stress(ticker);
+        }
+    }
+
+    // Orders for the instrument waiting to be applied. `CommandQueue`
+    // reports its own depth; ignored without a policy.
+    pub fn observe_queue_depth(&mut self, ticker: &TokenTicker, depth: usize) {
+        if let Some(throttle) = self.throttles.get_mut(ticker) {
+            throttle.queue_depth = depth;
+            self.update_stress(ticker);
+        }
+    }
+
+    fn update_stress(&mut self, ticker: &TokenTicker) {
+        let timestamp = self.now();
+        let throttle = self.throttles.get_mut(ticker).unwrap();
+        let stressed = throttle.match_nanos > throttle.policy.max_match_nanos
+            || throttle.queue_depth > throttle.policy.max_queue_depth;
+        if stressed == throttle.stressed {
+            return;
+        }
+        throttle.stressed = stressed;
+        self.stress_events.push(if stressed {
+            StressEvent::Stressed {
+                ticker: ticker.clone(),
+                match_nanos: throttle.match_nanos,
+                queue_depth: throttle.queue_depth,
+                timestamp,
+            }
+        } else {
+            StressEvent::Relaxed {
+                ticker: ticker.clone(),
+                timestamp,
+            }
+        });
+    }
+
+    // Admit one order from the wallet under the instrument's current limit
+    // and count it.
+    pub(crate) fn check_throttle(
+        &mut self,
+        wallet: &Wallet,
+        ticker: &TokenTicker,
+    ) -> Result<(), OrderError> {
+        self.check_throttle_headroom(wallet, ticker, 1)?;
+        self.record_throttle_entry(wallet, ticker);
+        Ok(())
+    }
+
+    // Whether the wallet may enter `orders` more orders on the instrument
+    // right now, without counting them.
+    pub(crate) fn check_throttle_headroom(
+        &self,
+        wallet: &Wallet,
+        ticker: &TokenTicker,
+        orders: usize,
+    ) -> Result<(), OrderError> {
+        let now = self.now();
+        let Some(throttle) = self.throttles.get(ticker) else {
+            return Ok(());
+        };
+        let limit = throttle.limit();
+        let window_millis = throttle.policy.window_millis;
+        let entered = throttle.entries.get(wallet).map_or(0, |entries| {
+            entries
+                .iter()
+                .filter(|entered| now.saturating_sub(**entered) < window_millis)
+                .count()
+        });
+        if entered + orders > limit as usize {
+            return Err(OrderError::Throttled {
+                limit,
+                window_millis,
+            });
+        }
+        Ok(())
+    }
+
+    // Count one order from the wallet against the instrument's window.
+    pub(crate) fn record_throttle_entry(&mut self, wallet: &Wallet, ticker: &TokenTicker) {
+        let now = self.now();
+        let Some(throttle) = self.throttles.get_mut(ticker) else {
+            return;
+        };
+        let window_millis = throttle.policy.window_millis;
+        let entries = throttle.entries.entry(wallet.clone()).or_default();
+        while entries
+            .front()
+            .is_some_and(|entered| now.saturating_sub(*entered) >= window_millis)
+        {
+            entries.pop_front();
+        }
+        entries.push_back(now);
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::clock::SimulatedClock;
+    use crate::corelib::command::{Command, CommandQueue};
+    use crate::corelib::order::{BuyOrSell, TimeInForce};
+
+    #[test]
+    fn test_stress_tightens_wallet_limits_until_it_clears() {
+        let clock = SimulatedClock::new(0);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        let wallet = Wallet::new(String::from("throttlewallet"));
+        engine.list_new_token(TokenTicker::ETH);
+        engine.set_throttle_policy(
+            TokenTicker::ETH,
+            Some(ThrottlePolicy {
+                window_millis: 1_000,
+                normal_limit: 3,
+                stressed_limit: 1,
+                max_match_nanos: 5_000_000,
+                max_queue_depth: 2,
+            }),
+        );
+        let submit = |engine: &mut TradeEngine| {
+            engine.submit_wallet_order(
+                &wallet,
+                &TokenTicker::ETH,
+                BuyOrSell::Buy,
+                10.0,
+                1,
+                TimeInForce::GoodTillCancel,
+            )
+        };
+        for _ in 0..3 {
+            submit(&mut engine).unwrap();
+        }
+        let throttled = Err(OrderError::Throttled {
+            limit: 3,
+            window_millis: 1_000,
+        });
+        assert_eq!(submit(&mut engine), throttled);
+        // anonymous flow is not per-wallet limited
+        assert!(engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Buy, 10.0, 1)
+            .is_ok());
+
+        // a backed-up queue stresses the instrument
+        clock.set(1_000);
+        let mut queue = CommandQueue::new(8);
+        for _ in 0..3 {
+            queue
+                .try_push(Command::SubmitOrder {
+                    ticker: TokenTicker::ETH,
+                    side: BuyOrSell::Sell,
+                    price: 11.0,
+                    quantity: 1,
+                    time_in_force: TimeInForce::GoodTillCancel,
+                    metadata: None,
+                })
+                .unwrap();
+        }
+        queue.process(&mut engine, 1);
+        assert!(engine.is_stressed(&TokenTicker::ETH));
+        assert_eq!(engine.current_order_limit(&TokenTicker::ETH), Some(1));
+        submit(&mut engine).unwrap();
+        assert_eq!(
+            submit(&mut engine),
+            Err(OrderError::Throttled {
+                limit: 1,
+                window_millis: 1_000,
+            })
+        );
+
+        // the queue drains, but a slow match pass keeps it stressed
+        engine.observe_match_latency(&TokenTicker::ETH, 9_000_000);
+        queue.process(&mut engine, usize::MAX);
+        queue.process(&mut engine, usize::MAX);
+        assert!(engine.is_stressed(&TokenTicker::ETH));
+        clock.set(1_500);
+        engine.observe_match_latency(&TokenTicker::ETH, 1_000_000);
+        assert!(!engine.is_stressed(&TokenTicker::ETH));
+        assert_eq!(
+            engine.stress_events,
+            vec![
+                StressEvent::Stressed {
+                    ticker: TokenTicker::ETH,
+                    match_nanos: 0,
+                    queue_depth: 3,
+                    timestamp: 1_000,
+                },
+                StressEvent::Relaxed {
+                    ticker: TokenTicker::ETH,
+                    timestamp: 1_500,
+                },
+            ]
+        );
+        assert_eq!(submit(&mut engine), Ok(9));
+    }
+}
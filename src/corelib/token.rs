@@ -1,10 +1,12 @@
-#[derive(Hash, PartialEq, Eq, Clone)]
+use std::fmt;
+
+#[derive(Hash, PartialEq, Eq, Clone, Debug)]
 pub enum Market {
     AfricaMarket(AfricaExchange),
     OtherMarket(CryptoExchange),
     USMarket(USExchange),
 }
-#[derive(Hash, PartialEq, Eq, Clone)]
+#[derive(Hash, PartialEq, Eq, Clone, Debug)]
 pub enum AfricaExchange {
     NajaEx,
     MorrockEx,
@@ -13,7 +15,7 @@ pub enum AfricaExchange {
     XMGCoin,
 }
 
-#[derive(Hash, PartialEq, Eq, Clone)]
+#[derive(Hash, PartialEq, Eq, Clone, Debug)]
 pub enum CryptoExchange {
     UpBit,
     KuCoin,
@@ -23,13 +25,13 @@ pub enum CryptoExchange {
     Binance,
 }
 
-#[derive(Hash, PartialEq, Eq, Clone)]
+#[derive(Hash, PartialEq, Eq, Clone, Debug)]
 pub enum USExchange {
     BinanceUS,
     Coinbase,
     Kraken,
 }
-#[derive(Hash, PartialEq, Eq, Clone)]
+#[derive(Hash, PartialEq, Eq, Clone, Debug)]
 pub enum Category {
     AI,
     Defi,
@@ -42,7 +44,7 @@ pub enum Category {
     Oracle,
 }
 
-#[derive(Hash, PartialEq, Eq, Clone)]
+#[derive(Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Debug)]
 pub enum TokenTicker {
     BTC,
     ETH,
@@ -64,9 +66,62 @@ pub enum TokenTicker {
     UNI,
     FIL,
     ROOT,
+    // LP token of a pair pool, once moved into the ledger.
+    Lp(Box<Pair>),
+    // Synthetic basket of listed tokens, by name.
+    Basket(String),
+}
+
+impl TokenTicker {
+    pub const ALL: [TokenTicker; 20] = [
+        TokenTicker::BTC,
+        TokenTicker::ETH,
+        TokenTicker::USDT,
+        TokenTicker::USDR,
+        TokenTicker::SOL,
+        TokenTicker::BNB,
+        TokenTicker::XRP,
+        TokenTicker::USDC,
+        TokenTicker::Doge,
+        TokenTicker::ADA,
+        TokenTicker::AVA,
+        TokenTicker::DOT,
+        TokenTicker::BCH,
+        TokenTicker::LINK,
+        TokenTicker::TRON,
+        TokenTicker::ICP,
+        TokenTicker::LTC,
+        TokenTicker::UNI,
+        TokenTicker::FIL,
+        TokenTicker::ROOT,
+    ];
+
+    pub fn lp(pair: Pair) -> TokenTicker {
+        TokenTicker::Lp(Box::new(pair))
+    }
+
+    // Listed ticker by name, in any case.
+    pub fn from_name(name: &str) -> Option<TokenTicker> {
+        TokenTicker::ALL
+            .iter()
+            .find(|ticker| format!("{:?}", ticker).eq_ignore_ascii_case(name))
+            .cloned()
+    }
+}
+
+// Listed tickers by their symbol, LP tokens as `LP(ETH/USDT)` and baskets
+// by name.
+impl fmt::Display for TokenTicker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenTicker::Lp(pair) => write!(f, "LP({}/{})", pair.ticker_a, pair.ticker_b),
+            TokenTicker::Basket(name) => write!(f, "{}", name),
+            ticker => write!(f, "{:?}", ticker),
+        }
+    }
 }
 
-#[derive(Hash, PartialEq, Eq, Clone)]
+#[derive(Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Debug)]
 pub struct Pair {
     pub ticker_a: TokenTicker,
     pub ticker_b: TokenTicker,
@@ -77,7 +132,7 @@ impl Pair {
         Pair { ticker_a, ticker_b }
     }
 }
-#[derive(Hash, PartialEq, Eq, Clone)]
+#[derive(Hash, PartialEq, Eq, Clone, Debug)]
 pub struct Token {
     pub ticker: TokenTicker,
     category: Category,
@@ -0,0 +1,182 @@
+use super::order::{OrderMetadata, Wallet};
+use super::token::TokenTicker;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TradeKind {
+    // Matched on the lit order book.
+    Lit,
+    // Negotiated away from the book and reported to the engine.
+    OffBook,
+    // Hidden orders crossed in a midpoint book.
+    Dark,
+    // Cleared in a batch auction at one price for the whole batch.
+    Auction,
+    // Leg of a spread execution, implied from or into the leg's book.
+    Implied,
+    // Crossed at the reference price in a scheduled crossing session.
+    Crossing,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trade {
+    pub id: u64,
+    pub ticker: TokenTicker,
+    pub price: f64,
+    pub quantity: u64,
+    pub buyer: Option<Wallet>,
+    pub seller: Option<Wallet>,
+    pub buy_order_id: Option<u64>,
+    pub sell_order_id: Option<u64>,
+    pub timestamp: u64,
+    pub kind: TradeKind,
+    // Metadata of the orders on each side, as the integrator attached it.
+    pub buy_metadata: Option<OrderMetadata>,
+    pub sell_metadata: Option<OrderMetadata>,
+    // Quote amount moved through the ledger, for trades that settled there.
+    pub settled_notional: Option<u64>,
+}
+
+// One line, e.g. `#7 ETH 5 @ 100.5 Lit, buy order 3 (alice), sell order
+// 4, at 1000`.
+impl fmt::Display for Trade {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#{} {} {} @ {} {:?}",
+            self.id, self.ticker, self.quantity, self.price, self.kind
+        )?;
+        for (side, order_id, wallet) in [
+            ("buy", self.buy_order_id, &self.buyer),
+            ("sell", self.sell_order_id, &self.seller),
+        ] {
+            write!(f, ", {}", side)?;
+            if let Some(order_id) = order_id {
+                write!(f, " order {}", order_id)?;
+            }
+            if let Some(wallet) = wallet {
+                write!(f, " ({})", wallet.address)?;
+            }
+        }
+        write!(f, ", at {}", self.timestamp)
+    }
+}
+
+// Append-only record of every trade executed by the engine.
+pub struct TradeFeed {
+    trades: Vec<Trade>,
+    next_trade_id: u64,
+}
+
+impl Default for TradeFeed {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TradeFeed {
+    pub fn new() -> TradeFeed {
+        TradeFeed {
+            trades: Vec::new(),
+            next_trade_id: 1,
+        }
+    }
+
+    // Assign the next trade id and append the trade.
+    pub fn publish(&mut self, mut trade: Trade) -> u64 {
+        trade.id = self.next_trade_id;
+        self.next_trade_id += 1;
+        self.trades.push(trade);
+        self.next_trade_id - 1
+    }
+
+    pub fn trades(&self) -> &[Trade] {
+        &self.trades
+    }
+
+    // Trades published after `trade_id`. Ids only grow, so this is a binary
+    // search rather than a scan of the history.
+    pub fn trades_after(&self, trade_id: u64) -> &[Trade] {
+        let start = self.trades.partition_point(|trade| trade.id <= trade_id);
+        &self.trades[start..]
+    }
+
+    pub fn trades_for<'a>(&'a self, ticker: &'a TokenTicker) -> impl Iterator<Item = &'a Trade> {
+        self.trades
+            .iter()
+            .filter(move |trade| &trade.ticker == ticker)
+    }
+
+    // Remove and return the trades `evict` selects, oldest first.
+    pub(crate) fn evict(&mut self, evict: impl Fn(&Trade) -> bool) -> Vec<Trade> {
+        let (evicted, kept) = std::mem::take(&mut self.trades)
+            .into_iter()
+            .partition(|trade| evict(trade));
+        self.trades = kept;
+        evicted
+    }
+
+    pub fn last_trade(&self, ticker: &TokenTicker) -> Option<&Trade> {
+        self.trades
+            .iter()
+            .rev()
+            .find(|trade| &trade.ticker == ticker)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_trade_prints_on_one_line() {
+        let trade = Trade {
+            id: 7,
+            ticker: TokenTicker::ETH,
+            price: 100.5,
+            quantity: 5,
+            buyer: Some(Wallet::new(String::from("alice"))),
+            seller: None,
+            buy_order_id: Some(3),
+            sell_order_id: Some(4),
+            timestamp: 1_000,
+            kind: TradeKind::Lit,
+            buy_metadata: None,
+            sell_metadata: None,
+            settled_notional: None,
+        };
+        assert_eq!(
+            trade.to_string(),
+            "#7 ETH 5 @ 100.5 Lit, buy order 3 (alice), sell order 4, at 1000"
+        );
+    }
+
+    #[test]
+    fn test_trades_after_skips_evicted_ids() {
+        let mut feed = TradeFeed::new();
+        for price in [10.0, 11.0, 12.0, 13.0] {
+            feed.publish(Trade {
+                id: 0,
+                ticker: TokenTicker::ETH,
+                price,
+                quantity: 1,
+                buyer: None,
+                seller: None,
+                buy_order_id: None,
+                sell_order_id: None,
+                timestamp: 0,
+                kind: TradeKind::Lit,
+                buy_metadata: None,
+                sell_metadata: None,
+                settled_notional: None,
+            });
+        }
+        feed.evict(|trade| trade.id == 3);
+        let ids = |trades: &[Trade]| trades.iter().map(|trade| trade.id).collect::<Vec<_>>();
+        assert_eq!(ids(feed.trades_after(0)), vec![1, 2, 4]);
+        assert_eq!(ids(feed.trades_after(2)), vec![4]);
+        assert_eq!(ids(feed.trades_after(3)), vec![4]);
+        assert!(feed.trades_after(4).is_empty());
+    }
+}
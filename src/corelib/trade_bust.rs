@@ -0,0 +1,318 @@
+use super::audit::AuditAction;
+use super::engine::TradeEngine;
+use super::ledger::LedgerError;
+use super::trade::Trade;
+
+// A trade cancelled as clearly erroneous. It no longer appears in the
+// trade feed, and the open interest, basket positions, rolling statistics
+// and published last trades that already counted it are rolled back.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TradeBust {
+    pub trade: Trade,
+    pub busted_at: u64,
+    // Whether the trade had settled through the ledger and was reversed.
+    pub reversed_settlement: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BustError {
+    // Never executed, or already busted.
+    UnknownTrade,
+    WindowExpired { executed_at: u64, deadline: u64 },
+    // The counterparties no longer hold what the reversal takes back.
+    Ledger(LedgerError),
+}
+
+impl From<LedgerError> for BustError {
+    fn from(err: LedgerError) -> Self {
+        BustError::Ledger(err)
+    }
+}
+
+impl TradeEngine {
+    // Bust a trade within `bust_window_millis` of its execution. Trades that
+    // settled through the ledger, i.e. block trades, have both legs moved
+    // back; book trades never moved balances. A busted fill of a basket
+    // child takes its units back out of the basket position. Returns the
+    // audit sequence number.
+    pub fn bust_trade(&mut self, trade_id: u64) -> Result<u64, BustError> {
+        let now = self.now();
+        let trade = self
+            .trade_feed
+            .trades()
+            .iter()
+            .find(|trade| trade.id == trade_id)
+            .ok_or(BustError::UnknownTrade)?;
+        let deadline = trade.timestamp.saturating_add(self.bust_window_millis);
+        if now > deadline {
+            return Err(BustError::WindowExpired {
+                executed_at: trade.timestamp,
+                deadline,
+            });
+        }
+
+        let reversed_settlement = match (&trade.settled_notional, &trade.buyer, &trade.seller) {
+            (Some(notional), Some(buyer), Some(seller)) => {
+                // the seller buys the base back for exactly what it was paid,
+                // whatever the rounding policy is now
+                self.ledger.settle(
+                    seller,
+                    buyer,
+                    &trade.ticker,
+                    trade.quantity,
+                    &self.quote_token,
+                    *notional,
+                )?;
+                true
+            }
+            _ => false,
+        };
+
+        let trade = self
+            .trade_feed
+            .evict(|trade| trade.id == trade_id)
+            .pop()
+            .unwrap();
+        self.unwind_open_interest(&trade);
+        self.unwind_basket_fill(&trade);
+        self.unwind_rolling_stats(&trade);
+        self.unpublish_trade(&trade);
+        let sequence = self.audit_log.record(
+            now,
+            AuditAction::TradeBusted {
+                trade_id,
+                ticker: trade.ticker.clone(),
+                price: trade.price,
+                quantity: trade.quantity,
+                reversed_settlement,
+            },
+        );
+        self.trade_busts.push(TradeBust {
+            trade,
+            busted_at: now,
+            reversed_settlement,
+        });
+        Ok(sequence)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::clock::SimulatedClock;
+    use crate::corelib::order::{BuyOrSell, Wallet};
+    use crate::corelib::token::TokenTicker;
+
+    #[test]
+    fn test_bust_reverses_settlement_within_window() {
+        let clock = SimulatedClock::new(0);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        let buyer = Wallet::new(String::from("bustbuyer"));
+        let seller = Wallet::new(String::from("bustseller"));
+        engine.list_new_token(TokenTicker::ETH);
+        engine.bust_window_millis = 60_000;
+        engine
+            .ledger
+            .deposit(buyer.clone(), TokenTicker::USDT, 10_000)
+            .unwrap();
+        engine
+            .ledger
+            .deposit(seller.clone(), TokenTicker::ETH, 10)
+            .unwrap();
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Buy, 99.0, 1)
+            .unwrap();
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Sell, 101.0, 1)
+            .unwrap();
+
+        let block = engine
+            .report_block_trade(TokenTicker::ETH, buyer.clone(), seller.clone(), 100.0, 4)
+            .unwrap();
+        assert_eq!(engine.ledger.free_balance(&buyer, &TokenTicker::ETH), 4);
+
+        clock.set(60_000);
+        let sequence = engine.bust_trade(block).unwrap();
+        assert_eq!(
+            engine.ledger.free_balance(&buyer, &TokenTicker::USDT),
+            10_000
+        );
+        assert_eq!(engine.ledger.free_balance(&seller, &TokenTicker::ETH), 10);
+        assert_eq!(engine.trade_feed.last_trade(&TokenTicker::ETH), None);
+        assert_eq!(engine.trade_busts[0].trade.id, block);
+        assert!(engine.trade_busts[0].reversed_settlement);
+        assert_eq!(
+            engine.audit_log.records()[sequence as usize - 1].action,
+            AuditAction::TradeBusted {
+                trade_id: block,
+                ticker: TokenTicker::ETH,
+                price: 100.0,
+                quantity: 4,
+                reversed_settlement: true,
+            }
+        );
+        assert_eq!(engine.bust_trade(block), Err(BustError::UnknownTrade));
+
+        let late = engine
+            .report_block_trade(TokenTicker::ETH, buyer, seller, 100.0, 1)
+            .unwrap();
+        clock.advance(60_001);
+        assert_eq!(
+            engine.bust_trade(late),
+            Err(BustError::WindowExpired {
+                executed_at: 60_000,
+                deadline: 120_000,
+            })
+        );
+    }
+
+    #[test]
+    fn test_bust_reverses_the_settled_notional() {
+        use crate::corelib::rounding::Rounding;
+
+        let mut engine = TradeEngine::new();
+        let buyer = Wallet::new(String::from("bustroundbuyer"));
+        let seller = Wallet::new(String::from("bustroundseller"));
+        engine.list_new_token(TokenTicker::ETH);
+        engine.bust_window_millis = u64::MAX;
+        engine
+            .ledger
+            .deposit(buyer.clone(), TokenTicker::USDT, 1_000)
+            .unwrap();
+        engine
+            .ledger
+            .deposit(seller.clone(), TokenTicker::ETH, 2)
+            .unwrap();
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Buy, 99.0, 1)
+            .unwrap();
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Sell, 101.0, 1)
+            .unwrap();
+
+        // 200.5 settles as 201 to the nearest unit
+        let block = engine
+            .report_block_trade(TokenTicker::ETH, buyer.clone(), seller.clone(), 100.25, 2)
+            .unwrap();
+        assert_eq!(engine.ledger.free_balance(&seller, &TokenTicker::USDT), 201);
+
+        // truncating would now give 200; the bust still moves 201 back
+        engine.set_rounding(Rounding::Truncate);
+        engine.bust_trade(block).unwrap();
+        assert_eq!(
+            engine.ledger.free_balance(&buyer, &TokenTicker::USDT),
+            1_000
+        );
+        assert_eq!(engine.ledger.free_balance(&seller, &TokenTicker::USDT), 0);
+        assert_eq!(engine.ledger.free_balance(&seller, &TokenTicker::ETH), 2);
+    }
+
+    #[test]
+    fn test_bust_rolls_back_open_interest_and_statistics() {
+        use crate::corelib::funding::FundingConfig;
+        use crate::corelib::order::TimeInForce;
+
+        let clock = SimulatedClock::new(0);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        engine.list_perpetual(TokenTicker::BTC, FundingConfig::default());
+        engine.track_rolling_stats(TokenTicker::BTC, 60_000);
+        let handle = engine.market_data_handle();
+        let wallets: Vec<Wallet> = ["bust-a", "bust-b", "bust-c"]
+            .into_iter()
+            .map(|name| Wallet::new(String::from(name)))
+            .collect();
+        let trade = |engine: &mut TradeEngine, seller: usize, price| {
+            for (wallet, side) in [(0, BuyOrSell::Buy), (seller, BuyOrSell::Sell)] {
+                engine
+                    .submit_wallet_order(
+                        &wallets[wallet],
+                        &TokenTicker::BTC,
+                        side,
+                        price,
+                        1,
+                        TimeInForce::GoodTillCancel,
+                    )
+                    .unwrap();
+            }
+            engine.match_orders();
+            clock.advance(1_000);
+            engine.trade_feed.trades().last().unwrap().id
+        };
+        trade(&mut engine, 1, 100.0);
+        let erroneous = trade(&mut engine, 2, 110.0);
+        let last = trade(&mut engine, 1, 105.0);
+        assert_eq!(
+            engine.open_interest(&TokenTicker::BTC).unwrap().contracts,
+            3
+        );
+
+        engine.bust_trade(erroneous).unwrap();
+        let open_interest = engine.open_interest(&TokenTicker::BTC).unwrap();
+        assert_eq!(open_interest.contracts, 2);
+        assert_eq!(open_interest.long_short_ratio(), Some(1.0));
+        let stats = engine.rolling_stats(&TokenTicker::BTC, 60_000).unwrap();
+        assert_eq!((stats.trade_count, stats.volume), (2, 2));
+        assert_eq!((stats.high, stats.low), (Some(105.0), Some(100.0)));
+        assert!((stats.realized_volatility - 1.05_f64.ln()).abs() < 1e-12);
+        assert_eq!(handle.last_trade(&TokenTicker::BTC).unwrap().id, last);
+
+        engine.bust_trade(last).unwrap();
+        let stats = engine.rolling_stats(&TokenTicker::BTC, 60_000).unwrap();
+        assert_eq!((stats.trade_count, stats.high), (1, Some(100.0)));
+        assert_eq!(stats.realized_volatility, 0.0);
+        assert_eq!(handle.last_trade(&TokenTicker::BTC).unwrap().price, 100.0);
+        assert_eq!(
+            engine.open_interest(&TokenTicker::BTC).unwrap().contracts,
+            1
+        );
+
+        // the next trade returns against the surviving price
+        trade(&mut engine, 1, 100.0);
+        let stats = engine.rolling_stats(&TokenTicker::BTC, 60_000).unwrap();
+        assert_eq!((stats.trade_count, stats.realized_volatility), (2, 0.0));
+    }
+
+    #[test]
+    fn test_bust_of_a_basket_child_fill_reduces_the_position() {
+        let mut engine = TradeEngine::new();
+        let wallet = Wallet::new(String::from("bustbasket"));
+        engine.list_new_token(TokenTicker::ETH);
+        engine.list_new_token(TokenTicker::BTC);
+        engine.bust_window_millis = 60_000;
+        let basket = engine
+            .define_basket("DUO", vec![(TokenTicker::ETH, 2), (TokenTicker::BTC, 1)])
+            .unwrap();
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Sell, 10.0, 4)
+            .unwrap();
+        engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Sell, 10.0, 100)
+            .unwrap();
+        engine
+            .submit_order(&TokenTicker::BTC, BuyOrSell::Sell, 100.0, 100)
+            .unwrap();
+        engine
+            .submit_basket_order(&wallet, &basket, BuyOrSell::Buy, 3)
+            .unwrap();
+        engine.match_orders();
+        let trade_id = |ticker: TokenTicker, quantity: u64| {
+            engine
+                .trade_feed
+                .trades()
+                .iter()
+                .find(|trade| trade.ticker == ticker && trade.quantity == quantity)
+                .unwrap()
+                .id
+        };
+        let (eth_fill, btc_fill) = (trade_id(TokenTicker::ETH, 2), trade_id(TokenTicker::BTC, 3));
+
+        // 2 ETH is one basket unit's worth
+        engine.bust_trade(eth_fill).unwrap();
+        let position = engine.basket_position(&wallet, &basket).unwrap();
+        assert_eq!(position.quantity, 2);
+        // the BTC fill covered all three units, one of them already gone
+        engine.bust_trade(btc_fill).unwrap();
+        assert!(engine.basket_position(&wallet, &basket).is_none());
+    }
+}
@@ -0,0 +1,76 @@
+use super::audit::AuditAction;
+use super::engine::TradeEngine;
+use super::ledger::LedgerError;
+use super::order::Wallet;
+use super::token::TokenTicker;
+
+impl TradeEngine {
+    // Fee-free transfer between wallets, recorded in the audit log with the
+    // caller's memo. Returns the audit sequence number.
+    pub fn transfer(
+        &mut self,
+        from: &Wallet,
+        to: &Wallet,
+        token: &TokenTicker,
+        amount: u64,
+        memo: Option<&str>,
+    ) -> Result<u64, LedgerError> {
+        self.ledger.transfer(from, to, token, amount)?;
+        let now = self.now();
+        Ok(self.audit_log.record(
+            now,
+            AuditAction::InternalTransfer {
+                from: from.clone(),
+                to: to.clone(),
+                token: token.clone(),
+                amount,
+                memo: memo.map(String::from),
+            },
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_transfer_moves_free_funds_and_audits() {
+        let mut engine = TradeEngine::new();
+        let alice = Wallet::new(String::from("transferalice"));
+        let bob = Wallet::new(String::from("transferbob"));
+        engine
+            .ledger
+            .deposit(alice.clone(), TokenTicker::USDT, 100)
+            .unwrap();
+        engine.ledger.lock(&alice, &TokenTicker::USDT, 30).unwrap();
+
+        let sequence = engine
+            .transfer(&alice, &bob, &TokenTicker::USDT, 70, Some("payout"))
+            .unwrap();
+        assert_eq!(engine.ledger.free_balance(&alice, &TokenTicker::USDT), 0);
+        assert_eq!(engine.ledger.free_balance(&bob, &TokenTicker::USDT), 70);
+        assert_eq!(
+            engine.audit_log.records()[sequence as usize - 1].action,
+            AuditAction::InternalTransfer {
+                from: alice.clone(),
+                to: bob.clone(),
+                token: TokenTicker::USDT,
+                amount: 70,
+                memo: Some(String::from("payout")),
+            }
+        );
+
+        // locked funds stay put
+        assert_eq!(
+            engine.transfer(&alice, &bob, &TokenTicker::USDT, 1, None),
+            Err(LedgerError::InsufficientFree {
+                token: TokenTicker::USDT,
+                available: 0,
+                requested: 1,
+            })
+        );
+        assert_eq!(engine.audit_log.records().len(), 1);
+    }
+}
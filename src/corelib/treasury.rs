@@ -0,0 +1,260 @@
+use super::amm::AMMPool;
+use super::audit::AuditAction;
+use super::engine::{DeadlineExpired, TradeEngine};
+use super::fees::FeeRouting;
+use super::ledger::LedgerError;
+use super::token::Pair;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TreasuryError {
+    InvalidShare,
+    ZeroAmount,
+    UnknownPool,
+    InsufficientLp { held: u64, requested: u64 },
+    // The deposit would mint no LP tokens.
+    BelowMinimumLiquidity,
+    // The pool's reserves or LP supply would overflow.
+    PoolOverflow,
+    DeadlineExpired(DeadlineExpired),
+    Ledger(LedgerError),
+}
+
+impl From<LedgerError> for TreasuryError {
+    fn from(err: LedgerError) -> Self {
+        TreasuryError::Ledger(err)
+    }
+}
+
+impl From<DeadlineExpired> for TreasuryError {
+    fn from(err: DeadlineExpired) -> Self {
+        TreasuryError::DeadlineExpired(err)
+    }
+}
+
+// Protocol-owned liquidity: the ledger treasury provides liquidity to pair
+// pools like any other LP, and every change is written to the audit log.
+impl TradeEngine {
+    pub fn set_fee_routing(&mut self, routing: FeeRouting) -> Result<(), TreasuryError> {
+        let from = self.fees.routing();
+        self.fees
+            .set_routing(routing)
+            .map_err(|_| TreasuryError::InvalidShare)?;
+        let now = self.now();
+        self.audit_log
+            .record(now, AuditAction::FeeRoutingChanged { from, to: routing });
+        Ok(())
+    }
+
+    // Move treasury funds into the `pair` pool, creating it if needed.
+    // Returns the LP tokens minted to the treasury.
+    pub fn seed_liquidity(
+        &mut self,
+        pair: Pair,
+        amount_a: u64,
+        amount_b: u64,
+        deadline: u64,
+    ) -> Result<u64, TreasuryError> {
+        self.check_deadline(deadline)?;
+        if amount_a == 0 || amount_b == 0 {
+            return Err(TreasuryError::ZeroAmount);
+        }
+        let minted = match self.amm_pools.get(&pair) {
+            Some(pool) => pool.lp_for_deposit(&pair, amount_a, amount_b),
+            None => AMMPool::new().lp_for_deposit(&pair, amount_a, amount_b),
+        }
+        .ok_or(TreasuryError::PoolOverflow)?;
+        if minted == 0 {
+            return Err(TreasuryError::BelowMinimumLiquidity);
+        }
+        let treasury = self.ledger.treasury().clone();
+        let available = self.ledger.free_balance(&treasury, &pair.ticker_b);
+        if available < amount_b {
+            return Err(LedgerError::InsufficientFree {
+                token: pair.ticker_b.clone(),
+                available,
+                requested: amount_b,
+            }
+            .into());
+        }
+        self.ledger
+            .debit_free(&treasury, &pair.ticker_a, amount_a)?;
+        self.ledger
+            .debit_free(&treasury, &pair.ticker_b, amount_b)?;
+
+        let now = self.now();
+        let lp_minted = self
+            .amm_pools
+            .entry(pair.clone())
+            .or_default()
+            .deposit(&treasury, &pair, amount_a, amount_b, deadline, now);
+        self.audit_log.record(
+            now,
+            AuditAction::TreasuryLiquiditySeeded {
+                pair,
+                amount_a,
+                amount_b,
+                lp_minted,
+            },
+        );
+        Ok(lp_minted)
+    }
+
+    // Burn treasury LP tokens of `pair` and credit the released reserves back
+    // to the treasury.
+    pub fn withdraw_liquidity(
+        &mut self,
+        pair: Pair,
+        lp_tokens: u64,
+        deadline: u64,
+    ) -> Result<(u64, u64), TreasuryError> {
+        self.check_deadline(deadline)?;
+        if lp_tokens == 0 {
+            return Err(TreasuryError::ZeroAmount);
+        }
+        let treasury = self.ledger.treasury().clone();
+        let now = self.now();
+        let pool = self
+            .amm_pools
+            .get_mut(&pair)
+            .ok_or(TreasuryError::UnknownPool)?;
+        let (amount_a, amount_b) = pool
+            .withdraw(&treasury, &pair, lp_tokens, deadline, now)
+            .ok_or(TreasuryError::InsufficientLp {
+                held: pool.lp_balance(&treasury, &pair),
+                requested: lp_tokens,
+            })?;
+
+        self.ledger.credit(&treasury, &pair.ticker_a, amount_a);
+        self.ledger.credit(&treasury, &pair.ticker_b, amount_b);
+        self.audit_log.record(
+            now,
+            AuditAction::TreasuryLiquidityWithdrawn {
+                pair,
+                lp_burned: lp_tokens,
+                amount_a,
+                amount_b,
+            },
+        );
+        Ok((amount_a, amount_b))
+    }
+
+    pub fn treasury_lp_balance(&self, pair: &Pair) -> u64 {
+        self.amm_pools
+            .get(pair)
+            .map(|pool| pool.lp_balance(self.ledger.treasury(), pair))
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::clock::SimulatedClock;
+    use crate::corelib::order::Wallet;
+    use crate::corelib::token::TokenTicker;
+
+    #[test]
+    fn test_seed_and_withdraw_protocol_liquidity() {
+        let clock = SimulatedClock::new(1_000);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        let treasury = engine.ledger.treasury().clone();
+        let pair = Pair::new(TokenTicker::ETH, TokenTicker::USDT);
+        engine
+            .ledger
+            .deposit(treasury.clone(), TokenTicker::ETH, 10)
+            .unwrap();
+        engine
+            .ledger
+            .deposit(treasury.clone(), TokenTicker::USDT, 30_000)
+            .unwrap();
+
+        // both legs are checked before anything moves
+        assert!(matches!(
+            engine.seed_liquidity(pair.clone(), 10, 40_000, 1_000),
+            Err(TreasuryError::Ledger(_))
+        ));
+        assert_eq!(engine.ledger.treasury_balance(&TokenTicker::ETH), 10);
+
+        let minted = engine
+            .seed_liquidity(pair.clone(), 10, 30_000, 1_000)
+            .unwrap();
+        // the first deposit locks the minimum liquidity
+        assert_eq!(minted, 29_010);
+        assert_eq!(engine.treasury_lp_balance(&pair), 29_010);
+        assert_eq!(engine.ledger.treasury_balance(&TokenTicker::USDT), 0);
+
+        clock.advance(500);
+        assert_eq!(
+            engine.withdraw_liquidity(pair.clone(), 15_005, 1_000),
+            Err(TreasuryError::DeadlineExpired(DeadlineExpired {
+                deadline: 1_000,
+                now: 1_500
+            }))
+        );
+        assert_eq!(
+            engine.withdraw_liquidity(pair.clone(), 40_000, 2_000),
+            Err(TreasuryError::InsufficientLp {
+                held: 29_010,
+                requested: 40_000
+            })
+        );
+        let (eth, usdt) = engine
+            .withdraw_liquidity(pair.clone(), 15_005, 2_000)
+            .unwrap();
+        assert_eq!((eth, usdt), (5, 15_000));
+        assert_eq!(engine.ledger.treasury_balance(&TokenTicker::USDT), 15_000);
+
+        let records = engine.audit_log.records();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].timestamp, 1_000);
+        assert_eq!(
+            records[1].action,
+            AuditAction::TreasuryLiquidityWithdrawn {
+                pair,
+                lp_burned: 15_005,
+                amount_a: 5,
+                amount_b: 15_000,
+            }
+        );
+    }
+
+    #[test]
+    fn test_fee_routing_credits_treasury() {
+        let mut engine = TradeEngine::new();
+        let taker = Wallet::new(String::from("routingtaker"));
+        engine
+            .ledger
+            .deposit(taker.clone(), TokenTicker::USDT, 100_000)
+            .unwrap();
+
+        assert_eq!(
+            engine.set_fee_routing(FeeRouting {
+                trading_treasury_bps: 10_001,
+                swap_treasury_bps: 0,
+            }),
+            Err(TreasuryError::InvalidShare)
+        );
+        let routing = FeeRouting {
+            trading_treasury_bps: 4_000,
+            swap_treasury_bps: 10_000,
+        };
+        engine.set_fee_routing(routing).unwrap();
+
+        let fee = engine
+            .fees
+            .charge_taker_fee(&mut engine.ledger, &taker, &TokenTicker::USDT, 50_000)
+            .unwrap();
+        assert_eq!(fee, 100);
+        assert_eq!(engine.ledger.treasury_balance(&TokenTicker::USDT), 40);
+        let fee_account = engine.fees.fee_account.clone();
+        assert_eq!(
+            engine.ledger.free_balance(&fee_account, &TokenTicker::USDT),
+            60
+        );
+        assert!(matches!(
+            engine.audit_log.records()[0].action,
+            AuditAction::FeeRoutingChanged { to, .. } if to == routing
+        ));
+    }
+}
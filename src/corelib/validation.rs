@@ -0,0 +1,121 @@
+use super::engine::OrderError;
+
+// Largest price magnitude order entry accepts. Anything bigger is a typo
+// or garbage, and its notional no longer fits the ledger's u64 amounts.
+pub const MAX_PRICE: f64 = 1e12;
+
+// Checks every order passes before it reaches a book. NaN prices break
+// the ordering of price levels, and a zero quantity order would rest
+// forever without being able to fill.
+pub fn validate_order(price: f64, quantity: u32) -> Result<(), OrderError> {
+    validate_price(price)?;
+    if quantity == 0 {
+        return Err(OrderError::ZeroQuantity);
+    }
+    Ok(())
+}
+
+pub fn validate_price(price: f64) -> Result<(), OrderError> {
+    if price.is_nan() {
+        return Err(OrderError::NanPrice);
+    }
+    if price.is_infinite() {
+        return Err(OrderError::InfinitePrice);
+    }
+    if price.is_subnormal() {
+        return Err(OrderError::SubnormalPrice);
+    }
+    if price.abs() > MAX_PRICE {
+        return Err(OrderError::PriceTooLarge { price });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::engine::TradeEngine;
+    use crate::corelib::order::BuyOrSell;
+    use crate::corelib::rng::SeededRng;
+    use crate::corelib::token::TokenTicker;
+
+    #[test]
+    fn test_invalid_prices_and_quantities_are_rejected() {
+        let mut engine = TradeEngine::new();
+        engine.list_new_token(TokenTicker::ETH);
+        for (price, quantity, err) in [
+            (f64::NAN, 1, OrderError::NanPrice),
+            (f64::INFINITY, 1, OrderError::InfinitePrice),
+            (f64::NEG_INFINITY, 1, OrderError::InfinitePrice),
+            (f64::MIN_POSITIVE / 2.0, 1, OrderError::SubnormalPrice),
+            (1e13, 1, OrderError::PriceTooLarge { price: 1e13 }),
+            (-1e13, 1, OrderError::PriceTooLarge { price: -1e13 }),
+            (100.0, 0, OrderError::ZeroQuantity),
+        ] {
+            assert_eq!(
+                engine.submit_order(&TokenTicker::ETH, BuyOrSell::Buy, price, quantity),
+                Err(err)
+            );
+        }
+        assert_eq!(engine.book_sequence(&TokenTicker::ETH), Some(0));
+        assert!(engine
+            .submit_order(&TokenTicker::ETH, BuyOrSell::Buy, MAX_PRICE, 1)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_fuzzed_orders_keep_the_book_sane() {
+        let specials = [
+            f64::NAN,
+            -f64::NAN,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            0.0,
+            -0.0,
+            f64::MIN_POSITIVE,
+            f64::MIN_POSITIVE / 4.0,
+            f64::MAX,
+            f64::MIN,
+            f64::EPSILON,
+            MAX_PRICE,
+        ];
+        let mut rng = SeededRng::new(971);
+        let mut engine = TradeEngine::new();
+        engine.list_new_token(TokenTicker::ETH);
+        for step in 0..5_000 {
+            let price = match rng.between(0, 3) {
+                0 => specials[rng.between(0, specials.len() as u64 - 1) as usize],
+                1 => f64::from_bits(rng.next_u64()),
+                _ => rng.between(90, 110) as f64 + rng.between(0, 3) as f64 / 4.0,
+            };
+            let quantity = match rng.between(0, 4) {
+                0 => 0,
+                1 => u32::MAX,
+                _ => rng.between(1, 50) as u32,
+            };
+            let side = if rng.between(0, 1) == 0 {
+                BuyOrSell::Buy
+            } else {
+                BuyOrSell::Sell
+            };
+            let result = engine.submit_order(&TokenTicker::ETH, side, price, quantity);
+            assert_eq!(result.is_ok(), validate_order(price, quantity).is_ok());
+            if step % 7 == 0 {
+                engine.match_orders();
+            }
+        }
+        engine.match_orders();
+
+        let book = &engine.order_books[&TokenTicker::ETH];
+        for (price, orders) in book
+            .levels(BuyOrSell::Buy)
+            .into_iter()
+            .chain(book.levels(BuyOrSell::Sell))
+        {
+            assert!(validate_price(price).is_ok());
+            assert!(orders.iter().all(|order| order.quantity > 0));
+        }
+        assert!(!book.is_crossed());
+    }
+}
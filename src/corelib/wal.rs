@@ -0,0 +1,1066 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use super::admin::AdminCommand;
+use super::clock::{Clock, SimulatedClock};
+use super::command::{Command, CommandResult};
+use super::engine::TradeEngine;
+use super::order::{BuyOrSell, Order, OrderMetadata, TimeInForce, Wallet};
+use super::orderbook::OrderBook;
+use super::schema::{CommandV1, CommandV2, VersionedRecord, WalRecordV1, WalRecordV2, WAL_VERSION};
+use super::token::{Pair, TokenTicker};
+
+const SEGMENT_EXTENSION: &str = "wal";
+const SNAPSHOT_EXTENSION: &str = "snap";
+// Segments open with the magic and the record model version. Segments
+// without it predate versioning and hold version 1 records.
+const SEGMENT_MAGIC: &[u8; 4] = b"TWAL";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WalError {
+    Io(String),
+    // A record or snapshot that cannot be decoded.
+    Corrupt { file: PathBuf },
+    // Written by a newer build than this one.
+    UnsupportedVersion { file: PathBuf, version: u16 },
+}
+
+impl From<std::io::Error> for WalError {
+    fn from(err: std::io::Error) -> Self {
+        WalError::Io(err.to_string())
+    }
+}
+
+// A segment is closed and a new one started once it holds
+// `max_segment_bytes`, or once `max_segment_millis` passed since its first
+// record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WalConfig {
+    pub max_segment_bytes: u64,
+    pub max_segment_millis: u64,
+}
+
+impl Default for WalConfig {
+    fn default() -> Self {
+        WalConfig {
+            max_segment_bytes: 64 * 1024 * 1024,
+            max_segment_millis: 60 * 60 * 1_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WalRecord {
+    pub sequence: u64,
+    pub timestamp: u64,
+    pub command: Command,
+}
+
+struct ActiveSegment {
+    file: File,
+    bytes: u64,
+    first_timestamp: Option<u64>,
+}
+
+// Write-ahead log of engine commands in a directory of binary segments
+// named after their first sequence number. `compact` folds the closed
+// segments into a snapshot of the order books, so recovery only replays
+// the segments written since.
+pub struct Wal {
+    directory: PathBuf,
+    config: WalConfig,
+    next_sequence: u64,
+    active: Option<ActiveSegment>,
+}
+
+impl Wal {
+    // Open the log in `directory`, creating it if needed. Appends go to a
+    // new segment.
+    pub fn open(directory: impl AsRef<Path>, config: WalConfig) -> Result<Wal, WalError> {
+        let directory = directory.as_ref().to_path_buf();
+        fs::create_dir_all(&directory)?;
+        let mut next_sequence =
+            latest_snapshot(&directory)?.map_or(0, |(sequence, _)| sequence) + 1;
+        for (_, path) in files(&directory, SEGMENT_EXTENSION)? {
+            if let Some(last) = read_segment(&path)?.last() {
+                next_sequence = next_sequence.max(last.sequence + 1);
+            }
+        }
+        Ok(Wal {
+            directory,
+            config,
+            next_sequence,
+            active: None,
+        })
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    // Append a command and return its sequence number.
+    pub fn append(&mut self, timestamp: u64, command: &Command) -> Result<u64, WalError> {
+        let due = self.active.as_ref().is_some_and(|active| {
+            active.bytes >= self.config.max_segment_bytes
+                || active.first_timestamp.is_some_and(|first| {
+                    timestamp.saturating_sub(first) >= self.config.max_segment_millis
+                })
+        });
+        if due {
+            self.rotate();
+        }
+        let sequence = self.next_sequence;
+        let mut payload = Vec::new();
+        encode_record(
+            &mut payload,
+            &WalRecord {
+                sequence,
+                timestamp,
+                command: command.clone(),
+            },
+        );
+        let mut bytes = (payload.len() as u32).to_le_bytes().to_vec();
+        bytes.extend_from_slice(&payload);
+
+        if self.active.is_none() {
+            let path = self.directory.join(file_name(sequence, SEGMENT_EXTENSION));
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            let mut header = SEGMENT_MAGIC.to_vec();
+            header.extend_from_slice(&WAL_VERSION.to_le_bytes());
+            file.write_all(&header)?;
+            self.active = Some(ActiveSegment {
+                file,
+                bytes: header.len() as u64,
+                first_timestamp: None,
+            });
+        }
+        let active = self.active.as_mut().unwrap();
+        active.file.write_all(&bytes)?;
+        active.file.flush()?;
+        active.bytes += bytes.len() as u64;
+        active.first_timestamp.get_or_insert(timestamp);
+        self.next_sequence += 1;
+        Ok(sequence)
+    }
+
+    // Close the active segment; the next append starts a new one.
+    pub fn rotate(&mut self) {
+        self.active = None;
+    }
+
+    // Fold every closed segment into a new snapshot and delete them along
+    // with older snapshots. The active segment is closed first. Returns the
+    // sequence the snapshot covers.
+    pub fn compact(&mut self) -> Result<u64, WalError> {
+        self.rotate();
+        let (engine, sequence) = replay(&self.directory)?;
+        let snapshot = self.directory.join(file_name(sequence, SNAPSHOT_EXTENSION));
+        let partial = snapshot.with_extension("tmp");
+        fs::write(&partial, encode_snapshot(sequence, &engine))?;
+        fs::rename(&partial, &snapshot)?;
+        for (_, path) in files(&self.directory, SEGMENT_EXTENSION)? {
+            fs::remove_file(path)?;
+        }
+        for (covered, path) in files(&self.directory, SNAPSHOT_EXTENSION)? {
+            if covered < sequence {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(sequence)
+    }
+}
+
+impl TradeEngine {
+    // Log the command, then apply it.
+    pub fn execute_logged(
+        &mut self,
+        wal: &mut Wal,
+        command: Command,
+    ) -> Result<CommandResult, WalError> {
+        wal.append(self.now(), &command)?;
+        if command == Command::Admin(AdminCommand::RotateLog) {
+            wal.rotate();
+        }
+        Ok(self.execute(command))
+    }
+
+    // Rebuild an engine from the latest snapshot and the segments written
+    // after it. Commands are replayed at their logged time; the engine then
+    // runs on `clock`. Book events and trades before the snapshot are not
+    // restored.
+    pub fn recover(
+        directory: impl AsRef<Path>,
+        clock: Box<dyn Clock>,
+    ) -> Result<TradeEngine, WalError> {
+        let (mut engine, _) = replay(directory.as_ref())?;
+        engine.set_clock(clock);
+        Ok(engine)
+    }
+
+    // Write the books and configuration to a standalone snapshot file.
+    pub fn save_snapshot(&self, path: impl AsRef<Path>) -> Result<(), WalError> {
+        let path = path.as_ref();
+        let partial = path.with_extension("tmp");
+        fs::write(&partial, encode_snapshot(0, self))?;
+        fs::rename(&partial, path)?;
+        Ok(())
+    }
+
+    // Engine holding the books and configuration of a snapshot file.
+    pub fn load_snapshot(
+        path: impl AsRef<Path>,
+        clock: Box<dyn Clock>,
+    ) -> Result<TradeEngine, WalError> {
+        let path = path.as_ref();
+        let mut engine = TradeEngine::with_clock(clock);
+        restore_snapshot(&mut engine, &fs::read(path)?).ok_or(WalError::Corrupt {
+            file: path.to_path_buf(),
+        })?;
+        Ok(engine)
+    }
+}
+
+// Records of a single segment file, upgraded to the current model.
+pub fn read_log(path: impl AsRef<Path>) -> Result<Vec<WalRecord>, WalError> {
+    read_segment(path.as_ref())
+}
+
+// Engine state as of the last logged record, with that record's sequence.
+fn replay(directory: &Path) -> Result<(TradeEngine, u64), WalError> {
+    let clock = SimulatedClock::new(0);
+    let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+    let mut sequence = 0;
+    if let Some((covered, path)) = latest_snapshot(directory)? {
+        let bytes = fs::read(&path)?;
+        restore_snapshot(&mut engine, &bytes).ok_or(WalError::Corrupt { file: path })?;
+        sequence = covered;
+    }
+    for (_, path) in files(directory, SEGMENT_EXTENSION)? {
+        for record in read_segment(&path)? {
+            if record.sequence <= sequence {
+                continue;
+            }
+            clock.set(record.timestamp);
+            engine.execute(record.command);
+            sequence = record.sequence;
+        }
+    }
+    Ok((engine, sequence))
+}
+
+fn file_name(sequence: u64, extension: &str) -> String {
+    format!("{:020}.{}", sequence, extension)
+}
+
+// Files with the extension, by the sequence in their name.
+fn files(directory: &Path, extension: &str) -> Result<Vec<(u64, PathBuf)>, WalError> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some(extension) {
+            continue;
+        }
+        if let Some(sequence) = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.parse().ok())
+        {
+            files.push((sequence, path));
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn latest_snapshot(directory: &Path) -> Result<Option<(u64, PathBuf)>, WalError> {
+    Ok(files(directory, SNAPSHOT_EXTENSION)?.pop())
+}
+
+// Records of one segment, upgraded to the current model. A torn record at
+// the end, left by a crash during the write, is ignored.
+fn read_segment(path: &Path) -> Result<Vec<WalRecord>, WalError> {
+    let bytes = fs::read(path)?;
+    let mut reader = Reader::new(&bytes);
+    let mut version = 1;
+    if bytes.starts_with(SEGMENT_MAGIC) {
+        reader.take(SEGMENT_MAGIC.len());
+        version = reader.u16().ok_or_else(|| WalError::Corrupt {
+            file: path.to_path_buf(),
+        })?;
+    }
+    let Some(decode) = decoder(version) else {
+        return Err(WalError::UnsupportedVersion {
+            file: path.to_path_buf(),
+            version,
+        });
+    };
+    let mut records = Vec::new();
+    while let Some(length) = reader.u32() {
+        let Some(payload) = reader.take(length as usize) else {
+            break;
+        };
+        let record = decode(payload).ok_or_else(|| WalError::Corrupt {
+            file: path.to_path_buf(),
+        })?;
+        records.push(record.upgrade());
+    }
+    Ok(records)
+}
+
+type RecordDecoder = fn(&[u8]) -> Option<VersionedRecord>;
+
+// Record decoders by the version that wrote the segment.
+fn decoder(version: u16) -> Option<RecordDecoder> {
+    match version {
+        1 => Some(|bytes| decode_record_v1(bytes).map(VersionedRecord::V1)),
+        2 => Some(|bytes| decode_record_v2(bytes).map(VersionedRecord::V2)),
+        3 => Some(|bytes| decode_record(bytes).map(VersionedRecord::V3)),
+        _ => None,
+    }
+}
+
+// Listed tickers by position in `TokenTicker::ALL`; LP tokens as a marker
+// followed by both tickers of the pair; baskets as a marker followed by the
+// length-prefixed name.
+const LP_TICKER: u8 = u8::MAX;
+const BASKET_TICKER: u8 = u8::MAX - 1;
+
+fn encode_optional_u64(buffer: &mut Vec<u8>, value: Option<u64>) {
+    match value {
+        Some(value) => {
+            buffer.push(1);
+            buffer.extend_from_slice(&value.to_le_bytes());
+        }
+        None => buffer.push(0),
+    }
+}
+
+pub(crate) fn encode_ticker(buffer: &mut Vec<u8>, ticker: &TokenTicker) {
+    match ticker {
+        TokenTicker::Lp(pair) => {
+            buffer.push(LP_TICKER);
+            encode_ticker(buffer, &pair.ticker_a);
+            encode_ticker(buffer, &pair.ticker_b);
+        }
+        TokenTicker::Basket(name) => {
+            buffer.push(BASKET_TICKER);
+            buffer.extend_from_slice(&(name.len() as u32).to_le_bytes());
+            buffer.extend_from_slice(name.as_bytes());
+        }
+        ticker => buffer.push(
+            TokenTicker::ALL
+                .iter()
+                .position(|candidate| candidate == ticker)
+                .unwrap() as u8,
+        ),
+    }
+}
+
+fn encode_string(buffer: &mut Vec<u8>, value: &str) {
+    buffer.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    buffer.extend_from_slice(value.as_bytes());
+}
+
+// Tag count followed by each key and value.
+fn encode_metadata(buffer: &mut Vec<u8>, metadata: &OrderMetadata) {
+    buffer.extend_from_slice(&(metadata.len() as u32).to_le_bytes());
+    for (key, value) in metadata.iter() {
+        encode_string(buffer, key);
+        encode_string(buffer, value);
+    }
+}
+
+fn tif_code(time_in_force: TimeInForce) -> u8 {
+    match time_in_force {
+        TimeInForce::GoodTillCancel => 0,
+        TimeInForce::Day => 1,
+    }
+}
+
+fn side_code(side: BuyOrSell) -> u8 {
+    match side {
+        BuyOrSell::Buy => 0,
+        BuyOrSell::Sell => 1,
+    }
+}
+
+fn encode_record(buffer: &mut Vec<u8>, record: &WalRecord) {
+    buffer.extend_from_slice(&record.sequence.to_le_bytes());
+    buffer.extend_from_slice(&record.timestamp.to_le_bytes());
+    match &record.command {
+        Command::ListToken { ticker } => {
+            buffer.push(2);
+            encode_ticker(buffer, ticker);
+        }
+        Command::Admin(command) => {
+            buffer.push(3);
+            encode_admin(buffer, command);
+        }
+        Command::SubmitOrder {
+            ticker,
+            side,
+            price,
+            quantity,
+            time_in_force,
+            metadata,
+        } => {
+            buffer.push(0);
+            encode_ticker(buffer, ticker);
+            buffer.push(side_code(*side));
+            buffer.extend_from_slice(&price.to_le_bytes());
+            buffer.extend_from_slice(&quantity.to_le_bytes());
+            buffer.push(tif_code(*time_in_force));
+            match metadata {
+                Some(metadata) => {
+                    buffer.push(1);
+                    encode_metadata(buffer, metadata);
+                }
+                None => buffer.push(0),
+            }
+        }
+        Command::MatchOrders => buffer.push(1),
+        Command::CancelOrder {
+            ticker,
+            order_id,
+            expected_sequence,
+        } => {
+            buffer.push(4);
+            encode_ticker(buffer, ticker);
+            buffer.extend_from_slice(&order_id.to_le_bytes());
+            encode_optional_u64(buffer, *expected_sequence);
+        }
+        Command::ReplaceOrder {
+            ticker,
+            order_id,
+            price,
+            quantity,
+            expected_sequence,
+        } => {
+            buffer.push(5);
+            encode_ticker(buffer, ticker);
+            buffer.extend_from_slice(&order_id.to_le_bytes());
+            buffer.extend_from_slice(&price.to_le_bytes());
+            buffer.extend_from_slice(&quantity.to_le_bytes());
+            encode_optional_u64(buffer, *expected_sequence);
+        }
+    }
+}
+
+fn decode_record(bytes: &[u8]) -> Option<WalRecord> {
+    let mut reader = Reader::new(bytes);
+    let sequence = reader.u64()?;
+    let timestamp = reader.u64()?;
+    let command = match reader.u8()? {
+        0 => Command::SubmitOrder {
+            ticker: reader.ticker()?,
+            side: reader.side()?,
+            price: reader.f64()?,
+            quantity: reader.u32()?,
+            time_in_force: reader.time_in_force()?,
+            metadata: match reader.u8()? {
+                0 => None,
+                1 => Some(reader.metadata()?),
+                _ => return None,
+            },
+        },
+        tag => decode_unchanged_command(&mut reader, tag)?,
+    };
+    Some(WalRecord {
+        sequence,
+        timestamp,
+        command,
+    })
+}
+
+fn decode_record_v2(bytes: &[u8]) -> Option<WalRecordV2> {
+    let mut reader = Reader::new(bytes);
+    let sequence = reader.u64()?;
+    let timestamp = reader.u64()?;
+    let command = match reader.u8()? {
+        0 => CommandV2::SubmitOrder {
+            ticker: reader.ticker()?,
+            side: reader.side()?,
+            price: reader.f64()?,
+            quantity: reader.u32()?,
+            time_in_force: reader.time_in_force()?,
+        },
+        tag => CommandV2::Unchanged(decode_unchanged_command(&mut reader, tag)?),
+    };
+    Some(WalRecordV2 {
+        sequence,
+        timestamp,
+        command,
+    })
+}
+
+fn decode_record_v1(bytes: &[u8]) -> Option<WalRecordV1> {
+    let mut reader = Reader::new(bytes);
+    let sequence = reader.u64()?;
+    let timestamp = reader.u64()?;
+    let command = match reader.u8()? {
+        0 => CommandV1::SubmitOrder {
+            ticker: reader.ticker()?,
+            side: reader.side()?,
+            price: reader.f64()?,
+            quantity: reader.u32()?,
+        },
+        tag => CommandV1::Unchanged(decode_unchanged_command(&mut reader, tag)?),
+    };
+    Some(WalRecordV1 {
+        sequence,
+        timestamp,
+        command,
+    })
+}
+
+// Commands encoded the same way in every version.
+fn decode_unchanged_command(reader: &mut Reader, tag: u8) -> Option<Command> {
+    Some(match tag {
+        1 => Command::MatchOrders,
+        2 => Command::ListToken {
+            ticker: reader.ticker()?,
+        },
+        3 => Command::Admin(decode_admin(reader)?),
+        4 => Command::CancelOrder {
+            ticker: reader.ticker()?,
+            order_id: reader.u64()?,
+            expected_sequence: reader.optional_u64()?,
+        },
+        5 => Command::ReplaceOrder {
+            ticker: reader.ticker()?,
+            order_id: reader.u64()?,
+            price: reader.f64()?,
+            quantity: reader.u32()?,
+            expected_sequence: reader.optional_u64()?,
+        },
+        _ => return None,
+    })
+}
+
+fn encode_admin(buffer: &mut Vec<u8>, command: &AdminCommand) {
+    match command {
+        AdminCommand::SetFeeSchedule {
+            maker_bps,
+            taker_bps,
+        } => {
+            buffer.push(0);
+            buffer.extend_from_slice(&maker_bps.to_le_bytes());
+            buffer.extend_from_slice(&taker_bps.to_le_bytes());
+        }
+        AdminCommand::SetOpenNotionalLimit { ticker, limit } => {
+            buffer.push(1);
+            encode_ticker(buffer, ticker);
+            encode_optional_u64(buffer, *limit);
+        }
+        AdminCommand::HaltInstrument { ticker } => {
+            buffer.push(2);
+            encode_ticker(buffer, ticker);
+        }
+        AdminCommand::ResumeInstrument { ticker } => {
+            buffer.push(3);
+            encode_ticker(buffer, ticker);
+        }
+        AdminCommand::RotateLog => buffer.push(4),
+    }
+}
+
+fn decode_admin(reader: &mut Reader) -> Option<AdminCommand> {
+    Some(match reader.u8()? {
+        0 => AdminCommand::SetFeeSchedule {
+            maker_bps: reader.u64()?,
+            taker_bps: reader.u64()?,
+        },
+        1 => AdminCommand::SetOpenNotionalLimit {
+            ticker: reader.ticker()?,
+            limit: reader.optional_u64()?,
+        },
+        2 => AdminCommand::HaltInstrument {
+            ticker: reader.ticker()?,
+        },
+        3 => AdminCommand::ResumeInstrument {
+            ticker: reader.ticker()?,
+        },
+        4 => AdminCommand::RotateLog,
+        _ => return None,
+    })
+}
+
+pub(crate) fn encode_snapshot(sequence: u64, engine: &TradeEngine) -> Vec<u8> {
+    let mut buffer = sequence.to_le_bytes().to_vec();
+    let mut books: Vec<(&TokenTicker, &OrderBook)> = engine.order_books.iter().collect();
+    books.sort_by_key(|(ticker, _)| *ticker);
+    buffer.extend_from_slice(&(books.len() as u32).to_le_bytes());
+    for (ticker, orderbook) in books.iter() {
+        let orders = orderbook.resting_orders();
+        encode_ticker(&mut buffer, ticker);
+        buffer.extend_from_slice(&orderbook.next_order_id().to_le_bytes());
+        buffer.extend_from_slice(&(orders.len() as u32).to_le_bytes());
+        for (side, order) in orders {
+            buffer.push(side_code(side));
+            buffer.extend_from_slice(&order.id.to_le_bytes());
+            buffer.extend_from_slice(&order.price.to_le_bytes());
+            buffer.extend_from_slice(&order.quantity.to_le_bytes());
+            buffer.extend_from_slice(&order.timestamp.to_le_bytes());
+            buffer.push(tif_code(order.time_in_force));
+            let wallet = order.wallet.as_ref().map_or("", |wallet| &wallet.address);
+            buffer.extend_from_slice(&(wallet.len() as u32).to_le_bytes());
+            buffer.extend_from_slice(wallet.as_bytes());
+        }
+    }
+    let configuration = engine.configuration();
+    buffer.extend_from_slice(&(configuration.len() as u32).to_le_bytes());
+    for command in configuration.iter() {
+        encode_admin(&mut buffer, command);
+    }
+    // book sequences trail the snapshot so older snapshots still load
+    buffer.extend_from_slice(&(books.len() as u32).to_le_bytes());
+    for (ticker, orderbook) in books.iter() {
+        encode_ticker(&mut buffer, ticker);
+        buffer.extend_from_slice(&orderbook.sequence().to_le_bytes());
+    }
+    // as are the tags of resting orders
+    let tagged: Vec<(&TokenTicker, u64, OrderMetadata)> = books
+        .iter()
+        .flat_map(|(ticker, orderbook)| {
+            orderbook
+                .resting_orders()
+                .into_iter()
+                .filter_map(move |(_, order)| Some((*ticker, order.id, order.metadata?)))
+        })
+        .collect();
+    buffer.extend_from_slice(&(tagged.len() as u32).to_le_bytes());
+    for (ticker, order_id, metadata) in tagged {
+        encode_ticker(&mut buffer, ticker);
+        buffer.extend_from_slice(&order_id.to_le_bytes());
+        encode_metadata(&mut buffer, &metadata);
+    }
+    buffer
+}
+
+// Load the books and configuration of a snapshot into an empty engine.
+pub(crate) fn restore_snapshot(engine: &mut TradeEngine, bytes: &[u8]) -> Option<()> {
+    let mut reader = Reader::new(bytes);
+    reader.u64()?;
+    for _ in 0..reader.u32()? {
+        let ticker = reader.ticker()?;
+        let next_order_id = reader.u64()?;
+        let mut orders = Vec::new();
+        for _ in 0..reader.u32()? {
+            let side = reader.side()?;
+            let id = reader.u64()?;
+            let price = reader.f64()?;
+            let quantity = reader.u32()?;
+            let timestamp = reader.u64()?;
+            let time_in_force = reader.time_in_force()?;
+            let length = reader.u32()? as usize;
+            let address = String::from_utf8(reader.take(length)?.to_vec()).ok()?;
+            let wallet = (!address.is_empty()).then(|| Wallet::new(address));
+            orders.push((
+                side,
+                Order {
+                    wallet,
+                    time_in_force,
+                    ..Order::new(id, quantity, price, timestamp)
+                },
+            ));
+        }
+        engine
+            .order_books
+            .insert(ticker, OrderBook::restore(next_order_id, orders));
+    }
+    for _ in 0..reader.u32()? {
+        engine.configure(&decode_admin(&mut reader)?);
+    }
+    // absent from snapshots written before books kept their sequence
+    for _ in 0..reader.u32().unwrap_or(0) {
+        let ticker = reader.ticker()?;
+        let sequence = reader.u64()?;
+        engine
+            .order_books
+            .get_mut(&ticker)?
+            .resume_sequence(sequence);
+    }
+    // absent from snapshots written before orders were tagged
+    for _ in 0..reader.u32().unwrap_or(0) {
+        let ticker = reader.ticker()?;
+        let order_id = reader.u64()?;
+        let metadata = reader.metadata()?;
+        if !engine
+            .order_books
+            .get_mut(&ticker)?
+            .restore_metadata(order_id, metadata)
+        {
+            return None;
+        }
+    }
+    Some(())
+}
+
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Reader<'a> {
+        Reader { bytes, position: 0 }
+    }
+
+    pub(crate) fn take(&mut self, length: usize) -> Option<&'a [u8]> {
+        let bytes = self.bytes.get(self.position..self.position + length)?;
+        self.position += length;
+        Some(bytes)
+    }
+
+    pub(crate) fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_le_bytes(self.take(2)?.try_into().ok()?))
+    }
+
+    pub(crate) fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    pub(crate) fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    fn optional_u64(&mut self) -> Option<Option<u64>> {
+        match self.u8()? {
+            0 => Some(None),
+            1 => Some(Some(self.u64()?)),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn f64(&mut self) -> Option<f64> {
+        Some(f64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    pub(crate) fn ticker(&mut self) -> Option<TokenTicker> {
+        match self.u8()? {
+            LP_TICKER => Some(TokenTicker::lp(Pair::new(self.ticker()?, self.ticker()?))),
+            BASKET_TICKER => {
+                let length = self.u32()? as usize;
+                let name = String::from_utf8(self.take(length)?.to_vec()).ok()?;
+                Some(TokenTicker::Basket(name))
+            }
+            code => TokenTicker::ALL.get(code as usize).cloned(),
+        }
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let length = self.u32()? as usize;
+        String::from_utf8(self.take(length)?.to_vec()).ok()
+    }
+
+    fn metadata(&mut self) -> Option<OrderMetadata> {
+        let mut tags = Vec::new();
+        for _ in 0..self.u32()? {
+            tags.push((self.string()?, self.string()?));
+        }
+        Some(tags.into())
+    }
+
+    fn time_in_force(&mut self) -> Option<TimeInForce> {
+        match self.u8()? {
+            0 => Some(TimeInForce::GoodTillCancel),
+            1 => Some(TimeInForce::Day),
+            _ => None,
+        }
+    }
+
+    fn side(&mut self) -> Option<BuyOrSell> {
+        match self.u8()? {
+            0 => Some(BuyOrSell::Buy),
+            1 => Some(BuyOrSell::Sell),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::book_state::assert_books_equal;
+
+    fn temp_directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("wal-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        directory
+    }
+
+    fn submit(side: BuyOrSell, price: f64, quantity: u32) -> Command {
+        Command::SubmitOrder {
+            ticker: TokenTicker::ETH,
+            side,
+            price,
+            quantity,
+            time_in_force: TimeInForce::GoodTillCancel,
+            metadata: None,
+        }
+    }
+
+    fn book(engine: &TradeEngine) -> &OrderBook {
+        &engine.order_books[&TokenTicker::ETH]
+    }
+
+    #[test]
+    fn test_rotation_compaction_and_recovery() {
+        let directory = temp_directory("recovery");
+        let clock = SimulatedClock::new(1_000);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        let mut wal = Wal::open(
+            &directory,
+            WalConfig {
+                max_segment_bytes: 1024,
+                max_segment_millis: 10_000,
+            },
+        )
+        .unwrap();
+
+        let commands = [
+            Command::ListToken {
+                ticker: TokenTicker::ETH,
+            },
+            submit(BuyOrSell::Buy, 100.0, 5),
+            submit(BuyOrSell::Sell, 101.0, 3),
+            submit(BuyOrSell::Sell, 100.0, 2),
+            Command::MatchOrders,
+            submit(BuyOrSell::Buy, 99.0, 7),
+            Command::Admin(AdminCommand::SetOpenNotionalLimit {
+                ticker: TokenTicker::ETH,
+                limit: Some(5_000),
+            }),
+        ];
+        for command in commands.iter().cloned() {
+            engine.execute_logged(&mut wal, command).unwrap();
+            clock.advance(6_000);
+        }
+        // rotated every other record by time
+        assert_eq!(files(&directory, SEGMENT_EXTENSION).unwrap().len(), 4);
+        let recovered = TradeEngine::recover(&directory, Box::new(clock.clone())).unwrap();
+        assert_books_equal(book(&recovered), book(&engine));
+        assert_eq!(recovered.trade_feed.trades(), engine.trade_feed.trades());
+
+        // snapshot plus tail
+        assert_eq!(wal.compact().unwrap(), 7);
+        assert!(files(&directory, SEGMENT_EXTENSION).unwrap().is_empty());
+        engine
+            .execute_logged(&mut wal, submit(BuyOrSell::Sell, 99.0, 1))
+            .unwrap();
+        engine
+            .execute_logged(&mut wal, Command::MatchOrders)
+            .unwrap();
+        // sequence checks give the same answers after recovery
+        let seen = engine.book_sequence(&TokenTicker::ETH);
+        let results: Vec<CommandResult> = [
+            Command::ReplaceOrder {
+                ticker: TokenTicker::ETH,
+                order_id: 4,
+                price: 98.0,
+                quantity: 6,
+                expected_sequence: seen,
+            },
+            Command::CancelOrder {
+                ticker: TokenTicker::ETH,
+                order_id: 2,
+                expected_sequence: seen,
+            },
+        ]
+        .into_iter()
+        .map(|command| engine.execute_logged(&mut wal, command).unwrap())
+        .collect();
+        assert_eq!(
+            results,
+            vec![
+                CommandResult::OrderReplaced {
+                    order_id: 4,
+                    new_order_id: 6
+                },
+                CommandResult::StaleSequence {
+                    expected: seen.unwrap(),
+                    current: seen.unwrap() + 2
+                }
+            ]
+        );
+        drop(wal);
+        assert_eq!(
+            Wal::open(&directory, WalConfig::default())
+                .unwrap()
+                .next_sequence,
+            12
+        );
+
+        let mut recovered = TradeEngine::recover(&directory, Box::new(clock.clone())).unwrap();
+        assert_books_equal(book(&recovered), book(&engine));
+        assert_eq!(recovered.open_notional_limits, engine.open_notional_limits);
+        assert_eq!(recovered.now(), clock.now_millis());
+        // order ids continue where the log left off
+        assert_eq!(
+            recovered.execute(submit(BuyOrSell::Buy, 90.0, 1)),
+            engine.execute(submit(BuyOrSell::Buy, 90.0, 1))
+        );
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_torn_tail_is_ignored() {
+        let directory = temp_directory("torn");
+        let mut wal = Wal::open(&directory, WalConfig::default()).unwrap();
+        wal.append(1, &Command::MatchOrders).unwrap();
+        wal.append(2, &Command::MatchOrders).unwrap();
+        let (_, path) = files(&directory, SEGMENT_EXTENSION).unwrap().remove(0);
+        let bytes = fs::read(&path).unwrap();
+        fs::write(&path, &bytes[..bytes.len() - 3]).unwrap();
+        assert_eq!(read_segment(&path).unwrap().len(), 1);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+    #[test]
+    fn test_replays_unversioned_segment() {
+        let directory = temp_directory("legacy");
+        fs::create_dir_all(&directory).unwrap();
+        // two records as written before segments were versioned
+        let mut segment = Vec::new();
+        for (sequence, command) in [(1u64, vec![2, 1]), (2, vec![0, 1, 1])] {
+            let mut payload = sequence.to_le_bytes().to_vec();
+            payload.extend_from_slice(&(sequence * 10).to_le_bytes());
+            payload.extend_from_slice(&command);
+            if sequence == 2 {
+                payload.extend_from_slice(&101.5f64.to_le_bytes());
+                payload.extend_from_slice(&4u32.to_le_bytes());
+            }
+            segment.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+            segment.extend_from_slice(&payload);
+        }
+        fs::write(directory.join(file_name(1, SEGMENT_EXTENSION)), segment).unwrap();
+
+        let records = read_segment(&directory.join(file_name(1, SEGMENT_EXTENSION))).unwrap();
+        assert_eq!(records[1].command, submit(BuyOrSell::Sell, 101.5, 4));
+        let engine = TradeEngine::recover(&directory, Box::new(SimulatedClock::new(0))).unwrap();
+        assert_eq!(
+            engine.order_books[&TokenTicker::ETH].level_count(BuyOrSell::Sell),
+            1
+        );
+
+        // new appends go to a versioned segment
+        let mut wal = Wal::open(&directory, WalConfig::default()).unwrap();
+        assert_eq!(wal.append(30, &Command::MatchOrders).unwrap(), 3);
+        let bytes = fs::read(directory.join(file_name(3, SEGMENT_EXTENSION))).unwrap();
+        assert!(bytes.starts_with(SEGMENT_MAGIC));
+        fs::write(
+            directory.join(file_name(4, SEGMENT_EXTENSION)),
+            b"TWAL\x09\x00",
+        )
+        .unwrap();
+        assert_eq!(
+            Wal::open(&directory, WalConfig::default()).err(),
+            Some(WalError::UnsupportedVersion {
+                file: directory.join(file_name(4, SEGMENT_EXTENSION)),
+                version: 9
+            })
+        );
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_standalone_snapshot_round_trip() {
+        let directory = temp_directory("standalone");
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("book.snap");
+        let mut engine = TradeEngine::new();
+        for command in [
+            Command::ListToken {
+                ticker: TokenTicker::ETH,
+            },
+            submit(BuyOrSell::Buy, 100.0, 5),
+            submit(BuyOrSell::Sell, 102.0, 3),
+        ] {
+            engine.execute(command);
+        }
+        engine.save_snapshot(&path).unwrap();
+
+        let loaded = TradeEngine::load_snapshot(&path, Box::new(SimulatedClock::new(0))).unwrap();
+        assert_books_equal(book(&loaded), book(&engine));
+        fs::write(&path, [1, 2, 3]).unwrap();
+        assert_eq!(
+            TradeEngine::load_snapshot(&path, Box::new(SimulatedClock::new(0))).err(),
+            Some(WalError::Corrupt { file: path })
+        );
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_tagged_orders_survive_log_and_snapshot() {
+        let metadata: OrderMetadata = vec![
+            (String::from("desk"), String::from("mm")),
+            (String::from("client_id"), String::from("c-1")),
+        ]
+        .into();
+        let record = WalRecord {
+            sequence: 2,
+            timestamp: 7,
+            command: Command::SubmitOrder {
+                ticker: TokenTicker::ETH,
+                side: BuyOrSell::Buy,
+                price: 99.5,
+                quantity: 2,
+                time_in_force: TimeInForce::GoodTillCancel,
+                metadata: Some(metadata.clone()),
+            },
+        };
+        let mut buffer = Vec::new();
+        encode_record(&mut buffer, &record);
+        assert_eq!(decode_record(&buffer), Some(record.clone()));
+        // a version 2 record ends after the time in force
+        let v2 = decoder(2).unwrap()(&buffer[..buffer.len() - 1]).unwrap();
+        assert_eq!(v2.upgrade().command, submit(BuyOrSell::Buy, 99.5, 2));
+
+        let mut engine = TradeEngine::new();
+        engine.execute(Command::ListToken {
+            ticker: TokenTicker::ETH,
+        });
+        engine.execute(record.command);
+        engine.execute(submit(BuyOrSell::Buy, 99.0, 1));
+        let mut restored = TradeEngine::new();
+        restore_snapshot(&mut restored, &encode_snapshot(2, &engine)).unwrap();
+        assert_eq!(
+            book(&restored).find_order(1).unwrap().1.metadata,
+            Some(metadata)
+        );
+        assert_eq!(book(&restored).find_order(2).unwrap().1.metadata, None);
+    }
+
+    #[test]
+    fn test_lp_ticker_round_trip() {
+        let record = WalRecord {
+            sequence: 3,
+            timestamp: 9,
+            command: Command::ListToken {
+                ticker: TokenTicker::lp(Pair::new(TokenTicker::ETH, TokenTicker::USDT)),
+            },
+        };
+        let mut buffer = Vec::new();
+        encode_record(&mut buffer, &record);
+        assert_eq!(decode_record(&buffer), Some(record));
+    }
+
+    #[test]
+    fn test_basket_ticker_round_trip() {
+        let record = WalRecord {
+            sequence: 4,
+            timestamp: 10,
+            command: Command::ListToken {
+                ticker: TokenTicker::Basket(String::from("DEFI5")),
+            },
+        };
+        let mut buffer = Vec::new();
+        encode_record(&mut buffer, &record);
+        assert_eq!(decode_record(&buffer), Some(record));
+    }
+}
@@ -0,0 +1,461 @@
+use std::collections::{BTreeMap, HashMap};
+
+use super::engine::TradeEngine;
+use super::ledger::{rescale_amount, LedgerError};
+use super::order::Wallet;
+use super::rounding::Flow;
+use super::token::TokenTicker;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WithdrawalError {
+    ZeroAmount,
+    UnknownRequest,
+    // The request is not in a state that allows the operation.
+    InvalidState { status: WithdrawalStatus },
+    Ledger(LedgerError),
+}
+
+impl From<LedgerError> for WithdrawalError {
+    fn from(err: LedgerError) -> Self {
+        WithdrawalError::Ledger(err)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HoldReason {
+    // The wallet was on either side of a trade above the large trade
+    // threshold within the look-back window.
+    RecentLargeTrade { trade_id: u64 },
+    PendingSettlements { count: u32 },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WithdrawalStatus {
+    // Waiting for the risk checks.
+    Queued,
+    Held(HoldReason),
+    // Passed the risk checks, waiting for approval.
+    Cleared,
+    Approved,
+    Rejected,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WithdrawalEvent {
+    Requested {
+        id: u64,
+        wallet: Wallet,
+        token: TokenTicker,
+        amount: u64,
+        timestamp: u64,
+    },
+    Held {
+        id: u64,
+        reason: HoldReason,
+        timestamp: u64,
+    },
+    Cleared {
+        id: u64,
+        timestamp: u64,
+    },
+    Approved {
+        id: u64,
+        timestamp: u64,
+    },
+    Rejected {
+        id: u64,
+        reason: String,
+        timestamp: u64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WithdrawalRequest {
+    pub id: u64,
+    pub wallet: Wallet,
+    pub token: TokenTicker,
+    pub amount: u64,
+    pub requested_at: u64,
+    pub status: WithdrawalStatus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WithdrawalRiskConfig {
+    // Trades with a notional at or above this hold withdrawals of both
+    // counterparties; None disables the check.
+    pub large_trade_notional: Option<u64>,
+    pub large_trade_window_millis: u64,
+    pub hold_on_pending_settlements: bool,
+}
+
+impl Default for WithdrawalRiskConfig {
+    fn default() -> Self {
+        WithdrawalRiskConfig {
+            large_trade_notional: None,
+            large_trade_window_millis: 24 * 60 * 60 * 1_000,
+            hold_on_pending_settlements: true,
+        }
+    }
+}
+
+// Withdrawals are not executed on request: the amount is locked in the
+// ledger and the request waits until it passes the risk checks and is
+// approved, or is rejected and the funds are released.
+pub struct WithdrawalQueue {
+    pub risk: WithdrawalRiskConfig,
+    requests: BTreeMap<u64, WithdrawalRequest>,
+    pending_settlements: HashMap<Wallet, u32>,
+    events: Vec<WithdrawalEvent>,
+    next_id: u64,
+}
+
+impl Default for WithdrawalQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WithdrawalQueue {
+    pub fn new() -> WithdrawalQueue {
+        WithdrawalQueue {
+            risk: WithdrawalRiskConfig::default(),
+            requests: BTreeMap::new(),
+            pending_settlements: HashMap::new(),
+            events: Vec::new(),
+            next_id: 1,
+        }
+    }
+
+    pub fn request(&self, id: u64) -> Option<&WithdrawalRequest> {
+        self.requests.get(&id)
+    }
+
+    pub fn events(&self) -> &[WithdrawalEvent] {
+        &self.events
+    }
+
+    // Settlements owed to or by the wallet outside the engine, reported by
+    // the settlement system.
+    pub fn add_pending_settlement(&mut self, wallet: &Wallet) {
+        *self.pending_settlements.entry(wallet.clone()).or_insert(0) += 1;
+    }
+
+    pub fn complete_pending_settlement(&mut self, wallet: &Wallet) {
+        if let Some(count) = self.pending_settlements.get_mut(wallet) {
+            *count = count.saturating_sub(1);
+        }
+    }
+
+    pub fn pending_settlements(&self, wallet: &Wallet) -> u32 {
+        self.pending_settlements.get(wallet).copied().unwrap_or(0)
+    }
+
+    // Requests of `token` whose amount is still locked in the ledger.
+    pub(crate) fn pending_in<'a>(
+        &'a self,
+        token: &'a TokenTicker,
+    ) -> impl Iterator<Item = &'a WithdrawalRequest> {
+        self.requests.values().filter(move |request| {
+            &request.token == token
+                && !matches!(
+                    request.status,
+                    WithdrawalStatus::Approved | WithdrawalStatus::Rejected
+                )
+        })
+    }
+
+    // Whether every pending amount of `token` still fits a u64 after
+    // `rescale_token` with the same ratio.
+    pub(crate) fn can_rescale_token(
+        &self,
+        token: &TokenTicker,
+        numerator: u64,
+        denominator: u64,
+    ) -> bool {
+        self.pending_in(token)
+            .all(|request| rescale_amount(request.amount, numerator, denominator).is_some())
+    }
+
+    // Multiply the amounts of pending requests of `token` by
+    // `numerator / denominator`, rounding down. Approved and rejected
+    // requests keep the amounts they were settled at.
+    pub(crate) fn rescale_token(&mut self, token: &TokenTicker, numerator: u64, denominator: u64) {
+        let ids: Vec<u64> = self.pending_in(token).map(|request| request.id).collect();
+        for id in ids {
+            let request = self.requests.get_mut(&id).unwrap();
+            request.amount = rescale_amount(request.amount, numerator, denominator)
+                .expect("rescale checked beforehand");
+        }
+    }
+}
+
+impl TradeEngine {
+    // Lock `amount` and queue the withdrawal. Returns the request id.
+    pub fn request_withdrawal(
+        &mut self,
+        wallet: &Wallet,
+        token: &TokenTicker,
+        amount: u64,
+    ) -> Result<u64, WithdrawalError> {
+        if amount == 0 {
+            return Err(WithdrawalError::ZeroAmount);
+        }
+        self.ledger.lock(wallet, token, amount)?;
+        let timestamp = self.now();
+        let queue = &mut self.withdrawals;
+        let id = queue.next_id;
+        queue.next_id += 1;
+        queue.requests.insert(
+            id,
+            WithdrawalRequest {
+                id,
+                wallet: wallet.clone(),
+                token: token.clone(),
+                amount,
+                requested_at: timestamp,
+                status: WithdrawalStatus::Queued,
+            },
+        );
+        queue.events.push(WithdrawalEvent::Requested {
+            id,
+            wallet: wallet.clone(),
+            token: token.clone(),
+            amount,
+            timestamp,
+        });
+        Ok(id)
+    }
+
+    // Run the risk checks on every queued or held request. Held requests are
+    // cleared once the reason has gone away. Returns the events emitted.
+    pub fn process_withdrawals(&mut self) -> Vec<WithdrawalEvent> {
+        let timestamp = self.now();
+        let mut emitted = Vec::new();
+        let ids: Vec<u64> = self
+            .withdrawals
+            .requests
+            .values()
+            .filter(|request| {
+                matches!(
+                    request.status,
+                    WithdrawalStatus::Queued | WithdrawalStatus::Held(_)
+                )
+            })
+            .map(|request| request.id)
+            .collect();
+        for id in ids {
+            let wallet = self.withdrawals.requests[&id].wallet.clone();
+            let status = match self.withdrawal_hold(&wallet, timestamp) {
+                Some(reason) => WithdrawalStatus::Held(reason),
+                None => WithdrawalStatus::Cleared,
+            };
+            let request = self.withdrawals.requests.get_mut(&id).unwrap();
+            if request.status == status {
+                continue;
+            }
+            request.status = status.clone();
+            emitted.push(match status {
+                WithdrawalStatus::Held(reason) => WithdrawalEvent::Held {
+                    id,
+                    reason,
+                    timestamp,
+                },
+                _ => WithdrawalEvent::Cleared { id, timestamp },
+            });
+        }
+        self.withdrawals.events.extend(emitted.iter().cloned());
+        emitted
+    }
+
+    // Execute a cleared withdrawal: the locked funds leave the ledger.
+    pub fn approve_withdrawal(&mut self, id: u64) -> Result<(), WithdrawalError> {
+        let request = self.pending_withdrawal(id, |status| *status == WithdrawalStatus::Cleared)?;
+        self.ledger
+            .debit_locked(&request.wallet, &request.token, request.amount)?;
+        let timestamp = self.now();
+        self.set_withdrawal_status(id, WithdrawalStatus::Approved);
+        self.withdrawals
+            .events
+            .push(WithdrawalEvent::Approved { id, timestamp });
+        Ok(())
+    }
+
+    // Reject a request that has not been approved yet and release its funds.
+    pub fn reject_withdrawal(&mut self, id: u64, reason: &str) -> Result<(), WithdrawalError> {
+        let request = self.pending_withdrawal(id, |status| {
+            !matches!(
+                status,
+                WithdrawalStatus::Approved | WithdrawalStatus::Rejected
+            )
+        })?;
+        self.ledger
+            .unlock(&request.wallet, &request.token, request.amount)?;
+        let timestamp = self.now();
+        self.set_withdrawal_status(id, WithdrawalStatus::Rejected);
+        self.withdrawals.events.push(WithdrawalEvent::Rejected {
+            id,
+            reason: reason.to_string(),
+            timestamp,
+        });
+        Ok(())
+    }
+
+    fn pending_withdrawal(
+        &self,
+        id: u64,
+        allowed: impl Fn(&WithdrawalStatus) -> bool,
+    ) -> Result<WithdrawalRequest, WithdrawalError> {
+        let request = self
+            .withdrawals
+            .requests
+            .get(&id)
+            .ok_or(WithdrawalError::UnknownRequest)?;
+        if !allowed(&request.status) {
+            return Err(WithdrawalError::InvalidState {
+                status: request.status.clone(),
+            });
+        }
+        Ok(request.clone())
+    }
+
+    fn set_withdrawal_status(&mut self, id: u64, status: WithdrawalStatus) {
+        if let Some(request) = self.withdrawals.requests.get_mut(&id) {
+            request.status = status;
+        }
+    }
+
+    fn withdrawal_hold(&self, wallet: &Wallet, now: u64) -> Option<HoldReason> {
+        let risk = self.withdrawals.risk;
+        let pending = self.withdrawals.pending_settlements(wallet);
+        if risk.hold_on_pending_settlements && pending > 0 {
+            return Some(HoldReason::PendingSettlements { count: pending });
+        }
+        let threshold = risk.large_trade_notional?;
+        let since = now.saturating_sub(risk.large_trade_window_millis);
+        self.trade_feed
+            .trades()
+            .iter()
+            .rev()
+            .take_while(|trade| trade.timestamp >= since)
+            .find(|trade| {
+                let involved =
+                    trade.buyer.as_ref() == Some(wallet) || trade.seller.as_ref() == Some(wallet);
+                involved
+                    && self
+                        .rounding()
+                        .amount(trade.price * trade.quantity as f64, Flow::Transfer)
+                        >= threshold
+            })
+            .map(|trade| HoldReason::RecentLargeTrade { trade_id: trade.id })
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::corelib::clock::SimulatedClock;
+    use crate::corelib::trade::{Trade, TradeKind};
+
+    #[test]
+    fn test_withdrawal_held_then_cleared_and_approved() {
+        let clock = SimulatedClock::new(0);
+        let mut engine = TradeEngine::with_clock(Box::new(clock.clone()));
+        let buyer = Wallet::new(String::from("withdrawbuyer"));
+        let seller = Wallet::new(String::from("withdrawseller"));
+        engine
+            .ledger
+            .deposit(seller.clone(), TokenTicker::USDT, 120_000)
+            .unwrap();
+        engine.withdrawals.risk.large_trade_notional = Some(100_000);
+        engine.withdrawals.risk.large_trade_window_millis = 60_000;
+
+        let trade_id = engine.trade_feed.publish(Trade {
+            id: 0,
+            ticker: TokenTicker::BTC,
+            price: 60_000.0,
+            quantity: 2,
+            buyer: Some(buyer),
+            seller: Some(seller.clone()),
+            buy_order_id: None,
+            sell_order_id: None,
+            timestamp: 0,
+            kind: TradeKind::OffBook,
+            buy_metadata: None,
+            sell_metadata: None,
+            settled_notional: None,
+        });
+
+        let id = engine
+            .request_withdrawal(&seller, &TokenTicker::USDT, 100_000)
+            .unwrap();
+        assert_eq!(
+            engine.ledger.locked_balance(&seller, &TokenTicker::USDT),
+            100_000
+        );
+        assert_eq!(
+            engine.process_withdrawals(),
+            vec![WithdrawalEvent::Held {
+                id,
+                reason: HoldReason::RecentLargeTrade { trade_id },
+                timestamp: 0,
+            }]
+        );
+        assert!(matches!(
+            engine.approve_withdrawal(id),
+            Err(WithdrawalError::InvalidState { .. })
+        ));
+
+        clock.advance(60_001);
+        assert_eq!(
+            engine.process_withdrawals(),
+            vec![WithdrawalEvent::Cleared {
+                id,
+                timestamp: 60_001
+            }]
+        );
+        engine.approve_withdrawal(id).unwrap();
+        assert_eq!(
+            engine.ledger.balance(&seller, &TokenTicker::USDT).total(),
+            20_000
+        );
+        assert_eq!(
+            engine.withdrawals.request(id).unwrap().status,
+            WithdrawalStatus::Approved
+        );
+        assert_eq!(engine.withdrawals.events().len(), 4);
+    }
+
+    #[test]
+    fn test_pending_settlement_hold_and_reject() {
+        let mut engine = TradeEngine::new();
+        let wallet = Wallet::new(String::from("withdrawwallet"));
+        engine
+            .ledger
+            .deposit(wallet.clone(), TokenTicker::ETH, 10)
+            .unwrap();
+        engine.withdrawals.add_pending_settlement(&wallet);
+
+        assert_eq!(
+            engine.request_withdrawal(&wallet, &TokenTicker::ETH, 0),
+            Err(WithdrawalError::ZeroAmount)
+        );
+        let id = engine
+            .request_withdrawal(&wallet, &TokenTicker::ETH, 10)
+            .unwrap();
+        engine.process_withdrawals();
+        assert_eq!(
+            engine.withdrawals.request(id).unwrap().status,
+            WithdrawalStatus::Held(HoldReason::PendingSettlements { count: 1 })
+        );
+        // unchanged requests emit nothing
+        assert!(engine.process_withdrawals().is_empty());
+
+        engine.reject_withdrawal(id, "manual review").unwrap();
+        assert_eq!(engine.ledger.free_balance(&wallet, &TokenTicker::ETH), 10);
+        assert!(matches!(
+            engine.reject_withdrawal(id, "again"),
+            Err(WithdrawalError::InvalidState { .. })
+        ));
+    }
+}
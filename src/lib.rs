@@ -1,4 +1,11 @@
+#[cfg(feature = "capi")]
+pub mod capi;
 pub mod corelib;
+pub mod prelude;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 #[cfg(test)]
 mod test {
@@ -167,4 +174,29 @@ mod test {
         assert_eq!(order_book.buy_volume().unwrap(), 641 + 87 + 900 + 784);
         assert_eq!(order_book.sell_volume().unwrap(), 200 + 100 + 10);
     }
+
+    #[test]
+    fn test_depth_snapshot() {
+        let mut order_book = OrderBook::new();
+        order_book.add_order(BuyOrSell::Buy, 99.5, 10, 1);
+        order_book.add_order(BuyOrSell::Buy, 99.5, 5, 2);
+        order_book.add_order(BuyOrSell::Buy, 100.0, 7, 3);
+        order_book.add_order(BuyOrSell::Sell, 101.0, 4, 4);
+        order_book.add_order(BuyOrSell::Sell, 100.5, 6, 5);
+
+        let snapshot = order_book.depth_snapshot();
+        let bids: Vec<(f64, u64)> = snapshot
+            .bids
+            .iter()
+            .map(|l| (l.price, l.quantity))
+            .collect();
+        let asks: Vec<(f64, u64)> = snapshot
+            .asks
+            .iter()
+            .map(|l| (l.price, l.quantity))
+            .collect();
+
+        assert_eq!(bids, vec![(100.0, 7), (99.5, 15)]);
+        assert_eq!(asks, vec![(100.5, 6), (101.0, 4)]);
+    }
 }
@@ -0,0 +1,12 @@
+// The types most programs against the engine need, for
+// `use trading_engine::prelude::*;`.
+pub use crate::corelib::amm::AMMPool;
+pub use crate::corelib::book_builder::OrderBookBuilder;
+pub use crate::corelib::depth::{DepthLevel, DepthSnapshot};
+pub use crate::corelib::engine::{OrderError, TradeEngine};
+pub use crate::corelib::events::BookEvent;
+pub use crate::corelib::order::{BuyOrSell, Order, OrderMetadata, TimeInForce, Wallet};
+pub use crate::corelib::orderbook::{BookRejection, Fill, OrderBook, OrderBookTrait};
+pub use crate::corelib::price::PriceLike;
+pub use crate::corelib::token::{Pair, TokenTicker};
+pub use crate::corelib::trade::{Trade, TradeKind};
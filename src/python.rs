@@ -0,0 +1,257 @@
+// Python bindings. `Engine` wraps a trade engine on a simulated clock so a
+// backtest steps time explicitly; tokens and sides are passed by name and
+// engine errors surface as `ValueError`.
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use crate::corelib::clock::SimulatedClock;
+use crate::corelib::depth::DepthLevel;
+use crate::corelib::engine::TradeEngine;
+use crate::corelib::order::{BuyOrSell, TimeInForce, Wallet};
+use crate::corelib::token::{Pair, TokenTicker};
+
+type Levels = Vec<(f64, u64)>;
+
+fn ticker(name: &str) -> PyResult<TokenTicker> {
+    TokenTicker::from_name(name)
+        .ok_or_else(|| PyValueError::new_err(format!("unknown ticker `{}`", name)))
+}
+
+fn side(name: &str) -> PyResult<BuyOrSell> {
+    match name {
+        "buy" => Ok(BuyOrSell::Buy),
+        "sell" => Ok(BuyOrSell::Sell),
+        name => Err(PyValueError::new_err(format!(
+            "side must be `buy` or `sell`, not `{}`",
+            name
+        ))),
+    }
+}
+
+fn rejected(err: impl std::fmt::Debug) -> PyErr {
+    PyValueError::new_err(format!("{:?}", err))
+}
+
+#[pyclass(name = "Engine", unsendable)]
+pub struct PyEngine {
+    engine: TradeEngine,
+    clock: SimulatedClock,
+}
+
+#[pymethods]
+impl PyEngine {
+    #[new]
+    #[pyo3(signature = (start_millis = 0))]
+    fn new(start_millis: u64) -> PyEngine {
+        let clock = SimulatedClock::new(start_millis);
+        PyEngine {
+            engine: TradeEngine::with_clock(Box::new(clock.clone())),
+            clock,
+        }
+    }
+
+    fn now(&self) -> u64 {
+        self.engine.now()
+    }
+
+    fn advance(&self, millis: u64) {
+        self.clock.advance(millis);
+    }
+
+    fn set_time(&self, millis: u64) {
+        self.clock.set(millis);
+    }
+
+    fn list_token(&mut self, name: &str) -> PyResult<()> {
+        self.engine.list_new_token(ticker(name)?);
+        Ok(())
+    }
+
+    // Good-till-cancel limit order; returns its id.
+    #[pyo3(signature = (name, side_name, price, quantity, wallet = None))]
+    fn submit_order(
+        &mut self,
+        name: &str,
+        side_name: &str,
+        price: f64,
+        quantity: u32,
+        wallet: Option<String>,
+    ) -> PyResult<u64> {
+        let (ticker, side) = (ticker(name)?, side(side_name)?);
+        match wallet {
+            Some(address) => self.engine.submit_wallet_order(
+                &Wallet::new(address),
+                &ticker,
+                side,
+                price,
+                quantity,
+                TimeInForce::GoodTillCancel,
+            ),
+            None => self.engine.submit_order(&ticker, side, price, quantity),
+        }
+        .map_err(rejected)
+    }
+
+    // (buy order id, sell order id, price, quantity) per fill.
+    fn match_orders(&mut self) -> Vec<(u64, u64, f64, u32)> {
+        self.engine.match_orders()
+    }
+
+    // (bids, asks) as (price, quantity) levels, best first.
+    #[pyo3(signature = (name, levels = None))]
+    fn depth(&self, name: &str, levels: Option<usize>) -> PyResult<(Levels, Levels)> {
+        let ticker = ticker(name)?;
+        let book = self
+            .engine
+            .order_books
+            .get(&ticker)
+            .ok_or_else(|| PyValueError::new_err(format!("{} is not listed", name)))?;
+        let snapshot = book.depth_snapshot();
+        let side = |side: &[DepthLevel]| {
+            side.iter()
+                .take(levels.unwrap_or(usize::MAX))
+                .map(|level| (level.price, level.quantity))
+                .collect()
+        };
+        Ok((side(&snapshot.bids), side(&snapshot.asks)))
+    }
+
+    // (trade id, price, quantity, timestamp) of the instrument's trades.
+    fn trades(&self, name: &str) -> PyResult<Vec<(u64, f64, u64, u64)>> {
+        let ticker = ticker(name)?;
+        Ok(self
+            .engine
+            .trade_feed
+            .trades_for(&ticker)
+            .map(|trade| (trade.id, trade.price, trade.quantity, trade.timestamp))
+            .collect())
+    }
+
+    fn credit(&mut self, wallet: String, name: &str, amount: u64) -> PyResult<()> {
+        self.engine
+            .ledger
+            .deposit(Wallet::new(wallet), ticker(name)?, amount)
+            .map_err(rejected)
+    }
+
+    fn free_balance(&self, wallet: String, name: &str) -> PyResult<u64> {
+        Ok(self
+            .engine
+            .ledger
+            .free_balance(&Wallet::new(wallet), &ticker(name)?))
+    }
+
+    // Fund a pool from the creator's free balances; returns the LP tokens
+    // minted.
+    fn create_pool(
+        &mut self,
+        creator: String,
+        token_a: &str,
+        token_b: &str,
+        fee_bps: u64,
+        amount_a: u64,
+        amount_b: u64,
+    ) -> PyResult<u64> {
+        let pair = Pair::new(ticker(token_a)?, ticker(token_b)?);
+        self.engine
+            .create_pool(
+                &Wallet::new(creator),
+                pair,
+                fee_bps,
+                amount_a,
+                amount_b,
+                u64::MAX,
+            )
+            .map_err(rejected)
+    }
+
+    // Reserves of the pair's primary pool, in pair order.
+    fn pool_reserves(&self, token_a: &str, token_b: &str) -> PyResult<Option<(u64, u64)>> {
+        let pair = Pair::new(ticker(token_a)?, ticker(token_b)?);
+        Ok(self.engine.amm_pools.get(&pair).map(|pool| {
+            (
+                pool.reserve(&pair.ticker_a).unwrap_or(0),
+                pool.reserve(&pair.ticker_b).unwrap_or(0),
+            )
+        }))
+    }
+
+    // What the best pool would pay for `amount_in`.
+    fn quote_swap(&self, token_in: &str, token_out: &str, amount_in: u64) -> PyResult<Option<u64>> {
+        Ok(self
+            .engine
+            .best_pool(&ticker(token_in)?, &ticker(token_out)?, amount_in)
+            .map(|quote| quote.amount_out))
+    }
+
+    #[pyo3(signature = (wallet, token_in, token_out, amount_in, min_out = 0))]
+    fn swap(
+        &mut self,
+        wallet: String,
+        token_in: &str,
+        token_out: &str,
+        amount_in: u64,
+        min_out: u64,
+    ) -> PyResult<u64> {
+        self.engine
+            .swap_exact_in(
+                &Wallet::new(wallet),
+                &ticker(token_in)?,
+                &ticker(token_out)?,
+                amount_in,
+                min_out,
+                u64::MAX,
+            )
+            .map_err(rejected)
+    }
+}
+
+#[pymodule]
+fn trading_engine(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyEngine>()
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_engine_round_trip() {
+        let mut engine = PyEngine::new(1_000);
+        engine.list_token("eth").unwrap();
+        engine.submit_order("ETH", "buy", 100.0, 5, None).unwrap();
+        engine.advance(10);
+        engine.submit_order("ETH", "sell", 99.0, 2, None).unwrap();
+        assert!(engine.submit_order("ETH", "hold", 1.0, 1, None).is_err());
+        assert_eq!(engine.match_orders(), vec![(1, 2, 99.0, 2)]);
+        assert_eq!(
+            engine.depth("ETH", None).unwrap(),
+            (vec![(100.0, 3)], vec![])
+        );
+        assert_eq!(engine.trades("ETH").unwrap(), vec![(1, 99.0, 2, 1_010)]);
+
+        engine.credit(String::from("lp"), "ETH", 10_000).unwrap();
+        engine.credit(String::from("lp"), "USDT", 20_000).unwrap();
+        engine.credit(String::from("taker"), "ETH", 100).unwrap();
+        engine
+            .create_pool(String::from("lp"), "ETH", "USDT", 30, 10_000, 20_000)
+            .unwrap();
+        let quote = engine.quote_swap("ETH", "USDT", 100).unwrap().unwrap();
+        assert_eq!(
+            engine
+                .swap(String::from("taker"), "ETH", "USDT", 100, 0)
+                .unwrap(),
+            quote
+        );
+        assert_eq!(
+            engine.pool_reserves("ETH", "USDT").unwrap(),
+            Some((10_100, 20_000 - quote))
+        );
+        assert_eq!(
+            engine.free_balance(String::from("taker"), "USDT").unwrap(),
+            quote
+        );
+    }
+}
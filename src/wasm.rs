@@ -0,0 +1,224 @@
+// wasm-bindgen wrappers for running simulations in the browser. Commands,
+// results, events and depth cross the boundary as JSON in the shapes of
+// `corelib::codec`, without the version envelope. Amounts and times are
+// JS numbers, exact up to 2^53.
+
+use wasm_bindgen::prelude::*;
+
+use crate::corelib::clock::SimulatedClock;
+use crate::corelib::codec::{Decode, Encode, Format, Value};
+use crate::corelib::command::Command;
+use crate::corelib::depth::DepthLevel;
+use crate::corelib::engine::TradeEngine;
+use crate::corelib::order::Wallet;
+use crate::corelib::token::{Pair, TokenTicker};
+
+fn ticker(name: &str) -> Result<TokenTicker, String> {
+    TokenTicker::from_name(name).ok_or_else(|| format!("unknown ticker `{}`", name))
+}
+
+fn json(value: &Value) -> String {
+    String::from_utf8(Format::Json.write(value)).expect("JSON output is UTF-8")
+}
+
+fn levels(levels: &[DepthLevel]) -> Value {
+    Value::Array(
+        levels
+            .iter()
+            .map(|level| Value::Array(vec![Value::Float(level.price), Value::UInt(level.quantity)]))
+            .collect(),
+    )
+}
+
+// Engine on a simulated clock the page steps with `advance`.
+#[wasm_bindgen(js_name = Engine)]
+pub struct WasmEngine {
+    engine: TradeEngine,
+    clock: SimulatedClock,
+}
+
+#[wasm_bindgen(js_class = Engine)]
+impl WasmEngine {
+    #[wasm_bindgen(constructor)]
+    pub fn new(start_millis: f64) -> WasmEngine {
+        let clock = SimulatedClock::new(start_millis as u64);
+        WasmEngine {
+            engine: TradeEngine::with_clock(Box::new(clock.clone())),
+            clock,
+        }
+    }
+
+    pub fn now(&self) -> f64 {
+        self.engine.now() as f64
+    }
+
+    pub fn advance(&self, millis: f64) {
+        self.clock.advance(millis as u64);
+    }
+
+    // Apply a JSON command such as
+    // `{"type":"SubmitOrder","ticker":"ETH","side":"Buy",...}` and return
+    // the JSON result.
+    pub fn execute(&mut self, command: &str) -> Result<String, String> {
+        let value = Format::Json
+            .read(command.as_bytes())
+            .and_then(|value| Command::decode(&value))
+            .map_err(|err| err.to_string())?;
+        Ok(json(&self.engine.execute(value).encode()))
+    }
+
+    // `{"bids":[[price,quantity],...],"asks":[...]}`, best first.
+    pub fn depth(&self, name: &str) -> Result<String, String> {
+        let book = self
+            .engine
+            .order_books
+            .get(&ticker(name)?)
+            .ok_or_else(|| format!("{} is not listed", name))?;
+        let snapshot = book.depth_snapshot();
+        Ok(json(&Value::Map(vec![
+            (String::from("bids"), levels(&snapshot.bids)),
+            (String::from("asks"), levels(&snapshot.asks)),
+        ])))
+    }
+
+    // Book events after `sequence` as `[{"sequence":n,"event":{...}},...]`.
+    // Fails once they are no longer retained; start over from `depth`.
+    pub fn events_since(&self, name: &str, sequence: f64) -> Result<String, String> {
+        let events = self
+            .engine
+            .book_deltas_since(&ticker(name)?, sequence as u64)
+            .ok_or_else(|| format!("events of {} after {} are gone", name, sequence))?;
+        Ok(json(&Value::Array(
+            events
+                .into_iter()
+                .map(|(sequence, event)| {
+                    Value::Map(vec![
+                        (String::from("sequence"), Value::UInt(sequence)),
+                        (String::from("event"), event.encode()),
+                    ])
+                })
+                .collect(),
+        )))
+    }
+
+    pub fn credit(&mut self, wallet: &str, name: &str, amount: f64) -> Result<(), String> {
+        self.engine
+            .ledger
+            .deposit(
+                Wallet::new(wallet.to_string()),
+                ticker(name)?,
+                amount as u64,
+            )
+            .map_err(|err| format!("{:?}", err))
+    }
+
+    pub fn free_balance(&self, wallet: &str, name: &str) -> Result<f64, String> {
+        Ok(self
+            .engine
+            .ledger
+            .free_balance(&Wallet::new(wallet.to_string()), &ticker(name)?) as f64)
+    }
+
+    // Fund a pool from the creator's free balances; returns the LP tokens
+    // minted.
+    pub fn create_pool(
+        &mut self,
+        creator: &str,
+        token_a: &str,
+        token_b: &str,
+        fee_bps: u32,
+        amount_a: f64,
+        amount_b: f64,
+    ) -> Result<f64, String> {
+        let pair = Pair::new(ticker(token_a)?, ticker(token_b)?);
+        self.engine
+            .create_pool(
+                &Wallet::new(creator.to_string()),
+                pair,
+                fee_bps as u64,
+                amount_a as u64,
+                amount_b as u64,
+                u64::MAX,
+            )
+            .map(|minted| minted as f64)
+            .map_err(|err| format!("{:?}", err))
+    }
+
+    // Sell exactly `amount_in` through the best pool; returns the amount
+    // received.
+    pub fn swap(
+        &mut self,
+        wallet: &str,
+        token_in: &str,
+        token_out: &str,
+        amount_in: f64,
+        min_out: f64,
+    ) -> Result<f64, String> {
+        self.engine
+            .swap_exact_in(
+                &Wallet::new(wallet.to_string()),
+                &ticker(token_in)?,
+                &ticker(token_out)?,
+                amount_in as u64,
+                min_out as u64,
+                u64::MAX,
+            )
+            .map(|amount_out| amount_out as f64)
+            .map_err(|err| format!("{:?}", err))
+    }
+
+    // `[reserve_a, reserve_b]` of the pair's primary pool, or `null`.
+    pub fn pool_reserves(&self, token_a: &str, token_b: &str) -> Result<String, String> {
+        let pair = Pair::new(ticker(token_a)?, ticker(token_b)?);
+        Ok(json(&match self.engine.amm_pools.get(&pair) {
+            Some(pool) => Value::Array(vec![
+                Value::UInt(pool.reserve(&pair.ticker_a).unwrap_or(0)),
+                Value::UInt(pool.reserve(&pair.ticker_b).unwrap_or(0)),
+            ]),
+            None => Value::Null,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_json_commands_and_events() {
+        let mut engine = WasmEngine::new(0.0);
+        assert_eq!(
+            engine.execute(r#"{"type":"ListToken","ticker":"ETH"}"#),
+            Ok(String::from(r#"{"type":"TokenListed"}"#))
+        );
+        let order = |side: &str| {
+            format!(
+                r#"{{"type":"SubmitOrder","ticker":"ETH","side":"{}","price":100.5,"quantity":4,"time_in_force":"GoodTillCancel"}}"#,
+                side
+            )
+        };
+        assert_eq!(
+            engine.execute(&order("Buy")),
+            Ok(String::from(r#"{"type":"OrderAccepted","order_id":1}"#))
+        );
+        engine.advance(5.0);
+        engine.execute(&order("Sell")).unwrap();
+        assert!(engine
+            .execute(r#"{"type":"MatchOrders"}"#)
+            .unwrap()
+            .contains(r#""quantity":4"#));
+        assert!(engine.execute(r#"{"type":"Launch"}"#).is_err());
+
+        assert_eq!(
+            engine.depth("ETH"),
+            Ok(String::from(r#"{"bids":[],"asks":[]}"#))
+        );
+        let events = engine.events_since("ETH", 3.0).unwrap();
+        assert!(events.starts_with(r#"[{"sequence":4,"event":{"type":"OrderFilled""#));
+        assert_eq!(
+            engine.pool_reserves("ETH", "USDT"),
+            Ok(String::from("null"))
+        );
+    }
+}